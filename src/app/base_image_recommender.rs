@@ -0,0 +1,401 @@
+use std::collections::BTreeSet;
+
+use futures::future::join_all;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::app::protocol::SeverityCounts;
+
+use super::{ImageScanner, ImageTag, RegistryClient, split_image_tag};
+
+/// Candidates scanned per recommendation, since each one is a full image scan.
+const MAX_CANDIDATES: usize = 4;
+
+/// Vulnerabilities and size of a scanned base image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseImageEvaluation {
+    pub image: String,
+    pub vulnerabilities: SeverityCounts,
+    pub size_in_bytes: u64,
+}
+
+impl BaseImageEvaluation {
+    /// Images are ranked by their most severe vulnerabilities first, then by size.
+    fn rank(&self) -> (usize, usize, usize, usize, usize, u64) {
+        let v = &self.vulnerabilities;
+        (
+            v.critical,
+            v.high,
+            v.medium,
+            v.low,
+            v.negligible,
+            self.size_in_bytes,
+        )
+    }
+}
+
+/// Alternatives to a base image, scanned and ranked best first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseImageRecommendation {
+    pub current: BaseImageEvaluation,
+    pub candidates: Vec<BaseImageEvaluation>,
+}
+
+impl BaseImageRecommendation {
+    /// The best candidate, if it ranks better than the current image.
+    pub fn best(&self) -> Option<&BaseImageEvaluation> {
+        self.candidates
+            .first()
+            .filter(|candidate| candidate.rank() < self.current.rank())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RecommendationError {
+    #[error("no alternatives to {0} are known")]
+    NoCandidates(String),
+
+    #[error("unable to scan {image}: {reason}")]
+    Scan { image: String, reason: String },
+
+    #[error("none of the alternatives to {0} could be scanned")]
+    NoCandidateScanned(String),
+}
+
+/// Looks for less vulnerable alternatives to a base image: newer tags of the same
+/// variant, its `slim` variant and its distroless equivalent, scanned with the
+/// (caching) scanner of the document.
+pub struct BaseImageRecommender<'a> {
+    registry_client: &'a (dyn RegistryClient + Send + Sync),
+    image_scanner: &'a (dyn ImageScanner + Send + Sync),
+}
+
+impl<'a> BaseImageRecommender<'a> {
+    pub fn new(
+        registry_client: &'a (dyn RegistryClient + Send + Sync),
+        image_scanner: &'a (dyn ImageScanner + Send + Sync),
+    ) -> Self {
+        Self {
+            registry_client,
+            image_scanner,
+        }
+    }
+
+    pub async fn recommend(
+        &self,
+        image: &str,
+    ) -> Result<BaseImageRecommendation, RecommendationError> {
+        let (repository, _) = split_image_tag(without_digest(image));
+        // Distroless equivalents don't need the tags, so an unreachable registry isn't fatal.
+        let tags = self
+            .registry_client
+            .list_tags(repository)
+            .await
+            .unwrap_or_else(|e| {
+                debug!("unable to list the tags of {repository}: {e}");
+                vec![]
+            });
+        let candidates = candidate_images(image, &tags);
+        if candidates.is_empty() {
+            return Err(RecommendationError::NoCandidates(image.to_string()));
+        }
+
+        let (current, evaluations) = tokio::join!(
+            self.evaluate(image),
+            join_all(candidates.iter().map(|candidate| self.evaluate(candidate)))
+        );
+        let current = current.map_err(|reason| RecommendationError::Scan {
+            image: image.to_string(),
+            reason,
+        })?;
+
+        let mut candidates: Vec<_> = evaluations
+            .into_iter()
+            .zip(&candidates)
+            .filter_map(|(evaluation, candidate)| {
+                evaluation
+                    .inspect_err(|e| debug!("unable to scan the alternative {candidate}: {e}"))
+                    .ok()
+            })
+            .collect();
+        if candidates.is_empty() {
+            return Err(RecommendationError::NoCandidateScanned(image.to_string()));
+        }
+        candidates.sort_by_key(BaseImageEvaluation::rank);
+
+        Ok(BaseImageRecommendation {
+            current,
+            candidates,
+        })
+    }
+
+    /// Scan errors aren't `Send`, so they are kept as messages while other scans run.
+    async fn evaluate(&self, image: &str) -> Result<BaseImageEvaluation, String> {
+        let scan_result = self
+            .image_scanner
+            .scan_image(image)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(BaseImageEvaluation {
+            image: image.to_string(),
+            vulnerabilities: SeverityCounts::from(&scan_result),
+            size_in_bytes: *scan_result.metadata().size_in_bytes(),
+        })
+    }
+}
+
+/// Alternatives to `image`, most conservative first: the newest tag of the same major
+/// version and variant, the newest one overall, the `slim` variant of the same
+/// version (among the `tags` of its repository) and the distroless equivalent.
+pub fn candidate_images(image: &str, tags: &[ImageTag]) -> Vec<String> {
+    let (repository, tag) = split_image_tag(without_digest(image));
+    let tag = tag.unwrap_or("latest");
+    let (version, variant) = parse_tag(tag);
+
+    let parsed_tags: Vec<_> = tags
+        .iter()
+        .map(|candidate| (candidate.name.as_str(), parse_tag(&candidate.name)))
+        .collect();
+    let mut candidate_tags = Vec::new();
+
+    if let Some(version) = &version {
+        let newer: Vec<_> = parsed_tags
+            .iter()
+            .filter_map(|(name, (candidate_version, candidate_variant))| {
+                let candidate_version = candidate_version.as_ref()?;
+                (candidate_variant == &variant
+                    && candidate_version.len() == version.len()
+                    && candidate_version > version)
+                    .then_some((candidate_version, *name))
+            })
+            .collect();
+        let newest_same_major = newer
+            .iter()
+            .filter(|(candidate_version, _)| candidate_version.first() == version.first())
+            .max();
+        let newest = newer.iter().max();
+        candidate_tags.extend(newest_same_major.into_iter().chain(newest).map(|(_, n)| *n));
+    }
+
+    if !variant.contains("slim") {
+        let mut slim_variant = variant.clone();
+        slim_variant.insert("slim");
+        candidate_tags.extend(
+            parsed_tags
+                .iter()
+                .filter(|(_, (candidate_version, candidate_variant))| {
+                    candidate_version == &version && candidate_variant == &slim_variant
+                })
+                .map(|(name, _)| *name)
+                .min_by_key(|name| name.len()),
+        );
+    }
+
+    let mut candidates: Vec<String> = candidate_tags
+        .into_iter()
+        .map(|candidate| format!("{repository}:{candidate}"))
+        .chain(distroless_equivalent(repository, version.as_deref()))
+        .collect();
+    let mut seen = BTreeSet::from([image.to_string()]);
+    candidates.retain(|candidate| seen.insert(candidate.clone()));
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+fn without_digest(image: &str) -> &str {
+    image.split_once('@').map_or(image, |(image, _)| image)
+}
+
+/// Splits a tag into its version, if it starts with one, and its variant: the rest of
+/// its `-` separated parts (e.g. `3.11-slim-bookworm` into `[3, 11]` and
+/// `{slim, bookworm}`). `latest` is no variant.
+fn parse_tag(tag: &str) -> (Option<Vec<u64>>, BTreeSet<&str>) {
+    let mut parts = tag.split('-');
+    let version = parts.next().and_then(|first| {
+        first
+            .strip_prefix('v')
+            .unwrap_or(first)
+            .split('.')
+            .map(|component| component.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()
+    });
+    let variant = match version {
+        Some(_) => parts.collect(),
+        None => tag.split('-').filter(|part| *part != "latest").collect(),
+    };
+    (version, variant)
+}
+
+/// Distroless image running the same kind of workloads as `repository`, for the
+/// runtimes distroless publishes images for.
+fn distroless_equivalent(repository: &str, version: Option<&[u64]>) -> Option<String> {
+    let name = repository.strip_prefix("docker.io/").unwrap_or(repository);
+    let name = name.strip_prefix("library/").unwrap_or(name);
+    let major = version.and_then(|version| version.first().copied());
+
+    let image = match (name, major) {
+        ("node", Some(major @ (18 | 20 | 22))) => format!("nodejs{major}-debian12"),
+        ("node", None) => "nodejs22-debian12".to_string(),
+        ("python", None | Some(3)) => "python3-debian12".to_string(),
+        ("openjdk" | "eclipse-temurin" | "amazoncorretto", Some(major @ (17 | 21))) => {
+            format!("java{major}-debian12")
+        }
+        ("openjdk" | "eclipse-temurin" | "amazoncorretto", None) => "java21-debian12".to_string(),
+        ("debian" | "ubuntu", _) => "base-debian12".to_string(),
+        ("golang", _) => "static-debian12".to_string(),
+        ("rust", _) => "cc-debian12".to_string(),
+        _ => return None,
+    };
+    Some(format!("gcr.io/distroless/{image}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::app::{ImageScanError, RegistryError};
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        scan_result::ScanResult,
+        scan_type::ScanType,
+        severity::Severity,
+    };
+
+    fn tags(names: &[&str]) -> Vec<ImageTag> {
+        names
+            .iter()
+            .map(|name| ImageTag {
+                name: name.to_string(),
+                digest: None,
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(
+        "node:18-alpine",
+        &["18-alpine", "18.1-alpine", "20-alpine", "22-alpine", "22", "latest"],
+        &["node:22-alpine", "gcr.io/distroless/nodejs18-debian12"]
+    )]
+    #[case(
+        "python:3.11-bookworm",
+        &["3.11-bookworm", "3.11-slim-bookworm", "3.11-slim", "3.12-bookworm", "4.0-bookworm"],
+        &["python:3.12-bookworm", "python:4.0-bookworm", "python:3.11-slim-bookworm", "gcr.io/distroless/python3-debian12"]
+    )]
+    #[case("debian", &["latest", "bookworm", "slim"], &["debian:slim", "gcr.io/distroless/base-debian12"])]
+    #[case("ghcr.io/org/app:1.0", &["1.0", "0.9"], &[])]
+    fn it_picks_newer_tags_slim_variants_and_distroless_equivalents(
+        #[case] image: &str,
+        #[case] available: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(candidate_images(image, &tags(available)), expected);
+    }
+
+    struct FixedRegistry(Vec<ImageTag>);
+
+    #[async_trait::async_trait]
+    impl RegistryClient for FixedRegistry {
+        async fn list_tags(&self, _: &str) -> Result<Vec<ImageTag>, RegistryError> {
+            Ok(self.0.clone())
+        }
+
+        async fn resolve_digest(&self, image: &str) -> Result<String, RegistryError> {
+            Err(RegistryError::Unreachable {
+                url: image.to_string(),
+                reason: "not needed by recommendations".to_string(),
+            })
+        }
+    }
+
+    /// Scans images into as many High vulnerabilities as listed for them.
+    struct FixedScanner(HashMap<&'static str, (usize, u64)>);
+
+    #[async_trait::async_trait]
+    impl ImageScanner for FixedScanner {
+        async fn scan_image(&self, image: &str) -> Result<ScanResult, ImageScanError> {
+            let (highs, size) = self
+                .0
+                .get(image)
+                .copied()
+                .ok_or_else(|| ImageScanError::ImageNotFound(image.to_string()))?;
+            let mut scan_result = ScanResult::new(
+                ScanType::Docker,
+                image.to_string(),
+                "sha256:image".to_string(),
+                None,
+                OperatingSystem::new(Family::Linux, "debian 12".to_string()),
+                size,
+                Architecture::Amd64,
+                HashMap::new(),
+                chrono::Utc::now(),
+                EvaluationResult::Passed,
+            );
+            for i in 0..highs {
+                scan_result.add_vulnerability(
+                    format!("CVE-2024-{i:04}"),
+                    Severity::High,
+                    chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                );
+            }
+            Ok(scan_result)
+        }
+    }
+
+    #[tokio::test]
+    async fn it_ranks_the_candidates_that_could_be_scanned_by_vulnerabilities_then_size() {
+        let registry = FixedRegistry(tags(&["3.11", "3.12", "3.11-slim"]));
+        let scanner = FixedScanner(HashMap::from([
+            ("python:3.11", (5, 900)),
+            ("python:3.12", (1, 1000)),
+            ("python:3.11-slim", (1, 100)),
+        ]));
+
+        let recommendation = BaseImageRecommender::new(&registry, &scanner)
+            .recommend("python:3.11")
+            .await
+            .unwrap();
+
+        assert_eq!(recommendation.current.vulnerabilities.high, 5);
+        let ranked: Vec<_> = recommendation
+            .candidates
+            .iter()
+            .map(|c| c.image.as_str())
+            .collect();
+        // The distroless image isn't known by the scanner, so it's left out.
+        assert_eq!(ranked, vec!["python:3.11-slim", "python:3.12"]);
+        assert_eq!(
+            recommendation.best().map(|b| b.image.as_str()),
+            Some("python:3.11-slim")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_recommends_nothing_when_no_candidate_is_better() {
+        let registry = FixedRegistry(tags(&["3.11", "3.12"]));
+        let scanner = FixedScanner(HashMap::from([
+            ("python:3.11", (0, 100)),
+            ("python:3.12", (2, 100)),
+        ]));
+
+        let recommendation = BaseImageRecommender::new(&registry, &scanner)
+            .recommend("python:3.11")
+            .await
+            .unwrap();
+
+        assert_eq!(recommendation.best(), None);
+    }
+}
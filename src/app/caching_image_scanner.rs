@@ -0,0 +1,347 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{Mutex, OnceCell};
+use tracing::debug;
+
+use crate::domain::scanresult::scan_result::ScanResult;
+
+use super::{ImageScanError, ImageScanner, scan_scheduler::ScanStatistics};
+
+/// Resolves the digest (`sha256:...`) identifying the contents a pull string currently
+/// points to, without scanning the image.
+#[async_trait::async_trait]
+pub trait ImageDigestResolver {
+    async fn resolve_digest(&self, image_pull_string: &str) -> Option<String>;
+}
+
+/// Scan result of an image, shared by every pull string resolving to the same digest.
+#[derive(Default)]
+struct CacheEntry {
+    scanned: OnceCell<(ScanResult, Instant)>,
+}
+
+/// Caches scan results by image digest and deduplicates in-flight scans, so
+/// `ubuntu:22.04` and `ubuntu@sha256:...` referencing the same image don't trigger
+/// duplicate scanner runs.
+///
+/// The digest is taken from the pull string if pinned, resolved with the
+/// `ImageDigestResolver` otherwise, and falls back to the pull string itself when it
+/// can't be resolved. Once a scan finishes, its result is also indexed by the digest
+/// reported by the scanner. Scans of a specific platform are cached apart, as the digest
+/// of a multi-platform image is shared by all of them.
+pub struct CachingImageScanner {
+    scanner: Box<dyn ImageScanner + Send + Sync>,
+    digest_resolver: Box<dyn ImageDigestResolver + Send + Sync>,
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Arc<CacheEntry>>>,
+    statistics: Option<Arc<ScanStatistics>>,
+}
+
+impl CachingImageScanner {
+    /// Results older than `ttl` are scanned again; a zero `ttl` only deduplicates
+    /// concurrent scans of the same image.
+    pub fn new(
+        scanner: Box<dyn ImageScanner + Send + Sync>,
+        digest_resolver: Box<dyn ImageDigestResolver + Send + Sync>,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            scanner,
+            digest_resolver,
+            ttl,
+            entries: Default::default(),
+            statistics: None,
+        }
+    }
+
+    /// Records the cache hits and misses in `statistics`.
+    pub fn with_statistics(self, statistics: Arc<ScanStatistics>) -> Self {
+        Self {
+            statistics: Some(statistics),
+            ..self
+        }
+    }
+
+    async fn cache_key_for(&self, image_pull_string: &str) -> String {
+        if let Some(digest) = digest_in_pull_string(image_pull_string) {
+            return digest.to_string();
+        }
+
+        match self.digest_resolver.resolve_digest(image_pull_string).await {
+            Some(digest) => digest,
+            None => {
+                debug!(
+                    "unable to resolve the digest of {image_pull_string}, caching by pull string"
+                );
+                image_pull_string.to_string()
+            }
+        }
+    }
+
+    async fn entry_for(&self, key: &str) -> Arc<CacheEntry> {
+        let mut entries = self.entries.lock().await;
+        let ttl = self.ttl;
+        entries.retain(|_, entry| match entry.scanned.get() {
+            Some((_, scanned_at)) => scanned_at.elapsed() < ttl,
+            // Not scanned yet (or the scan failed): keep it only while someone awaits it.
+            None => Arc::strong_count(entry) > 1,
+        });
+        entries.entry(key.to_string()).or_default().clone()
+    }
+
+    /// Also indexes the entry of `key` by the digest reported by the scanner.
+    async fn index_by_digest(
+        &self,
+        key: &str,
+        entry: &Arc<CacheEntry>,
+        scan_result: &ScanResult,
+        platform: Option<&str>,
+    ) {
+        if let Some(digest) = scan_result.metadata().digest() {
+            let digest_key = with_platform(digest.to_string(), platform);
+            if digest_key != key {
+                self.entries.lock().await.insert(digest_key, entry.clone());
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ImageScanner for CachingImageScanner {
+    async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+        self.scan_image_for_platform(image_pull_string, None).await
+    }
+
+    async fn scan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        let key = with_platform(self.cache_key_for(image_pull_string).await, platform);
+        let entry = self.entry_for(&key).await;
+        if let Some(statistics) = &self.statistics {
+            statistics.record_cache_lookup(entry.scanned.initialized());
+        }
+
+        let (scan_result, _) = entry
+            .scanned
+            .get_or_try_init(|| async {
+                let scan_result = self
+                    .scanner
+                    .scan_image_for_platform(image_pull_string, platform)
+                    .await?;
+                Ok::<_, ImageScanError>((scan_result, Instant::now()))
+            })
+            .await?;
+        self.index_by_digest(&key, &entry, scan_result, platform)
+            .await;
+
+        Ok(scan_result.clone())
+    }
+
+    async fn rescan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        let key = with_platform(self.cache_key_for(image_pull_string).await, platform);
+        let scan_result = self
+            .scanner
+            .rescan_image_for_platform(image_pull_string, platform)
+            .await?;
+
+        let entry = Arc::new(CacheEntry {
+            scanned: OnceCell::new_with(Some((scan_result.clone(), Instant::now()))),
+        });
+        self.entries.lock().await.insert(key.clone(), entry.clone());
+        self.index_by_digest(&key, &entry, &scan_result, platform)
+            .await;
+
+        Ok(scan_result)
+    }
+}
+
+/// Cache key of the scans of `platform`, e.g. `sha256:abc linux/arm64`.
+fn with_platform(key: String, platform: Option<&str>) -> String {
+    match platform {
+        Some(platform) => format!("{key} {platform}"),
+        None => key,
+    }
+}
+
+/// Digest of a pinned pull string, e.g. `sha256:abc` for `ubuntu@sha256:abc`.
+pub fn digest_in_pull_string(image_pull_string: &str) -> Option<&str> {
+    image_pull_string
+        .split_once('@')
+        .map(|(_, digest)| digest)
+        .filter(|digest| digest.starts_with("sha256:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        scan_type::ScanType,
+    };
+
+    const DIGEST: &str = "sha256:1111";
+
+    #[derive(Default)]
+    struct CountingScanner {
+        scans: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl ImageScanner for CountingScanner {
+        async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+            self.scans.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(ScanResult::new(
+                ScanType::Docker,
+                image_pull_string.to_string(),
+                "sha256:image-id".to_string(),
+                Some(DIGEST.to_string()),
+                OperatingSystem::new(Family::Linux, "ubuntu:22.04".to_string()),
+                1024,
+                Architecture::Amd64,
+                HashMap::new(),
+                chrono::Utc::now(),
+                EvaluationResult::Passed,
+            ))
+        }
+    }
+
+    struct StaticResolver(Option<&'static str>);
+
+    #[async_trait::async_trait]
+    impl ImageDigestResolver for StaticResolver {
+        async fn resolve_digest(&self, _: &str) -> Option<String> {
+            self.0.map(str::to_string)
+        }
+    }
+
+    fn caching_scanner(
+        resolved_digest: Option<&'static str>,
+        ttl: Duration,
+    ) -> (CachingImageScanner, Arc<AtomicUsize>) {
+        let scanner = CountingScanner::default();
+        let scans = scanner.scans.clone();
+        let caching = CachingImageScanner::new(
+            Box::new(scanner),
+            Box::new(StaticResolver(resolved_digest)),
+            ttl,
+        );
+        (caching, scans)
+    }
+
+    #[tokio::test]
+    async fn it_reuses_results_for_pull_strings_resolving_to_the_same_digest() {
+        let (scanner, scans) = caching_scanner(Some(DIGEST), Duration::from_secs(60));
+
+        scanner.scan_image("ubuntu:22.04").await.unwrap();
+        scanner.scan_image("ubuntu:jammy").await.unwrap();
+        scanner
+            .scan_image(&format!("ubuntu@{DIGEST}"))
+            .await
+            .unwrap();
+
+        assert_eq!(scans.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn it_indexes_results_by_the_digest_reported_by_the_scanner() {
+        let (scanner, scans) = caching_scanner(None, Duration::from_secs(60));
+
+        scanner.scan_image("ubuntu:22.04").await.unwrap();
+        scanner
+            .scan_image(&format!("ubuntu@{DIGEST}"))
+            .await
+            .unwrap();
+
+        assert_eq!(scans.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn it_caches_the_scans_of_each_platform_apart() {
+        let (scanner, scans) = caching_scanner(Some(DIGEST), Duration::from_secs(60));
+
+        scanner.scan_image("ubuntu:22.04").await.unwrap();
+        scanner
+            .scan_image_for_platform("ubuntu:22.04", Some("linux/arm64"))
+            .await
+            .unwrap();
+        scanner
+            .scan_image_for_platform(&format!("ubuntu@{DIGEST}"), Some("linux/arm64"))
+            .await
+            .unwrap();
+
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn it_deduplicates_concurrent_scans_even_without_caching() {
+        let (scanner, scans) = caching_scanner(Some(DIGEST), Duration::ZERO);
+        let pinned = format!("ubuntu@{DIGEST}");
+
+        let (first, second) = tokio::join!(
+            scanner.scan_image("ubuntu:22.04"),
+            scanner.scan_image(&pinned)
+        );
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 1);
+
+        scanner.scan_image("ubuntu:22.04").await.unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn it_rescans_cached_images_and_caches_the_new_result() {
+        let (scanner, scans) = caching_scanner(Some(DIGEST), Duration::from_secs(60));
+
+        scanner.scan_image("ubuntu:22.04").await.unwrap();
+        scanner
+            .rescan_image_for_platform("ubuntu:22.04", None)
+            .await
+            .unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+
+        scanner.scan_image("ubuntu:jammy").await.unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn it_records_the_cache_hits_and_misses() {
+        let statistics = Arc::new(ScanStatistics::default());
+        let (scanner, _) = caching_scanner(Some(DIGEST), Duration::from_secs(60));
+        let scanner = scanner.with_statistics(statistics.clone());
+
+        scanner.scan_image("ubuntu:22.04").await.unwrap();
+        scanner.scan_image("ubuntu:jammy").await.unwrap();
+        scanner.scan_image("ubuntu:22.04").await.unwrap();
+
+        let counts = statistics.cache_counts(60);
+        assert_eq!((counts.hits, counts.misses, counts.ttl_seconds), (2, 1, 60));
+    }
+
+    #[test]
+    fn it_extracts_digests_from_pinned_pull_strings() {
+        assert_eq!(
+            digest_in_pull_string("docker.io/library/ubuntu:22.04@sha256:abc"),
+            Some("sha256:abc")
+        );
+        assert_eq!(digest_in_pull_string("ubuntu:22.04"), None);
+    }
+}
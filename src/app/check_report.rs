@@ -0,0 +1,326 @@
+use std::{fmt::Write, path::Path, str::FromStr};
+
+use serde::Serialize;
+use tabled::{builder::Builder, settings::Style};
+
+use crate::domain::scanresult::{
+    evaluation_result::EvaluationResult,
+    sarif::{SarifArtifactLocations, SarifLog},
+    severity::Severity,
+};
+
+use super::{
+    file_check::{FileCheck, FileCheckError, ImageCheck},
+    markdown::MarkdownData,
+    protocol::{PolicyEvaluationSummary, SeverityCounts},
+    scan_result_export::{evaluation_result_str, sarif_region},
+};
+
+/// Output of `sysdig-lsp --check`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CheckFormat {
+    /// A line per image, then the summary table.
+    #[default]
+    Text,
+    Json,
+    /// The summary table, then the report of each image as shown in the editor.
+    Markdown,
+    /// SARIF 2.1.0, for code scanning services.
+    Sarif,
+}
+
+impl FromStr for CheckFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(CheckFormat::Text),
+            "json" => Ok(CheckFormat::Json),
+            "markdown" => Ok(CheckFormat::Markdown),
+            "sarif" => Ok(CheckFormat::Sarif),
+            _ => Err(format!(
+                "unknown format '{s}', expected text, json, markdown or sarif"
+            )),
+        }
+    }
+}
+
+/// Outcome of a check, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckStatus {
+    Passed,
+    /// A policy failed or a vulnerability reached the severity threshold.
+    Failed,
+    /// Some file or image couldn't be scanned, so the check is incomplete.
+    Errored,
+}
+
+/// Checks of every file of a `sysdig-lsp --check` run.
+pub struct CheckReport {
+    pub files: Vec<Result<FileCheck, FileCheckError>>,
+    /// Also fails the images with reported vulnerabilities at least this severe.
+    pub fail_on: Option<Severity>,
+}
+
+impl CheckReport {
+    pub fn status(&self) -> CheckStatus {
+        self.files
+            .iter()
+            .map(|file| match file {
+                Ok(file) => file.status(self.fail_on),
+                Err(_) => CheckStatus::Errored,
+            })
+            .max()
+            .unwrap_or(CheckStatus::Passed)
+    }
+
+    /// The report in `format`. `workspace_root` is what the SARIF paths are relative to.
+    pub fn render(&self, format: CheckFormat, workspace_root: &Path) -> String {
+        match format {
+            CheckFormat::Text => self.to_text(),
+            CheckFormat::Json => serde_json::to_string_pretty(&self.to_json()).unwrap_or_default(),
+            CheckFormat::Markdown => self.to_markdown(),
+            CheckFormat::Sarif => {
+                serde_json::to_string_pretty(&self.to_sarif(workspace_root)).unwrap_or_default()
+            }
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        for file in &self.files {
+            match file {
+                Ok(file) => text.push_str(&file.to_string()),
+                Err(e) => {
+                    let _ = writeln!(text, "{e}");
+                }
+            }
+        }
+        let mut table = self.summary_table();
+        table.with(Style::psql());
+        let _ = writeln!(text, "\n{table}");
+        text
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut table = self.summary_table();
+        table.with(Style::markdown());
+        let mut markdown = format!("# Sysdig LSP check\n\n{table}\n");
+
+        for file in &self.files {
+            let file = match file {
+                Ok(file) => file,
+                Err(e) => {
+                    let _ = write!(markdown, "\n> [!CAUTION]\n> {e}\n");
+                    continue;
+                }
+            };
+            for image in &file.images {
+                let location = image_location(file, image);
+                match &image.outcome {
+                    Ok(verdict) => {
+                        let report = MarkdownData::from(verdict.scan_result.clone())
+                            .with_suppressed_vulns(verdict.suppressed_vulns);
+                        let _ = write!(
+                            markdown,
+                            "\n<details><summary>{location}</summary>\n\n{report}\n</details>\n"
+                        );
+                    }
+                    Err(e) => {
+                        let _ =
+                            write!(markdown, "\n> [!CAUTION]\n> {location}: scan failed: {e}\n");
+                    }
+                }
+            }
+        }
+        markdown
+    }
+
+    fn to_json(&self) -> CheckReportJson {
+        let files = self
+            .files
+            .iter()
+            .map(|file| match file {
+                Ok(file) => FileCheckJson {
+                    path: file.path.display().to_string(),
+                    error: None,
+                    images: file
+                        .images
+                        .iter()
+                        .map(|image| self.image_json(image))
+                        .collect(),
+                },
+                Err(e) => FileCheckJson {
+                    path: e.path(),
+                    error: Some(e.to_string()),
+                    images: vec![],
+                },
+            })
+            .collect();
+
+        CheckReportJson {
+            status: self.status(),
+            fail_on: self
+                .fail_on
+                .map(|severity| severity.to_string().to_lowercase()),
+            files,
+        }
+    }
+
+    fn image_json(&self, image: &ImageCheck) -> ImageCheckJson {
+        let mut json = ImageCheckJson {
+            image: image.image.clone(),
+            line: image.range.start.line + 1,
+            status: CheckStatus::Errored,
+            error: None,
+            policy_evaluation: None,
+            vulnerabilities: None,
+        };
+        match &image.outcome {
+            Ok(verdict) => {
+                json.status = if verdict.fails(self.fail_on) {
+                    CheckStatus::Failed
+                } else {
+                    CheckStatus::Passed
+                };
+                json.policy_evaluation = Some(PolicyEvaluationSummary {
+                    result: evaluation_result_str(&verdict.evaluation_result).to_string(),
+                    failed_policies: verdict.failed_policies.clone(),
+                });
+                json.vulnerabilities = Some(verdict.vulnerabilities);
+            }
+            Err(e) => json.error = Some(e.clone()),
+        }
+        json
+    }
+
+    /// Findings of every scanned image in a single SARIF run, located at the image
+    /// references.
+    fn to_sarif(&self, workspace_root: &Path) -> SarifLog {
+        let logs = self
+            .files
+            .iter()
+            .flatten()
+            .flat_map(|file| {
+                // Code scanning services expect paths relative to the repository root.
+                let artifact_uri = file
+                    .path
+                    .strip_prefix(workspace_root)
+                    .unwrap_or(&file.path)
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                file.images.iter().filter_map(move |image| {
+                    let verdict = image.outcome.as_ref().ok()?;
+                    let locations = SarifArtifactLocations {
+                        artifact_uri: artifact_uri.clone(),
+                        image_region: sarif_region(image.range),
+                        layer_regions: Default::default(),
+                    };
+                    Some(verdict.scan_result.to_sarif(&locations))
+                })
+            })
+            .collect::<Vec<_>>();
+        SarifLog::merge(logs)
+    }
+
+    /// A row per image of every file, with its verdict.
+    fn summary_table(&self) -> tabled::Table {
+        let mut builder = Builder::default();
+        builder.push_record([
+            "IMAGE",
+            "POLICY",
+            "CRITICAL",
+            "HIGH",
+            "MEDIUM",
+            "LOW",
+            "NEGLIGIBLE",
+            "RESULT",
+        ]);
+        for file in self.files.iter().flatten() {
+            for image in &file.images {
+                let location = image_location(file, image);
+                let Ok(verdict) = &image.outcome else {
+                    builder.push_record([
+                        location,
+                        "-".into(),
+                        "-".into(),
+                        "-".into(),
+                        "-".into(),
+                        "-".into(),
+                        "-".into(),
+                        "ERROR".into(),
+                    ]);
+                    continue;
+                };
+                let policy = match verdict.evaluation_result {
+                    EvaluationResult::Passed => "passed",
+                    EvaluationResult::Failed => "failed",
+                    EvaluationResult::NotEvaluated => "not evaluated",
+                };
+                let counts: SeverityCounts = verdict.vulnerabilities;
+                let result = if verdict.fails(self.fail_on) {
+                    "FAILED"
+                } else {
+                    "PASSED"
+                };
+                builder.push_record([
+                    location,
+                    policy.to_string(),
+                    counts.critical.to_string(),
+                    counts.high.to_string(),
+                    counts.medium.to_string(),
+                    counts.low.to_string(),
+                    counts.negligible.to_string(),
+                    result.to_string(),
+                ]);
+            }
+        }
+        builder.build()
+    }
+}
+
+/// Where an image is referenced, e.g. `Dockerfile:1 alpine:3.18`.
+pub(super) fn image_location(file: &FileCheck, image: &ImageCheck) -> String {
+    format!(
+        "{}:{} {}",
+        file.path.display(),
+        image.range.start.line + 1,
+        image.image
+    )
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckReportJson {
+    status: CheckStatus,
+    fail_on: Option<String>,
+    files: Vec<FileCheckJson>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileCheckJson {
+    path: String,
+    /// Why the file couldn't be checked.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    images: Vec<ImageCheckJson>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ImageCheckJson {
+    image: String,
+    /// 1-based line of the image reference.
+    line: u32,
+    status: CheckStatus,
+    /// Why the image couldn't be scanned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy_evaluation: Option<PolicyEvaluationSummary>,
+    /// Reported vulnerabilities, i.e. not ignored, by severity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vulnerabilities: Option<SeverityCounts>,
+}
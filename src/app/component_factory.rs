@@ -1,28 +1,724 @@
-use std::env::VarError;
+use std::{collections::HashMap, path::PathBuf};
 
+use schemars::JsonSchema;
 use serde::Deserialize;
+use serde_json::Value;
 use thiserror::Error;
 use tower_lsp::jsonrpc::{Error as LspError, ErrorCode};
 
-use super::{IacScanner, ImageBuilder, ImageScanner};
+use tower_lsp::lsp_types::{DiagnosticSeverity, TraceValue, Url};
 
-#[derive(Clone, Debug, Default, Deserialize)]
+use crate::domain::image_reference::is_image_platform;
+use crate::domain::scanresult::severity::Severity;
+
+use super::lint::LINT_RULES;
+use super::redaction::REDACTED;
+use super::{
+    CredentialsChecker, FilesystemScanner, IacScanner, ImageBuilder, ImageScanner, RegistryClient,
+    RiskAcceptanceClient, ScanResultStore, ScanScheduler, TenantScope,
+};
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 pub struct Config {
     pub sysdig: SysdigConfig,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
+impl Config {
+    /// Reads the settings sent by the client (`initializationOptions` or
+    /// `workspace/didChangeConfiguration`), pointing at the offending setting if invalid.
+    pub fn from_settings(settings: &Value) -> Result<Self, InvalidConfigError> {
+        let config: Config =
+            serde_path_to_error::deserialize(settings).map_err(|e| InvalidConfigError {
+                path: e.path().to_string(),
+                message: e.into_inner().to_string(),
+            })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the values serde can't: URLs, empty tokens and versions.
+    fn validate(&self) -> Result<(), InvalidConfigError> {
+        let sysdig = &self.sysdig;
+        // Offline scans never reach the API, so they don't need its URL.
+        if !(sysdig.scan.offline && sysdig.api_url.is_empty()) {
+            validate_url("sysdig.api_url", &sysdig.api_url)?;
+        }
+        validate_token("sysdig.api_token", sysdig.api_token.as_ref())?;
+        if let Some(remote_url) = &sysdig.cache.remote_url {
+            validate_url("sysdig.cache.remote_url", remote_url)?;
+        }
+        validate_token(
+            "sysdig.cache.remote_token",
+            sysdig.cache.remote_token.as_ref(),
+        )?;
+        if let Some(version) = &sysdig.scanner.version
+            && version != "latest"
+            && semver::Version::parse(version.trim_start_matches('v')).is_err()
+        {
+            return Err(InvalidConfigError {
+                path: "sysdig.scanner.version".to_string(),
+                message: format!("expected a version (e.g. `1.24.1`) or `latest`, got `{version}`"),
+            });
+        }
+        if let Some(platform) = &sysdig.scan.platform
+            && !is_image_platform(platform)
+        {
+            return Err(InvalidConfigError {
+                path: "sysdig.scan.platform".to_string(),
+                message: format!(
+                    "expected `os/arch[/variant]` (e.g. `linux/arm64`), got `{platform}`"
+                ),
+            });
+        }
+        if let Some(rule) = sysdig
+            .lint
+            .rules
+            .keys()
+            .find(|rule| !LINT_RULES.iter().any(|(known, _)| known == rule))
+        {
+            return Err(InvalidConfigError {
+                path: format!("sysdig.lint.rules.{rule}"),
+                message: format!("unknown lint rule `{rule}`"),
+            });
+        }
+        if let Some((prefix, mirror)) = sysdig.registry.mirrors.iter().find(|(prefix, mirror)| {
+            [prefix, mirror]
+                .iter()
+                .any(|reference| reference.trim().is_empty() || reference.contains("://"))
+        }) {
+            return Err(InvalidConfigError {
+                path: format!("sysdig.registry.mirrors.{prefix}"),
+                message: format!(
+                    "expected a registry or repository without scheme (e.g. `mirror.example.com/dockerhub`), got `{prefix}` -> `{mirror}`"
+                ),
+            });
+        }
+        for (registry, credentials) in &sysdig.registry.credentials {
+            if registry.trim().is_empty() || registry.contains("://") {
+                return Err(InvalidConfigError {
+                    path: format!("sysdig.registry.credentials.{registry}"),
+                    message: format!(
+                        "expected a registry host without scheme (e.g. `ghcr.io`), got `{registry}`"
+                    ),
+                });
+            }
+            if credentials.username.trim().is_empty() {
+                return Err(InvalidConfigError {
+                    path: format!("sysdig.registry.credentials.{registry}.username"),
+                    message: "must not be empty".to_string(),
+                });
+            }
+            validate_token(
+                &format!("sysdig.registry.credentials.{registry}.password"),
+                Some(&credentials.password),
+            )?;
+        }
+        if let Some(name) = sysdig
+            .build
+            .args
+            .keys()
+            .find(|name| name.trim().is_empty() || name.contains(['=', ' ']))
+        {
+            return Err(InvalidConfigError {
+                path: format!("sysdig.build.args.{name}"),
+                message: format!("expected the name of an `ARG`, got `{name}`"),
+            });
+        }
+        for (i, scope) in sysdig.scopes.iter().enumerate() {
+            if let Some(api_url) = &scope.api_url {
+                validate_url(&format!("sysdig.scopes[{i}].api_url"), api_url)?;
+            }
+            validate_token(
+                &format!("sysdig.scopes[{i}].api_token"),
+                scope.api_token.as_ref(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// JSON Schema of the settings, for clients to offer a settings UI and validation.
+    pub fn json_schema() -> Value {
+        serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default()
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("invalid setting `{path}`: {message}")]
+pub struct InvalidConfigError {
+    /// Dotted path of the setting, e.g. `sysdig.scopes[0].paths`.
+    pub path: String,
+    pub message: String,
+}
+
+fn validate_url(path: &str, url: &str) -> Result<(), InvalidConfigError> {
+    let message = match Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => return Ok(()),
+        Ok(url) => format!("expected an http(s) URL, got the `{}` scheme", url.scheme()),
+        Err(e) => format!("invalid URL `{url}`: {e}"),
+    };
+    Err(InvalidConfigError {
+        path: path.to_string(),
+        message,
+    })
+}
+
+/// Tokens are optional, but an empty one is most likely a mistake.
+fn validate_token(path: &str, token: Option<&Secret>) -> Result<(), InvalidConfigError> {
+    match token {
+        Some(token) if token.expose().trim().is_empty() => Err(InvalidConfigError {
+            path: path.to_string(),
+            message: "the token is empty, remove the setting if it isn't needed".to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Setting holding a token, redacted when the configuration is logged.
+#[derive(Clone, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
 pub struct SysdigConfig {
     #[serde(alias = "apiUrl")]
     pub api_url: String,
     #[serde(alias = "apiToken")]
-    pub api_token: Option<String>,
+    pub api_token: Option<Secret>,
+    /// Environment variable the API token is read from when `api_token` isn't set,
+    /// before `SECURE_API_TOKEN`.
+    #[serde(alias = "apiTokenEnv")]
+    pub api_token_env: Option<String>,
+    /// Reads the API token from the OS keychain when it isn't set nor in the
+    /// environment: the entry of the `sysdig-lsp` service with the API URL as account.
+    #[serde(default, alias = "apiTokenKeychain")]
+    pub api_token_keychain: bool,
+    #[serde(default)]
+    pub report: ReportConfig,
+    #[serde(default)]
+    pub diagnostics: DiagnosticsConfig,
+    #[serde(default)]
+    pub docker: DockerConfig,
+    #[serde(default)]
+    pub build: BuildConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub scan: ScanConfig,
+    #[serde(default)]
+    pub scanner: ScannerConfig,
+    #[serde(default, alias = "autoScan")]
+    pub auto_scan: AutoScanConfig,
+    #[serde(default)]
+    pub trace: TraceConfig,
+    #[serde(default)]
+    pub lint: LintConfig,
+    #[serde(default)]
+    pub registry: RegistryConfig,
+    /// Interval at which the policies of scanned images are re-evaluated, since they
+    /// can change server-side without the image changing. `0` disables it.
+    #[serde(default, alias = "policyRefreshIntervalSeconds")]
+    pub policy_refresh_interval_seconds: u64,
+    /// Path globs scanned with a different API URL and/or token, for workspaces split
+    /// across Sysdig tenants. The first matching scope wins.
+    #[serde(default)]
+    pub scopes: Vec<ScopeConfig>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct ScopeConfig {
+    /// Globs matched against the document path, e.g. `services/payments/**`.
+    pub paths: Vec<String>,
+    /// Defaults to `sysdig.api_url`.
+    #[serde(alias = "apiUrl")]
+    pub api_url: Option<String>,
+    /// Defaults to the token of the default tenant.
+    #[serde(alias = "apiToken")]
+    pub api_token: Option<Secret>,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct CacheConfig {
+    /// Persists image scan diagnostics and reports to disk, so they are restored when
+    /// an unchanged document is reopened in a later session.
+    #[serde(default)]
+    pub persist: bool,
+    /// Where persisted scan results are stored. Defaults to `<user cache dir>/sysdig-lsp/scan-results`.
+    pub directory: Option<PathBuf>,
+    /// How long image scan results are reused for pull strings resolving to the same
+    /// digest. `0` disables caching, but concurrent scans of an image are still deduplicated.
+    #[serde(
+        default = "default_scan_results_ttl_seconds",
+        alias = "scanResultsTtlSeconds"
+    )]
+    pub scan_results_ttl_seconds: u64,
+    /// Team-shared HTTP cache where scan reports are published and looked up by image
+    /// digest (`GET`/`PUT <remote_url>/<digest>`), so an image scanned by one developer
+    /// isn't scanned again by the rest of the team.
+    #[serde(alias = "remoteUrl")]
+    pub remote_url: Option<String>,
+    /// Sent as a bearer token to the remote cache.
+    #[serde(alias = "remoteToken")]
+    pub remote_token: Option<Secret>,
+    /// How long reports of the remote cache are reused before the image is scanned again.
+    #[serde(default = "default_remote_ttl_seconds", alias = "remoteTtlSeconds")]
+    pub remote_ttl_seconds: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            persist: false,
+            directory: None,
+            scan_results_ttl_seconds: default_scan_results_ttl_seconds(),
+            remote_url: None,
+            remote_token: None,
+            remote_ttl_seconds: default_remote_ttl_seconds(),
+        }
+    }
+}
+
+fn default_scan_results_ttl_seconds() -> u64 {
+    300
+}
+
+fn default_remote_ttl_seconds() -> u64 {
+    86400
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct ScanConfig {
+    /// Scans images with the vulnerability database cached by the CLI scanner, without
+    /// reaching the Sysdig backend. Policies aren't evaluated, so only vulnerabilities
+    /// are reported.
+    #[serde(default)]
+    pub offline: bool,
+    /// Vulnerability database used by offline scans. Defaults to the one cached by the CLI scanner.
+    #[serde(alias = "dbPath")]
+    pub db_path: Option<PathBuf>,
+    /// Attempts of the scanner download and of each scan when they fail transiently
+    /// (network errors, rate limits), waiting exponentially longer between them. `1`
+    /// disables retries.
+    #[serde(default = "default_max_attempts", alias = "maxAttempts")]
+    pub max_attempts: u32,
+    /// Image scans running at the same time, across every document and tenant. Further
+    /// scans wait for one of them to finish.
+    #[serde(default = "default_max_concurrent_scans", alias = "maxConcurrentScans")]
+    pub max_concurrent_scans: usize,
+    /// Seconds the running scans and builds get to finish when the server shuts down,
+    /// before they're aborted.
+    #[serde(
+        default = "default_shutdown_grace_seconds",
+        alias = "shutdownGraceSeconds"
+    )]
+    pub shutdown_grace_seconds: u64,
+    /// Platform of multi-platform images to scan, e.g. `linux/arm64` when deploying to ARM
+    /// from an amd64 host. Defaults to the platform of the host.
+    pub platform: Option<String>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            offline: false,
+            db_path: None,
+            max_attempts: default_max_attempts(),
+            max_concurrent_scans: default_max_concurrent_scans(),
+            shutdown_grace_seconds: default_shutdown_grace_seconds(),
+            platform: None,
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_max_concurrent_scans() -> usize {
+    4
+}
+
+fn default_shutdown_grace_seconds() -> u64 {
+    5
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct ScannerConfig {
+    /// Pre-installed Sysdig CLI scanner used instead of downloading it, e.g. in
+    /// air-gapped environments. It must be at least the version the LSP expects.
+    pub path: Option<PathBuf>,
+    /// Version of the scanner to download (e.g. `1.24.1`), or `latest` for the newest
+    /// release. Defaults to the version this release of the LSP was tested with.
+    pub version: Option<String>,
+    /// How images are scanned.
+    #[serde(default)]
+    pub mode: ScannerMode,
+}
+
+/// Backend of the image scans.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScannerMode {
+    /// Runs the Sysdig CLI scanner, downloaded unless `path` is set.
+    #[default]
+    Cli,
+    /// Reads the latest result of the image from the Sysdig Secure API, without the CLI
+    /// scanner. Only registry images already scanned by Sysdig Secure can be scanned,
+    /// so images built locally (e.g. by "Build and scan") fail.
+    Api,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct TraceConfig {
+    /// Verbosity of the logs sent to the client, overriding the `trace` of the client
+    /// (`initialize`, `$/setTrace`).
+    pub server: Option<TraceLevel>,
+    /// Also shows errors to the user with `window/showMessage`, besides logging them.
+    #[serde(default, alias = "showErrors")]
+    pub show_errors: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct LintConfig {
+    /// Enables (`true`) or disables (`false`) lint rules by their code, e.g.
+    /// `{ "latest-tag": false }`. Rules not listed keep their default.
+    #[serde(default)]
+    pub rules: HashMap<String, bool>,
+}
+
+impl LintConfig {
+    pub fn is_enabled(&self, rule: &str) -> bool {
+        self.rules.get(rule).copied().unwrap_or_else(|| {
+            LINT_RULES
+                .iter()
+                .any(|(known, enabled_by_default)| *known == rule && *enabled_by_default)
+        })
+    }
+}
+
+/// Verbosity of the logs, named after the LSP `TraceValue`s: `off` sends info, warnings
+/// and errors, `messages` adds debug logs (e.g. every command run) and `verbose` adds
+/// trace logs and the context of every log (command, image).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceLevel {
+    #[default]
+    Off,
+    Messages,
+    Verbose,
+}
+
+impl From<TraceValue> for TraceLevel {
+    fn from(trace: TraceValue) -> Self {
+        match trace {
+            TraceValue::Off => TraceLevel::Off,
+            TraceValue::Messages => TraceLevel::Messages,
+            TraceValue::Verbose => TraceLevel::Verbose,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct RegistryConfig {
+    /// Mirrors images are scanned from instead of their registry, by prefix of the image
+    /// reference with its implied `docker.io/library/` and `:latest` made explicit, e.g.
+    /// `{ "docker.io": "mirror.example.com/dockerhub" }`. The longest matching prefix wins.
+    #[serde(default)]
+    pub mirrors: HashMap<String, String>,
+    /// Credentials of private registries by host, e.g. `{ "ghcr.io": { "username": "bot",
+    /// "password": "..." } }`, used to scan their images and to look up their tags and digests.
+    #[serde(default)]
+    pub credentials: HashMap<String, RegistryCredentialsConfig>,
+    /// Also uses the credentials of the Docker CLI (`~/.docker/config.json` and its
+    /// credential helpers) for the registries missing from `credentials`.
+    #[serde(default = "default_docker_config", alias = "dockerConfig")]
+    pub docker_config: bool,
+}
+
+impl Default for RegistryConfig {
+    fn default() -> Self {
+        Self {
+            mirrors: HashMap::new(),
+            credentials: HashMap::new(),
+            docker_config: default_docker_config(),
+        }
+    }
+}
+
+fn default_docker_config() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct RegistryCredentialsConfig {
+    pub username: String,
+    pub password: Secret,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct AutoScanConfig {
+    /// Re-runs the last scan of a document when it's saved, once saves settle down.
+    /// Documents that weren't scanned in the session aren't scanned on save.
+    #[serde(default, alias = "onSave")]
+    pub on_save: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct DockerConfig {
+    /// Docker CLI context (`docker context ls`) whose daemon is used to build and scan
+    /// images. If omitted, the daemon is discovered from the environment and local sockets.
+    pub context: Option<String>,
+    /// Daemon address in DOCKER_HOST format (`unix://`, `tcp://`, `ssh://user@host`), for
+    /// remote or rootless daemons without a CLI context. Exclusive with `context`.
+    pub host: Option<String>,
+    /// Directory with `ca.pem`, `cert.pem` and `key.pem` to connect to a `tcp://` host over TLS.
+    #[serde(alias = "certPath")]
+    pub cert_path: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, JsonSchema)]
+pub struct BuildConfig {
+    /// Values of the `ARG`s of the Dockerfiles built by "Build and scan", as `--build-arg`
+    /// does, e.g. `{ "NODE_VERSION": "20" }`. Overridden by the `# sysdig-lsp: build-arg
+    /// NAME=value` comments of the Dockerfile.
+    #[serde(default)]
+    pub args: HashMap<String, Secret>,
+    /// Builds with BuildKit through `docker buildx build` instead of the legacy builder
+    /// of the Docker API, for Dockerfiles using `RUN --mount`, heredocs or other
+    /// BuildKit-only syntax. Needs the `docker` CLI with the buildx plugin.
+    #[serde(default)]
+    pub buildkit: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct ReportConfig {
+    /// Appends a table to the "Build and scan" report showing which Dockerfile
+    /// instruction each image layer was matched to.
+    #[serde(default, alias = "layerMapping")]
+    pub layer_mapping: bool,
+    /// Only reports the vulnerabilities of packages loaded at runtime, for images with
+    /// Sysdig runtime insights. Images without runtime context report every vulnerability.
+    #[serde(default, alias = "focusInUse")]
+    pub focus_in_use: bool,
+    /// Reports the vulnerabilities in the CISA Known Exploited Vulnerabilities catalog as
+    /// errors, whatever their severity, along with the image and layer summaries listing them.
+    #[serde(default, alias = "escalateKnownExploited")]
+    pub escalate_known_exploited: bool,
+    /// Offers to re-scan images whose shown scan result is at least this many hours old,
+    /// e.g. restored from a previous session or served from a cache. `0` disables it.
+    #[serde(default = "default_stale_after_hours", alias = "staleAfterHours")]
+    pub stale_after_hours: u64,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            layer_mapping: false,
+            focus_in_use: false,
+            escalate_known_exploited: false,
+            stale_after_hours: default_stale_after_hours(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, JsonSchema)]
+pub struct DiagnosticsConfig {
+    /// Maximum number of per-vulnerability hints reported on each Dockerfile instruction
+    /// by "Build and scan", in `hint_order`. The rest are summarized in a single hint and
+    /// listed on demand by `sysdig-lsp.show-all-layer-findings`. Every vulnerability is
+    /// reported if omitted.
+    #[serde(
+        alias = "maxHintsPerLayer",
+        alias = "max_vulns_per_layer",
+        alias = "maxVulnsPerLayer"
+    )]
+    pub max_hints_per_layer: Option<usize>,
+    /// Order of the per-vulnerability hints of each instruction.
+    #[serde(default, alias = "hintOrder")]
+    pub hint_order: HintOrder,
+    /// Clears the diagnostics of documents when they are closed. When disabled, they
+    /// stay in the problems panel until the file is scanned again.
+    #[serde(default = "default_clear_on_close", alias = "clearOnClose")]
+    pub clear_on_close: bool,
+    /// Makes "Build and scan" also scan the base image of the final stage and only
+    /// report the vulnerabilities the Dockerfile adds on top of it.
+    #[serde(default, alias = "onlyNewVulns")]
+    pub only_new_vulns: bool,
+    /// Makes "Build and scan" flag the instructions whose layer is larger than this
+    /// many megabytes (1 MB = 1000 KB). `0` disables it.
+    #[serde(
+        default = "default_large_layer_threshold_mb",
+        alias = "largeLayerThresholdMb"
+    )]
+    pub large_layer_threshold_mb: u64,
+    /// Severity of the vulnerability summaries of the images and of their layers, by
+    /// their most severe vulnerability, regardless of the policy evaluation. When unset,
+    /// failed policies make image summaries errors.
+    #[serde(default, alias = "severityMapping")]
+    pub severity_mapping: Option<SeverityMapping>,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            max_hints_per_layer: None,
+            hint_order: HintOrder::default(),
+            clear_on_close: default_clear_on_close(),
+            only_new_vulns: false,
+            large_layer_threshold_mb: default_large_layer_threshold_mb(),
+            severity_mapping: None,
+        }
+    }
+}
+
+/// Diagnostic severity of each vulnerability severity. Severities left out keep their
+/// default, the one base image scans use: Critical and High are errors, Medium warnings
+/// and the rest information.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct SeverityMapping {
+    pub critical: DiagnosticLevel,
+    pub high: DiagnosticLevel,
+    pub medium: DiagnosticLevel,
+    pub low: DiagnosticLevel,
+    /// Also used for vulnerabilities of unknown severity.
+    pub negligible: DiagnosticLevel,
+}
+
+impl SeverityMapping {
+    pub fn diagnostic_severity(&self, severity: Severity) -> DiagnosticSeverity {
+        match severity {
+            Severity::Critical => self.critical,
+            Severity::High => self.high,
+            Severity::Medium => self.medium,
+            Severity::Low => self.low,
+            Severity::Negligible | Severity::Unknown => self.negligible,
+        }
+        .into()
+    }
+}
+
+impl Default for SeverityMapping {
+    fn default() -> Self {
+        Self {
+            critical: DiagnosticLevel::Error,
+            high: DiagnosticLevel::Error,
+            medium: DiagnosticLevel::Warning,
+            low: DiagnosticLevel::Information,
+            negligible: DiagnosticLevel::Information,
+        }
+    }
+}
+
+/// Severity of a diagnostic, as the settings spell it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticLevel {
+    Error,
+    Warning,
+    #[serde(alias = "info")]
+    Information,
+    Hint,
+}
+
+impl From<DiagnosticLevel> for DiagnosticSeverity {
+    fn from(level: DiagnosticLevel) -> Self {
+        match level {
+            DiagnosticLevel::Error => DiagnosticSeverity::ERROR,
+            DiagnosticLevel::Warning => DiagnosticSeverity::WARNING,
+            DiagnosticLevel::Information => DiagnosticSeverity::INFORMATION,
+            DiagnosticLevel::Hint => DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+/// Order of the per-vulnerability hints of a layer, which decides the ones kept by
+/// `max_hints_per_layer`. Vulnerabilities without a CVSS score go last among their peers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HintOrder {
+    /// Most severe first, then highest CVSS score.
+    #[default]
+    Severity,
+    /// Highest CVSS score first, then most severe.
+    Cvss,
+}
+
+fn default_stale_after_hours() -> u64 {
+    24
+}
+
+fn default_clear_on_close() -> bool {
+    true
+}
+
+fn default_large_layer_threshold_mb() -> u64 {
+    100
 }
 
 pub struct Components {
     pub scanner: Box<dyn ImageScanner + Send + Sync>,
+    /// Runs the scans of `scanner` and of the `scopes` scanners, shared by all of them.
+    pub scan_scheduler: ScanScheduler,
     pub builder: Box<dyn ImageBuilder + Send + Sync>,
     pub iac_scanner: Box<dyn IacScanner + Send + Sync>,
+    /// Scans the packages of directories, for the default tenant.
+    pub filesystem_scanner: Box<dyn FilesystemScanner + Send + Sync>,
+    /// `None` unless scan result persistence is enabled.
+    pub scan_result_store: Option<Box<dyn ScanResultStore + Send + Sync>>,
+    /// Scanners for the documents matching `sysdig.scopes`, in configuration order.
+    pub scopes: Vec<TenantScope>,
+    /// Checks the credentials of the default tenant. `None` when the Sysdig API isn't
+    /// used, e.g. by offline scans.
+    pub credentials_checker: Option<Box<dyn CredentialsChecker + Send + Sync>>,
+    /// Creates risk acceptances in the default tenant. `None` when the Sysdig API isn't used.
+    pub risk_acceptance_client: Option<Box<dyn RiskAcceptanceClient + Send + Sync>>,
+    /// Lists image tags for completion. `None` for offline scans, which can't assume
+    /// registries are reachable.
+    pub registry_client: Option<Box<dyn RegistryClient + Send + Sync>>,
+}
+
+impl Components {
+    /// Image scanner of the tenant the document belongs to.
+    pub fn scanner_for(&self, uri: &Url) -> &(dyn ImageScanner + Send + Sync) {
+        self.scope_for(uri)
+            .map_or(self.scanner.as_ref(), |scope| scope.scanner.as_ref())
+    }
+
+    /// IaC scanner of the tenant the document belongs to.
+    pub fn iac_scanner_for(&self, uri: &Url) -> &(dyn IacScanner + Send + Sync) {
+        self.scope_for(uri)
+            .map_or(self.iac_scanner.as_ref(), |scope| {
+                scope.iac_scanner.as_ref()
+            })
+    }
+
+    /// Risk acceptance client of the tenant the document belongs to. Only the default
+    /// tenant has one, so it's `None` for the documents of `sysdig.scopes`.
+    pub fn risk_acceptance_client_for(
+        &self,
+        uri: &Url,
+    ) -> Option<&(dyn RiskAcceptanceClient + Send + Sync)> {
+        if self.scope_for(uri).is_some() {
+            return None;
+        }
+        self.risk_acceptance_client.as_deref()
+    }
+
+    fn scope_for(&self, uri: &Url) -> Option<&TenantScope> {
+        self.scopes.iter().find(|scope| scope.contains(uri))
+    }
 }
 
 pub trait ComponentFactory: Send + Sync {
@@ -31,24 +727,58 @@ pub trait ComponentFactory: Send + Sync {
 
 #[derive(Error, Debug)]
 pub enum ComponentFactoryError {
-    #[error("unable to retrieve sysdig api token from env var: {0}")]
-    UnableToRetrieveAPITokenFromEnvVar(#[from] VarError),
+    #[error("no Sysdig API token found, tried: {0}")]
+    MissingApiToken(String),
 
     #[error("docker client error: {0:?}")]
     DockerClientError(String),
+
+    #[error("invalid docker configuration: {0}")]
+    InvalidDockerConfig(String),
+
+    #[error("invalid path glob in sysdig.scopes: {0}")]
+    InvalidScopePath(#[from] globset::Error),
+
+    #[error("invalid sysdig.cache.remote_url '{0}': {1}")]
+    InvalidRemoteCacheUrl(String, String),
+
+    #[error("invalid sysdig.scanner.path '{0}': no such file")]
+    InvalidScannerPath(PathBuf),
+
+    #[error("invalid sysdig.scanner.version '{0}', expected a version or \"latest\": {1}")]
+    InvalidScannerVersion(String, String),
+
+    #[error("sysdig.scanner.mode \"api\" can't scan offline, use \"cli\" with sysdig.scan.offline")]
+    OfflineApiScanner,
 }
 
 impl From<ComponentFactoryError> for LspError {
     fn from(err: ComponentFactoryError) -> Self {
         let (code, message) = match err {
-            ComponentFactoryError::UnableToRetrieveAPITokenFromEnvVar(e) => (
-                ErrorCode::InternalError,
-                format!("Could not read SECURE_API_TOKEN from environment: {}", e),
+            ComponentFactoryError::MissingApiToken(sources) => (
+                ErrorCode::InvalidParams,
+                format!(
+                    "No Sysdig API token found (tried {sources}). Set sysdig.api_token, sysdig.api_token_env or SECURE_API_TOKEN"
+                ),
             ),
             ComponentFactoryError::DockerClientError(e) => (
                 ErrorCode::InternalError,
-                format!("Failed to connect to Docker: {}", e),
+                format!(
+                    "Failed to connect to Docker: {}. Set sysdig.docker.host or sysdig.docker.context to use a remote or rootless daemon",
+                    e
+                ),
+            ),
+            ComponentFactoryError::InvalidDockerConfig(e) => (
+                ErrorCode::InvalidParams,
+                format!("Invalid sysdig.docker configuration: {}", e),
             ),
+            e @ (ComponentFactoryError::InvalidScopePath(_)
+            | ComponentFactoryError::InvalidRemoteCacheUrl(..)
+            | ComponentFactoryError::InvalidScannerPath(_)
+            | ComponentFactoryError::InvalidScannerVersion(..)
+            | ComponentFactoryError::OfflineApiScanner) => {
+                (ErrorCode::InvalidParams, e.to_string())
+            }
         };
         LspError {
             code,
@@ -57,3 +787,170 @@ impl From<ComponentFactoryError> for LspError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn it_describes_every_setting_in_the_schema() {
+        let schema = Config::json_schema();
+        let sysdig = &schema["$defs"]["SysdigConfig"]["properties"];
+
+        assert!(sysdig["api_url"].is_object(), "{schema:#}");
+        assert!(sysdig["cache"].is_object(), "{schema:#}");
+        assert_eq!(
+            schema["$defs"]["CacheConfig"]["properties"]["scan_results_ttl_seconds"]["default"],
+            300
+        );
+    }
+
+    #[test]
+    fn it_accepts_camel_case_settings() {
+        let config = Config::from_settings(&json!({
+            "sysdig": { "apiUrl": "https://secure.sysdig.com", "cache": { "scanResultsTtlSeconds": 60 } }
+        }))
+        .unwrap();
+
+        assert_eq!(config.sysdig.api_url, "https://secure.sysdig.com");
+        assert_eq!(config.sysdig.cache.scan_results_ttl_seconds, 60);
+    }
+
+    #[rstest::rstest]
+    #[case(json!({ "apiUrl": "secure.sysdig.com" }), "sysdig.api_url")]
+    #[case(json!({ "apiUrl": "ftp://secure.sysdig.com" }), "sysdig.api_url")]
+    #[case(json!({ "apiUrl": "https://secure.sysdig.com", "apiToken": " " }), "sysdig.api_token")]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "scanner": { "version": "newest" } }),
+        "sysdig.scanner.version"
+    )]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "scanner": { "mode": "grpc" } }),
+        "sysdig.scanner.mode"
+    )]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "lint": { "rules": { "latest-tags": false } } }),
+        "sysdig.lint.rules.latest-tags"
+    )]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "registry": { "mirrors": { "docker.io": "https://mirror.example.com" } } }),
+        "sysdig.registry.mirrors.docker.io"
+    )]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "registry": { "credentials": { "ghcr.io": { "username": "bot", "password": "" } } } }),
+        "sysdig.registry.credentials.ghcr.io.password"
+    )]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "scan": { "platform": "arm64" } }),
+        "sysdig.scan.platform"
+    )]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "build": { "args": { "A=B": "c" } } }),
+        "sysdig.build.args.A=B"
+    )]
+    #[case(
+        json!({ "apiUrl": "https://secure.sysdig.com", "scopes": [{ "paths": ["a/**"], "apiUrl": "eu1" }] }),
+        "sysdig.scopes[0].api_url"
+    )]
+    fn it_points_at_invalid_values(#[case] sysdig: Value, #[case] path: &str) {
+        let error = Config::from_settings(&json!({ "sysdig": sysdig })).unwrap_err();
+
+        assert_eq!(error.path, path);
+    }
+
+    #[test]
+    fn it_redacts_tokens_when_logged() {
+        let config = Config::from_settings(&json!({
+            "sysdig": {
+                "apiUrl": "https://secure.sysdig.com",
+                "apiToken": "secret-token",
+                "apiTokenEnv": "MY_SYSDIG_TOKEN",
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.sysdig.api_token.as_ref().map(Secret::expose),
+            Some("secret-token")
+        );
+        assert_eq!(
+            config.sysdig.api_token_env.as_deref(),
+            Some("MY_SYSDIG_TOKEN")
+        );
+        let logged = format!("{config:?}");
+        assert!(!logged.contains("secret-token"), "{logged}");
+        assert!(logged.contains("[redacted]"), "{logged}");
+    }
+
+    #[test]
+    fn it_toggles_lint_rules_over_their_defaults() {
+        let config = Config::from_settings(&json!({
+            "sysdig": {
+                "apiUrl": "https://secure.sysdig.com",
+                "lint": { "rules": { "latest-tag": false, "missing-user": true } }
+            }
+        }))
+        .unwrap();
+
+        let lint = &config.sysdig.lint;
+        assert!(!lint.is_enabled("latest-tag"));
+        assert!(lint.is_enabled("missing-user"));
+        assert!(lint.is_enabled("dead-stage"));
+        assert!(!LintConfig::default().is_enabled("missing-user"));
+    }
+
+    #[test]
+    fn it_accepts_max_vulns_per_layer_as_the_hint_cap() {
+        let config = Config::from_settings(&json!({
+            "sysdig": {
+                "apiUrl": "https://secure.sysdig.com",
+                "diagnostics": { "maxVulnsPerLayer": 5, "hintOrder": "cvss" }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(config.sysdig.diagnostics.max_hints_per_layer, Some(5));
+        assert_eq!(config.sysdig.diagnostics.hint_order, HintOrder::Cvss);
+    }
+
+    #[test]
+    fn it_reads_the_severity_mapping_keeping_the_defaults_of_the_severities_left_out() {
+        let config = Config::from_settings(&json!({
+            "sysdig": {
+                "apiUrl": "https://secure.sysdig.com",
+                "diagnostics": { "severityMapping": { "high": "warning", "low": "hint" } }
+            }
+        }))
+        .unwrap();
+
+        let mapping = config.sysdig.diagnostics.severity_mapping.unwrap();
+        assert_eq!(
+            mapping.diagnostic_severity(Severity::Critical),
+            DiagnosticSeverity::ERROR
+        );
+        assert_eq!(
+            mapping.diagnostic_severity(Severity::High),
+            DiagnosticSeverity::WARNING
+        );
+        assert_eq!(
+            mapping.diagnostic_severity(Severity::Low),
+            DiagnosticSeverity::HINT
+        );
+        assert_eq!(
+            mapping.diagnostic_severity(Severity::Unknown),
+            DiagnosticSeverity::INFORMATION
+        );
+        assert_eq!(DiagnosticsConfig::default().severity_mapping, None);
+    }
+
+    #[test]
+    fn it_doesnt_require_the_api_url_of_offline_scans() {
+        let config = Config::from_settings(&json!({
+            "sysdig": { "apiUrl": "", "scan": { "offline": true } }
+        }));
+
+        assert!(config.is_ok());
+    }
+}
@@ -0,0 +1,17 @@
+use thiserror::Error;
+
+/// Verifies the configured Sysdig API URL and token up front, so a bad token is
+/// reported when it's configured instead of by the first failing scan.
+#[async_trait::async_trait]
+pub trait CredentialsChecker {
+    async fn check_credentials(&self) -> Result<(), CredentialsError>;
+}
+
+#[derive(Error, Debug)]
+pub enum CredentialsError {
+    #[error("the Sysdig API token was rejected by {0}")]
+    Rejected(String),
+
+    #[error("unable to reach the Sysdig API at {url}: {reason}")]
+    Unreachable { url: String, reason: String },
+}
@@ -1,11 +1,26 @@
 use std::{collections::HashMap, sync::Arc};
 
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use tower_lsp::lsp_types::{Diagnostic, Position, Range};
 
+use crate::app::BaseImageRecommendation;
+use crate::app::protocol::ScanResultParams;
+use crate::domain::image_reference::normalize_image_reference;
+use crate::domain::scanresult::{scan_result::ScanResult, severity::Severity};
+
 #[derive(Default, Debug, Clone)]
 pub struct InMemoryDocumentDatabase {
     documents: Arc<RwLock<HashMap<String, Document>>>,
+    /// Most severe vulnerability reported for each scanned image, by pull string. Kept
+    /// apart from the documents, as the same image can be referenced by several of them.
+    image_severities: Arc<RwLock<HashMap<String, Severity>>>,
+    /// Summary of the last scan of each image, by normalized reference of the image as
+    /// requested, for `sysdig-lsp.list-images`.
+    image_scans: Arc<RwLock<HashMap<String, ScanResultParams>>>,
+    /// Less vulnerable alternatives found for base images, by pull string.
+    base_image_recommendations: Arc<RwLock<HashMap<String, BaseImageRecommendation>>>,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -14,16 +29,136 @@ struct Document {
     /// empty); `None` for entries that only hold diagnostics for files that were
     /// never opened (e.g. discovered by a workspace-wide IaC scan).
     pub text: Option<String>,
+    /// Language id the client opened the document with, e.g. `dockerfile`.
+    pub language_id: Option<String>,
     pub diagnostics: Vec<Diagnostic>,
     pub documentations: Vec<Documentation>,
+    /// Result of the last image scan run on the document, kept for exports.
+    pub last_scan_result: Option<DocumentScanResult>,
 }
 
-#[derive(Default, Debug, Clone)]
-struct Documentation {
+/// An image scan result together with where its findings were reported in the document.
+#[derive(Debug, Clone)]
+pub struct DocumentScanResult {
+    pub scan_result: ScanResult,
+    /// Range of the scanned image reference (the build lens range for "Build and scan").
+    pub image_range: Range,
+    /// Ranges of the instructions the image layers were matched to, by layer index.
+    pub layer_ranges: HashMap<usize, Range>,
+}
+
+/// Hover content reported over a range of the document.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Documentation {
+    pub range: Range,
+    pub content: String,
+    /// Narrower hovers within `range` (e.g. the package names of an instruction),
+    /// shown instead of `content` when the cursor is over them.
+    pub anchors: Vec<DocumentationAnchor>,
+    /// Policy verdict of the image the report is about, shown in a code lens at `range`.
+    pub verdict: Option<PolicyVerdict>,
+    /// When the image the report is about was scanned, if the scanner told.
+    pub scanned_at: Option<DateTime<Utc>>,
+}
+
+/// Policy evaluation of a scanned image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyVerdict {
+    pub failed: bool,
+    pub failed_policies: usize,
+}
+
+impl PolicyVerdict {
+    /// `None` if the image wasn't evaluated against policies.
+    pub fn of(scan_result: &ScanResult) -> Option<Self> {
+        let evaluation_result = scan_result.evaluation_result();
+        if !evaluation_result.is_failed() && !evaluation_result.is_passed() {
+            return None;
+        }
+
+        Some(Self {
+            failed: evaluation_result.is_failed(),
+            failed_policies: scan_result
+                .policies()
+                .iter()
+                .filter(|policy| policy.evaluation_result().is_failed())
+                .count(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentationAnchor {
     pub range: Range,
     pub content: String,
 }
 
+impl Documentation {
+    pub fn new(range: Range, content: String) -> Self {
+        Self {
+            range,
+            content,
+            anchors: vec![],
+            verdict: None,
+            scanned_at: None,
+        }
+    }
+
+    pub fn with_anchors(self, anchors: Vec<DocumentationAnchor>) -> Self {
+        Self { anchors, ..self }
+    }
+
+    pub fn with_verdict(self, verdict: Option<PolicyVerdict>) -> Self {
+        Self { verdict, ..self }
+    }
+
+    pub fn with_scanned_at(self, scanned_at: Option<DateTime<Utc>>) -> Self {
+        Self { scanned_at, ..self }
+    }
+
+    /// How long before `now` the image was scanned, e.g. `2 days ago`. `None` if it's
+    /// unknown or was less than a minute ago, when there's nothing worth telling.
+    pub fn scanned_ago(&self, now: DateTime<Utc>) -> Option<String> {
+        format_age(now - self.scanned_at?)
+    }
+
+    /// Whether the image was scanned at least `stale_after` before `now`.
+    pub fn is_stale(&self, now: DateTime<Utc>, stale_after: TimeDelta) -> bool {
+        self.scanned_at
+            .is_some_and(|scanned_at| now - scanned_at >= stale_after)
+    }
+
+    /// Whether the hover is shown at `position`. Hovers cover whole lines, since
+    /// instruction ranges can be empty (`start == end`) or stop before the cursor does.
+    pub fn covers(&self, position: Position) -> bool {
+        (self.range.start.line..=self.range.end.line).contains(&position.line)
+    }
+
+    /// The content of the innermost hover at `position`: the anchor under it if there
+    /// is one, the whole documentation otherwise.
+    pub fn content_at(&self, position: Position) -> &str {
+        self.anchors
+            .iter()
+            .find(|anchor| position >= anchor.range.start && position < anchor.range.end)
+            .map_or(&self.content, |anchor| &anchor.content)
+    }
+}
+
+fn format_age(age: TimeDelta) -> Option<String> {
+    let (amount, unit) = if age.num_days() > 0 {
+        (age.num_days(), "day")
+    } else if age.num_hours() > 0 {
+        (age.num_hours(), "hour")
+    } else if age.num_minutes() > 0 {
+        (age.num_minutes(), "minute")
+    } else {
+        return None;
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+    Some(format!("{amount} {unit}{plural} ago"))
+}
+
 /// Which documents a diagnostics replacement clears before inserting new ones.
 #[derive(Debug, Clone, Copy)]
 pub enum DiagnosticsScope<'a> {
@@ -34,6 +169,18 @@ pub enum DiagnosticsScope<'a> {
     DocumentsWithUriPrefix(&'a str),
 }
 
+/// URI `uri` takes when `from` is renamed to `to`: `to` itself for the renamed
+/// document, or the same path under `to` for the documents of a renamed folder.
+pub fn renamed_uri(uri: &str, from: &str, to: &str) -> Option<String> {
+    if uri == from {
+        return Some(to.to_string());
+    }
+    let relative = uri
+        .strip_prefix(from.trim_end_matches('/'))?
+        .strip_prefix('/')?;
+    Some(format!("{}/{relative}", to.trim_end_matches('/')))
+}
+
 impl InMemoryDocumentDatabase {
     pub async fn write_document_text(&self, uri: impl Into<String>, text: impl Into<String>) {
         let text = text.into();
@@ -49,6 +196,19 @@ impl InMemoryDocumentDatabase {
             });
     }
 
+    pub async fn write_document_language_id(&self, uri: &str, language_id: &str) {
+        self.documents
+            .write()
+            .await
+            .entry(uri.to_string())
+            .or_default()
+            .language_id = Some(language_id.to_string());
+    }
+
+    pub async fn read_document_language_id(&self, uri: &str) -> Option<String> {
+        self.read_document(uri).await.and_then(|e| e.language_id)
+    }
+
     async fn read_document(&self, uri: &str) -> Option<Document> {
         self.documents.read().await.get(uri).cloned()
     }
@@ -57,13 +217,52 @@ impl InMemoryDocumentDatabase {
         self.read_document(uri).await.and_then(|e| e.text)
     }
 
+    /// Forgets everything derived from the text of a closed document. The entry is
+    /// dropped by the next publish once it has no diagnostics left, which are kept
+    /// unless `clear_diagnostics`.
+    pub async fn close_document(&self, uri: &str, clear_diagnostics: bool) {
+        if let Some(document) = self.documents.write().await.get_mut(uri) {
+            document.text = None;
+            document.language_id = None;
+            document.documentations.clear();
+            document.last_scan_result = None;
+            if clear_diagnostics {
+                document.diagnostics.clear();
+            }
+        }
+    }
+
+    /// Moves everything kept for a renamed document, or for the documents of a renamed
+    /// folder, to their new URIs, which are returned along with the old ones. An empty
+    /// entry is left behind, so the next publish clears the diagnostics under the old
+    /// URI before reclaiming it.
+    pub async fn rename_document(&self, from: &str, to: &str) -> Vec<(String, String)> {
+        let mut documents = self.documents.write().await;
+        let renamed: Vec<_> = documents
+            .keys()
+            .filter_map(|uri| Some((uri.clone(), renamed_uri(uri, from, to)?)))
+            .collect();
+        for (old_uri, new_uri) in &renamed {
+            if let Some(document) = documents.remove(old_uri) {
+                documents.insert(new_uri.clone(), document);
+            }
+        }
+        for (old_uri, _) in &renamed {
+            documents.entry(old_uri.clone()).or_default();
+        }
+        renamed
+    }
+
     /// Drops the given document entries if they (still) hold no state at all:
     /// never opened by the client and no diagnostics/documentation left to publish.
     pub async fn prune_documents_if_empty(&self, uris: &[&str]) {
         let mut documents = self.documents.write().await;
         for uri in uris {
             let is_empty = documents.get(*uri).is_some_and(|d| {
-                d.text.is_none() && d.diagnostics.is_empty() && d.documentations.is_empty()
+                d.text.is_none()
+                    && d.diagnostics.is_empty()
+                    && d.documentations.is_empty()
+                    && d.last_scan_result.is_none()
             });
             if is_empty {
                 documents.remove(*uri);
@@ -130,37 +329,149 @@ impl InMemoryDocumentDatabase {
             .map(|(uri, doc)| (uri, doc.diagnostics))
     }
 
-    pub async fn append_documentation(&self, uri: &str, range: Range, documentation: String) {
+    pub async fn read_diagnostics(&self, uri: &str) -> Vec<Diagnostic> {
+        self.read_document(uri)
+            .await
+            .map(|document| document.diagnostics)
+            .unwrap_or_default()
+    }
+
+    pub async fn read_diagnostics_with_sources(
+        &self,
+        uri: &str,
+        sources: &[&str],
+    ) -> Vec<Diagnostic> {
+        self.read_document(uri)
+            .await
+            .map(|document| document.diagnostics)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|diag| {
+                diag.source
+                    .as_deref()
+                    .is_some_and(|source| sources.contains(&source))
+            })
+            .collect()
+    }
+
+    pub async fn read_documentations(&self, uri: &str) -> Vec<Documentation> {
+        self.read_document(uri)
+            .await
+            .map(|document| document.documentations)
+            .unwrap_or_default()
+    }
+
+    pub async fn write_last_scan_result(&self, uri: &str, scan_result: DocumentScanResult) {
         self.documents
             .write()
             .await
             .entry(uri.into())
-            .and_modify(|d| {
-                d.documentations.push(Documentation {
-                    range,
-                    content: documentation.clone(),
-                })
-            })
-            .or_insert_with(|| Document {
-                documentations: vec![Documentation {
-                    range,
-                    content: documentation,
-                }],
-                ..Default::default()
-            });
+            .or_default()
+            .last_scan_result = Some(scan_result);
+    }
+
+    pub async fn read_last_scan_result(&self, uri: &str) -> Option<DocumentScanResult> {
+        self.documents
+            .read()
+            .await
+            .get(uri)
+            .and_then(|document| document.last_scan_result.clone())
+    }
+
+    /// Records the most severe vulnerability reported for the image, or that none
+    /// was reported.
+    pub async fn write_image_severity(&self, image: &str, severity: Option<Severity>) {
+        let mut image_severities = self.image_severities.write().await;
+        match severity {
+            Some(severity) => image_severities.insert(image.to_string(), severity),
+            None => image_severities.remove(image),
+        };
+    }
+
+    pub async fn read_image_severities(&self) -> HashMap<String, Severity> {
+        self.image_severities.read().await.clone()
     }
 
-    pub async fn read_documentation_at(&self, uri: &str, position: Position) -> Option<String> {
+    pub async fn write_image_scan(&self, summary: ScanResultParams) {
+        let image = summary
+            .requested_pull_string
+            .as_deref()
+            .unwrap_or(&summary.pull_string);
+        self.image_scans
+            .write()
+            .await
+            .insert(normalize_image_reference(image), summary);
+    }
+
+    /// Summary of the last scan of `image`, however it's written.
+    pub async fn read_image_scan(&self, image: &str) -> Option<ScanResultParams> {
+        self.image_scans
+            .read()
+            .await
+            .get(&normalize_image_reference(image))
+            .cloned()
+    }
+
+    pub async fn write_base_image_recommendation(
+        &self,
+        image: &str,
+        recommendation: BaseImageRecommendation,
+    ) {
+        self.base_image_recommendations
+            .write()
+            .await
+            .insert(image.to_string(), recommendation);
+    }
+
+    pub async fn read_base_image_recommendation(
+        &self,
+        image: &str,
+    ) -> Option<BaseImageRecommendation> {
+        self.base_image_recommendations
+            .read()
+            .await
+            .get(image)
+            .cloned()
+    }
+
+    /// Appends `content` to the documentation reported over exactly `range`, or adds
+    /// it as a new one if there is none.
+    pub async fn extend_documentation(&self, uri: &str, range: Range, content: &str) {
+        let mut documents = self.documents.write().await;
+        let documentations = &mut documents.entry(uri.to_string()).or_default().documentations;
+        match documentations.iter_mut().find(|d| d.range == range) {
+            Some(documentation) => documentation.content.push_str(content),
+            None => documentations.push(Documentation::new(range, content.to_string())),
+        }
+    }
+
+    pub async fn append_documentation(&self, uri: &str, documentation: Documentation) {
+        self.documents
+            .write()
+            .await
+            .entry(uri.into())
+            .or_default()
+            .documentations
+            .push(documentation);
+    }
+
+    pub async fn read_documentation_at(
+        &self,
+        uri: &str,
+        position: Position,
+    ) -> Option<Documentation> {
         let documents = self.documents.read().await;
         let document_asked_for = documents.get(uri);
-        let mut documentations_for_document = document_asked_for
+        // The narrowest one wins, e.g. the report of a layer over a report spanning
+        // the whole stage; the first one on ties.
+        document_asked_for
             .iter()
-            .flat_map(|d| d.documentations.iter());
-        let first_documentation_in_range = documentations_for_document.find(|documentation| {
-            position > documentation.range.start && position < documentation.range.end
-        });
-
-        first_documentation_in_range.map(|d| d.content.clone())
+            .flat_map(|d| d.documentations.iter())
+            .filter(|documentation| documentation.covers(position))
+            .min_by_key(|documentation| {
+                documentation.range.end.line - documentation.range.start.line
+            })
+            .cloned()
     }
 
     pub async fn remove_documentations(&self, uri: &str) {
@@ -194,6 +505,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_tells_how_long_ago_the_image_was_scanned() {
+        let now = Utc::now();
+        let scanned = |age: TimeDelta| Documentation::default().with_scanned_at(Some(now - age));
+
+        assert_eq!(scanned(TimeDelta::seconds(30)).scanned_ago(now), None);
+        assert_eq!(
+            scanned(TimeDelta::minutes(1)).scanned_ago(now).as_deref(),
+            Some("1 minute ago")
+        );
+        assert_eq!(
+            scanned(TimeDelta::minutes(150)).scanned_ago(now).as_deref(),
+            Some("2 hours ago")
+        );
+        assert_eq!(
+            scanned(TimeDelta::days(3)).scanned_ago(now).as_deref(),
+            Some("3 days ago")
+        );
+        assert_eq!(Documentation::default().scanned_ago(now), None);
+    }
+
+    #[test]
+    fn it_is_stale_once_scanned_at_least_the_threshold_ago() {
+        let now = Utc::now();
+        let scanned = |age: TimeDelta| Documentation::default().with_scanned_at(Some(now - age));
+
+        assert!(scanned(TimeDelta::hours(24)).is_stale(now, TimeDelta::hours(24)));
+        assert!(!scanned(TimeDelta::hours(23)).is_stale(now, TimeDelta::hours(24)));
+        assert!(!Documentation::default().is_stale(now, TimeDelta::hours(24)));
+    }
+
     #[tokio::test]
     async fn test_add_text_if_not_exists() {
         let db = InMemoryDocumentDatabase::default();
@@ -215,6 +557,93 @@ mod tests {
         assert_eq!(document.text.as_deref(), Some("updated"));
     }
 
+    #[tokio::test]
+    async fn test_close_document_keeps_the_diagnostics_unless_cleared() {
+        let db = InMemoryDocumentDatabase::default();
+        for uri in ["file://kept.rs", "file://cleared.rs"] {
+            db.write_document_text(uri, "contents").await;
+            seed_diagnostics(&db, uri, vec![create_diagnostic((0, 0), (0, 1), "finding")]).await;
+            db.append_documentation(uri, Documentation::default()).await;
+        }
+
+        db.close_document("file://kept.rs", false).await;
+        db.close_document("file://cleared.rs", true).await;
+        db.prune_documents_if_empty(&["file://kept.rs", "file://cleared.rs"])
+            .await;
+
+        let kept = db.read_document("file://kept.rs").await.unwrap();
+        assert_eq!(kept.text, None);
+        assert!(kept.documentations.is_empty());
+        assert_eq!(kept.diagnostics.len(), 1);
+        assert!(db.read_document("file://cleared.rs").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rename_document_leaves_an_empty_entry_behind() {
+        let db = InMemoryDocumentDatabase::default();
+        db.write_document_text("file://old.rs", "contents").await;
+        seed_diagnostics(
+            &db,
+            "file://old.rs",
+            vec![create_diagnostic((0, 0), (0, 1), "finding")],
+        )
+        .await;
+        db.append_documentation("file://old.rs", Documentation::default())
+            .await;
+
+        db.rename_document("file://old.rs", "file://new.rs").await;
+        db.rename_document("file://unknown.rs", "file://other.rs")
+            .await;
+
+        let renamed = db.read_document("file://new.rs").await.unwrap();
+        assert_eq!(renamed.text.as_deref(), Some("contents"));
+        assert_eq!(renamed.diagnostics.len(), 1);
+        assert_eq!(renamed.documentations.len(), 1);
+        db.prune_documents_if_empty(&["file://old.rs"]).await;
+        assert!(db.read_document("file://old.rs").await.is_none());
+        assert!(db.read_document("file://other.rs").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rename_document_moves_the_documents_of_a_renamed_folder() {
+        let db = InMemoryDocumentDatabase::default();
+        db.write_document_text("file:///app/Dockerfile", "FROM alpine")
+            .await;
+        db.write_document_text("file:///app/api/compose.yml", "services:")
+            .await;
+        db.write_document_text("file:///application/Dockerfile", "FROM ubuntu")
+            .await;
+
+        let renamed = db.rename_document("file:///app", "file:///service").await;
+
+        assert_eq!(
+            renamed.into_iter().sorted().collect_vec(),
+            [
+                (
+                    "file:///app/Dockerfile".to_string(),
+                    "file:///service/Dockerfile".to_string()
+                ),
+                (
+                    "file:///app/api/compose.yml".to_string(),
+                    "file:///service/api/compose.yml".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            db.read_document_text("file:///service/api/compose.yml")
+                .await
+                .as_deref(),
+            Some("services:")
+        );
+        assert_eq!(
+            db.read_document_text("file:///application/Dockerfile")
+                .await
+                .as_deref(),
+            Some("FROM ubuntu")
+        );
+        assert_eq!(db.read_document_text("file:///app/Dockerfile").await, None);
+    }
+
     /// Seeds diagnostics as-is: replacing a source no diagnostic has just appends.
     async fn seed_diagnostics(
         db: &InMemoryDocumentDatabase,
@@ -295,7 +724,7 @@ mod tests {
     async fn test_replace_diagnostics_with_source_across_all_documents() {
         let db = InMemoryDocumentDatabase::default();
 
-        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-iac"));
+        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-lsp/iac"));
         let other = create_diagnostic_with_source("Other tool finding", Some("other-source"));
         let untagged = create_diagnostic_with_source("Image scan finding", None);
 
@@ -307,9 +736,9 @@ mod tests {
         .await;
         seed_diagnostics(&db, "file://doc2.yaml", vec![iac.clone()]).await;
 
-        let replacement = create_diagnostic_with_source("New IaC finding", Some("sysdig-iac"));
+        let replacement = create_diagnostic_with_source("New IaC finding", Some("sysdig-lsp/iac"));
         db.replace_diagnostics_with_source(
-            "sysdig-iac",
+            "sysdig-lsp/iac",
             DiagnosticsScope::DocumentsWithUriPrefix(""),
             HashMap::from([("file://doc3.yaml".to_string(), vec![replacement])]),
         )
@@ -342,10 +771,10 @@ mod tests {
     async fn test_replace_drops_insertions_outside_a_document_scope() {
         let db = InMemoryDocumentDatabase::default();
 
-        let in_scope = create_diagnostic_with_source("In scope", Some("sysdig-iac"));
-        let out_of_scope = create_diagnostic_with_source("Out of scope", Some("sysdig-iac"));
+        let in_scope = create_diagnostic_with_source("In scope", Some("sysdig-lsp/iac"));
+        let out_of_scope = create_diagnostic_with_source("Out of scope", Some("sysdig-lsp/iac"));
         db.replace_diagnostics_with_source(
-            "sysdig-iac",
+            "sysdig-lsp/iac",
             DiagnosticsScope::Document("file:///a.yaml"),
             HashMap::from([
                 ("file:///a.yaml".to_string(), vec![in_scope]),
@@ -371,12 +800,12 @@ mod tests {
     async fn test_replace_diagnostics_with_source_scoped_by_uri_prefix() {
         let db = InMemoryDocumentDatabase::default();
 
-        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-iac"));
+        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-lsp/iac"));
         seed_diagnostics(&db, "file:///workspace/a.yaml", vec![iac.clone()]).await;
         seed_diagnostics(&db, "file:///outside/b.yaml", vec![iac.clone()]).await;
 
         db.replace_diagnostics_with_source(
-            "sysdig-iac",
+            "sysdig-lsp/iac",
             DiagnosticsScope::DocumentsWithUriPrefix("file:///workspace/"),
             HashMap::new(),
         )
@@ -416,7 +845,7 @@ mod tests {
     async fn test_prune_keeps_entries_that_are_no_longer_empty() {
         let db = InMemoryDocumentDatabase::default();
 
-        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-iac"));
+        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-lsp/iac"));
         seed_diagnostics(&db, "file:///refilled.yaml", vec![iac]).await;
 
         // A concurrent replacement refilled the entry between the publish snapshot
@@ -432,13 +861,13 @@ mod tests {
     async fn test_replace_diagnostics_with_source_scoped_to_a_single_document() {
         let db = InMemoryDocumentDatabase::default();
 
-        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-iac"));
+        let iac = create_diagnostic_with_source("IaC finding", Some("sysdig-lsp/iac"));
         seed_diagnostics(&db, "file://doc1.yaml", vec![iac.clone()]).await;
         seed_diagnostics(&db, "file://doc2.yaml", vec![iac.clone()]).await;
 
-        let replacement = create_diagnostic_with_source("New IaC finding", Some("sysdig-iac"));
+        let replacement = create_diagnostic_with_source("New IaC finding", Some("sysdig-lsp/iac"));
         db.replace_diagnostics_with_source(
-            "sysdig-iac",
+            "sysdig-lsp/iac",
             DiagnosticsScope::Document("file://doc1.yaml"),
             HashMap::from([("file://doc1.yaml".to_string(), vec![replacement])]),
         )
@@ -455,6 +884,79 @@ mod tests {
         assert_eq!(all_diagnostics[1].1[0].message, "IaC finding");
     }
 
+    #[tokio::test]
+    async fn test_documentation_anchors_narrow_the_hover() {
+        let db = InMemoryDocumentDatabase::default();
+        let documentation = Documentation::new(
+            Range::new(Position::new(1, 0), Position::new(1, 24)),
+            "layer report".to_string(),
+        )
+        .with_anchors(vec![DocumentationAnchor {
+            range: Range::new(Position::new(1, 12), Position::new(1, 19)),
+            content: "openssl card".to_string(),
+        }]);
+        db.append_documentation("file://Dockerfile", documentation)
+            .await;
+
+        let content_at = |documentation: Option<Documentation>, position: Position| {
+            documentation.map(|d| d.content_at(position).to_string())
+        };
+        let over_package = Position::new(1, 14);
+        let over_instruction = Position::new(1, 3);
+        assert_eq!(
+            content_at(
+                db.read_documentation_at("file://Dockerfile", over_package)
+                    .await,
+                over_package
+            )
+            .as_deref(),
+            Some("openssl card")
+        );
+        assert_eq!(
+            content_at(
+                db.read_documentation_at("file://Dockerfile", over_instruction)
+                    .await,
+                over_instruction
+            )
+            .as_deref(),
+            Some("layer report")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_documentation_covers_whole_lines() {
+        let db = InMemoryDocumentDatabase::default();
+        let empty_range = Range::new(Position::new(2, 0), Position::new(2, 0));
+        db.append_documentation(
+            "file://Dockerfile",
+            Documentation::new(
+                Range::new(Position::new(0, 0), Position::new(3, 10)),
+                "stage report".to_string(),
+            ),
+        )
+        .await;
+        db.append_documentation(
+            "file://Dockerfile",
+            Documentation::new(empty_range, "layer report".to_string()),
+        )
+        .await;
+
+        let content_at = async |position: Position| {
+            db.read_documentation_at("file://Dockerfile", position)
+                .await
+                .map(|d| d.content)
+        };
+        assert_eq!(
+            content_at(Position::new(2, 15)).await.as_deref(),
+            Some("layer report")
+        );
+        assert_eq!(
+            content_at(Position::new(1, 0)).await.as_deref(),
+            Some("stage report")
+        );
+        assert_eq!(content_at(Position::new(4, 0)).await, None);
+    }
+
     #[tokio::test]
     async fn test_empty_database() {
         let db = InMemoryDocumentDatabase::default();
@@ -0,0 +1,351 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use futures::future::join_all;
+use globset::GlobBuilder;
+use itertools::Itertools;
+use thiserror::Error;
+use tower_lsp::lsp_types::{Range, Url};
+
+use crate::domain::scanresult::{
+    evaluation_result::EvaluationResult, scan_result::ScanResult, severity::Severity,
+};
+
+use super::{
+    CheckStatus, SysdigIgnore,
+    check_report::{CheckReport, image_location},
+    component_factory::{Components, Config},
+    lsp_server::{
+        command_generator::{DocumentKind, document_comments, image_references_for_uri},
+        commands::scan_base_image::most_severe_vulnerability,
+    },
+    protocol::SeverityCounts,
+};
+
+#[derive(Error, Debug)]
+pub enum FileCheckError {
+    #[error("unable to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+
+    #[error("{0} is neither a Dockerfile, a Compose file, a K8s manifest nor a CI config")]
+    Unsupported(PathBuf),
+
+    #[error("invalid pattern `{0}`: {1}")]
+    InvalidPattern(String, globset::Error),
+
+    #[error("no files match `{0}`")]
+    NoMatches(String),
+}
+
+impl FileCheckError {
+    /// File (or pattern) the error is about.
+    pub fn path(&self) -> String {
+        match self {
+            FileCheckError::Read(path, _) | FileCheckError::Unsupported(path) => {
+                path.display().to_string()
+            }
+            FileCheckError::InvalidPattern(pattern, _) | FileCheckError::NoMatches(pattern) => {
+                pattern.clone()
+            }
+        }
+    }
+}
+
+/// Images of a file scanned outside of an editor (`sysdig-lsp --check`), with the same
+/// detection, ignore rules and policy evaluation as the editor diagnostics.
+pub struct FileCheck {
+    pub path: PathBuf,
+    pub images: Vec<ImageCheck>,
+}
+
+pub struct ImageCheck {
+    pub image: String,
+    /// Where the image is referenced in the file.
+    pub range: Range,
+    /// The verdict, or why the image couldn't be scanned.
+    pub outcome: Result<ImageVerdict, String>,
+}
+
+pub struct ImageVerdict {
+    pub scan_result: ScanResult,
+    /// Vulnerabilities left out by the ignore rules.
+    pub suppressed_vulns: usize,
+    pub evaluation_result: EvaluationResult,
+    /// Names of the failed policies.
+    pub failed_policies: Vec<String>,
+    /// Vulnerabilities reported, i.e. not ignored, by severity.
+    pub vulnerabilities: SeverityCounts,
+    pub most_severe: Option<Severity>,
+}
+
+impl ImageVerdict {
+    fn new(scan_result: ScanResult, sysdig_ignore: &SysdigIgnore) -> Self {
+        let reported = sysdig_ignore.reported(&scan_result, scan_result.vulnerabilities());
+        let severities = reported.iter().counts_by(|vuln| vuln.severity());
+        let count = |severity| severities.get(&severity).copied().unwrap_or_default();
+
+        Self {
+            evaluation_result: scan_result.evaluation_result(),
+            failed_policies: scan_result
+                .policies()
+                .iter()
+                .filter(|policy| policy.evaluation_result().is_failed())
+                .map(|policy| policy.name().to_string())
+                .sorted()
+                .collect(),
+            vulnerabilities: SeverityCounts {
+                critical: count(Severity::Critical),
+                high: count(Severity::High),
+                medium: count(Severity::Medium),
+                low: count(Severity::Low),
+                negligible: count(Severity::Negligible),
+            },
+            most_severe: most_severe_vulnerability(&scan_result, sysdig_ignore),
+            suppressed_vulns: sysdig_ignore.suppressed(&scan_result),
+            scan_result,
+        }
+    }
+
+    /// Whether the image fails its policies or has a reported vulnerability at least as
+    /// severe as `fail_on`.
+    pub fn fails(&self, fail_on: Option<Severity>) -> bool {
+        self.evaluation_result.is_failed()
+            || fail_on.is_some_and(|fail_on| self.most_severe.is_some_and(|most| most <= fail_on))
+    }
+}
+
+impl FileCheck {
+    pub fn status(&self, fail_on: Option<Severity>) -> CheckStatus {
+        self.images
+            .iter()
+            .map(|image| match &image.outcome {
+                Ok(verdict) if verdict.fails(fail_on) => CheckStatus::Failed,
+                Ok(_) => CheckStatus::Passed,
+                Err(_) => CheckStatus::Errored,
+            })
+            .max()
+            .unwrap_or(CheckStatus::Passed)
+    }
+}
+
+/// Scans every image referenced in the file at `path`, as the "Scan all images" lens of
+/// the editor does. `workspace_root` is where the `.sysdigignore` file is looked up.
+pub async fn check_file(
+    components: &Components,
+    config: &Config,
+    workspace_root: &Path,
+    path: &Path,
+) -> Result<FileCheck, FileCheckError> {
+    let read_error = |e| FileCheckError::Read(path.to_path_buf(), e);
+    let absolute_path = tokio::fs::canonicalize(path).await.map_err(read_error)?;
+    let content = tokio::fs::read_to_string(&absolute_path)
+        .await
+        .map_err(read_error)?;
+    let uri = Url::from_file_path(&absolute_path)
+        .map_err(|()| FileCheckError::Unsupported(path.to_path_buf()))?;
+
+    let kind = DocumentKind::detect(&uri, &content, None);
+    if kind == DocumentKind::Unsupported {
+        return Err(FileCheckError::Unsupported(path.to_path_buf()));
+    }
+    let sysdig_ignore = SysdigIgnore::load(Some(workspace_root), Some(&absolute_path))
+        .await
+        .with_inline_directives(document_comments(&content, kind).iter().map(String::as_str))
+        .focus_in_use(config.sysdig.report.focus_in_use)
+        .escalate_known_exploited(config.sysdig.report.escalate_known_exploited);
+
+    // The scans are bounded by the scan scheduler of the components.
+    let (scanner, sysdig_ignore) = (components.scanner_for(&uri), &sysdig_ignore);
+    let images = join_all(
+        image_references_for_uri(&uri, &content, kind)
+            .into_iter()
+            .map(|reference| async move {
+                let outcome = scanner
+                    .scan_image_for_platform(&reference.image, reference.platform.as_deref())
+                    .await
+                    .map(|scan_result| ImageVerdict::new(scan_result, sysdig_ignore))
+                    .map_err(|e| e.to_string());
+                ImageCheck {
+                    image: reference.image,
+                    range: reference.range,
+                    outcome,
+                }
+            }),
+    )
+    .await;
+
+    Ok(FileCheck {
+        path: path.to_path_buf(),
+        images,
+    })
+}
+
+/// Checks every file of `paths`, a few at a time as bounded by the scan scheduler.
+pub async fn check_files(
+    components: &Components,
+    config: &Config,
+    workspace_root: &Path,
+    paths: &[PathBuf],
+    fail_on: Option<Severity>,
+) -> CheckReport {
+    let files = join_all(
+        paths
+            .iter()
+            .map(|path| check_file(components, config, workspace_root, path)),
+    )
+    .await;
+    CheckReport { files, fail_on }
+}
+
+/// Characters making an argument of `--check` a glob instead of a path.
+const GLOB_CHARS: [char; 4] = ['*', '?', '[', '{'];
+
+/// Files to check for the `--check` arguments: paths as given, and the files under
+/// `root` matching the globs (e.g. `services/**/Dockerfile`), sorted and without
+/// duplicates. Hidden directories are only walked if the glob names them, e.g.
+/// `.github/workflows/*.yml`.
+pub fn expand_check_paths(
+    root: &Path,
+    patterns: &[String],
+) -> Result<Vec<PathBuf>, FileCheckError> {
+    let mut paths = vec![];
+    for pattern in patterns {
+        if !pattern.contains(GLOB_CHARS) {
+            paths.push(PathBuf::from(pattern));
+            continue;
+        }
+
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| FileCheckError::InvalidPattern(pattern.clone(), e))?
+            .compile_matcher();
+        // Only the directory the glob starts at is walked, e.g. `services` for
+        // `services/**/Dockerfile`.
+        let base = Path::new(pattern)
+            .components()
+            .take_while(|component| !component.as_os_str().to_string_lossy().contains(GLOB_CHARS))
+            .collect::<PathBuf>();
+        let mut files = vec![];
+        collect_files(root, &base, &mut files);
+        let matches = files
+            .into_iter()
+            .filter(|file| glob.is_match(file))
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            return Err(FileCheckError::NoMatches(pattern.clone()));
+        }
+        paths.extend(matches);
+    }
+    Ok(paths.into_iter().sorted().dedup().collect())
+}
+
+/// Files under `root.join(dir)`, as paths relative to `root`. Unreadable directories are
+/// skipped, as they can't be checked anyway.
+pub(crate) fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(root.join(dir)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = dir.join(entry.file_name());
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    collect_files(root, &path, files);
+                }
+            }
+            Ok(_) => files.push(path),
+            Err(_) => {}
+        }
+    }
+}
+
+/// One line per image, e.g. `Dockerfile:1 alpine:3.18: policy failed (No critical),
+/// 1 critical, 2 high, 0 medium, 0 low, 0 negligible`.
+impl Display for FileCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.images.is_empty() {
+            return writeln!(f, "{}: no images found", self.path.display());
+        }
+        for image in &self.images {
+            let location = image_location(self, image);
+            let verdict = match &image.outcome {
+                Ok(verdict) => verdict,
+                Err(e) => {
+                    writeln!(f, "{location}: scan failed: {e}")?;
+                    continue;
+                }
+            };
+            let policy = match verdict.evaluation_result {
+                EvaluationResult::Passed => "policy passed".to_string(),
+                EvaluationResult::NotEvaluated => "policies not evaluated".to_string(),
+                EvaluationResult::Failed if verdict.failed_policies.is_empty() => {
+                    "policy failed".to_string()
+                }
+                EvaluationResult::Failed => {
+                    format!("policy failed ({})", verdict.failed_policies.join(", "))
+                }
+            };
+            let counts = verdict.vulnerabilities;
+            writeln!(
+                f,
+                "{location}: {policy}, {} critical, {} high, {} medium, {} low, {} negligible",
+                counts.critical, counts.high, counts.medium, counts.low, counts.negligible
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_expands_the_globs_among_the_paths_to_check() {
+        let root = tempfile::tempdir().unwrap();
+        for file in [
+            "Dockerfile",
+            "services/api/Dockerfile",
+            "services/web/Dockerfile",
+            "services/web/README.md",
+            "services/.cache/Dockerfile",
+            ".github/workflows/ci.yml",
+        ] {
+            let path = root.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let patterns = [
+            "services/**/Dockerfile",
+            ".github/workflows/*.yml",
+            "compose.yaml",
+            "services/api/Dockerfile",
+        ]
+        .map(str::to_string);
+
+        let paths = expand_check_paths(root.path(), &patterns).unwrap();
+
+        assert_eq!(
+            paths,
+            [
+                ".github/workflows/ci.yml",
+                "compose.yaml",
+                "services/api/Dockerfile",
+                "services/web/Dockerfile",
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn it_rejects_globs_matching_no_files() {
+        let root = tempfile::tempdir().unwrap();
+
+        let error = expand_check_paths(root.path(), &["**/Dockerfile".to_string()]).unwrap_err();
+
+        assert_eq!(error.to_string(), "no files match `**/Dockerfile`");
+    }
+}
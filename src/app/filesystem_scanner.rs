@@ -0,0 +1,16 @@
+use std::path::Path;
+
+use crate::domain::scanresult::scan_result::ScanResult;
+
+use super::ImageScanError;
+
+/// Scans the packages of a directory (e.g. the dependencies listed in a
+/// `package-lock.json` or `requirements.txt`) instead of an image.
+///
+/// The same CLI scanner runs underneath, so it fails with the same errors as an
+/// image scan. The packages found keep the path of the file listing them, as
+/// reported by the scanner: under the directory, or relative to it.
+#[async_trait::async_trait]
+pub trait FilesystemScanner {
+    async fn scan_directory(&self, directory: &Path) -> Result<ScanResult, ImageScanError>;
+}
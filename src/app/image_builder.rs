@@ -1,18 +1,170 @@
-use std::{error::Error, path::Path};
+use std::{collections::HashMap, error::Error, path::Path};
 
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
 use thiserror::Error;
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::{redaction::REDACTED, sysdig_ignore::DIRECTIVE_PREFIX};
+
+/// Receives the output lines of a build as they are produced. The build drops it
+/// once it finishes.
+pub type BuildLogSender = UnboundedSender<String>;
+
+/// Parts of build arg names that suggest their value is a credential.
+const SECRET_ARG_HINTS: [&str; 6] = ["TOKEN", "PASSWORD", "PASSWD", "SECRET", "KEY", "CREDENTIAL"];
 
 #[async_trait::async_trait]
 pub trait ImageBuilder {
-    async fn build_image(&self, containerfile: &Path) -> Result<ImageBuildResult, ImageBuildError>;
+    async fn build_image(
+        &self,
+        containerfile: &Path,
+        options: &ImageBuildOptions,
+        build_log: BuildLogSender,
+    ) -> Result<ImageBuildResult, ImageBuildError>;
+
+    /// Removes an image built by `build_image`, once it's been scanned.
+    async fn remove_image(&self, image_name: &str) -> Result<(), ImageBuildError>;
+
+    /// Removes the images left behind by earlier builds, e.g. when the server was stopped
+    /// mid-scan, returning their names.
+    async fn remove_temporary_images(&self) -> Result<Vec<String>, ImageBuildError>;
+}
+
+/// How an image is built, besides its Containerfile.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct ImageBuildOptions {
+    /// Values of the `ARG`s of the Containerfile, as `--build-arg` does.
+    pub build_args: HashMap<String, String>,
+    /// Stage of a multi-stage Containerfile the build stops at, as `--target` does.
+    /// The final stage is built if `None`.
+    pub target: Option<String>,
+}
+
+impl ImageBuildOptions {
+    pub fn with_build_args<K, V>(mut self, build_args: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.build_args.extend(
+            build_args
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        self
+    }
+
+    pub fn with_target(self, target: Option<String>) -> Self {
+        Self { target, ..self }
+    }
+
+    /// Adds the build args of the `sysdig-lsp: build-arg NAME=value` directives among the
+    /// comments of a Containerfile, overriding the ones already set. As with `--build-arg`,
+    /// a `NAME` without value takes it from the environment, and is skipped if unset there.
+    pub fn with_inline_directives<'a>(self, comments: impl IntoIterator<Item = &'a str>) -> Self {
+        let build_args = comments
+            .into_iter()
+            .filter_map(build_arg_of_directive)
+            .filter_map(|(name, value)| {
+                let value = value
+                    .map(str::to_string)
+                    .or_else(|| std::env::var(name).ok())?;
+                Some((name, value))
+            })
+            .collect_vec();
+        self.with_build_args(build_args)
+    }
+
+    /// Values of the build args whose name suggests a credential, e.g. `NPM_TOKEN`.
+    pub fn secret_values(&self) -> impl Iterator<Item = &str> {
+        self.build_args
+            .iter()
+            .filter(|(name, _)| {
+                let name = name.to_uppercase();
+                SECRET_ARG_HINTS.iter().any(|hint| name.contains(hint))
+            })
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Build arg values may be credentials, so only their names are logged.
+impl std::fmt::Debug for ImageBuildOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageBuildOptions")
+            .field(
+                "build_args",
+                &self
+                    .build_args
+                    .keys()
+                    .sorted()
+                    .map(|name| format!("{name}={REDACTED}"))
+                    .collect_vec(),
+            )
+            .field("target", &self.target)
+            .finish()
+    }
+}
+
+/// Name and value (if any) of a `sysdig-lsp: build-arg NAME[=value]` comment, `None` for
+/// any other comment.
+fn build_arg_of_directive(comment: &str) -> Option<(&str, Option<&str>)> {
+    let build_arg = comment
+        .trim_start_matches('#')
+        .trim()
+        .strip_prefix(DIRECTIVE_PREFIX)?
+        .trim_start()
+        .strip_prefix("build-arg")
+        .filter(|rest| rest.starts_with(char::is_whitespace))?
+        .trim();
+    let (name, value) = match build_arg.split_once('=') {
+        Some((name, value)) => (name, Some(value)),
+        None => (build_arg, None),
+    };
+    (!name.is_empty() && !name.contains(char::is_whitespace)).then_some((name, value))
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct ImageBuildResult {
     // FIXME(fede): Eventually we will need to check if this dead code is actually needed for our use case
     #[allow(dead_code)]
     pub image_id: String,
     pub image_name: String,
+    /// Size of the image in bytes, if the builder could tell.
+    pub size: Option<u64>,
+    /// When the image was created, if the builder could tell.
+    pub created: Option<DateTime<Utc>>,
+    /// Keeps `remove_temporary_images` from removing the image while it's scanned.
+    pub in_use: Option<ImageInUse>,
+}
+
+/// Marks a temporary image as being built or scanned, until dropped, so removing the
+/// leftovers of other builds keeps it. Being dropped with the command using it, it
+/// doesn't outlive a failed removal or a cancelled build.
+pub struct ImageInUse {
+    release: Option<Box<dyn FnOnce() + Send + Sync>>,
+}
+
+impl ImageInUse {
+    pub fn new(release: impl FnOnce() + Send + Sync + 'static) -> Self {
+        Self {
+            release: Some(Box::new(release)),
+        }
+    }
+}
+
+impl Drop for ImageInUse {
+    fn drop(&mut self) {
+        if let Some(release) = self.release.take() {
+            release();
+        }
+    }
+}
+
+impl std::fmt::Debug for ImageInUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImageInUse").finish_non_exhaustive()
+    }
 }
 
 #[derive(Error, Debug)]
@@ -20,3 +172,45 @@ pub enum ImageBuildError {
     #[error("image builder error: {0}")]
     ImageBuilderError(#[from] Box<dyn Error>),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn it_reads_the_build_arg_directives_over_the_configured_ones() {
+        let options = ImageBuildOptions::default()
+            .with_build_args([("NODE_VERSION", "18"), ("APP_ENV", "dev")])
+            .with_inline_directives([
+                "sysdig-lsp: build-arg NODE_VERSION=20",
+                "sysdig-lsp: build-arg PATH",
+                "sysdig-lsp: build-arg SYSDIG_LSP_UNSET_BUILD_ARG",
+                "sysdig-lsp: build-args IGNORED=1",
+                "sysdig-lsp: ignore CVE-2023-1234",
+                "build-arg NOT_A_DIRECTIVE=1",
+            ]);
+
+        assert_eq!(
+            options.build_args,
+            HashMap::from([
+                ("NODE_VERSION".to_string(), "20".to_string()),
+                ("APP_ENV".to_string(), "dev".to_string()),
+                ("PATH".to_string(), std::env::var("PATH").unwrap()),
+            ])
+        );
+    }
+
+    #[test]
+    fn it_only_logs_the_build_arg_names() {
+        let options = ImageBuildOptions::default()
+            .with_build_args([("NPM_TOKEN", "npm-secret"), ("NODE_VERSION", "20")]);
+
+        assert_eq!(options.secret_values().collect_vec(), vec!["npm-secret"]);
+        assert_eq!(
+            format!("{options:?}"),
+            r#"ImageBuildOptions { build_args: ["NODE_VERSION=[redacted]", "NPM_TOKEN=[redacted]"], target: None }"#
+        );
+    }
+}
@@ -7,10 +7,61 @@ use crate::domain::scanresult::scan_result::ScanResult;
 #[async_trait::async_trait]
 pub trait ImageScanner {
     async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError>;
+
+    /// Scans the variant of a multi-platform image for `platform` (e.g. `linux/arm64`),
+    /// or the default one when `None`. Scanners unable to choose the platform scan the
+    /// default one.
+    async fn scan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        let _ = platform;
+        self.scan_image(image_pull_string).await
+    }
+
+    /// Scans `platform` of the image again without reusing cached results, which are
+    /// replaced by the new one, e.g. to notice policy changes of an unchanged image.
+    /// Scanners that don't cache scan as `scan_image_for_platform`.
+    async fn rescan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        self.scan_image_for_platform(image_pull_string, platform)
+            .await
+    }
 }
 
+/// Why a scan failed, classified from the exit code and output of the scanner so
+/// users get a hint of what to fix.
 #[derive(Error, Debug)]
 pub enum ImageScanError {
+    #[error("the Sysdig API rejected the credentials, check the API URL and token: {0}")]
+    AuthFailed(String),
+
+    #[error("rate limited by the Sysdig API or the registry, try again later: {0}")]
+    RateLimited(String),
+
+    #[error("image not found, check its name, tag and the registry credentials: {0}")]
+    ImageNotFound(String),
+
+    #[error("unable to reach the Sysdig API or the registry: {0}")]
+    NetworkError(String),
+
+    #[error("unable to read the report of the scanner: {0}")]
+    MalformedOutput(String),
+
     #[error("error in the internal scanner execution: {0}")]
     InternalScannerError(Box<dyn Error>),
 }
+
+impl ImageScanError {
+    /// Whether the same scan may succeed if retried later.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            ImageScanError::RateLimited(_) | ImageScanError::NetworkError(_)
+        )
+    }
+}
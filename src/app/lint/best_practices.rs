@@ -0,0 +1,396 @@
+use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range, TextEdit};
+
+use crate::infra::{Instruction, Stage};
+
+use super::{LintFinding, LintFix};
+
+pub const LATEST_TAG_RULE: &str = "latest-tag";
+pub const APT_NO_INSTALL_RECOMMENDS_RULE: &str = "apt-no-install-recommends";
+pub const APT_CACHE_NOT_CLEANED_RULE: &str = "apt-cache-not-cleaned";
+pub const ADD_INSTEAD_OF_COPY_RULE: &str = "add-instead-of-copy";
+
+/// Tar archives, which `ADD` extracts into the destination.
+const ARCHIVE_EXTENSIONS: &[&str] = &[
+    ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz", ".tbz2", ".tar.xz", ".txz", ".tar.zst",
+];
+
+/// Flags the instructions going against the Dockerfile best practices: unpinned base
+/// images, `apt-get install` pulling recommended packages or leaving its package lists
+/// behind, and `ADD` where `COPY` does the same.
+pub fn find_best_practice_violations(content: &str, stages: &[Stage]) -> Vec<LintFinding> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut findings = vec![];
+
+    for stage in stages {
+        for instruction in &stage.instructions {
+            match instruction.keyword.as_str() {
+                "FROM" => findings.extend(latest_tag_finding(stages, stage, instruction)),
+                "RUN" => findings.extend(apt_findings(&lines, instruction)),
+                "ADD" => findings.extend(add_instead_of_copy_finding(instruction)),
+                _ => {}
+            }
+        }
+    }
+
+    findings
+}
+
+/// Arguments after the flags of the instruction, e.g. `--platform` or `--mount`.
+fn split_flags(instruction: &Instruction) -> (&[String], &[String]) {
+    let flags = instruction
+        .arguments
+        .iter()
+        .take_while(|argument| argument.starts_with("--"))
+        .count();
+    instruction.arguments.split_at(flags)
+}
+
+fn latest_tag_finding(stages: &[Stage], stage: &Stage, from: &Instruction) -> Option<LintFinding> {
+    let (_, arguments) = split_flags(from);
+    let image = arguments.first()?;
+    let is_previous_stage = stages[..stage.index]
+        .iter()
+        .any(|previous| previous.is_referenced_by(image));
+    // Images given by digest are pinned, and `ARG`s can't be resolved here.
+    if is_previous_stage || image.eq_ignore_ascii_case("scratch") || image.contains(['@', '$']) {
+        return None;
+    }
+
+    let name = image.rsplit('/').next().unwrap_or(image);
+    let message = match name.split_once(':') {
+        Some((_, "latest")) => format!(
+            "Image '{image}' uses the `latest` tag: pin a version so builds are reproducible"
+        ),
+        Some(_) => return None,
+        None => format!(
+            "Image '{image}' has no tag, so it resolves to `latest`: pin a version so builds are reproducible"
+        ),
+    };
+
+    Some(LintFinding {
+        rule: LATEST_TAG_RULE,
+        message,
+        range: from.range,
+        severity: DiagnosticSeverity::WARNING,
+        tags: vec![],
+        fix: None,
+    })
+}
+
+/// Commands of a shell form `RUN`, split on `&&`, `||`, `;` and `|`.
+fn shell_commands(arguments: &[String]) -> Vec<Vec<&str>> {
+    let mut commands = vec![vec![]];
+    for argument in arguments {
+        let (word, ends_command) = match argument.strip_suffix(';') {
+            Some(word) => (word, true),
+            None => (argument.as_str(), false),
+        };
+        if matches!(word, "&&" | "||" | ";" | "|") {
+            commands.push(vec![]);
+            continue;
+        }
+        if !word.is_empty()
+            && let Some(command) = commands.last_mut()
+        {
+            command.push(word);
+        }
+        if ends_command {
+            commands.push(vec![]);
+        }
+    }
+    commands
+}
+
+/// `apt-get install` or `apt install`, possibly after `sudo` or environment variables.
+fn is_apt_install(command: &[&str]) -> bool {
+    command
+        .iter()
+        .position(|word| matches!(*word, "apt-get" | "apt"))
+        .is_some_and(|apt| command[apt + 1..].contains(&"install"))
+}
+
+fn apt_findings(lines: &[&str], run: &Instruction) -> Vec<LintFinding> {
+    let (flags, arguments) = split_flags(run);
+    let installs: Vec<Vec<&str>> = shell_commands(arguments)
+        .into_iter()
+        .filter(|command| is_apt_install(command))
+        .collect();
+    if installs.is_empty() {
+        return vec![];
+    }
+    let mut findings = vec![];
+
+    let with_recommends = installs
+        .iter()
+        .filter(|command| !command.contains(&"--no-install-recommends"))
+        .count();
+    if with_recommends > 0 {
+        // Only fixed when every install misses the flag, and each can be found verbatim.
+        let occurrences = apt_install_occurrences(lines, run.range);
+        let fix =
+            (with_recommends == installs.len() && occurrences.len() == installs.len()).then(|| {
+                LintFix {
+                    title: "Add --no-install-recommends".to_string(),
+                    edits: occurrences
+                        .into_iter()
+                        .map(|end| TextEdit {
+                            range: Range::new(end, end),
+                            new_text: " --no-install-recommends".to_string(),
+                        })
+                        .collect(),
+                }
+            });
+        findings.push(LintFinding {
+            rule: APT_NO_INSTALL_RECOMMENDS_RULE,
+            message: "apt-get install also installs the recommended packages: add --no-install-recommends to keep the image small"
+                .to_string(),
+            range: run.range,
+            severity: DiagnosticSeverity::INFORMATION,
+            tags: vec![],
+            fix,
+        });
+    }
+
+    // Cache mounts keep the package lists out of the image already.
+    let caches_apt = flags.iter().any(|flag| {
+        flag.starts_with("--mount=") && flag.contains("type=cache") && flag.contains("/var/lib/apt")
+    });
+    if !caches_apt && !run.arguments_str.contains("/var/lib/apt/lists") {
+        findings.push(LintFinding {
+            rule: APT_CACHE_NOT_CLEANED_RULE,
+            message: "The apt package lists are left in the image: remove /var/lib/apt/lists/* in the same RUN"
+                .to_string(),
+            range: run.range,
+            severity: DiagnosticSeverity::INFORMATION,
+            tags: vec![],
+            fix: clean_apt_lists_fix(lines, run),
+        });
+    }
+
+    findings
+}
+
+/// Ends of the `apt-get install` / `apt install` written in the lines of the instruction,
+/// leaving out comments.
+fn apt_install_occurrences(lines: &[&str], range: Range) -> Vec<Position> {
+    let mut occurrences = vec![];
+    for line_number in range.start.line..=range.end.line {
+        let Some(line) = lines.get(line_number as usize) else {
+            break;
+        };
+        let code = line.split('#').next().unwrap_or_default();
+        for needle in ["apt-get install", "apt install"] {
+            for (start, _) in code.match_indices(needle) {
+                let end = start + needle.len();
+                let bounded_before = !code[..start]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|c| c.is_alphanumeric() || matches!(c, '-' | '_'));
+                let bounded_after = code[end..].chars().next().is_none_or(char::is_whitespace);
+                if bounded_before && bounded_after {
+                    occurrences.push(Position::new(line_number, end as u32));
+                }
+            }
+        }
+    }
+    occurrences
+}
+
+/// Appends the cleanup to the instruction, unless it ends in a comment or isn't a
+/// plain shell command (exec form, heredocs).
+fn clean_apt_lists_fix(lines: &[&str], run: &Instruction) -> Option<LintFix> {
+    let arguments = run.arguments_str.trim_start();
+    let last_line = lines.get(run.range.end.line as usize)?;
+    if arguments.starts_with('[') || arguments.contains("<<") || last_line.contains('#') {
+        return None;
+    }
+
+    Some(LintFix {
+        title: "Remove the apt package lists".to_string(),
+        edits: vec![TextEdit {
+            range: Range::new(run.range.end, run.range.end),
+            new_text: " && rm -rf /var/lib/apt/lists/*".to_string(),
+        }],
+    })
+}
+
+/// `ADD` of local files or directories, which `COPY` handles the same way. Remote
+/// sources, archives (which `ADD` extracts) and sources that could match archives
+/// (wildcards, variables) are left alone.
+fn add_instead_of_copy_finding(add: &Instruction) -> Option<LintFinding> {
+    let (flags, arguments) = split_flags(add);
+    let add_only_flag = flags.iter().any(|flag| {
+        ["--checksum", "--keep-git-dir", "--unpack"]
+            .iter()
+            .any(|add_only| flag.starts_with(add_only))
+    });
+    // The exec form (`ADD ["src", "dest"]`) is quoted JSON.
+    let paths: Vec<String> = match serde_json::from_str(arguments.join(" ").as_str()) {
+        Ok(paths) => paths,
+        Err(_) => arguments.to_vec(),
+    };
+    let (_, sources) = paths.split_last()?;
+    if add_only_flag || sources.is_empty() || !sources.iter().all(|source| is_local_file(source)) {
+        return None;
+    }
+
+    let keyword_start = add.range.start;
+    let keyword_end = Position::new(keyword_start.line, keyword_start.character + 3);
+    Some(LintFinding {
+        rule: ADD_INSTEAD_OF_COPY_RULE,
+        message: "ADD copies local files just like COPY, which doesn't fetch URLs nor extract archives: use COPY"
+            .to_string(),
+        range: add.range,
+        severity: DiagnosticSeverity::WARNING,
+        tags: vec![],
+        fix: Some(LintFix {
+            title: "Replace ADD with COPY".to_string(),
+            edits: vec![TextEdit {
+                range: Range::new(keyword_start, keyword_end),
+                new_text: "COPY".to_string(),
+            }],
+        }),
+    })
+}
+
+fn is_local_file(source: &str) -> bool {
+    let lowercase = source.to_lowercase();
+    !source.contains("://")
+        && !source.starts_with("git@")
+        && !source.contains(['*', '?', '[', '$'])
+        && !ARCHIVE_EXTENSIONS
+            .iter()
+            .any(|extension| lowercase.ends_with(extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use crate::infra::parse_dockerfile_stages;
+
+    use super::*;
+
+    fn findings(dockerfile: &str) -> Vec<LintFinding> {
+        find_best_practice_violations(dockerfile, &parse_dockerfile_stages(dockerfile))
+    }
+
+    fn rules(dockerfile: &str) -> Vec<&'static str> {
+        findings(dockerfile)
+            .into_iter()
+            .map(|finding| finding.rule)
+            .collect()
+    }
+
+    /// Applies the insertions of the fix of the only finding of the rule.
+    fn fixed(dockerfile: &str, rule: &str) -> String {
+        let findings = findings(dockerfile);
+        let finding = findings
+            .iter()
+            .find(|finding| finding.rule == rule)
+            .unwrap();
+        let mut lines: Vec<String> = dockerfile.lines().map(str::to_string).collect();
+        for edit in finding.fix.as_ref().unwrap().edits.iter().rev() {
+            let Position { line, character } = edit.range.start;
+            let line = &mut lines[line as usize];
+            line.replace_range(
+                character as usize..edit.range.end.character as usize,
+                &edit.new_text,
+            );
+        }
+        lines.join("\n")
+    }
+
+    #[rstest::rstest]
+    #[case("FROM alpine\n", true)]
+    #[case("FROM alpine:latest\n", true)]
+    #[case("FROM --platform=linux/amd64 localhost:5000/team/app\n", true)]
+    #[case("FROM alpine:3.19\n", false)]
+    #[case("FROM localhost:5000/team/app:1.0\n", false)]
+    #[case("FROM alpine@sha256:1234\n", false)]
+    #[case("FROM scratch\n", false)]
+    #[case("ARG TAG=3.19\nFROM alpine:$TAG\n", false)]
+    #[case("FROM golang:1.22 AS build\nFROM build\n", false)]
+    fn it_flags_images_without_a_pinned_tag(#[case] dockerfile: &str, #[case] flagged: bool) {
+        assert_eq!(rules(dockerfile).contains(&LATEST_TAG_RULE), flagged);
+    }
+
+    #[test]
+    fn it_flags_apt_installs_with_recommends_and_package_lists() {
+        let dockerfile = "FROM debian:12\nRUN apt-get update && apt-get install -y curl\n";
+
+        assert_eq!(
+            rules(dockerfile),
+            [APT_NO_INSTALL_RECOMMENDS_RULE, APT_CACHE_NOT_CLEANED_RULE]
+        );
+        assert_eq!(
+            findings(dockerfile)[0].range,
+            Range::new(Position::new(1, 0), Position::new(1, 45))
+        );
+    }
+
+    #[test]
+    fn it_accepts_lean_apt_installs() {
+        assert!(
+            rules("FROM debian:12\nRUN apt-get update \\\n && apt-get install -y --no-install-recommends curl \\\n && rm -rf /var/lib/apt/lists/*\n")
+                .is_empty()
+        );
+        assert!(
+            rules("FROM debian:12\nRUN --mount=type=cache,target=/var/lib/apt apt-get update; apt-get install --no-install-recommends -y curl\n")
+                .is_empty()
+        );
+        assert!(rules("FROM debian:12\nRUN apt-get update\n").is_empty());
+    }
+
+    #[test]
+    fn it_adds_no_install_recommends_to_every_install() {
+        let dockerfile = "FROM debian:12\nRUN apt-get update && apt-get install -y curl \\\n && sudo apt install git\n";
+
+        assert_eq!(
+            fixed(dockerfile, APT_NO_INSTALL_RECOMMENDS_RULE),
+            "FROM debian:12\nRUN apt-get update && apt-get install --no-install-recommends -y curl \\\n && sudo apt install --no-install-recommends git"
+        );
+    }
+
+    #[test]
+    fn it_doesnt_fix_installs_it_cant_find_verbatim() {
+        let findings = findings("FROM debian:12\nRUN apt-get -y install curl\n");
+
+        assert_eq!(findings[0].rule, APT_NO_INSTALL_RECOMMENDS_RULE);
+        assert_eq!(findings[0].fix, None);
+    }
+
+    #[test]
+    fn it_removes_the_package_lists_at_the_end_of_the_run() {
+        let dockerfile = "FROM debian:12\nRUN apt-get update \\\n && apt-get install -y --no-install-recommends curl\n";
+
+        assert_eq!(
+            fixed(dockerfile, APT_CACHE_NOT_CLEANED_RULE),
+            "FROM debian:12\nRUN apt-get update \\\n && apt-get install -y --no-install-recommends curl && rm -rf /var/lib/apt/lists/*"
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("ADD app.py /app/\n", true)]
+    #[case("ADD --chown=app [\"config\", \"/etc/app/\"]\n", true)]
+    #[case("ADD https://example.com/app.py /app/\n", false)]
+    #[case("ADD rootfs.tar.gz /\n", false)]
+    #[case("ADD files/* /app/\n", false)]
+    #[case("ADD --checksum=sha256:1234 app.py /app/\n", false)]
+    fn it_flags_add_of_local_files(#[case] add: &str, #[case] flagged: bool) {
+        let dockerfile = format!("FROM alpine:3.19\n{add}");
+
+        assert_eq!(rules(&dockerfile) == [ADD_INSTEAD_OF_COPY_RULE], flagged);
+    }
+
+    #[test]
+    fn it_replaces_add_with_copy() {
+        assert_eq!(
+            fixed(
+                "FROM alpine:3.19\n  add app.py /app/\n",
+                ADD_INSTEAD_OF_COPY_RULE
+            ),
+            "FROM alpine:3.19\n  COPY app.py /app/"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use tower_lsp::lsp_types::{DiagnosticSeverity, DiagnosticTag, Position, Range, TextEdit};
+
+use crate::infra::{Instruction, Stage};
+
+use super::{LintFinding, LintFix};
+
+pub const DEAD_STAGE_RULE: &str = "dead-stage";
+
+/// Flags the stages that don't contribute to the final stage: neither the final
+/// stage nor any stage it (transitively) depends on references them.
+pub fn find_dead_stages(stages: &[Stage]) -> Vec<LintFinding> {
+    let Some(final_stage) = stages.last() else {
+        return vec![];
+    };
+
+    // Stages can only reference previous stages, so a single backwards pass
+    // starting from the final stage reaches every live stage.
+    let mut live = vec![false; stages.len()];
+    live[final_stage.index] = true;
+    for stage in stages.iter().rev() {
+        if !live[stage.index] {
+            continue;
+        }
+        for reference in stage_references(stage) {
+            for dependency in &stages[..stage.index] {
+                if dependency.is_referenced_by(&reference) {
+                    live[dependency.index] = true;
+                }
+            }
+        }
+    }
+
+    stages
+        .iter()
+        .filter(|stage| !live[stage.index])
+        .map(dead_stage_finding)
+        .collect()
+}
+
+fn dead_stage_finding(stage: &Stage) -> LintFinding {
+    let stage_label = match &stage.name {
+        Some(name) => format!("'{name}'"),
+        None => format!("#{}", stage.index),
+    };
+    let stage_range = stage.range();
+    let from_range = stage
+        .instructions
+        .first()
+        .map(|from| from.range)
+        .unwrap_or(stage_range);
+
+    LintFinding {
+        rule: DEAD_STAGE_RULE,
+        message: format!(
+            "Build stage {stage_label} is never used by the final stage, but may still be built by some builders"
+        ),
+        range: from_range,
+        severity: DiagnosticSeverity::WARNING,
+        tags: vec![DiagnosticTag::UNNECESSARY],
+        fix: Some(LintFix {
+            title: format!("Remove unused stage {stage_label}"),
+            edits: vec![TextEdit {
+                // Whole lines, so the removal doesn't leave partial lines behind.
+                range: Range::new(
+                    Position::new(stage_range.start.line, 0),
+                    Position::new(stage_range.end.line + 1, 0),
+                ),
+                new_text: String::new(),
+            }],
+        }),
+    }
+}
+
+/// Stage names or indexes referenced by `FROM <stage>`, `COPY/ADD --from=<stage>`
+/// and `RUN --mount=...,from=<stage>`.
+fn stage_references(stage: &Stage) -> Vec<String> {
+    let mut references: Vec<String> = stage.base.iter().cloned().collect();
+
+    references.extend(stage.instructions.iter().flat_map(instruction_references));
+
+    references
+}
+
+fn instruction_references(instruction: &Instruction) -> Vec<String> {
+    instruction
+        .arguments
+        .iter()
+        .filter_map(|arg| {
+            if let Some(from) = arg.strip_prefix("--from=") {
+                return Some(from.to_string());
+            }
+            arg.strip_prefix("--mount=")?
+                .split(',')
+                .find_map(|option| option.strip_prefix("from="))
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use crate::infra::parse_dockerfile_stages;
+
+    use super::find_dead_stages;
+
+    fn dead_stage_messages(dockerfile: &str) -> Vec<String> {
+        find_dead_stages(&parse_dockerfile_stages(dockerfile))
+            .into_iter()
+            .map(|finding| finding.message)
+            .collect()
+    }
+
+    #[test]
+    fn it_finds_nothing_in_single_stage_dockerfiles() {
+        assert!(dead_stage_messages("FROM alpine\nRUN echo\n").is_empty());
+    }
+
+    #[test]
+    fn it_considers_stages_referenced_by_copy_run_mounts_and_from_as_used() {
+        let dockerfile = r#"FROM golang:1.19 AS build
+RUN go build -o /app
+
+FROM build AS tested
+RUN go test ./...
+
+FROM node:18 AS assets
+RUN npm run build
+
+FROM alpine AS cache
+
+FROM alpine
+COPY --from=tested /app /app
+COPY --from=2 /dist /dist
+RUN --mount=type=cache,from=cache,target=/cache ls /cache
+"#;
+
+        assert!(dead_stage_messages(dockerfile).is_empty());
+    }
+
+    #[test]
+    fn it_flags_stages_not_reaching_the_final_stage() {
+        let dockerfile = r#"FROM golang:1.19 AS build
+RUN go build -o /app
+
+FROM build AS debug
+RUN go install github.com/go-delve/delve/cmd/dlv@latest
+
+FROM node:18
+RUN npm test
+
+FROM alpine
+COPY --from=build /app /app
+"#;
+
+        assert_eq!(
+            dead_stage_messages(dockerfile),
+            vec![
+                "Build stage 'debug' is never used by the final stage, but may still be built by some builders",
+                "Build stage #2 is never used by the final stage, but may still be built by some builders",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_flags_stages_only_used_by_dead_stages() {
+        let dockerfile = r#"FROM alpine AS base
+FROM base AS unused
+FROM alpine
+"#;
+
+        let findings = find_dead_stages(&parse_dockerfile_stages(dockerfile));
+
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].range,
+            Range::new(Position::new(0, 0), Position::new(0, 19))
+        );
+    }
+
+    #[test]
+    fn it_offers_removing_the_whole_stage() {
+        let dockerfile = "FROM node:18 AS unused\nRUN npm test\n\nFROM alpine\n";
+
+        let findings = find_dead_stages(&parse_dockerfile_stages(dockerfile));
+        let fix = findings[0].fix.as_ref().unwrap();
+
+        assert_eq!(fix.title, "Remove unused stage 'unused'");
+        assert_eq!(
+            fix.edits[0].range,
+            Range::new(Position::new(0, 0), Position::new(2, 0))
+        );
+        assert_eq!(fix.edits[0].new_text, "");
+    }
+}
@@ -0,0 +1,242 @@
+use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range, TextEdit};
+
+use crate::infra::K8sContainer;
+
+use super::{LintFinding, LintFix};
+
+pub const RUN_AS_ROOT_RULE: &str = "run-as-root";
+pub const MISSING_RESOURCE_LIMITS_RULE: &str = "missing-resource-limits";
+
+/// Flags the containers that may run as root or without resource limits.
+pub fn find_misconfigurations(containers: &[K8sContainer]) -> Vec<LintFinding> {
+    containers
+        .iter()
+        .flat_map(|container| {
+            let run_as_root = run_as_root_finding(container);
+            let missing_limits = (!container.ephemeral && !container.has_resource_limits)
+                .then(|| missing_resource_limits_finding(container));
+            run_as_root.into_iter().chain(missing_limits)
+        })
+        .collect()
+}
+
+fn container_label(container: &K8sContainer) -> String {
+    match &container.name {
+        Some(name) => format!("Container '{name}'"),
+        None => "Container".to_string(),
+    }
+}
+
+/// Containers run as root unless they, or their pod, set `runAsNonRoot: true` or a
+/// non-root `runAsUser`.
+fn run_as_root_finding(container: &K8sContainer) -> Option<LintFinding> {
+    if container.run_as_non_root == Some(true)
+        || container.run_as_user.is_some_and(|user| user != 0)
+    {
+        return None;
+    }
+
+    let label = container_label(container);
+    let message = match container.run_as_user {
+        Some(_) => format!("{label} runs as root (runAsUser: 0)"),
+        None => format!(
+            "{label} may run as root: set securityContext.runAsNonRoot to true or a non-root runAsUser"
+        ),
+    };
+    // Kubelet refuses to start containers asking for both root and non-root.
+    let fix = match container.run_as_user {
+        Some(_) => None,
+        None => run_as_non_root_fix(container),
+    };
+
+    Some(LintFinding {
+        rule: RUN_AS_ROOT_RULE,
+        message,
+        range: container.range,
+        severity: DiagnosticSeverity::WARNING,
+        tags: vec![],
+        fix,
+    })
+}
+
+fn run_as_non_root_fix(container: &K8sContainer) -> Option<LintFix> {
+    let edit = if let Some(value) = container.run_as_non_root_value {
+        TextEdit {
+            range: value,
+            new_text: "true".to_string(),
+        }
+    } else if let Some(security_context) = &container.security_context {
+        let column = security_context.keys_column?;
+        insert_line(
+            security_context.line + 1,
+            format!("{}runAsNonRoot: true\n", " ".repeat(column as usize)),
+        )
+    } else if container.single_line_first_key {
+        let indent = " ".repeat(container.range.start.character as usize);
+        insert_line(
+            container.range.start.line + 1,
+            format!("{indent}securityContext:\n{indent}  runAsNonRoot: true\n"),
+        )
+    } else {
+        return None;
+    };
+
+    Some(LintFix {
+        title: "Set runAsNonRoot: true".to_string(),
+        edits: vec![edit],
+    })
+}
+
+fn insert_line(line: u32, text: String) -> TextEdit {
+    let start = Position::new(line, 0);
+    TextEdit {
+        range: Range::new(start, start),
+        new_text: text,
+    }
+}
+
+fn missing_resource_limits_finding(container: &K8sContainer) -> LintFinding {
+    LintFinding {
+        rule: MISSING_RESOURCE_LIMITS_RULE,
+        message: format!(
+            "{} has no resource limits: it may use up the CPU and memory of its node",
+            container_label(container)
+        ),
+        range: container.range,
+        severity: DiagnosticSeverity::WARNING,
+        tags: vec![],
+        fix: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use crate::app::{component_factory::LintConfig, lint};
+
+    use super::{MISSING_RESOURCE_LIMITS_RULE, RUN_AS_ROOT_RULE};
+
+    fn lint_k8s_manifest(manifest: &str) -> Vec<lint::LintFinding> {
+        lint::lint_k8s_manifest(manifest, &LintConfig::default())
+    }
+
+    /// Applies the fix of the run-as-root finding, which must be offered.
+    fn fixed(manifest: &str) -> String {
+        let findings = lint_k8s_manifest(manifest);
+        let finding = findings
+            .iter()
+            .find(|finding| finding.rule == RUN_AS_ROOT_RULE)
+            .expect("run-as-root finding");
+        let edit = &finding.fix.as_ref().expect("fix").edits[0];
+        assert_eq!(edit.range.start, edit.range.end, "fixes only insert text");
+
+        let mut lines: Vec<String> = manifest.lines().map(str::to_string).collect();
+        let Position { line, character } = edit.range.start;
+        let target = &mut lines[line as usize];
+        target.insert_str(character as usize, &edit.new_text);
+        lines.join("\n")
+    }
+
+    #[test]
+    fn it_flags_containers_running_as_root_without_limits() {
+        let manifest = "apiVersion: v1\nkind: Pod\nspec:\n  containers:\n  - name: nginx\n    image: nginx:1.27\n";
+
+        let findings = lint_k8s_manifest(manifest);
+
+        let rules: Vec<_> = findings.iter().map(|finding| finding.rule).collect();
+        assert_eq!(rules, [RUN_AS_ROOT_RULE, MISSING_RESOURCE_LIMITS_RULE]);
+        assert!(
+            findings[0]
+                .message
+                .starts_with("Container 'nginx' may run as root")
+        );
+        assert_eq!(
+            findings[0].range,
+            Range::new(Position::new(4, 4), Position::new(4, 8))
+        );
+    }
+
+    #[test]
+    fn it_accepts_non_root_containers_with_limits() {
+        let manifest = r#"
+apiVersion: apps/v1
+kind: Deployment
+spec:
+  template:
+    spec:
+      securityContext:
+        runAsUser: 1000
+      containers:
+      - name: app
+        image: app:1.0
+        resources:
+          limits:
+            memory: 128Mi
+      - name: sidecar
+        image: sidecar:1.0
+        securityContext:
+          runAsNonRoot: true
+        resources:
+          limits:
+            cpu: 100m
+"#;
+
+        assert_eq!(lint_k8s_manifest(manifest), vec![]);
+    }
+
+    #[test]
+    fn it_adds_a_security_context_below_the_first_key() {
+        let manifest = "spec:\n  containers:\n  - name: nginx\n    image: nginx:1.27\n";
+
+        assert_eq!(
+            fixed(manifest),
+            "spec:\n  containers:\n  - name: nginx\n    securityContext:\n      runAsNonRoot: true\n    image: nginx:1.27"
+        );
+    }
+
+    #[test]
+    fn it_sets_run_as_non_root_in_the_existing_security_context() {
+        let manifest = "spec:\n  containers:\n  - name: nginx\n    securityContext:\n      readOnlyRootFilesystem: true\n";
+
+        assert_eq!(
+            fixed(manifest),
+            "spec:\n  containers:\n  - name: nginx\n    securityContext:\n      runAsNonRoot: true\n      readOnlyRootFilesystem: true"
+        );
+    }
+
+    #[test]
+    fn it_replaces_run_as_non_root_false() {
+        let manifest = "spec:\n  containers:\n  - name: nginx\n    securityContext:\n      runAsNonRoot: false\n";
+
+        let findings = lint_k8s_manifest(manifest);
+
+        let edit = &findings[0].fix.as_ref().unwrap().edits[0];
+        assert_eq!(edit.new_text, "true");
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(4, 20), Position::new(4, 25))
+        );
+    }
+
+    #[test]
+    fn it_offers_no_fix_for_containers_asking_for_root() {
+        let manifest =
+            "spec:\n  containers:\n  - name: nginx\n    securityContext:\n      runAsUser: 0\n";
+
+        let findings = lint_k8s_manifest(manifest);
+
+        assert_eq!(
+            findings[0].message,
+            "Container 'nginx' runs as root (runAsUser: 0)"
+        );
+        assert_eq!(findings[0].fix, None);
+    }
+
+    #[test]
+    fn it_skips_resource_limits_of_ephemeral_containers() {
+        let manifest = "spec:\n  ephemeralContainers:\n  - name: debugger\n    image: busybox\n    securityContext:\n      runAsNonRoot: true\n";
+
+        assert_eq!(lint_k8s_manifest(manifest), vec![]);
+    }
+}
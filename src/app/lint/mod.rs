@@ -0,0 +1,82 @@
+mod best_practices;
+mod dead_stages;
+mod k8s_misconfigurations;
+mod root_user;
+
+use tower_lsp::lsp_types::{
+    Diagnostic, DiagnosticSeverity, DiagnosticTag, NumberOrString, Range, TextEdit,
+};
+
+use crate::infra::{parse_dockerfile_stages, parse_k8s_containers};
+
+use super::{LINT_DIAGNOSTIC_SOURCE, component_factory::LintConfig};
+
+/// Every rule, with whether it runs unless `sysdig.lint.rules` says otherwise.
+pub const LINT_RULES: &[(&str, bool)] = &[
+    (dead_stages::DEAD_STAGE_RULE, true),
+    (root_user::ROOT_USER_RULE, true),
+    // Images without `USER` run as the user of their base image, often an unprivileged one.
+    (root_user::MISSING_USER_RULE, false),
+    (best_practices::LATEST_TAG_RULE, true),
+    (best_practices::APT_NO_INSTALL_RECOMMENDS_RULE, true),
+    (best_practices::APT_CACHE_NOT_CLEANED_RULE, true),
+    (best_practices::ADD_INSTEAD_OF_COPY_RULE, true),
+    (k8s_misconfigurations::RUN_AS_ROOT_RULE, true),
+    (k8s_misconfigurations::MISSING_RESOURCE_LIMITS_RULE, true),
+];
+
+/// A problem found by statically analyzing a document, without building or scanning anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintFinding {
+    /// Stable identifier of the rule, published as the diagnostic code.
+    pub rule: &'static str,
+    pub message: String,
+    pub range: Range,
+    pub severity: DiagnosticSeverity,
+    pub tags: Vec<DiagnosticTag>,
+    pub fix: Option<LintFix>,
+}
+
+/// Edits that resolve a finding, offered as a quick fix code action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintFix {
+    pub title: String,
+    pub edits: Vec<TextEdit>,
+}
+
+impl From<&LintFinding> for Diagnostic {
+    fn from(value: &LintFinding) -> Self {
+        Diagnostic {
+            range: value.range,
+            severity: Some(value.severity),
+            code: Some(NumberOrString::String(value.rule.to_string())),
+            source: Some(LINT_DIAGNOSTIC_SOURCE.to_owned()),
+            message: value.message.clone(),
+            tags: (!value.tags.is_empty()).then(|| value.tags.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn lint_dockerfile(content: &str, config: &LintConfig) -> Vec<LintFinding> {
+    let stages = parse_dockerfile_stages(content);
+
+    let mut findings = dead_stages::find_dead_stages(&stages);
+    findings.extend(root_user::find_root_user(&stages));
+    findings.extend(root_user::find_missing_user(&stages));
+    findings.extend(best_practices::find_best_practice_violations(
+        content, &stages,
+    ));
+    findings.retain(|finding| config.is_enabled(finding.rule));
+    findings
+}
+
+/// Misconfigurations of the containers of a K8s manifest. Manifests that aren't valid
+/// YAML, e.g. while being edited, have none.
+pub fn lint_k8s_manifest(content: &str, config: &LintConfig) -> Vec<LintFinding> {
+    let mut findings = parse_k8s_containers(content)
+        .map(|containers| k8s_misconfigurations::find_misconfigurations(&containers))
+        .unwrap_or_default();
+    findings.retain(|finding| config.is_enabled(finding.rule));
+    findings
+}
@@ -0,0 +1,172 @@
+use tower_lsp::lsp_types::{DiagnosticSeverity, Range, TextEdit};
+
+use crate::infra::{Instruction, Stage};
+
+use super::{LintFinding, LintFix};
+
+pub const ROOT_USER_RULE: &str = "root-user";
+pub const MISSING_USER_RULE: &str = "missing-user";
+
+/// Unprivileged UID the quick fix switches to, the one of distroless `nonroot` images.
+/// Numeric, so it doesn't need an entry in `/etc/passwd`.
+const NON_ROOT_UID: &str = "65532";
+
+/// Flags the final stage when its image runs as root because its last `USER` (or the
+/// one of the stages it's based on) is root. Stages without any `USER` run as the user
+/// of their base image, which can't be told without pulling it.
+pub fn find_root_user(stages: &[Stage]) -> Vec<LintFinding> {
+    let Some(final_stage) = stages.last() else {
+        return vec![];
+    };
+    let Some(user) = last_user(stages, final_stage).filter(|user| is_root(user)) else {
+        return vec![];
+    };
+
+    vec![LintFinding {
+        rule: ROOT_USER_RULE,
+        message:
+            "The image runs as root: switch to an unprivileged USER at the end of the final stage"
+                .to_string(),
+        range: user.range,
+        severity: DiagnosticSeverity::WARNING,
+        tags: vec![],
+        fix: Some(non_root_user_fix(final_stage)),
+    }]
+}
+
+/// Flags the `FROM` of the final stage when neither it nor the stages it's based on
+/// set a `USER`, so the image runs as whatever user its base image sets.
+pub fn find_missing_user(stages: &[Stage]) -> Vec<LintFinding> {
+    let Some(final_stage) = stages.last() else {
+        return vec![];
+    };
+    if last_user(stages, final_stage).is_some() {
+        return vec![];
+    }
+    let Some(from) = final_stage.instructions.first() else {
+        return vec![];
+    };
+
+    vec![LintFinding {
+        rule: MISSING_USER_RULE,
+        message: "The image sets no USER, so it runs as the user of its base image, root unless it says otherwise"
+            .to_string(),
+        range: from.range,
+        severity: DiagnosticSeverity::INFORMATION,
+        tags: vec![],
+        fix: Some(non_root_user_fix(final_stage)),
+    }]
+}
+
+fn non_root_user_fix(final_stage: &Stage) -> LintFix {
+    let end = final_stage.range().end;
+    LintFix {
+        title: format!("Run as the unprivileged user {NON_ROOT_UID}"),
+        // Appended, so the instructions of the stage keep running as before.
+        edits: vec![TextEdit {
+            range: Range::new(end, end),
+            new_text: format!("\nUSER {NON_ROOT_UID}"),
+        }],
+    }
+}
+
+/// Last `USER` of the stage, or of the stages it's (transitively) based on.
+fn last_user<'a>(stages: &'a [Stage], stage: &'a Stage) -> Option<&'a Instruction> {
+    let user = stage
+        .instructions
+        .iter()
+        .rev()
+        .find(|instruction| instruction.keyword.eq_ignore_ascii_case("USER"));
+    if user.is_some() {
+        return user;
+    }
+
+    let base = stage.base.as_deref()?;
+    stages[..stage.index]
+        .iter()
+        .rev()
+        .find(|previous| previous.is_referenced_by(base))
+        .and_then(|previous| last_user(stages, previous))
+}
+
+/// `USER root`, `USER 0` or with a group, e.g. `USER root:root`.
+fn is_root(user: &Instruction) -> bool {
+    let name = user
+        .arguments
+        .first()
+        .and_then(|argument| argument.split(':').next())
+        .unwrap_or_default();
+    name == "root" || name == "0"
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use crate::infra::parse_dockerfile_stages;
+
+    use super::{find_missing_user, find_root_user};
+
+    fn ranges(dockerfile: &str) -> Vec<Range> {
+        find_root_user(&parse_dockerfile_stages(dockerfile))
+            .into_iter()
+            .map(|finding| finding.range)
+            .collect()
+    }
+
+    #[test]
+    fn it_flags_images_whose_last_user_is_root() {
+        assert_eq!(
+            ranges("FROM alpine\nUSER app\nRUN apk add curl\nUSER root\n"),
+            [Range::new(Position::new(3, 0), Position::new(3, 9))]
+        );
+        assert_eq!(
+            ranges("FROM alpine AS base\nUSER 0:0\n\nFROM base\nCMD [\"sh\"]\n"),
+            [Range::new(Position::new(1, 0), Position::new(1, 8))]
+        );
+    }
+
+    #[test]
+    fn it_accepts_unprivileged_or_unset_users() {
+        assert!(ranges("FROM alpine\nUSER 1000\n").is_empty());
+        assert!(ranges("FROM alpine\nRUN apk add curl\n").is_empty());
+        assert!(
+            ranges("FROM alpine AS base\nUSER app\n\nFROM golang AS build\n\nFROM base\nCMD [\"app\"]\n")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn it_appends_an_unprivileged_user_to_the_final_stage() {
+        let findings = find_root_user(&parse_dockerfile_stages(
+            "FROM alpine\nUSER root\nRUN apk add curl\n",
+        ));
+
+        let edit = &findings[0].fix.as_ref().unwrap().edits[0];
+        assert_eq!(edit.new_text, "\nUSER 65532");
+        assert_eq!(edit.range.start, Position::new(2, 16));
+    }
+
+    #[test]
+    fn it_flags_final_stages_without_any_user() {
+        let stages = parse_dockerfile_stages(
+            "FROM alpine AS base\nRUN apk add curl\n\nFROM base\nCMD [\"sh\"]\n",
+        );
+
+        let findings = find_missing_user(&stages);
+
+        assert_eq!(
+            findings[0].range,
+            Range::new(Position::new(3, 0), Position::new(3, 9))
+        );
+        let edit = &findings[0].fix.as_ref().unwrap().edits[0];
+        assert_eq!(edit.new_text, "\nUSER 65532");
+        assert_eq!(edit.range.start, Position::new(4, 10));
+        assert!(
+            find_missing_user(&parse_dockerfile_stages(
+                "FROM alpine AS base\nUSER app\n\nFROM base\n"
+            ))
+            .is_empty()
+        );
+    }
+}
@@ -2,19 +2,31 @@ use std::fmt::Display;
 
 use tower_lsp::{
     Client as TowerClient,
-    lsp_types::{Diagnostic, MessageType, Url},
+    jsonrpc::Result,
+    lsp_types::{
+        ApplyWorkspaceEditResponse, Diagnostic, MessageType, Registration, Url, WorkspaceEdit,
+        notification::Notification,
+    },
 };
 use tracing::{error, info};
 
 #[async_trait::async_trait]
 pub trait LSPClient {
     async fn show_message<M: Display + Send>(&self, message_type: MessageType, message: M);
+    async fn log_message<M: Display + Send>(&self, message_type: MessageType, message: M);
     async fn publish_diagnostics(
         &self,
         url: &str,
         diagnostics: Vec<Diagnostic>,
         version: Option<i32>,
     );
+    async fn register_capability(&self, registrations: Vec<Registration>) -> Result<()>;
+    async fn apply_edit(&self, edit: WorkspaceEdit) -> Result<ApplyWorkspaceEditResponse>;
+    async fn code_lens_refresh(&self) -> Result<()>;
+    async fn workspace_diagnostic_refresh(&self) -> Result<()>;
+    async fn send_notification<N: Notification>(&self, params: N::Params)
+    where
+        N::Params: Send;
 }
 
 #[async_trait::async_trait]
@@ -23,6 +35,10 @@ impl LSPClient for TowerClient {
         TowerClient::show_message(self, message_type, message).await
     }
 
+    async fn log_message<M: Display + Send>(&self, message_type: MessageType, message: M) {
+        TowerClient::log_message(self, message_type, message).await
+    }
+
     async fn publish_diagnostics(
         &self,
         url: &str,
@@ -44,4 +60,27 @@ impl LSPClient for TowerClient {
             }
         }
     }
+
+    async fn register_capability(&self, registrations: Vec<Registration>) -> Result<()> {
+        TowerClient::register_capability(self, registrations).await
+    }
+
+    async fn apply_edit(&self, edit: WorkspaceEdit) -> Result<ApplyWorkspaceEditResponse> {
+        TowerClient::apply_edit(self, edit).await
+    }
+
+    async fn code_lens_refresh(&self) -> Result<()> {
+        TowerClient::code_lens_refresh(self).await
+    }
+
+    async fn workspace_diagnostic_refresh(&self) -> Result<()> {
+        TowerClient::workspace_diagnostic_refresh(self).await
+    }
+
+    async fn send_notification<N: Notification>(&self, params: N::Params)
+    where
+        N::Params: Send,
+    {
+        TowerClient::send_notification::<N>(self, params).await
+    }
 }
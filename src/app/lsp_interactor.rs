@@ -2,10 +2,28 @@ use std::collections::HashMap;
 
 use tower_lsp::{
     jsonrpc::Result,
-    lsp_types::{Diagnostic, MessageType, Position, Range},
+    lsp_types::{
+        ApplyWorkspaceEditResponse, Diagnostic, MessageType, Range, Registration, WorkspaceEdit,
+    },
 };
 
-use super::{DiagnosticsScope, InMemoryDocumentDatabase, LSPClient, VULN_DIAGNOSTIC_SOURCE};
+use tracing::{debug, warn};
+
+use super::protocol::{
+    ScanProgressNotification, ScanProgressParams, ScanQueueNotification, ScanQueueParams,
+    ScanResultNotification, ScanResultParams,
+};
+use super::{
+    BaseImageRecommendation, DiagnosticsScope, DocumentScanResult, Documentation,
+    InMemoryDocumentDatabase, LSPClient, POLICY_DIAGNOSTIC_SOURCE, ScanSnapshot,
+    ServerStatusNotification, ServerStatusParams, SnapshotDocumentation, StoredDocumentScanResult,
+    VULN_DIAGNOSTIC_SOURCE,
+};
+use crate::domain::scanresult::severity::Severity;
+
+/// Sources of the diagnostics produced by image scans, which share the lifecycle
+/// of the document text.
+const IMAGE_SCAN_SOURCES: [&str; 2] = [VULN_DIAGNOSTIC_SOURCE, POLICY_DIAGNOSTIC_SOURCE];
 
 #[derive(Clone)]
 pub struct LspInteractor<C> {
@@ -15,6 +33,11 @@ pub struct LspInteractor<C> {
     /// publish could send a stale snapshot after a clearing publish, and the
     /// prune would then drop the entry so no future publish self-heals it.
     publish_lock: std::sync::Arc<tokio::sync::Mutex<()>>,
+    /// Whether the client supports `workspace/codeLens/refresh`.
+    code_lens_refresh: bool,
+    /// Whether the client pulls the diagnostics (`textDocument/diagnostic`), asked to
+    /// pull them again (`workspace/diagnostic/refresh`) instead of having them published.
+    pull_diagnostics: bool,
 }
 
 impl<C> LspInteractor<C> {
@@ -23,8 +46,18 @@ impl<C> LspInteractor<C> {
             client,
             document_database,
             publish_lock: Default::default(),
+            code_lens_refresh: false,
+            pull_diagnostics: false,
         }
     }
+
+    pub fn set_code_lens_refresh_support(&mut self, supported: bool) {
+        self.code_lens_refresh = supported;
+    }
+
+    pub fn set_pull_diagnostics_support(&mut self, supported: bool) {
+        self.pull_diagnostics = supported;
+    }
 }
 
 impl<C> LspInteractor<C>
@@ -33,25 +66,104 @@ where
 {
     pub async fn update_document_with_text(&self, uri: &str, text: &str) {
         self.document_database.write_document_text(uri, text).await;
-        // Vulnerability diagnostics anchor to specific lines, so they go stale as soon
+        // Image scan diagnostics anchor to specific lines, so they go stale as soon
         // as the text changes. IaC diagnostics anchor to the top of the file and keep
         // being meaningful across edits, so they survive the document lifecycle.
+        for source in IMAGE_SCAN_SOURCES {
+            self.document_database
+                .replace_diagnostics_with_source(
+                    source,
+                    DiagnosticsScope::Document(uri),
+                    HashMap::new(),
+                )
+                .await;
+        }
+        self.document_database.remove_documentations(uri).await;
+        let _ = self.publish_all_diagnostics().await;
+    }
+
+    /// Publishes the diagnostics of the closed document as cleared, if
+    /// `clear_diagnostics`, and reclaims its entry.
+    pub async fn close_document(&self, uri: &str, clear_diagnostics: bool) {
         self.document_database
-            .replace_diagnostics_with_source(
-                VULN_DIAGNOSTIC_SOURCE,
-                DiagnosticsScope::Document(uri),
-                HashMap::new(),
-            )
+            .close_document(uri, clear_diagnostics)
             .await;
-        self.document_database.remove_documentations(uri).await;
         let _ = self.publish_all_diagnostics().await;
     }
 
+    /// Publishes the diagnostics of a renamed document, or of the documents of a renamed
+    /// folder, under their new URIs, and as cleared under the old ones. Returns the old
+    /// and new URIs of the documents moved.
+    pub async fn rename_document(&self, from: &str, to: &str) -> Vec<(String, String)> {
+        let renamed = self.document_database.rename_document(from, to).await;
+        let _ = self.publish_all_diagnostics().await;
+        renamed
+    }
+
     pub async fn show_message(&self, message_type: MessageType, message: &str) {
         self.client.show_message(message_type, message).await;
     }
 
+    pub async fn log_message(&self, message_type: MessageType, message: &str) {
+        self.client.log_message(message_type, message).await;
+    }
+
+    pub async fn register_capability(&self, registrations: Vec<Registration>) -> Result<()> {
+        self.client.register_capability(registrations).await
+    }
+
+    /// Asks the client to request the code lenses again, e.g. once a scan reported
+    /// the policy verdict shown in them. No-op if the client doesn't support it.
+    pub async fn refresh_code_lenses(&self) {
+        if !self.code_lens_refresh {
+            return;
+        }
+        if let Err(e) = self.client.code_lens_refresh().await {
+            warn!("unable to refresh the code lenses: {e}");
+        }
+    }
+
+    pub async fn apply_edit(&self, edit: WorkspaceEdit) -> Result<ApplyWorkspaceEditResponse> {
+        self.client.apply_edit(edit).await
+    }
+
+    pub async fn send_scan_result(&self, params: ScanResultParams) {
+        self.document_database
+            .write_image_scan(params.clone())
+            .await;
+        self.client
+            .send_notification::<ScanResultNotification>(params)
+            .await;
+    }
+
+    pub async fn send_scan_queue(&self, params: ScanQueueParams) {
+        self.client
+            .send_notification::<ScanQueueNotification>(params)
+            .await;
+    }
+
+    pub async fn send_scan_progress(&self, params: ScanProgressParams) {
+        self.client
+            .send_notification::<ScanProgressNotification>(params)
+            .await;
+    }
+
+    pub async fn send_server_status(&self, status: ServerStatusParams) {
+        self.client
+            .send_notification::<ServerStatusNotification>(status)
+            .await;
+    }
+
     pub async fn publish_all_diagnostics(&self) -> Result<()> {
+        // Clients pulling the diagnostics would show the published ones twice. Their
+        // entries aren't pruned either: emptied ones are still reported as cleared.
+        if self.pull_diagnostics {
+            if let Err(e) = self.client.workspace_diagnostic_refresh().await {
+                debug!("unable to ask the client to pull the diagnostics again: {e}");
+            }
+            return Ok(());
+        }
+
         let _guard = self.publish_lock.lock().await;
 
         let all_diagnostics: Vec<_> = self.document_database.all_diagnostics().await.collect();
@@ -82,6 +194,22 @@ where
         self.document_database.read_document_text(uri).await
     }
 
+    pub async fn write_document_language_id(&self, uri: &str, language_id: &str) {
+        self.document_database
+            .write_document_language_id(uri, language_id)
+            .await;
+    }
+
+    pub async fn read_document_language_id(&self, uri: &str) -> Option<String> {
+        self.document_database.read_document_language_id(uri).await
+    }
+
+    pub async fn read_diagnostics_with_source(&self, uri: &str, source: &str) -> Vec<Diagnostic> {
+        self.document_database
+            .read_diagnostics_with_sources(uri, &[source])
+            .await
+    }
+
     pub async fn replace_diagnostics_with_source(
         &self,
         source: &str,
@@ -93,18 +221,143 @@ where
             .await
     }
 
-    pub async fn append_documentation(&self, uri: &str, range: Range, documentation: String) {
+    pub async fn append_documentation(&self, uri: &str, documentation: Documentation) {
         self.document_database
-            .append_documentation(uri, range, documentation)
+            .append_documentation(uri, documentation)
             .await
     }
-    pub async fn read_documentation_at(&self, uri: &str, position: Position) -> Option<String> {
-        self.document_database
-            .read_documentation_at(uri, position)
-            .await
+
+    pub async fn read_documentations(&self, uri: &str) -> Vec<Documentation> {
+        self.document_database.read_documentations(uri).await
     }
 
     pub async fn remove_documentations(&self, uri: &str) {
         self.document_database.remove_documentations(uri).await
     }
+
+    pub async fn write_last_scan_result(&self, uri: &str, scan_result: DocumentScanResult) {
+        self.document_database
+            .write_last_scan_result(uri, scan_result)
+            .await
+    }
+
+    pub async fn read_last_scan_result(&self, uri: &str) -> Option<DocumentScanResult> {
+        self.document_database.read_last_scan_result(uri).await
+    }
+
+    pub async fn write_image_severity(&self, image: &str, severity: Option<Severity>) {
+        self.document_database
+            .write_image_severity(image, severity)
+            .await
+    }
+
+    pub async fn read_image_severities(&self) -> HashMap<String, Severity> {
+        self.document_database.read_image_severities().await
+    }
+
+    pub async fn read_image_scan(&self, image: &str) -> Option<ScanResultParams> {
+        self.document_database.read_image_scan(image).await
+    }
+
+    pub async fn write_base_image_recommendation(
+        &self,
+        image: &str,
+        recommendation: BaseImageRecommendation,
+    ) {
+        self.document_database
+            .write_base_image_recommendation(image, recommendation)
+            .await
+    }
+
+    pub async fn read_base_image_recommendation(
+        &self,
+        image: &str,
+    ) -> Option<BaseImageRecommendation> {
+        self.document_database
+            .read_base_image_recommendation(image)
+            .await
+    }
+
+    pub async fn extend_documentation(&self, uri: &str, range: Range, content: &str) {
+        self.document_database
+            .extend_documentation(uri, range, content)
+            .await
+    }
+
+    /// Image scan results currently held for the document, tied to its text.
+    pub async fn scan_snapshot(&self, uri: &str) -> Option<ScanSnapshot> {
+        let text = self.document_database.read_document_text(uri).await?;
+        let diagnostics = self
+            .document_database
+            .read_diagnostics_with_sources(uri, &IMAGE_SCAN_SOURCES)
+            .await;
+        let documentations = self
+            .document_database
+            .read_documentations(uri)
+            .await
+            .into_iter()
+            .map(|documentation| SnapshotDocumentation {
+                range: documentation.range,
+                content: documentation.content,
+                anchors: documentation.anchors,
+                verdict: documentation.verdict,
+                scanned_at: documentation.scanned_at,
+            })
+            .collect();
+
+        let scan_result = self
+            .document_database
+            .read_last_scan_result(uri)
+            .await
+            .map(|scan_result| StoredDocumentScanResult::from(&scan_result));
+
+        Some(ScanSnapshot {
+            content_hash: ScanSnapshot::content_hash_of(&text),
+            diagnostics,
+            documentations,
+            scan_result,
+        })
+    }
+
+    /// Replaces the image scan results of the document with the snapshot ones and
+    /// publishes them. The caller is responsible for checking the snapshot matches
+    /// the document content.
+    pub async fn restore_scan_snapshot(&self, uri: &str, snapshot: ScanSnapshot) -> Result<()> {
+        let snapshot = snapshot.with_current_sources();
+        for source in IMAGE_SCAN_SOURCES {
+            let diagnostics = snapshot
+                .diagnostics
+                .iter()
+                .filter(|diag| diag.source.as_deref() == Some(source))
+                .cloned()
+                .collect();
+            self.document_database
+                .replace_diagnostics_with_source(
+                    source,
+                    DiagnosticsScope::Document(uri),
+                    HashMap::from([(uri.to_owned(), diagnostics)]),
+                )
+                .await;
+        }
+
+        self.document_database.remove_documentations(uri).await;
+        for documentation in snapshot.documentations {
+            self.document_database
+                .append_documentation(
+                    uri,
+                    Documentation::new(documentation.range, documentation.content)
+                        .with_anchors(documentation.anchors)
+                        .with_verdict(documentation.verdict)
+                        .with_scanned_at(documentation.scanned_at),
+                )
+                .await;
+        }
+        if let Some(scan_result) = snapshot.scan_result {
+            self.document_database
+                .write_last_scan_result(uri, scan_result.into())
+                .await;
+        }
+
+        self.publish_all_diagnostics().await
+    }
 }
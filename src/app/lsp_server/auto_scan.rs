@@ -0,0 +1,92 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::sync::Mutex;
+use tower_lsp::lsp_types::Url;
+use tracing::debug;
+
+use super::{LSPClient, lsp_server_inner::CommandExecutor, supported_commands::SupportedCommands};
+
+/// How long a document must go without being saved again before it's re-scanned.
+pub const AUTO_SCAN_DEBOUNCE: Duration = Duration::from_millis(1500);
+
+struct ScannedDocument {
+    /// Last scan command run on the document.
+    command: SupportedCommands,
+    /// Bumped on every save, so only the task of the latest save scans.
+    generation: u64,
+    /// Held while the document is being re-scanned, so scans never overlap.
+    scanning: Arc<Mutex<()>>,
+}
+
+/// Remembers the last scan command run on each document, to run it again when the
+/// document is saved (`sysdig.auto_scan.on_save`).
+///
+/// Saves are debounced: a burst of saves runs a single scan, and a save arriving
+/// while a scan is running waits for it instead of starting another one.
+#[derive(Clone, Default)]
+pub struct AutoScanner {
+    /// Keyed by document URI.
+    documents: Arc<Mutex<HashMap<String, ScannedDocument>>>,
+}
+
+impl AutoScanner {
+    pub async fn record(&self, uri: &Url, command: SupportedCommands) {
+        let mut documents = self.documents.lock().await;
+        match documents.get_mut(uri.as_str()) {
+            Some(document) => document.command = command,
+            None => {
+                documents.insert(
+                    uri.to_string(),
+                    ScannedDocument {
+                        command,
+                        generation: 0,
+                        scanning: Arc::default(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Stops re-scanning the document on save, e.g. because it was closed. A scan
+    /// already scheduled for it is skipped.
+    pub async fn forget_document(&self, uri: &str) {
+        self.documents.lock().await.remove(uri);
+    }
+
+    /// Re-runs the last scan of the document once it stops being saved for `debounce`.
+    /// Documents that weren't scanned yet are left alone.
+    pub async fn schedule<C>(&self, uri: Url, executor: CommandExecutor<C>, debounce: Duration)
+    where
+        C: LSPClient + Send + Sync + 'static,
+    {
+        let (generation, scanning) = {
+            let mut documents = self.documents.lock().await;
+            let Some(document) = documents.get_mut(uri.as_str()) else {
+                return;
+            };
+            document.generation += 1;
+            (document.generation, document.scanning.clone())
+        };
+
+        let auto_scanner = self.clone();
+        let running_commands = executor.running_commands().clone();
+        running_commands.spawn(async move {
+            tokio::time::sleep(debounce).await;
+            let _scanning = scanning.lock().await;
+
+            let command = auto_scanner
+                .documents
+                .lock()
+                .await
+                .get(uri.as_str())
+                .filter(|document| document.generation == generation)
+                .map(|document| document.command.clone());
+            let Some(command) = command else {
+                debug!("skipping auto scan of {uri}: superseded by a later save");
+                return;
+            };
+
+            executor.rescan(&uri, command).await;
+        });
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, Location, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::app::{BaseImageRecommendation, protocol::SeverityCounts};
+
+use super::command_generator::CommandInfo;
+use super::commands::scan_base_image::{IMAGE_DATA_KEY, VULNERABILITIES_DATA_KEY};
+use super::pin_image_digest::image_range;
+use super::supported_commands::SupportedCommands;
+
+/// Image scanned for the summary diagnostic, if it reported Critical or High
+/// vulnerabilities: the ones worth looking for a less vulnerable alternative to. Told
+/// from the counts in its data, as `sysdig.diagnostics.severity_mapping` may have given
+/// the summary any severity.
+pub fn vulnerable_image(diagnostic: &Diagnostic) -> Option<&str> {
+    let data = diagnostic.data.as_ref()?;
+    let counts = SeverityCounts::deserialize(data.get(VULNERABILITIES_DATA_KEY)?).ok()?;
+    if counts.critical + counts.high == 0 {
+        return None;
+    }
+    data.get(IMAGE_DATA_KEY)?.as_str()
+}
+
+/// Quick fix for the summary diagnostic of a vulnerable image: runs
+/// `sysdig-lsp.recommend-base-image` until alternatives were evaluated, and then
+/// switches the reference to the best of them, if any is less vulnerable.
+pub fn base_image_alternative_action(
+    uri: &Url,
+    content: &str,
+    diagnostic: &Diagnostic,
+    recommendation: Option<&BaseImageRecommendation>,
+) -> Option<CodeActionOrCommand> {
+    let image = vulnerable_image(diagnostic)?;
+
+    let Some(recommendation) = recommendation else {
+        let command = CommandInfo::from(SupportedCommands::RecommendBaseImage {
+            location: Location::new(uri.clone(), diagnostic.range),
+            image: image.to_string(),
+        });
+        return Some(CodeActionOrCommand::CodeAction(CodeAction {
+            title: command.title.clone(),
+            kind: Some(CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            command: Some(command.into()),
+            ..Default::default()
+        }));
+    };
+
+    let best = recommendation.best()?;
+    let range = image_range(content, diagnostic.range, image)?;
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Switch base image to {}", best.image),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(
+                uri.clone(),
+                vec![TextEdit::new(range, best.image.clone())],
+            )])),
+            ..Default::default()
+        }),
+        is_preferred: Some(true),
+        ..Default::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Utc};
+    use serde_json::json;
+    use tower_lsp::lsp_types::{DiagnosticSeverity, Position, Range};
+
+    use super::*;
+    use crate::app::SysdigIgnore;
+    use crate::app::base_image_recommender::BaseImageEvaluation;
+    use crate::app::component_factory::{DiagnosticLevel, SeverityMapping};
+    use crate::app::lsp_server::commands::{
+        diagnostics_factory::DiagnosticsFactory, scan_base_image::vulnerability_diagnostic,
+    };
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        scan_result::ScanResult,
+        scan_type::ScanType,
+        severity::Severity,
+    };
+
+    fn diagnostic(high: usize) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(0, 0), Position::new(0, 16)),
+            severity: Some(DiagnosticSeverity::ERROR),
+            data: Some(json!({
+                IMAGE_DATA_KEY: "node:18",
+                VULNERABILITIES_DATA_KEY: SeverityCounts { high, ..Default::default() },
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn scan_result(severity: Severity) -> ScanResult {
+        let mut result = ScanResult::new(
+            ScanType::Docker,
+            "node:18".to_string(),
+            "sha256:12345".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "debian:12".to_string()),
+            123456,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Passed,
+        );
+        result.add_vulnerability(
+            "CVE-2024-0001".to_string(),
+            severity,
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            None,
+            false,
+            None,
+            None,
+            None,
+        );
+        result
+    }
+
+    fn evaluation(image: &str, high: usize) -> BaseImageEvaluation {
+        BaseImageEvaluation {
+            image: image.to_string(),
+            vulnerabilities: SeverityCounts {
+                high,
+                ..Default::default()
+            },
+            size_in_bytes: 100,
+        }
+    }
+
+    fn uri() -> Url {
+        "file:///Dockerfile".parse().unwrap()
+    }
+
+    #[test]
+    fn it_looks_for_alternatives_to_vulnerable_images_only() {
+        let content = "FROM node:18 AS app\n";
+
+        let Some(CodeActionOrCommand::CodeAction(action)) =
+            base_image_alternative_action(&uri(), content, &diagnostic(1), None)
+        else {
+            panic!("expected a code action");
+        };
+        assert_eq!(
+            action.command.map(|c| c.command).as_deref(),
+            Some("sysdig-lsp.recommend-base-image")
+        );
+
+        assert!(base_image_alternative_action(&uri(), content, &diagnostic(0), None).is_none());
+    }
+
+    #[test]
+    fn it_switches_to_the_best_alternative_once_evaluated() {
+        let content = "FROM node:18 AS app\n";
+        let recommendation = BaseImageRecommendation {
+            current: evaluation("node:18", 5),
+            candidates: vec![evaluation("node:22", 1)],
+        };
+
+        let Some(CodeActionOrCommand::CodeAction(action)) =
+            base_image_alternative_action(&uri(), content, &diagnostic(1), Some(&recommendation))
+        else {
+            panic!("expected a code action");
+        };
+
+        assert_eq!(action.title, "Switch base image to node:22");
+        let edits = &action.edit.unwrap().changes.unwrap()[&uri()];
+        assert_eq!(
+            edits,
+            &vec![TextEdit::new(
+                Range::new(Position::new(0, 5), Position::new(0, 12)),
+                "node:22".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn it_offers_nothing_when_no_alternative_is_better() {
+        let recommendation = BaseImageRecommendation {
+            current: evaluation("node:18", 1),
+            candidates: vec![evaluation("node:22", 3)],
+        };
+
+        let action = base_image_alternative_action(
+            &uri(),
+            "FROM node:18\n",
+            &diagnostic(1),
+            Some(&recommendation),
+        );
+
+        assert!(action.is_none());
+    }
+
+    #[test]
+    fn it_tells_vulnerable_images_by_their_counts_whatever_the_severity_mapping() {
+        let sysdig_ignore = SysdigIgnore::default();
+        let mapping = SeverityMapping {
+            high: DiagnosticLevel::Hint,
+            medium: DiagnosticLevel::Error,
+            ..Default::default()
+        };
+        let diagnostics = DiagnosticsFactory::new(&sysdig_ignore, Some(mapping));
+        let summary = |severity| {
+            vulnerability_diagnostic(
+                Range::default(),
+                "node:18",
+                &scan_result(severity),
+                &diagnostics,
+            )
+        };
+
+        let high = summary(Severity::High);
+        assert_eq!(high.severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(vulnerable_image(&high), Some("node:18"));
+
+        let medium = summary(Severity::Medium);
+        assert_eq!(medium.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(vulnerable_image(&medium), None);
+    }
+}
@@ -1,8 +1,22 @@
+use std::path::{Component, Path, PathBuf};
+
+use itertools::Itertools;
 use serde_json::{Value, json};
-use tower_lsp::lsp_types::{CodeLens, Command, Location, Range, Url};
+use tower_lsp::lsp_types::{CodeLens, Command, Location, Position, Range, Url};
 
+use crate::app::PolicyVerdict;
 use crate::app::lsp_server::supported_commands::SupportedCommands;
-use crate::infra::{parse_compose_file, parse_dockerfile, parse_k8s_manifest};
+use crate::domain::image_reference::is_image_platform;
+use crate::infra::{
+    BuildInstruction, EffectiveImage, KUSTOMIZATION_FILE_NAMES, find_compose_override,
+    find_kustomization, kustomization_resource_images, merge_compose_override,
+    parse_circleci_config, parse_compose_builds, parse_compose_file, parse_dockerfile,
+    parse_dockerfile_stages, parse_github_workflow, parse_gitlab_ci, parse_k8s_manifest,
+    parse_kustomization,
+};
+
+/// Language ids clients open Dockerfiles with.
+const DOCKERFILE_LANGUAGE_IDS: [&str; 2] = ["dockerfile", "containerfile"];
 
 pub struct CommandInfo {
     pub title: String,
@@ -14,26 +28,214 @@ pub struct CommandInfo {
 impl From<SupportedCommands> for CommandInfo {
     fn from(value: SupportedCommands) -> Self {
         match &value {
-            SupportedCommands::ExecuteBaseImageScan { location, image } => CommandInfo {
-                title: "Scan base image".to_owned(),
+            SupportedCommands::ExecuteBaseImageScan {
+                location,
+                image,
+                platform,
+            } => CommandInfo {
+                title: match platform {
+                    Some(platform) => format!("Scan base image ({platform})"),
+                    None => "Scan base image".to_owned(),
+                },
                 command: value.as_string_command(),
-                arguments: Some(vec![json!(location), json!(image)]),
+                arguments: Some(
+                    [json!(location), json!(image)]
+                        .into_iter()
+                        .chain(platform.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
                 range: location.range,
             },
 
-            SupportedCommands::ExecuteBuildAndScan { location } => CommandInfo {
-                title: "Build and scan".to_owned(),
+            SupportedCommands::ExecuteBuildAndScan { location, target } => CommandInfo {
+                title: match target {
+                    Some(_) => "Build and scan this stage".to_owned(),
+                    None => "Build and scan".to_owned(),
+                },
                 command: value.as_string_command(),
-                arguments: Some(vec![json!(location)]),
+                arguments: Some(
+                    std::iter::once(json!(location))
+                        .chain(target.as_ref().map(|t| json!(t)))
+                        .collect(),
+                ),
                 range: location.range,
             },
 
+            SupportedCommands::ScanImageUnderCursor { uri, position } => CommandInfo {
+                title: "Scan image under cursor".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(vec![json!(uri), json!(position)]),
+                range: Range::new(*position, *position),
+            },
+
+            SupportedCommands::ScanAllImages { uri } => CommandInfo {
+                title: "Scan all images in file".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(vec![json!(uri)]),
+                range: Range::default(),
+            },
+
             SupportedCommands::ExecuteIacScan { uri } => CommandInfo {
                 title: "Scan IaC file".to_owned(),
                 command: value.as_string_command(),
                 arguments: uri.as_ref().map(|u| vec![json!(u)]),
                 range: Range::default(),
             },
+
+            SupportedCommands::ExportScanResult { uri, path } => CommandInfo {
+                title: "Export scan result".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(
+                    std::iter::once(json!(uri))
+                        .chain(path.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
+                range: Range::default(),
+            },
+
+            SupportedCommands::ExportSarif { uri, path } => CommandInfo {
+                title: "Export SARIF report".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(
+                    std::iter::once(json!(uri))
+                        .chain(path.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
+                range: Range::default(),
+            },
+
+            SupportedCommands::ExportLicenses { uri, path } => CommandInfo {
+                title: "Export licenses".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(
+                    std::iter::once(json!(uri))
+                        .chain(path.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
+                range: Range::default(),
+            },
+
+            SupportedCommands::GenerateSbom { uri, format, path } => CommandInfo {
+                title: "Generate SBOM".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(
+                    [json!(uri), json!(format.as_str())]
+                        .into_iter()
+                        .chain(path.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
+                range: Range::default(),
+            },
+
+            SupportedCommands::GenerateSysdigIgnore { uri, path } => CommandInfo {
+                title: "Ignore current findings".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(
+                    std::iter::once(json!(uri))
+                        .chain(path.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
+                range: Range::default(),
+            },
+
+            SupportedCommands::PinImageDigest { location, image } => CommandInfo {
+                title: "Pin to digest".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(vec![json!(location), json!(image)]),
+                range: location.range,
+            },
+
+            SupportedCommands::RecommendBaseImage { location, image } => CommandInfo {
+                title: "Find a less vulnerable base image".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(vec![json!(location), json!(image)]),
+                range: location.range,
+            },
+
+            SupportedCommands::RequestAcceptedRisk { uri, cve, package } => CommandInfo {
+                title: match package {
+                    Some(package) => format!(
+                        "Request accepted risk in Sysdig for {cve} in {} {}",
+                        package.name, package.version
+                    ),
+                    None => format!("Request accepted risk in Sysdig for {cve}"),
+                },
+                command: value.as_string_command(),
+                arguments: Some(
+                    [json!(uri), json!(cve)]
+                        .into_iter()
+                        .chain(package.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
+                range: Range::default(),
+            },
+
+            SupportedCommands::ScanDirectory { uri } => CommandInfo {
+                title: "Scan directory packages".to_owned(),
+                command: value.as_string_command(),
+                arguments: uri.as_ref().map(|u| vec![json!(u)]),
+                range: Range::default(),
+            },
+
+            SupportedCommands::CleanupTempImages => CommandInfo {
+                title: "Clean up temporary images".to_owned(),
+                command: value.as_string_command(),
+                arguments: None,
+                range: Range::default(),
+            },
+
+            SupportedCommands::ListImages => CommandInfo {
+                title: "List workspace images".to_owned(),
+                command: value.as_string_command(),
+                arguments: None,
+                range: Range::default(),
+            },
+
+            SupportedCommands::ShowAllLayerFindings { uri, position } => CommandInfo {
+                title: "Show all layer findings".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(vec![json!(uri), json!(position)]),
+                range: Range::new(*position, *position),
+            },
+
+            SupportedCommands::ShowScanReport { uri, position } => CommandInfo {
+                title: "View scan report".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(
+                    std::iter::once(json!(uri))
+                        .chain(position.as_ref().map(|p| json!(p)))
+                        .collect(),
+                ),
+                range: position.map(|p| Range::new(p, p)).unwrap_or_default(),
+            },
+
+            SupportedCommands::OpenScanInBrowser { uri } => CommandInfo {
+                title: "Open in Sysdig Secure".to_owned(),
+                command: value.as_string_command(),
+                arguments: Some(vec![json!(uri)]),
+                range: Range::default(),
+            },
+
+            SupportedCommands::AcceptRisks {
+                uri,
+                cves,
+                reason,
+                description,
+                expiration_date,
+            } => CommandInfo {
+                title: format!("Accept the risk of {} vulnerabilities", cves.len()),
+                command: value.as_string_command(),
+                arguments: Some(vec![
+                    json!(uri),
+                    json!({
+                        "cves": cves,
+                        "reason": reason.as_str(),
+                        "description": description,
+                        "expirationDate": expiration_date,
+                    }),
+                ]),
+                range: Range::default(),
+            },
         }
     }
 }
@@ -62,36 +264,403 @@ impl From<CommandInfo> for CodeLens {
     }
 }
 
-pub fn generate_commands_for_uri(uri: &Url, content: &str) -> Vec<CommandInfo> {
-    let file_uri = uri.as_str();
+/// An image pull string referenced in a document, with the range where it appears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageReference {
+    pub image: String,
+    pub range: Range,
+    /// Platform the document pulls the image for, e.g. `linux/arm64` for
+    /// `FROM --platform=linux/arm64 alpine`.
+    pub platform: Option<String>,
+}
+
+/// Kind of a document, deciding the features it gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    /// GitHub Actions workflows, GitLab CI and CircleCI configurations.
+    CiConfig,
+    Compose,
+    /// `kustomization.yaml` files, whose `images:` override the ones of their resources.
+    Kustomization,
+    K8sManifest,
+    Dockerfile,
+    /// Any other document the client sent, e.g. a text file opened in the editor.
+    Unsupported,
+}
+
+impl DocumentKind {
+    /// Detects the kind by file name, looking at the contents of YAML files to tell K8s
+    /// manifests apart. Dockerfiles with unconventional names (e.g. untitled buffers)
+    /// are recognized by the language id the client opened them with.
+    pub fn detect(uri: &Url, content: &str, language_id: Option<&str>) -> Self {
+        let file_uri = uri.as_str();
+        if is_ci_config_file(file_uri) {
+            DocumentKind::CiConfig
+        } else if is_compose_file(file_uri) {
+            DocumentKind::Compose
+        } else if is_kustomization_file(uri) {
+            DocumentKind::Kustomization
+        } else if is_k8s_manifest_file(file_uri, content) {
+            DocumentKind::K8sManifest
+        } else if is_dockerfile_name(uri)
+            || language_id.is_some_and(|id| {
+                DOCKERFILE_LANGUAGE_IDS
+                    .iter()
+                    .any(|dockerfile_id| id.eq_ignore_ascii_case(dockerfile_id))
+            })
+        {
+            DocumentKind::Dockerfile
+        } else {
+            DocumentKind::Unsupported
+        }
+    }
+}
+
+pub fn generate_commands_for_uri(uri: &Url, content: &str, kind: DocumentKind) -> Vec<CommandInfo> {
+    match kind {
+        DocumentKind::CiConfig => match ci_config_images(uri.as_str(), content) {
+            Some(images) => generate_ci_config_commands(uri, images),
+            None => vec![],
+        },
+        DocumentKind::Compose => generate_compose_commands(uri, content),
+        DocumentKind::Kustomization => {
+            generate_image_commands(uri, kustomization_images(uri, content))
+        }
+        DocumentKind::K8sManifest => {
+            generate_image_commands(uri, k8s_manifest_images(uri, content))
+        }
+        DocumentKind::Dockerfile => generate_dockerfile_commands(uri, content),
+        DocumentKind::Unsupported => vec![],
+    }
+}
+
+/// Lens shown over a scanned image with its policy verdict, opening its scan report.
+/// `scanned_ago` tells how old the result is, e.g. `2 days ago`.
+pub fn policy_verdict_command(
+    uri: &Url,
+    range: Range,
+    verdict: PolicyVerdict,
+    scanned_ago: Option<&str>,
+) -> CommandInfo {
+    let verdict_title = match (verdict.failed, verdict.failed_policies) {
+        (false, _) => "Policy: PASSED".to_owned(),
+        (true, 0) => "Policy: FAILED".to_owned(),
+        (true, 1) => "Policy: FAILED (1 policy)".to_owned(),
+        (true, failed) => format!("Policy: FAILED ({failed} policies)"),
+    };
+    let show_report = SupportedCommands::ShowScanReport {
+        uri: uri.clone(),
+        position: Some(range.start),
+    };
+
+    let age = scanned_ago.map_or_else(String::new, |ago| format!(" (scanned {ago})"));
+
+    CommandInfo {
+        title: format!("{verdict_title} — View details{age}"),
+        range,
+        ..show_report.into()
+    }
+}
+
+/// Re-runs the scan `scan` of an image whose shown result was scanned `scanned_ago`.
+pub fn rescan_command(scan: &CommandInfo, scanned_ago: &str) -> CommandInfo {
+    CommandInfo {
+        title: format!("{} again (scanned {scanned_ago})", scan.title),
+        command: scan.command.clone(),
+        arguments: scan.arguments.clone(),
+        range: scan.range,
+    }
+}
+
+/// Finds the image reference closest to `position`: the one on the cursor line if any,
+/// otherwise the nearest one, preferring references above the cursor on ties.
+pub fn find_image_reference_near(
+    uri: &Url,
+    content: &str,
+    kind: DocumentKind,
+    position: Position,
+) -> Option<ImageReference> {
+    image_references_for_uri(uri, content, kind)
+        .into_iter()
+        .min_by_key(|reference| {
+            let (start, end) = (reference.range.start.line, reference.range.end.line);
+            if (start..=end).contains(&position.line) {
+                (0, 0)
+            } else if end < position.line {
+                (position.line - end, 0)
+            } else {
+                (start - position.line, 1)
+            }
+        })
+}
+
+/// Every image referenced in the document, in document order.
+pub fn image_references_for_uri(
+    uri: &Url,
+    content: &str,
+    kind: DocumentKind,
+) -> Vec<ImageReference> {
+    let parsed = match kind {
+        DocumentKind::CiConfig => match ci_config_images(uri.as_str(), content) {
+            Some(images) => images,
+            None => return vec![],
+        },
+        DocumentKind::Compose => compose_images(uri, content).map(|images| {
+            images
+                .into_iter()
+                .map(|i| (i.image_name, i.range))
+                .collect::<Vec<_>>()
+        }),
+        DocumentKind::Kustomization => kustomization_images(uri, content).map(|images| {
+            images
+                .into_iter()
+                .map(|i| (i.image_name, i.range))
+                .collect::<Vec<_>>()
+        }),
+        DocumentKind::K8sManifest => k8s_manifest_images(uri, content).map(|images| {
+            images
+                .into_iter()
+                .map(|i| (i.image_name, i.range))
+                .collect::<Vec<_>>()
+        }),
+        DocumentKind::Dockerfile => return dockerfile_image_references(content),
+        DocumentKind::Unsupported => return vec![],
+    };
+
+    match parsed {
+        Ok(images) => images
+            .into_iter()
+            .map(|(image, range)| ImageReference {
+                image,
+                range,
+                platform: None,
+            })
+            .collect(),
+        Err(err) => {
+            tracing::warn!("unable to extract image references: {err}");
+            vec![]
+        }
+    }
+}
+
+/// Text of every comment of the document, without the leading `#`.
+pub fn document_comments(content: &str, kind: DocumentKind) -> Vec<String> {
+    if kind == DocumentKind::Dockerfile {
+        return parse_dockerfile(content)
+            .into_iter()
+            .filter_map(|instruction| instruction.comment)
+            .collect();
+    }
+
+    // YAML comments start with a `#` at the start of the line or after whitespace.
+    content
+        .lines()
+        .filter_map(|line| {
+            line.char_indices()
+                .find(|&(i, c)| {
+                    c == '#' && line[..i].chars().last().is_none_or(char::is_whitespace)
+                })
+                .map(|(i, _)| line[i + 1..].trim().to_string())
+        })
+        .collect()
+}
+
+/// Every FROM instruction pulling an actual image, i.e. skipping `scratch` and
+/// references to earlier build stages.
+fn dockerfile_image_references(content: &str) -> Vec<ImageReference> {
+    let mut stage_names: Vec<String> = vec![];
+    let mut references = vec![];
+
+    for instruction in parse_dockerfile(content) {
+        if instruction.keyword != "FROM" {
+            continue;
+        }
+
+        let mut arguments = instruction
+            .arguments
+            .iter()
+            .filter(|arg| !arg.starts_with("--"));
+        let Some(image) = arguments.next() else {
+            continue;
+        };
+        if !image.eq_ignore_ascii_case("scratch")
+            && !stage_names.iter().any(|s| s.eq_ignore_ascii_case(image))
+        {
+            references.push(ImageReference {
+                image: image.to_owned(),
+                range: instruction.range,
+                platform: from_platform(&instruction.arguments),
+            });
+        }
+        if let (Some(as_keyword), Some(stage_name)) = (arguments.next(), arguments.next())
+            && as_keyword.eq_ignore_ascii_case("AS")
+        {
+            stage_names.push(stage_name.to_owned());
+        }
+    }
+
+    references
+}
+
+/// Platform of the `--platform` flag of a FROM instruction, unless it's left to a build
+/// argument such as `$BUILDPLATFORM`.
+fn from_platform(arguments: &[String]) -> Option<String> {
+    arguments
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--platform="))
+        .filter(|platform| is_image_platform(platform))
+        .map(str::to_owned)
+}
+
+/// `Dockerfile`, `Containerfile`, and variants like `Dockerfile.dev`, `app.dockerfile`
+/// or `app.containerfile`, matching `SUPPORTED_FILE_PATTERNS`.
+pub(super) fn is_dockerfile_name(uri: &Url) -> bool {
+    let Some(file_name) = uri
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+    else {
+        return false;
+    };
+    let file_name = file_name.to_ascii_lowercase();
+    ["dockerfile", "containerfile"].iter().any(|name| {
+        file_name == *name
+            || file_name.starts_with(&format!("{name}."))
+            || file_name.ends_with(&format!(".{name}"))
+    })
+}
+
+pub(super) fn is_yaml_file(file_uri: &str) -> bool {
+    file_uri.ends_with(".yml") || file_uri.ends_with(".yaml")
+}
+
+fn is_github_workflow_file(file_uri: &str) -> bool {
+    file_uri.contains("/.github/workflows/") && is_yaml_file(file_uri)
+}
 
-    if file_uri.contains("docker-compose.yml")
+fn is_gitlab_ci_file(file_uri: &str) -> bool {
+    file_uri.ends_with(".gitlab-ci.yml") || file_uri.ends_with(".gitlab-ci.yaml")
+}
+
+fn is_circleci_config_file(file_uri: &str) -> bool {
+    file_uri.ends_with("/.circleci/config.yml") || file_uri.ends_with("/.circleci/config.yaml")
+}
+
+fn is_ci_config_file(file_uri: &str) -> bool {
+    is_github_workflow_file(file_uri)
+        || is_gitlab_ci_file(file_uri)
+        || is_circleci_config_file(file_uri)
+}
+
+/// Images pulled by the CI configuration (GitHub Actions, GitLab CI or CircleCI),
+/// `None` if the document isn't one.
+fn ci_config_images(file_uri: &str, content: &str) -> Option<Result<Vec<(String, Range)>, String>> {
+    let images = if is_github_workflow_file(file_uri) {
+        parse_github_workflow(content)
+            .map(|instructions| {
+                instructions
+                    .into_iter()
+                    .map(|i| (i.image_name, i.range))
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|e| e.to_string())
+    } else if is_gitlab_ci_file(file_uri) {
+        parse_gitlab_ci(content)
+            .map(|instructions| {
+                instructions
+                    .into_iter()
+                    .map(|i| (i.image_name, i.range))
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|e| e.to_string())
+    } else if is_circleci_config_file(file_uri) {
+        parse_circleci_config(content)
+            .map(|instructions| {
+                instructions
+                    .into_iter()
+                    .map(|i| (i.image_name, i.range))
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|e| e.to_string())
+    } else {
+        return None;
+    };
+    Some(images)
+}
+
+fn generate_ci_config_commands(
+    url: &Url,
+    images: Result<Vec<(String, Range)>, String>,
+) -> Vec<CommandInfo> {
+    // CI configs aren't supported by the IaC scanner, so only their images are scanned.
+    match images {
+        Ok(images) => images
+            .into_iter()
+            .map(|(image, range)| {
+                SupportedCommands::ExecuteBaseImageScan {
+                    location: Location::new(url.clone(), range),
+                    image,
+                    platform: None,
+                }
+                .into()
+            })
+            .collect(),
+        Err(err) => {
+            tracing::warn!("unable to generate image scan commands: {err}");
+            vec![]
+        }
+    }
+}
+
+pub(super) fn is_compose_file(file_uri: &str) -> bool {
+    file_uri.contains("docker-compose.yml")
         || file_uri.contains("compose.yml")
         || file_uri.contains("docker-compose.yaml")
         || file_uri.contains("compose.yaml")
-    {
-        generate_compose_commands(uri, content)
-    } else if is_k8s_manifest_file(file_uri, content) {
-        generate_k8s_manifest_commands(uri, content)
-    } else {
-        generate_dockerfile_commands(uri, content)
-    }
+        || file_uri.contains("compose.override.yml")
+        || file_uri.contains("compose.override.yaml")
 }
 
 fn generate_compose_commands(url: &Url, content: &str) -> Vec<CommandInfo> {
+    let mut commands = generate_image_commands(url, compose_images(url, content));
+    // Invalid YAML was already reported by `generate_image_commands`.
+    let builds = parse_compose_builds(content).unwrap_or_default();
+    commands.extend(
+        builds
+            .into_iter()
+            .filter_map(|build| compose_build_command_for(url, build)),
+    );
+
+    commands
+}
+
+/// IaC scan lens of a YAML document, plus a scan lens per image and one scanning all of
+/// them when there are several.
+fn generate_image_commands(
+    url: &Url,
+    images: Result<Vec<EffectiveImage>, String>,
+) -> Vec<CommandInfo> {
     // The IaC scan doesn't need parseable image instructions: the CLI scanner
     // parses the file itself, so the lens is offered even if image parsing fails.
     let mut commands = vec![iac_scan_command_for(url)];
-    match parse_compose_file(content) {
-        Ok(instructions) => {
-            for instruction in instructions {
-                commands.push(
-                    SupportedCommands::ExecuteBaseImageScan {
-                        location: Location::new(url.clone(), instruction.range),
-                        image: instruction.image_name,
-                    }
-                    .into(),
-                );
+    match images {
+        Ok(images) => {
+            if images.len() > 1 {
+                commands.push(scan_all_images_command_for(url));
+            }
+            for image in images {
+                let command: CommandInfo = SupportedCommands::ExecuteBaseImageScan {
+                    location: Location::new(url.clone(), image.range),
+                    image: image.image_name,
+                    platform: None,
+                }
+                .into();
+                commands.push(match image.overridden_by {
+                    Some(file_name) => CommandInfo {
+                        title: format!("{} (from {file_name})", command.title),
+                        ..command
+                    },
+                    None => command,
+                });
             }
         }
         Err(err) => tracing::warn!("unable to generate image scan commands: {err}"),
@@ -100,6 +669,78 @@ fn generate_compose_commands(url: &Url, content: &str) -> Vec<CommandInfo> {
     commands
 }
 
+/// Images of the services of a Compose file, merged with its override file (e.g.
+/// `docker-compose.override.yml`) when there is one next to it.
+fn compose_images(url: &Url, content: &str) -> Result<Vec<EffectiveImage>, String> {
+    if let Some(compose_override) = url
+        .to_file_path()
+        .ok()
+        .and_then(|path| find_compose_override(&path))
+    {
+        match merge_compose_override(content, &compose_override) {
+            Ok(images) => return Ok(images),
+            Err(err) => tracing::warn!("ignoring {}: {err}", compose_override.file_name),
+        }
+    }
+
+    parse_compose_file(content)
+        .map(|instructions| {
+            instructions
+                .into_iter()
+                .map(|i| EffectiveImage {
+                    image_name: i.image_name,
+                    range: i.range,
+                    overridden_by: None,
+                })
+                .collect()
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// "Build and scan" lens over a Compose `build:` key, building the Dockerfile it
+/// references, resolved against the directory of the Compose file.
+fn compose_build_command_for(url: &Url, build: BuildInstruction) -> Option<CommandInfo> {
+    let dockerfile = compose_build_dockerfile(&url.to_file_path().ok()?, &build)?;
+    let command = SupportedCommands::ExecuteBuildAndScan {
+        // Where the final FROM of the Dockerfile is isn't known until it's read, an
+        // empty range lets the build find it.
+        location: Location::new(Url::from_file_path(dockerfile).ok()?, Range::default()),
+        target: None,
+    };
+
+    Some(CommandInfo {
+        range: build.range,
+        ..command.into()
+    })
+}
+
+/// Dockerfile a `build:` section of the Compose file at `compose_file` builds.
+pub(super) fn compose_build_dockerfile(
+    compose_file: &Path,
+    build: &BuildInstruction,
+) -> Option<PathBuf> {
+    let compose_dir = compose_file.parent()?;
+    Some(normalize_path(
+        &compose_dir.join(&build.context).join(&build.dockerfile),
+    ))
+}
+
+/// Resolves `.` and `..` without touching the filesystem, so the URI of the Dockerfile
+/// matches the one the editor uses for it.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
 fn iac_scan_command_for(url: &Url) -> CommandInfo {
     SupportedCommands::ExecuteIacScan {
         uri: Some(url.clone()),
@@ -107,6 +748,10 @@ fn iac_scan_command_for(url: &Url) -> CommandInfo {
     .into()
 }
 
+fn scan_all_images_command_for(url: &Url) -> CommandInfo {
+    SupportedCommands::ScanAllImages { uri: url.clone() }.into()
+}
+
 fn is_k8s_manifest_file(file_uri: &str, content: &str) -> bool {
     // Must be a YAML file
     if !(file_uri.ends_with(".yaml") || file_uri.ends_with(".yml")) {
@@ -118,25 +763,80 @@ fn is_k8s_manifest_file(file_uri: &str, content: &str) -> bool {
     content.contains("apiVersion:") && content.contains("kind:")
 }
 
-fn generate_k8s_manifest_commands(url: &Url, content: &str) -> Vec<CommandInfo> {
-    // See generate_compose_commands: the IaC lens is independent of image parsing.
-    let mut commands = vec![iac_scan_command_for(url)];
-    match parse_k8s_manifest(content) {
-        Ok(instructions) => {
-            for instruction in instructions {
-                commands.push(
-                    SupportedCommands::ExecuteBaseImageScan {
-                        location: Location::new(url.clone(), instruction.range),
-                        image: instruction.image_name,
-                    }
-                    .into(),
-                );
+/// Images of a K8s manifest, as transformed by the kustomization next to it listing
+/// the manifest as a resource, if any.
+fn k8s_manifest_images(url: &Url, content: &str) -> Result<Vec<EffectiveImage>, String> {
+    let instructions = parse_k8s_manifest(content).map_err(|e| e.to_string())?;
+    let kustomization = url
+        .to_file_path()
+        .ok()
+        .and_then(|path| find_kustomization(&path));
+
+    Ok(instructions
+        .into_iter()
+        .map(|instruction| {
+            let kustomized = kustomization
+                .as_ref()
+                .and_then(|(file_name, kustomization)| {
+                    Some((file_name, kustomization.transform(&instruction.image_name)?))
+                });
+            match kustomized {
+                Some((file_name, image_name)) => EffectiveImage {
+                    image_name,
+                    range: instruction.range,
+                    overridden_by: Some(file_name.clone()),
+                },
+                None => EffectiveImage {
+                    image_name: instruction.image_name,
+                    range: instruction.range,
+                    overridden_by: None,
+                },
             }
-        }
-        Err(err) => tracing::warn!("unable to generate image scan commands: {err}"),
-    }
+        })
+        .collect())
+}
 
-    commands
+/// Images a kustomization deploys through its `images:` overrides, on the `name` of
+/// each override: the overridden images of the manifests it lists as resources or,
+/// when none of them uses it, the image the override sets.
+fn kustomization_images(url: &Url, content: &str) -> Result<Vec<EffectiveImage>, String> {
+    let kustomization = parse_kustomization(content).map_err(|e| e.to_string())?;
+    let resource_images = url
+        .to_file_path()
+        .ok()
+        .and_then(|path| {
+            Some(kustomization_resource_images(
+                path.parent()?,
+                &kustomization,
+            ))
+        })
+        .unwrap_or_default();
+
+    Ok(kustomization
+        .images
+        .iter()
+        .flat_map(|image_override| {
+            let mut images: Vec<_> = resource_images
+                .iter()
+                .filter_map(|image| image_override.apply(image))
+                .unique()
+                .collect();
+            if images.is_empty() {
+                images.push(image_override.image());
+            }
+            images.into_iter().map(|image_name| EffectiveImage {
+                image_name,
+                range: image_override.range,
+                overridden_by: None,
+            })
+        })
+        .collect())
+}
+
+fn is_kustomization_file(uri: &Url) -> bool {
+    uri.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .is_some_and(|file_name| KUSTOMIZATION_FILE_NAMES.contains(&file_name))
 }
 
 fn generate_dockerfile_commands(uri: &Url, content: &str) -> Vec<CommandInfo> {
@@ -150,18 +850,353 @@ fn generate_dockerfile_commands(uri: &Url, content: &str) -> Vec<CommandInfo> {
         commands.push(
             SupportedCommands::ExecuteBuildAndScan {
                 location: Location::new(uri.clone(), range),
+                target: None,
             }
             .into(),
         );
-        if let Some(image) = last_from_instruction.arguments.first() {
+        if let Some(image) = last_from_instruction
+            .arguments
+            .iter()
+            .find(|arg| !arg.starts_with("--"))
+        {
             commands.push(
                 SupportedCommands::ExecuteBaseImageScan {
                     location: Location::new(uri.clone(), range),
                     image: image.to_owned(),
+                    platform: from_platform(&last_from_instruction.arguments),
                 }
                 .into(),
             );
         }
     }
+    commands.extend(stage_build_commands(uri, content));
     commands
 }
+
+/// "Build and scan this stage" lenses over the `FROM ... AS <name>` of every stage but
+/// the final one, which "Build and scan" already builds.
+fn stage_build_commands(uri: &Url, content: &str) -> Vec<CommandInfo> {
+    let mut stages = parse_dockerfile_stages(content);
+    stages.pop();
+    stages
+        .into_iter()
+        .filter_map(|stage| {
+            let target = stage.name?;
+            let from = stage.instructions.first()?;
+            Some(
+                SupportedCommands::ExecuteBuildAndScan {
+                    location: Location::new(uri.clone(), from.range),
+                    target: Some(target),
+                }
+                .into(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use tower_lsp::lsp_types::{Position, Url};
+
+    use super::{
+        DocumentKind, document_comments, find_image_reference_near, generate_commands_for_uri,
+        policy_verdict_command,
+    };
+    use crate::app::PolicyVerdict;
+
+    const MULTI_STAGE_DOCKERFILE: &str = r#"FROM golang:1.19 AS build
+RUN go build -o app main.go
+
+FROM build AS test
+RUN go test ./...
+
+FROM --platform=linux/amd64 alpine:3.17
+COPY --from=build /app /app
+"#;
+
+    fn dockerfile_url() -> Url {
+        "file:///Dockerfile".parse().unwrap()
+    }
+
+    #[test]
+    fn it_finds_the_image_on_the_cursor_line() {
+        let reference = find_image_reference_near(
+            &dockerfile_url(),
+            MULTI_STAGE_DOCKERFILE,
+            DocumentKind::Dockerfile,
+            Position::new(6, 30),
+        )
+        .unwrap();
+
+        assert_eq!(reference.image, "alpine:3.17");
+        assert_eq!(reference.range.start.line, 6);
+        assert_eq!(reference.platform.as_deref(), Some("linux/amd64"));
+    }
+
+    #[test]
+    fn it_scans_the_platform_of_the_final_from_instruction() {
+        let commands = generate_commands_for_uri(
+            &dockerfile_url(),
+            "FROM --platform=linux/arm64 alpine:3.19\n",
+            DocumentKind::Dockerfile,
+        );
+
+        let scan = commands
+            .iter()
+            .find(|command| command.command == "sysdig-lsp.execute-scan")
+            .unwrap();
+        assert_eq!(scan.title, "Scan base image (linux/arm64)");
+        let arguments = scan.arguments.as_ref().unwrap();
+        assert_eq!(arguments[1], "alpine:3.19");
+        assert_eq!(arguments[2], "linux/arm64");
+
+        let commands = generate_commands_for_uri(
+            &dockerfile_url(),
+            "FROM --platform=$BUILDPLATFORM alpine:3.19\n",
+            DocumentKind::Dockerfile,
+        );
+        let scan = commands
+            .iter()
+            .find(|command| command.command == "sysdig-lsp.execute-scan")
+            .unwrap();
+        assert_eq!(scan.title, "Scan base image");
+        assert_eq!(scan.arguments.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn it_builds_and_scans_each_named_stage() {
+        let commands = generate_commands_for_uri(
+            &dockerfile_url(),
+            MULTI_STAGE_DOCKERFILE,
+            DocumentKind::Dockerfile,
+        );
+
+        let builds: Vec<_> = commands
+            .iter()
+            .filter(|command| command.command == "sysdig-lsp.execute-build-and-scan")
+            .map(|command| {
+                (
+                    command.title.as_str(),
+                    command.range.start.line,
+                    command.arguments.as_ref().unwrap().get(1).cloned(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            builds,
+            vec![
+                ("Build and scan", 6, None),
+                ("Build and scan this stage", 0, Some("build".into())),
+                ("Build and scan this stage", 3, Some("test".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_finds_the_closest_image_skipping_stage_references() {
+        let reference = find_image_reference_near(
+            &dockerfile_url(),
+            MULTI_STAGE_DOCKERFILE,
+            DocumentKind::Dockerfile,
+            Position::new(4, 0),
+        )
+        .unwrap();
+
+        assert_eq!(reference.image, "alpine:3.17");
+
+        let reference = find_image_reference_near(
+            &dockerfile_url(),
+            MULTI_STAGE_DOCKERFILE,
+            DocumentKind::Dockerfile,
+            Position::new(1, 0),
+        )
+        .unwrap();
+
+        assert_eq!(reference.image, "golang:1.19");
+    }
+
+    #[test]
+    fn it_finds_images_in_compose_files() {
+        let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
+        let content = include_str!("../../../tests/fixtures/docker-compose.yml");
+
+        let reference = find_image_reference_near(
+            &compose_url,
+            content,
+            DocumentKind::Compose,
+            Position::new(4, 0),
+        )
+        .unwrap();
+
+        assert_eq!(reference.image, "postgres:13");
+    }
+
+    #[test]
+    fn it_finds_images_in_github_workflows() {
+        let workflow_url: Url = "file:///repo/.github/workflows/ci.yml".parse().unwrap();
+        let content = "jobs:\n  test:\n    container: node:20\n    steps:\n      - uses: docker://alpine:3.19\n";
+
+        let reference = find_image_reference_near(
+            &workflow_url,
+            content,
+            DocumentKind::CiConfig,
+            Position::new(4, 0),
+        )
+        .unwrap();
+
+        assert_eq!(reference.image, "alpine:3.19");
+    }
+
+    #[test]
+    fn it_finds_images_in_gitlab_and_circleci_configs() {
+        let gitlab_url: Url = "file:///repo/.gitlab-ci.yml".parse().unwrap();
+        let reference = find_image_reference_near(
+            &gitlab_url,
+            "image: ruby:3.3\ntest:\n  services:\n    - postgres:16\n",
+            DocumentKind::CiConfig,
+            Position::new(3, 0),
+        )
+        .unwrap();
+        assert_eq!(reference.image, "postgres:16");
+
+        let circleci_url: Url = "file:///repo/.circleci/config.yml".parse().unwrap();
+        let reference = find_image_reference_near(
+            &circleci_url,
+            "jobs:\n  test:\n    docker:\n      - image: cimg/python:3.12\n",
+            DocumentKind::CiConfig,
+            Position::new(0, 0),
+        )
+        .unwrap();
+        assert_eq!(reference.image, "cimg/python:3.12");
+    }
+
+    #[test]
+    fn it_extracts_the_comments_of_dockerfiles_and_yaml_documents() {
+        let dockerfile = "# syntax=docker/dockerfile:1\nFROM alpine # base\nRUN echo hi\n";
+        assert_eq!(
+            document_comments(dockerfile, DocumentKind::Dockerfile),
+            ["syntax=docker/dockerfile:1", "base"]
+        );
+
+        let compose =
+            "services:\n  # web\n  web:\n    image: nginx # pinned\n    command: echo a#b\n";
+        assert_eq!(
+            document_comments(compose, DocumentKind::Compose),
+            ["web", "pinned"]
+        );
+    }
+
+    #[test]
+    fn it_finds_nothing_in_documents_without_images() {
+        assert_eq!(
+            find_image_reference_near(
+                &dockerfile_url(),
+                "FROM scratch\n",
+                DocumentKind::Dockerfile,
+                Position::new(0, 0)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn it_titles_the_verdict_lens_with_the_failed_policies() {
+        let title = |failed, failed_policies| {
+            let verdict = PolicyVerdict {
+                failed,
+                failed_policies,
+            };
+            policy_verdict_command(&dockerfile_url(), Default::default(), verdict, None).title
+        };
+
+        assert_eq!(title(false, 0), "Policy: PASSED — View details");
+        assert_eq!(title(true, 1), "Policy: FAILED (1 policy) — View details");
+        assert_eq!(title(true, 3), "Policy: FAILED (3 policies) — View details");
+    }
+
+    #[test]
+    fn it_tells_the_age_of_the_result_in_the_verdict_lens() {
+        let verdict = PolicyVerdict {
+            failed: false,
+            failed_policies: 0,
+        };
+
+        assert_eq!(
+            policy_verdict_command(
+                &dockerfile_url(),
+                Default::default(),
+                verdict,
+                Some("2 days ago")
+            )
+            .title,
+            "Policy: PASSED — View details (scanned 2 days ago)"
+        );
+    }
+
+    #[test]
+    fn it_builds_the_dockerfiles_of_compose_build_sections() {
+        let compose_url = Url::parse("file:///project/deploy/compose.yaml").unwrap();
+        let compose = "services:\n  web:\n    build: ../web\n  api:\n    build:\n      context: /srv/api\n      dockerfile: ./docker/Dockerfile\n";
+
+        let built: Vec<_> = generate_commands_for_uri(&compose_url, compose, DocumentKind::Compose)
+            .into_iter()
+            .filter(|command| command.command == "sysdig-lsp.execute-build-and-scan")
+            .map(|command| {
+                (
+                    command.range.start.line,
+                    command.arguments.unwrap()[0]["uri"].clone(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            built,
+            [
+                (2, "file:///project/web/Dockerfile".into()),
+                (4, "file:///srv/api/docker/Dockerfile".into()),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("file:///repo/Dockerfile", None, DocumentKind::Dockerfile)]
+    #[case("file:///repo/Containerfile", None, DocumentKind::Dockerfile)]
+    #[case("file:///repo/Dockerfile.dev", None, DocumentKind::Dockerfile)]
+    #[case("file:///repo/api.dockerfile", None, DocumentKind::Dockerfile)]
+    #[case("file:///repo/api.Containerfile", None, DocumentKind::Dockerfile)]
+    #[case("untitled:Untitled-1", Some("dockerfile"), DocumentKind::Dockerfile)]
+    #[case(
+        "file:///repo/build/image",
+        Some("containerfile"),
+        DocumentKind::Dockerfile
+    )]
+    #[case("file:///repo/notes.txt", Some("plaintext"), DocumentKind::Unsupported)]
+    #[case("file:///repo/notes.txt", None, DocumentKind::Unsupported)]
+    #[case("file:///repo/config.yaml", Some("yaml"), DocumentKind::Unsupported)]
+    #[case("file:///repo/compose.yaml", Some("yaml"), DocumentKind::Compose)]
+    #[case("file:///repo/.gitlab-ci.yml", None, DocumentKind::CiConfig)]
+    #[case(
+        "file:///repo/kustomization.yaml",
+        Some("yaml"),
+        DocumentKind::Kustomization
+    )]
+    #[case("file:///repo/Kustomization", None, DocumentKind::Kustomization)]
+    fn it_detects_the_kind_of_documents(
+        #[case] uri: &str,
+        #[case] language_id: Option<&str>,
+        #[case] expected: DocumentKind,
+    ) {
+        let uri = Url::parse(uri).unwrap();
+
+        assert_eq!(DocumentKind::detect(&uri, "", language_id), expected);
+    }
+
+    #[test]
+    fn it_generates_no_lenses_for_unsupported_documents() {
+        let uri = Url::parse("file:///repo/notes.txt").unwrap();
+        let kind = DocumentKind::detect(&uri, "FROM alpine\n", None);
+
+        assert!(generate_commands_for_uri(&uri, "FROM alpine\n", kind).is_empty());
+    }
+}
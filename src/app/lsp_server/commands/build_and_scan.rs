@@ -1,22 +1,41 @@
-use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 
 use itertools::Itertools;
+use tokio::sync::mpsc;
 use tower_lsp::jsonrpc::Result;
 use tower_lsp::lsp_types::{
-    Diagnostic, DiagnosticSeverity, Location, MessageType, Position, Range,
+    Diagnostic, DiagnosticSeverity, DiagnosticTag, Location, MessageType, NumberOrString, Position,
+    Range,
 };
 
-use crate::app::markdown::{MarkdownData, MarkdownLayerData};
+use crate::app::component_factory::{DiagnosticsConfig, HintOrder, ReportConfig};
+use crate::app::markdown::{
+    LargestLayersTable, LayerMapping, LayerMappingTable, MarkdownData, MarkdownLayerData,
+    MarkdownPackageCard, MarkdownScanResultDiff, human_size,
+};
 use crate::{
     app::{
-        DiagnosticsScope, ImageBuilder, ImageScanner, LSPClient, LspInteractor,
-        lsp_server::WithContext,
+        DiagnosticsScope, DocumentScanResult, Documentation, DocumentationAnchor,
+        ImageBuildOptions, ImageBuilder, ImageScanner, LSPClient, LspInteractor, PolicyVerdict,
+        SysdigIgnore,
+        lsp_server::{
+            WithContext,
+            command_generator::{DocumentKind, document_comments},
+        },
+        protocol::ScanResultParams,
+        redaction::{self, redact},
+    },
+    domain::scanresult::{
+        evaluation_result::EvaluationResult, layer::Layer, scan_result::ScanResult,
+        scan_result_diff::ScanResultDiff, vulnerability::Vulnerability,
     },
-    domain::scanresult::{layer::Layer, scan_result::ScanResult, severity::Severity},
-    infra::parse_dockerfile,
+    infra::{parse_dockerfile, parse_dockerfile_stages},
 };
 
-use super::{LspCommand, VULN_DIAGNOSTIC_SOURCE};
+use super::{
+    LspCommand, POLICIES_NOT_EVALUATED_NOTE, POLICY_DIAGNOSTIC_SOURCE, VULN_DIAGNOSTIC_SOURCE,
+    diagnostics_factory::DiagnosticsFactory, image_scan_error, policy_diagnostic,
+};
 
 pub struct BuildAndScanCommand<'a, C, B: ?Sized, S: ?Sized>
 where
@@ -27,6 +46,10 @@ where
     image_scanner: &'a S,
     interactor: &'a LspInteractor<C>,
     location: Location,
+    report_config: &'a ReportConfig,
+    diagnostics_config: &'a DiagnosticsConfig,
+    sysdig_ignore: &'a SysdigIgnore,
+    build_options: ImageBuildOptions,
 }
 
 impl<'a, C, B: ?Sized, S: ?Sized> BuildAndScanCommand<'a, C, B, S>
@@ -39,12 +62,67 @@ where
         image_scanner: &'a S,
         interactor: &'a LspInteractor<C>,
         location: Location,
+        report_config: &'a ReportConfig,
+        diagnostics_config: &'a DiagnosticsConfig,
+        sysdig_ignore: &'a SysdigIgnore,
     ) -> Self {
         Self {
             image_builder,
             image_scanner,
             interactor,
             location,
+            report_config,
+            diagnostics_config,
+            sysdig_ignore,
+            build_options: ImageBuildOptions::default(),
+        }
+    }
+
+    /// Options of the build, completed with the build arg directives of the Dockerfile.
+    pub fn with_build_options(self, build_options: ImageBuildOptions) -> Self {
+        Self {
+            build_options,
+            ..self
+        }
+    }
+}
+
+impl<'a, C, B: ?Sized, S: ?Sized> BuildAndScanCommand<'a, C, B, S>
+where
+    C: LSPClient + Sync,
+    B: ImageBuilder + Sync,
+    S: ImageScanner + Sync,
+{
+    /// Scans the base image of the final stage to tell apart the vulnerabilities the
+    /// Dockerfile adds. Every vulnerability is reported if it can't be scanned.
+    async fn compare_with_base_image(
+        &self,
+        document_text: &str,
+        scan_result: &ScanResult,
+    ) -> Option<BaseImageComparison> {
+        let image = final_base_image(document_text)?;
+        // The scan error isn't `Send`, so it's converted before awaiting again.
+        let base_scan_result = self
+            .image_scanner
+            .scan_image(&image)
+            .await
+            .map_err(|e| e.to_string());
+        match base_scan_result {
+            Ok(base_scan_result) => Some(BaseImageComparison {
+                diff: scan_result.diff(&base_scan_result),
+                image,
+            }),
+            Err(e) => {
+                self.interactor
+                    .show_message(
+                        MessageType::WARNING,
+                        &format!(
+                            "Unable to scan base image {image}, reporting every vulnerability: {e}"
+                        ),
+                    )
+                    .await;
+                None
+            }
         }
     }
 }
@@ -58,41 +136,88 @@ where
 {
     async fn execute(&mut self) -> Result<()> {
         let uri = self.location.uri.as_str();
-        let line = self.location.range.start.line;
 
-        let document_text = self
-            .interactor
-            .read_document_text(uri)
-            .await
-            .ok_or_else(|| {
-                tower_lsp::jsonrpc::Error::internal_error()
-                    .with_message("unable to obtain document to scan")
-            })?;
-
-        let uri_without_file_path = uri.strip_prefix("file://").ok_or_else(|| {
-            tower_lsp::jsonrpc::Error::internal_error()
-                .with_message("unable to strip prefix file:// from uri")
+        // Unlike stripping `file://`, this decodes the path and handles Windows drive
+        // letters (`file:///C:/project/Dockerfile`).
+        let dockerfile_path = self.location.uri.to_file_path().map_err(|_| {
+            tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "only file:// URIs are supported, received: {uri}"
+            ))
         })?;
 
+        // Dockerfiles built from the `build:` section of a Compose file may not be open.
+        let document_text = match self.interactor.read_document_text(uri).await {
+            Some(text) => text,
+            None => tokio::fs::read_to_string(&dockerfile_path)
+                .await
+                .map_err(|e| {
+                    tower_lsp::jsonrpc::Error::internal_error()
+                        .with_message(format!("unable to obtain document to scan: {e}"))
+                })?,
+        };
+        let build_options = self.build_options.clone().with_inline_directives(
+            document_comments(&document_text, DocumentKind::Dockerfile)
+                .iter()
+                .map(String::as_str),
+        );
+        // Only the stages up to the target end up in the image.
+        let document_text = built_part(&document_text, build_options.target.as_deref()).to_owned();
+        if self.location.range.start == self.location.range.end
+            && let Some(last_from) = parse_dockerfile(&document_text)
+                .into_iter()
+                .rfind(|instruction| instruction.keyword == "FROM")
+        {
+            self.location.range = last_from.range;
+        }
+        let line = self.location.range.start.line;
+
+        // The build may print them, e.g. in the output of a `RUN` using them.
+        for secret in build_options.secret_values() {
+            redaction::register_secret(secret);
+        }
+        tracing::debug!(
+            "building {} with {build_options:?}",
+            dockerfile_path.display()
+        );
+
         self.interactor
             .show_message(
                 MessageType::INFO,
-                format!("Starting build of {uri_without_file_path}...").as_str(),
+                format!("Starting build of {}...", dockerfile_path.display()).as_str(),
             )
             .await;
 
-        let build_result = self
-            .image_builder
-            .build_image(&PathBuf::from_str(uri_without_file_path).unwrap())
-            .await
-            .map_err(|e| tower_lsp::jsonrpc::Error::internal_error().with_message(e.to_string()))?;
+        let (build_log, mut build_log_lines) = mpsc::unbounded_channel();
+        // The build error isn't `Send`, so it's converted before being held across the join.
+        let build = async {
+            self.image_builder
+                .build_image(&dockerfile_path, &build_options, build_log)
+                .await
+                .map_err(|e| {
+                    tower_lsp::jsonrpc::Error::internal_error().with_message(e.to_string())
+                })
+        };
+        // Forwarded while building so users can follow the build live in the output pane.
+        let forward_build_log = async {
+            while let Some(line) = build_log_lines.recv().await {
+                self.interactor
+                    .log_message(MessageType::LOG, &format!("[build] {}", redact(&line)))
+                    .await;
+            }
+        };
+        let (build_result, ()) = tokio::join!(build, forward_build_log);
+        let mut build_result = build_result?;
 
         self.interactor
             .show_message(
                 MessageType::INFO,
                 format!(
-                    "Temporal image built '{}', starting scan...",
-                    &build_result.image_name
+                    "Temporal image built '{}'{}, starting scan...",
+                    build_result.image_name,
+                    build_result
+                        .size
+                        .map(|size| format!(" ({})", human_size(size)))
+                        .unwrap_or_default()
                 )
                 .as_str(),
             )
@@ -102,23 +227,95 @@ where
             .image_scanner
             .scan_image(&build_result.image_name)
             .await
-            .map_err(|e| tower_lsp::jsonrpc::Error::internal_error().with_message(e.to_string()))?;
+            .map_err(|e| image_scan_error(&build_result.image_name, e));
+        // Scanned or not, the image is of no use anymore. Leftovers can still be removed
+        // with `sysdig-lsp.cleanup-temp-images`, once no longer in use.
+        if let Err(e) = self
+            .image_builder
+            .remove_image(&build_result.image_name)
+            .await
+        {
+            tracing::warn!("unable to remove image {}: {e}", build_result.image_name);
+        }
+        drop(build_result.in_use.take());
+        let scan_result = scan_result?;
 
         self.interactor
             .show_message(
                 MessageType::INFO,
-                format!("Finished scan of {}.", &build_result.image_name).as_str(),
+                format!("Finished scan of {}.", build_result.image_name).as_str(),
             )
             .await;
 
-        let diagnostic = diagnostic_for_image(line, &document_text, &scan_result);
-        let (diagnostics_per_layer, docs_per_layer) =
-            diagnostics_for_layers(&document_text, &scan_result)?;
+        let base_image_comparison = if self.diagnostics_config.only_new_vulns {
+            self.compare_with_base_image(&document_text, &scan_result)
+                .await
+        } else {
+            None
+        };
+
+        let diagnostic = diagnostic_for_image(
+            line,
+            &document_text,
+            &scan_result,
+            &DiagnosticsFactory::new(self.sysdig_ignore, self.diagnostics_config.severity_mapping),
+            base_image_comparison.as_ref(),
+        );
+        let policy_diagnostics: Vec<_> = policy_diagnostic(diagnostic.range, &scan_result)
+            .into_iter()
+            .collect();
+        let LayerScanResult {
+            diagnostics: diagnostics_per_layer,
+            docs: docs_per_layer,
+            layer_mapping,
+            layer_ranges,
+        } = diagnostics_for_layers(
+            &document_text,
+            &scan_result,
+            self.sysdig_ignore,
+            self.diagnostics_config,
+            base_image_comparison.as_ref().map(|c| &c.diff),
+        )?;
 
         let mut diagnostics = Vec::with_capacity(1 + diagnostics_per_layer.len());
         diagnostics.push(diagnostic);
         diagnostics.extend(diagnostics_per_layer);
 
+        self.interactor
+            .write_last_scan_result(
+                uri,
+                DocumentScanResult {
+                    scan_result: scan_result.clone(),
+                    image_range: self.location.range,
+                    layer_ranges,
+                },
+            )
+            .await;
+        self.interactor
+            .send_scan_result(ScanResultParams::new(
+                self.location.uri.clone(),
+                self.location.range,
+                &scan_result,
+            ))
+            .await;
+        let suppressed_vulns = self.sysdig_ignore.suppressed(&scan_result);
+        let verdict = PolicyVerdict::of(&scan_result);
+        let scanned_at = scan_result.metadata().scanned_at();
+        let mut report = MarkdownData::from(scan_result)
+            .with_suppressed_vulns(suppressed_vulns)
+            .to_string();
+        report.push_str(&LargestLayersTable::from(layer_mapping.as_slice()).to_string());
+        if self.report_config.layer_mapping {
+            report.push_str(&LayerMappingTable(layer_mapping).to_string());
+        }
+        if let Some(comparison) = &base_image_comparison {
+            report.push_str(&format!(
+                "\n\nCompared with the base image `{}`:\n\n{}",
+                comparison.image,
+                MarkdownScanResultDiff::from(&comparison.diff)
+            ));
+        }
+
         self.interactor.remove_documentations(uri).await;
         self.interactor
             .replace_diagnostics_with_source(
@@ -127,25 +324,124 @@ where
                 HashMap::from([(uri.to_owned(), diagnostics)]),
             )
             .await;
+        self.interactor
+            .replace_diagnostics_with_source(
+                POLICY_DIAGNOSTIC_SOURCE,
+                DiagnosticsScope::Document(uri),
+                HashMap::from([(uri.to_owned(), policy_diagnostics)]),
+            )
+            .await;
         self.interactor
             .append_documentation(
                 uri,
-                self.location.range,
-                MarkdownData::from(scan_result).to_string(),
+                Documentation::new(self.location.range, report)
+                    .with_verdict(verdict)
+                    .with_scanned_at(scanned_at),
             )
             .await;
-        for (range, docs) in docs_per_layer {
-            self.interactor.append_documentation(uri, range, docs).await;
+        for docs in docs_per_layer {
+            self.interactor.append_documentation(uri, docs).await;
         }
         self.interactor.publish_all_diagnostics().await
     }
 }
 
-pub type LayerScanResult = (Vec<Diagnostic>, Vec<(Range, String)>);
+pub struct LayerScanResult {
+    pub diagnostics: Vec<Diagnostic>,
+    pub docs: Vec<Documentation>,
+    /// Every layer of the image, with the instruction it was matched to (if any).
+    pub layer_mapping: Vec<LayerMapping>,
+    /// Range of the instruction each matched layer was built by, by layer index.
+    pub layer_ranges: HashMap<usize, Range>,
+}
+
+/// Vulnerabilities of the built image compared with the ones of its base image,
+/// when `sysdig.diagnostics.only_new_vulns` is enabled.
+pub struct BaseImageComparison {
+    pub image: String,
+    pub diff: ScanResultDiff,
+}
+
+/// The Dockerfile up to the end of the `target` stage (the final one if `None`), which
+/// the image is built from.
+fn built_part<'t>(document_text: &'t str, target: Option<&str>) -> &'t str {
+    let Some(end) = target.and_then(|target| {
+        parse_dockerfile_stages(document_text)
+            .into_iter()
+            .find(|stage| {
+                stage
+                    .name
+                    .as_deref()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(target))
+            })
+            .map(|stage| stage.range().end.line as usize)
+    }) else {
+        return document_text;
+    };
+    let length = document_text
+        .split_inclusive('\n')
+        .take(end + 1)
+        .map(str::len)
+        .sum();
+    &document_text[..length]
+}
+
+/// Image the final stage is built on, following the stages it's based on.
+/// `None` for images built from `scratch`, where every vulnerability is new.
+fn final_base_image(document_text: &str) -> Option<String> {
+    let stages = parse_dockerfile_stages(document_text);
+    let mut stage = stages.last()?;
+    loop {
+        let base = stage.base.as_deref()?;
+        match stages[..stage.index]
+            .iter()
+            .rfind(|previous| previous.is_referenced_by(base))
+        {
+            Some(previous) => stage = previous,
+            None if base.eq_ignore_ascii_case("scratch") => return None,
+            None => return Some(base.to_owned()),
+        }
+    }
+}
+
+/// Vulnerabilities to report, leaving out the ignored ones and, when comparing with
+/// the base image, the ones already in it.
+fn reported_vulnerabilities(
+    scan_result: &ScanResult,
+    vulnerabilities: Vec<Arc<Vulnerability>>,
+    sysdig_ignore: &SysdigIgnore,
+    base_image_diff: Option<&ScanResultDiff>,
+) -> Vec<Arc<Vulnerability>> {
+    let mut reported = sysdig_ignore.reported(scan_result, vulnerabilities);
+    if let Some(diff) = base_image_diff {
+        reported.retain(|v| diff.is_added(v.cve()));
+    }
+    reported
+}
+
+/// Data of the hint standing for the vulnerabilities left out by `max_hints_per_layer`,
+/// so code actions can offer to list them all.
+pub const OMITTED_HINTS_DATA_KEY: &str = "omittedHints";
+/// Data of the hint of a vulnerability holding its CVE, so code actions can offer
+/// to accept its risk.
+pub const CVE_DATA_KEY: &str = "cve";
+/// Data of the hint of a vulnerability holding the packages (`name` and `version`)
+/// it was found in.
+pub const PACKAGES_DATA_KEY: &str = "packages";
+/// Data of the hint of a vulnerability whose risk is accepted, in Sysdig Secure or
+/// from the editor, so the action to accept it isn't offered.
+pub const RISK_ACCEPTED_DATA_KEY: &str = "riskAccepted";
+/// Data of the vulnerability summaries holding the CVEs they count.
+pub const CVES_DATA_KEY: &str = "cves";
+/// Code of the diagnostics of layers above `sysdig.diagnostics.large_layer_threshold_mb`.
+pub const LARGE_LAYER_CODE: &str = "large-layer";
 
 pub fn diagnostics_for_layers(
     document_text: &str,
     scan_result: &ScanResult,
+    sysdig_ignore: &SysdigIgnore,
+    diagnostics_config: &DiagnosticsConfig,
+    base_image_diff: Option<&ScanResultDiff>,
 ) -> Result<LayerScanResult> {
     let instructions = parse_dockerfile(document_text);
     let layers = &scan_result.layers();
@@ -153,8 +449,21 @@ pub fn diagnostics_for_layers(
     let mut instr_idx = instructions.len().checked_sub(1);
     let mut layer_idx = layers.len().checked_sub(1);
 
+    let diagnostics_factory =
+        DiagnosticsFactory::new(sysdig_ignore, diagnostics_config.severity_mapping);
     let mut diagnostics = Vec::new();
     let mut docs = Vec::new();
+    let mut layer_ranges = HashMap::new();
+    let mut layer_mapping: Vec<LayerMapping> = layers
+        .iter()
+        .map(|layer| LayerMapping {
+            index: layer.index(),
+            digest: layer.digest().map(str::to_string),
+            command: layer.command().to_string(),
+            size: layer.size().copied(),
+            instruction_line: None,
+        })
+        .collect();
 
     while let (Some(i), Some(l)) = (instr_idx, layer_idx) {
         let instr = &instructions[i];
@@ -167,68 +476,249 @@ pub fn diagnostics_for_layers(
         instr_idx = instr_idx.and_then(|x| x.checked_sub(1));
         layer_idx = layer_idx.and_then(|x| x.checked_sub(1));
 
-        if !layer.vulnerabilities().is_empty() {
-            let vulns = layer.vulnerabilities().iter().counts_by(|v| v.severity());
-            let msg = format!(
-                "Vulnerabilities found in layer: {} Critical, {} High, {} Medium, {} Low, {} Negligible",
-                vulns.get(&Severity::Critical).unwrap_or(&0_usize),
-                vulns.get(&Severity::High).unwrap_or(&0_usize),
-                vulns.get(&Severity::Medium).unwrap_or(&0_usize),
-                vulns.get(&Severity::Low).unwrap_or(&0_usize),
-                vulns.get(&Severity::Negligible).unwrap_or(&0_usize),
-            );
-            let diagnostic = Diagnostic {
-                range: instr.range,
-                severity: Some(DiagnosticSeverity::WARNING),
-                message: msg,
-                source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
-                ..Default::default()
-            };
-
-            diagnostics.push(diagnostic);
-            docs.push((
+        if let Some(mapping) = layer_mapping.get_mut(l) {
+            mapping.instruction_line = Some(instr.range.start.line);
+        }
+        layer_ranges.insert(layer.index(), instr.range);
+        diagnostics.extend(large_layer_diagnostic(
+            instr.range,
+            layer,
+            diagnostics_config.large_layer_threshold_mb,
+        ));
+
+        let vulnerabilities = reported_vulnerabilities(
+            scan_result,
+            layer.vulnerabilities(),
+            sysdig_ignore,
+            base_image_diff,
+        );
+        if !vulnerabilities.is_empty() {
+            diagnostics.push(diagnostics_factory.vulnerability_summary(
+                instr.range,
+                "Vulnerabilities found in layer",
+                &vulnerabilities,
+                Some(DiagnosticSeverity::WARNING),
+            ));
+            fill_vulnerability_hints_for_layer(
+                vulnerabilities,
+                instr.range,
+                diagnostics_config,
+                sysdig_ignore,
+                &mut diagnostics,
+            )
+        }
+
+        docs.push(
+            Documentation::new(
                 instr.range,
                 MarkdownLayerData::from(layer.clone()).to_string(),
-            ));
+            )
+            .with_anchors(package_anchors(
+                document_text,
+                instr.range,
+                layer,
+                scan_result,
+            )),
+        );
+    }
+
+    Ok(LayerScanResult {
+        diagnostics,
+        docs,
+        layer_mapping,
+        layer_ranges,
+    })
+}
+
+/// Sorted CVEs of the vulnerabilities, each once.
+pub fn cve_ids(vulnerabilities: &[Arc<Vulnerability>]) -> Vec<&str> {
+    vulnerabilities
+        .iter()
+        .map(|vuln| vuln.cve())
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+fn large_layer_diagnostic(range: Range, layer: &Layer, threshold_mb: u64) -> Option<Diagnostic> {
+    let size = *layer.size()?;
+    if threshold_mb == 0 || size <= threshold_mb.saturating_mul(1_000_000) {
+        return None;
+    }
+
+    Some(Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String(LARGE_LAYER_CODE.to_string())),
+        message: format!(
+            "Layer size: {}, above the {threshold_mb} MB threshold",
+            human_size(size)
+        ),
+        source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
+        ..Default::default()
+    })
+}
+
+/// Anchors the focused card of each fixable package of the layer at the places the
+/// instruction mentions it (e.g. `openssl` in `RUN apk add openssl=3.1.4-r5`).
+fn package_anchors(
+    document_text: &str,
+    range: Range,
+    layer: &Arc<Layer>,
+    scan_result: &ScanResult,
+) -> Vec<DocumentationAnchor> {
+    let cards: Vec<(String, String)> = layer
+        .packages()
+        .iter()
+        .map(|package| package.name().to_string())
+        .unique()
+        .filter_map(|name| {
+            let card = MarkdownPackageCard::for_package(scan_result, &name)?.to_string();
+            Some((name, card))
+        })
+        .collect();
+    if cards.is_empty() {
+        return vec![];
+    }
 
-            fill_vulnerability_hints_for_layer(layer, instr.range, &mut diagnostics)
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || "-_.+".contains(c);
+    let lines = document_text
+        .lines()
+        .enumerate()
+        .skip(range.start.line as usize)
+        .take(range.end.line.saturating_sub(range.start.line) as usize + 1);
+
+    let mut anchors = Vec::new();
+    for (line_number, line) in lines {
+        let line_number = line_number as u32;
+        for (name, card) in &cards {
+            for (start, _) in line.match_indices(name.as_str()) {
+                let end = start + name.len();
+                let is_whole_name = !line[..start].ends_with(is_name_char)
+                    && !line[end..].starts_with(is_name_char);
+                let anchor_range = Range::new(
+                    Position::new(line_number, start as u32),
+                    Position::new(line_number, end as u32),
+                );
+                if is_whole_name
+                    && anchor_range.start >= range.start
+                    && anchor_range.end <= range.end
+                {
+                    anchors.push(DocumentationAnchor {
+                        range: anchor_range,
+                        content: card.clone(),
+                    });
+                }
+            }
         }
     }
+    anchors
+}
 
-    Ok((diagnostics, docs))
+/// Sorts the vulnerabilities in the order their hints are reported, the CVE breaking ties
+/// so the hints kept by `max_hints_per_layer` don't change between scans.
+fn sort_for_hints(vulnerabilities: &mut [Arc<Vulnerability>], order: HintOrder) {
+    // Descending scores, with the unscored ones last.
+    let by_cvss = |a: &Arc<Vulnerability>, b: &Arc<Vulnerability>| {
+        let score = |v: &Vulnerability| v.cvss_score().unwrap_or(-1.0);
+        score(b).total_cmp(&score(a))
+    };
+    let by_severity =
+        |a: &Arc<Vulnerability>, b: &Arc<Vulnerability>| a.severity().cmp(&b.severity());
+    vulnerabilities.sort_by(|a, b| {
+        match order {
+            HintOrder::Severity => by_severity(a, b).then_with(|| by_cvss(a, b)),
+            HintOrder::Cvss => by_cvss(a, b).then_with(|| by_severity(a, b)),
+        }
+        .then_with(|| a.cve().cmp(b.cve()))
+    });
 }
 
 fn fill_vulnerability_hints_for_layer(
-    layer: &Arc<Layer>,
+    mut vulnerabilities: Vec<Arc<Vulnerability>>,
     range: Range,
+    diagnostics_config: &DiagnosticsConfig,
+    sysdig_ignore: &SysdigIgnore,
     diagnostics: &mut Vec<Diagnostic>,
 ) {
-    let vulns_per_severity = layer
-        .vulnerabilities()
-        .iter()
-        .cloned()
-        .sorted_by_key(|v| v.severity());
-
-    // TODO(fede): eventually we would want to add here a .take() to truncate the number
-    // of vulnerabilities shown as hint per layer.
-    vulns_per_severity.for_each(|vuln| {
+    let total = vulnerabilities.len();
+    let shown = diagnostics_config
+        .max_hints_per_layer
+        .unwrap_or(total)
+        .min(total);
+    sort_for_hints(&mut vulnerabilities, diagnostics_config.hint_order);
+
+    vulnerabilities.into_iter().take(shown).for_each(|vuln| {
         let url = format!("https://nvd.nist.gov/vuln/detail/{}", vuln.cve());
-        diagnostics.push(Diagnostic {
+        let cvss = vuln
+            .cvss_score()
+            .map(|score| format!(", CVSS {score:.1}"))
+            .unwrap_or_default();
+        let known_exploited = if vuln.known_exploited() {
+            ", known exploited"
+        } else {
+            ""
+        };
+        let severity = if sysdig_ignore.escalates(std::slice::from_ref(&vuln)) {
+            DiagnosticSeverity::ERROR
+        } else {
+            DiagnosticSeverity::HINT
+        };
+        let mut diagnostic = Diagnostic {
             range,
-            severity: Some(DiagnosticSeverity::HINT),
+            severity: Some(severity),
             message: format!(
-                "Vulnerability: {} ({:?}) {}",
+                "Vulnerability: {} ({:?}{cvss}{known_exploited}) {}",
                 vuln.cve(),
                 vuln.severity(),
                 url
             ),
             source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
+            data: Some(serde_json::json!({
+                CVE_DATA_KEY: vuln.cve(),
+                PACKAGES_DATA_KEY: vuln
+                    .found_in_packages()
+                    .iter()
+                    .map(|p| (p.name(), p.version()))
+                    .unique()
+                    .map(|(name, version)| serde_json::json!({ "name": name, "version": version }))
+                    .collect::<Vec<_>>(),
+            })),
             ..Default::default()
-        });
+        };
+        // Accepted risks are still reported, faded out, so they're not mistaken for fixed.
+        if !vuln.accepted_risks().is_empty() {
+            diagnostic.message.push_str(" (risk accepted)");
+            diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+            if let Some(data) = diagnostic.data.as_mut() {
+                data[RISK_ACCEPTED_DATA_KEY] = serde_json::Value::Bool(true);
+            }
+        }
+        diagnostics.push(diagnostic);
     });
+
+    let omitted = total - shown;
+    if omitted > 0 {
+        diagnostics.push(Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::HINT),
+            message: format!(
+                "{omitted} more vulnerabilities in this layer, use \"Show all layer findings\" to list them"
+            ),
+            source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
+            data: Some(serde_json::json!({ OMITTED_HINTS_DATA_KEY: omitted })),
+            ..Default::default()
+        });
+    }
 }
 
-fn diagnostic_for_image(line: u32, document_text: &str, scan_result: &ScanResult) -> Diagnostic {
+fn diagnostic_for_image(
+    line: u32,
+    document_text: &str,
+    scan_result: &ScanResult,
+    diagnostics: &DiagnosticsFactory,
+    base_image_comparison: Option<&BaseImageComparison>,
+) -> Diagnostic {
     let range_for_selected_line = Range::new(
         Position::new(line, 0),
         Position::new(
@@ -241,33 +731,39 @@ fn diagnostic_for_image(line: u32, document_text: &str, scan_result: &ScanResult
         ),
     );
 
-    let mut diagnostic = Diagnostic {
-        range: range_for_selected_line,
-        severity: Some(DiagnosticSeverity::HINT),
-        message: "No vulnerabilities found.".to_owned(),
-        source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
-        ..Default::default()
+    let on_top_of_base = base_image_comparison
+        .map(|comparison| format!(" on top of {}", comparison.image))
+        .unwrap_or_default();
+    let vulnerabilities = reported_vulnerabilities(
+        scan_result,
+        scan_result.vulnerabilities(),
+        diagnostics.sysdig_ignore(),
+        base_image_comparison.map(|comparison| &comparison.diff),
+    );
+    let mut diagnostic = if vulnerabilities.is_empty() {
+        Diagnostic {
+            range: range_for_selected_line,
+            severity: Some(DiagnosticSeverity::HINT),
+            message: format!("No vulnerabilities found{on_top_of_base}."),
+            source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
+            data: Some(serde_json::json!({ CVES_DATA_KEY: cve_ids(&vulnerabilities) })),
+            ..Default::default()
+        }
+    } else {
+        let policy_severity = match scan_result.evaluation_result() {
+            EvaluationResult::Passed => DiagnosticSeverity::INFORMATION,
+            EvaluationResult::Failed => DiagnosticSeverity::ERROR,
+            EvaluationResult::NotEvaluated => DiagnosticSeverity::WARNING,
+        };
+        diagnostics.vulnerability_summary(
+            range_for_selected_line,
+            &format!("Vulnerabilities found{on_top_of_base}"),
+            &vulnerabilities,
+            Some(policy_severity),
+        )
     };
-
-    if !scan_result.vulnerabilities().is_empty() {
-        let vulns = scan_result
-            .vulnerabilities()
-            .iter()
-            .counts_by(|v| v.severity());
-        diagnostic.message = format!(
-            "Vulnerabilities found: {} Critical, {} High, {} Medium, {} Low, {} Negligible",
-            vulns.get(&Severity::Critical).unwrap_or(&0_usize),
-            vulns.get(&Severity::High).unwrap_or(&0_usize),
-            vulns.get(&Severity::Medium).unwrap_or(&0_usize),
-            vulns.get(&Severity::Low).unwrap_or(&0_usize),
-            vulns.get(&Severity::Negligible).unwrap_or(&0_usize),
-        );
-
-        diagnostic.severity = Some(if scan_result.evaluation_result().is_passed() {
-            DiagnosticSeverity::INFORMATION
-        } else {
-            DiagnosticSeverity::ERROR
-        });
+    if scan_result.evaluation_result() == EvaluationResult::NotEvaluated {
+        diagnostic.message.push_str(POLICIES_NOT_EVALUATED_NOTE);
     }
 
     diagnostic
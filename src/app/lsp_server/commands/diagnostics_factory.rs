@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Range};
+
+use crate::app::SysdigIgnore;
+use crate::app::component_factory::SeverityMapping;
+use crate::domain::scanresult::{severity::Severity, vulnerability::Vulnerability};
+
+use super::VULN_DIAGNOSTIC_SOURCE;
+use super::build_and_scan::{CVES_DATA_KEY, cve_ids};
+
+/// Builds the vulnerability summaries of the image scan commands, of the scanned images
+/// and of the layers of "Build and scan", with the severity of
+/// `sysdig.diagnostics.severity_mapping` if set.
+pub struct DiagnosticsFactory<'a> {
+    sysdig_ignore: &'a SysdigIgnore,
+    severity_mapping: Option<SeverityMapping>,
+}
+
+impl<'a> DiagnosticsFactory<'a> {
+    pub fn new(sysdig_ignore: &'a SysdigIgnore, severity_mapping: Option<SeverityMapping>) -> Self {
+        Self {
+            sysdig_ignore,
+            severity_mapping,
+        }
+    }
+
+    pub fn sysdig_ignore(&self) -> &'a SysdigIgnore {
+        self.sysdig_ignore
+    }
+
+    /// Counts the reported `vulnerabilities` by severity after `found`, e.g.
+    /// `Vulnerabilities found in layer: 1 Critical, 0 High, 2 Medium, 0 Low, 0 Negligible`.
+    ///
+    /// It's as severe as the mapping says of the most severe vulnerability. Without a
+    /// mapping, it's `unmapped`, or as the default mapping says if `None`. Known exploited
+    /// vulnerabilities make it an error anyway when they're escalated.
+    pub fn vulnerability_summary(
+        &self,
+        range: Range,
+        found: &str,
+        vulnerabilities: &[Arc<Vulnerability>],
+        unmapped: Option<DiagnosticSeverity>,
+    ) -> Diagnostic {
+        let counts = vulnerabilities.iter().counts_by(|v| v.severity());
+        let count = |severity| counts.get(&severity).copied().unwrap_or_default();
+        let message = format!(
+            "{found}: {} Critical, {} High, {} Medium, {} Low, {} Negligible",
+            count(Severity::Critical),
+            count(Severity::High),
+            count(Severity::Medium),
+            count(Severity::Low),
+            count(Severity::Negligible),
+        );
+
+        Diagnostic {
+            range,
+            severity: Some(self.summary_severity(vulnerabilities, unmapped)),
+            message,
+            source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
+            data: Some(serde_json::json!({ CVES_DATA_KEY: cve_ids(vulnerabilities) })),
+            ..Default::default()
+        }
+    }
+
+    fn summary_severity(
+        &self,
+        vulnerabilities: &[Arc<Vulnerability>],
+        unmapped: Option<DiagnosticSeverity>,
+    ) -> DiagnosticSeverity {
+        if self.sysdig_ignore.escalates(vulnerabilities) {
+            return DiagnosticSeverity::ERROR;
+        }
+        let most_severe = vulnerabilities.iter().map(|v| v.severity()).min();
+        match (self.severity_mapping, unmapped, most_severe) {
+            (Some(mapping), _, Some(severity)) => mapping.diagnostic_severity(severity),
+            (None, Some(unmapped), _) => unmapped,
+            (None, None, Some(severity)) => {
+                SeverityMapping::default().diagnostic_severity(severity)
+            }
+            (_, _, None) => DiagnosticSeverity::HINT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, Utc};
+
+    use super::*;
+    use crate::app::component_factory::DiagnosticLevel;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        exploit::{CisaKev, Exploit},
+        operating_system::{Family, OperatingSystem},
+        scan_result::ScanResult,
+        scan_type::ScanType,
+    };
+
+    fn vulnerabilities() -> Vec<Arc<Vulnerability>> {
+        let mut result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:latest".to_string(),
+            "sha256:12345".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+            123456,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Failed,
+        );
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let exploit = Some(Exploit::new(None, vec![], Some(CisaKev::new(None, None))));
+        vec![
+            result.add_vulnerability(
+                "CVE-2024-0001".to_string(),
+                Severity::Medium,
+                date,
+                None,
+                false,
+                None,
+                None,
+                exploit,
+            ),
+            result.add_vulnerability(
+                "CVE-2024-0002".to_string(),
+                Severity::Low,
+                date,
+                None,
+                false,
+                None,
+                None,
+                None,
+            ),
+        ]
+    }
+
+    #[test]
+    fn it_maps_the_most_severe_vulnerability_to_the_summary_severity() {
+        let vulnerabilities = vulnerabilities();
+        let sysdig_ignore = SysdigIgnore::default();
+        let mapping = SeverityMapping {
+            medium: DiagnosticLevel::Error,
+            ..Default::default()
+        };
+        let summary = |factory: DiagnosticsFactory, unmapped| {
+            factory.vulnerability_summary(Range::default(), "Found", &vulnerabilities, unmapped)
+        };
+
+        let mapped = summary(
+            DiagnosticsFactory::new(&sysdig_ignore, Some(mapping)),
+            Some(DiagnosticSeverity::INFORMATION),
+        );
+        assert_eq!(mapped.severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            mapped.message,
+            "Found: 0 Critical, 0 High, 1 Medium, 1 Low, 0 Negligible"
+        );
+
+        let unmapped = DiagnosticsFactory::new(&sysdig_ignore, None);
+        assert_eq!(
+            summary(unmapped, Some(DiagnosticSeverity::INFORMATION)).severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        let unmapped = DiagnosticsFactory::new(&sysdig_ignore, None);
+        assert_eq!(
+            summary(unmapped, None).severity,
+            Some(DiagnosticSeverity::WARNING)
+        );
+
+        let escalating = SysdigIgnore::default().escalate_known_exploited(true);
+        let escalated = summary(
+            DiagnosticsFactory::new(&escalating, None),
+            Some(DiagnosticSeverity::INFORMATION),
+        );
+        assert_eq!(escalated.severity, Some(DiagnosticSeverity::ERROR));
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, MessageType, Position, Range, Url};
+
+use crate::{
+    app::{DiagnosticsScope, FilesystemScanner, LSPClient, LspInteractor},
+    domain::scanresult::{package::Package, severity::Severity},
+};
+
+use super::{
+    FILESYSTEM_DIAGNOSTIC_SOURCE, LspCommand,
+    build_and_scan::{CVES_DATA_KEY, PACKAGES_DATA_KEY, cve_ids},
+    directory_uri_prefix, image_scan_error,
+};
+
+/// Scans the packages of a directory, publishing a diagnostic per vulnerable package
+/// on the file listing it (e.g. `package-lock.json` or `requirements.txt`).
+pub struct DirectoryScanCommand<'a, C, S: ?Sized>
+where
+    S: FilesystemScanner,
+{
+    scanner: &'a S,
+    interactor: &'a LspInteractor<C>,
+    directory: PathBuf,
+}
+
+impl<'a, C, S: ?Sized> DirectoryScanCommand<'a, C, S>
+where
+    S: FilesystemScanner,
+{
+    pub fn new(scanner: &'a S, interactor: &'a LspInteractor<C>, directory: PathBuf) -> Self {
+        Self {
+            scanner,
+            interactor,
+            directory,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, C, S: ?Sized> LspCommand for DirectoryScanCommand<'a, C, S>
+where
+    C: LSPClient + Sync,
+    S: FilesystemScanner + Sync,
+{
+    async fn execute(&mut self) -> tower_lsp::jsonrpc::Result<()> {
+        let directory_display = self.directory.display().to_string();
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                format!("Starting scan of the packages in {directory_display}...").as_str(),
+            )
+            .await;
+
+        let scan_result = self
+            .scanner
+            .scan_directory(&self.directory)
+            .await
+            .map_err(|e| image_scan_error(&directory_display, e))?;
+
+        let mut packages_per_file: HashMap<PathBuf, Vec<Arc<Package>>> = HashMap::new();
+        for package in scan_result.packages() {
+            if package.vulnerabilities().is_empty() {
+                continue;
+            }
+            packages_per_file
+                .entry(manifest_path(&self.directory, package.path()))
+                .or_default()
+                .push(package);
+        }
+
+        let vulnerable_packages: usize = packages_per_file.values().map(Vec::len).sum();
+        let mut diagnostics_per_uri: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+        for (path, packages) in &packages_per_file {
+            let Ok(uri) = Url::from_file_path(path) else {
+                tracing::warn!(
+                    "unable to build a file URI for the package file: {}",
+                    path.display()
+                );
+                continue;
+            };
+            let text = self.manifest_text(&uri, path).await;
+            let diagnostics = packages
+                .iter()
+                .map(|package| package_diagnostic(&text, package))
+                .collect();
+            diagnostics_per_uri.insert(uri.to_string(), diagnostics);
+        }
+
+        let scope_key = directory_uri_prefix(&self.directory);
+        self.interactor
+            .replace_diagnostics_with_source(
+                FILESYSTEM_DIAGNOSTIC_SOURCE,
+                DiagnosticsScope::DocumentsWithUriPrefix(&scope_key),
+                diagnostics_per_uri,
+            )
+            .await;
+        self.interactor.publish_all_diagnostics().await?;
+
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                format!(
+                    "Finished scan of the packages in {directory_display}: {vulnerable_packages} vulnerable packages in {} files.",
+                    packages_per_file.len()
+                )
+                .as_str(),
+            )
+            .await;
+
+        Ok(())
+    }
+}
+
+impl<'a, C, S: ?Sized> DirectoryScanCommand<'a, C, S>
+where
+    C: LSPClient + Sync,
+    S: FilesystemScanner,
+{
+    /// Text of the package file, as edited if it's open. Files that can't be read get
+    /// their diagnostics at the start.
+    async fn manifest_text(&self, uri: &Url, path: &Path) -> String {
+        if let Some(text) = self.interactor.read_document_text(uri.as_str()).await {
+            return text;
+        }
+        tokio::fs::read_to_string(path).await.unwrap_or_else(|e| {
+            tracing::debug!("unable to read the package file {}: {e}", path.display());
+            String::new()
+        })
+    }
+}
+
+/// File a package was found in, for the paths the scanner reports relative to the
+/// scanned directory (with or without a leading `/`) as well as under it.
+fn manifest_path(directory: &Path, package_path: &str) -> PathBuf {
+    let path = Path::new(package_path);
+    if path.starts_with(directory) {
+        return path.to_path_buf();
+    }
+    directory.join(package_path.trim_start_matches('/'))
+}
+
+fn package_diagnostic(text: &str, package: &Package) -> Diagnostic {
+    let vulnerabilities = package.vulnerabilities();
+    let mut counts: Vec<(Severity, usize)> = vec![];
+    for vulnerability in &vulnerabilities {
+        match counts
+            .iter_mut()
+            .find(|(severity, _)| *severity == vulnerability.severity())
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((vulnerability.severity(), 1)),
+        }
+    }
+    counts.sort();
+
+    let summary = counts
+        .iter()
+        .map(|(severity, count)| format!("{count} {severity}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let fix = package
+        .suggested_fix_version()
+        .map(|version| format!(", fixed in {version}"))
+        .unwrap_or_default();
+
+    Diagnostic {
+        range: package_range(text, package.name()),
+        severity: counts
+            .first()
+            .map(|(severity, _)| diagnostic_severity_for(*severity)),
+        message: format!(
+            "{} {}: {} vulnerabilities ({summary}){fix}",
+            package.name(),
+            package.version(),
+            vulnerabilities.len()
+        ),
+        source: Some(FILESYSTEM_DIAGNOSTIC_SOURCE.to_owned()),
+        data: Some(serde_json::json!({
+            CVES_DATA_KEY: cve_ids(&vulnerabilities),
+            PACKAGES_DATA_KEY: [{ "name": package.name(), "version": package.version() }],
+        })),
+        ..Default::default()
+    }
+}
+
+/// First mention of the package name in the file, e.g. `"node_modules/lodash"` in a
+/// `package-lock.json` or `requests==2.31.0` in a `requirements.txt`, not counting the
+/// names containing it (`lodash.merge`).
+fn package_range(text: &str, name: &str) -> Range {
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, '-' | '_' | '.');
+    for (line_number, line) in text.lines().enumerate() {
+        for (start, _) in line.match_indices(name) {
+            let end = start + name.len();
+            let bounded_before = !line[..start].chars().next_back().is_some_and(is_name_char);
+            let bounded_after = !line[end..].chars().next().is_some_and(is_name_char);
+            if bounded_before && bounded_after {
+                let character = |byte: usize| line[..byte].encode_utf16().count() as u32;
+                return Range::new(
+                    Position::new(line_number as u32, character(start)),
+                    Position::new(line_number as u32, character(end)),
+                );
+            }
+        }
+    }
+    Range::default()
+}
+
+fn diagnostic_severity_for(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Critical | Severity::High => DiagnosticSeverity::ERROR,
+        Severity::Medium => DiagnosticSeverity::WARNING,
+        Severity::Low | Severity::Negligible | Severity::Unknown => DiagnosticSeverity::INFORMATION,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_resolves_the_package_files_against_the_scanned_directory() {
+        let directory = Path::new("/workspace/app");
+
+        assert_eq!(
+            manifest_path(directory, "/workspace/app/web/package-lock.json"),
+            PathBuf::from("/workspace/app/web/package-lock.json")
+        );
+        assert_eq!(
+            manifest_path(directory, "/requirements.txt"),
+            PathBuf::from("/workspace/app/requirements.txt")
+        );
+        assert_eq!(
+            manifest_path(directory, "api/go.sum"),
+            PathBuf::from("/workspace/app/api/go.sum")
+        );
+    }
+
+    #[test]
+    fn it_finds_the_first_mention_of_the_package_name() {
+        let lockfile = "{\n  \"packages\": {\n    \"node_modules/lodash.merge\": {},\n    \"node_modules/lodash\": {}\n  }\n}";
+        assert_eq!(
+            package_range(lockfile, "lodash"),
+            Range::new(Position::new(3, 18), Position::new(3, 24))
+        );
+
+        let requirements = "flask==3.0.0\nrequests==2.19.0\n";
+        assert_eq!(
+            package_range(requirements, "requests"),
+            Range::new(Position::new(1, 0), Position::new(1, 8))
+        );
+
+        assert_eq!(package_range(requirements, "django"), Range::default());
+    }
+}
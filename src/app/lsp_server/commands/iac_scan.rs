@@ -10,7 +10,7 @@ use crate::{
     domain::iacscanresult::iac_severity::IacSeverity,
 };
 
-use super::{IAC_DIAGNOSTIC_SOURCE, LspCommand};
+use super::{IAC_DIAGNOSTIC_SOURCE, LspCommand, directory_uri_prefix};
 
 pub struct IacScanCommand<'a, C, S: ?Sized>
 where
@@ -94,19 +94,7 @@ where
         // beyond it, so results for files outside the root are preserved.
         let scope_key = match &self.scope {
             IacScanScope::File { uri, .. } => uri.to_string(),
-            IacScanScope::Directory(root) => Url::from_file_path(root)
-                .map(|u| {
-                    let uri = String::from(u);
-                    // A root of `/` already yields a trailing slash (`file:///`).
-                    if uri.ends_with('/') {
-                        uri
-                    } else {
-                        format!("{uri}/")
-                    }
-                })
-                // An empty prefix matches every document: falling back to the
-                // previous whole-database refresh is safe, just broader.
-                .unwrap_or_default(),
+            IacScanScope::Directory(root) => directory_uri_prefix(root),
         };
         let scope = match &self.scope {
             IacScanScope::File { .. } => DiagnosticsScope::Document(&scope_key),
@@ -1,12 +1,104 @@
 pub mod build_and_scan;
+pub mod diagnostics_factory;
+pub mod directory_scan;
 pub mod iac_scan;
+pub mod scan_all_images;
 pub mod scan_base_image;
 
-use tower_lsp::jsonrpc::Result;
+use std::path::Path;
+use tower_lsp::jsonrpc::{self, Result};
 
-pub use crate::app::{IAC_DIAGNOSTIC_SOURCE, VULN_DIAGNOSTIC_SOURCE};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Range, Url};
+
+use crate::app::{ImageScanError, lsp_server::WithContext};
+use crate::domain::scanresult::scan_result::ScanResult;
+
+pub use crate::app::{
+    FILESYSTEM_DIAGNOSTIC_SOURCE, IAC_DIAGNOSTIC_SOURCE, POLICY_DIAGNOSTIC_SOURCE,
+    VULN_DIAGNOSTIC_SOURCE,
+};
 
 #[async_trait::async_trait]
 pub trait LspCommand {
     async fn execute(&mut self) -> Result<()>;
 }
+
+/// Appended to the image summary of scans whose policies weren't evaluated, so the
+/// missing verdict isn't mistaken for a pass.
+pub const POLICIES_NOT_EVALUATED_NOTE: &str = " (offline scan, policies not evaluated)";
+
+/// Error answering a command whose image scan failed. Failures users fix on their
+/// side, the credentials or the image reference, are reported as invalid params.
+pub fn image_scan_error(image: &str, error: ImageScanError) -> jsonrpc::Error {
+    let message = format!("scan of {image} failed: {error}");
+    match error {
+        ImageScanError::AuthFailed(_) | ImageScanError::ImageNotFound(_) => {
+            jsonrpc::Error::invalid_params(message)
+        }
+        ImageScanError::RateLimited(_)
+        | ImageScanError::NetworkError(_)
+        | ImageScanError::MalformedOutput(_)
+        | ImageScanError::InternalScannerError(_) => {
+            jsonrpc::Error::internal_error().with_message(message)
+        }
+    }
+}
+
+/// URI prefix of the documents under `root`, to refresh the diagnostics of a directory
+/// scan for every file under the scanned root, but not beyond it.
+pub fn directory_uri_prefix(root: &Path) -> String {
+    Url::from_file_path(root)
+        .map(|u| {
+            let uri = String::from(u);
+            // A root of `/` already yields a trailing slash (`file:///`).
+            if uri.ends_with('/') {
+                uri
+            } else {
+                format!("{uri}/")
+            }
+        })
+        // An empty prefix matches every document: falling back to the
+        // previous whole-database refresh is safe, just broader.
+        .unwrap_or_default()
+}
+
+/// Data of the policy diagnostics holding the names of the failed policies.
+pub const FAILED_POLICIES_DATA_KEY: &str = "failedPolicies";
+
+/// Diagnostic reporting the policy verdict of a scanned image, if any policy failed.
+/// It is kept apart from the vulnerability summary so users can filter or re-rank
+/// policy failures independently in their editor.
+pub fn policy_diagnostic(range: Range, scan_result: &ScanResult) -> Option<Diagnostic> {
+    if !scan_result.evaluation_result().is_failed() {
+        return None;
+    }
+
+    let failed_policies = scan_result
+        .policies()
+        .iter()
+        .filter(|policy| policy.evaluation_result().is_failed())
+        .map(|policy| policy.name().to_string())
+        .collect::<Vec<_>>();
+
+    let message = if failed_policies.is_empty() {
+        format!(
+            "Policy evaluation failed for {}",
+            scan_result.metadata().pull_string()
+        )
+    } else {
+        format!(
+            "Policy evaluation failed for {}: {}",
+            scan_result.metadata().pull_string(),
+            failed_policies.join(", ")
+        )
+    };
+
+    Some(Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        message,
+        source: Some(POLICY_DIAGNOSTIC_SOURCE.to_owned()),
+        data: Some(serde_json::json!({ FAILED_POLICIES_DATA_KEY: failed_policies })),
+        ..Default::default()
+    })
+}
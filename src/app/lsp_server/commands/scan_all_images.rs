@@ -0,0 +1,208 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::{sync::Semaphore, task::JoinSet};
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::{Diagnostic, MessageType, Url};
+use tracing::warn;
+
+use crate::{
+    app::{
+        DiagnosticsScope, DocumentScanResult, Documentation, LSPClient, LspInteractor,
+        PolicyVerdict, SysdigIgnore,
+        component_factory::{Components, SeverityMapping},
+        lsp_server::{WithContext, command_generator::ImageReference},
+        markdown::MarkdownData,
+        protocol::ScanResultParams,
+    },
+    domain::scanresult::evaluation_result::EvaluationResult,
+};
+
+use super::{
+    LspCommand, POLICY_DIAGNOSTIC_SOURCE, VULN_DIAGNOSTIC_SOURCE,
+    diagnostics_factory::DiagnosticsFactory,
+    policy_diagnostic,
+    scan_base_image::{most_severe_vulnerability, vulnerability_diagnostic},
+};
+
+/// Number of images of a document scanned at the same time.
+pub const MAX_CONCURRENT_IMAGE_SCANS: usize = 4;
+
+/// Scans every image referenced in a document (e.g. the services of a Compose file)
+/// concurrently, publishing the diagnostics of each image as soon as it's scanned.
+pub struct ScanAllImagesCommand<'a, C> {
+    components: Arc<Components>,
+    interactor: &'a LspInteractor<C>,
+    uri: Url,
+    images: Vec<ImageReference>,
+    sysdig_ignore: &'a SysdigIgnore,
+    severity_mapping: Option<SeverityMapping>,
+    scanned: Vec<(ImageReference, EvaluationResult)>,
+}
+
+impl<'a, C> ScanAllImagesCommand<'a, C> {
+    pub fn new(
+        components: Arc<Components>,
+        interactor: &'a LspInteractor<C>,
+        uri: Url,
+        images: Vec<ImageReference>,
+        sysdig_ignore: &'a SysdigIgnore,
+    ) -> Self {
+        Self {
+            components,
+            interactor,
+            uri,
+            images,
+            sysdig_ignore,
+            severity_mapping: None,
+            scanned: vec![],
+        }
+    }
+
+    /// Severity of the vulnerability summaries by their most severe vulnerability
+    /// (`sysdig.diagnostics.severity_mapping`).
+    pub fn with_severity_mapping(self, severity_mapping: Option<SeverityMapping>) -> Self {
+        Self {
+            severity_mapping,
+            ..self
+        }
+    }
+
+    /// Reference and policy evaluation of every image scanned successfully.
+    pub fn scanned(&self) -> &[(ImageReference, EvaluationResult)] {
+        &self.scanned
+    }
+}
+
+#[async_trait::async_trait]
+impl<'a, C> LspCommand for ScanAllImagesCommand<'a, C>
+where
+    C: LSPClient + Sync,
+{
+    async fn execute(&mut self) -> Result<()> {
+        let uri = self.uri.as_str();
+        let total = self.images.len();
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                &format!("Starting scan of {total} images..."),
+            )
+            .await;
+
+        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_IMAGE_SCANS));
+        let mut scans = JoinSet::new();
+        for reference in self.images.iter().cloned() {
+            let (components, semaphore, document) =
+                (self.components.clone(), semaphore.clone(), self.uri.clone());
+            scans.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let result = components
+                    .scanner_for(&document)
+                    .scan_image_for_platform(&reference.image, reference.platform.as_deref())
+                    .await
+                    .map_err(|e| e.to_string());
+                (reference, result)
+            });
+        }
+
+        // Every image of the document is rescanned, so previous results are dropped
+        // up front and the new ones accumulated as they arrive.
+        self.interactor.remove_documentations(uri).await;
+        let diagnostics_factory =
+            DiagnosticsFactory::new(self.sysdig_ignore, self.severity_mapping);
+        let mut vulnerability_diagnostics: Vec<Diagnostic> = vec![];
+        let mut policy_diagnostics: Vec<Diagnostic> = vec![];
+        let mut failures = vec![];
+        while let Some(scan) = scans.join_next().await {
+            let (reference, scan_result) = match scan {
+                Ok((reference, Ok(scan_result))) => (reference, scan_result),
+                Ok((reference, Err(e))) => {
+                    warn!("scan of {} failed: {e}", reference.image);
+                    failures.push(format!("{}: {e}", reference.image));
+                    continue;
+                }
+                Err(e) => {
+                    failures.push(e.to_string());
+                    continue;
+                }
+            };
+
+            vulnerability_diagnostics.push(vulnerability_diagnostic(
+                reference.range,
+                &reference.image,
+                &scan_result,
+                &diagnostics_factory,
+            ));
+            policy_diagnostics.extend(policy_diagnostic(reference.range, &scan_result));
+            for (source, diagnostics) in [
+                (VULN_DIAGNOSTIC_SOURCE, &vulnerability_diagnostics),
+                (POLICY_DIAGNOSTIC_SOURCE, &policy_diagnostics),
+            ] {
+                self.interactor
+                    .replace_diagnostics_with_source(
+                        source,
+                        DiagnosticsScope::Document(uri),
+                        HashMap::from([(uri.to_owned(), diagnostics.clone())]),
+                    )
+                    .await;
+            }
+            self.interactor.publish_all_diagnostics().await?;
+            self.interactor
+                .write_image_severity(
+                    &reference.image,
+                    most_severe_vulnerability(&scan_result, self.sysdig_ignore),
+                )
+                .await;
+
+            self.interactor
+                .write_last_scan_result(
+                    uri,
+                    DocumentScanResult {
+                        scan_result: scan_result.clone(),
+                        image_range: reference.range,
+                        layer_ranges: HashMap::new(),
+                    },
+                )
+                .await;
+            self.interactor
+                .send_scan_result(ScanResultParams::new(
+                    self.uri.clone(),
+                    reference.range,
+                    &scan_result,
+                ))
+                .await;
+            self.scanned
+                .push((reference.clone(), scan_result.evaluation_result()));
+            let suppressed_vulns = self.sysdig_ignore.suppressed(&scan_result);
+            let verdict = PolicyVerdict::of(&scan_result);
+            let scanned_at = scan_result.metadata().scanned_at();
+            self.interactor
+                .append_documentation(
+                    uri,
+                    Documentation::new(
+                        reference.range,
+                        MarkdownData::from(scan_result)
+                            .with_suppressed_vulns(suppressed_vulns)
+                            .to_string(),
+                    )
+                    .with_verdict(verdict)
+                    .with_scanned_at(scanned_at),
+                )
+                .await;
+        }
+
+        if !failures.is_empty() {
+            return Err(Error::internal_error().with_message(format!(
+                "{} of {total} image scans failed: {}",
+                failures.len(),
+                failures.join("; ")
+            )));
+        }
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                &format!("Finished scan of {total} images."),
+            )
+            .await;
+        Ok(())
+    }
+}
@@ -1,17 +1,27 @@
 use std::collections::HashMap;
 
-use itertools::Itertools;
-use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Location, MessageType};
+use serde_json::json;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Location, MessageType, Range};
 
 use crate::{
     app::{
-        DiagnosticsScope, ImageScanner, LSPClient, LspInteractor, lsp_server::WithContext,
+        DiagnosticsScope, DocumentScanResult, Documentation, ImageScanner, LSPClient,
+        LspInteractor, PolicyVerdict, SysdigIgnore,
+        component_factory::SeverityMapping,
         markdown::MarkdownData,
+        protocol::{ScanResultParams, SeverityCounts},
+    },
+    domain::scanresult::{
+        evaluation_result::EvaluationResult, scan_result::ScanResult, severity::Severity,
     },
-    domain::scanresult::severity::Severity,
 };
 
-use super::{LspCommand, VULN_DIAGNOSTIC_SOURCE};
+use super::{
+    LspCommand, POLICIES_NOT_EVALUATED_NOTE, POLICY_DIAGNOSTIC_SOURCE, VULN_DIAGNOSTIC_SOURCE,
+    build_and_scan::{CVES_DATA_KEY, cve_ids},
+    diagnostics_factory::DiagnosticsFactory,
+    image_scan_error, policy_diagnostic,
+};
 
 pub struct ScanBaseImageCommand<'a, C, S: ?Sized>
 where
@@ -21,6 +31,10 @@ where
     interactor: &'a LspInteractor<C>,
     location: Location,
     image: String,
+    platform: Option<String>,
+    sysdig_ignore: &'a SysdigIgnore,
+    severity_mapping: Option<SeverityMapping>,
+    evaluation_result: Option<EvaluationResult>,
 }
 
 impl<'a, C, S: ?Sized> ScanBaseImageCommand<'a, C, S>
@@ -32,14 +46,39 @@ where
         interactor: &'a LspInteractor<C>,
         location: Location,
         image: String,
+        sysdig_ignore: &'a SysdigIgnore,
     ) -> Self {
         Self {
             image_scanner,
             interactor,
             location,
             image,
+            platform: None,
+            sysdig_ignore,
+            severity_mapping: None,
+            evaluation_result: None,
         }
     }
+
+    /// Scans the variant of the image for `platform` (e.g. `linux/arm64`) instead of the
+    /// default one.
+    pub fn with_platform(self, platform: Option<String>) -> Self {
+        Self { platform, ..self }
+    }
+
+    /// Severity of the vulnerability summary by its most severe vulnerability
+    /// (`sysdig.diagnostics.severity_mapping`).
+    pub fn with_severity_mapping(self, severity_mapping: Option<SeverityMapping>) -> Self {
+        Self {
+            severity_mapping,
+            ..self
+        }
+    }
+
+    /// Policy evaluation of the scanned image, once the command executed successfully.
+    pub fn evaluation_result(&self) -> Option<EvaluationResult> {
+        self.evaluation_result
+    }
 }
 
 #[async_trait::async_trait]
@@ -50,18 +89,22 @@ where
 {
     async fn execute(&mut self) -> tower_lsp::jsonrpc::Result<()> {
         let image_name = &self.image;
+        let platform_suffix = self
+            .platform
+            .as_ref()
+            .map_or_else(String::new, |platform| format!(" ({platform})"));
         self.interactor
             .show_message(
                 MessageType::INFO,
-                format!("Starting scan of {image_name}...").as_str(),
+                format!("Starting scan of {image_name}{platform_suffix}...").as_str(),
             )
             .await;
 
         let scan_result = self
             .image_scanner
-            .scan_image(image_name)
+            .scan_image_for_platform(image_name, self.platform.as_deref())
             .await
-            .map_err(|e| tower_lsp::jsonrpc::Error::internal_error().with_message(e.to_string()))?;
+            .map_err(|e| image_scan_error(image_name, e))?;
 
         self.interactor
             .show_message(
@@ -70,48 +113,17 @@ where
             )
             .await;
 
-        let diagnostic = {
-            let mut diagnostic = Diagnostic {
-                range: self.location.range,
-                severity: Some(DiagnosticSeverity::HINT),
-                message: "No vulnerabilities found.".to_owned(),
-                source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
-                ..Default::default()
-            };
-
-            if !scan_result.vulnerabilities().is_empty() {
-                let vulns = scan_result
-                    .vulnerabilities()
-                    .iter()
-                    .counts_by(|v| v.severity());
-                let critical_count = vulns.get(&Severity::Critical).unwrap_or(&0_usize);
-                let high_count = vulns.get(&Severity::High).unwrap_or(&0_usize);
-                let medium_count = vulns.get(&Severity::Medium).unwrap_or(&0_usize);
-                let low_count = vulns.get(&Severity::Low).unwrap_or(&0_usize);
-                let negligible_count = vulns.get(&Severity::Negligible).unwrap_or(&0_usize);
-
-                diagnostic.message = format!(
-                    "Vulnerabilities found for {}: {} Critical, {} High, {} Medium, {} Low, {} Negligible",
-                    image_name,
-                    critical_count,
-                    high_count,
-                    medium_count,
-                    low_count,
-                    negligible_count,
-                );
-
-                // Determine severity based on vulnerability counts, not just policy evaluation
-                diagnostic.severity = Some(if *critical_count > 0 || *high_count > 0 {
-                    DiagnosticSeverity::ERROR
-                } else if *medium_count > 0 {
-                    DiagnosticSeverity::WARNING
-                } else {
-                    DiagnosticSeverity::INFORMATION
-                });
-            }
-
-            diagnostic
-        };
+        let diagnostic = vulnerability_diagnostic(
+            self.location.range,
+            image_name,
+            &scan_result,
+            &DiagnosticsFactory::new(self.sysdig_ignore, self.severity_mapping),
+        );
+        let policy_diagnostics: Vec<_> = policy_diagnostic(self.location.range, &scan_result)
+            .into_iter()
+            .collect();
+
+        self.evaluation_result = Some(scan_result.evaluation_result());
 
         let uri = self.location.uri.as_str();
         self.interactor.remove_documentations(uri).await;
@@ -122,14 +134,115 @@ where
                 HashMap::from([(uri.to_owned(), vec![diagnostic])]),
             )
             .await;
+        self.interactor
+            .replace_diagnostics_with_source(
+                POLICY_DIAGNOSTIC_SOURCE,
+                DiagnosticsScope::Document(uri),
+                HashMap::from([(uri.to_owned(), policy_diagnostics)]),
+            )
+            .await;
         self.interactor.publish_all_diagnostics().await?;
+        self.interactor
+            .write_image_severity(
+                image_name,
+                most_severe_vulnerability(&scan_result, self.sysdig_ignore),
+            )
+            .await;
+        // The layers belong to the base image, so they aren't matched to any instruction.
+        self.interactor
+            .write_last_scan_result(
+                uri,
+                DocumentScanResult {
+                    scan_result: scan_result.clone(),
+                    image_range: self.location.range,
+                    layer_ranges: HashMap::new(),
+                },
+            )
+            .await;
+        self.interactor
+            .send_scan_result(ScanResultParams::new(
+                self.location.uri.clone(),
+                self.location.range,
+                &scan_result,
+            ))
+            .await;
+        let suppressed_vulns = self.sysdig_ignore.suppressed(&scan_result);
+        let verdict = PolicyVerdict::of(&scan_result);
+        let scanned_at = scan_result.metadata().scanned_at();
         self.interactor
             .append_documentation(
                 self.location.uri.as_str(),
-                self.location.range,
-                MarkdownData::from(scan_result).to_string(),
+                Documentation::new(
+                    self.location.range,
+                    MarkdownData::from(scan_result)
+                        .with_suppressed_vulns(suppressed_vulns)
+                        .to_string(),
+                )
+                .with_verdict(verdict)
+                .with_scanned_at(scanned_at),
             )
             .await;
         Ok(())
     }
 }
+
+/// Most severe vulnerability of the scan reported in the diagnostics, if any.
+pub fn most_severe_vulnerability(
+    scan_result: &ScanResult,
+    sysdig_ignore: &SysdigIgnore,
+) -> Option<Severity> {
+    sysdig_ignore
+        .reported(scan_result, scan_result.vulnerabilities())
+        .iter()
+        .map(|vuln| vuln.severity())
+        .min()
+}
+
+/// Key of the `data` of image summary diagnostics holding the scanned image reference.
+pub const IMAGE_DATA_KEY: &str = "image";
+/// Key of the `data` of image summary diagnostics holding the image digest reported by
+/// the scanner, if any, used to pin the reference without querying the registry.
+pub const IMAGE_DIGEST_DATA_KEY: &str = "imageDigest";
+/// Key of the `data` of image summary diagnostics holding the reported vulnerabilities
+/// by severity, whatever severity the summary was given.
+pub const VULNERABILITIES_DATA_KEY: &str = "vulnerabilities";
+
+/// Summary of the vulnerabilities of a scanned image, reported at its reference.
+pub fn vulnerability_diagnostic(
+    range: Range,
+    image_name: &str,
+    scan_result: &ScanResult,
+    diagnostics: &DiagnosticsFactory,
+) -> Diagnostic {
+    let vulnerabilities = diagnostics
+        .sysdig_ignore()
+        .reported(scan_result, scan_result.vulnerabilities());
+    let mut diagnostic = if vulnerabilities.is_empty() {
+        Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::HINT),
+            message: "No vulnerabilities found.".to_owned(),
+            source: Some(VULN_DIAGNOSTIC_SOURCE.to_owned()),
+            data: Some(json!({ CVES_DATA_KEY: cve_ids(&vulnerabilities) })),
+            ..Default::default()
+        }
+    } else {
+        // Based on the vulnerabilities found, not just on the policy evaluation.
+        diagnostics.vulnerability_summary(
+            range,
+            &format!("Vulnerabilities found for {image_name}"),
+            &vulnerabilities,
+            None,
+        )
+    };
+    if let Some(data) = diagnostic.data.as_mut() {
+        data[IMAGE_DATA_KEY] = json!(image_name);
+        data[IMAGE_DIGEST_DATA_KEY] = json!(scan_result.metadata().digest());
+        data[VULNERABILITIES_DATA_KEY] = json!(SeverityCounts::from(vulnerabilities.as_slice()));
+    }
+    if scan_result.evaluation_result() == EvaluationResult::NotEvaluated {
+        diagnostic.message.push_str(POLICIES_NOT_EVALUATED_NOTE);
+    }
+
+    diagnostic
+}
@@ -0,0 +1,440 @@
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Position, Range, TextEdit,
+};
+
+use crate::app::{ImageTag, split_image_tag};
+use crate::infra::parse_dockerfile;
+
+use super::command_generator::DocumentKind;
+
+/// Characters that make clients request completions right away.
+pub const COMPLETION_TRIGGER_CHARACTERS: [&str; 1] = [":"];
+
+/// An image whose tag is being typed at the cursor, in a `FROM` instruction or in the
+/// `image:` of a Compose service.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TagCompletionContext {
+    /// Image name without the tag, e.g. `nginx` or `ghcr.io/org/app`.
+    pub repository: String,
+    /// Range of the tag typed so far, replaced by the completion.
+    pub range: Range,
+}
+
+pub fn tag_completion_context(
+    kind: DocumentKind,
+    content: &str,
+    position: Position,
+) -> Option<TagCompletionContext> {
+    let line = content.lines().nth(position.line as usize)?;
+    let before_cursor: String = line.chars().take(position.character as usize).collect();
+
+    let image = match kind {
+        DocumentKind::Compose => compose_image_before_cursor(&before_cursor)?,
+        DocumentKind::Dockerfile => from_image_before_cursor(&before_cursor)?,
+        _ => return None,
+    };
+
+    let (repository, tag) = split_image_tag(image);
+    let tag = tag?;
+    if repository.ends_with('/') || repository.is_empty() || image.contains('@') {
+        return None;
+    }
+
+    let cursor = before_cursor.chars().count() as u32;
+    let tag_start = cursor - tag.chars().count() as u32;
+    Some(TagCompletionContext {
+        repository: repository.to_string(),
+        range: Range::new(
+            Position::new(position.line, tag_start),
+            Position::new(position.line, cursor),
+        ),
+    })
+}
+
+/// Image being typed as the first argument of `FROM`, after its flags.
+fn from_image_before_cursor(before_cursor: &str) -> Option<&str> {
+    if before_cursor.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let mut arguments = before_cursor.split_whitespace();
+    if !arguments.next()?.eq_ignore_ascii_case("FROM") {
+        return None;
+    }
+    let mut arguments = arguments.filter(|argument| !argument.starts_with("--"));
+    let image = arguments.next()?;
+    arguments.next().is_none().then_some(image)
+}
+
+/// Image being typed as the value of an `image:` key.
+fn compose_image_before_cursor(before_cursor: &str) -> Option<&str> {
+    let value = before_cursor
+        .trim_start()
+        .strip_prefix("image:")?
+        .trim_start();
+    let value = value.trim_start_matches(['"', '\'']);
+    (!value.is_empty() && !value.contains(char::is_whitespace)).then_some(value)
+}
+
+/// Tags in the order the registry listed them, with their digest as detail.
+pub fn tag_completion_items(
+    context: &TagCompletionContext,
+    tags: Vec<ImageTag>,
+) -> Vec<CompletionItem> {
+    tags.into_iter()
+        .enumerate()
+        .map(|(index, tag)| CompletionItem {
+            label: tag.name.clone(),
+            kind: Some(CompletionItemKind::VALUE),
+            detail: tag.digest,
+            sort_text: Some(format!("{index:04}")),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+                context.range,
+                tag.name,
+            ))),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Dockerfile instructions, with the summary shown as their detail.
+const DOCKERFILE_KEYWORDS: [(&str, &str); 17] = [
+    ("ADD", "Add local or remote files and directories"),
+    ("ARG", "Use build-time variables"),
+    ("CMD", "Specify default commands"),
+    ("COPY", "Copy files and directories"),
+    ("ENTRYPOINT", "Specify default executable"),
+    ("ENV", "Set environment variables"),
+    (
+        "EXPOSE",
+        "Describe which ports your application is listening on",
+    ),
+    ("FROM", "Create a new build stage from a base image"),
+    ("HEALTHCHECK", "Check a container's health on startup"),
+    ("LABEL", "Add metadata to an image"),
+    (
+        "ONBUILD",
+        "Specify instructions for when the image is used in a build",
+    ),
+    ("RUN", "Execute build commands"),
+    ("SHELL", "Set the default shell of an image"),
+    (
+        "STOPSIGNAL",
+        "Specify the system call signal for exiting a container",
+    ),
+    ("USER", "Set user and group ID"),
+    ("VOLUME", "Create volume mounts"),
+    ("WORKDIR", "Change working directory"),
+];
+
+/// Flags accepted by the instructions that have any, inserted up to their `=`.
+const DOCKERFILE_FLAGS: [(&str, &[&str]); 5] = [
+    ("FROM", &["--platform="]),
+    ("RUN", &["--mount=", "--network=", "--security="]),
+    (
+        "COPY",
+        &[
+            "--from=",
+            "--chown=",
+            "--chmod=",
+            "--link",
+            "--parents",
+            "--exclude=",
+        ],
+    ),
+    (
+        "ADD",
+        &[
+            "--chown=",
+            "--chmod=",
+            "--link",
+            "--checksum=",
+            "--keep-git-dir=",
+            "--exclude=",
+        ],
+    ),
+    (
+        "HEALTHCHECK",
+        &[
+            "--interval=",
+            "--timeout=",
+            "--start-period=",
+            "--start-interval=",
+            "--retries=",
+        ],
+    ),
+];
+
+/// What `HEALTHCHECK` accepts after its flags.
+const HEALTHCHECK_ARGUMENTS: [(&str, &str); 2] = [
+    ("CMD", "Command run to check the container's health"),
+    (
+        "NONE",
+        "Disable any healthcheck inherited from the base image",
+    ),
+];
+
+/// Instruction keywords at the start of an instruction, and the flags of the
+/// instruction at the cursor while they're typed before its other arguments.
+pub fn dockerfile_completion_items(
+    kind: DocumentKind,
+    content: &str,
+    position: Position,
+) -> Option<Vec<CompletionItem>> {
+    if kind != DocumentKind::Dockerfile {
+        return None;
+    }
+    let line = content.lines().nth(position.line as usize).unwrap_or("");
+    let before_cursor: String = line.chars().take(position.character as usize).collect();
+    if before_cursor.trim_start().starts_with('#') {
+        return None;
+    }
+
+    let word = before_cursor
+        .rsplit(char::is_whitespace)
+        .next()
+        .unwrap_or_default();
+    let cursor = before_cursor.chars().count() as u32;
+    let range = Range::new(
+        Position::new(position.line, cursor - word.chars().count() as u32),
+        position,
+    );
+
+    // Lines continued with `\` belong to the instruction started on a previous line.
+    let instruction_start = parse_dockerfile(content)
+        .into_iter()
+        .find(|instruction| {
+            (instruction.range.start.line..=instruction.range.end.line).contains(&position.line)
+        })
+        .map_or(position.line, |instruction| instruction.range.start.line);
+    let instruction: Vec<&str> = content
+        .lines()
+        .skip(instruction_start as usize)
+        .take((position.line - instruction_start) as usize)
+        .chain(std::iter::once(before_cursor.as_str()))
+        .flat_map(|line| line.trim_end().trim_end_matches('\\').split_whitespace())
+        .collect();
+
+    let Some((keyword, arguments)) = instruction.split_first() else {
+        return Some(keyword_items(range));
+    };
+    if arguments.is_empty() && !before_cursor.ends_with(char::is_whitespace) {
+        return Some(keyword_items(range));
+    }
+
+    // Flags go before any other argument of the instruction.
+    let previous_arguments = match word.is_empty() {
+        true => arguments,
+        false => &arguments[..arguments.len() - 1],
+    };
+    if !previous_arguments.iter().all(|arg| arg.starts_with("--")) {
+        return None;
+    }
+    let keyword = keyword.to_uppercase();
+    if word.starts_with('-') && !word.contains('=') {
+        let (_, flags) = DOCKERFILE_FLAGS
+            .iter()
+            .find(|(instruction, _)| *instruction == keyword)?;
+        return Some(
+            flags
+                .iter()
+                .map(|flag| item(flag, None, CompletionItemKind::PROPERTY, range))
+                .collect(),
+        );
+    }
+    (keyword == "HEALTHCHECK").then(|| {
+        HEALTHCHECK_ARGUMENTS
+            .iter()
+            .map(|(argument, detail)| {
+                item(argument, Some(detail), CompletionItemKind::KEYWORD, range)
+            })
+            .collect()
+    })
+}
+
+fn keyword_items(range: Range) -> Vec<CompletionItem> {
+    DOCKERFILE_KEYWORDS
+        .iter()
+        .map(|(keyword, detail)| item(keyword, Some(detail), CompletionItemKind::KEYWORD, range))
+        .collect()
+}
+
+fn item(
+    label: &str,
+    detail: Option<&str>,
+    kind: CompletionItemKind,
+    range: Range,
+) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail: detail.map(str::to_string),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(
+            range,
+            label.to_string(),
+        ))),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn context_at(uri: &str, content: &str, character: u32) -> Option<TagCompletionContext> {
+        let kind = DocumentKind::detect(&uri.parse().unwrap(), content, None);
+        tag_completion_context(kind, content, Position::new(0, character))
+    }
+
+    #[rstest]
+    #[case("FROM nginx:", "nginx", 11)]
+    #[case("FROM nginx:1.2", "nginx", 11)]
+    #[case("from --platform=linux/amd64 nginx:", "nginx", 34)]
+    #[case("FROM localhost:5000/app:", "localhost:5000/app", 24)]
+    fn it_completes_tags_in_from_instructions(
+        #[case] line: &str,
+        #[case] repository: &str,
+        #[case] tag_start: u32,
+    ) {
+        let context = context_at("file:///Dockerfile", line, line.len() as u32).unwrap();
+
+        assert_eq!(context.repository, repository);
+        assert_eq!(
+            context.range,
+            Range::new(
+                Position::new(0, tag_start),
+                Position::new(0, line.len() as u32)
+            )
+        );
+    }
+
+    #[rstest]
+    #[case("FROM nginx")]
+    #[case("FROM localhost:5000/app")]
+    #[case("FROM nginx@sha256:")]
+    #[case("FROM nginx:latest AS ")]
+    #[case("RUN echo a:")]
+    fn it_doesnt_complete_outside_of_from_image_tags(#[case] line: &str) {
+        assert_eq!(
+            context_at("file:///Dockerfile", line, line.len() as u32),
+            None
+        );
+    }
+
+    #[test]
+    fn it_completes_tags_in_compose_images() {
+        let content = "    image: \"postgres:1";
+
+        let context = context_at("file:///docker-compose.yml", content, 22).unwrap();
+
+        assert_eq!(context.repository, "postgres");
+        assert_eq!(context.range.start, Position::new(0, 21));
+    }
+
+    #[test]
+    fn it_keeps_the_registry_order_of_tags() {
+        let context = TagCompletionContext {
+            repository: "nginx".to_string(),
+            range: Range::default(),
+        };
+        let tags = vec![
+            ImageTag {
+                name: "1.27".to_string(),
+                digest: Some("sha256:abc".to_string()),
+            },
+            ImageTag {
+                name: "1.26".to_string(),
+                digest: None,
+            },
+        ];
+
+        let items = tag_completion_items(&context, tags);
+
+        assert_eq!(items[0].label, "1.27");
+        assert_eq!(items[0].detail.as_deref(), Some("sha256:abc"));
+        assert!(items[0].sort_text < items[1].sort_text);
+    }
+
+    fn labels_at(content: &str, position: Position) -> Option<Vec<String>> {
+        dockerfile_completion_items(DocumentKind::Dockerfile, content, position)
+            .map(|items| items.into_iter().map(|item| item.label).collect())
+    }
+
+    #[rstest]
+    #[case("", 0)]
+    #[case("FR", 2)]
+    #[case("  ru", 4)]
+    fn it_completes_keywords_at_the_start_of_instructions(
+        #[case] line: &str,
+        #[case] character: u32,
+    ) {
+        let items = dockerfile_completion_items(
+            DocumentKind::Dockerfile,
+            line,
+            Position::new(0, character),
+        )
+        .unwrap();
+
+        assert!(items.iter().any(|item| item.label == "FROM"));
+        assert!(items.iter().any(|item| item.label == "HEALTHCHECK"));
+        let Some(CompletionTextEdit::Edit(edit)) = &items[0].text_edit else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(
+            edit.range.start.character,
+            character - line.trim_start().len() as u32
+        );
+    }
+
+    #[rstest]
+    #[case("COPY --", 7, "--chown=")]
+    #[case("COPY --from=build --ch", 22, "--chown=")]
+    #[case("HEALTHCHECK --", 14, "--interval=")]
+    #[case("run --m", 7, "--mount=")]
+    fn it_completes_flags_of_the_instruction(
+        #[case] line: &str,
+        #[case] character: u32,
+        #[case] expected: &str,
+    ) {
+        let labels = labels_at(line, Position::new(0, character)).unwrap();
+
+        assert!(labels.contains(&expected.to_string()), "{labels:?}");
+    }
+
+    #[test]
+    fn it_completes_flags_in_continuation_lines() {
+        let content = "RUN \\\n    --";
+
+        let labels = labels_at(content, Position::new(1, 6)).unwrap();
+
+        assert!(labels.contains(&"--mount=".to_string()));
+    }
+
+    #[test]
+    fn it_completes_the_healthcheck_command_after_its_flags() {
+        let labels = labels_at("HEALTHCHECK --interval=30s ", Position::new(0, 27)).unwrap();
+
+        assert_eq!(labels, ["CMD", "NONE"]);
+    }
+
+    #[rstest]
+    #[case("COPY src --", Position::new(0, 11))]
+    #[case("RUN apt-get -", Position::new(0, 13))]
+    #[case("RUN \\\n  apt", Position::new(1, 5))]
+    #[case("# FR", Position::new(0, 4))]
+    #[case("COPY --chown=", Position::new(0, 13))]
+    fn it_doesnt_complete_other_arguments(#[case] content: &str, #[case] position: Position) {
+        assert_eq!(labels_at(content, position), None);
+    }
+
+    #[test]
+    fn it_doesnt_complete_keywords_in_yaml_files() {
+        let kind = DocumentKind::detect(&"file:///values.yaml".parse().unwrap(), "re", None);
+
+        assert_eq!(
+            dockerfile_completion_items(kind, "re", Position::new(0, 2)),
+            None
+        );
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use itertools::Itertools;
+use tower_lsp::lsp_types::{Location, Position, Range, Url};
+
+use crate::app::file_check::collect_files;
+use crate::app::split_image_tag;
+use crate::domain::image_reference::normalize_image_reference;
+use crate::infra::{BuildInstruction, parse_compose_builds};
+
+use super::command_generator::{compose_build_dockerfile, is_compose_file};
+
+/// Dockerfile built by the `build:` section of a Compose file under `position`: its key
+/// or its `context` and `dockerfile` values.
+pub fn compose_build_definition(uri: &Url, content: &str, position: Position) -> Option<Location> {
+    let compose_file = uri.to_file_path().ok()?;
+    let build = parse_compose_builds(content)
+        .ok()?
+        .into_iter()
+        .find(|build| {
+            std::iter::once(&build.range)
+                .chain(&build.path_ranges)
+                .any(|range| contains(range, position))
+        })?;
+    dockerfile_location(&compose_file, &build)
+}
+
+/// Dockerfiles building `image` in the workspace: those of the Compose services under
+/// `workspace_root` tagging their build with the same repository, whatever the tag,
+/// e.g. `services/api/Dockerfile` for a K8s manifest deploying `acme/api:1.2`.
+///
+/// The Compose files are read from disk on each request, so the index is never stale.
+pub fn built_image_definitions(workspace_root: &Path, image: &str) -> Vec<Location> {
+    let repository = repository_of(image);
+    let mut files = vec![];
+    collect_files(workspace_root, Path::new(""), &mut files);
+
+    files
+        .into_iter()
+        .filter(|file| is_compose_file(&file.to_string_lossy()))
+        .map(|file| workspace_root.join(file))
+        .filter_map(|compose_file| {
+            let content = std::fs::read_to_string(&compose_file).ok()?;
+            let builds = parse_compose_builds(&content).ok()?;
+            Some((compose_file, builds))
+        })
+        .flat_map(|(compose_file, builds)| {
+            builds
+                .into_iter()
+                .filter(|build| {
+                    build
+                        .image
+                        .as_deref()
+                        .is_some_and(|built| repository_of(built) == repository)
+                })
+                .filter_map(move |build| dockerfile_location(&compose_file, &build))
+        })
+        .unique_by(|location| location.uri.clone())
+        .sorted_by(|a, b| a.uri.as_str().cmp(b.uri.as_str()))
+        .collect()
+}
+
+/// The top of the Dockerfile, if it exists.
+fn dockerfile_location(compose_file: &Path, build: &BuildInstruction) -> Option<Location> {
+    let dockerfile = compose_build_dockerfile(compose_file, build)?;
+    if !dockerfile.is_file() {
+        return None;
+    }
+    Some(Location::new(
+        Url::from_file_path(dockerfile).ok()?,
+        Range::default(),
+    ))
+}
+
+/// Repository of the image as Docker resolves it, without tag nor digest, e.g.
+/// `docker.io/library/alpine` for `alpine:3.19`.
+fn repository_of(image: &str) -> String {
+    let normalized = normalize_image_reference(image);
+    let without_digest = normalized.split('@').next().unwrap_or(&normalized);
+    split_image_tag(without_digest).0.to_string()
+}
+
+fn contains(range: &Range, position: Position) -> bool {
+    range.start <= position && position <= range.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(root: &Path, path: &str, content: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn it_finds_the_dockerfiles_building_the_repository_of_the_image() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            root.path(),
+            "docker-compose.yml",
+            "services:\n  api:\n    image: acme/api:dev\n    build: ./api\n  web:\n    image: acme/web\n    build: ./web\n",
+        );
+        write(
+            root.path(),
+            "tools/compose.yaml",
+            "services:\n  api:\n    image: docker.io/acme/api\n    build:\n      context: ../api\n",
+        );
+        write(root.path(), "api/Dockerfile", "FROM alpine\n");
+        let api = Url::from_file_path(root.path().join("api/Dockerfile")).unwrap();
+
+        let definitions = built_image_definitions(root.path(), "acme/api:1.2");
+
+        assert_eq!(definitions, vec![Location::new(api, Range::default())]);
+        // The Dockerfile of `web` doesn't exist.
+        assert!(built_image_definitions(root.path(), "acme/web").is_empty());
+        assert!(built_image_definitions(root.path(), "alpine").is_empty());
+    }
+
+    #[test]
+    fn it_goes_from_the_build_section_to_its_dockerfile() {
+        let root = tempfile::tempdir().unwrap();
+        let content = "services:\n  api:\n    build:\n      context: ./api\n      dockerfile: Dockerfile.prod\n";
+        write(root.path(), "api/Dockerfile.prod", "FROM alpine\n");
+        let uri = Url::from_file_path(root.path().join("docker-compose.yml")).unwrap();
+        let dockerfile = Url::from_file_path(root.path().join("api/Dockerfile.prod")).unwrap();
+
+        for position in [Position::new(2, 6), Position::new(4, 20)] {
+            assert_eq!(
+                compose_build_definition(&uri, content, position),
+                Some(Location::new(dockerfile.clone(), Range::default()))
+            );
+        }
+        assert_eq!(
+            compose_build_definition(&uri, content, Position::new(1, 3)),
+            None
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use super::completion::COMPLETION_TRIGGER_CHARACTERS;
+use super::semantic_tokens::semantic_tokens_legend;
+use serde_json::{Value, json};
+use tower_lsp::lsp_types::{ClientCapabilities, DocumentFilter, DocumentSelector, Registration};
+
+/// Files the server provides features for, as LSP glob patterns. Registered with
+/// the clients that support it, so supporting a new kind of file only needs a new
+/// pattern here instead of an update of every editor extension.
+pub const SUPPORTED_FILE_PATTERNS: [&str; 4] = [
+    "**/{Dockerfile,Containerfile}",
+    "**/{Dockerfile,Containerfile}.*",
+    "**/*.{Dockerfile,dockerfile,Containerfile}",
+    // Compose files and Kubernetes manifests, told apart by name and contents.
+    "**/*.{yml,yaml}",
+];
+
+/// Language id of Dockerfiles whose name doesn't match any pattern, e.g. untitled buffers.
+const DOCKERFILE_LANGUAGE_ID: &str = "dockerfile";
+
+pub fn supported_document_selector() -> DocumentSelector {
+    SUPPORTED_FILE_PATTERNS
+        .iter()
+        .map(|pattern| DocumentFilter {
+            language: None,
+            scheme: Some("file".to_string()),
+            pattern: Some(pattern.to_string()),
+        })
+        .chain(std::iter::once(DocumentFilter {
+            language: Some(DOCKERFILE_LANGUAGE_ID.to_string()),
+            scheme: None,
+            pattern: None,
+        }))
+        .collect()
+}
+
+/// Document features the client lets the server register at runtime with
+/// `client/registerCapability`. Those are registered for the supported files only,
+/// instead of being advertised for every document in the initialize result.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DynamicRegistrations {
+    pub code_lens: bool,
+    pub code_action: bool,
+    pub hover: bool,
+    pub definition: bool,
+    pub references: bool,
+    pub document_symbol: bool,
+    pub completion: bool,
+    pub semantic_tokens: bool,
+}
+
+impl DynamicRegistrations {
+    pub fn supported_by(capabilities: &ClientCapabilities) -> Self {
+        let Some(text_document) = capabilities.text_document.as_ref() else {
+            return Self::default();
+        };
+
+        Self {
+            code_lens: text_document
+                .code_lens
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+            code_action: text_document
+                .code_action
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+            hover: text_document
+                .hover
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+            definition: text_document
+                .definition
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+            references: text_document
+                .references
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+            document_symbol: text_document
+                .document_symbol
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+            completion: text_document
+                .completion
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+            semantic_tokens: text_document
+                .semantic_tokens
+                .as_ref()
+                .and_then(|c| c.dynamic_registration)
+                .unwrap_or(false),
+        }
+    }
+
+    pub fn registrations(&self) -> Vec<Registration> {
+        let selector = supported_document_selector();
+        let options = |extra: Value| {
+            let mut options = json!({ "documentSelector": selector });
+            if let (Some(options), Value::Object(extra)) = (options.as_object_mut(), extra) {
+                options.extend(extra);
+            }
+            options
+        };
+
+        [
+            (
+                self.code_lens,
+                "textDocument/codeLens",
+                options(json!({ "resolveProvider": false })),
+            ),
+            (
+                self.code_action,
+                "textDocument/codeAction",
+                options(json!({})),
+            ),
+            (self.hover, "textDocument/hover", options(json!({}))),
+            (
+                self.definition,
+                "textDocument/definition",
+                options(json!({})),
+            ),
+            (
+                self.references,
+                "textDocument/references",
+                options(json!({})),
+            ),
+            (
+                self.document_symbol,
+                "textDocument/documentSymbol",
+                options(json!({})),
+            ),
+            (
+                self.completion,
+                "textDocument/completion",
+                options(json!({ "triggerCharacters": COMPLETION_TRIGGER_CHARACTERS })),
+            ),
+            (
+                self.semantic_tokens,
+                "textDocument/semanticTokens",
+                options(json!({ "legend": semantic_tokens_legend(), "full": true })),
+            ),
+        ]
+        .into_iter()
+        .filter(|(enabled, _, _)| *enabled)
+        .map(|(_, method, register_options)| Registration {
+            id: format!("sysdig-lsp/{method}"),
+            method: method.to_string(),
+            register_options: Some(register_options),
+        })
+        .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use globset::GlobBuilder;
+    use rstest::rstest;
+    use tower_lsp::lsp_types::{
+        CodeLensClientCapabilities, HoverClientCapabilities, TextDocumentClientCapabilities,
+    };
+
+    use super::*;
+
+    #[rstest]
+    #[case("/repo/Dockerfile")]
+    #[case("/repo/Containerfile")]
+    #[case("/repo/Dockerfile.dev")]
+    #[case("/repo/api.Dockerfile")]
+    #[case("/repo/docker-compose.yml")]
+    #[case("/repo/deploy/deployment.yaml")]
+    fn it_selects_the_supported_files(#[case] path: &str) {
+        let matches = SUPPORTED_FILE_PATTERNS.iter().any(|pattern| {
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .unwrap()
+                .compile_matcher()
+                .is_match(path)
+        });
+
+        assert!(matches, "{path} should be selected");
+    }
+
+    #[test]
+    fn it_registers_only_the_features_the_client_supports_registering() {
+        let capabilities = ClientCapabilities {
+            text_document: Some(TextDocumentClientCapabilities {
+                code_lens: Some(CodeLensClientCapabilities {
+                    dynamic_registration: Some(true),
+                }),
+                hover: Some(HoverClientCapabilities {
+                    dynamic_registration: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let registrations = DynamicRegistrations::supported_by(&capabilities).registrations();
+
+        assert_eq!(registrations.len(), 1);
+        assert_eq!(registrations[0].method, "textDocument/codeLens");
+        let options = registrations[0].register_options.as_ref().unwrap();
+        assert_eq!(options["resolveProvider"], false);
+        assert_eq!(
+            options["documentSelector"].as_array().unwrap().len(),
+            SUPPORTED_FILE_PATTERNS.len() + 1
+        );
+        assert!(DynamicRegistrations::supported_by(&ClientCapabilities::default()).is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use tower_lsp::lsp_types::{DocumentSymbol, SymbolKind};
+
+use crate::infra::{Instruction, Stage, parse_dockerfile, parse_dockerfile_stages};
+
+const MAX_SYMBOL_NAME_LENGTH: usize = 60;
+
+/// Outline of a Dockerfile: global `ARG`s followed by one symbol per build stage,
+/// whose children are the instructions of the stage.
+pub fn dockerfile_document_symbols(content: &str) -> Vec<DocumentSymbol> {
+    let global_args = parse_dockerfile(content)
+        .into_iter()
+        .take_while(|instruction| instruction.keyword != "FROM")
+        .filter(|instruction| !instruction.keyword.is_empty())
+        .map(|instruction| instruction_symbol(&instruction));
+
+    global_args
+        .chain(parse_dockerfile_stages(content).iter().map(stage_symbol))
+        .collect()
+}
+
+#[allow(deprecated)] // `deprecated` must be set even though it's superseded by `tags`
+fn stage_symbol(stage: &Stage) -> DocumentSymbol {
+    let name = match &stage.name {
+        Some(name) => name.clone(),
+        None => format!("stage #{}", stage.index),
+    };
+    let selection_range = stage
+        .instructions
+        .first()
+        .map(|from| from.range)
+        .unwrap_or_else(|| stage.range());
+
+    DocumentSymbol {
+        name,
+        detail: stage.base.clone(),
+        kind: SymbolKind::MODULE,
+        tags: None,
+        deprecated: None,
+        range: stage.range(),
+        selection_range,
+        children: Some(
+            stage
+                .instructions
+                .iter()
+                .skip(1) // the FROM instruction is represented by the stage itself
+                .map(instruction_symbol)
+                .collect(),
+        ),
+    }
+}
+
+#[allow(deprecated)]
+fn instruction_symbol(instruction: &Instruction) -> DocumentSymbol {
+    let name = format!(
+        "{} {}",
+        instruction.keyword,
+        instruction.arguments.join(" ")
+    );
+    let name = if name.chars().count() > MAX_SYMBOL_NAME_LENGTH {
+        let prefix: String = name.chars().take(MAX_SYMBOL_NAME_LENGTH).collect();
+        format!("{prefix}…")
+    } else {
+        name
+    };
+
+    DocumentSymbol {
+        name,
+        detail: None,
+        kind: symbol_kind_for(&instruction.keyword),
+        tags: None,
+        deprecated: None,
+        range: instruction.range,
+        selection_range: instruction.range,
+        children: None,
+    }
+}
+
+fn symbol_kind_for(keyword: &str) -> SymbolKind {
+    match keyword {
+        "ENTRYPOINT" | "CMD" => SymbolKind::FUNCTION,
+        "EXPOSE" => SymbolKind::INTERFACE,
+        "ARG" | "ENV" => SymbolKind::VARIABLE,
+        "LABEL" => SymbolKind::PROPERTY,
+        "RUN" | "COPY" | "ADD" => SymbolKind::METHOD,
+        _ => SymbolKind::KEY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range, SymbolKind};
+
+    use super::dockerfile_document_symbols;
+
+    #[test]
+    fn it_groups_instructions_by_stage() {
+        let dockerfile = r#"ARG GO_VERSION=1.19
+FROM golang:${GO_VERSION} AS build
+RUN go build -o /app
+
+# runtime
+FROM alpine:3.17
+COPY --from=build /app /app
+EXPOSE 8080
+ENTRYPOINT ["/app"]
+"#;
+
+        let symbols = dockerfile_document_symbols(dockerfile);
+
+        let names: Vec<_> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["ARG GO_VERSION=1.19", "build", "stage #1"]);
+        assert_eq!(symbols[0].kind, SymbolKind::VARIABLE);
+
+        let build = &symbols[1];
+        assert_eq!(build.kind, SymbolKind::MODULE);
+        assert_eq!(build.detail.as_deref(), Some("golang:${GO_VERSION}"));
+        assert_eq!(
+            build.range,
+            Range::new(Position::new(1, 0), Position::new(2, 20))
+        );
+        assert_eq!(
+            build.selection_range,
+            Range::new(Position::new(1, 0), Position::new(1, 34))
+        );
+
+        let runtime_children: Vec<_> = symbols[2]
+            .children
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind))
+            .collect();
+        assert_eq!(
+            runtime_children,
+            vec![
+                ("COPY --from=build /app /app", SymbolKind::METHOD),
+                ("EXPOSE 8080", SymbolKind::INTERFACE),
+                ("ENTRYPOINT [\"/app\"]", SymbolKind::FUNCTION),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_truncates_long_instructions() {
+        let dockerfile = format!("FROM alpine\nRUN {}\n", "a ".repeat(100));
+
+        let symbols = dockerfile_document_symbols(&dockerfile);
+
+        let run = &symbols[0].children.as_ref().unwrap()[0];
+        assert!(run.name.ends_with('…'));
+        assert_eq!(run.name.chars().count(), 61);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{collections::HashMap, path::Path};
+
+use itertools::Itertools;
+use tokio::sync::{OnceCell, RwLock};
+use tower_lsp::lsp_types::{Location, Url};
+
+use crate::app::{file_check::collect_files, renamed_uri};
+use crate::domain::image_reference::normalize_image_reference;
+
+use super::command_generator::{
+    DocumentKind, ImageReference, image_references_for_uri, is_dockerfile_name, is_yaml_file,
+};
+
+/// Image references of the workspace files, by URI, answering `textDocument/references`.
+///
+/// The workspace is read from disk on the first request. Open documents are indexed with
+/// their text in the editor instead, on `didOpen` and `didChange`, and read from disk
+/// again once closed, as unsaved changes are dropped then.
+#[derive(Default)]
+pub(super) struct ImageIndex {
+    references: RwLock<HashMap<Url, Vec<ImageReference>>>,
+    workspace_indexed: OnceCell<()>,
+}
+
+impl ImageIndex {
+    /// Indexes the image references of a document, replacing the previous ones.
+    pub async fn update(&self, uri: &Url, content: &str, kind: DocumentKind) {
+        let references = image_references_for_uri(uri, content, kind);
+        let mut index = self.references.write().await;
+        if references.is_empty() {
+            index.remove(uri);
+        } else {
+            index.insert(uri.clone(), references);
+        }
+    }
+
+    /// Indexes the file of a closed document as saved on disk, if the workspace was
+    /// indexed already.
+    pub async fn reload_from_disk(&self, uri: &Url) {
+        if !self.workspace_indexed.initialized() {
+            self.references.write().await.remove(uri);
+            return;
+        }
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        let references = tokio::task::spawn_blocking(move || references_in_file(&path))
+            .await
+            .ok()
+            .flatten();
+        let mut index = self.references.write().await;
+        match references {
+            Some((_, references)) if !references.is_empty() => {
+                index.insert(uri.clone(), references);
+            }
+            _ => {
+                index.remove(uri);
+            }
+        }
+    }
+
+    /// Moves the references of a renamed document, or of the documents of a renamed
+    /// folder, to their new URIs.
+    pub async fn rename(&self, from: &Url, to: &Url) {
+        let mut index = self.references.write().await;
+        let renamed: Vec<_> = index
+            .keys()
+            .filter_map(|uri| {
+                let new_uri = renamed_uri(uri.as_str(), from.as_str(), to.as_str())?;
+                Some((uri.clone(), Url::parse(&new_uri).ok()?))
+            })
+            .collect();
+        for (old_uri, new_uri) in renamed {
+            if let Some(references) = index.remove(&old_uri) {
+                index.insert(new_uri, references);
+            }
+        }
+    }
+
+    /// Reads the Dockerfiles and YAML files under `workspace_root` the first time it's
+    /// called. Documents indexed meanwhile keep the text of the editor.
+    pub async fn index_workspace(&self, workspace_root: &Path) {
+        self.workspace_indexed
+            .get_or_init(|| async {
+                let root = workspace_root.to_path_buf();
+                let files = tokio::task::spawn_blocking(move || workspace_references(&root))
+                    .await
+                    .unwrap_or_default();
+                let mut index = self.references.write().await;
+                for (uri, references) in files {
+                    index.entry(uri).or_insert(references);
+                }
+            })
+            .await;
+    }
+
+    /// Every reference to `image` indexed, as Docker resolves it: `nginx:1.23` and
+    /// `docker.io/library/nginx:1.23` are the same image.
+    pub async fn references_to(&self, image: &str) -> Vec<Location> {
+        let image = normalize_image_reference(image);
+        self.images()
+            .await
+            .into_iter()
+            .filter(|(_, referenced)| normalize_image_reference(referenced) == image)
+            .map(|(location, _)| location)
+            .collect()
+    }
+
+    /// Every image reference indexed, as written, sorted by file and position.
+    pub async fn images(&self) -> Vec<(Location, String)> {
+        self.references
+            .read()
+            .await
+            .iter()
+            .flat_map(|(uri, references)| {
+                references.iter().map(|reference| {
+                    (
+                        Location::new(uri.clone(), reference.range),
+                        reference.image.clone(),
+                    )
+                })
+            })
+            .sorted_by(|(a, _), (b, _)| {
+                (a.uri.as_str(), a.range.start).cmp(&(b.uri.as_str(), b.range.start))
+            })
+            .collect()
+    }
+}
+
+fn workspace_references(workspace_root: &Path) -> Vec<(Url, Vec<ImageReference>)> {
+    let mut files = vec![];
+    collect_files(workspace_root, Path::new(""), &mut files);
+    files
+        .into_iter()
+        .filter_map(|file| references_in_file(&workspace_root.join(file)))
+        .filter(|(_, references)| !references.is_empty())
+        .collect()
+}
+
+/// Image references of a Dockerfile, Compose file or K8s manifest on disk. Other files
+/// aren't read.
+fn references_in_file(path: &Path) -> Option<(Url, Vec<ImageReference>)> {
+    let uri = Url::from_file_path(path).ok()?;
+    if !is_dockerfile_name(&uri) && !is_yaml_file(uri.as_str()) {
+        return None;
+    }
+    let content = std::fs::read_to_string(path).ok()?;
+    let kind = DocumentKind::detect(&uri, &content, None);
+    let references = image_references_for_uri(&uri, &content, kind);
+    Some((uri, references))
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn it_finds_the_references_to_an_image_across_the_workspace() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("Dockerfile"), "FROM nginx:1.23\n").unwrap();
+        std::fs::write(
+            root.path().join("docker-compose.yml"),
+            "services:\n  web:\n    image: docker.io/library/nginx:1.23\n  db:\n    image: postgres\n",
+        )
+        .unwrap();
+        std::fs::write(root.path().join("notes.txt"), "nginx:1.23\n").unwrap();
+        let index = ImageIndex::default();
+        let dockerfile = Url::from_file_path(root.path().join("Dockerfile")).unwrap();
+        let compose = Url::from_file_path(root.path().join("docker-compose.yml")).unwrap();
+
+        // Open documents keep the text of the editor.
+        index
+            .update(&dockerfile, "\nFROM nginx:1.23\n", DocumentKind::Dockerfile)
+            .await;
+        index.index_workspace(root.path()).await;
+
+        assert_eq!(
+            index.references_to("nginx:1.23").await,
+            vec![
+                Location::new(
+                    dockerfile.clone(),
+                    Range::new(Position::new(1, 0), Position::new(1, 15))
+                ),
+                Location::new(
+                    compose,
+                    Range::new(Position::new(2, 11), Position::new(2, 39))
+                ),
+            ]
+        );
+
+        index.reload_from_disk(&dockerfile).await;
+        assert_eq!(
+            index.references_to("nginx:1.23").await[0],
+            Location::new(
+                dockerfile,
+                Range::new(Position::new(0, 0), Position::new(0, 15))
+            )
+        );
+        assert!(index.references_to("nginx:1.25").await.is_empty());
+    }
+}
@@ -1,108 +1,1062 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use serde_json::Value;
+use chrono::{NaiveDate, TimeDelta, Utc};
+
+use serde::Serialize;
+use serde_json::{Value, json};
+use tokio::task::AbortHandle;
 use tower_lsp::jsonrpc::{Error, ErrorCode, Result};
 use tower_lsp::lsp_types::HoverContents::Markup;
 use tower_lsp::lsp_types::MarkupKind::Markdown;
 use tower_lsp::lsp_types::{
-    CodeActionOrCommand, CodeActionParams, CodeActionProviderCapability, CodeActionResponse,
-    CodeLens, CodeLensOptions, CodeLensParams, DidChangeConfigurationParams,
-    DidChangeTextDocumentParams, DidOpenTextDocumentParams, ExecuteCommandOptions,
-    ExecuteCommandParams, Hover, HoverParams, HoverProviderCapability, InitializeParams,
-    InitializeResult, InitializedParams, MarkupContent, MessageType, ServerCapabilities,
-    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, CodeLens, CodeLensOptions, CodeLensParams,
+    CompletionOptions, CompletionParams, CompletionResponse, Diagnostic,
+    DidChangeConfigurationParams, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, DocumentDiagnosticParams,
+    DocumentDiagnosticReportResult, DocumentSymbolParams, DocumentSymbolResponse,
+    ExecuteCommandOptions, ExecuteCommandParams, FileOperationFilter, FileOperationPattern,
+    FileOperationRegistrationOptions, GotoDefinitionParams, GotoDefinitionResponse, Hover,
+    HoverParams, HoverProviderCapability, InitializeParams, InitializeResult, InitializedParams,
+    Location, MarkupContent, MessageType, OneOf, Position, Range, ReferenceParams,
+    RenameFilesParams, SemanticTokens, SemanticTokensFullOptions, SemanticTokensOptions,
+    SemanticTokensParams, SemanticTokensResult, SemanticTokensServerCapabilities,
+    ServerCapabilities, SetTraceParams, TextDocumentPositionParams, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Url,
+    WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult, WorkspaceEdit,
+    WorkspaceFileOperationsServerCapabilities, WorkspaceServerCapabilities,
 };
-use tracing::{debug, info};
+use tracing::{Instrument, debug, info, info_span, warn};
 
-use super::super::component_factory::{ComponentFactory, Components, Config};
+use super::super::component_factory::{ComponentFactory, Components, Config, LintConfig};
+use super::super::protocol::{ImageInventoryEntry, ServerStatusResult, ServerVersionResult};
 use super::super::queries::QueryExecutor;
-use super::command_generator;
+use super::auto_scan::{AUTO_SCAN_DEBOUNCE, AutoScanner};
+use super::base_image_recommendation;
+use super::command_generator::{self, DocumentKind};
 use super::commands::{
-    LspCommand, build_and_scan::BuildAndScanCommand, iac_scan::IacScanCommand,
+    LspCommand, VULN_DIAGNOSTIC_SOURCE,
+    build_and_scan::{BuildAndScanCommand, OMITTED_HINTS_DATA_KEY},
+    directory_scan::DirectoryScanCommand,
+    iac_scan::IacScanCommand,
+    scan_all_images::ScanAllImagesCommand,
     scan_base_image::ScanBaseImageCommand,
 };
+use super::completion::{self, COMPLETION_TRIGGER_CHARACTERS};
+use super::definition::{built_image_definitions, compose_build_definition};
+use super::document_selector::DynamicRegistrations;
+use super::document_symbols::dockerfile_document_symbols;
+use super::image_index::ImageIndex;
+use super::pending_documents::PendingDocuments;
+use super::pin_image_digest;
+use super::policy_refresher::PolicyRefresher;
+use super::pull_diagnostics::{self, pulls_diagnostics};
+use super::request_accepted_risk;
+use super::running_commands::RunningCommands;
+use super::scan_queue::spawn_scan_queue_notifier;
+use super::semantic_tokens::{semantic_tokens_legend, vulnerable_image_tokens};
+use super::server_status::{
+    ServerHealth, ServerStatusParams, server_status, server_version, spawn_credentials_check,
+};
 use super::{InMemoryDocumentDatabase, LSPClient, WithContext};
-use crate::app::IacScanScope;
-use crate::app::LspInteractor;
+use crate::app::lint::{LintFinding, lint_dockerfile, lint_k8s_manifest};
+use crate::app::markdown::{BaseImageComparisonTable, MarkdownData, MarkdownLayerData};
+use crate::app::scan_result_export::sarif_region;
+use crate::app::{
+    BaseImageRecommender, DiagnosticsScope, DocumentScanResult, IacScanScope, ImageBuildOptions,
+    LINT_DIAGNOSTIC_SOURCE, LicenseInventoryExport, LspInteractor, PackageVersion, RiskAcceptance,
+    RiskAcceptanceScope, SYSDIG_IGNORE_FILE, ScanResultExport, ServerTrace, SysdigIgnore,
+};
+use crate::domain::scanresult::accepted_risk_reason::AcceptedRiskReason;
+use crate::domain::scanresult::sarif::SarifArtifactLocations;
+use crate::domain::scanresult::sbom::SbomFormat;
 
 use super::supported_commands::SupportedCommands;
 
+/// Description of the risk acceptances requested from the code action of a hint,
+/// which can't prompt for one.
+const ACCEPTED_FROM_EDITOR_DESCRIPTION: &str = "Accepted from the editor with Sysdig LSP";
+
+/// Reported while no settings were received, since some clients only send them with
+/// `workspace/didChangeConfiguration` after initializing.
+const NOT_CONFIGURED_MESSAGE: &str = "Sysdig LSP not configured: send the `sysdig` settings in \
+     `initializationOptions` or `workspace/didChangeConfiguration`";
+
 pub struct LSPServerInner<C, F: ComponentFactory> {
     interactor: LspInteractor<C>,
     query_executor: QueryExecutor,
     component_factory: F,
     components: Option<Arc<Components>>,
+    config: Config,
     workspace_root: Option<PathBuf>,
+    policy_refresher: PolicyRefresher,
+    policy_refresh_task: Option<AbortHandle>,
+    auto_scanner: AutoScanner,
+    credentials_check_task: Option<AbortHandle>,
+    scan_queue_task: Option<AbortHandle>,
+    running_commands: RunningCommands,
+    trace: ServerTrace,
+    dynamic_registrations: DynamicRegistrations,
+    pending_documents: PendingDocuments,
+    image_index: Arc<ImageIndex>,
 }
 
 /// Executes LSP commands with its own clones of the server dependencies, so
 /// long-running scans don't hold the server-wide lock.
+#[derive(Clone)]
 pub struct CommandExecutor<C> {
     components: Option<Arc<Components>>,
+    config: Config,
     interactor: LspInteractor<C>,
     workspace_root: Option<PathBuf>,
+    policy_refresher: PolicyRefresher,
+    auto_scanner: AutoScanner,
+    running_commands: RunningCommands,
+    image_index: Arc<ImageIndex>,
 }
 
 impl<C> CommandExecutor<C>
 where
-    C: LSPClient + Send + Sync + 'static,
+    C: LSPClient + Clone + Send + Sync + 'static,
 {
+    /// Runs the command as one of the `RunningCommands`, so it's stopped on shutdown,
+    /// in a `command` span so its logs can be told apart.
     pub async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<Value>> {
         let command: SupportedCommands = params.try_into()?;
+        let span = info_span!("command", name = %command.as_string_command());
+        let executor = self.clone();
+        self.running_commands
+            .run(
+                async move {
+                    let started = Instant::now();
+                    let result = executor.execute(command).await;
+                    debug!(
+                        duration_ms = started.elapsed().as_millis() as u64,
+                        ok = result.is_ok(),
+                        "command finished"
+                    );
+                    result
+                }
+                .instrument(span),
+            )
+            .await
+            .map_err(|_| Error {
+                code: ErrorCode::RequestCancelled,
+                message: "the command was aborted because the server is shutting down".into(),
+                data: None,
+            })?
+    }
+}
+
+impl<C> CommandExecutor<C> {
+    pub(super) fn running_commands(&self) -> &RunningCommands {
+        &self.running_commands
+    }
+}
+
+impl<C> CommandExecutor<C>
+where
+    C: LSPClient + Send + Sync + 'static,
+{
+    /// Completes the tag of the image at the cursor with the tags listed by its
+    /// registry, or Dockerfile keywords and flags anywhere else. Registry failures
+    /// only leave the completion empty.
+    pub async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let TextDocumentPositionParams {
+            text_document,
+            position,
+        } = params.text_document_position;
+        let Some(content) = self
+            .interactor
+            .read_document_text(text_document.uri.as_str())
+            .await
+        else {
+            return Ok(None);
+        };
+        let kind = document_kind(&self.interactor, &text_document.uri, &content).await;
+        let Some(context) = completion::tag_completion_context(kind, &content, position) else {
+            return Ok(
+                completion::dockerfile_completion_items(kind, &content, position)
+                    .map(CompletionResponse::Array),
+            );
+        };
+        let Some(registry_client) = self
+            .components
+            .as_ref()
+            .and_then(|components| components.registry_client.as_deref())
+        else {
+            return Ok(None);
+        };
+
+        match registry_client.list_tags(&context.repository).await {
+            Ok(tags) => Ok(Some(CompletionResponse::Array(
+                completion::tag_completion_items(&context, tags),
+            ))),
+            Err(e) => {
+                debug!("unable to list the tags of {}: {e}", context.repository);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Re-runs a scan recorded by the `AutoScanner` against the current text of the
+    /// document, whose image reference (or build lens) may have moved or changed.
+    pub(super) async fn rescan(&self, uri: &Url, command: SupportedCommands) {
+        let Some(content) = self.interactor.read_document_text(uri.as_str()).await else {
+            return;
+        };
+
+        let kind = document_kind(&self.interactor, uri, &content).await;
+        let command = match command {
+            SupportedCommands::ExecuteBaseImageScan {
+                location, platform, ..
+            } => {
+                command_generator::find_image_reference_near(
+                    uri,
+                    &content,
+                    kind,
+                    location.range.start,
+                )
+                .map(|reference| SupportedCommands::ExecuteBaseImageScan {
+                    location: Location::new(uri.clone(), reference.range),
+                    image: reference.image,
+                    // The platform chosen for the scan, unless the document now sets one.
+                    platform: reference.platform.or(platform),
+                })
+            }
+            SupportedCommands::ExecuteBuildAndScan { target, .. } => {
+                let build_command = SupportedCommands::ExecuteBuildAndScan {
+                    location: Location::new(uri.clone(), Range::default()),
+                    target: None,
+                }
+                .as_string_command();
+                // The lens of the same stage, wherever its `FROM` is now.
+                command_generator::generate_commands_for_uri(uri, &content, kind)
+                    .into_iter()
+                    .find(|command| {
+                        let command_target = command
+                            .arguments
+                            .as_ref()
+                            .and_then(|arguments| arguments.get(1))
+                            .and_then(Value::as_str);
+                        command.command == build_command && command_target == target.as_deref()
+                    })
+                    .map(|command| SupportedCommands::ExecuteBuildAndScan {
+                        location: Location::new(uri.clone(), command.range),
+                        target,
+                    })
+            }
+            command => Some(command),
+        };
+        let Some(command) = command else {
+            debug!("skipping auto scan of {uri}: nothing to scan in the document anymore");
+            return;
+        };
+
+        info!("auto scanning {uri} on save");
+        // Failures were already shown to the user.
+        let _ = self.execute(command).await;
+    }
+
+    async fn execute(&self, command: SupportedCommands) -> Result<Option<Value>> {
         let command_name = command.to_string();
 
         let result = match command {
-            SupportedCommands::ExecuteBaseImageScan { location, image } => {
-                self.execute_base_image_scan(location, image).await
+            SupportedCommands::ExecuteBaseImageScan {
+                location,
+                image,
+                platform,
+            } => self
+                .execute_base_image_scan(location, image, platform)
+                .await
+                .map(|_| None),
+            SupportedCommands::ExecuteBuildAndScan { location, target } => self
+                .execute_build_and_scan(location, target)
+                .await
+                .map(|_| None),
+            SupportedCommands::ExecuteIacScan { uri } => {
+                self.execute_iac_scan(uri).await.map(|_| None)
+            }
+            SupportedCommands::ScanImageUnderCursor { uri, position } => self
+                .execute_scan_image_under_cursor(uri, position)
+                .await
+                .map(|_| None),
+            SupportedCommands::ScanAllImages { uri } => {
+                self.execute_scan_all_images(uri).await.map(|_| None)
+            }
+            SupportedCommands::ExportScanResult { uri, path } => {
+                self.execute_export_scan_result(uri, path).await
+            }
+            SupportedCommands::ExportSarif { uri, path } => {
+                self.execute_export_sarif(uri, path).await
             }
-            SupportedCommands::ExecuteBuildAndScan { location } => {
-                self.execute_build_and_scan(location).await
+            SupportedCommands::ExportLicenses { uri, path } => {
+                self.execute_export_licenses(uri, path).await
             }
-            SupportedCommands::ExecuteIacScan { uri } => self.execute_iac_scan(uri).await,
+            SupportedCommands::GenerateSbom { uri, format, path } => {
+                self.execute_generate_sbom(uri, format, path).await
+            }
+            SupportedCommands::GenerateSysdigIgnore { uri, path } => {
+                self.execute_generate_sysdig_ignore(uri, path).await
+            }
+            SupportedCommands::AcceptRisks {
+                uri,
+                cves,
+                reason,
+                description,
+                expiration_date,
+            } => {
+                self.execute_accept_risks(uri, cves, reason, description, expiration_date)
+                    .await
+            }
+            SupportedCommands::ShowAllLayerFindings { uri, position } => {
+                self.execute_show_all_layer_findings(uri, position).await
+            }
+            SupportedCommands::ShowScanReport { uri, position } => {
+                self.execute_show_scan_report(uri, position).await
+            }
+            SupportedCommands::OpenScanInBrowser { uri } => {
+                self.execute_open_scan_in_browser(uri).await
+            }
+            SupportedCommands::PinImageDigest { location, image } => self
+                .execute_pin_image_digest(location, image)
+                .await
+                .map(|_| None),
+            SupportedCommands::RecommendBaseImage { location, image } => {
+                self.execute_recommend_base_image(location, image).await
+            }
+            SupportedCommands::RequestAcceptedRisk { uri, cve, package } => self
+                .execute_request_accepted_risk(uri, cve, package)
+                .await
+                .map(|_| None),
+            SupportedCommands::ScanDirectory { uri } => {
+                self.execute_scan_directory(uri).await.map(|_| None)
+            }
+            SupportedCommands::CleanupTempImages => self.execute_cleanup_temp_images().await,
+            SupportedCommands::ListImages => self.execute_list_images().await,
         };
 
         match result {
-            Ok(_) => Ok(None),
+            Ok(value) => Ok(value),
             Err(e) => Err(self.handle_command_error(&command_name, e).await),
         }
     }
 
-    /// Resolved here (not when building the executor) so a missing initialization
+    /// Resolved here (not when building the executor) so a missing configuration
     /// flows through `handle_command_error` and is surfaced to the user.
     fn components(&self) -> Result<&Arc<Components>> {
         self.components
             .as_ref()
-            .ok_or_else(|| Error::internal_error().with_message("LSP not initialized"))
+            .ok_or_else(|| Error::invalid_request().with_message(NOT_CONFIGURED_MESSAGE))
+    }
+
+    /// Findings the user chose to leave out of the diagnostics of the document, in the
+    /// ignore file or with ignore directives in the document itself.
+    async fn sysdig_ignore_for(&self, uri: &Url) -> SysdigIgnore {
+        let document = uri.to_file_path().ok();
+        let comments = match self.interactor.read_document_text(uri.as_str()).await {
+            Some(content) => {
+                let kind = document_kind(&self.interactor, uri, &content).await;
+                command_generator::document_comments(&content, kind)
+            }
+            None => vec![],
+        };
+        SysdigIgnore::load(self.workspace_root.as_deref(), document.as_deref())
+            .await
+            .with_inline_directives(comments.iter().map(String::as_str))
+            .focus_in_use(self.config.sysdig.report.focus_in_use)
+            .escalate_known_exploited(self.config.sysdig.report.escalate_known_exploited)
+    }
+
+    /// Options of the images built from the configuration, before the directives of
+    /// their Dockerfile.
+    fn build_options(&self) -> ImageBuildOptions {
+        ImageBuildOptions::default().with_build_args(
+            self.config
+                .sysdig
+                .build
+                .args
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.expose())),
+        )
+    }
+
+    /// Remembers the scan, so it runs again when the document is saved.
+    async fn record_scan(&self, uri: &Url, command: SupportedCommands) {
+        self.auto_scanner.record(uri, command).await;
     }
 
     async fn execute_base_image_scan(
         &self,
         location: tower_lsp::lsp_types::Location,
         image: String,
+        platform: Option<String>,
     ) -> Result<()> {
-        ScanBaseImageCommand::new(
-            self.components()?.scanner.as_ref(),
+        let sysdig_ignore = self.sysdig_ignore_for(&location.uri).await;
+        let mut command = ScanBaseImageCommand::new(
+            self.components()?.scanner_for(&location.uri),
             &self.interactor,
-            location,
-            image,
+            location.clone(),
+            image.clone(),
+            &sysdig_ignore,
+        )
+        .with_platform(platform.clone())
+        .with_severity_mapping(self.config.sysdig.diagnostics.severity_mapping);
+        command.execute().await?;
+
+        if let Some(evaluation_result) = command.evaluation_result() {
+            self.policy_refresher
+                .track(
+                    location.clone(),
+                    image.clone(),
+                    platform.clone(),
+                    evaluation_result,
+                    sysdig_ignore,
+                    self.config.sysdig.diagnostics.severity_mapping,
+                )
+                .await;
+        }
+        self.persist_scan_results(&location.uri).await;
+        self.interactor.refresh_code_lenses().await;
+        self.record_scan(
+            &location.uri,
+            SupportedCommands::ExecuteBaseImageScan {
+                location: location.clone(),
+                image,
+                platform,
+            },
+        )
+        .await;
+        Ok(())
+    }
+
+    async fn execute_scan_image_under_cursor(&self, uri: Url, position: Position) -> Result<()> {
+        let content = self
+            .interactor
+            .read_document_text(uri.as_str())
+            .await
+            .ok_or_else(|| {
+                Error::internal_error().with_message(format!(
+                    "unable to extract document content for document: {uri}"
+                ))
+            })?;
+
+        let kind = document_kind(&self.interactor, &uri, &content).await;
+        let reference =
+            command_generator::find_image_reference_near(&uri, &content, kind, position)
+                .ok_or_else(|| {
+                    Error::invalid_params(format!("no image reference found in document: {uri}"))
+                })?;
+
+        self.execute_base_image_scan(
+            Location::new(uri, reference.range),
+            reference.image,
+            reference.platform,
         )
-        .execute()
         .await
     }
 
-    async fn execute_build_and_scan(&self, location: tower_lsp::lsp_types::Location) -> Result<()> {
+    async fn execute_scan_all_images(&self, uri: Url) -> Result<()> {
+        let content = self
+            .interactor
+            .read_document_text(uri.as_str())
+            .await
+            .ok_or_else(|| {
+                Error::internal_error().with_message(format!(
+                    "unable to extract document content for document: {uri}"
+                ))
+            })?;
+
+        let kind = document_kind(&self.interactor, &uri, &content).await;
+        let references = command_generator::image_references_for_uri(&uri, &content, kind);
+        if references.is_empty() {
+            return Err(Error::invalid_params(format!(
+                "no image references found in document: {uri}"
+            )));
+        }
+
+        let sysdig_ignore = self.sysdig_ignore_for(&uri).await;
+        let mut command = ScanAllImagesCommand::new(
+            self.components()?.clone(),
+            &self.interactor,
+            uri.clone(),
+            references,
+            &sysdig_ignore,
+        )
+        .with_severity_mapping(self.config.sysdig.diagnostics.severity_mapping);
+        // Images scanned before another one failed still get their results kept.
+        let result = command.execute().await;
+
+        for (reference, evaluation_result) in command.scanned() {
+            self.policy_refresher
+                .track(
+                    Location::new(uri.clone(), reference.range),
+                    reference.image.clone(),
+                    reference.platform.clone(),
+                    *evaluation_result,
+                    sysdig_ignore.clone(),
+                    self.config.sysdig.diagnostics.severity_mapping,
+                )
+                .await;
+        }
+        self.persist_scan_results(&uri).await;
+        self.interactor.refresh_code_lenses().await;
+        self.record_scan(&uri, SupportedCommands::ScanAllImages { uri: uri.clone() })
+            .await;
+        result
+    }
+
+    async fn execute_build_and_scan(
+        &self,
+        location: tower_lsp::lsp_types::Location,
+        target: Option<String>,
+    ) -> Result<()> {
         let components = self.components()?;
+        let uri = location.uri.clone();
+        let sysdig_ignore = self.sysdig_ignore_for(&uri).await;
         BuildAndScanCommand::new(
             components.builder.as_ref(),
-            components.scanner.as_ref(),
+            components.scanner_for(&uri),
             &self.interactor,
-            location,
+            location.clone(),
+            &self.config.sysdig.report,
+            &self.config.sysdig.diagnostics,
+            &sysdig_ignore,
         )
+        .with_build_options(self.build_options().with_target(target.clone()))
         .execute()
+        .await?;
+        self.persist_scan_results(&uri).await;
+        self.interactor.refresh_code_lenses().await;
+        self.record_scan(
+            &uri,
+            SupportedCommands::ExecuteBuildAndScan { location, target },
+        )
+        .await;
+        Ok(())
+    }
+
+    async fn execute_export_scan_result(
+        &self,
+        uri: Url,
+        path: Option<PathBuf>,
+    ) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+        self.export_json(
+            ScanResultExport::from(&last_scan.scan_result),
+            path,
+            "Scan result",
+        )
+        .await
+    }
+
+    async fn execute_export_sarif(&self, uri: Url, path: Option<PathBuf>) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+
+        // Code scanning services expect paths relative to the repository root.
+        let artifact_uri = uri
+            .to_file_path()
+            .ok()
+            .zip(self.workspace_root.as_ref())
+            .and_then(|(path, root)| {
+                path.strip_prefix(root)
+                    .ok()
+                    .map(|relative| relative.to_string_lossy().replace('\\', "/"))
+            })
+            .unwrap_or_else(|| uri.to_string());
+        let locations = SarifArtifactLocations {
+            artifact_uri,
+            image_region: sarif_region(last_scan.image_range),
+            layer_regions: last_scan
+                .layer_ranges
+                .iter()
+                .map(|(index, range)| (*index, sarif_region(*range)))
+                .collect(),
+        };
+
+        self.export_json(
+            last_scan.scan_result.to_sarif(&locations),
+            path,
+            "SARIF report",
+        )
+        .await
+    }
+
+    async fn execute_export_licenses(
+        &self,
+        uri: Url,
+        path: Option<PathBuf>,
+    ) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+        self.export_json(
+            LicenseInventoryExport::from(&last_scan.scan_result),
+            path,
+            "License inventory",
+        )
         .await
     }
 
+    async fn execute_generate_sbom(
+        &self,
+        uri: Url,
+        format: SbomFormat,
+        path: Option<PathBuf>,
+    ) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+
+        // Relative paths are written next to the scanned document.
+        let path = path.map(|path| {
+            uri.to_file_path()
+                .ok()
+                .filter(|_| path.is_relative())
+                .and_then(|document| document.parent().map(|dir| dir.join(&path)))
+                .unwrap_or(path)
+        });
+        let created = Utc::now();
+        match format {
+            SbomFormat::CycloneDx => {
+                self.export_json(
+                    last_scan.scan_result.to_cyclonedx(created),
+                    path,
+                    "CycloneDX SBOM",
+                )
+                .await
+            }
+            SbomFormat::Spdx => {
+                self.export_json(last_scan.scan_result.to_spdx(created), path, "SPDX SBOM")
+                    .await
+            }
+        }
+    }
+
+    async fn execute_generate_sysdig_ignore(
+        &self,
+        uri: Url,
+        path: Option<PathBuf>,
+    ) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+        let document = uri.to_file_path().ok();
+        let path = path
+            .or_else(|| SysdigIgnore::path_for(self.workspace_root.as_deref(), document.as_deref()))
+            .ok_or_else(|| {
+                Error::invalid_params(format!(
+                    "no workspace root to place the {SYSDIG_IGNORE_FILE} in, pass an output path"
+                ))
+            })?;
+
+        let existing = match tokio::fs::read_to_string(&path).await {
+            Ok(existing) => Some(existing),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(Error::internal_error()
+                    .with_message(format!("unable to read {}: {e}", path.display())));
+            }
+        };
+        let (contents, added) =
+            SysdigIgnore::scaffold_file(existing.as_deref(), &last_scan.scan_result);
+        tokio::fs::write(&path, contents).await.map_err(|e| {
+            Error::internal_error().with_message(format!("unable to write {}: {e}", path.display()))
+        })?;
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                &format!(
+                    "Added {added} findings to {}, they won't be reported from the next scan on",
+                    path.display()
+                ),
+            )
+            .await;
+
+        Ok(Some(Value::String(path.display().to_string())))
+    }
+
+    /// Creates the risk acceptances one by one, reporting the ones the backend
+    /// rejected instead of stopping at the first failure.
+    async fn execute_accept_risks(
+        &self,
+        uri: Url,
+        cves: Vec<String>,
+        reason: AcceptedRiskReason,
+        description: String,
+        expiration_date: Option<NaiveDate>,
+    ) -> Result<Option<Value>> {
+        let client = self
+            .components()?
+            .risk_acceptance_client_for(&uri)
+            .ok_or_else(|| {
+                Error::invalid_params(
+                    "risks can only be accepted in the default tenant with online scans",
+                )
+            })?;
+        let last_scan = self.last_scan_result(&uri).await?;
+        let image = last_scan.scan_result.metadata().pull_string().to_string();
+        if let Some(unknown) = cves.iter().find(|cve| {
+            last_scan
+                .scan_result
+                .find_vulnerability_by_cve(cve)
+                .is_none()
+        }) {
+            return Err(Error::invalid_params(format!(
+                "{unknown} wasn't found in the last scan of {image}"
+            )));
+        }
+
+        let mut accepted = Vec::new();
+        let mut failed = Vec::new();
+        for cve in cves {
+            let acceptance = RiskAcceptance {
+                cve,
+                scope: RiskAcceptanceScope::Image(image.clone()),
+                reason,
+                description: description.clone(),
+                expiration_date,
+            };
+            match client.accept_risk(&acceptance).await {
+                Ok(()) => accepted.push(acceptance.cve),
+                Err(e) => {
+                    warn!("unable to accept the risk of {}: {e}", acceptance.cve);
+                    failed.push(json!({ "cve": acceptance.cve, "error": e.to_string() }));
+                }
+            }
+        }
+
+        let (message_type, message) = if failed.is_empty() {
+            (
+                MessageType::INFO,
+                format!(
+                    "Accepted the risk of {} vulnerabilities in {image}, rescan the image to update its policy evaluation",
+                    accepted.len()
+                ),
+            )
+        } else {
+            (
+                MessageType::WARNING,
+                format!(
+                    "Accepted the risk of {} of {} vulnerabilities in {image}, see the logs for the failures",
+                    accepted.len(),
+                    accepted.len() + failed.len()
+                ),
+            )
+        };
+        self.interactor.show_message(message_type, &message).await;
+
+        Ok(Some(json!({ "accepted": accepted, "failed": failed })))
+    }
+
+    async fn execute_show_all_layer_findings(
+        &self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+        let layer = last_scan
+            .layer_ranges
+            .iter()
+            .find(|(_, range)| (range.start.line..=range.end.line).contains(&position.line))
+            .and_then(|(index, _)| {
+                last_scan
+                    .scan_result
+                    .layers()
+                    .into_iter()
+                    .find(|layer| layer.index() == *index)
+            })
+            .ok_or_else(|| {
+                Error::invalid_params(format!(
+                    "no image layer was built by the instruction at line {} of {uri}",
+                    position.line + 1
+                ))
+            })?;
+
+        let report = format!(
+            "# Layer {}: `{}`\n\n{}",
+            layer.index(),
+            layer.command(),
+            MarkdownLayerData::from(layer.clone())
+        );
+        Ok(Some(Value::String(report)))
+    }
+
+    async fn execute_show_scan_report(
+        &self,
+        uri: Url,
+        position: Option<Position>,
+    ) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+        let covers_last_scan = |position: Position| {
+            let range = last_scan.image_range;
+            (range.start.line..=range.end.line).contains(&position.line)
+        };
+
+        // Only the result of the last scan is kept, so other images of the document
+        // (e.g. all the images of a Compose file) fall back to their hover report.
+        if let Some(position) = position.filter(|position| !covers_last_scan(*position)) {
+            let report = self
+                .interactor
+                .read_documentations(uri.as_str())
+                .await
+                .into_iter()
+                .find(|documentation| {
+                    documentation.verdict.is_some() && documentation.covers(position)
+                })
+                .ok_or_else(|| {
+                    Error::invalid_params(format!(
+                        "no image was scanned at line {} of {uri}",
+                        position.line + 1
+                    ))
+                })?;
+            return Ok(Some(Value::String(report.content)));
+        }
+
+        let suppressed_vulns = self
+            .sysdig_ignore_for(&uri)
+            .await
+            .suppressed(&last_scan.scan_result);
+        let report = MarkdownData::full_report(last_scan.scan_result)
+            .with_suppressed_vulns(suppressed_vulns)
+            .to_string();
+        Ok(Some(Value::String(report)))
+    }
+
+    async fn execute_open_scan_in_browser(&self, uri: Url) -> Result<Option<Value>> {
+        let last_scan = self.last_scan_result(&uri).await?;
+        let metadata = last_scan.scan_result.metadata();
+        let result_url = metadata.result_url().ok_or_else(|| {
+            Error::invalid_params(format!(
+                "the scan of {} wasn't uploaded to Sysdig Secure",
+                metadata.pull_string()
+            ))
+        })?;
+        Ok(Some(Value::String(result_url.to_string())))
+    }
+
+    async fn execute_pin_image_digest(&self, location: Location, image: String) -> Result<()> {
+        let registry_client = self
+            .components()?
+            .registry_client
+            .as_deref()
+            .ok_or_else(|| {
+                Error::invalid_params("the registry isn't queried for digests in offline mode")
+            })?;
+        let digest = registry_client.resolve_digest(&image).await.map_err(|e| {
+            Error::internal_error()
+                .with_message(format!("unable to resolve the digest of {image}: {e}"))
+        })?;
+
+        let response = self
+            .interactor
+            .apply_edit(pin_image_digest::pin_edit(
+                &location.uri,
+                location.range,
+                &image,
+                &digest,
+            ))
+            .await?;
+        if !response.applied {
+            return Err(Error::internal_error().with_message(format!(
+                "the editor didn't pin {image} to {digest}: {}",
+                response.failure_reason.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Scans alternatives to a base image and adds their comparison to the hover of the
+    /// image. The best one, if any, is returned and offered as a quick fix from then on.
+    async fn execute_recommend_base_image(
+        &self,
+        location: Location,
+        image: String,
+    ) -> Result<Option<Value>> {
+        let components = self.components()?;
+        let registry_client = components.registry_client.as_deref().ok_or_else(|| {
+            Error::invalid_params("alternatives to base images aren't scanned in offline mode")
+        })?;
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                &format!("Looking for less vulnerable alternatives to {image}..."),
+            )
+            .await;
+
+        let recommendation =
+            BaseImageRecommender::new(registry_client, components.scanner_for(&location.uri))
+                .recommend(&image)
+                .await
+                .map_err(|e| Error::internal_error().with_message(e.to_string()))?;
+
+        self.interactor
+            .extend_documentation(
+                location.uri.as_str(),
+                location.range,
+                &BaseImageComparisonTable(&recommendation).to_string(),
+            )
+            .await;
+        let best = recommendation.best().cloned();
+        let message = match &best {
+            Some(best) => format!(
+                "{} is less vulnerable than {image}, switch to it from the quick fixes of the image.",
+                best.image
+            ),
+            None => format!("No alternative is less vulnerable than {image}."),
+        };
+        self.interactor
+            .show_message(MessageType::INFO, &message)
+            .await;
+        self.interactor
+            .write_base_image_recommendation(&image, recommendation)
+            .await;
+
+        serde_json::to_value(best).map(Some).map_err(|e| {
+            Error::internal_error()
+                .with_message(format!("unable to serialize the alternative: {e}"))
+        })
+    }
+
+    /// Accepts the risk of a vulnerability of the last scan, from the code action of
+    /// its hint, and marks its hints as accepted.
+    async fn execute_request_accepted_risk(
+        &self,
+        uri: Url,
+        cve: String,
+        package: Option<PackageVersion>,
+    ) -> Result<()> {
+        let client = self
+            .components()?
+            .risk_acceptance_client_for(&uri)
+            .ok_or_else(|| {
+                Error::invalid_params(
+                    "risks can only be accepted in the default tenant with online scans",
+                )
+            })?;
+        let last_scan = self.last_scan_result(&uri).await?;
+        let image = last_scan.scan_result.metadata().pull_string().to_string();
+        let vulnerability = last_scan
+            .scan_result
+            .find_vulnerability_by_cve(&cve)
+            .ok_or_else(|| {
+                Error::invalid_params(format!("{cve} wasn't found in the last scan of {image}"))
+            })?;
+        if let Some(package) = &package
+            && !vulnerability
+                .found_in_packages()
+                .iter()
+                .any(|p| *p.name() == package.name && *p.version() == package.version)
+        {
+            return Err(Error::invalid_params(format!(
+                "{cve} wasn't found in {} {} in the last scan of {image}",
+                package.name, package.version
+            )));
+        }
+
+        let (scope, accepted_in) = match &package {
+            Some(package) => (
+                RiskAcceptanceScope::Package(package.clone()),
+                format!("{} {}", package.name, package.version),
+            ),
+            None => (RiskAcceptanceScope::Image(image.clone()), image),
+        };
+        client
+            .accept_risk(&RiskAcceptance {
+                cve: cve.clone(),
+                scope,
+                reason: AcceptedRiskReason::RiskOwned,
+                description: ACCEPTED_FROM_EDITOR_DESCRIPTION.to_string(),
+                expiration_date: None,
+            })
+            .await
+            .map_err(|e| Error::internal_error().with_message(e.to_string()))?;
+
+        let diagnostics = self
+            .interactor
+            .read_diagnostics_with_source(uri.as_str(), VULN_DIAGNOSTIC_SOURCE)
+            .await;
+        self.interactor
+            .replace_diagnostics_with_source(
+                VULN_DIAGNOSTIC_SOURCE,
+                DiagnosticsScope::Document(uri.as_str()),
+                HashMap::from([(
+                    uri.to_string(),
+                    request_accepted_risk::mark_risk_accepted(diagnostics, &cve, package.as_ref()),
+                )]),
+            )
+            .await;
+        self.interactor.publish_all_diagnostics().await?;
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                &format!(
+                    "Accepted the risk of {cve} in {accepted_in}, rescan the image to update its policy evaluation"
+                ),
+            )
+            .await;
+        Ok(())
+    }
+
+    async fn last_scan_result(&self, uri: &Url) -> Result<DocumentScanResult> {
+        self.interactor
+            .read_last_scan_result(uri.as_str())
+            .await
+            .ok_or_else(|| {
+                Error::invalid_params(format!(
+                    "no scan result available for {uri}, scan an image first"
+                ))
+            })
+    }
+
+    /// Returns `exported` as JSON, or writes it to `path` and returns the path.
+    async fn export_json(
+        &self,
+        exported: impl Serialize,
+        path: Option<PathBuf>,
+        what: &str,
+    ) -> Result<Option<Value>> {
+        let serialization_error = |e: serde_json::Error| {
+            Error::internal_error().with_message(format!("unable to serialize {what}: {e}"))
+        };
+
+        let Some(path) = path else {
+            return serde_json::to_value(exported)
+                .map(Some)
+                .map_err(serialization_error);
+        };
+
+        let contents = serde_json::to_vec_pretty(&exported).map_err(serialization_error)?;
+        tokio::fs::write(&path, contents).await.map_err(|e| {
+            Error::internal_error()
+                .with_message(format!("unable to write {what} to {}: {e}", path.display()))
+        })?;
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                &format!("{what} exported to {}", path.display()),
+            )
+            .await;
+
+        Ok(Some(Value::String(path.display().to_string())))
+    }
+
+    /// Saves the image scan results of the document, if persistence is enabled.
+    /// Failing to persist doesn't fail the scan, whose results were already published.
+    async fn persist_scan_results(&self, uri: &Url) {
+        let Some(store) = self
+            .components
+            .as_ref()
+            .and_then(|components| components.scan_result_store.as_ref())
+        else {
+            return;
+        };
+        let Some(snapshot) = self.interactor.scan_snapshot(uri.as_str()).await else {
+            return;
+        };
+
+        if let Err(e) = store.save(uri.as_str(), &snapshot).await {
+            warn!("unable to persist scan results for {uri}: {e}");
+        }
+    }
+
     async fn execute_iac_scan(&self, uri: Option<Url>) -> Result<()> {
         let scope = match uri {
             Some(uri) => {
@@ -119,15 +1073,86 @@ where
             })?),
         };
 
-        IacScanCommand::new(
-            self.components()?.iac_scanner.as_ref(),
+        // Workspace-wide scans run against the default tenant; scoped tenants apply to single files.
+        let components = self.components()?;
+        let iac_scanner = match &scope {
+            IacScanScope::File { uri, .. } => components.iac_scanner_for(uri),
+            IacScanScope::Directory(_) => components.iac_scanner.as_ref(),
+        };
+
+        IacScanCommand::new(iac_scanner, &self.interactor, scope.clone())
+            .execute()
+            .await?;
+        if let IacScanScope::File { uri, .. } = scope {
+            self.record_scan(
+                &uri,
+                SupportedCommands::ExecuteIacScan {
+                    uri: Some(uri.clone()),
+                },
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    async fn execute_scan_directory(&self, uri: Option<Url>) -> Result<()> {
+        let directory = match uri {
+            Some(uri) => uri.to_file_path().map_err(|_| {
+                Error::invalid_params(format!("only file:// URIs are supported, received: {uri}"))
+            })?,
+            None => self.workspace_root.clone().ok_or_else(|| {
+                Error::internal_error()
+                    .with_message("no workspace root available; open a folder or pass a folder URI")
+            })?,
+        };
+
+        // Like workspace-wide IaC scans, directories are scanned by the default tenant.
+        let components = self.components()?;
+        DirectoryScanCommand::new(
+            components.filesystem_scanner.as_ref(),
             &self.interactor,
-            scope,
+            directory,
         )
         .execute()
         .await
     }
 
+    async fn execute_cleanup_temp_images(&self) -> Result<Option<Value>> {
+        // The error isn't `Send`, so it's converted before awaiting again.
+        let removed = self
+            .components()?
+            .builder
+            .remove_temporary_images()
+            .await
+            .map_err(|e| {
+                Error::internal_error()
+                    .with_message(format!("unable to remove the temporary images: {e}"))
+            })?;
+        self.interactor
+            .show_message(
+                MessageType::INFO,
+                &format!("Removed {} temporary images.", removed.len()),
+            )
+            .await;
+        Ok(Some(json!(removed)))
+    }
+
+    async fn execute_list_images(&self) -> Result<Option<Value>> {
+        if let Some(workspace_root) = &self.workspace_root {
+            self.image_index.index_workspace(workspace_root).await;
+        }
+        let mut entries = vec![];
+        for (location, image) in self.image_index.images().await {
+            entries.push(ImageInventoryEntry {
+                uri: location.uri,
+                range: location.range,
+                last_scan: self.interactor.read_image_scan(&image).await,
+                image,
+            });
+        }
+        Ok(Some(json!(entries)))
+    }
+
     async fn handle_command_error(&self, command_name: &str, e: Error) -> Error {
         self.interactor
             .show_message(MessageType::ERROR, e.to_string().as_str())
@@ -149,34 +1174,122 @@ impl<C, F: ComponentFactory> LSPServerInner<C, F> {
             query_executor: QueryExecutor::new(document_database.clone()),
             component_factory,
             components: None,
+            config: Config::default(),
             workspace_root: None,
+            policy_refresher: PolicyRefresher::default(),
+            policy_refresh_task: None,
+            auto_scanner: AutoScanner::default(),
+            credentials_check_task: None,
+            scan_queue_task: None,
+            running_commands: RunningCommands::default(),
+            trace: ServerTrace::default(),
+            dynamic_registrations: DynamicRegistrations::default(),
+            pending_documents: PendingDocuments::default(),
+            image_index: Arc::default(),
+        }
+    }
+
+    /// Shares the verbosity of the logs with the tracing layer forwarding them.
+    pub fn set_server_trace(&mut self, trace: ServerTrace) {
+        self.trace = trace;
+    }
+
+    /// `$/setTrace`, changing the verbosity of the logs.
+    pub fn set_trace(&self, params: SetTraceParams) {
+        self.trace.set_client_trace(params.value);
+    }
+
+    /// `sysdig/status` request.
+    pub fn status(&self) -> ServerStatusResult {
+        server_status(&self.config, self.components.as_deref())
+    }
+
+    /// `sysdig/version` request.
+    pub fn version(&self) -> ServerVersionResult {
+        server_version(self.components.as_deref())
+    }
+}
+
+impl<C, F: ComponentFactory> Drop for LSPServerInner<C, F> {
+    fn drop(&mut self) {
+        if let Some(task) = self.policy_refresh_task.take() {
+            task.abort();
         }
+        if let Some(task) = self.credentials_check_task.take() {
+            task.abort();
+        }
+        if let Some(task) = self.scan_queue_task.take() {
+            task.abort();
+        }
+        // The connection is lost if the server is dropped without shutting down.
+        self.running_commands.abort_all();
     }
 }
 
 impl<C, F: ComponentFactory> LSPServerInner<C, F>
 where
-    C: LSPClient + Send + Sync + 'static,
+    C: LSPClient + Clone + Send + Sync + 'static,
 {
     fn update_components(&mut self, config: &Value) -> Result<()> {
-        let config = serde_json::from_value::<Config>(config.clone()).map_err(|e| {
-            Error::internal_error()
-                .with_message(format!("unable to transform json into config: {e}"))
+        let config = Config::from_settings(config).map_err(|e| Error {
+            code: ErrorCode::InvalidParams,
+            message: e.to_string().into(),
+            data: Some(serde_json::json!({ "path": e.path })),
         })?;
 
+        self.trace.configure(&config.sysdig.trace);
         debug!("updating with configuration: {config:?}");
 
-        let components = self.component_factory.create_components(config)?;
-        self.components.replace(Arc::new(components));
+        let components = Arc::new(self.component_factory.create_components(config.clone())?);
+        if let Some(previous) = self.components.replace(components.clone()) {
+            components
+                .scan_scheduler
+                .statistics()
+                .carry_over(&previous.scan_scheduler.statistics());
+        }
+
+        if let Some(task) = self.scan_queue_task.take() {
+            task.abort();
+        }
+        self.scan_queue_task = Some(spawn_scan_queue_notifier(
+            &components.scan_scheduler,
+            self.interactor.clone(),
+        ));
+
+        if let Some(task) = self.policy_refresh_task.take() {
+            task.abort();
+        }
+        if config.sysdig.policy_refresh_interval_seconds > 0 && config.sysdig.scan.offline {
+            warn!("policy refresh disabled: policies aren't evaluated by offline scans");
+        } else if config.sysdig.policy_refresh_interval_seconds > 0 {
+            self.policy_refresh_task = Some(self.policy_refresher.spawn(
+                components,
+                self.interactor.clone(),
+                Duration::from_secs(config.sysdig.policy_refresh_interval_seconds),
+            ));
+        }
+
+        self.config = config;
 
         debug!("updated configuration");
         Ok(())
     }
+
+    /// Replaces any check still running for a previous configuration.
+    fn check_credentials(&mut self) {
+        if let Some(task) = self.credentials_check_task.take() {
+            task.abort();
+        }
+        if let Some(components) = self.components.clone() {
+            self.credentials_check_task =
+                spawn_credentials_check(components, self.interactor.clone());
+        }
+    }
 }
 
 impl<C, F: ComponentFactory> LSPServerInner<C, F>
 where
-    C: LSPClient + Send + Sync + 'static,
+    C: LSPClient + Clone + Send + Sync + 'static,
 {
     async fn get_commands_for_document(
         &self,
@@ -188,7 +1301,10 @@ where
             )));
         };
 
-        Ok(command_generator::generate_commands_for_uri(uri, &content))
+        let kind = document_kind(&self.interactor, uri, &content).await;
+        Ok(command_generator::generate_commands_for_uri(
+            uri, &content, kind,
+        ))
     }
 
     pub async fn initialize(
@@ -196,86 +1312,459 @@ where
         initialize_params: InitializeParams,
     ) -> Result<InitializeResult> {
         self.workspace_root = workspace_root_from(&initialize_params);
+        self.dynamic_registrations =
+            DynamicRegistrations::supported_by(&initialize_params.capabilities);
+        self.interactor.set_code_lens_refresh_support(
+            initialize_params
+                .capabilities
+                .workspace
+                .as_ref()
+                .and_then(|workspace| workspace.code_lens.as_ref())
+                .and_then(|code_lens| code_lens.refresh_support)
+                .unwrap_or(false),
+        );
+        self.interactor
+            .set_pull_diagnostics_support(pulls_diagnostics(&initialize_params.capabilities));
+        let registered = self.dynamic_registrations;
+        if let Some(trace) = initialize_params.trace {
+            self.trace.set_client_trace(trace);
+        }
 
-        let Some(config) = initialize_params.initialization_options else {
-            return Err(Error {
-                code: ErrorCode::InvalidParams,
-                message: "expected parameters to configure the LSP, received nothing".into(),
-                data: None,
-            });
-        };
-
-        self.update_components(&config)?;
+        match initialize_params.initialization_options {
+            Some(config) if !settings_are_empty(&config) => self.update_components(&config)?,
+            _ => info!("no settings received on initialize, waiting for didChangeConfiguration"),
+        }
 
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::FULL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..Default::default()
+                    },
                 )),
-                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
-                code_lens_provider: Some(CodeLensOptions {
+                // Features the client can register dynamically are registered on
+                // `initialized`, for the supported files only.
+                code_action_provider: (!registered.code_action)
+                    .then_some(CodeActionProviderCapability::Simple(true)),
+                code_lens_provider: (!registered.code_lens).then_some(CodeLensOptions {
                     resolve_provider: Some(false),
                 }),
                 execute_command_provider: Some(ExecuteCommandOptions {
                     commands: SupportedCommands::all_supported_commands_as_string(),
                     ..Default::default()
                 }),
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                hover_provider: (!registered.hover)
+                    .then_some(HoverProviderCapability::Simple(true)),
+                definition_provider: (!registered.definition).then_some(OneOf::Left(true)),
+                references_provider: (!registered.references).then_some(OneOf::Left(true)),
+                document_symbol_provider: (!registered.document_symbol)
+                    .then_some(OneOf::Left(true)),
+                completion_provider: (!registered.completion).then(|| CompletionOptions {
+                    trigger_characters: Some(
+                        COMPLETION_TRIGGER_CHARACTERS.map(str::to_string).to_vec(),
+                    ),
+                    ..Default::default()
+                }),
+                diagnostic_provider: Some(pull_diagnostics::diagnostic_provider()),
+                semantic_tokens_provider: (!registered.semantic_tokens).then(|| {
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                        legend: semantic_tokens_legend(),
+                        full: Some(SemanticTokensFullOptions::Bool(true)),
+                        ..Default::default()
+                    })
+                }),
+                workspace: Some(WorkspaceServerCapabilities {
+                    file_operations: Some(WorkspaceFileOperationsServerCapabilities {
+                        did_rename: Some(FileOperationRegistrationOptions {
+                            filters: vec![FileOperationFilter {
+                                scheme: Some("file".to_string()),
+                                pattern: FileOperationPattern {
+                                    glob: "**".to_string(),
+                                    ..Default::default()
+                                },
+                            }],
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
             ..Default::default()
         })
     }
 
-    pub async fn initialized(&self, _: InitializedParams) {
+    pub async fn initialized(&mut self, _: InitializedParams) {
         info!("Initialized");
+        if !self.dynamic_registrations.is_empty()
+            && let Err(e) = self
+                .interactor
+                .register_capability(self.dynamic_registrations.registrations())
+                .await
+        {
+            warn!("unable to register the supported documents: {e}");
+            self.interactor
+                .show_message(
+                    MessageType::WARNING,
+                    &format!("Sysdig LSP could not register its features with the editor: {e}"),
+                )
+                .await;
+        }
         self.interactor
             .show_message(MessageType::INFO, "Sysdig LSP initialized")
             .await;
+        // Reported once initialized: clients don't expect notifications before that.
+        if self.components.is_none() {
+            self.interactor
+                .show_message(MessageType::WARNING, NOT_CONFIGURED_MESSAGE)
+                .await;
+            self.interactor
+                .send_server_status(ServerStatusParams {
+                    health: ServerHealth::NotConfigured,
+                    message: Some(NOT_CONFIGURED_MESSAGE.to_string()),
+                })
+                .await;
+        }
+        self.check_credentials();
     }
 
     pub async fn did_change_configuration(&mut self, params: DidChangeConfigurationParams) {
-        let _ = self.update_components(&params.settings);
+        // The previous configuration stays in place, so the error is only reported.
+        match self.update_components(&params.settings) {
+            Ok(()) => {
+                self.check_credentials();
+                for params in self.pending_documents.take() {
+                    self.did_open(params).await;
+                }
+            }
+            Err(e) => {
+                let message = format!("Sysdig LSP configuration not applied: {}", e.message);
+                self.interactor
+                    .show_message(MessageType::ERROR, &message)
+                    .await;
+                self.interactor
+                    .send_server_status(ServerStatusParams {
+                        health: ServerHealth::Error,
+                        message: Some(message),
+                    })
+                    .await;
+            }
+        }
     }
 
     pub async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        if self.components.is_none() {
+            self.pending_documents.opened(&params);
+        }
+        self.policy_refresher
+            .forget_document(params.text_document.uri.as_str())
+            .await;
+        self.interactor
+            .write_document_language_id(
+                params.text_document.uri.as_str(),
+                &params.text_document.language_id,
+            )
+            .await;
+        self.lint_document(&params.text_document.uri, &params.text_document.text)
+            .await;
+        self.index_images(&params.text_document.uri, &params.text_document.text)
+            .await;
         self.interactor
             .update_document_with_text(
                 params.text_document.uri.as_str(),
                 params.text_document.text.as_str(),
             )
             .await;
+        self.restore_scan_results(&params.text_document.uri, &params.text_document.text)
+            .await;
+    }
+
+    /// Republishes the results persisted by a previous session, as long as the
+    /// document didn't change since it was scanned.
+    async fn restore_scan_results(&self, uri: &Url, text: &str) {
+        let Some(store) = self
+            .components
+            .as_ref()
+            .and_then(|components| components.scan_result_store.as_ref())
+        else {
+            return;
+        };
+
+        let snapshot = match store.load(uri.as_str()).await {
+            Ok(Some(snapshot)) if snapshot.matches_content(text) => snapshot,
+            Ok(Some(_)) => {
+                debug!("not restoring scan results for {uri}: the document changed since the scan");
+                return;
+            }
+            Ok(None) => return,
+            Err(e) => {
+                warn!("unable to restore scan results for {uri}: {e}");
+                return;
+            }
+        };
+
+        if self
+            .interactor
+            .restore_scan_snapshot(uri.as_str(), snapshot)
+            .await
+            .is_ok()
+        {
+            self.interactor.refresh_code_lenses().await;
+        }
     }
 
     pub async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        if self.components.is_none() {
+            self.pending_documents.changed(&params);
+        }
         if let Some(change) = params.content_changes.into_iter().next_back() {
+            self.policy_refresher
+                .forget_document(params.text_document.uri.as_str())
+                .await;
+            self.lint_document(&params.text_document.uri, &change.text)
+                .await;
+            self.index_images(&params.text_document.uri, &change.text)
+                .await;
             self.interactor
                 .update_document_with_text(params.text_document.uri.as_str(), &change.text)
                 .await;
         }
     }
 
+    /// Releases everything kept for the document: its text, hovers, scan result and
+    /// scans to refresh, and its diagnostics if `sysdig.diagnostics.clear_on_close`.
+    pub async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.pending_documents.closed(&params);
+        self.image_index
+            .reload_from_disk(&params.text_document.uri)
+            .await;
+        let uri = params.text_document.uri.as_str();
+        self.policy_refresher.forget_document(uri).await;
+        self.auto_scanner.forget_document(uri).await;
+        self.interactor
+            .close_document(uri, self.config.sysdig.diagnostics.clear_on_close)
+            .await;
+    }
+
+    /// Moves the text, diagnostics, hovers and stored scan results of the renamed
+    /// documents to their new URI, so they don't have to be scanned again. Scans to
+    /// refresh are forgotten, until the document is scanned again.
+    pub async fn did_rename_files(&self, params: RenameFilesParams) {
+        for file in params.files {
+            let (Ok(from), Ok(to)) = (Url::parse(&file.old_uri), Url::parse(&file.new_uri)) else {
+                debug!(
+                    "ignoring the rename of {} to {}",
+                    file.old_uri, file.new_uri
+                );
+                continue;
+            };
+            self.pending_documents.renamed(&from, &to);
+            self.image_index.rename(&from, &to).await;
+            let renamed = self
+                .interactor
+                .rename_document(from.as_str(), to.as_str())
+                .await;
+            let old_uris = renamed.iter().map(|(old_uri, _)| old_uri.as_str());
+            for old_uri in [from.as_str()].into_iter().chain(old_uris) {
+                self.policy_refresher.forget_document(old_uri).await;
+                self.auto_scanner.forget_document(old_uri).await;
+            }
+            if let Some(store) = self
+                .components
+                .as_ref()
+                .and_then(|components| components.scan_result_store.as_ref())
+                && let Err(e) = store.rename(from.as_str(), to.as_str()).await
+            {
+                warn!("unable to move the scan results of {from} to {to}: {e}");
+            }
+        }
+        self.interactor.refresh_code_lenses().await;
+    }
+
+    pub async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        if !self.config.sysdig.auto_scan.on_save {
+            return;
+        }
+        self.auto_scanner
+            .schedule(
+                params.text_document.uri,
+                self.command_executor(),
+                AUTO_SCAN_DEBOUNCE,
+            )
+            .await;
+    }
+
+    async fn index_images(&self, uri: &Url, text: &str) {
+        let kind = document_kind(&self.interactor, uri, text).await;
+        self.image_index.update(uri, text, kind).await;
+    }
+
+    /// Replaces the lint diagnostics of the document; they are published along
+    /// with the rest by the following document update.
+    async fn lint_document(&self, uri: &Url, text: &str) {
+        let kind = document_kind(&self.interactor, uri, text).await;
+        let diagnostics = lint_findings_for(kind, text, &self.config.sysdig.lint)
+            .iter()
+            .map(Diagnostic::from)
+            .collect();
+
+        self.interactor
+            .replace_diagnostics_with_source(
+                LINT_DIAGNOSTIC_SOURCE,
+                DiagnosticsScope::Document(uri.as_str()),
+                HashMap::from([(uri.to_string(), diagnostics)]),
+            )
+            .await;
+    }
+
     pub async fn code_action(
         &self,
         params: CodeActionParams,
     ) -> Result<Option<CodeActionResponse>> {
-        let commands = self
-            .get_commands_for_document(&params.text_document.uri)
-            .await?;
-        let code_actions: Vec<CodeActionOrCommand> = commands
+        let uri = &params.text_document.uri;
+        let commands = self.get_commands_for_document(uri).await?;
+        let rescans = self
+            .rescan_commands(uri, &commands, params.range.start)
+            .await;
+        let mut code_actions: Vec<CodeActionOrCommand> = commands
             .into_iter()
             .filter(|cmd| cmd.range.start.line == params.range.start.line)
+            .chain(rescans)
             .map(|cmd| CodeActionOrCommand::Command(cmd.into()))
             .collect();
 
+        code_actions.extend(
+            params
+                .context
+                .diagnostics
+                .iter()
+                .filter(|diagnostic| {
+                    diagnostic
+                        .data
+                        .as_ref()
+                        .is_some_and(|data| data.get(OMITTED_HINTS_DATA_KEY).is_some())
+                })
+                .map(|diagnostic| {
+                    CodeActionOrCommand::Command(
+                        command_generator::CommandInfo::from(
+                            SupportedCommands::ShowAllLayerFindings {
+                                uri: uri.clone(),
+                                position: diagnostic.range.start,
+                            },
+                        )
+                        .into(),
+                    )
+                }),
+        );
+
+        let content = self
+            .query_executor
+            .get_document_text(uri.as_str())
+            .await
+            .unwrap_or_default();
+        code_actions.extend(params.context.diagnostics.iter().filter_map(|diagnostic| {
+            pin_image_digest::pin_to_digest_action(uri, &content, diagnostic)
+        }));
+        code_actions.extend(params.context.diagnostics.iter().flat_map(|diagnostic| {
+            request_accepted_risk::request_accepted_risk_actions(uri, diagnostic)
+        }));
+        for diagnostic in &params.context.diagnostics {
+            let Some(image) = base_image_recommendation::vulnerable_image(diagnostic) else {
+                continue;
+            };
+            let recommendation = self.interactor.read_base_image_recommendation(image).await;
+            code_actions.extend(base_image_recommendation::base_image_alternative_action(
+                uri,
+                &content,
+                diagnostic,
+                recommendation.as_ref(),
+            ));
+        }
+        let kind = document_kind(&self.interactor, uri, &content).await;
+        code_actions.extend(
+            lint_findings_for(kind, &content, &self.config.sysdig.lint)
+                .iter()
+                .filter(|finding| {
+                    finding.range.start.line <= params.range.end.line
+                        && finding.range.end.line >= params.range.start.line
+                })
+                .filter_map(|finding| lint_fix_code_action(uri, finding))
+                .map(CodeActionOrCommand::CodeAction),
+        );
+
         Ok(Some(code_actions))
     }
 
+    /// Re-scans of the images at `position` whose shown result is older than
+    /// `sysdig.report.stale_after_hours`, reusing the scan command of their lens.
+    async fn rescan_commands(
+        &self,
+        uri: &Url,
+        commands: &[command_generator::CommandInfo],
+        position: Position,
+    ) -> Vec<command_generator::CommandInfo> {
+        let stale_after_hours = self.config.sysdig.report.stale_after_hours;
+        if stale_after_hours == 0 {
+            return vec![];
+        }
+        let stale_after = i64::try_from(stale_after_hours)
+            .ok()
+            .and_then(TimeDelta::try_hours)
+            .unwrap_or(TimeDelta::MAX);
+        let now = Utc::now();
+
+        self.interactor
+            .read_documentations(uri.as_str())
+            .await
+            .into_iter()
+            .filter(|documentation| {
+                documentation.covers(position) && documentation.is_stale(now, stale_after)
+            })
+            .filter_map(|documentation| {
+                let scanned_ago = documentation.scanned_ago(now)?;
+                Some((documentation.range.start.line, scanned_ago))
+            })
+            .flat_map(|(line, scanned_ago)| {
+                // The documentation doesn't tell which scan it comes from, e.g. "Scan base
+                // image" or "Build and scan" on the same line, so all of them are offered.
+                commands
+                    .iter()
+                    .filter(move |cmd| {
+                        cmd.range.start.line == line
+                            && SupportedCommands::is_image_scan(&cmd.command)
+                    })
+                    .map(move |scan| command_generator::rescan_command(scan, &scanned_ago))
+            })
+            .collect()
+    }
+
     pub async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
         let commands = self
             .get_commands_for_document(&params.text_document.uri)
             .await?;
-        let code_lenses = commands.into_iter().map(|cmd| cmd.into()).collect();
+        let uri = &params.text_document.uri;
+        let verdicts = self
+            .interactor
+            .read_documentations(uri.as_str())
+            .await
+            .into_iter()
+            .filter_map(|documentation| {
+                let verdict = documentation.verdict?;
+                Some(command_generator::policy_verdict_command(
+                    uri,
+                    documentation.range,
+                    verdict,
+                    documentation.scanned_ago(Utc::now()).as_deref(),
+                ))
+            });
+        let code_lenses = commands
+            .into_iter()
+            .chain(verdicts)
+            .map(|cmd| cmd.into())
+            .collect();
 
         Ok(Some(code_lenses))
     }
@@ -290,23 +1779,37 @@ where
     {
         CommandExecutor {
             components: self.components.clone(),
+            config: self.config.clone(),
             interactor: self.interactor.clone(),
             workspace_root: self.workspace_root.clone(),
+            policy_refresher: self.policy_refresher.clone(),
+            auto_scanner: self.auto_scanner.clone(),
+            running_commands: self.running_commands.clone(),
+            image_index: self.image_index.clone(),
         }
     }
 
+    /// The detail of the vulnerability under the cursor if there is one, otherwise the
+    /// documentation covering the position.
     pub async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        let documentation_found = self
-            .interactor
-            .read_documentation_at(
-                params
-                    .text_document_position_params
-                    .text_document
-                    .uri
-                    .as_str(),
-                params.text_document_position_params.position,
-            )
-            .await;
+        let uri = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .as_str();
+        let position = params.text_document_position_params.position;
+        let documentation_found = match self
+            .query_executor
+            .get_vulnerability_detail_at(uri, position)
+            .await
+        {
+            Some(detail) => Some(detail),
+            None => {
+                self.query_executor
+                    .get_documentation_at(uri, position)
+                    .await
+            }
+        };
 
         let Some(documentation) = documentation_found else {
             return Ok(None);
@@ -321,11 +1824,198 @@ where
         }))
     }
 
+    /// The Dockerfile a Compose `build:` section builds, or the Dockerfiles building the
+    /// image under the cursor in the workspace.
+    pub async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let uri = &params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let Some(content) = self.query_executor.get_document_text(uri.as_str()).await else {
+            return Ok(None);
+        };
+        let kind = document_kind(&self.interactor, uri, &content).await;
+
+        if kind == DocumentKind::Compose
+            && let Some(location) = compose_build_definition(uri, &content, position)
+        {
+            return Ok(Some(GotoDefinitionResponse::Scalar(location)));
+        }
+
+        let Some(image) = command_generator::image_references_for_uri(uri, &content, kind)
+            .into_iter()
+            .find(|reference| reference.range.start <= position && position <= reference.range.end)
+            .map(|reference| reference.image)
+        else {
+            return Ok(None);
+        };
+        let Some(workspace_root) = self.workspace_root.clone() else {
+            return Ok(None);
+        };
+        // Walking the workspace can take a while in large ones.
+        let locations =
+            tokio::task::spawn_blocking(move || built_image_definitions(&workspace_root, &image))
+                .await
+                .unwrap_or_default();
+
+        Ok((!locations.is_empty()).then_some(GotoDefinitionResponse::Array(locations)))
+    }
+
+    /// The other references to the image under the cursor in the workspace, and the one
+    /// under the cursor too if `includeDeclaration`.
+    pub async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let Some(content) = self.query_executor.get_document_text(uri.as_str()).await else {
+            return Ok(None);
+        };
+        let kind = document_kind(&self.interactor, uri, &content).await;
+        let Some(reference) = command_generator::image_references_for_uri(uri, &content, kind)
+            .into_iter()
+            .find(|reference| reference.range.start <= position && position <= reference.range.end)
+        else {
+            return Ok(None);
+        };
+
+        if let Some(workspace_root) = &self.workspace_root {
+            self.image_index.index_workspace(workspace_root).await;
+        }
+        let locations = self
+            .image_index
+            .references_to(&reference.image)
+            .await
+            .into_iter()
+            .filter(|location| {
+                params.context.include_declaration
+                    || !(&location.uri == uri && location.range == reference.range)
+            })
+            .collect();
+        Ok(Some(locations))
+    }
+
+    pub async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = &params.text_document.uri;
+        let Some(content) = self.query_executor.get_document_text(uri.as_str()).await else {
+            return Ok(None);
+        };
+
+        if document_kind(&self.interactor, uri, &content).await != DocumentKind::Dockerfile {
+            return Ok(None);
+        }
+
+        Ok(Some(DocumentSymbolResponse::Nested(
+            dockerfile_document_symbols(&content),
+        )))
+    }
+
+    /// Highlights the image references whose last scan reported Critical or High
+    /// vulnerabilities, so editors can style them even without diagnostics.
+    pub async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let uri = &params.text_document.uri;
+        let Some(content) = self.query_executor.get_document_text(uri.as_str()).await else {
+            return Ok(None);
+        };
+
+        let kind = document_kind(&self.interactor, uri, &content).await;
+        let references = command_generator::image_references_for_uri(uri, &content, kind);
+        let image_severities = self.interactor.read_image_severities().await;
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: vulnerable_image_tokens(&references, &image_severities),
+        })))
+    }
+
+    pub async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        let diagnostics = self
+            .query_executor
+            .get_diagnostics(params.text_document.uri.as_str())
+            .await;
+        Ok(
+            pull_diagnostics::document_report(diagnostics, params.previous_result_id.as_deref())
+                .into(),
+        )
+    }
+
+    pub async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> Result<WorkspaceDiagnosticReportResult> {
+        let all_diagnostics = self.query_executor.get_all_diagnostics().await;
+        Ok(pull_diagnostics::workspace_report(all_diagnostics, &params.previous_result_ids).into())
+    }
+
+    /// Stops the background tasks and gives the running commands
+    /// `sysdig.scan.shutdown_grace_seconds` to finish before aborting them.
     pub async fn shutdown(&self) -> Result<()> {
+        for task in [&self.policy_refresh_task, &self.credentials_check_task]
+            .into_iter()
+            .flatten()
+        {
+            task.abort();
+        }
+        self.running_commands
+            .shutdown(Duration::from_secs(
+                self.config.sysdig.scan.shutdown_grace_seconds,
+            ))
+            .await;
         Ok(())
     }
 }
 
+/// Kind of the document, recognizing Dockerfiles by the language id the client opened
+/// them with too.
+async fn document_kind<C: LSPClient>(
+    interactor: &LspInteractor<C>,
+    uri: &Url,
+    content: &str,
+) -> DocumentKind {
+    let language_id = interactor.read_document_language_id(uri.as_str()).await;
+    DocumentKind::detect(uri, content, language_id.as_deref())
+}
+
+fn lint_findings_for(kind: DocumentKind, text: &str, config: &LintConfig) -> Vec<LintFinding> {
+    match kind {
+        DocumentKind::Dockerfile => lint_dockerfile(text, config),
+        DocumentKind::K8sManifest => lint_k8s_manifest(text, config),
+        _ => vec![],
+    }
+}
+
+fn lint_fix_code_action(uri: &Url, finding: &LintFinding) -> Option<CodeAction> {
+    let fix = finding.fix.as_ref()?;
+
+    Some(CodeAction {
+        title: fix.title.clone(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![Diagnostic::from(finding)]),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), fix.edits.clone())])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Whether the client sent no settings at all, as some do (`null` or `{}`), rather
+/// than invalid ones, which are still rejected.
+fn settings_are_empty(settings: &Value) -> bool {
+    match settings {
+        Value::Null => true,
+        Value::Object(settings) => settings.is_empty(),
+        _ => false,
+    }
+}
+
 fn workspace_root_from(initialize_params: &InitializeParams) -> Option<PathBuf> {
     let from_workspace_folders = initialize_params
         .workspace_folders
@@ -4,16 +4,40 @@ use tokio::sync::RwLock;
 use tower_lsp::LanguageServer;
 use tower_lsp::jsonrpc::{Error, Result};
 use tower_lsp::lsp_types::{
-    CodeActionParams, CodeActionResponse, CodeLens, CodeLensParams, DidChangeConfigurationParams,
-    DidChangeTextDocumentParams, DidOpenTextDocumentParams, ExecuteCommandParams, Hover,
-    HoverParams, InitializeParams, InitializeResult, InitializedParams,
+    CodeActionParams, CodeActionResponse, CodeLens, CodeLensParams, CompletionParams,
+    CompletionResponse, DidChangeConfigurationParams, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    DocumentDiagnosticParams, DocumentDiagnosticReportResult, DocumentSymbolParams,
+    DocumentSymbolResponse, ExecuteCommandParams, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverParams, InitializeParams, InitializeResult, InitializedParams, Location,
+    ReferenceParams, RenameFilesParams, SemanticTokensParams, SemanticTokensResult, SetTraceParams,
+    WorkspaceDiagnosticParams, WorkspaceDiagnosticReportResult,
 };
 
-use super::{InMemoryDocumentDatabase, LSPClient};
+use super::{
+    InMemoryDocumentDatabase, LSPClient, ServerTrace,
+    protocol::{ServerStatusResult, ServerVersionResult},
+};
 
+mod auto_scan;
+mod base_image_recommendation;
 pub mod command_generator;
 pub mod commands;
+mod completion;
+mod definition;
+mod document_selector;
+mod document_symbols;
+mod image_index;
 mod lsp_server_inner;
+mod pending_documents;
+mod pin_image_digest;
+mod policy_refresher;
+mod pull_diagnostics;
+mod request_accepted_risk;
+mod running_commands;
+mod scan_queue;
+mod semantic_tokens;
+pub mod server_status;
 pub mod supported_commands;
 use crate::app::component_factory::ComponentFactory;
 use lsp_server_inner::LSPServerInner;
@@ -39,6 +63,28 @@ impl<C, F: ComponentFactory> LSPServer<C, F> {
             inner: RwLock::new(LSPServerInner::new(client, component_factory)),
         }
     }
+
+    /// Shares the verbosity of the logs with the tracing layer forwarding them to the
+    /// client, which the server updates from the client trace and the settings.
+    pub fn with_trace(mut self, trace: ServerTrace) -> Self {
+        self.inner.get_mut().set_server_trace(trace);
+        self
+    }
+
+    /// `$/setTrace` notification, registered as a custom method of the service.
+    pub async fn set_trace(&self, params: SetTraceParams) {
+        self.inner.read().await.set_trace(params)
+    }
+
+    /// `sysdig/status` request, registered as a custom method of the service.
+    pub async fn status(&self) -> Result<ServerStatusResult> {
+        Ok(self.inner.read().await.status())
+    }
+
+    /// `sysdig/version` request, registered as a custom method of the service.
+    pub async fn version(&self) -> Result<ServerVersionResult> {
+        Ok(self.inner.read().await.version())
+    }
 }
 
 #[async_trait::async_trait]
@@ -52,7 +98,7 @@ where
     }
 
     async fn initialized(&self, params: InitializedParams) {
-        self.inner.read().await.initialized(params).await
+        self.inner.write().await.initialized(params).await
     }
 
     async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
@@ -71,6 +117,18 @@ where
         self.inner.read().await.did_change(params).await
     }
 
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        self.inner.read().await.did_close(params).await
+    }
+
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        self.inner.read().await.did_save(params).await
+    }
+
+    async fn did_rename_files(&self, params: RenameFilesParams) {
+        self.inner.read().await.did_rename_files(params).await
+    }
+
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
         self.inner.read().await.code_action(params).await
     }
@@ -88,10 +146,56 @@ where
         executor.execute_command(params).await
     }
 
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        // Registries can take seconds to answer, so they're queried without holding
+        // the server lock, as commands are.
+        let executor = self.inner.read().await.command_executor();
+        executor.completion(params).await
+    }
+
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         self.inner.read().await.hover(params).await
     }
 
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        self.inner.read().await.goto_definition(params).await
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        self.inner.read().await.references(params).await
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        self.inner.read().await.document_symbol(params).await
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        self.inner.read().await.semantic_tokens_full(params).await
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> Result<DocumentDiagnosticReportResult> {
+        self.inner.read().await.diagnostic(params).await
+    }
+
+    async fn workspace_diagnostic(
+        &self,
+        params: WorkspaceDiagnosticParams,
+    ) -> Result<WorkspaceDiagnosticReportResult> {
+        self.inner.read().await.workspace_diagnostic(params).await
+    }
+
     async fn shutdown(&self) -> Result<()> {
         self.inner.read().await.shutdown().await
     }
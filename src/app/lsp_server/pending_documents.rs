@@ -0,0 +1,78 @@
+use std::sync::Mutex;
+
+use tower_lsp::lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, Url,
+};
+
+use crate::app::renamed_uri;
+
+/// Documents opened while the server isn't configured yet, as some clients only send
+/// the settings with `workspace/didChangeConfiguration` after opening them. Their
+/// `didOpen` is handled right away with the default settings, and replayed with their
+/// latest text once the settings arrive, so the work depending on them (lint rules,
+/// scan results restored from the store) isn't lost.
+#[derive(Default)]
+pub(super) struct PendingDocuments {
+    opened: Mutex<Vec<DidOpenTextDocumentParams>>,
+}
+
+impl PendingDocuments {
+    pub fn opened(&self, params: &DidOpenTextDocumentParams) {
+        let Ok(mut opened) = self.opened.lock() else {
+            return;
+        };
+        opened.retain(|pending| pending.text_document.uri != params.text_document.uri);
+        opened.push(params.clone());
+    }
+
+    /// Keeps the text the document will be replayed with up to date.
+    pub fn changed(&self, params: &DidChangeTextDocumentParams) {
+        let Some(change) = params.content_changes.last() else {
+            return;
+        };
+        let Ok(mut opened) = self.opened.lock() else {
+            return;
+        };
+        if let Some(pending) = opened
+            .iter_mut()
+            .find(|pending| pending.text_document.uri == params.text_document.uri)
+        {
+            pending.text_document.text = change.text.clone();
+            pending.text_document.version = params.text_document.version;
+        }
+    }
+
+    /// Closed documents aren't replayed.
+    pub fn closed(&self, params: &DidCloseTextDocumentParams) {
+        if let Ok(mut opened) = self.opened.lock() {
+            opened.retain(|pending| pending.text_document.uri != params.text_document.uri);
+        }
+    }
+
+    /// Renamed documents, and the documents of renamed folders, are replayed under
+    /// their new URI.
+    pub fn renamed(&self, from: &Url, to: &Url) {
+        let Ok(mut opened) = self.opened.lock() else {
+            return;
+        };
+        for pending in opened.iter_mut() {
+            let renamed = renamed_uri(
+                pending.text_document.uri.as_str(),
+                from.as_str(),
+                to.as_str(),
+            )
+            .and_then(|uri| Url::parse(&uri).ok());
+            if let Some(renamed) = renamed {
+                pending.text_document.uri = renamed;
+            }
+        }
+    }
+
+    /// `didOpen` of the documents still open, in the order they were opened.
+    pub fn take(&self) -> Vec<DidOpenTextDocumentParams> {
+        self.opened
+            .lock()
+            .map(|mut opened| std::mem::take(&mut *opened))
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, Location, Position, Range,
+    TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::app::split_image_tag;
+
+use super::command_generator::CommandInfo;
+use super::commands::scan_base_image::{IMAGE_DATA_KEY, IMAGE_DIGEST_DATA_KEY};
+use super::supported_commands::SupportedCommands;
+
+/// "Pin to digest" for the summary diagnostic of a scanned image referenced by a
+/// mutable tag. It edits the reference right away when the scanner reported the
+/// digest of the image, and otherwise runs `sysdig-lsp.pin-image-digest`, which
+/// resolves the digest from the registry.
+pub fn pin_to_digest_action(
+    uri: &Url,
+    content: &str,
+    diagnostic: &Diagnostic,
+) -> Option<CodeActionOrCommand> {
+    let data = diagnostic.data.as_ref()?;
+    let image = data.get(IMAGE_DATA_KEY)?.as_str()?;
+    if image.contains('@') {
+        return None;
+    }
+    let range = image_range(content, diagnostic.range, image)?;
+
+    let command = CommandInfo::from(SupportedCommands::PinImageDigest {
+        location: Location::new(uri.clone(), range),
+        image: image.to_string(),
+    });
+    let mut action = CodeAction {
+        title: command.title.clone(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        ..Default::default()
+    };
+    match data.get(IMAGE_DIGEST_DATA_KEY).and_then(|d| d.as_str()) {
+        Some(digest) => action.edit = Some(pin_edit(uri, range, image, digest)),
+        None => action.command = Some(command.into()),
+    }
+    Some(CodeActionOrCommand::CodeAction(action))
+}
+
+/// Replaces the image reference at `range` with `<repository>@<digest>`.
+pub fn pin_edit(uri: &Url, range: Range, image: &str, digest: &str) -> WorkspaceEdit {
+    let (repository, _) = split_image_tag(image);
+    WorkspaceEdit {
+        changes: Some(HashMap::from([(
+            uri.clone(),
+            vec![TextEdit::new(range, format!("{repository}@{digest}"))],
+        )])),
+        ..Default::default()
+    }
+}
+
+/// Range of the first whole occurrence of `image` within `range` (e.g. the `FROM`
+/// instruction the image summary is reported at).
+pub(super) fn image_range(content: &str, range: Range, image: &str) -> Option<Range> {
+    let is_boundary = |c: Option<char>| c.is_none_or(|c| c.is_whitespace() || "\"'=".contains(c));
+
+    content
+        .lines()
+        .enumerate()
+        .skip(range.start.line as usize)
+        .take((range.end.line - range.start.line) as usize + 1)
+        .find_map(|(line_number, line)| {
+            let from = match line_number == range.start.line as usize {
+                true => range.start.character as usize,
+                false => 0,
+            };
+            let (offset, _) = line.char_indices().nth(from).unwrap_or((line.len(), ' '));
+            let (start, _) = line[offset..].match_indices(image).find(|(start, _)| {
+                let start = offset + start;
+                is_boundary(line[..start].chars().next_back())
+                    && is_boundary(line[start + image.len()..].chars().next())
+            })?;
+
+            let start = line[..offset + start].chars().count() as u32;
+            let line_number = line_number as u32;
+            Some(Range::new(
+                Position::new(line_number, start),
+                Position::new(line_number, start + image.chars().count() as u32),
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tower_lsp::lsp_types::{Command, Position};
+
+    use super::*;
+
+    fn diagnostic(range: Range, image: &str, digest: Option<&str>) -> Diagnostic {
+        Diagnostic {
+            range,
+            data: Some(json!({ IMAGE_DATA_KEY: image, IMAGE_DIGEST_DATA_KEY: digest })),
+            ..Default::default()
+        }
+    }
+
+    fn uri() -> Url {
+        "file:///Dockerfile".parse().unwrap()
+    }
+
+    #[test]
+    fn it_pins_the_image_to_the_digest_reported_by_the_scanner() {
+        let content = "FROM --platform=linux/amd64 nginx:latest AS web\n";
+        let from = Range::new(Position::new(0, 0), Position::new(0, 47));
+
+        let Some(CodeActionOrCommand::CodeAction(action)) = pin_to_digest_action(
+            &uri(),
+            content,
+            &diagnostic(from, "nginx:latest", Some("sha256:abc")),
+        ) else {
+            panic!("expected a code action");
+        };
+
+        assert_eq!(action.title, "Pin to digest");
+        let edits = &action.edit.unwrap().changes.unwrap()[&uri()];
+        assert_eq!(
+            edits,
+            &[TextEdit::new(
+                Range::new(Position::new(0, 28), Position::new(0, 40)),
+                "nginx@sha256:abc".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn it_resolves_the_digest_with_a_command_when_the_scanner_didnt_report_it() {
+        let content = "services:\n  db:\n    image: \"postgres\"\n";
+        let value = Range::new(Position::new(2, 11), Position::new(2, 21));
+
+        let Some(CodeActionOrCommand::CodeAction(action)) =
+            pin_to_digest_action(&uri(), content, &diagnostic(value, "postgres", None))
+        else {
+            panic!("expected a code action");
+        };
+
+        assert!(action.edit.is_none());
+        let Command {
+            command, arguments, ..
+        } = action.command.unwrap();
+        assert_eq!(command, "sysdig-lsp.pin-image-digest");
+        assert_eq!(
+            arguments.unwrap(),
+            [
+                json!(Location::new(
+                    uri(),
+                    Range::new(Position::new(2, 12), Position::new(2, 20))
+                )),
+                json!("postgres")
+            ]
+        );
+    }
+
+    #[test]
+    fn it_doesnt_pin_images_already_pinned() {
+        let content = "FROM nginx@sha256:abc\n";
+        let from = Range::new(Position::new(0, 0), Position::new(0, 21));
+
+        assert!(
+            pin_to_digest_action(
+                &uri(),
+                content,
+                &diagnostic(from, "nginx@sha256:abc", Some("sha256:abc"))
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn it_only_matches_whole_image_references() {
+        let from = Range::new(Position::new(0, 0), Position::new(0, 14));
+
+        assert_eq!(image_range("FROM node-base\n", from, "node"), None);
+    }
+}
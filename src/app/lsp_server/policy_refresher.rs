@@ -0,0 +1,169 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::{sync::Mutex, task::AbortHandle};
+use tower_lsp::lsp_types::{Location, MessageType};
+use tracing::{debug, info};
+
+use crate::{
+    app::{
+        LSPClient, LspInteractor, SysdigIgnore,
+        component_factory::{Components, SeverityMapping},
+    },
+    domain::scanresult::evaluation_result::EvaluationResult,
+};
+
+use super::commands::{LspCommand, scan_base_image::ScanBaseImageCommand};
+
+#[derive(Clone, Debug)]
+struct TrackedImageScan {
+    location: Location,
+    image: String,
+    /// Platform the image was scanned for, the default one if `None`.
+    platform: Option<String>,
+    evaluation_result: EvaluationResult,
+    /// Findings ignored when the image was scanned, so refreshes report the same ones.
+    sysdig_ignore: SysdigIgnore,
+    /// `sysdig.diagnostics.severity_mapping` when the image was scanned.
+    severity_mapping: Option<SeverityMapping>,
+}
+
+/// Keeps track of the images scanned in each document and periodically re-evaluates
+/// them: policies and accepted risks change server-side without the image changing,
+/// so a passing file could otherwise silently become non-compliant.
+///
+/// Only the policy verdict is compared; diagnostics are refreshed if it flips.
+#[derive(Clone, Default)]
+pub struct PolicyRefresher {
+    /// Keyed by document URI and image pull string.
+    tracked: Arc<Mutex<HashMap<(String, String), TrackedImageScan>>>,
+}
+
+impl PolicyRefresher {
+    pub async fn track(
+        &self,
+        location: Location,
+        image: String,
+        platform: Option<String>,
+        evaluation_result: EvaluationResult,
+        sysdig_ignore: SysdigIgnore,
+        severity_mapping: Option<SeverityMapping>,
+    ) {
+        self.tracked.lock().await.insert(
+            (location.uri.to_string(), image.clone()),
+            TrackedImageScan {
+                location,
+                image,
+                platform,
+                evaluation_result,
+                sysdig_ignore,
+                severity_mapping,
+            },
+        );
+    }
+
+    /// Stops refreshing the scans of a document, e.g. because its text changed and
+    /// the scan diagnostics were cleared.
+    pub async fn forget_document(&self, uri: &str) {
+        self.tracked
+            .lock()
+            .await
+            .retain(|(tracked_uri, _), _| tracked_uri != uri);
+    }
+
+    /// Spawns the background task re-evaluating every tracked scan each `interval`.
+    pub fn spawn<C>(
+        &self,
+        components: Arc<Components>,
+        interactor: LspInteractor<C>,
+        interval: Duration,
+    ) -> AbortHandle
+    where
+        C: LSPClient + Send + Sync + 'static,
+    {
+        let refresher = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                refresher.refresh(&components, &interactor).await;
+            }
+        })
+        .abort_handle()
+    }
+
+    async fn refresh<C>(&self, components: &Components, interactor: &LspInteractor<C>)
+    where
+        C: LSPClient + Send + Sync,
+    {
+        let tracked: Vec<_> = self.tracked.lock().await.values().cloned().collect();
+        debug!("re-evaluating policies of {} scanned images", tracked.len());
+
+        for scan in tracked {
+            // Cached results would hide verdicts changed since the image was scanned.
+            let scanner = components.scanner_for(&scan.location.uri);
+            let scan_result = match scanner
+                .rescan_image_for_platform(&scan.image, scan.platform.as_deref())
+                .await
+            {
+                Ok(scan_result) => scan_result,
+                Err(e) => {
+                    debug!("unable to re-evaluate policies of {}: {e}", scan.image);
+                    continue;
+                }
+            };
+            if scan_result.evaluation_result() == scan.evaluation_result {
+                continue;
+            }
+
+            // The document may have changed while scanning, leaving the location stale.
+            let key = (scan.location.uri.to_string(), scan.image.clone());
+            if !self.tracked.lock().await.contains_key(&key) {
+                continue;
+            }
+
+            info!(
+                "policy evaluation of {} changed to {:?}",
+                scan.image,
+                scan_result.evaluation_result()
+            );
+            let mut command = ScanBaseImageCommand::new(
+                scanner,
+                interactor,
+                scan.location.clone(),
+                scan.image.clone(),
+                &scan.sysdig_ignore,
+            )
+            .with_platform(scan.platform.clone())
+            .with_severity_mapping(scan.severity_mapping);
+            if let Err(e) = command.execute().await {
+                debug!("unable to refresh diagnostics of {}: {e}", scan.image);
+                continue;
+            }
+
+            let evaluation_result = command
+                .evaluation_result()
+                .unwrap_or(scan_result.evaluation_result());
+            interactor
+                .show_message(
+                    match evaluation_result {
+                        EvaluationResult::Failed => MessageType::WARNING,
+                        EvaluationResult::Passed | EvaluationResult::NotEvaluated => {
+                            MessageType::INFO
+                        }
+                    },
+                    &format!(
+                        "Policy evaluation of {} changed: it now {}",
+                        scan.image,
+                        match evaluation_result {
+                            EvaluationResult::Failed => "fails",
+                            EvaluationResult::Passed => "passes",
+                            EvaluationResult::NotEvaluated => "isn't evaluated",
+                        }
+                    ),
+                )
+                .await;
+            if let Some(tracked) = self.tracked.lock().await.get_mut(&key) {
+                tracked.evaluation_result = evaluation_result;
+            }
+        }
+    }
+}
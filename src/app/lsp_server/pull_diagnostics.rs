@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+use tower_lsp::lsp_types::{
+    ClientCapabilities, Diagnostic, DiagnosticOptions, DiagnosticServerCapabilities,
+    DocumentDiagnosticReport, FullDocumentDiagnosticReport, PreviousResultId,
+    RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport,
+    UnchangedDocumentDiagnosticReport, Url, WorkspaceDiagnosticReport,
+    WorkspaceDocumentDiagnosticReport, WorkspaceFullDocumentDiagnosticReport,
+    WorkspaceUnchangedDocumentDiagnosticReport,
+};
+use tracing::debug;
+
+/// Identifier of the diagnostics pulled from the server, telling them apart from
+/// the ones of other servers of the same documents.
+const DIAGNOSTIC_IDENTIFIER: &str = "sysdig-lsp";
+
+pub fn diagnostic_provider() -> DiagnosticServerCapabilities {
+    DiagnosticServerCapabilities::Options(DiagnosticOptions {
+        identifier: Some(DIAGNOSTIC_IDENTIFIER.to_string()),
+        // Diagnostics of a document only change with its own scans and edits.
+        inter_file_dependencies: false,
+        // IaC and directory scans report on files that were never opened.
+        workspace_diagnostics: true,
+        ..Default::default()
+    })
+}
+
+/// Whether the client pulls the diagnostics, so they don't have to be published. Its
+/// `workspace.diagnostics.refreshSupport` can't be told: `lsp-types` reads it from
+/// `workspace.diagnostic`, so clients pulling them are asked to refresh regardless.
+pub fn pulls_diagnostics(capabilities: &ClientCapabilities) -> bool {
+    capabilities
+        .text_document
+        .as_ref()
+        .is_some_and(|text_document| text_document.diagnostic.is_some())
+}
+
+/// Result id of a report: the hash of its diagnostics, so a report is unchanged as
+/// long as its diagnostics are, without keeping track of the reports sent.
+fn result_id(diagnostics: &[Diagnostic]) -> String {
+    let serialized = serde_json::to_vec(diagnostics).unwrap_or_default();
+    Sha256::digest(serialized)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Report of `textDocument/diagnostic`, unchanged if the client already has the
+/// diagnostics of `previous_result_id`.
+pub fn document_report(
+    diagnostics: Vec<Diagnostic>,
+    previous_result_id: Option<&str>,
+) -> DocumentDiagnosticReport {
+    let result_id = result_id(&diagnostics);
+    if previous_result_id == Some(result_id.as_str()) {
+        return DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+            related_documents: None,
+            unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport { result_id },
+        });
+    }
+
+    DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+        related_documents: None,
+        full_document_diagnostic_report: FullDocumentDiagnosticReport {
+            result_id: Some(result_id),
+            items: diagnostics,
+        },
+    })
+}
+
+/// Report of `workspace/diagnostic` covering every document with diagnostics, or
+/// whose diagnostics were cleared since the client pulled them.
+pub fn workspace_report(
+    all_diagnostics: impl IntoIterator<Item = (String, Vec<Diagnostic>)>,
+    previous_result_ids: &[PreviousResultId],
+) -> WorkspaceDiagnosticReport {
+    let previous: HashMap<&Url, &str> = previous_result_ids
+        .iter()
+        .map(|previous| (&previous.uri, previous.value.as_str()))
+        .collect();
+
+    let items = all_diagnostics
+        .into_iter()
+        .filter_map(|(uri, diagnostics)| match Url::parse(&uri) {
+            Ok(uri) => Some((uri, diagnostics)),
+            Err(e) => {
+                debug!("leaving {uri} out of the workspace diagnostics: {e}");
+                None
+            }
+        })
+        .map(|(uri, diagnostics)| {
+            let result_id = result_id(&diagnostics);
+            if previous.get(&uri) == Some(&result_id.as_str()) {
+                WorkspaceDocumentDiagnosticReport::Unchanged(
+                    WorkspaceUnchangedDocumentDiagnosticReport {
+                        uri,
+                        version: None,
+                        unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                            result_id,
+                        },
+                    },
+                )
+            } else {
+                WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                    uri,
+                    version: None,
+                    full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                        result_id: Some(result_id),
+                        items: diagnostics,
+                    },
+                })
+            }
+        })
+        .collect();
+
+    WorkspaceDiagnosticReport { items }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Diagnostic, DocumentDiagnosticReport};
+
+    use super::*;
+
+    fn diagnostic(message: &str) -> Diagnostic {
+        Diagnostic {
+            message: message.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn full_result_id(report: &DocumentDiagnosticReport) -> String {
+        match report {
+            DocumentDiagnosticReport::Full(report) => report
+                .full_document_diagnostic_report
+                .result_id
+                .clone()
+                .unwrap(),
+            DocumentDiagnosticReport::Unchanged(_) => panic!("expected a full report"),
+        }
+    }
+
+    #[test]
+    fn it_reports_the_document_as_unchanged_until_its_diagnostics_change() {
+        let first = document_report(vec![diagnostic("Vulnerabilities found")], None);
+        let result_id = full_result_id(&first);
+
+        let unchanged =
+            document_report(vec![diagnostic("Vulnerabilities found")], Some(&result_id));
+        let changed = document_report(vec![diagnostic("No vulnerabilities")], Some(&result_id));
+
+        assert!(matches!(
+            unchanged,
+            DocumentDiagnosticReport::Unchanged(ref report)
+                if report.unchanged_document_diagnostic_report.result_id == result_id
+        ));
+        assert_ne!(full_result_id(&changed), result_id);
+    }
+
+    #[test]
+    fn it_reports_only_the_changed_documents_of_the_workspace_in_full() {
+        let dockerfile = Url::parse("file:///Dockerfile").unwrap();
+        let compose = Url::parse("file:///compose.yaml").unwrap();
+        let previous = PreviousResultId {
+            uri: dockerfile.clone(),
+            value: result_id(&[diagnostic("Vulnerabilities found")]),
+        };
+
+        let report = workspace_report(
+            [
+                (
+                    dockerfile.to_string(),
+                    vec![diagnostic("Vulnerabilities found")],
+                ),
+                (compose.to_string(), vec![]),
+                ("not a uri".to_string(), vec![diagnostic("Lost")]),
+            ],
+            &[previous],
+        );
+
+        let kinds: Vec<_> = report
+            .items
+            .iter()
+            .map(|item| match item {
+                WorkspaceDocumentDiagnosticReport::Full(report) => ("full", &report.uri),
+                WorkspaceDocumentDiagnosticReport::Unchanged(report) => ("unchanged", &report.uri),
+            })
+            .collect();
+        assert_eq!(kinds, [("unchanged", &dockerfile), ("full", &compose)]);
+    }
+}
@@ -0,0 +1,155 @@
+use serde_json::Value;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Diagnostic, DiagnosticTag, Url,
+};
+
+use crate::app::PackageVersion;
+
+use super::command_generator::CommandInfo;
+use super::commands::build_and_scan::{CVE_DATA_KEY, PACKAGES_DATA_KEY, RISK_ACCEPTED_DATA_KEY};
+use super::supported_commands::SupportedCommands;
+
+/// "Request accepted risk in Sysdig" for the hint of a vulnerability: one action
+/// scoped to the scanned image, and one per package the vulnerability was found in.
+pub fn request_accepted_risk_actions(
+    uri: &Url,
+    diagnostic: &Diagnostic,
+) -> Vec<CodeActionOrCommand> {
+    let Some(data) = diagnostic.data.as_ref() else {
+        return vec![];
+    };
+    let Some(cve) = data.get(CVE_DATA_KEY).and_then(Value::as_str) else {
+        return vec![];
+    };
+    if data.get(RISK_ACCEPTED_DATA_KEY).is_some() {
+        return vec![];
+    }
+
+    std::iter::once(None)
+        .chain(packages(data).into_iter().map(Some))
+        .map(|package| {
+            let command = CommandInfo::from(SupportedCommands::RequestAcceptedRisk {
+                uri: uri.clone(),
+                cve: cve.to_string(),
+                package,
+            });
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: command.title.clone(),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                command: Some(command.into()),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Marks the hints of `cve` (only the ones found in `package`, if given) as accepted.
+pub fn mark_risk_accepted(
+    diagnostics: Vec<Diagnostic>,
+    cve: &str,
+    package: Option<&PackageVersion>,
+) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .map(|mut diagnostic| {
+            let Some(data) = diagnostic.data.as_mut() else {
+                return diagnostic;
+            };
+            let is_accepted = data.get(CVE_DATA_KEY).and_then(Value::as_str) == Some(cve)
+                && data.get(RISK_ACCEPTED_DATA_KEY).is_none()
+                && package.is_none_or(|package| packages(data).contains(package));
+            if is_accepted {
+                data[RISK_ACCEPTED_DATA_KEY] = Value::Bool(true);
+                diagnostic.message.push_str(" (risk accepted)");
+                diagnostic.tags = Some(vec![DiagnosticTag::UNNECESSARY]);
+            }
+            diagnostic
+        })
+        .collect()
+}
+
+fn packages(data: &Value) -> Vec<PackageVersion> {
+    data.get(PACKAGES_DATA_KEY)
+        .and_then(|packages| serde_json::from_value(packages.clone()).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tower_lsp::lsp_types::{CodeActionOrCommand, Diagnostic, Url};
+
+    use super::*;
+
+    fn hint(cve: &str) -> Diagnostic {
+        Diagnostic {
+            message: format!("Vulnerability: {cve} (High)"),
+            data: Some(json!({
+                "cve": cve,
+                "packages": [{ "name": "openssl", "version": "3.1.4-r0" }],
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn openssl() -> PackageVersion {
+        PackageVersion {
+            name: "openssl".to_string(),
+            version: "3.1.4-r0".to_string(),
+        }
+    }
+
+    #[test]
+    fn it_offers_to_accept_the_risk_in_the_image_or_in_each_package() {
+        let uri = Url::parse("file:///Dockerfile").unwrap();
+
+        let titles: Vec<_> = request_accepted_risk_actions(&uri, &hint("CVE-2021-1234"))
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => action.title,
+                CodeActionOrCommand::Command(command) => command.title,
+            })
+            .collect();
+
+        assert_eq!(
+            titles,
+            [
+                "Request accepted risk in Sysdig for CVE-2021-1234",
+                "Request accepted risk in Sysdig for CVE-2021-1234 in openssl 3.1.4-r0",
+            ]
+        );
+        assert!(request_accepted_risk_actions(&uri, &Diagnostic::default()).is_empty());
+    }
+
+    #[test]
+    fn it_marks_the_hints_of_the_accepted_vulnerability() {
+        let other_package = PackageVersion {
+            name: "curl".to_string(),
+            ..openssl()
+        };
+
+        let not_marked = mark_risk_accepted(
+            vec![hint("CVE-2021-1234")],
+            "CVE-2021-1234",
+            Some(&other_package),
+        );
+        assert_eq!(not_marked, [hint("CVE-2021-1234")]);
+
+        let marked = mark_risk_accepted(
+            vec![hint("CVE-2021-1234"), hint("CVE-2021-5678")],
+            "CVE-2021-1234",
+            Some(&openssl()),
+        );
+        assert_eq!(
+            marked[0].message,
+            "Vulnerability: CVE-2021-1234 (High) (risk accepted)"
+        );
+        assert_eq!(marked[1], hint("CVE-2021-5678"));
+
+        let uri = Url::parse("file:///Dockerfile").unwrap();
+        assert!(request_accepted_risk_actions(&uri, &marked[0]).is_empty());
+        let marked_again = mark_risk_accepted(marked.clone(), "CVE-2021-1234", None);
+        assert_eq!(marked_again, marked);
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::{sync::Notify, task::AbortHandle};
+use tracing::{info, warn};
+
+/// Tasks of the commands in progress (scans, builds), so they can be stopped when the
+/// server shuts down or the connection is lost. Aborting a task drops its future,
+/// which kills the scanner processes it spawned (`kill_on_drop`) and closes its Docker
+/// build stream, cancelling the build.
+#[derive(Clone, Default)]
+pub struct RunningCommands {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, AbortHandle>>,
+    /// Notified every time a task is removed.
+    finished: Notify,
+    shutting_down: AtomicBool,
+}
+
+/// The command was aborted because the server is shutting down.
+#[derive(Debug)]
+pub struct CommandAborted;
+
+impl RunningCommands {
+    /// Runs `command` in a tracked task and waits for it. Dropping the returned future,
+    /// e.g. because the client cancelled the request, aborts the task too.
+    pub async fn run<F>(&self, command: F) -> Result<F::Output, CommandAborted>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        if self.inner.shutting_down.load(Ordering::SeqCst) {
+            return Err(CommandAborted);
+        }
+
+        let task = tokio::spawn(command);
+        let _tracked = TrackedTask::new(self.inner.clone(), task.abort_handle());
+        task.await.map_err(|_| CommandAborted)
+    }
+
+    /// Runs `command` in a tracked background task.
+    pub fn spawn<F>(&self, command: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let running = self.clone();
+        tokio::spawn(async move {
+            let _ = running.run(command).await;
+        });
+    }
+
+    /// Refuses new commands and gives the running ones `grace_period` to finish before
+    /// aborting them, waiting until they're stopped.
+    pub async fn shutdown(&self, grace_period: Duration) {
+        self.inner.shutting_down.store(true, Ordering::SeqCst);
+
+        let running = self.len();
+        if running == 0 {
+            return;
+        }
+        info!("waiting up to {grace_period:?} for {running} running commands to finish");
+        if tokio::time::timeout(grace_period, self.finished())
+            .await
+            .is_err()
+        {
+            warn!("aborting {} commands still running", self.len());
+            self.abort_all();
+            self.finished().await;
+        }
+    }
+
+    /// Aborts every running command without waiting for them, e.g. once the
+    /// connection to the client is lost.
+    pub fn abort_all(&self) {
+        self.inner.shutting_down.store(true, Ordering::SeqCst);
+        for task in self.tasks().values() {
+            task.abort();
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.tasks().len()
+    }
+
+    async fn finished(&self) {
+        loop {
+            let finished = self.inner.finished.notified();
+            if self.len() == 0 {
+                return;
+            }
+            finished.await;
+        }
+    }
+
+    fn tasks(&self) -> MutexGuard<'_, HashMap<u64, AbortHandle>> {
+        self.inner.tasks()
+    }
+}
+
+impl Inner {
+    fn tasks(&self) -> MutexGuard<'_, HashMap<u64, AbortHandle>> {
+        // Tasks are only inserted and removed while holding the lock, so a poisoned
+        // map is still consistent.
+        self.tasks
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Keeps a task in `RunningCommands` until it completes, aborting it if dropped before.
+struct TrackedTask {
+    inner: Arc<Inner>,
+    id: u64,
+    task: AbortHandle,
+}
+
+impl TrackedTask {
+    fn new(inner: Arc<Inner>, task: AbortHandle) -> Self {
+        let id = inner.next_id.fetch_add(1, Ordering::SeqCst);
+        inner.tasks().insert(id, task.clone());
+        Self { inner, id, task }
+    }
+}
+
+impl Drop for TrackedTask {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.inner.tasks().remove(&self.id);
+        self.inner.finished.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    /// Counts the commands whose future was dropped before completing.
+    struct DropCounter(Arc<AtomicUsize>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn command(duration: Duration, dropped: &Arc<AtomicUsize>) -> impl Future<Output = ()> + use<> {
+        let counter = DropCounter(dropped.clone());
+        async move {
+            tokio::time::sleep(duration).await;
+            std::mem::forget(counter);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_lets_commands_finish_within_the_grace_period() {
+        let running = RunningCommands::default();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        running.spawn(command(Duration::from_secs(1), &dropped));
+        tokio::task::yield_now().await;
+
+        running.shutdown(Duration::from_secs(5)).await;
+
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+        assert_eq!(running.len(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_aborts_the_commands_outliving_the_grace_period() {
+        let running = RunningCommands::default();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let request = tokio::spawn({
+            let running = running.clone();
+            let command = command(Duration::from_secs(60), &dropped);
+            async move { running.run(command).await }
+        });
+        tokio::task::yield_now().await;
+
+        running.shutdown(Duration::from_secs(5)).await;
+
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        assert!(request.await.unwrap().is_err());
+        assert!(running.run(async {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn it_aborts_the_command_when_the_request_is_dropped() {
+        let running = RunningCommands::default();
+        let dropped = Arc::new(AtomicUsize::new(0));
+
+        let request = running.run(command(Duration::from_secs(60), &dropped));
+        let _ = tokio::time::timeout(Duration::from_millis(10), request).await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+        assert_eq!(running.len(), 0);
+    }
+}
@@ -0,0 +1,38 @@
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::AbortHandle;
+
+use crate::app::{LSPClient, LspInteractor, ScanScheduler};
+
+/// Forwards the state of the scan queue to the client with `sysdig/scanQueue`, and the
+/// phases of the running scans with `sysdig/scanProgress`, until the scheduler is
+/// replaced by a new configuration.
+pub(super) fn spawn_scan_queue_notifier<C>(
+    scheduler: &ScanScheduler,
+    interactor: LspInteractor<C>,
+) -> AbortHandle
+where
+    C: LSPClient + Send + Sync + 'static,
+{
+    let mut queue = scheduler.subscribe();
+    let mut progress = scheduler.subscribe_progress();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                changed = queue.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let state = queue.borrow_and_update().clone();
+                    interactor.send_scan_queue(state).await;
+                }
+                update = progress.recv() => match update {
+                    Ok(update) => interactor.send_scan_progress(update).await,
+                    // Phases are superseded by the next ones, so missing some is fine.
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    })
+    .abort_handle()
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+};
+
+use crate::domain::scanresult::severity::Severity;
+
+use super::command_generator::ImageReference;
+
+/// Image references whose last scan reported Critical or High vulnerabilities.
+pub const VULNERABLE_IMAGE_TOKEN_TYPE: &str = "vulnerableImage";
+/// Modifiers telling the most severe vulnerability of a vulnerable image apart.
+pub const CRITICAL_TOKEN_MODIFIER: &str = "critical";
+pub const HIGH_TOKEN_MODIFIER: &str = "high";
+
+/// Token types and modifiers the server reports, indexed by the encoded tokens.
+pub fn semantic_tokens_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![SemanticTokenType::new(VULNERABLE_IMAGE_TOKEN_TYPE)],
+        token_modifiers: vec![
+            SemanticTokenModifier::new(CRITICAL_TOKEN_MODIFIER),
+            SemanticTokenModifier::new(HIGH_TOKEN_MODIFIER),
+        ],
+    }
+}
+
+/// Tokens of the image references whose scan reported Critical or High
+/// vulnerabilities, relative-encoded in document order as LSP expects.
+pub fn vulnerable_image_tokens(
+    references: &[ImageReference],
+    image_severities: &HashMap<String, Severity>,
+) -> Vec<SemanticToken> {
+    let mut vulnerable: Vec<_> = references
+        .iter()
+        // Tokens can't span lines.
+        .filter(|reference| reference.range.start.line == reference.range.end.line)
+        .filter_map(|reference| {
+            let modifier = match image_severities.get(&reference.image)? {
+                Severity::Critical => 0,
+                Severity::High => 1,
+                _ => return None,
+            };
+            Some((reference.range, modifier))
+        })
+        .collect();
+    vulnerable.sort_by_key(|(range, _)| (range.start.line, range.start.character));
+    vulnerable.dedup_by_key(|(range, _)| range.start);
+
+    let mut previous = None;
+    vulnerable
+        .into_iter()
+        .map(|(range, modifier)| {
+            let (delta_line, delta_start) = match previous.replace(range.start) {
+                Some(prev) if prev.line == range.start.line => {
+                    (0, range.start.character - prev.character)
+                }
+                Some(prev) => (range.start.line - prev.line, range.start.character),
+                None => (range.start.line, range.start.character),
+            };
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: range.end.character.saturating_sub(range.start.character),
+                token_type: 0,
+                token_modifiers_bitset: 1 << modifier,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::*;
+
+    fn reference(image: &str, line: u32, start: u32) -> ImageReference {
+        ImageReference {
+            image: image.to_string(),
+            range: Range::new(
+                Position::new(line, start),
+                Position::new(line, start + image.len() as u32),
+            ),
+            platform: None,
+        }
+    }
+
+    #[test]
+    fn it_encodes_the_vulnerable_images_relative_to_the_previous_one() {
+        let references = [
+            reference("nginx:1.19", 4, 11),
+            reference("alpine:3.21", 1, 5),
+            reference("postgres:13", 4, 30),
+            reference("redis:7", 6, 11),
+        ];
+        let severities = HashMap::from([
+            ("alpine:3.21".to_string(), Severity::High),
+            ("nginx:1.19".to_string(), Severity::Critical),
+            ("postgres:13".to_string(), Severity::High),
+            ("redis:7".to_string(), Severity::Medium),
+        ]);
+
+        let tokens = vulnerable_image_tokens(&references, &severities);
+
+        let encoded: Vec<_> = tokens
+            .iter()
+            .map(|t| {
+                (
+                    t.delta_line,
+                    t.delta_start,
+                    t.length,
+                    t.token_modifiers_bitset,
+                )
+            })
+            .collect();
+        assert_eq!(
+            encoded,
+            vec![(1, 5, 11, 0b10), (3, 11, 10, 0b01), (0, 19, 11, 0b10)]
+        );
+    }
+
+    #[test]
+    fn it_doesnt_highlight_images_never_scanned() {
+        let tokens = vulnerable_image_tokens(&[reference("alpine", 0, 5)], &HashMap::new());
+
+        assert!(tokens.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::task::AbortHandle;
+use tower_lsp::lsp_types::{MessageType, notification::Notification};
+use tracing::{info, warn};
+
+use crate::app::{
+    CredentialsError, LSPClient, LspInteractor,
+    component_factory::{Components, Config, ScannerMode},
+    protocol::{CacheCounts, ScanCounts, ServerStatusResult, ServerVersionResult},
+    redaction::REDACTED,
+};
+
+/// `sysdig/serverStatus` notification, telling clients whether scans can work with
+/// the current configuration (e.g. to show it in a status bar).
+pub enum ServerStatusNotification {}
+
+impl Notification for ServerStatusNotification {
+    type Params = ServerStatusParams;
+    const METHOD: &'static str = "sysdig/serverStatus";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerStatusParams {
+    pub health: ServerHealth,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerHealth {
+    Ok,
+    /// The Sysdig API couldn't be reached, scans may fail.
+    Warning,
+    /// The credentials were rejected, scans will fail.
+    Error,
+    /// No settings were received yet, so nothing can be scanned until they are.
+    NotConfigured,
+}
+
+/// Checks the credentials of the configured tenant in the background, reporting
+/// the outcome with `window/showMessage` and `sysdig/serverStatus`.
+pub(super) fn spawn_credentials_check<C>(
+    components: Arc<Components>,
+    interactor: LspInteractor<C>,
+) -> Option<AbortHandle>
+where
+    C: LSPClient + Send + Sync + 'static,
+{
+    components.credentials_checker.as_ref()?;
+
+    let task = tokio::spawn(async move {
+        let Some(checker) = components.credentials_checker.as_ref() else {
+            return;
+        };
+
+        let (health, message_type, message) = match checker.check_credentials().await {
+            Ok(()) => (ServerHealth::Ok, MessageType::INFO, None),
+            Err(e @ CredentialsError::Rejected(_)) => (
+                ServerHealth::Error,
+                MessageType::ERROR,
+                Some(format!(
+                    "{e}, scans will fail until `sysdig.api_token` is fixed"
+                )),
+            ),
+            Err(e @ CredentialsError::Unreachable { .. }) => (
+                ServerHealth::Warning,
+                MessageType::WARNING,
+                Some(e.to_string()),
+            ),
+        };
+
+        let shown = match &message {
+            None => {
+                info!("Sysdig API credentials verified");
+                "Sysdig API credentials verified".to_string()
+            }
+            Some(message) => {
+                warn!("credentials check failed: {message}");
+                format!("Sysdig LSP: {message}")
+            }
+        };
+        interactor.show_message(message_type, &shown).await;
+        interactor
+            .send_server_status(ServerStatusParams { health, message })
+            .await;
+    });
+    Some(task.abort_handle())
+}
+
+/// Answer of `sysdig/version`.
+pub(super) fn server_version(components: Option<&Components>) -> ServerVersionResult {
+    ServerVersionResult {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        scanner_version: components
+            .and_then(|components| components.scan_scheduler.statistics().scanner_version()),
+    }
+}
+
+/// Answer of `sysdig/status`, with the settings of `config` once `components` were
+/// created from them.
+pub(super) fn server_status(
+    config: &Config,
+    components: Option<&Components>,
+) -> ServerStatusResult {
+    let Some(components) = components else {
+        return ServerStatusResult {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            configured: false,
+            api_url: None,
+            api_token: None,
+            scanner_mode: None,
+            scanner_version: None,
+            offline: false,
+            scans: ScanCounts::default(),
+            cache: CacheCounts::default(),
+        };
+    };
+
+    let statistics = components.scan_scheduler.statistics();
+    ServerStatusResult {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        configured: true,
+        api_url: Some(config.sysdig.api_url.clone()),
+        // The components can't be created without a token.
+        api_token: Some(REDACTED.to_string()),
+        scanner_mode: Some(
+            match config.sysdig.scanner.mode {
+                ScannerMode::Cli => "cli",
+                ScannerMode::Api => "api",
+            }
+            .to_string(),
+        ),
+        scanner_version: statistics.scanner_version(),
+        offline: config.sysdig.scan.offline,
+        scans: components.scan_scheduler.scan_counts(),
+        cache: statistics.cache_counts(config.sysdig.cache.scan_results_ttl_seconds),
+    }
+}
@@ -1,21 +1,162 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
+use chrono::NaiveDate;
+use serde::Deserialize;
 use tower_lsp::{
     jsonrpc::{self, Error},
-    lsp_types::{ExecuteCommandParams, Location, Url},
+    lsp_types::{ExecuteCommandParams, Location, Position, Range, Url},
 };
 
+use crate::app::PackageVersion;
+use crate::domain::image_reference::is_image_platform;
+use crate::domain::scanresult::accepted_risk_reason::AcceptedRiskReason;
+use crate::domain::scanresult::sbom::SbomFormat;
+
 const CMD_EXECUTE_SCAN: &str = "sysdig-lsp.execute-scan";
 const CMD_BUILD_AND_SCAN: &str = "sysdig-lsp.execute-build-and-scan";
 const CMD_EXECUTE_IAC_SCAN: &str = "sysdig-lsp.execute-iac-scan";
+const CMD_SCAN_IMAGE_UNDER_CURSOR: &str = "sysdig-lsp.scan-image-under-cursor";
+const CMD_EXPORT_SCAN_RESULT: &str = "sysdig-lsp.export-scan-result";
+const CMD_EXPORT_SARIF: &str = "sysdig-lsp.export-sarif";
+const CMD_EXPORT_LICENSES: &str = "sysdig-lsp.export-licenses";
+const CMD_GENERATE_SBOM: &str = "sysdig-lsp.generate-sbom";
+const CMD_GENERATE_SYSDIG_IGNORE: &str = "sysdig-lsp.generate-sysdigignore";
+const CMD_ACCEPT_RISKS: &str = "sysdig-lsp.accept-risks";
+const CMD_SCAN_ALL_IMAGES: &str = "sysdig-lsp.scan-all-images";
+const CMD_SHOW_ALL_LAYER_FINDINGS: &str = "sysdig-lsp.show-all-layer-findings";
+const CMD_SHOW_SCAN_REPORT: &str = "sysdig-lsp.show-scan-report";
+const CMD_OPEN_SCAN_IN_BROWSER: &str = "sysdig-lsp.open-scan-in-browser";
+const CMD_PIN_IMAGE_DIGEST: &str = "sysdig-lsp.pin-image-digest";
+const CMD_RECOMMEND_BASE_IMAGE: &str = "sysdig-lsp.recommend-base-image";
+const CMD_REQUEST_ACCEPTED_RISK: &str = "sysdig-lsp.request-accepted-risk";
+const CMD_SCAN_DIRECTORY: &str = "sysdig-lsp.scan-directory";
+const CMD_CLEANUP_TEMP_IMAGES: &str = "sysdig-lsp.cleanup-temp-images";
+const CMD_LIST_IMAGES: &str = "sysdig-lsp.list-images";
 
 // The variants intentionally mirror the `sysdig-lsp.execute-*` command identifiers.
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
 pub enum SupportedCommands {
-    ExecuteBaseImageScan { location: Location, image: String },
-    ExecuteBuildAndScan { location: Location },
-    ExecuteIacScan { uri: Option<Url> },
+    /// Scans `image`, the variant of `platform` (e.g. `linux/arm64`) if given, and the
+    /// one of `sysdig.scan.platform` (or of the host) otherwise.
+    ExecuteBaseImageScan {
+        location: Location,
+        image: String,
+        platform: Option<String>,
+    },
+    /// Builds the Dockerfile of `location`, up to the `target` stage if given (as
+    /// `--target` does), and scans the image.
+    ExecuteBuildAndScan {
+        location: Location,
+        target: Option<String>,
+    },
+    ExecuteIacScan {
+        uri: Option<Url>,
+    },
+    ScanImageUnderCursor {
+        uri: Url,
+        position: Position,
+    },
+    /// Scans every image referenced in the document concurrently.
+    ScanAllImages {
+        uri: Url,
+    },
+    /// Returns the last scan result of the document as JSON, or writes it to `path`.
+    ExportScanResult {
+        uri: Url,
+        path: Option<PathBuf>,
+    },
+    /// Returns the last scan result of the document as a SARIF 2.1.0 log, or writes it to `path`.
+    ExportSarif {
+        uri: Url,
+        path: Option<PathBuf>,
+    },
+    /// Returns the packages of the last scan of the document grouped by license as JSON,
+    /// or writes them to `path`.
+    ExportLicenses {
+        uri: Url,
+        path: Option<PathBuf>,
+    },
+    /// Returns the package inventory of the last scan of the document as an SBOM in
+    /// `format`, or writes it to `path` (relative paths are next to the document).
+    GenerateSbom {
+        uri: Url,
+        format: SbomFormat,
+        path: Option<PathBuf>,
+    },
+    /// Adds the findings of the last scan of the document to the `.sysdigignore` at the
+    /// workspace root (or at `path`), so only new findings are reported from then on.
+    GenerateSysdigIgnore {
+        uri: Url,
+        path: Option<PathBuf>,
+    },
+    /// Accepts the risk of `cves` in the image of the last scan of the document, all
+    /// with the same reason, description and expiration date.
+    AcceptRisks {
+        uri: Url,
+        cves: Vec<String>,
+        reason: AcceptedRiskReason,
+        description: String,
+        expiration_date: Option<NaiveDate>,
+    },
+    /// Returns the full report of the layer built by the instruction at `position`,
+    /// as markdown, for the vulnerabilities left out of its hints.
+    ShowAllLayerFindings {
+        uri: Url,
+        position: Position,
+    },
+    /// Returns the full report of the last scan of the document as markdown, for
+    /// clients to show in a preview pane. With a `position`, of the image scanned there.
+    ShowScanReport {
+        uri: Url,
+        position: Option<Position>,
+    },
+    /// Returns the URL of the last scan of the document in Sysdig Secure, for clients to
+    /// open in a browser.
+    OpenScanInBrowser {
+        uri: Url,
+    },
+    /// Rewrites the reference of `image` at `location` to the digest its tag currently
+    /// points to in the registry.
+    PinImageDigest {
+        location: Location,
+        image: String,
+    },
+    /// Scans alternatives to the `image` referenced at `location` and ranks them, to
+    /// recommend the least vulnerable one.
+    RecommendBaseImage {
+        location: Location,
+        image: String,
+    },
+    /// Accepts the risk of `cve` in the image of the last scan of the document, or
+    /// in `package` (in any image) when given.
+    RequestAcceptedRisk {
+        uri: Url,
+        cve: String,
+        package: Option<PackageVersion>,
+    },
+    /// Scans the packages of the folder at `uri` (the workspace root by default),
+    /// reporting their vulnerabilities on the files listing them.
+    ScanDirectory {
+        uri: Option<Url>,
+    },
+    /// Removes the images "Build and scan" left behind in the Docker daemon, returning
+    /// their names.
+    CleanupTempImages,
+    /// Returns every image referenced in the workspace with the summary of its last
+    /// scan, as JSON.
+    ListImages,
+}
+
+/// Second argument of `sysdig-lsp.accept-risks`.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AcceptRisksArguments {
+    cves: Vec<String>,
+    reason: String,
+    #[serde(default)]
+    description: String,
+    expiration_date: Option<NaiveDate>,
 }
 
 impl SupportedCommands {
@@ -24,15 +165,58 @@ impl SupportedCommands {
             SupportedCommands::ExecuteBaseImageScan { .. } => CMD_EXECUTE_SCAN,
             SupportedCommands::ExecuteBuildAndScan { .. } => CMD_BUILD_AND_SCAN,
             SupportedCommands::ExecuteIacScan { .. } => CMD_EXECUTE_IAC_SCAN,
+            SupportedCommands::ScanImageUnderCursor { .. } => CMD_SCAN_IMAGE_UNDER_CURSOR,
+            SupportedCommands::ScanAllImages { .. } => CMD_SCAN_ALL_IMAGES,
+            SupportedCommands::ExportScanResult { .. } => CMD_EXPORT_SCAN_RESULT,
+            SupportedCommands::ExportSarif { .. } => CMD_EXPORT_SARIF,
+            SupportedCommands::ExportLicenses { .. } => CMD_EXPORT_LICENSES,
+            SupportedCommands::GenerateSbom { .. } => CMD_GENERATE_SBOM,
+            SupportedCommands::GenerateSysdigIgnore { .. } => CMD_GENERATE_SYSDIG_IGNORE,
+            SupportedCommands::AcceptRisks { .. } => CMD_ACCEPT_RISKS,
+            SupportedCommands::ShowAllLayerFindings { .. } => CMD_SHOW_ALL_LAYER_FINDINGS,
+            SupportedCommands::ShowScanReport { .. } => CMD_SHOW_SCAN_REPORT,
+            SupportedCommands::OpenScanInBrowser { .. } => CMD_OPEN_SCAN_IN_BROWSER,
+            SupportedCommands::PinImageDigest { .. } => CMD_PIN_IMAGE_DIGEST,
+            SupportedCommands::RecommendBaseImage { .. } => CMD_RECOMMEND_BASE_IMAGE,
+            SupportedCommands::RequestAcceptedRisk { .. } => CMD_REQUEST_ACCEPTED_RISK,
+            SupportedCommands::ScanDirectory { .. } => CMD_SCAN_DIRECTORY,
+            SupportedCommands::CleanupTempImages => CMD_CLEANUP_TEMP_IMAGES,
+            SupportedCommands::ListImages => CMD_LIST_IMAGES,
         }
         .to_string()
     }
 
+    /// Whether `command` scans the image of a single reference, so it can re-scan it.
+    pub fn is_image_scan(command: &str) -> bool {
+        matches!(command, CMD_EXECUTE_SCAN | CMD_BUILD_AND_SCAN)
+    }
+
     pub fn all_supported_commands_as_string() -> Vec<String> {
-        [CMD_EXECUTE_SCAN, CMD_BUILD_AND_SCAN, CMD_EXECUTE_IAC_SCAN]
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect()
+        [
+            CMD_EXECUTE_SCAN,
+            CMD_BUILD_AND_SCAN,
+            CMD_EXECUTE_IAC_SCAN,
+            CMD_SCAN_IMAGE_UNDER_CURSOR,
+            CMD_SCAN_ALL_IMAGES,
+            CMD_EXPORT_SCAN_RESULT,
+            CMD_EXPORT_SARIF,
+            CMD_EXPORT_LICENSES,
+            CMD_GENERATE_SBOM,
+            CMD_GENERATE_SYSDIG_IGNORE,
+            CMD_ACCEPT_RISKS,
+            CMD_SHOW_ALL_LAYER_FINDINGS,
+            CMD_SHOW_SCAN_REPORT,
+            CMD_OPEN_SCAN_IN_BROWSER,
+            CMD_PIN_IMAGE_DIGEST,
+            CMD_RECOMMEND_BASE_IMAGE,
+            CMD_REQUEST_ACCEPTED_RISK,
+            CMD_SCAN_DIRECTORY,
+            CMD_CLEANUP_TEMP_IMAGES,
+            CMD_LIST_IMAGES,
+        ]
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect()
     }
 }
 
@@ -48,11 +232,55 @@ impl TryFrom<ExecuteCommandParams> for SupportedCommands {
                     .as_str()
                     .ok_or_else(|| Error::invalid_params("image must be string"))?
                     .to_owned(),
+                platform: None,
+            }),
+            (CMD_EXECUTE_SCAN, [location, image, platform]) if location.is_object() => {
+                let platform = platform
+                    .as_str()
+                    .filter(|platform| is_image_platform(platform))
+                    .ok_or_else(|| {
+                        Error::invalid_params(
+                            "platform must be `os/arch[/variant]`, e.g. `linux/arm64`",
+                        )
+                    })?;
+                Ok(SupportedCommands::ExecuteBaseImageScan {
+                    location: serde_json::from_value(location.clone())
+                        .map_err(|_| Error::invalid_params("location must be a Location object"))?,
+                    image: image
+                        .as_str()
+                        .ok_or_else(|| Error::invalid_params("image must be string"))?
+                        .to_owned(),
+                    platform: Some(platform.to_owned()),
+                })
+            }
+            (CMD_EXECUTE_SCAN, [uri, line, image]) => Ok(SupportedCommands::ExecuteBaseImageScan {
+                location: legacy_location(uri, line)?,
+                image: image
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("image must be string"))?
+                    .to_owned(),
+                platform: None,
             }),
             (CMD_BUILD_AND_SCAN, [location]) => Ok(SupportedCommands::ExecuteBuildAndScan {
                 location: serde_json::from_value(location.clone())
                     .map_err(|_| Error::invalid_params("location must be a Location object"))?,
+                target: None,
             }),
+            (CMD_BUILD_AND_SCAN, [location, target]) => {
+                Ok(SupportedCommands::ExecuteBuildAndScan {
+                    location: serde_json::from_value(location.clone())
+                        .map_err(|_| Error::invalid_params("location must be a Location object"))?,
+                    target: Some(
+                        target
+                            .as_str()
+                            .filter(|target| !target.trim().is_empty())
+                            .ok_or_else(|| {
+                                Error::invalid_params("target must be the name of a stage")
+                            })?
+                            .to_owned(),
+                    ),
+                })
+            }
             (CMD_EXECUTE_IAC_SCAN, []) => Ok(SupportedCommands::ExecuteIacScan { uri: None }),
             (CMD_EXECUTE_IAC_SCAN, [uri]) => {
                 let uri = uri
@@ -65,6 +293,178 @@ impl TryFrom<ExecuteCommandParams> for SupportedCommands {
             (CMD_EXECUTE_IAC_SCAN, _) => {
                 Err(Error::invalid_params("expected at most one uri argument"))
             }
+            (CMD_SCAN_DIRECTORY, []) => Ok(SupportedCommands::ScanDirectory { uri: None }),
+            (CMD_SCAN_DIRECTORY, [uri]) => {
+                let uri = uri
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+                let uri = Url::parse(uri)
+                    .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+                Ok(SupportedCommands::ScanDirectory { uri: Some(uri) })
+            }
+            (CMD_SCAN_DIRECTORY, _) => {
+                Err(Error::invalid_params("expected at most one uri argument"))
+            }
+            (CMD_CLEANUP_TEMP_IMAGES, []) => Ok(SupportedCommands::CleanupTempImages),
+            (CMD_CLEANUP_TEMP_IMAGES, _) => Err(Error::invalid_params("expected no arguments")),
+            (CMD_LIST_IMAGES, []) => Ok(SupportedCommands::ListImages),
+            (CMD_LIST_IMAGES, _) => Err(Error::invalid_params("expected no arguments")),
+            (CMD_SCAN_IMAGE_UNDER_CURSOR, arguments) => {
+                let (uri, position) = uri_and_position(arguments)?;
+                Ok(SupportedCommands::ScanImageUnderCursor { uri, position })
+            }
+            (CMD_SCAN_ALL_IMAGES, [uri]) => {
+                let uri = uri
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+                let uri = Url::parse(uri)
+                    .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+                Ok(SupportedCommands::ScanAllImages { uri })
+            }
+            (CMD_SCAN_ALL_IMAGES, _) => Err(Error::invalid_params("expected a uri as argument")),
+            (CMD_SHOW_ALL_LAYER_FINDINGS, arguments) => {
+                let (uri, position) = uri_and_position(arguments)?;
+                Ok(SupportedCommands::ShowAllLayerFindings { uri, position })
+            }
+            (CMD_SHOW_SCAN_REPORT, [uri]) => {
+                let uri = uri
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+                let uri = Url::parse(uri)
+                    .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+                Ok(SupportedCommands::ShowScanReport {
+                    uri,
+                    position: None,
+                })
+            }
+            (CMD_SHOW_SCAN_REPORT, arguments) => {
+                let (uri, position) = uri_and_position(arguments)?;
+                Ok(SupportedCommands::ShowScanReport {
+                    uri,
+                    position: Some(position),
+                })
+            }
+            (CMD_OPEN_SCAN_IN_BROWSER, [uri]) => {
+                let uri = uri
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+                let uri = Url::parse(uri)
+                    .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+                Ok(SupportedCommands::OpenScanInBrowser { uri })
+            }
+            (CMD_OPEN_SCAN_IN_BROWSER, _) => {
+                Err(Error::invalid_params("expected a uri as argument"))
+            }
+            (CMD_PIN_IMAGE_DIGEST, [location, image]) => Ok(SupportedCommands::PinImageDigest {
+                location: serde_json::from_value(location.clone())
+                    .map_err(|_| Error::invalid_params("location must be a Location object"))?,
+                image: image
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("image must be string"))?
+                    .to_owned(),
+            }),
+            (CMD_PIN_IMAGE_DIGEST, _) => Err(Error::invalid_params(
+                "expected a location and an image as arguments",
+            )),
+            (CMD_RECOMMEND_BASE_IMAGE, [location, image]) => {
+                Ok(SupportedCommands::RecommendBaseImage {
+                    location: serde_json::from_value(location.clone())
+                        .map_err(|_| Error::invalid_params("location must be a Location object"))?,
+                    image: image
+                        .as_str()
+                        .ok_or_else(|| Error::invalid_params("image must be string"))?
+                        .to_owned(),
+                })
+            }
+            (CMD_RECOMMEND_BASE_IMAGE, _) => Err(Error::invalid_params(
+                "expected a location and an image as arguments",
+            )),
+            (CMD_EXPORT_SCAN_RESULT, arguments) => {
+                let (uri, path) = uri_and_optional_path(arguments)?;
+                Ok(SupportedCommands::ExportScanResult { uri, path })
+            }
+            (CMD_EXPORT_SARIF, arguments) => {
+                let (uri, path) = uri_and_optional_path(arguments)?;
+                Ok(SupportedCommands::ExportSarif { uri, path })
+            }
+            (CMD_EXPORT_LICENSES, arguments) => {
+                let (uri, path) = uri_and_optional_path(arguments)?;
+                Ok(SupportedCommands::ExportLicenses { uri, path })
+            }
+            (CMD_GENERATE_SBOM, [uri, rest @ ..]) if rest.len() <= 2 => {
+                let format = match rest.first() {
+                    Some(format) => format
+                        .as_str()
+                        .ok_or_else(|| Error::invalid_params("format must be a string"))?
+                        .parse()
+                        .map_err(Error::invalid_params)?,
+                    None => SbomFormat::default(),
+                };
+                let arguments = [uri.clone()]
+                    .into_iter()
+                    .chain(rest.get(1).cloned())
+                    .collect::<Vec<_>>();
+                let (uri, path) = uri_and_optional_path(&arguments)?;
+                Ok(SupportedCommands::GenerateSbom { uri, format, path })
+            }
+            (CMD_GENERATE_SBOM, _) => Err(Error::invalid_params(
+                "expected a uri, an optional format and an optional output path as arguments",
+            )),
+            (CMD_GENERATE_SYSDIG_IGNORE, arguments) => {
+                let (uri, path) = uri_and_optional_path(arguments)?;
+                Ok(SupportedCommands::GenerateSysdigIgnore { uri, path })
+            }
+            (CMD_ACCEPT_RISKS, [uri, arguments]) => {
+                let uri = uri
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+                let uri = Url::parse(uri)
+                    .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+                let arguments: AcceptRisksArguments = serde_json::from_value(arguments.clone())
+                    .map_err(|e| {
+                        Error::invalid_params(format!("invalid risk acceptance arguments: {e}"))
+                    })?;
+                if arguments.cves.is_empty() {
+                    return Err(Error::invalid_params("cves must not be empty"));
+                }
+                Ok(SupportedCommands::AcceptRisks {
+                    uri,
+                    cves: arguments.cves,
+                    reason: arguments.reason.parse().map_err(Error::invalid_params)?,
+                    description: arguments.description,
+                    expiration_date: arguments.expiration_date,
+                })
+            }
+            (CMD_ACCEPT_RISKS, _) => Err(Error::invalid_params(
+                "expected a uri and the risk acceptance arguments",
+            )),
+            (CMD_REQUEST_ACCEPTED_RISK, [uri, cve, package @ ..]) if package.len() <= 1 => {
+                let uri = uri
+                    .as_str()
+                    .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+                let uri = Url::parse(uri)
+                    .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+                let package = package
+                    .first()
+                    .map(|package| serde_json::from_value(package.clone()))
+                    .transpose()
+                    .map_err(|e| {
+                        Error::invalid_params(format!(
+                            "package must have a name and a version: {e}"
+                        ))
+                    })?;
+                Ok(SupportedCommands::RequestAcceptedRisk {
+                    uri,
+                    cve: cve
+                        .as_str()
+                        .ok_or_else(|| Error::invalid_params("cve must be a string"))?
+                        .to_owned(),
+                    package,
+                })
+            }
+            (CMD_REQUEST_ACCEPTED_RISK, _) => Err(Error::invalid_params(
+                "expected a uri, a cve and an optional package",
+            )),
             (other, _) => Err(Error::invalid_params(format!(
                 "command not supported: {other}"
             ))),
@@ -72,21 +472,168 @@ impl TryFrom<ExecuteCommandParams> for SupportedCommands {
     }
 }
 
+/// `Location` of the `[uri, line, image]` arguments older clients send to
+/// `sysdig-lsp.execute-scan`, spanning the whole (0-based) line.
+fn legacy_location(
+    uri: &serde_json::Value,
+    line: &serde_json::Value,
+) -> std::result::Result<Location, jsonrpc::Error> {
+    let uri = uri
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+    let uri = Url::parse(uri)
+        .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+    let line = line
+        .as_u64()
+        .and_then(|line| u32::try_from(line).ok())
+        .ok_or_else(|| Error::invalid_params("line must be a non-negative integer"))?;
+
+    Ok(Location::new(
+        uri,
+        Range::new(Position::new(line, 0), Position::new(line + 1, 0)),
+    ))
+}
+
+/// Arguments of the commands acting on a position of a document.
+fn uri_and_position(
+    arguments: &[serde_json::Value],
+) -> std::result::Result<(Url, Position), jsonrpc::Error> {
+    let [uri, position] = arguments else {
+        return Err(Error::invalid_params(
+            "expected a uri and a position as arguments",
+        ));
+    };
+    let uri = uri
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+    let uri = Url::parse(uri)
+        .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+    let position = serde_json::from_value(position.clone())
+        .map_err(|_| Error::invalid_params("position must be a Position object"))?;
+
+    Ok((uri, position))
+}
+
+/// Arguments of the commands writing a file: a document URI and an optional output path.
+fn uri_and_optional_path(
+    arguments: &[serde_json::Value],
+) -> std::result::Result<(Url, Option<PathBuf>), jsonrpc::Error> {
+    let [uri, rest @ ..] = arguments else {
+        return Err(Error::invalid_params(
+            "expected a uri and an optional output path as arguments",
+        ));
+    };
+    let path = match rest {
+        [] => None,
+        [path] => {
+            Some(PathBuf::from(path.as_str().ok_or_else(|| {
+                Error::invalid_params("path must be a string")
+            })?))
+        }
+        _ => {
+            return Err(Error::invalid_params(
+                "expected a uri and an optional output path as arguments",
+            ));
+        }
+    };
+    let uri = uri
+        .as_str()
+        .ok_or_else(|| Error::invalid_params("uri must be a string"))?;
+    let uri = Url::parse(uri)
+        .map_err(|e| Error::invalid_params(format!("uri must be a valid URI: {e}")))?;
+
+    Ok((uri, path))
+}
+
 impl Display for SupportedCommands {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            SupportedCommands::ExecuteBaseImageScan { location, image } => {
+            SupportedCommands::ExecuteBaseImageScan {
+                location,
+                image,
+                platform,
+            } => {
                 write!(
                     f,
-                    "ExecuteBaseImageScan(location: {location:?}, image: {image})",
+                    "ExecuteBaseImageScan(location: {location:?}, image: {image}, platform: {platform:?})",
                 )
             }
-            SupportedCommands::ExecuteBuildAndScan { location } => {
-                write!(f, "ExecuteBuildAndScan(location: {location:?})")
+            SupportedCommands::ExecuteBuildAndScan { location, target } => {
+                write!(
+                    f,
+                    "ExecuteBuildAndScan(location: {location:?}, target: {target:?})"
+                )
             }
             SupportedCommands::ExecuteIacScan { uri } => {
                 write!(f, "ExecuteIacScan(uri: {uri:?})")
             }
+            SupportedCommands::ScanImageUnderCursor { uri, position } => {
+                write!(
+                    f,
+                    "ScanImageUnderCursor(uri: {uri}, position: {position:?})"
+                )
+            }
+            SupportedCommands::ScanAllImages { uri } => {
+                write!(f, "ScanAllImages(uri: {uri})")
+            }
+            SupportedCommands::ExportScanResult { uri, path } => {
+                write!(f, "ExportScanResult(uri: {uri}, path: {path:?})")
+            }
+            SupportedCommands::ExportSarif { uri, path } => {
+                write!(f, "ExportSarif(uri: {uri}, path: {path:?})")
+            }
+            SupportedCommands::ExportLicenses { uri, path } => {
+                write!(f, "ExportLicenses(uri: {uri}, path: {path:?})")
+            }
+            SupportedCommands::GenerateSbom { uri, format, path } => {
+                write!(
+                    f,
+                    "GenerateSbom(uri: {uri}, format: {format}, path: {path:?})"
+                )
+            }
+            SupportedCommands::GenerateSysdigIgnore { uri, path } => {
+                write!(f, "GenerateSysdigIgnore(uri: {uri}, path: {path:?})")
+            }
+            SupportedCommands::AcceptRisks {
+                uri, cves, reason, ..
+            } => {
+                write!(
+                    f,
+                    "AcceptRisks(uri: {uri}, cves: {cves:?}, reason: {reason:?})"
+                )
+            }
+            SupportedCommands::ShowAllLayerFindings { uri, position } => {
+                write!(
+                    f,
+                    "ShowAllLayerFindings(uri: {uri}, position: {position:?})"
+                )
+            }
+            SupportedCommands::ShowScanReport { uri, position } => {
+                write!(f, "ShowScanReport(uri: {uri}, position: {position:?})")
+            }
+            SupportedCommands::OpenScanInBrowser { uri } => {
+                write!(f, "OpenScanInBrowser(uri: {uri})")
+            }
+            SupportedCommands::PinImageDigest { location, image } => {
+                write!(f, "PinImageDigest(location: {location:?}, image: {image})")
+            }
+            SupportedCommands::RecommendBaseImage { location, image } => {
+                write!(
+                    f,
+                    "RecommendBaseImage(location: {location:?}, image: {image})"
+                )
+            }
+            SupportedCommands::RequestAcceptedRisk { uri, cve, package } => {
+                write!(
+                    f,
+                    "RequestAcceptedRisk(uri: {uri}, cve: {cve}, package: {package:?})"
+                )
+            }
+            SupportedCommands::ScanDirectory { uri } => {
+                write!(f, "ScanDirectory(uri: {uri:?})")
+            }
+            SupportedCommands::CleanupTempImages => write!(f, "CleanupTempImages"),
+            SupportedCommands::ListImages => write!(f, "ListImages"),
         }
     }
 }
@@ -94,8 +641,14 @@ impl Display for SupportedCommands {
 #[cfg(test)]
 mod tests {
     use super::SupportedCommands;
+    use crate::app::PackageVersion;
+    use crate::domain::scanresult::accepted_risk_reason::AcceptedRiskReason;
+    use crate::domain::scanresult::sbom::SbomFormat;
     use serde_json::json;
-    use tower_lsp::{jsonrpc, lsp_types::ExecuteCommandParams};
+    use tower_lsp::{
+        jsonrpc,
+        lsp_types::{ExecuteCommandParams, Position, Range},
+    };
 
     fn params(command: &str, arguments: Vec<serde_json::Value>) -> ExecuteCommandParams {
         ExecuteCommandParams {
@@ -105,6 +658,136 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_parses_execute_scan_with_a_location() {
+        let command: SupportedCommands = params(
+            "sysdig-lsp.execute-scan",
+            vec![
+                json!({"uri": "file:///Dockerfile", "range": {"start": {"line": 2, "character": 0}, "end": {"line": 2, "character": 16}}}),
+                json!("alpine:3.19"),
+            ],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+
+        match command {
+            SupportedCommands::ExecuteBaseImageScan {
+                location,
+                image,
+                platform: None,
+            } => {
+                assert_eq!(location.uri.as_str(), "file:///Dockerfile");
+                assert_eq!(
+                    (location.range.start.line, location.range.end.character),
+                    (2, 16)
+                );
+                assert_eq!(image, "alpine:3.19");
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+    }
+
+    #[test]
+    fn it_parses_execute_scan_with_a_platform() {
+        let location = json!({"uri": "file:///Dockerfile", "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 11}}});
+        let command: SupportedCommands = params(
+            "sysdig-lsp.execute-scan",
+            vec![location.clone(), json!("alpine"), json!("linux/arm64")],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+
+        match command {
+            SupportedCommands::ExecuteBaseImageScan {
+                image, platform, ..
+            } => {
+                assert_eq!(image, "alpine");
+                assert_eq!(platform.as_deref(), Some("linux/arm64"));
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+
+        let result: Result<SupportedCommands, _> = params(
+            "sysdig-lsp.execute-scan",
+            vec![location, json!("alpine"), json!("arm64")],
+        )
+        .try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn it_parses_execute_scan_with_legacy_uri_line_and_image_arguments() {
+        let command: SupportedCommands = params(
+            "sysdig-lsp.execute-scan",
+            vec![json!("file:///Dockerfile"), json!(2), json!("alpine:3.19")],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+
+        match command {
+            SupportedCommands::ExecuteBaseImageScan {
+                location,
+                image,
+                platform: None,
+            } => {
+                assert_eq!(location.uri.as_str(), "file:///Dockerfile");
+                assert_eq!(
+                    location.range,
+                    Range::new(Position::new(2, 0), Position::new(3, 0))
+                );
+                assert_eq!(image, "alpine:3.19");
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_execute_scan_with_an_invalid_legacy_line() {
+        for line in [json!(-1), json!("2"), json!(1.5)] {
+            let result: Result<SupportedCommands, _> = params(
+                "sysdig-lsp.execute-scan",
+                vec![json!("file:///Dockerfile"), line.clone(), json!("alpine")],
+            )
+            .try_into();
+
+            assert!(result.is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn it_parses_build_and_scan_with_an_optional_target() {
+        let location = json!({"uri": "file:///Dockerfile", "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 22}}});
+
+        let command: SupportedCommands =
+            params("sysdig-lsp.execute-build-and-scan", vec![location.clone()])
+                .try_into()
+                .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+        assert!(matches!(
+            command,
+            SupportedCommands::ExecuteBuildAndScan { target: None, .. }
+        ));
+
+        let command: SupportedCommands = params(
+            "sysdig-lsp.execute-build-and-scan",
+            vec![location.clone(), json!("build")],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+        match command {
+            SupportedCommands::ExecuteBuildAndScan { target, .. } => {
+                assert_eq!(target.as_deref(), Some("build"));
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+
+        let result: Result<SupportedCommands, _> = params(
+            "sysdig-lsp.execute-build-and-scan",
+            vec![location, json!(1)],
+        )
+        .try_into();
+        assert!(result.is_err());
+    }
+
     #[test]
     fn it_parses_iac_scan_without_arguments() {
         let command: SupportedCommands = params("sysdig-lsp.execute-iac-scan", vec![])
@@ -159,4 +842,297 @@ mod tests {
         let err = result.expect_err("should reject multiple arguments");
         assert!(err.message.contains("at most one"));
     }
+
+    #[test]
+    fn it_parses_scan_image_under_cursor() {
+        let command: SupportedCommands = params(
+            "sysdig-lsp.scan-image-under-cursor",
+            vec![
+                json!("file:///Dockerfile"),
+                json!({"line": 3, "character": 7}),
+            ],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+
+        match command {
+            SupportedCommands::ScanImageUnderCursor { uri, position } => {
+                assert_eq!(uri.as_str(), "file:///Dockerfile");
+                assert_eq!((position.line, position.character), (3, 7));
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_scan_image_under_cursor_without_position() {
+        let result: Result<SupportedCommands, _> = params(
+            "sysdig-lsp.scan-image-under-cursor",
+            vec![json!("file:///Dockerfile")],
+        )
+        .try_into();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn it_parses_export_scan_result_with_and_without_path() {
+        let command: SupportedCommands = params(
+            "sysdig-lsp.export-scan-result",
+            vec![json!("file:///Dockerfile")],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+        assert!(matches!(
+            command,
+            SupportedCommands::ExportScanResult { path: None, .. }
+        ));
+
+        let command: SupportedCommands = params(
+            "sysdig-lsp.export-scan-result",
+            vec![json!("file:///Dockerfile"), json!("/tmp/scan.json")],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+        match command {
+            SupportedCommands::ExportScanResult { path, .. } => {
+                assert_eq!(path, Some("/tmp/scan.json".into()));
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+    }
+
+    #[test]
+    fn it_parses_show_scan_report_with_an_optional_position() {
+        let parse = |arguments: Vec<serde_json::Value>| -> Result<SupportedCommands, _> {
+            params("sysdig-lsp.show-scan-report", arguments).try_into()
+        };
+
+        match parse(vec![json!("file:///Dockerfile")]) {
+            Ok(SupportedCommands::ShowScanReport { uri, position }) => {
+                assert_eq!(uri.as_str(), "file:///Dockerfile");
+                assert_eq!(position, None);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        match parse(vec![
+            json!("file:///Dockerfile"),
+            json!({"line": 2, "character": 5}),
+        ]) {
+            Ok(SupportedCommands::ShowScanReport { position, .. }) => {
+                assert_eq!(position, Some(Position::new(2, 5)));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn it_parses_generate_sbom_with_an_optional_format_and_path() {
+        let parse = |arguments: Vec<serde_json::Value>| -> Result<SupportedCommands, _> {
+            params("sysdig-lsp.generate-sbom", arguments).try_into()
+        };
+
+        match parse(vec![json!("file:///Dockerfile")]) {
+            Ok(SupportedCommands::GenerateSbom { format, path, .. }) => {
+                assert_eq!(format, SbomFormat::CycloneDx);
+                assert_eq!(path, None);
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        match parse(vec![
+            json!("file:///Dockerfile"),
+            json!("spdx"),
+            json!("sbom.spdx.json"),
+        ]) {
+            Ok(SupportedCommands::GenerateSbom { format, path, .. }) => {
+                assert_eq!(format, SbomFormat::Spdx);
+                assert_eq!(path, Some("sbom.spdx.json".into()));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert!(parse(vec![json!("file:///Dockerfile"), json!("swid")]).is_err());
+    }
+
+    #[test]
+    fn it_rejects_export_scan_result_without_uri() {
+        let result: Result<SupportedCommands, _> =
+            params("sysdig-lsp.export-scan-result", vec![]).try_into();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn it_rejects_export_sarif_with_extra_arguments() {
+        let result: Result<SupportedCommands, _> = params(
+            "sysdig-lsp.export-sarif",
+            vec![json!("file:///Dockerfile"), json!("/tmp/a.sarif"), json!(1)],
+        )
+        .try_into();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn it_parses_scan_directory_with_an_optional_uri() {
+        let parse = |arguments: Vec<serde_json::Value>| -> Result<SupportedCommands, _> {
+            params("sysdig-lsp.scan-directory", arguments).try_into()
+        };
+
+        assert!(matches!(
+            parse(vec![]),
+            Ok(SupportedCommands::ScanDirectory { uri: None })
+        ));
+        match parse(vec![json!("file:///workspace/app")]) {
+            Ok(SupportedCommands::ScanDirectory { uri: Some(uri) }) => {
+                assert_eq!(uri.as_str(), "file:///workspace/app")
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+        assert!(parse(vec![json!(42)]).is_err());
+        assert!(parse(vec![json!("file:///a"), json!("file:///b")]).is_err());
+    }
+
+    #[test]
+    fn it_parses_cleanup_temp_images_without_arguments() {
+        let parse = |arguments: Vec<serde_json::Value>| -> Result<SupportedCommands, _> {
+            params("sysdig-lsp.cleanup-temp-images", arguments).try_into()
+        };
+
+        assert!(matches!(
+            parse(vec![]),
+            Ok(SupportedCommands::CleanupTempImages)
+        ));
+        assert!(parse(vec![json!("file:///Dockerfile")]).is_err());
+    }
+
+    #[test]
+    fn it_parses_list_images_without_arguments() {
+        let parse = |arguments: Vec<serde_json::Value>| -> Result<SupportedCommands, _> {
+            params("sysdig-lsp.list-images", arguments).try_into()
+        };
+
+        assert!(matches!(parse(vec![]), Ok(SupportedCommands::ListImages)));
+        assert!(parse(vec![json!("file:///Dockerfile")]).is_err());
+    }
+
+    #[test]
+    fn it_parses_request_accepted_risk_with_an_optional_package() {
+        let parse = |arguments: Vec<serde_json::Value>| -> Result<SupportedCommands, _> {
+            params("sysdig-lsp.request-accepted-risk", arguments).try_into()
+        };
+
+        match parse(vec![json!("file:///Dockerfile"), json!("CVE-2021-1234")]) {
+            Ok(SupportedCommands::RequestAcceptedRisk { uri, cve, package }) => {
+                assert_eq!(uri.as_str(), "file:///Dockerfile");
+                assert_eq!(cve, "CVE-2021-1234");
+                assert_eq!(package, None);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+        match parse(vec![
+            json!("file:///Dockerfile"),
+            json!("CVE-2021-1234"),
+            json!({"name": "openssl", "version": "3.1.4-r0"}),
+        ]) {
+            Ok(SupportedCommands::RequestAcceptedRisk { package, .. }) => assert_eq!(
+                package,
+                Some(PackageVersion {
+                    name: "openssl".to_string(),
+                    version: "3.1.4-r0".to_string(),
+                })
+            ),
+            other => panic!("unexpected command: {other:?}"),
+        }
+
+        assert!(parse(vec![json!("file:///Dockerfile")]).is_err());
+        assert!(
+            parse(vec![
+                json!("file:///Dockerfile"),
+                json!("CVE-2021-1234"),
+                json!({"name": "openssl"}),
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn it_parses_pin_image_digest() {
+        let command: SupportedCommands = params(
+            "sysdig-lsp.pin-image-digest",
+            vec![
+                json!({"uri": "file:///Dockerfile", "range": {"start": {"line": 0, "character": 5}, "end": {"line": 0, "character": 17}}}),
+                json!("nginx:latest"),
+            ],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+
+        match command {
+            SupportedCommands::PinImageDigest { location, image } => {
+                assert_eq!(location.uri.as_str(), "file:///Dockerfile");
+                assert_eq!(image, "nginx:latest");
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+
+        let result: Result<SupportedCommands, _> = params(
+            "sysdig-lsp.pin-image-digest",
+            vec![json!("file:///Dockerfile")],
+        )
+        .try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn it_parses_accept_risks() {
+        let command: SupportedCommands = params(
+            "sysdig-lsp.accept-risks",
+            vec![
+                json!("file:///Dockerfile"),
+                json!({
+                    "cves": ["CVE-2021-1234", "CVE-2022-5678"],
+                    "reason": "RiskOwned",
+                    "expirationDate": "2027-01-31"
+                }),
+            ],
+        )
+        .try_into()
+        .unwrap_or_else(|e| panic!("failed to parse: {e}"));
+
+        match command {
+            SupportedCommands::AcceptRisks {
+                cves,
+                reason,
+                description,
+                expiration_date,
+                ..
+            } => {
+                assert_eq!(cves, ["CVE-2021-1234", "CVE-2022-5678"]);
+                assert_eq!(reason, AcceptedRiskReason::RiskOwned);
+                assert_eq!(description, "");
+                assert_eq!(
+                    expiration_date,
+                    chrono::NaiveDate::from_ymd_opt(2027, 1, 31)
+                );
+            }
+            other => panic!("unexpected command: {other}"),
+        }
+    }
+
+    #[test]
+    fn it_rejects_accept_risks_without_cves_or_with_an_unknown_reason() {
+        for arguments in [
+            json!({ "cves": [], "reason": "RiskOwned" }),
+            json!({ "cves": ["CVE-2021-1234"], "reason": "Because" }),
+            json!({ "cves": ["CVE-2021-1234"], "reason": "RiskOwned", "expirationDate": "soon" }),
+        ] {
+            let result: Result<SupportedCommands, _> = params(
+                "sysdig-lsp.accept-risks",
+                vec![json!("file:///Dockerfile"), arguments.clone()],
+            )
+            .try_into();
+
+            assert!(result.is_err(), "{arguments}");
+        }
+    }
 }
@@ -0,0 +1,82 @@
+use std::fmt::{Display, Formatter};
+
+use chrono::NaiveDate;
+use itertools::Itertools;
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use crate::domain::scanresult::scan_result::ScanResult;
+
+#[derive(Clone, Debug, Default)]
+pub struct AcceptedRiskRow {
+    pub reason: String,
+    pub description: String,
+    pub expiration_date: Option<NaiveDate>,
+    pub active: bool,
+    /// CVEs and packages (`name version`) the risk was accepted for.
+    pub applies_to: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AcceptedRiskTable(pub Vec<AcceptedRiskRow>);
+
+impl From<&ScanResult> for AcceptedRiskTable {
+    fn from(value: &ScanResult) -> Self {
+        AcceptedRiskTable(
+            value
+                .accepted_risks()
+                .iter()
+                .map(|risk| AcceptedRiskRow {
+                    reason: risk.reason().as_str().to_string(),
+                    description: risk.description().to_string(),
+                    expiration_date: risk.expiration_date(),
+                    active: risk.is_active(),
+                    applies_to: risk
+                        .assigned_to_vulnerabilities()
+                        .iter()
+                        .map(|vuln| vuln.cve().to_string())
+                        .sorted()
+                        .chain(
+                            risk.assigned_to_packages()
+                                .iter()
+                                .map(|package| format!("{} {}", package.name(), package.version()))
+                                .sorted(),
+                        )
+                        .collect(),
+                })
+                .sorted_by_key(|row| !row.active)
+                .collect(),
+        )
+    }
+}
+
+impl Display for AcceptedRiskTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("\n### Accepted Risks\n\nNo accepted risks apply to this image.\n");
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record(["APPLIES TO", "REASON", "DESCRIPTION", "EXPIRES", "ACTIVE"]);
+
+        for risk in &self.0 {
+            builder.push_record([
+                risk.applies_to.join(", "),
+                risk.reason.clone(),
+                risk.description.replace('|', "\\|"),
+                risk.expiration_date
+                    .map_or_else(|| "Never".to_string(), |date| date.to_string()),
+                if risk.active { "✅" } else { "❌" }.to_string(),
+            ]);
+        }
+
+        let mut table = builder.build();
+        table
+            .with(Style::markdown())
+            .modify(Columns::new(4..=4), Alignment::center());
+
+        write!(f, "\n### Accepted Risks\n\n{table}\n")
+    }
+}
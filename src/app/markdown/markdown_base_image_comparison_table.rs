@@ -0,0 +1,108 @@
+use std::fmt::{Display, Formatter};
+
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use crate::app::BaseImageRecommendation;
+
+use super::markdown_layer_breakdown_table::human_size;
+
+/// Vulnerabilities and size of a base image next to the ones of its alternatives.
+pub struct BaseImageComparisonTable<'a>(pub &'a BaseImageRecommendation);
+
+impl Display for BaseImageComparisonTable<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let recommendation = self.0;
+        let best = recommendation.best();
+
+        let mut builder = Builder::default();
+        builder.push_record([
+            "IMAGE",
+            "CRITICAL",
+            "HIGH",
+            "MEDIUM",
+            "LOW",
+            "NEGLIGIBLE",
+            "SIZE",
+        ]);
+        let current = std::iter::once((&recommendation.current, " (current)"));
+        let candidates = recommendation.candidates.iter().map(|candidate| {
+            let label = match best {
+                Some(best) if best == candidate => " (recommended)",
+                _ => "",
+            };
+            (candidate, label)
+        });
+        for (evaluation, label) in current.chain(candidates) {
+            let vulns = &evaluation.vulnerabilities;
+            builder.push_record([
+                format!("`{}`{label}", evaluation.image),
+                vulns.critical.to_string(),
+                vulns.high.to_string(),
+                vulns.medium.to_string(),
+                vulns.low.to_string(),
+                vulns.negligible.to_string(),
+                human_size(evaluation.size_in_bytes),
+            ]);
+        }
+
+        let mut table = builder.build();
+        table
+            .with(Style::markdown())
+            .modify(Columns::new(1..=6), Alignment::center());
+
+        writeln!(f, "\n### Base Image Alternatives\n\n{table}\n")?;
+        match best {
+            Some(best) => write!(
+                f,
+                "`{}` is the least vulnerable alternative, switch to it from the quick fixes of the image.",
+                best.image
+            ),
+            None => write!(
+                f,
+                "None of the alternatives is less vulnerable than `{}`.",
+                recommendation.current.image
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::base_image_recommender::BaseImageEvaluation;
+    use crate::app::protocol::SeverityCounts;
+
+    fn evaluation(image: &str, high: usize, size_in_bytes: u64) -> BaseImageEvaluation {
+        BaseImageEvaluation {
+            image: image.to_string(),
+            vulnerabilities: SeverityCounts {
+                high,
+                ..Default::default()
+            },
+            size_in_bytes,
+        }
+    }
+
+    #[test]
+    fn it_compares_the_current_image_with_its_alternatives() {
+        let recommendation = BaseImageRecommendation {
+            current: evaluation("node:18", 12, 400_000_000),
+            candidates: vec![
+                evaluation("gcr.io/distroless/nodejs18-debian12", 1, 60_000_000),
+                evaluation("node:22", 3, 420_000_000),
+            ],
+        };
+
+        let markdown = BaseImageComparisonTable(&recommendation).to_string();
+
+        assert!(markdown.contains("| `node:18` (current)"));
+        assert!(markdown.contains("| `gcr.io/distroless/nodejs18-debian12` (recommended) |"));
+        assert!(markdown.contains("60.0 MB"));
+        assert!(markdown.ends_with(
+            "`gcr.io/distroless/nodejs18-debian12` is the least vulnerable alternative, switch to it from the quick fixes of the image."
+        ));
+    }
+}
@@ -4,7 +4,8 @@ use crate::domain::scanresult::scan_result::ScanResult;
 
 use super::{
     markdown_fixable_package_table::FixablePackageTable,
-    markdown_policy_evaluated_table::PolicyEvaluatedTable, markdown_summary::MarkdownSummary,
+    markdown_policy_evaluated_table::PolicyEvaluatedTable,
+    markdown_report_details::MarkdownReportDetails, markdown_summary::MarkdownSummary,
     markdown_vulnerability_evaluated_table::VulnerabilityEvaluatedTable,
 };
 
@@ -14,6 +15,8 @@ pub struct MarkdownData {
     pub fixable_packages: FixablePackageTable,
     pub policies: PolicyEvaluatedTable,
     pub vulnerabilities: VulnerabilityEvaluatedTable,
+    /// Only rendered in full reports, see `MarkdownData::full_report`.
+    pub details: Option<MarkdownReportDetails>,
 }
 
 impl From<ScanResult> for MarkdownData {
@@ -23,10 +26,29 @@ impl From<ScanResult> for MarkdownData {
             fixable_packages: FixablePackageTable::from(&value),
             policies: PolicyEvaluatedTable::from(&value),
             vulnerabilities: VulnerabilityEvaluatedTable::from(&value),
+            details: None,
         }
     }
 }
 
+impl MarkdownData {
+    /// The hover report plus the accepted risks, layers and package inventory of the
+    /// image, for clients showing it in a preview pane.
+    pub fn full_report(scan_result: ScanResult) -> Self {
+        let details = MarkdownReportDetails::from(&scan_result);
+        Self {
+            details: Some(details),
+            ..Self::from(scan_result)
+        }
+    }
+
+    /// Reports how many vulnerabilities the diagnostics left out.
+    pub fn with_suppressed_vulns(mut self, suppressed_vulns: usize) -> Self {
+        self.summary.suppressed_vulns = suppressed_vulns;
+        self
+    }
+}
+
 impl Display for MarkdownData {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let summary_section = self.summary.to_string();
@@ -41,7 +63,12 @@ impl Display for MarkdownData {
             fixable_packages_section,
             policy_evaluation_section,
             vulnerability_detail_section
-        )
+        )?;
+
+        match &self.details {
+            Some(details) => write!(f, "\n{details}"),
+            None => Ok(()),
+        }
     }
 }
 
@@ -64,6 +91,7 @@ mod test {
         let markdown_data = MarkdownData {
             summary: MarkdownSummary {
                 pull_string: "ubuntu:23.04".to_string(),
+                requested_pull_string: None,
                 image_id: "sha256:f4cdeba72b994748f5eb1f525a70a9cc553b66037ec37e23645fbf3f0f5c160d"
                     .to_string(),
                 digest: Some(
@@ -71,6 +99,10 @@ mod test {
                         .to_string(),
                 ),
                 base_os: "ubuntu 23.04".to_string(),
+                architecture: "amd64".to_string(),
+                result_url: Some(
+                    "https://secure.sysdig.com/#/vulnerabilities/results/1".to_string(),
+                ),
 
                 total_vulns_found: MarkdownSummaryTable {
                     total_found: 11,
@@ -85,6 +117,7 @@ mod test {
                     negligible: 0,
                     negligible_fixable: 0,
                 },
+                suppressed_vulns: 0,
             },
             fixable_packages: FixablePackageTable(vec![
                 FixablePackage {
@@ -92,6 +125,7 @@ mod test {
                     package_type: "os".to_string(),
                     version: "3.7.8-5ubuntu1.1".to_string(),
                     suggested_fix: Some("3.7.8-5ubuntu1.2".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -100,12 +134,14 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
                 FixablePackage {
                     name: "libc-bin".to_string(),
                     package_type: "os".to_string(),
                     version: "2.37-0ubuntu2.1".to_string(),
                     suggested_fix: Some("2.37-0ubuntu2.2".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -114,12 +150,14 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
                 FixablePackage {
                     name: "libc6".to_string(),
                     package_type: "os".to_string(),
                     version: "2.37-0ubuntu2.1".to_string(),
                     suggested_fix: Some("2.37-0ubuntu2.2".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -128,12 +166,14 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
                 FixablePackage {
                     name: "libpam-modules".to_string(),
                     package_type: "os".to_string(),
                     version: "1.5.2-5ubuntu1".to_string(),
                     suggested_fix: Some("1.5.2-5ubuntu1.1".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -142,12 +182,14 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
                 FixablePackage {
                     name: "libpam-modules-bin".to_string(),
                     package_type: "os".to_string(),
                     version: "1.5.2-5ubuntu1".to_string(),
                     suggested_fix: Some("1.5.2-5ubuntu1.1".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -156,12 +198,14 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
                 FixablePackage {
                     name: "libpam-runtime".to_string(),
                     package_type: "os".to_string(),
                     version: "1.5.2-5ubuntu1".to_string(),
                     suggested_fix: Some("1.5.2-5ubuntu1.1".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -170,12 +214,14 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
                 FixablePackage {
                     name: "libpam0g".to_string(),
                     package_type: "os".to_string(),
                     version: "1.5.2-5ubuntu1".to_string(),
                     suggested_fix: Some("1.5.2-5ubuntu1.1".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -184,12 +230,14 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
                 FixablePackage {
                     name: "tar".to_string(),
                     package_type: "os".to_string(),
                     version: "1.34+dfsg-1.2ubuntu0.1".to_string(),
                     suggested_fix: Some("1.34+dfsg-1.2ubuntu0.2".to_string()),
+                    suggested_fix_computed: false,
                     vulnerabilities: FixablePackageVulnerabilities {
                         critical: 0,
                         high: 0,
@@ -198,6 +246,7 @@ mod test {
                         negligible: 0,
                     },
                     exploits: 0,
+                    in_use: false,
                 },
             ]),
             policies: PolicyEvaluatedTable(vec![
@@ -249,52 +298,65 @@ mod test {
                 VulnerabilityEvaluated {
                     cve: "CVE-2023-39804".to_string(),
                     severity: "Medium".to_string(),
+                    cvss: Some("5.5 (v3.1)".to_string()),
                     packages_found: 1,
                     fixable: true,
                     exploitable: false,
+                    known_exploited: false,
                     accepted_risk: false,
                 },
                 VulnerabilityEvaluated {
                     cve: "CVE-2023-4806".to_string(),
                     severity: "Low".to_string(),
+                    cvss: None,
                     packages_found: 2,
                     fixable: true,
                     exploitable: false,
+                    known_exploited: false,
                     accepted_risk: false,
                 },
                 VulnerabilityEvaluated {
                     cve: "CVE-2023-5156".to_string(),
                     severity: "Medium".to_string(),
+                    cvss: None,
                     packages_found: 2,
                     fixable: true,
                     exploitable: false,
+                    known_exploited: false,
                     accepted_risk: false,
                 },
                 VulnerabilityEvaluated {
                     cve: "CVE-2024-0553".to_string(),
                     severity: "Medium".to_string(),
+                    cvss: None,
                     packages_found: 1,
                     fixable: true,
                     exploitable: false,
+                    known_exploited: false,
                     accepted_risk: false,
                 },
                 VulnerabilityEvaluated {
                     cve: "CVE-2024-0567".to_string(),
                     severity: "Medium".to_string(),
+                    cvss: None,
                     packages_found: 1,
                     fixable: true,
                     exploitable: false,
+                    known_exploited: false,
                     accepted_risk: false,
                 },
                 VulnerabilityEvaluated {
                     cve: "CVE-2024-22365".to_string(),
                     severity: "Medium".to_string(),
+                    cvss: None,
                     packages_found: 4,
                     fixable: true,
                     exploitable: false,
+                    known_exploited: false,
                     accepted_risk: false,
                 },
             ]),
+            details: None,
         };
         let expected_markdown_output = r#"## Sysdig Scan Result
 ### Summary
@@ -302,6 +364,8 @@ mod test {
 * **ImageID**: `sha256:f4cdeba72b994748f5eb1f525a70a9cc553b66037ec37e23645fbf3f0f5c160d`
 * **Digest**: `sha256:5a828e28de105c3d7821c4442f0f5d1c52dc16acf4999d5f31a3bc0f03f06edd`
 * **BaseOS**: ubuntu 23.04
+* **Architecture**: amd64
+* **Sysdig Secure**: [Open the full report](https://secure.sysdig.com/#/vulnerabilities/results/1)
 
 | TOTAL VULNS FOUND | CRITICAL | HIGH |    MEDIUM     |      LOW      | NEGLIGIBLE |
 |-------------------|----------|------|---------------|---------------|------------|
@@ -333,14 +397,14 @@ mod test {
 
 ### Vulnerability Detail
 
-| VULN CVE       | SEVERITY | PACKAGES | FIXABLE | EXPLOITABLE | ACCEPTED RISK |
-|----------------|----------|----------|---------|-------------|---------------|
-| CVE-2023-39804 | Medium   | 1        | ✅      | ❌          | ❌            |
-| CVE-2023-4806  | Low      | 2        | ✅      | ❌          | ❌            |
-| CVE-2023-5156  | Medium   | 2        | ✅      | ❌          | ❌            |
-| CVE-2024-0553  | Medium   | 1        | ✅      | ❌          | ❌            |
-| CVE-2024-0567  | Medium   | 1        | ✅      | ❌          | ❌            |
-| CVE-2024-22365 | Medium   | 4        | ✅      | ❌          | ❌            |"#;
+| VULN CVE       | SEVERITY | CVSS       | PACKAGES | FIXABLE | EXPLOITABLE | KEV | ACCEPTED RISK |
+|----------------|----------|------------|----------|---------|-------------|-----|---------------|
+| CVE-2023-39804 | Medium   | 5.5 (v3.1) | 1        | ✅      | ❌          | ❌  | ❌            |
+| CVE-2023-4806  | Low      | -          | 2        | ✅      | ❌          | ❌  | ❌            |
+| CVE-2023-5156  | Medium   | -          | 2        | ✅      | ❌          | ❌  | ❌            |
+| CVE-2024-0553  | Medium   | -          | 1        | ✅      | ❌          | ❌  | ❌            |
+| CVE-2024-0567  | Medium   | -          | 1        | ✅      | ❌          | ❌  | ❌            |
+| CVE-2024-22365 | Medium   | -          | 4        | ✅      | ❌          | ❌  | ❌            |"#;
 
         assert_eq!(
             markdown_data.to_string().trim(),
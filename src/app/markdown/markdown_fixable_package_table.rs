@@ -8,7 +8,9 @@ use tabled::{
     settings::{Alignment, Style, object::Columns},
 };
 
-use crate::domain::scanresult::{layer::Layer, scan_result::ScanResult, severity::Severity};
+use crate::domain::scanresult::{
+    layer::Layer, package::SuggestedFixSource, scan_result::ScanResult, severity::Severity,
+};
 
 #[derive(Clone, Debug, Default)]
 pub struct FixablePackage {
@@ -16,8 +18,13 @@ pub struct FixablePackage {
     pub package_type: String,
     pub version: String,
     pub suggested_fix: Option<String>,
+    /// The suggested fix was computed from the fix versions of the vulnerabilities
+    /// because the scanner didn't suggest one.
+    pub suggested_fix_computed: bool,
     pub vulnerabilities: FixablePackageVulnerabilities,
     pub exploits: u32,
+    /// Loaded at runtime, according to Sysdig runtime insights.
+    pub in_use: bool,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -56,13 +63,18 @@ impl From<&ScanResult> for FixablePackageTable {
                         }
                     }
 
+                    let suggested_fix = p.suggested_fix();
                     FixablePackage {
                         name: p.name().to_string(),
                         package_type: p.package_type().to_string(),
                         version: p.version().to_string(),
-                        suggested_fix: p.suggested_fix_version().map(|v| v.to_string()),
+                        suggested_fix_computed: suggested_fix
+                            .as_ref()
+                            .is_some_and(|fix| fix.source == SuggestedFixSource::Computed),
+                        suggested_fix: suggested_fix.map(|fix| fix.version),
                         vulnerabilities: vulns,
                         exploits,
+                        in_use: p.is_running(),
                     }
                 })
                 .collect(),
@@ -94,13 +106,18 @@ impl From<&Arc<Layer>> for FixablePackageTable {
                         }
                     }
 
+                    let suggested_fix = p.suggested_fix();
                     FixablePackage {
                         name: p.name().to_string(),
                         package_type: p.package_type().to_string(),
                         version: p.version().to_string(),
-                        suggested_fix: p.suggested_fix_version().map(|v| v.to_string()),
+                        suggested_fix_computed: suggested_fix
+                            .as_ref()
+                            .is_some_and(|fix| fix.source == SuggestedFixSource::Computed),
+                        suggested_fix: suggested_fix.map(|fix| fix.version),
                         vulnerabilities: vulns,
                         exploits,
+                        in_use: p.is_running(),
                     }
                 })
                 .collect(),
@@ -114,8 +131,11 @@ impl Display for FixablePackageTable {
             return f.write_str("");
         }
 
+        // Only scans with runtime context tell packages in use from dormant ones.
+        let show_in_use = self.0.iter().any(|p| p.in_use);
+
         let mut builder = Builder::default();
-        builder.push_record([
+        let mut header = vec![
             "PACKAGE",
             "TYPE",
             "VERSION",
@@ -126,14 +146,22 @@ impl Display for FixablePackageTable {
             "LOW",
             "NEGLIGIBLE",
             "EXPLOIT",
-        ]);
+        ];
+        if show_in_use {
+            header.push("IN USE");
+        }
+        builder.push_record(header);
 
         for p in &self.0 {
-            builder.push_record([
+            let mut record = vec![
                 p.name.clone(),
                 p.package_type.clone(),
                 p.version.clone(),
-                p.suggested_fix.clone().unwrap_or_default(),
+                match &p.suggested_fix {
+                    Some(fix) if p.suggested_fix_computed => format!("{fix} \\*"),
+                    Some(fix) => fix.clone(),
+                    None => String::new(),
+                },
                 if p.vulnerabilities.critical > 0 {
                     p.vulnerabilities.critical.to_string()
                 } else {
@@ -164,7 +192,11 @@ impl Display for FixablePackageTable {
                 } else {
                     "-".to_string()
                 },
-            ]);
+            ];
+            if show_in_use {
+                record.push(if p.in_use { "✅" } else { "-" }.to_string());
+            }
+            builder.push_record(record);
         }
 
         let mut table = builder.build();
@@ -172,11 +204,65 @@ impl Display for FixablePackageTable {
             .with(Style::markdown())
             // TYPE column (index 1) centered
             .modify(Columns::new(1..=1), Alignment::center())
-            // Severity columns (4-8) and EXPLOIT (9) centered
-            .modify(Columns::new(4..=9), Alignment::center());
+            // Severity columns (4-8), EXPLOIT (9) and IN USE (10) centered
+            .modify(Columns::new(4..=10), Alignment::center());
 
-        let format = format!("\n### Fixable Packages\n{}", table);
+        let mut format = format!("\n### Fixable Packages\n{}", table);
+        if self.0.iter().any(|p| p.suggested_fix_computed) {
+            format.push_str(
+                "\n\n\\* Computed from the fix versions of the vulnerabilities, the scanner suggested none.",
+            );
+        }
 
         f.write_str(&format)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, in_use: bool) -> FixablePackage {
+        FixablePackage {
+            name: name.to_string(),
+            package_type: "os".to_string(),
+            version: "1.0".to_string(),
+            suggested_fix: Some("1.1".to_string()),
+            suggested_fix_computed: false,
+            vulnerabilities: FixablePackageVulnerabilities {
+                high: 1,
+                ..Default::default()
+            },
+            exploits: 0,
+            in_use,
+        }
+    }
+
+    #[test]
+    fn it_shows_the_packages_in_use_only_with_runtime_context() {
+        let with_runtime_context =
+            FixablePackageTable(vec![package("openssl", true), package("curl", false)]).to_string();
+        assert!(with_runtime_context.contains("| IN USE |"));
+        assert!(with_runtime_context.contains("|   ✅   |"));
+
+        let without_runtime_context = FixablePackageTable(vec![package("curl", false)]).to_string();
+        assert!(!without_runtime_context.contains("IN USE"));
+    }
+
+    #[test]
+    fn it_marks_the_suggested_fixes_the_scanner_did_not_provide() {
+        let from_scanner = FixablePackageTable(vec![package("openssl", false)]).to_string();
+        assert!(from_scanner.contains("| 1.1           |"));
+        assert!(!from_scanner.contains("Computed"));
+
+        let computed = FixablePackageTable(vec![FixablePackage {
+            suggested_fix_computed: true,
+            ..package("curl", false)
+        }])
+        .to_string();
+        assert!(computed.contains("| 1.1 \\*        |"));
+        assert!(computed.ends_with(
+            "\\* Computed from the fix versions of the vulnerabilities, the scanner suggested none."
+        ));
+    }
+}
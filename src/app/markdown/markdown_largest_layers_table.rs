@@ -0,0 +1,121 @@
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use super::{
+    markdown_layer_breakdown_table::human_size,
+    markdown_layer_mapping_table::{LayerMapping, sanitize_command},
+};
+
+const LARGEST_LAYERS: usize = 5;
+
+/// The largest layers of a built image, with the instruction that created them, to
+/// tell where the image size comes from.
+#[derive(Clone, Debug, Default)]
+pub struct LargestLayersTable {
+    layers: Vec<LayerMapping>,
+    total_size: u64,
+}
+
+impl From<&[LayerMapping]> for LargestLayersTable {
+    fn from(value: &[LayerMapping]) -> Self {
+        Self {
+            layers: value
+                .iter()
+                .filter(|layer| layer.size.is_some_and(|size| size > 0))
+                .sorted_by_key(|layer| std::cmp::Reverse(layer.size))
+                .take(LARGEST_LAYERS)
+                .cloned()
+                .collect(),
+            total_size: value.iter().filter_map(|layer| layer.size).sum(),
+        }
+    }
+}
+
+impl Display for LargestLayersTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.layers.is_empty() {
+            return f.write_str("");
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record(["LAYER", "SIZE", "% OF IMAGE", "INSTRUCTION LINE", "COMMAND"]);
+
+        for layer in &self.layers {
+            let size = layer.size.unwrap_or_default();
+            builder.push_record([
+                layer.index.to_string(),
+                human_size(size),
+                format!("{:.1}%", size as f64 * 100.0 / self.total_size as f64),
+                layer
+                    .instruction_line
+                    .map(|line| (line + 1).to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                sanitize_command(&layer.command),
+            ]);
+        }
+
+        let mut table = builder.build();
+        table
+            .with(Style::markdown())
+            .modify(Columns::new(0..=3), Alignment::center());
+
+        write!(f, "\n### Largest Layers\n\n{table}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(index: usize, size: Option<u64>, instruction_line: Option<u32>) -> LayerMapping {
+        LayerMapping {
+            index,
+            digest: None,
+            command: format!("RUN step {index}"),
+            size,
+            instruction_line,
+        }
+    }
+
+    #[test]
+    fn it_lists_the_five_largest_layers_by_size() {
+        let layers = vec![
+            layer(0, Some(50_000_000), None),
+            layer(1, Some(0), Some(1)),
+            layer(2, Some(200_000_000), Some(2)),
+            layer(3, Some(10_000_000), Some(3)),
+            layer(4, None, Some(4)),
+            layer(5, Some(20_000_000), Some(5)),
+            layer(6, Some(1_000), Some(6)),
+            layer(7, Some(5_000_000), Some(7)),
+            layer(8, Some(15_000_000), Some(8)),
+        ];
+
+        let expected = r#"
+### Largest Layers
+
+| LAYER |   SIZE   | % OF IMAGE | INSTRUCTION LINE | COMMAND    |
+|-------|----------|------------|------------------|------------|
+|   2   | 200.0 MB |   66.7%    |        3         | RUN step 2 |
+|   0   | 50.0 MB  |   16.7%    |        -         | RUN step 0 |
+|   5   | 20.0 MB  |    6.7%    |        6         | RUN step 5 |
+|   8   | 15.0 MB  |    5.0%    |        9         | RUN step 8 |
+|   3   | 10.0 MB  |    3.3%    |        4         | RUN step 3 |"#;
+        assert_eq!(
+            LargestLayersTable::from(layers.as_slice()).to_string(),
+            expected
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_without_sizes() {
+        let layers = vec![layer(0, None, None), layer(1, Some(0), Some(1))];
+
+        assert_eq!(LargestLayersTable::from(layers.as_slice()).to_string(), "");
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use crate::domain::scanresult::{scan_result::ScanResult, severity::Severity};
+
+const MAX_COMMAND_LENGTH: usize = 60;
+
+#[derive(Clone, Debug, Default)]
+pub struct LayerBreakdown {
+    pub index: usize,
+    pub command: String,
+    pub size: Option<u64>,
+    pub packages: usize,
+    pub critical: usize,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+    pub negligible: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LayerBreakdownTable(pub Vec<LayerBreakdown>);
+
+impl From<&ScanResult> for LayerBreakdownTable {
+    fn from(value: &ScanResult) -> Self {
+        LayerBreakdownTable(
+            value
+                .layers()
+                .iter()
+                .map(|layer| {
+                    let vulns = layer.vulnerabilities().iter().counts_by(|v| v.severity());
+                    let count = |severity| vulns.get(&severity).copied().unwrap_or_default();
+                    LayerBreakdown {
+                        index: layer.index(),
+                        command: layer.command().to_string(),
+                        size: layer.size().copied(),
+                        packages: layer.packages().len(),
+                        critical: count(Severity::Critical),
+                        high: count(Severity::High),
+                        medium: count(Severity::Medium),
+                        low: count(Severity::Low),
+                        negligible: count(Severity::Negligible),
+                    }
+                })
+                .sorted_by_key(|layer| layer.index)
+                .collect(),
+        )
+    }
+}
+
+impl Display for LayerBreakdownTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("");
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record([
+            "#",
+            "COMMAND",
+            "SIZE",
+            "PACKAGES",
+            "CRITICAL",
+            "HIGH",
+            "MEDIUM",
+            "LOW",
+            "NEGLIGIBLE",
+        ]);
+
+        let count = |n: usize| match n {
+            0 => "-".to_string(),
+            n => n.to_string(),
+        };
+        for layer in &self.0 {
+            builder.push_record([
+                layer.index.to_string(),
+                format!("`{}`", truncated(&layer.command).replace('|', "\\|")),
+                layer.size.map_or_else(|| "-".to_string(), human_size),
+                layer.packages.to_string(),
+                count(layer.critical),
+                count(layer.high),
+                count(layer.medium),
+                count(layer.low),
+                count(layer.negligible),
+            ]);
+        }
+
+        let mut table = builder.build();
+        table
+            .with(Style::markdown())
+            .modify(Columns::new(2..=8), Alignment::center());
+
+        write!(f, "\n### Layers\n\n{table}\n")
+    }
+}
+
+fn truncated(command: &str) -> String {
+    let command = command.split_whitespace().join(" ");
+    if command.chars().count() <= MAX_COMMAND_LENGTH {
+        return command;
+    }
+    let prefix: String = command.chars().take(MAX_COMMAND_LENGTH).collect();
+    format!("{prefix}…")
+}
+
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
@@ -7,10 +7,12 @@ use crate::domain::scanresult::layer::Layer;
 
 use super::{
     markdown_fixable_package_table::FixablePackageTable,
+    markdown_layer_breakdown_table::human_size,
     markdown_vulnerability_evaluated_table::VulnerabilityEvaluatedTable,
 };
 
 pub struct MarkdownLayerData {
+    pub size: Option<u64>,
     pub fixable_packages: FixablePackageTable,
     pub vulnerabilities: VulnerabilityEvaluatedTable,
 }
@@ -18,6 +20,7 @@ pub struct MarkdownLayerData {
 impl From<Arc<Layer>> for MarkdownLayerData {
     fn from(value: Arc<Layer>) -> Self {
         Self {
+            size: value.size().copied(),
             fixable_packages: FixablePackageTable::from(&value),
             vulnerabilities: VulnerabilityEvaluatedTable::from(&value),
         }
@@ -26,12 +29,21 @@ impl From<Arc<Layer>> for MarkdownLayerData {
 
 impl Display for MarkdownLayerData {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("## Sysdig Scan Result for Layer\n")?;
+        if let Some(size) = self.size {
+            writeln!(f, "Layer size: **{}**\n", human_size(size))?;
+        }
+
+        if self.fixable_packages.0.is_empty() && self.vulnerabilities.0.is_empty() {
+            return f.write_str("No vulnerabilities found in this layer.");
+        }
+
         let fixable_packages_section = self.fixable_packages.to_string();
         let vulnerability_detail_section = self.vulnerabilities.to_string();
 
         write!(
             f,
-            "## Sysdig Scan Result for Layer\n{}\n{}",
+            "{}\n{}",
             fixable_packages_section, vulnerability_detail_section
         )
     }
@@ -0,0 +1,120 @@
+use std::fmt::{Display, Formatter};
+
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+const MAX_COMMAND_LENGTH: usize = 80;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LayerMapping {
+    pub index: usize,
+    pub digest: Option<String>,
+    pub command: String,
+    /// Size of the layer in bytes, if the scanner reported it.
+    pub size: Option<u64>,
+    /// Zero-based line of the Dockerfile instruction the layer was matched to,
+    /// or `None` if the layer was not matched (e.g. it belongs to the base image).
+    pub instruction_line: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LayerMappingTable(pub Vec<LayerMapping>);
+
+impl Display for LayerMappingTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("");
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record(["LAYER", "DIGEST", "COMMAND", "INSTRUCTION LINE"]);
+
+        for mapping in &self.0 {
+            builder.push_record([
+                mapping.index.to_string(),
+                mapping.digest.clone().unwrap_or_else(|| "-".to_string()),
+                sanitize_command(&mapping.command),
+                mapping
+                    .instruction_line
+                    .map(|line| (line + 1).to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            ]);
+        }
+
+        let mut table = builder.build();
+        table
+            .with(Style::markdown())
+            // LAYER and INSTRUCTION LINE columns centered
+            .modify(Columns::first(), Alignment::center())
+            .modify(Columns::last(), Alignment::center());
+
+        let format = format!("\n### Layer Mapping\n\n{}", table);
+
+        f.write_str(&format)
+    }
+}
+
+/// Layer commands come straight from the image history, so they may contain
+/// pipes or newlines that would break the markdown table.
+pub(super) fn sanitize_command(command: &str) -> String {
+    let single_line = command.split_whitespace().collect::<Vec<_>>().join(" ");
+    let truncated = if single_line.chars().count() > MAX_COMMAND_LENGTH {
+        let prefix: String = single_line.chars().take(MAX_COMMAND_LENGTH).collect();
+        format!("{prefix}…")
+    } else {
+        single_line
+    };
+    truncated.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_renders_matched_and_unmatched_layers() {
+        let table = LayerMappingTable(vec![
+            LayerMapping {
+                index: 0,
+                digest: Some("sha256:aaa".to_string()),
+                command: "ADD file:123 in /".to_string(),
+                size: None,
+                instruction_line: None,
+            },
+            LayerMapping {
+                index: 1,
+                digest: None,
+                command: "RUN apk add curl | tee /log".to_string(),
+                size: None,
+                instruction_line: Some(2),
+            },
+        ]);
+
+        let expected = r#"
+### Layer Mapping
+
+| LAYER | DIGEST     | COMMAND                      | INSTRUCTION LINE |
+|-------|------------|------------------------------|------------------|
+|   0   | sha256:aaa | ADD file:123 in /            |        -         |
+|   1   | -          | RUN apk add curl \| tee /log |        3         |"#;
+
+        assert_eq!(table.to_string(), expected);
+    }
+
+    #[test]
+    fn it_renders_nothing_without_layers() {
+        assert_eq!(LayerMappingTable::default().to_string(), "");
+    }
+
+    #[test]
+    fn it_truncates_long_commands() {
+        let long_command = format!("RUN {}", "x".repeat(200));
+
+        let sanitized = sanitize_command(&long_command);
+
+        assert_eq!(sanitized.chars().count(), MAX_COMMAND_LENGTH + 1);
+        assert!(sanitized.ends_with('…'));
+    }
+}
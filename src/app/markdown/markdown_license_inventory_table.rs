@@ -0,0 +1,152 @@
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use crate::domain::scanresult::scan_result::ScanResult;
+
+/// Packages listed per license, the rest are only counted.
+const MAX_LISTED_PACKAGES: usize = 5;
+
+#[derive(Clone, Debug, Default)]
+pub struct LicenseUsage {
+    /// `None` for the packages whose license the scanner couldn't tell.
+    pub license: Option<String>,
+    /// `name version` of the packages under the license, sorted.
+    pub packages: Vec<String>,
+}
+
+/// Licenses of the packages found in the image, most used first, for compliance
+/// reviews. Packages without a known license go last.
+#[derive(Clone, Debug, Default)]
+pub struct LicenseInventoryTable(pub Vec<LicenseUsage>);
+
+impl From<&ScanResult> for LicenseInventoryTable {
+    fn from(value: &ScanResult) -> Self {
+        LicenseInventoryTable(
+            value
+                .packages_by_license()
+                .into_iter()
+                .map(|(license, packages)| LicenseUsage {
+                    license,
+                    packages: packages
+                        .iter()
+                        .map(|package| format!("{} {}", package.name(), package.version()))
+                        .collect(),
+                })
+                .sorted_by(|a, b| {
+                    a.license
+                        .is_none()
+                        .cmp(&b.license.is_none())
+                        .then(b.packages.len().cmp(&a.packages.len()))
+                        .then(a.license.cmp(&b.license))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Display for LicenseInventoryTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("");
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record(["LICENSE", "COUNT", "PACKAGES"]);
+
+        for usage in &self.0 {
+            let mut packages = usage.packages.iter().take(MAX_LISTED_PACKAGES).join(", ");
+            if usage.packages.len() > MAX_LISTED_PACKAGES {
+                packages.push_str(&format!(
+                    " and {} more",
+                    usage.packages.len() - MAX_LISTED_PACKAGES
+                ));
+            }
+            builder.push_record([
+                usage.license.as_deref().unwrap_or("Unknown").to_string(),
+                usage.packages.len().to_string(),
+                packages,
+            ]);
+        }
+
+        let mut table = builder.build();
+        table
+            .with(Style::markdown())
+            .modify(Columns::new(1..=1), Alignment::center());
+
+        write!(
+            f,
+            "\n### License Inventory\n\n{} licenses found.\n\n{table}\n",
+            self.0
+                .iter()
+                .filter(|usage| usage.license.is_some())
+                .count()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::Utc;
+
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        package_type::PackageType,
+        scan_type::ScanType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_lists_the_most_used_licenses_first_and_the_unknown_last() {
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:3.18".to_string(),
+            "sha256:image".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine 3.18".to_string()),
+            0,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Passed,
+        );
+        let layer = scan_result.add_layer("sha256:layer".to_string(), 0, None, String::new());
+        let packages = (1..=7)
+            .map(|i| (format!("package{i}"), Some("GPL-2.0-only")))
+            .chain([
+                ("musl".to_string(), Some("MIT")),
+                ("ca-certificates".to_string(), None),
+            ]);
+        for (name, license) in packages {
+            scan_result.add_package(
+                PackageType::Os,
+                name,
+                "1.0".to_string(),
+                "/lib/apk/db/installed".to_string(),
+                layer.clone(),
+                false,
+                license.map(str::to_string),
+                None,
+            );
+        }
+
+        assert_eq!(
+            LicenseInventoryTable::from(&scan_result).to_string(),
+            "\n### License Inventory\n\n2 licenses found.\n\n\
+             | LICENSE      | COUNT | PACKAGES                                                                        |\n\
+             |--------------|-------|---------------------------------------------------------------------------------|\n\
+             | GPL-2.0-only |   7   | package1 1.0, package2 1.0, package3 1.0, package4 1.0, package5 1.0 and 2 more |\n\
+             | MIT          |   1   | musl 1.0                                                                        |\n\
+             | Unknown      |   1   | ca-certificates 1.0                                                             |\n"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use crate::domain::scanresult::{package::Package, scan_result::ScanResult};
+
+use super::markdown_layer_mapping_table::sanitize_command;
+
+/// Focused report of a single fixable package: every version of it found in the image,
+/// the layers that installed them and the vulnerabilities they carry.
+pub struct MarkdownPackageCard {
+    name: String,
+    packages: Vec<Arc<Package>>,
+}
+
+impl MarkdownPackageCard {
+    /// `None` unless the image has a fixable vulnerability in a package with that name.
+    pub fn for_package(scan_result: &ScanResult, name: &str) -> Option<Self> {
+        let packages: Vec<_> = scan_result
+            .packages()
+            .into_iter()
+            .filter(|p| p.name() == name)
+            .sorted_by(|a, b| {
+                (a.found_in_layer().index(), a.version())
+                    .cmp(&(b.found_in_layer().index(), b.version()))
+            })
+            .collect();
+
+        let fixable = packages
+            .iter()
+            .any(|p| p.vulnerabilities().iter().any(|v| v.fixable()));
+        fixable.then(|| Self {
+            name: name.to_string(),
+            packages,
+        })
+    }
+}
+
+impl Display for MarkdownPackageCard {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut versions = Builder::default();
+        versions.push_record(["VERSION", "TYPE", "SUGGESTED FIX", "LAYER"]);
+        for p in &self.packages {
+            let layer = p.found_in_layer();
+            versions.push_record([
+                p.version().clone(),
+                p.package_type().to_string(),
+                p.suggested_fix_version().unwrap_or_else(|| "-".to_string()),
+                format!("{}: {}", layer.index(), sanitize_command(layer.command())),
+            ]);
+        }
+        let mut versions = versions.build();
+        versions
+            .with(Style::markdown())
+            .modify(Columns::new(1..=1), Alignment::center());
+
+        let mut vulnerabilities = Builder::default();
+        vulnerabilities.push_record(["CVE", "SEVERITY", "VERSION", "FIXED IN", "EXPLOIT"]);
+        let rows = self
+            .packages
+            .iter()
+            .flat_map(|p| p.vulnerabilities().into_iter().map(move |v| (p, v)))
+            .sorted_by(|(pa, a), (pb, b)| {
+                (a.severity(), a.cve(), pa.version()).cmp(&(b.severity(), b.cve(), pb.version()))
+            });
+        for (p, v) in rows {
+            vulnerabilities.push_record([
+                v.cve().to_string(),
+                format!("{:?}", v.severity()),
+                p.version().clone(),
+                v.fix_version().cloned().unwrap_or_else(|| "-".to_string()),
+                if v.exploitable() { "yes" } else { "-" }.to_string(),
+            ]);
+        }
+        let mut vulnerabilities = vulnerabilities.build();
+        vulnerabilities
+            .with(Style::markdown())
+            .modify(Columns::new(1..=1), Alignment::center())
+            .modify(Columns::last(), Alignment::center());
+
+        write!(
+            f,
+            "## Sysdig Scan Result for Package `{}`\n\n{}\n\n### Vulnerabilities\n\n{}",
+            self.name, versions, vulnerabilities
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, Utc};
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        package_type::PackageType,
+        scan_type::ScanType,
+        severity::Severity,
+    };
+
+    fn scan_result() -> ScanResult {
+        let mut result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:latest".to_string(),
+            "sha256:12345".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+            123456,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Passed,
+        );
+        let layer = result.add_layer(
+            "sha256:layer1".to_string(),
+            0,
+            None,
+            "RUN apk add openssl curl".to_string(),
+        );
+        let openssl = result.add_package(
+            PackageType::Os,
+            "openssl".to_string(),
+            "3.1.0".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            layer.clone(),
+            false,
+            None,
+            None,
+        );
+        let curl = result.add_package(
+            PackageType::Os,
+            "curl".to_string(),
+            "8.0.0".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            layer,
+            false,
+            None,
+            None,
+        );
+        let fixable = result.add_vulnerability(
+            "CVE-2024-0001".to_string(),
+            Severity::High,
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            None,
+            true,
+            Some("3.1.4".to_string()),
+            None,
+            None,
+        );
+        openssl.add_vulnerability_found(fixable);
+        let unfixable = result.add_vulnerability(
+            "CVE-2024-0002".to_string(),
+            Severity::Low,
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            None,
+            false,
+            None,
+            None,
+            None,
+        );
+        curl.add_vulnerability_found(unfixable);
+        result
+    }
+
+    #[test]
+    fn it_renders_a_card_for_fixable_packages_only() {
+        let scan_result = scan_result();
+
+        let card = MarkdownPackageCard::for_package(&scan_result, "openssl")
+            .unwrap()
+            .to_string();
+
+        assert!(card.starts_with("## Sysdig Scan Result for Package `openssl`"));
+        assert!(card.contains("| 3.1.0   |  os  | 3.1.4         | 0: RUN apk add openssl curl |"));
+        assert!(card.contains("| CVE-2024-0001 |   High   | 3.1.0   | 3.1.4    |   yes   |"));
+        assert!(MarkdownPackageCard::for_package(&scan_result, "curl").is_none());
+        assert!(MarkdownPackageCard::for_package(&scan_result, "busybox").is_none());
+    }
+}
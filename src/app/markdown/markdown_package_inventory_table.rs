@@ -0,0 +1,81 @@
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use crate::domain::scanresult::{package_version::compare_versions, scan_result::ScanResult};
+
+#[derive(Clone, Debug, Default)]
+pub struct InventoryPackage {
+    pub name: String,
+    pub package_type: String,
+    pub version: String,
+    pub path: String,
+    pub vulnerabilities: usize,
+    pub in_use: bool,
+}
+
+/// Every package found in the image, vulnerable or not.
+#[derive(Clone, Debug, Default)]
+pub struct PackageInventoryTable(pub Vec<InventoryPackage>);
+
+impl From<&ScanResult> for PackageInventoryTable {
+    fn from(value: &ScanResult) -> Self {
+        PackageInventoryTable(
+            value
+                .packages()
+                .iter()
+                .map(|package| InventoryPackage {
+                    name: package.name().to_string(),
+                    package_type: package.package_type().to_string(),
+                    version: package.version().to_string(),
+                    path: package.path().to_string(),
+                    vulnerabilities: package.vulnerabilities().len(),
+                    in_use: package.is_running(),
+                })
+                .sorted_by(|a, b| {
+                    a.name
+                        .cmp(&b.name)
+                        .then_with(|| compare_versions(&a.version, &b.version))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Display for PackageInventoryTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("");
+        }
+
+        let mut builder = Builder::default();
+        builder.push_record(["PACKAGE", "TYPE", "VERSION", "PATH", "VULNS", "IN USE"]);
+
+        for package in &self.0 {
+            builder.push_record([
+                package.name.clone(),
+                package.package_type.clone(),
+                package.version.clone(),
+                package.path.clone(),
+                package.vulnerabilities.to_string(),
+                if package.in_use { "✅" } else { "-" }.to_string(),
+            ]);
+        }
+
+        let mut table = builder.build();
+        table
+            .with(Style::markdown())
+            .modify(Columns::new(1..=1), Alignment::center())
+            .modify(Columns::new(4..=5), Alignment::center());
+
+        write!(
+            f,
+            "\n### Package Inventory\n\n{} packages found.\n\n{table}\n",
+            self.0.len()
+        )
+    }
+}
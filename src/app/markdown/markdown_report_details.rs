@@ -0,0 +1,146 @@
+use std::fmt::{Display, Formatter};
+
+use crate::domain::scanresult::scan_result::ScanResult;
+
+use super::{
+    markdown_accepted_risk_table::AcceptedRiskTable,
+    markdown_layer_breakdown_table::LayerBreakdownTable,
+    markdown_license_inventory_table::LicenseInventoryTable,
+    markdown_package_inventory_table::PackageInventoryTable,
+};
+
+/// Sections of the full scan report left out of hovers, which need to stay short.
+#[derive(Clone, Debug, Default)]
+pub struct MarkdownReportDetails {
+    pub accepted_risks: AcceptedRiskTable,
+    pub layers: LayerBreakdownTable,
+    pub packages: PackageInventoryTable,
+    pub licenses: LicenseInventoryTable,
+}
+
+impl From<&ScanResult> for MarkdownReportDetails {
+    fn from(value: &ScanResult) -> Self {
+        Self {
+            accepted_risks: AcceptedRiskTable::from(value),
+            layers: LayerBreakdownTable::from(value),
+            packages: PackageInventoryTable::from(value),
+            licenses: LicenseInventoryTable::from(value),
+        }
+    }
+}
+
+impl Display for MarkdownReportDetails {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}{}",
+            self.accepted_risks, self.layers, self.packages, self.licenses
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, Utc};
+
+    use crate::domain::scanresult::{
+        accepted_risk_reason::AcceptedRiskReason,
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        package_type::PackageType,
+        scan_type::ScanType,
+        severity::Severity,
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_renders_the_accepted_risks_layers_and_packages() {
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:3.18".to_string(),
+            "sha256:image".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine 3.18".to_string()),
+            0,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Passed,
+        );
+        let layer = scan_result.add_layer(
+            "sha256:layer".to_string(),
+            0,
+            Some(7_340_032),
+            "RUN   apk add openssl".to_string(),
+        );
+        let openssl = scan_result.add_package(
+            PackageType::Os,
+            "openssl".to_string(),
+            "3.1.0".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            layer.clone(),
+            true,
+            Some("Apache-2.0".to_string()),
+            None,
+        );
+        scan_result.add_package(
+            PackageType::Os,
+            "busybox".to_string(),
+            "1.36.1".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            layer,
+            false,
+            None,
+            None,
+        );
+        let vulnerability = scan_result.add_vulnerability(
+            "CVE-2023-0001".to_string(),
+            Severity::High,
+            NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            None,
+            false,
+            None,
+            None,
+            None,
+        );
+        openssl.add_vulnerability_found(vulnerability.clone());
+        scan_result
+            .add_accepted_risk(
+                "risk".to_string(),
+                AcceptedRiskReason::RiskMitigated,
+                "Not reachable".to_string(),
+                NaiveDate::from_ymd_opt(2030, 1, 1),
+                true,
+                Utc::now(),
+                Utc::now(),
+            )
+            .add_for_vulnerability(vulnerability);
+
+        let report = MarkdownReportDetails::from(&scan_result).to_string();
+
+        assert!(report.contains("### Accepted Risks"), "{report}");
+        assert!(
+            report.contains(
+                "| CVE-2023-0001 | RiskMitigated | Not reachable | 2030-01-01 |   ✅   |"
+            )
+        );
+        assert!(report.contains("### Layers"));
+        assert!(
+            report.contains("| 0 | `RUN apk add openssl` | 7.3 MB |    2     |"),
+            "{report}"
+        );
+        assert!(report.contains("2 packages found."));
+        let busybox = report.find("| busybox").unwrap();
+        let openssl = report.find("| openssl").unwrap();
+        assert!(busybox < openssl);
+        assert!(report.contains("### License Inventory"));
+        assert!(
+            report.contains("| Apache-2.0 |   1   | openssl 3.1.0  |"),
+            "{report}"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use std::fmt::{Display, Formatter};
+
+use tabled::{
+    builder::Builder,
+    settings::{Alignment, Style, object::Columns},
+};
+
+use crate::domain::scanresult::{
+    evaluation_result::EvaluationResult,
+    scan_result_diff::{EvaluationResultChange, ScanResultDiff},
+};
+
+#[derive(Clone, Debug, Default)]
+struct VulnerabilityChange {
+    change: &'static str,
+    cve: String,
+    severity: String,
+    fix_version: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct PackageChange {
+    change: &'static str,
+    name: String,
+    version: String,
+    package_type: String,
+}
+
+/// Report of the changes between two scan results: new and fixed vulnerabilities,
+/// added and removed packages and policy status changes.
+#[derive(Clone, Debug)]
+pub struct MarkdownScanResultDiff {
+    unchanged_vulnerabilities: usize,
+    vulnerabilities: Vec<VulnerabilityChange>,
+    packages: Vec<PackageChange>,
+    evaluation_result: EvaluationResultChange,
+    policies: Vec<(String, EvaluationResultChange)>,
+}
+
+impl From<&ScanResultDiff> for MarkdownScanResultDiff {
+    fn from(value: &ScanResultDiff) -> Self {
+        let vulnerabilities = [
+            ("New", value.added_vulnerabilities()),
+            ("Fixed", value.removed_vulnerabilities()),
+        ]
+        .into_iter()
+        .flat_map(|(change, vulnerabilities)| {
+            vulnerabilities.iter().map(move |v| VulnerabilityChange {
+                change,
+                cve: v.cve().to_string(),
+                severity: v.severity().to_string(),
+                fix_version: v.fix_version().cloned(),
+            })
+        })
+        .collect();
+
+        let packages = [
+            ("Added", value.added_packages()),
+            ("Removed", value.removed_packages()),
+        ]
+        .into_iter()
+        .flat_map(|(change, packages)| {
+            packages.iter().map(move |p| PackageChange {
+                change,
+                name: p.name().to_string(),
+                version: p.version().to_string(),
+                package_type: p.package_type().to_string(),
+            })
+        })
+        .collect();
+
+        MarkdownScanResultDiff {
+            unchanged_vulnerabilities: value.unchanged_vulnerabilities().len(),
+            vulnerabilities,
+            packages,
+            evaluation_result: value.evaluation_result(),
+            policies: value
+                .policy_changes()
+                .iter()
+                .map(|p| (p.policy_name.clone(), p.change))
+                .collect(),
+        }
+    }
+}
+
+fn evaluation_icon(evaluation_result: EvaluationResult) -> &'static str {
+    match evaluation_result {
+        EvaluationResult::Passed => "✅",
+        EvaluationResult::Failed => "❌",
+        EvaluationResult::NotEvaluated => "➖",
+    }
+}
+
+impl Display for MarkdownScanResultDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let count = |change: &str| {
+            self.vulnerabilities
+                .iter()
+                .filter(|v| v.change == change)
+                .count()
+        };
+
+        writeln!(f, "### Changes")?;
+        writeln!(
+            f,
+            "* **Vulnerabilities**: {} new, {} fixed, {} unchanged",
+            count("New"),
+            count("Fixed"),
+            self.unchanged_vulnerabilities
+        )?;
+        let EvaluationResultChange { before, after } = self.evaluation_result;
+        if self.evaluation_result.is_changed() {
+            writeln!(
+                f,
+                "* **Policy evaluation**: {} → {}",
+                evaluation_icon(before),
+                evaluation_icon(after)
+            )?;
+        } else {
+            writeln!(
+                f,
+                "* **Policy evaluation**: {} (unchanged)",
+                evaluation_icon(after)
+            )?;
+        }
+
+        if !self.vulnerabilities.is_empty() {
+            let mut builder = Builder::default();
+            builder.push_record(["CHANGE", "CVE", "SEVERITY", "FIX"]);
+            for v in &self.vulnerabilities {
+                builder.push_record([
+                    v.change.to_string(),
+                    v.cve.clone(),
+                    v.severity.clone(),
+                    v.fix_version.clone().unwrap_or_else(|| "-".to_string()),
+                ]);
+            }
+            let mut table = builder.build();
+            table.with(Style::markdown());
+            write!(f, "\n#### Vulnerabilities\n\n{table}\n")?;
+        }
+
+        if !self.packages.is_empty() {
+            let mut builder = Builder::default();
+            builder.push_record(["CHANGE", "PACKAGE", "VERSION", "TYPE"]);
+            for p in &self.packages {
+                builder.push_record([
+                    p.change.to_string(),
+                    p.name.clone(),
+                    p.version.clone(),
+                    p.package_type.clone(),
+                ]);
+            }
+            let mut table = builder.build();
+            table.with(Style::markdown());
+            write!(f, "\n#### Packages\n\n{table}\n")?;
+        }
+
+        if !self.policies.is_empty() {
+            let mut builder = Builder::default();
+            builder.push_record(["POLICY", "BEFORE", "AFTER"]);
+            for (name, change) in &self.policies {
+                builder.push_record([
+                    name.clone(),
+                    evaluation_icon(change.before).to_string(),
+                    evaluation_icon(change.after).to_string(),
+                ]);
+            }
+            let mut table = builder.build();
+            table
+                .with(Style::markdown())
+                .modify(Columns::new(1..), Alignment::center());
+            write!(f, "\n#### Policies\n\n{table}\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_renders_the_changes() {
+        let report = MarkdownScanResultDiff {
+            unchanged_vulnerabilities: 3,
+            vulnerabilities: vec![
+                VulnerabilityChange {
+                    change: "New",
+                    cve: "CVE-2024-0001".to_string(),
+                    severity: "High".to_string(),
+                    fix_version: Some("3.1.5".to_string()),
+                },
+                VulnerabilityChange {
+                    change: "Fixed",
+                    cve: "CVE-2023-0002".to_string(),
+                    severity: "Low".to_string(),
+                    fix_version: None,
+                },
+            ],
+            packages: vec![PackageChange {
+                change: "Added",
+                name: "openssl".to_string(),
+                version: "3.1.4".to_string(),
+                package_type: "os".to_string(),
+            }],
+            evaluation_result: EvaluationResultChange {
+                before: EvaluationResult::Passed,
+                after: EvaluationResult::Failed,
+            },
+            policies: vec![(
+                "Sysdig Best Practices".to_string(),
+                EvaluationResultChange {
+                    before: EvaluationResult::Passed,
+                    after: EvaluationResult::Failed,
+                },
+            )],
+        };
+
+        let expected = r#"### Changes
+* **Vulnerabilities**: 1 new, 1 fixed, 3 unchanged
+* **Policy evaluation**: ✅ → ❌
+
+#### Vulnerabilities
+
+| CHANGE | CVE           | SEVERITY | FIX   |
+|--------|---------------|----------|-------|
+| New    | CVE-2024-0001 | High     | 3.1.5 |
+| Fixed  | CVE-2023-0002 | Low      | -     |
+
+#### Packages
+
+| CHANGE | PACKAGE | VERSION | TYPE |
+|--------|---------|---------|------|
+| Added  | openssl | 3.1.4   | os   |
+
+#### Policies
+
+| POLICY                | BEFORE | AFTER |
+|-----------------------|--------|-------|
+| Sysdig Best Practices |   ✅   |  ❌   |
+"#;
+
+        assert_eq!(report.to_string(), expected);
+    }
+
+    #[test]
+    fn it_only_renders_the_summary_without_changes() {
+        let report = MarkdownScanResultDiff {
+            unchanged_vulnerabilities: 1,
+            vulnerabilities: vec![],
+            packages: vec![],
+            evaluation_result: EvaluationResultChange {
+                before: EvaluationResult::Passed,
+                after: EvaluationResult::Passed,
+            },
+            policies: vec![],
+        };
+
+        assert_eq!(
+            report.to_string(),
+            "### Changes\n* **Vulnerabilities**: 0 new, 0 fixed, 1 unchanged\n* **Policy evaluation**: ✅ (unchanged)\n"
+        );
+    }
+}
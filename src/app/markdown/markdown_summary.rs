@@ -7,20 +7,34 @@ use super::markdown_summary_table::MarkdownSummaryTable;
 #[derive(Clone, Debug, Default)]
 pub struct MarkdownSummary {
     pub pull_string: String,
+    /// Image as referenced in the document, when it was scanned as `pull_string` through
+    /// a registry mirror.
+    pub requested_pull_string: Option<String>,
     pub image_id: String,
     pub digest: Option<String>,
     pub base_os: String,
+    /// Architecture of the scanned image, e.g. `arm64`, telling which platform of a
+    /// multi-platform image the report is about.
+    pub architecture: String,
+    /// Page of the scan result in Sysdig Secure, when the scan was uploaded.
+    pub result_url: Option<String>,
     pub total_vulns_found: MarkdownSummaryTable,
+    /// Vulnerabilities left out of the diagnostics by ignore directives or the ignore file.
+    pub suppressed_vulns: usize,
 }
 
 impl From<&ScanResult> for MarkdownSummary {
     fn from(value: &ScanResult) -> Self {
         MarkdownSummary {
             pull_string: value.metadata().pull_string().to_string(),
+            requested_pull_string: value.metadata().requested_pull_string().map(str::to_string),
             image_id: value.metadata().image_id().to_string(),
             digest: value.metadata().digest().map(|s| s.to_string()),
             base_os: value.metadata().base_os().name().to_string(),
+            architecture: format!("{:?}", value.metadata().architecture()).to_lowercase(),
+            result_url: value.metadata().result_url().map(str::to_string),
             total_vulns_found: MarkdownSummaryTable::from(value),
+            suppressed_vulns: 0,
         }
     }
 }
@@ -28,14 +42,61 @@ impl From<&ScanResult> for MarkdownSummary {
 impl Display for MarkdownSummary {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "### Summary")?;
-        writeln!(f, "* **PullString**: {}", self.pull_string)?;
+        match &self.requested_pull_string {
+            Some(requested) => {
+                writeln!(f, "* **PullString**: {requested}")?;
+                writeln!(f, "* **Scanned from mirror**: {}", self.pull_string)?;
+            }
+            None => writeln!(f, "* **PullString**: {}", self.pull_string)?,
+        }
         writeln!(f, "* **ImageID**: `{}`", self.image_id)?;
         match &self.digest {
             Some(digest) => writeln!(f, "* **Digest**: `{}`", digest)?,
             None => writeln!(f, "* **Digest**: None")?,
         }
         writeln!(f, "* **BaseOS**: {}", self.base_os)?;
+        writeln!(f, "* **Architecture**: {}", self.architecture)?;
+        if let Some(result_url) = &self.result_url {
+            writeln!(
+                f,
+                "* **Sysdig Secure**: [Open the full report]({result_url})"
+            )?;
+        }
+        if self.suppressed_vulns > 0 {
+            writeln!(
+                f,
+                "* **Suppressed vulnerabilities**: {} (ignore directives or `.sysdigignore`)",
+                self.suppressed_vulns
+            )?;
+        }
         writeln!(f)?;
         write!(f, "{}", self.total_vulns_found)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_shows_both_references_of_images_scanned_through_a_mirror() {
+        let summary = MarkdownSummary {
+            pull_string: "mirror.example.com/dockerhub/library/alpine:3.19".to_string(),
+            requested_pull_string: Some("alpine:3.19".to_string()),
+            ..Default::default()
+        };
+
+        let markdown = summary.to_string();
+
+        assert!(
+            markdown.contains("* **PullString**: alpine:3.19\n"),
+            "{markdown}"
+        );
+        assert!(
+            markdown.contains(
+                "* **Scanned from mirror**: mirror.example.com/dockerhub/library/alpine:3.19\n"
+            ),
+            "{markdown}"
+        );
+    }
+}
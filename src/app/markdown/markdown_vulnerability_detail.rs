@@ -0,0 +1,256 @@
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+};
+
+use itertools::Itertools;
+use tabled::{builder::Builder, settings::Style};
+
+use crate::domain::scanresult::{
+    accepted_risk::AcceptedRisk, scan_result::ScanResult, vulnerability::Vulnerability,
+};
+
+use super::markdown_layer_mapping_table::sanitize_command;
+
+/// Everything the scan told about a single vulnerability: its CVSS assessment, dates,
+/// fix, exploitation, the packages carrying it and the risks accepted for it.
+pub struct MarkdownVulnerabilityDetail {
+    vulnerability: Arc<Vulnerability>,
+}
+
+impl MarkdownVulnerabilityDetail {
+    /// `None` unless the image has a vulnerability with that id.
+    pub fn for_cve(scan_result: &ScanResult, cve: &str) -> Option<Self> {
+        scan_result
+            .find_vulnerability_by_cve(cve)
+            .map(|vulnerability| Self { vulnerability })
+    }
+
+    /// Risks accepted for the vulnerability itself or for a package carrying it.
+    fn accepted_risks(&self) -> Vec<Arc<AcceptedRisk>> {
+        self.vulnerability
+            .accepted_risks()
+            .into_iter()
+            .chain(
+                self.vulnerability
+                    .found_in_packages()
+                    .iter()
+                    .flat_map(|package| package.accepted_risks()),
+            )
+            .unique_by(|risk| risk.id().to_string())
+            .sorted_by_key(|risk| (!risk.is_active(), risk.id().to_string()))
+            .collect()
+    }
+}
+
+impl Display for MarkdownVulnerabilityDetail {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let v = &self.vulnerability;
+        writeln!(f, "## Sysdig Vulnerability Detail for `{}`\n", v.cve())?;
+        writeln!(f, "- **Severity:** {}", v.severity())?;
+        match v.cvss() {
+            Some(cvss) => {
+                let vector = cvss
+                    .vector()
+                    .map(|vector| format!(", `{vector}`"))
+                    .unwrap_or_default();
+                writeln!(
+                    f,
+                    "- **CVSS:** {:.1} (v{}{vector})",
+                    cvss.score(),
+                    cvss.version()
+                )?
+            }
+            None => writeln!(f, "- **CVSS:** -")?,
+        }
+        writeln!(f, "- **Disclosed:** {}", v.disclosure_date())?;
+        writeln!(
+            f,
+            "- **Solution available:** {}",
+            v.solution_date()
+                .map_or_else(|| "-".to_string(), |date| date.to_string())
+        )?;
+        writeln!(
+            f,
+            "- **Fixed in:** {}",
+            v.fix_version().map_or("no fix available", String::as_str)
+        )?;
+        writeln!(f, "- **Exploit:** {}", exploit_status(v))?;
+        writeln!(
+            f,
+            "- **Details:** [NVD](https://nvd.nist.gov/vuln/detail/{})",
+            v.cve()
+        )?;
+
+        let mut packages = Builder::default();
+        packages.push_record(["PACKAGE", "VERSION", "TYPE", "PATH", "LAYER"]);
+        for p in v
+            .found_in_packages()
+            .iter()
+            .sorted_by(|a, b| a.cmp_by_name_and_version(b))
+        {
+            let layer = p.found_in_layer();
+            packages.push_record([
+                p.name().to_string(),
+                p.version().clone(),
+                p.package_type().to_string(),
+                p.path().to_string(),
+                format!("{}: {}", layer.index(), sanitize_command(layer.command())),
+            ]);
+        }
+        let mut packages = packages.build();
+        packages.with(Style::markdown());
+        write!(f, "\n### Affected Packages\n\n{packages}\n")?;
+
+        let accepted_risks = self.accepted_risks();
+        if accepted_risks.is_empty() {
+            return f.write_str(
+                "\n### Accepted Risks\n\nNo accepted risks apply to this vulnerability.\n",
+            );
+        }
+        let mut risks = Builder::default();
+        risks.push_record(["REASON", "DESCRIPTION", "EXPIRES", "ACTIVE"]);
+        for risk in &accepted_risks {
+            risks.push_record([
+                risk.reason().as_str().to_string(),
+                risk.description().replace('|', "\\|"),
+                risk.expiration_date()
+                    .map_or_else(|| "Never".to_string(), |date| date.to_string()),
+                if risk.is_active() { "✅" } else { "❌" }.to_string(),
+            ]);
+        }
+        let mut risks = risks.build();
+        risks.with(Style::markdown());
+        write!(f, "\n### Accepted Risks\n\n{risks}\n")
+    }
+}
+
+fn exploit_status(vulnerability: &Vulnerability) -> String {
+    let kev = vulnerability
+        .exploit()
+        .and_then(|exploit| exploit.cisa_kev());
+    if let Some(kev) = kev {
+        return match kev.due_date() {
+            Some(due) => format!("known exploited (CISA KEV, remediation due {due})"),
+            None => "known exploited (CISA KEV)".to_string(),
+        };
+    }
+    let published = vulnerability
+        .exploit()
+        .and_then(|exploit| exploit.publication_date());
+    match (vulnerability.exploitable(), published) {
+        (true, Some(date)) => format!("public exploit since {date}"),
+        (true, None) => "public exploit available".to_string(),
+        (false, _) => "none known".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, Utc};
+
+    use super::*;
+    use crate::domain::scanresult::{
+        accepted_risk_reason::AcceptedRiskReason,
+        architecture::Architecture,
+        cvss::Cvss,
+        evaluation_result::EvaluationResult,
+        exploit::{CisaKev, Exploit},
+        operating_system::{Family, OperatingSystem},
+        package_type::PackageType,
+        scan_type::ScanType,
+        severity::Severity,
+    };
+
+    fn scan_result() -> ScanResult {
+        let mut result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:latest".to_string(),
+            "sha256:12345".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+            123456,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Passed,
+        );
+        let layer = result.add_layer(
+            "sha256:layer1".to_string(),
+            0,
+            None,
+            "RUN apk add openssl".to_string(),
+        );
+        let openssl = result.add_package(
+            PackageType::Os,
+            "openssl".to_string(),
+            "3.1.0".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            layer,
+            false,
+            None,
+            None,
+        );
+        let date = |day| NaiveDate::from_ymd_opt(2024, 1, day).unwrap();
+        let vulnerability = result.add_vulnerability(
+            "CVE-2024-0001".to_string(),
+            Severity::High,
+            date(1),
+            Some(date(5)),
+            true,
+            Some("3.1.4".to_string()),
+            Some(Cvss::new(
+                8.1,
+                "AV:N/AC:H/PR:N/UI:N/S:U/C:H/I:H/A:H".to_string(),
+                "3.1".to_string(),
+            )),
+            Some(Exploit::new(
+                Some(date(2)),
+                vec![],
+                Some(CisaKev::new(Some(date(3)), Some(date(24)))),
+            )),
+        );
+        openssl.add_vulnerability_found(vulnerability.clone());
+        let risk = result.add_accepted_risk(
+            "risk-1".to_string(),
+            AcceptedRiskReason::RiskMitigated,
+            "Not reachable | behind the proxy".to_string(),
+            None,
+            true,
+            Utc::now(),
+            Utc::now(),
+        );
+        risk.add_for_vulnerability(vulnerability);
+        result
+    }
+
+    #[test]
+    fn it_renders_the_detail_of_the_vulnerability() {
+        let scan_result = scan_result();
+
+        let detail = MarkdownVulnerabilityDetail::for_cve(&scan_result, "CVE-2024-0001")
+            .unwrap()
+            .to_string();
+
+        assert!(detail.starts_with("## Sysdig Vulnerability Detail for `CVE-2024-0001`"));
+        assert!(detail.contains("- **CVSS:** 8.1 (v3.1, `AV:N/AC:H/PR:N/UI:N/S:U/C:H/I:H/A:H`)"));
+        assert!(detail.contains("- **Disclosed:** 2024-01-01"));
+        assert!(detail.contains("- **Solution available:** 2024-01-05"));
+        assert!(detail.contains("- **Fixed in:** 3.1.4"));
+        assert!(
+            detail
+                .contains("- **Exploit:** known exploited (CISA KEV, remediation due 2024-01-24)")
+        );
+        assert!(detail.contains(
+            "| openssl | 3.1.0   | os   | /lib/apk/db/installed | 0: RUN apk add openssl |"
+        ));
+        assert!(
+            detail.contains(
+                "| RiskMitigated | Not reachable \\| behind the proxy | Never   | ✅     |"
+            )
+        );
+        assert!(MarkdownVulnerabilityDetail::for_cve(&scan_result, "CVE-2024-9999").is_none());
+    }
+}
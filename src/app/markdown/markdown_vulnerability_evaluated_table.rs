@@ -6,15 +6,21 @@ use std::{
 use itertools::Itertools;
 use tabled::{builder::Builder, settings::Style};
 
-use crate::domain::scanresult::{layer::Layer, scan_result::ScanResult};
+use crate::domain::scanresult::{
+    layer::Layer, scan_result::ScanResult, vulnerability::Vulnerability,
+};
 
 #[derive(Clone, Debug, Default)]
 pub struct VulnerabilityEvaluated {
     pub cve: String,
     pub severity: String,
+    /// CVSS score and version, e.g. `9.8 (v3.1)`.
+    pub cvss: Option<String>,
     pub packages_found: u32,
     pub fixable: bool,
     pub exploitable: bool,
+    /// In the CISA Known Exploited Vulnerabilities catalog.
+    pub known_exploited: bool,
     pub accepted_risk: bool,
 }
 
@@ -39,9 +45,11 @@ impl From<&ScanResult> for VulnerabilityEvaluatedTable {
                 .map(|v| VulnerabilityEvaluated {
                     cve: v.cve().to_string(),
                     severity: v.severity().to_string(),
+                    cvss: cvss_label(v),
                     packages_found: v.found_in_packages().len() as u32,
                     fixable: v.fixable(),
                     exploitable: v.exploitable(),
+                    known_exploited: v.known_exploited(),
                     accepted_risk: !v.accepted_risks().is_empty(),
                 })
                 .collect(),
@@ -66,9 +74,11 @@ impl From<&Arc<Layer>> for VulnerabilityEvaluatedTable {
                 .map(|v| VulnerabilityEvaluated {
                     cve: v.cve().to_string(),
                     severity: v.severity().to_string(),
+                    cvss: cvss_label(v),
                     packages_found: v.found_in_packages().len() as u32,
                     fixable: v.fixable(),
                     exploitable: v.exploitable(),
+                    known_exploited: v.known_exploited(),
                     accepted_risk: !v.accepted_risks().is_empty(),
                 })
                 .collect(),
@@ -76,6 +86,12 @@ impl From<&Arc<Layer>> for VulnerabilityEvaluatedTable {
     }
 }
 
+fn cvss_label(vulnerability: &Vulnerability) -> Option<String> {
+    vulnerability
+        .cvss()
+        .map(|cvss| format!("{:.1} (v{})", cvss.score(), cvss.version()))
+}
+
 impl Display for VulnerabilityEvaluatedTable {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         if self.0.is_empty() {
@@ -86,9 +102,11 @@ impl Display for VulnerabilityEvaluatedTable {
         builder.push_record([
             "VULN CVE",
             "SEVERITY",
+            "CVSS",
             "PACKAGES",
             "FIXABLE",
             "EXPLOITABLE",
+            "KEV",
             "ACCEPTED RISK",
         ]);
 
@@ -96,9 +114,11 @@ impl Display for VulnerabilityEvaluatedTable {
             builder.push_record([
                 v.cve.clone(),
                 v.severity.clone(),
+                v.cvss.clone().unwrap_or_else(|| "-".to_string()),
                 v.packages_found.to_string(),
                 if v.fixable { "✅" } else { "❌" }.to_string(),
                 if v.exploitable { "✅" } else { "❌" }.to_string(),
+                if v.known_exploited { "✅" } else { "❌" }.to_string(),
                 if v.accepted_risk { "✅" } else { "❌" }.to_string(),
             ]);
         }
@@ -1,10 +1,28 @@
+mod markdown_accepted_risk_table;
+mod markdown_base_image_comparison_table;
 mod markdown_data;
 mod markdown_fixable_package_table;
+mod markdown_largest_layers_table;
+mod markdown_layer_breakdown_table;
 mod markdown_layer_data;
+mod markdown_layer_mapping_table;
+mod markdown_license_inventory_table;
+mod markdown_package_card;
+mod markdown_package_inventory_table;
 mod markdown_policy_evaluated_table;
+mod markdown_report_details;
+mod markdown_scan_result_diff;
 mod markdown_summary;
 mod markdown_summary_table;
+mod markdown_vulnerability_detail;
 mod markdown_vulnerability_evaluated_table;
 
+pub use markdown_base_image_comparison_table::BaseImageComparisonTable;
 pub use markdown_data::MarkdownData;
+pub use markdown_largest_layers_table::LargestLayersTable;
+pub use markdown_layer_breakdown_table::human_size;
 pub use markdown_layer_data::MarkdownLayerData;
+pub use markdown_layer_mapping_table::{LayerMapping, LayerMappingTable};
+pub use markdown_package_card::MarkdownPackageCard;
+pub use markdown_scan_result_diff::MarkdownScanResultDiff;
+pub use markdown_vulnerability_detail::MarkdownVulnerabilityDetail;
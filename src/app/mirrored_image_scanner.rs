@@ -0,0 +1,148 @@
+use tracing::debug;
+
+use crate::domain::{image_reference::RegistryMirrors, scanresult::scan_result::ScanResult};
+
+use super::{ImageScanError, ImageScanner};
+
+/// Scans images through the registry mirrors of `sysdig.registry.mirrors`, so scans pull
+/// from the registry the organization uses. Results of rewritten references keep the
+/// reference they were requested as in `Metadata::requested_pull_string`.
+pub struct MirroredImageScanner {
+    scanner: Box<dyn ImageScanner + Send + Sync>,
+    mirrors: RegistryMirrors,
+}
+
+impl MirroredImageScanner {
+    pub fn new(scanner: Box<dyn ImageScanner + Send + Sync>, mirrors: RegistryMirrors) -> Self {
+        Self { scanner, mirrors }
+    }
+
+    async fn scan(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+        rescan: bool,
+    ) -> Result<ScanResult, ImageScanError> {
+        let mirrored = self.mirrors.resolve(image_pull_string);
+        if let Some(mirrored) = &mirrored {
+            debug!("scanning {image_pull_string} through its registry mirror as {mirrored}");
+        }
+
+        let scanned = mirrored.as_deref().unwrap_or(image_pull_string);
+        let mut scan_result = match rescan {
+            true => {
+                self.scanner
+                    .rescan_image_for_platform(scanned, platform)
+                    .await?
+            }
+            false => {
+                self.scanner
+                    .scan_image_for_platform(scanned, platform)
+                    .await?
+            }
+        };
+        if mirrored.is_some() {
+            scan_result.set_requested_pull_string(image_pull_string.to_string());
+        }
+        Ok(scan_result)
+    }
+}
+
+#[async_trait::async_trait]
+impl ImageScanner for MirroredImageScanner {
+    async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+        self.scan_image_for_platform(image_pull_string, None).await
+    }
+
+    async fn scan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        self.scan(image_pull_string, platform, false).await
+    }
+
+    async fn rescan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        self.scan(image_pull_string, platform, true).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        scan_type::ScanType,
+    };
+
+    #[derive(Default)]
+    struct RecordingScanner {
+        scanned: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl ImageScanner for RecordingScanner {
+        async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+            self.scanned
+                .lock()
+                .unwrap()
+                .push(image_pull_string.to_string());
+            Ok(ScanResult::new(
+                ScanType::Docker,
+                image_pull_string.to_string(),
+                "sha256:image-id".to_string(),
+                None,
+                OperatingSystem::new(Family::Linux, "alpine:3.19".to_string()),
+                1024,
+                Architecture::Amd64,
+                HashMap::new(),
+                chrono::Utc::now(),
+                EvaluationResult::Passed,
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn it_scans_mirrored_images_through_their_mirror() {
+        let recording = RecordingScanner::default();
+        let scanned = recording.scanned.clone();
+        let scanner = MirroredImageScanner::new(
+            Box::new(recording),
+            RegistryMirrors::new([(
+                "docker.io".to_string(),
+                "mirror.example.com/dockerhub".to_string(),
+            )]),
+        );
+
+        let mirrored = scanner.scan_image("alpine:3.19").await.unwrap();
+        let direct = scanner.scan_image("quay.io/acme/app:1").await.unwrap();
+
+        assert_eq!(
+            *scanned.lock().unwrap(),
+            [
+                "mirror.example.com/dockerhub/library/alpine:3.19",
+                "quay.io/acme/app:1"
+            ]
+        );
+        assert_eq!(
+            mirrored.metadata().pull_string(),
+            "mirror.example.com/dockerhub/library/alpine:3.19"
+        );
+        assert_eq!(
+            mirrored.metadata().requested_pull_string(),
+            Some("alpine:3.19")
+        );
+        assert_eq!(direct.metadata().requested_pull_string(), None);
+    }
+}
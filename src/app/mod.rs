@@ -1,24 +1,72 @@
+mod base_image_recommender;
+mod caching_image_scanner;
+mod check_report;
 pub mod component_factory;
+mod credentials_checker;
 mod document_database;
+mod file_check;
+mod filesystem_scanner;
 mod iac_scanner;
 mod image_builder;
 mod image_scanner;
+mod lint;
 mod lsp_client;
 mod lsp_interactor;
 mod lsp_server;
 mod markdown;
+mod mirrored_image_scanner;
+pub mod protocol;
 mod queries;
+pub mod redaction;
+mod registry_client;
+mod risk_acceptance;
+mod scan_result_export;
+mod scan_result_store;
+mod scan_scheduler;
+mod server_trace;
+mod stored_scan_result;
+mod sysdig_ignore;
+mod tenant_scope;
+mod token_provider;
 
+pub use base_image_recommender::{BaseImageRecommendation, BaseImageRecommender};
+pub use caching_image_scanner::{CachingImageScanner, ImageDigestResolver, digest_in_pull_string};
+pub use check_report::{CheckFormat, CheckStatus};
+pub use credentials_checker::{CredentialsChecker, CredentialsError};
 pub use document_database::*;
+pub use file_check::{check_files, expand_check_paths};
+pub use filesystem_scanner::FilesystemScanner;
 pub use iac_scanner::{IacScanError, IacScanScope, IacScanner};
 
 /// `Diagnostic.source` tags identifying which scan type produced a diagnostic.
 /// Each producer replaces only its own diagnostics, so different scan types
-/// coexist on the same document with independent lifecycles.
-pub const IAC_DIAGNOSTIC_SOURCE: &str = "sysdig-iac";
-pub const VULN_DIAGNOSTIC_SOURCE: &str = "sysdig-vuln";
-pub use image_builder::{ImageBuildError, ImageBuildResult, ImageBuilder};
+/// coexist on the same document with independent lifecycles. They share the
+/// `sysdig-lsp/` namespace, so clients can match all of them at once.
+pub const IAC_DIAGNOSTIC_SOURCE: &str = "sysdig-lsp/iac";
+pub const VULN_DIAGNOSTIC_SOURCE: &str = "sysdig-lsp/vuln";
+pub const POLICY_DIAGNOSTIC_SOURCE: &str = "sysdig-lsp/policy";
+pub const LINT_DIAGNOSTIC_SOURCE: &str = "sysdig-lsp/lint";
+pub const FILESYSTEM_DIAGNOSTIC_SOURCE: &str = "sysdig-lsp/fs-vuln";
+pub use image_builder::{
+    BuildLogSender, ImageBuildError, ImageBuildOptions, ImageBuildResult, ImageBuilder, ImageInUse,
+};
 pub use image_scanner::{ImageScanError, ImageScanner};
 pub use lsp_client::LSPClient;
 pub use lsp_interactor::LspInteractor;
 pub use lsp_server::LSPServer;
+pub use lsp_server::server_status::*;
+pub use mirrored_image_scanner::MirroredImageScanner;
+pub use registry_client::{ImageTag, RegistryClient, RegistryError, split_image_tag};
+pub use risk_acceptance::{
+    PackageVersion, RiskAcceptance, RiskAcceptanceClient, RiskAcceptanceError, RiskAcceptanceScope,
+};
+pub use scan_result_export::{LicenseInventoryExport, ScanResultExport};
+pub use scan_result_store::{
+    ScanResultStore, ScanResultStoreError, ScanSnapshot, SnapshotDocumentation,
+};
+pub use scan_scheduler::{ScanScheduler, ScheduledImageScanner};
+pub use server_trace::ServerTrace;
+pub use stored_scan_result::StoredDocumentScanResult;
+pub use sysdig_ignore::{SYSDIG_IGNORE_FILE, SysdigIgnore};
+pub use tenant_scope::TenantScope;
+pub use token_provider::{TokenProvider, TokenProviderError, first_token};
@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, Url};
+
+use super::ScanResultParams;
+
+/// Item of the `sysdig-lsp.list-images` result: an image referenced in the workspace,
+/// e.g. for clients to list them in a tree view.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageInventoryEntry {
+    pub uri: Url,
+    pub range: Range,
+    /// Image as referenced in the file.
+    pub image: String,
+    /// Summary of the last scan of the image in the session, from whichever document it
+    /// was scanned, as sent in `sysdig/scanResult`. `None` until it's scanned.
+    pub last_scan: Option<ScanResultParams>,
+}
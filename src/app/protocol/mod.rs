@@ -0,0 +1,13 @@
+//! Custom LSP messages exchanged with editor extensions, on top of the standard protocol.
+
+mod image_inventory;
+mod scan_progress_notification;
+mod scan_queue_notification;
+mod scan_result_notification;
+mod server_status_request;
+
+pub use image_inventory::*;
+pub use scan_progress_notification::*;
+pub use scan_queue_notification::*;
+pub use scan_result_notification::*;
+pub use server_status_request::*;
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::notification::Notification;
+
+/// `sysdig/scanProgress` notification, sent when a running image scan moves on to
+/// another phase, so clients can tell a long pull apart from a stuck scan.
+pub enum ScanProgressNotification {}
+
+impl Notification for ScanProgressNotification {
+    type Params = ScanProgressParams;
+    const METHOD: &'static str = "sysdig/scanProgress";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanProgressParams {
+    /// Image being scanned, as in `sysdig/scanQueue`.
+    pub pull_string: String,
+    pub phase: ScanPhase,
+    /// Log line of the scanner the phase was read from.
+    pub message: String,
+}
+
+/// Phases of an image scan, as told by the console log of the scanner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScanPhase {
+    /// Downloading the vulnerability database.
+    UpdatingDatabase,
+    /// Pulling the image, which takes most of the scan of large remote images.
+    Pulling,
+    /// Cataloging the packages of the image and matching their vulnerabilities.
+    Analyzing,
+    EvaluatingPolicies,
+    Uploading,
+}
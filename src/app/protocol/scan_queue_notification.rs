@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::notification::Notification;
+
+/// `sysdig/scanQueue` notification, sent every time an image scan is queued, starts or
+/// finishes, so clients can show the progress of the scans (e.g. in a status bar).
+pub enum ScanQueueNotification {}
+
+impl Notification for ScanQueueNotification {
+    type Params = ScanQueueParams;
+    const METHOD: &'static str = "sysdig/scanQueue";
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanQueueParams {
+    /// Pull strings of the images being scanned.
+    pub running: Vec<String>,
+    /// Pull strings of the images waiting for a scan to finish, in arrival order.
+    pub queued: Vec<String>,
+}
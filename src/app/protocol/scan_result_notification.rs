@@ -0,0 +1,209 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Range, Url, notification::Notification};
+
+use crate::app::scan_result_export::{duration_ms, evaluation_result_str};
+use crate::domain::scanresult::{
+    scan_result::ScanResult, severity::Severity, vulnerability::Vulnerability,
+};
+
+/// Version of the `sysdig/scanResult` params, bumped on breaking changes so clients
+/// can ignore the notifications they don't understand.
+pub const SCAN_RESULT_NOTIFICATION_VERSION: u32 = 1;
+
+/// `sysdig/scanResult` notification, sent after each image scan with a summary of its
+/// result, so clients can render it (e.g. in a status bar) without parsing diagnostics.
+pub enum ScanResultNotification {}
+
+impl Notification for ScanResultNotification {
+    type Params = ScanResultParams;
+    const METHOD: &'static str = "sysdig/scanResult";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanResultParams {
+    /// Always `SCAN_RESULT_NOTIFICATION_VERSION`.
+    pub version: u32,
+    /// Document the image is referenced (or built) from.
+    pub uri: Url,
+    /// Range of the image reference, or of the build lens for "Build and scan".
+    pub range: Range,
+    pub pull_string: String,
+    /// Image as referenced in the document, when it was scanned as `pull_string` through
+    /// a registry mirror.
+    #[serde(default)]
+    pub requested_pull_string: Option<String>,
+    pub image_id: String,
+    pub digest: Option<String>,
+    /// Vulnerabilities found in the image, by severity.
+    pub vulnerabilities: SeverityCounts,
+    pub policy_evaluation: PolicyEvaluationSummary,
+    /// Page of the result in Sysdig Secure, when the scan was uploaded.
+    pub result_url: Option<String>,
+    /// Parts of the scanner report left out because they couldn't be read.
+    #[serde(default)]
+    pub parse_warnings: Vec<String>,
+    /// When the scanner scanned the image, earlier than the notification for cached results.
+    #[serde(default)]
+    pub scan_time: Option<DateTime<Utc>>,
+    /// How long the scanner took, in milliseconds.
+    #[serde(default)]
+    pub scan_duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeverityCounts {
+    pub critical: usize,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+    pub negligible: usize,
+}
+
+impl From<&ScanResult> for SeverityCounts {
+    fn from(scan_result: &ScanResult) -> Self {
+        Self::from(scan_result.vulnerabilities().as_slice())
+    }
+}
+
+impl From<&[Arc<Vulnerability>]> for SeverityCounts {
+    fn from(vulnerabilities: &[Arc<Vulnerability>]) -> Self {
+        let severities = vulnerabilities.iter().counts_by(|v| v.severity());
+        let count = |severity| severities.get(&severity).copied().unwrap_or_default();
+
+        SeverityCounts {
+            critical: count(Severity::Critical),
+            high: count(Severity::High),
+            medium: count(Severity::Medium),
+            low: count(Severity::Low),
+            negligible: count(Severity::Negligible),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyEvaluationSummary {
+    /// `passed`, `failed` or `notEvaluated` (offline scans).
+    pub result: String,
+    /// Names of the failed policies, sorted.
+    pub failed_policies: Vec<String>,
+}
+
+impl ScanResultParams {
+    pub fn new(uri: Url, range: Range, scan_result: &ScanResult) -> Self {
+        let metadata = scan_result.metadata();
+
+        ScanResultParams {
+            version: SCAN_RESULT_NOTIFICATION_VERSION,
+            uri,
+            range,
+            pull_string: metadata.pull_string().to_string(),
+            requested_pull_string: metadata.requested_pull_string().map(str::to_string),
+            image_id: metadata.image_id().to_string(),
+            digest: metadata.digest().map(str::to_string),
+            vulnerabilities: SeverityCounts::from(scan_result),
+            policy_evaluation: PolicyEvaluationSummary {
+                result: evaluation_result_str(&scan_result.evaluation_result()).to_string(),
+                failed_policies: scan_result
+                    .policies()
+                    .iter()
+                    .filter(|policy| policy.evaluation_result().is_failed())
+                    .map(|policy| policy.name().to_string())
+                    .sorted()
+                    .collect(),
+            },
+            result_url: metadata.result_url().map(str::to_string),
+            parse_warnings: metadata.parse_warnings().to_vec(),
+            scan_time: metadata.scanned_at(),
+            scan_duration_ms: metadata.scan_duration().map(duration_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, Utc};
+    use serde_json::json;
+    use tower_lsp::lsp_types::Position;
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        scan_type::ScanType,
+    };
+
+    #[test]
+    fn it_summarizes_the_scan_result() {
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:3.19".to_string(),
+            "sha256:12345".to_string(),
+            Some("sha256:67890".to_string()),
+            OperatingSystem::new(Family::Linux, "alpine:3.19".to_string()),
+            123456,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Failed,
+        );
+        for (cve, severity) in [
+            ("CVE-1", Severity::Critical),
+            ("CVE-2", Severity::High),
+            ("CVE-3", Severity::High),
+        ] {
+            scan_result.add_vulnerability(
+                cve.to_string(),
+                severity,
+                NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                None,
+                false,
+                None,
+                None,
+                None,
+            );
+        }
+        scan_result.set_result_url("https://secure.sysdig.com/scans/1".to_string());
+        scan_result.add_parse_warning(
+            "vulnerabilities.v1.severity: unknown value \"extreme\"".to_string(),
+        );
+        scan_result.set_scan_time(
+            "2025-09-30T16:05:33Z".parse().unwrap(),
+            Some(std::time::Duration::from_millis(10_413)),
+        );
+
+        let params = ScanResultParams::new(
+            "file:///Dockerfile".parse().unwrap(),
+            Range::new(Position::new(0, 0), Position::new(0, 16)),
+            &scan_result,
+        );
+
+        assert_eq!(
+            serde_json::to_value(params).unwrap(),
+            json!({
+                "version": 1,
+                "uri": "file:///Dockerfile",
+                "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 16}},
+                "pullString": "alpine:3.19",
+                "requestedPullString": null,
+                "imageId": "sha256:12345",
+                "digest": "sha256:67890",
+                "vulnerabilities": {"critical": 1, "high": 2, "medium": 0, "low": 0, "negligible": 0},
+                "policyEvaluation": {"result": "failed", "failedPolicies": []},
+                "resultUrl": "https://secure.sysdig.com/scans/1",
+                "parseWarnings": ["vulnerabilities.v1.severity: unknown value \"extreme\""],
+                "scanTime": "2025-09-30T16:05:33Z",
+                "scanDurationMs": 10413
+            })
+        );
+    }
+}
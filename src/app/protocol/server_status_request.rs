@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::request::Request;
+
+/// `sysdig/version` request, answering the versions of the server and of the scanner,
+/// e.g. for clients to warn about outdated servers.
+pub enum ServerVersionRequest {}
+
+impl Request for ServerVersionRequest {
+    type Params = ();
+    type Result = ServerVersionResult;
+    const METHOD: &'static str = "sysdig/version";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerVersionResult {
+    pub version: String,
+    /// Version of the CLI scanner in use, known once it ran a scan.
+    pub scanner_version: Option<String>,
+}
+
+/// `sysdig/status` request, answering the configuration in use and what the server did
+/// so far, so clients can show it (e.g. in a status bar) and users can share it when
+/// reporting issues.
+pub enum ServerStatusRequest {}
+
+impl Request for ServerStatusRequest {
+    type Params = ();
+    type Result = ServerStatusResult;
+    const METHOD: &'static str = "sysdig/status";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatusResult {
+    pub version: String,
+    /// Whether settings were received, so scans can run.
+    pub configured: bool,
+    pub api_url: Option<String>,
+    /// `[redacted]` once a token was found, wherever it came from.
+    pub api_token: Option<String>,
+    /// `cli` or `api`.
+    pub scanner_mode: Option<String>,
+    /// Version of the CLI scanner in use, known once it ran a scan.
+    pub scanner_version: Option<String>,
+    pub offline: bool,
+    pub scans: ScanCounts,
+    pub cache: CacheCounts,
+}
+
+/// Image scans of the session, across configuration changes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanCounts {
+    pub succeeded: u64,
+    pub failed: u64,
+    pub running: u64,
+    pub queued: u64,
+}
+
+/// Lookups of the scan results cached since the settings were last applied.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheCounts {
+    /// Scans answered with a cached result.
+    pub hits: u64,
+    /// Scans that had to run the scanner.
+    pub misses: u64,
+    pub ttl_seconds: u64,
+}
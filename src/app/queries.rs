@@ -1,4 +1,8 @@
+use chrono::Utc;
+use tower_lsp::lsp_types::{Diagnostic, Position};
+
 use super::InMemoryDocumentDatabase;
+use super::markdown::MarkdownVulnerabilityDetail;
 
 pub struct QueryExecutor {
     document_database: InMemoryDocumentDatabase,
@@ -12,4 +16,88 @@ impl QueryExecutor {
     pub async fn get_document_text(&self, uri: &str) -> Option<String> {
         self.document_database.read_document_text(uri).await
     }
+
+    pub async fn get_diagnostics(&self, uri: &str) -> Vec<Diagnostic> {
+        self.document_database.read_diagnostics(uri).await
+    }
+
+    pub async fn get_all_diagnostics(&self) -> Vec<(String, Vec<Diagnostic>)> {
+        self.document_database.all_diagnostics().await.collect()
+    }
+
+    /// Hover content at `position`: the focused card of the package under the cursor if
+    /// there is one, otherwise the report of the scan result covering the position.
+    pub async fn get_documentation_at(&self, uri: &str, position: Position) -> Option<String> {
+        self.document_database
+            .read_documentation_at(uri, position)
+            .await
+            .map(|documentation| {
+                let content = documentation.content_at(position);
+                match documentation.scanned_ago(Utc::now()) {
+                    Some(ago) => format!("{content}\n\n_Scanned {ago}._"),
+                    None => content.to_string(),
+                }
+            })
+    }
+
+    /// Detail of the vulnerability whose id is under the cursor (e.g. in a layer or a
+    /// `# sysdig-lsp: ignore` comment), if the last scan of the document found it.
+    pub async fn get_vulnerability_detail_at(
+        &self,
+        uri: &str,
+        position: Position,
+    ) -> Option<String> {
+        let text = self.document_database.read_document_text(uri).await?;
+        let line = text.lines().nth(position.line as usize)?;
+        let id = vulnerability_id_at(line, position.character as usize)?;
+        let last_scan = self.document_database.read_last_scan_result(uri).await?;
+        MarkdownVulnerabilityDetail::for_cve(&last_scan.scan_result, &id)
+            .map(|detail| detail.to_string())
+    }
+}
+
+/// The vulnerability id-like token (e.g. `CVE-2024-0001`, `GHSA-xxxx-xxxx-xxxx`) the
+/// `character` is in.
+fn vulnerability_id_at(line: &str, character: usize) -> Option<String> {
+    let is_id_char = |c: &char| c.is_ascii_alphanumeric() || *c == '-';
+    let chars: Vec<char> = line.chars().collect();
+    if !chars.get(character).is_some_and(is_id_char) {
+        return None;
+    }
+    let start = chars[..character]
+        .iter()
+        .rposition(|c| !is_id_char(c))
+        .map_or(0, |i| i + 1);
+    let end = chars[character..]
+        .iter()
+        .position(|c| !is_id_char(c))
+        .map_or(chars.len(), |i| character + i);
+    let id: String = chars[start..end].iter().collect();
+    id.contains('-').then_some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::vulnerability_id_at;
+
+    #[test]
+    fn it_finds_the_vulnerability_id_under_the_cursor() {
+        let line = "# sysdig-lsp: ignore CVE-2024-0001, GHSA-abcd-1234-wxyz";
+
+        assert_eq!(
+            vulnerability_id_at(line, 21).as_deref(),
+            Some("CVE-2024-0001")
+        );
+        assert_eq!(
+            vulnerability_id_at(line, 33).as_deref(),
+            Some("CVE-2024-0001")
+        );
+        assert_eq!(
+            vulnerability_id_at(line, 40).as_deref(),
+            Some("GHSA-abcd-1234-wxyz")
+        );
+        assert_eq!(vulnerability_id_at(line, 34), None);
+        assert_eq!(vulnerability_id_at(line, 15), None);
+        assert_eq!(vulnerability_id_at(line, 100), None);
+    }
 }
@@ -0,0 +1,64 @@
+use thiserror::Error;
+
+/// A tag of an image repository, as listed by its registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageTag {
+    pub name: String,
+    /// Digest of the manifest the tag points to, when the registry lists it.
+    pub digest: Option<String>,
+}
+
+/// Queries container registries (Docker Hub or any OCI distribution registry).
+#[async_trait::async_trait]
+pub trait RegistryClient {
+    /// Tags of `repository`, the image name without tag nor digest (e.g. `nginx` or
+    /// `ghcr.io/org/app`), most recently updated first when the registry tells.
+    async fn list_tags(&self, repository: &str) -> Result<Vec<ImageTag>, RegistryError>;
+
+    /// Digest of the manifest (or multi-platform index) `image` currently points to,
+    /// with a `HEAD` request of its tag (`latest` if it has none).
+    async fn resolve_digest(&self, image: &str) -> Result<String, RegistryError>;
+}
+
+/// Splits an image reference without digest into its repository and tag. The tag
+/// follows the last `:` of the last path component, so registry ports
+/// (`localhost:5000/app`) aren't mistaken for tags.
+pub fn split_image_tag(image: &str) -> (&str, Option<&str>) {
+    let last_component = image.rsplit('/').next().unwrap_or(image);
+    match last_component.rsplit_once(':') {
+        Some((_, tag)) => (&image[..image.len() - tag.len() - 1], Some(tag)),
+        None => (image, None),
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RegistryError {
+    #[error("unable to reach the registry at {url}: {reason}")]
+    Unreachable { url: String, reason: String },
+
+    #[error("the registry at {url} answered with status {status}")]
+    UnexpectedStatus { url: String, status: u16 },
+
+    #[error("invalid response from the registry at {url}: {reason}")]
+    InvalidResponse { url: String, reason: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("nginx", "nginx", None)]
+    #[case("nginx:1.27", "nginx", Some("1.27"))]
+    #[case("localhost:5000/app", "localhost:5000/app", None)]
+    #[case("localhost:5000/app:v1", "localhost:5000/app", Some("v1"))]
+    fn it_splits_the_tag_of_an_image(
+        #[case] image: &str,
+        #[case] repository: &str,
+        #[case] tag: Option<&str>,
+    ) {
+        assert_eq!(split_image_tag(image), (repository, tag));
+    }
+}
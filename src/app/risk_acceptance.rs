@@ -0,0 +1,52 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::domain::scanresult::accepted_risk_reason::AcceptedRiskReason;
+
+/// Acceptance of the risk of a vulnerability in an image, so it stops failing the
+/// policies of the image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RiskAcceptance {
+    pub cve: String,
+    pub scope: RiskAcceptanceScope,
+    pub reason: AcceptedRiskReason,
+    pub description: String,
+    /// `None` never expires.
+    pub expiration_date: Option<NaiveDate>,
+}
+
+/// Where the vulnerability of a risk acceptance is accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RiskAcceptanceScope {
+    /// In the image with this pull string.
+    Image(String),
+    /// In this version of a package, in any image.
+    Package(PackageVersion),
+}
+
+/// A package of a scanned image, as referenced by risk acceptances.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageVersion {
+    pub name: String,
+    pub version: String,
+}
+
+/// Creates risk acceptances in the Sysdig backend.
+#[async_trait::async_trait]
+pub trait RiskAcceptanceClient {
+    async fn accept_risk(&self, acceptance: &RiskAcceptance) -> Result<(), RiskAcceptanceError>;
+}
+
+#[derive(Error, Debug)]
+pub enum RiskAcceptanceError {
+    #[error("the risk acceptance of {cve} was rejected with status {status}: {message}")]
+    Rejected {
+        cve: String,
+        status: u16,
+        message: String,
+    },
+
+    #[error("unable to reach the Sysdig API at {url}: {reason}")]
+    Unreachable { url: String, reason: String },
+}
@@ -0,0 +1,479 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tower_lsp::lsp_types::Range;
+
+use crate::domain::scanresult::{
+    evaluation_result::EvaluationResult, package_version::compare_versions,
+    policy_bundle_rule_failure::PolicyBundleRuleFailure, sarif::SarifRegion,
+    scan_result::ScanResult,
+};
+
+/// Machine-readable view of a `ScanResult`, flattening the domain object graph:
+/// relations between entities are expressed by reference (layer index, CVE id,
+/// accepted risk id) instead of nesting.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanResultExport {
+    pub metadata: MetadataExport,
+    pub evaluation_result: &'static str,
+    pub layers: Vec<LayerExport>,
+    pub packages: Vec<PackageExport>,
+    pub vulnerabilities: Vec<VulnerabilityExport>,
+    pub policies: Vec<PolicyExport>,
+    pub accepted_risks: Vec<AcceptedRiskExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataExport {
+    pub pull_string: String,
+    /// Image as referenced in the document, when it was scanned through a registry mirror.
+    pub requested_pull_string: Option<String>,
+    pub image_id: String,
+    pub digest: Option<String>,
+    pub base_os: String,
+    pub size_in_bytes: u64,
+    pub architecture: String,
+    pub labels: HashMap<String, String>,
+    pub created_at: DateTime<Utc>,
+    /// Parts of the scanner report left out because they couldn't be read.
+    pub parse_warnings: Vec<String>,
+    /// When the scanner scanned the image, if it told.
+    pub scan_time: Option<DateTime<Utc>>,
+    /// How long the scanner took, in milliseconds.
+    pub scan_duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerExport {
+    pub index: usize,
+    pub digest: Option<String>,
+    pub size: Option<u64>,
+    pub command: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageExport {
+    pub name: String,
+    pub version: String,
+    #[serde(rename = "type")]
+    pub package_type: String,
+    pub path: String,
+    pub layer_index: usize,
+    pub license: Option<String>,
+    pub suggested_fix_version: Option<String>,
+    /// Loaded at runtime, according to Sysdig runtime insights.
+    pub in_use: bool,
+    /// CVE ids of the vulnerabilities found in the package.
+    pub vulnerabilities: Vec<String>,
+    pub accepted_risks: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VulnerabilityExport {
+    pub cve: String,
+    pub severity: String,
+    pub cvss: Option<CvssExport>,
+    pub disclosure_date: NaiveDate,
+    pub solution_date: Option<NaiveDate>,
+    pub exploitable: bool,
+    /// In the CISA Known Exploited Vulnerabilities catalog.
+    pub known_exploited: bool,
+    pub fixable: bool,
+    pub fix_version: Option<String>,
+    pub accepted_risks: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CvssExport {
+    pub score: f32,
+    pub vector: Option<String>,
+    pub version: String,
+}
+
+/// Packages of a `ScanResult` grouped by license, for compliance reviews. Packages
+/// whose license the scanner couldn't tell are under a `null` license.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseInventoryExport {
+    pub pull_string: String,
+    pub licenses: Vec<LicenseExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseExport {
+    pub license: Option<String>,
+    pub packages: Vec<LicensedPackageExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicensedPackageExport {
+    pub name: String,
+    pub version: String,
+    #[serde(rename = "type")]
+    pub package_type: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyExport {
+    pub id: String,
+    pub name: String,
+    pub evaluation_result: &'static str,
+    pub bundles: Vec<PolicyBundleExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyBundleExport {
+    pub id: String,
+    pub name: String,
+    pub evaluation_result: &'static str,
+    pub rules: Vec<PolicyRuleExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyRuleExport {
+    pub id: String,
+    pub description: String,
+    pub evaluation_result: &'static str,
+    pub failures: Vec<PolicyRuleFailureExport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PolicyRuleFailureExport {
+    ImageConfig { description: String },
+    PkgVuln { remediation: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptedRiskExport {
+    pub id: String,
+    pub reason: String,
+    pub description: String,
+    pub expiration_date: Option<NaiveDate>,
+    pub is_active: bool,
+}
+
+pub(super) fn evaluation_result_str(evaluation_result: &EvaluationResult) -> &'static str {
+    match evaluation_result {
+        EvaluationResult::Passed => "passed",
+        EvaluationResult::Failed => "failed",
+        EvaluationResult::NotEvaluated => "notEvaluated",
+    }
+}
+
+/// LSP positions are 0-based, SARIF ones 1-based.
+pub(super) fn sarif_region(range: Range) -> SarifRegion {
+    SarifRegion {
+        start_line: range.start.line + 1,
+        start_column: range.start.character + 1,
+        end_line: range.end.line + 1,
+        end_column: range.end.character + 1,
+    }
+}
+
+/// Milliseconds of `duration`, saturating for durations no scan lasts.
+pub(super) fn duration_ms(duration: std::time::Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+impl From<&ScanResult> for ScanResultExport {
+    fn from(scan_result: &ScanResult) -> Self {
+        let metadata = scan_result.metadata();
+
+        let mut layers: Vec<_> = scan_result
+            .layers()
+            .iter()
+            .map(|layer| LayerExport {
+                index: layer.index(),
+                digest: layer.digest().map(str::to_string),
+                size: layer.size().copied(),
+                command: layer.command().to_string(),
+            })
+            .collect();
+        layers.sort_by_key(|layer| layer.index);
+
+        let mut packages: Vec<_> = scan_result
+            .packages()
+            .iter()
+            .map(|package| PackageExport {
+                name: package.name().to_string(),
+                version: package.version().to_string(),
+                package_type: package.package_type().to_string(),
+                path: package.path().to_string(),
+                layer_index: package.found_in_layer().index(),
+                license: package.license().map(str::to_string),
+                suggested_fix_version: package.suggested_fix_version(),
+                in_use: package.is_running(),
+                vulnerabilities: sorted(package.vulnerabilities().iter().map(|v| v.cve())),
+                accepted_risks: sorted(package.accepted_risks().iter().map(|r| r.id())),
+            })
+            .collect();
+        packages.sort_by(|a, b| {
+            a.name
+                .cmp(&b.name)
+                .then_with(|| compare_versions(&a.version, &b.version))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        let mut vulnerabilities: Vec<_> = scan_result
+            .vulnerabilities()
+            .iter()
+            .map(|vuln| VulnerabilityExport {
+                cve: vuln.cve().to_string(),
+                severity: vuln.severity().to_string(),
+                cvss: vuln.cvss().map(|cvss| CvssExport {
+                    score: cvss.score(),
+                    vector: cvss.vector().map(str::to_string),
+                    version: cvss.version().to_string(),
+                }),
+                disclosure_date: vuln.disclosure_date(),
+                solution_date: vuln.solution_date(),
+                exploitable: vuln.exploitable(),
+                known_exploited: vuln.known_exploited(),
+                fixable: vuln.fixable(),
+                fix_version: vuln.fix_version().cloned(),
+                accepted_risks: sorted(vuln.accepted_risks().iter().map(|r| r.id())),
+            })
+            .collect();
+        vulnerabilities.sort_by(|a, b| a.cve.cmp(&b.cve));
+
+        let mut policies: Vec<_> = scan_result
+            .policies()
+            .iter()
+            .map(|policy| {
+                let mut bundles: Vec<_> = policy
+                    .bundles()
+                    .iter()
+                    .map(|bundle| {
+                        let mut rules: Vec<_> = bundle
+                            .rules()
+                            .iter()
+                            .map(|rule| PolicyRuleExport {
+                                id: rule.id().to_string(),
+                                description: rule.description().to_string(),
+                                evaluation_result: evaluation_result_str(rule.evaluation_result()),
+                                failures: rule
+                                    .failures()
+                                    .iter()
+                                    .map(|failure| match failure {
+                                        PolicyBundleRuleFailure::ImageConfig(f) => {
+                                            PolicyRuleFailureExport::ImageConfig {
+                                                description: f.description().to_string(),
+                                            }
+                                        }
+                                        PolicyBundleRuleFailure::PkgVuln(f) => {
+                                            PolicyRuleFailureExport::PkgVuln {
+                                                remediation: f.remediation().to_string(),
+                                            }
+                                        }
+                                    })
+                                    .collect(),
+                            })
+                            .collect();
+                        rules.sort_by(|a, b| a.id.cmp(&b.id));
+
+                        PolicyBundleExport {
+                            id: bundle.id().to_string(),
+                            name: bundle.name().to_string(),
+                            evaluation_result: evaluation_result_str(&bundle.evaluation_result()),
+                            rules,
+                        }
+                    })
+                    .collect();
+                bundles.sort_by(|a, b| a.id.cmp(&b.id));
+
+                PolicyExport {
+                    id: policy.id().to_string(),
+                    name: policy.name().to_string(),
+                    evaluation_result: evaluation_result_str(&policy.evaluation_result()),
+                    bundles,
+                }
+            })
+            .collect();
+        policies.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut accepted_risks: Vec<_> = scan_result
+            .accepted_risks()
+            .iter()
+            .map(|risk| AcceptedRiskExport {
+                id: risk.id().to_string(),
+                reason: format!("{:?}", risk.reason()),
+                description: risk.description().to_string(),
+                expiration_date: risk.expiration_date(),
+                is_active: risk.is_active(),
+            })
+            .collect();
+        accepted_risks.sort_by(|a, b| a.id.cmp(&b.id));
+
+        ScanResultExport {
+            metadata: MetadataExport {
+                pull_string: metadata.pull_string().to_string(),
+                requested_pull_string: metadata.requested_pull_string().map(str::to_string),
+                image_id: metadata.image_id().to_string(),
+                digest: metadata.digest().map(str::to_string),
+                base_os: metadata.base_os().name().to_string(),
+                size_in_bytes: *metadata.size_in_bytes(),
+                architecture: format!("{:?}", metadata.architecture()).to_lowercase(),
+                labels: metadata.labels().clone(),
+                created_at: metadata.created_at(),
+                parse_warnings: metadata.parse_warnings().to_vec(),
+                scan_time: metadata.scanned_at(),
+                scan_duration_ms: metadata.scan_duration().map(duration_ms),
+            },
+            evaluation_result: evaluation_result_str(&scan_result.evaluation_result()),
+            layers,
+            packages,
+            vulnerabilities,
+            policies,
+            accepted_risks,
+        }
+    }
+}
+
+impl From<&ScanResult> for LicenseInventoryExport {
+    fn from(scan_result: &ScanResult) -> Self {
+        LicenseInventoryExport {
+            pull_string: scan_result.metadata().pull_string().to_string(),
+            licenses: scan_result
+                .packages_by_license()
+                .into_iter()
+                .map(|(license, packages)| LicenseExport {
+                    license,
+                    packages: packages
+                        .iter()
+                        .map(|package| LicensedPackageExport {
+                            name: package.name().to_string(),
+                            version: package.version().to_string(),
+                            package_type: package.package_type().to_string(),
+                            path: package.path().to_string(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The domain keeps relations in hash sets, so they are sorted for a stable output.
+pub(super) fn sorted<'a>(ids: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut ids: Vec<_> = ids.map(str::to_string).collect();
+    ids.sort();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::ScanResultExport;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        cvss::Cvss,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        package_type::PackageType,
+        scan_result::ScanResult,
+        scan_type::ScanType,
+        severity::Severity,
+    };
+
+    #[test]
+    fn it_exports_relations_by_reference() {
+        let created_at = chrono::DateTime::from_timestamp(0, 0).unwrap();
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:3.18".to_string(),
+            "sha256:image".to_string(),
+            Some("sha256:digest".to_string()),
+            OperatingSystem::new(Family::Linux, "alpine 3.18".to_string()),
+            1024,
+            Architecture::Amd64,
+            HashMap::new(),
+            created_at,
+            EvaluationResult::Failed,
+        );
+        let layer = scan_result.add_layer(
+            "sha256:layer".to_string(),
+            0,
+            Some(512),
+            "ADD rootfs /".to_string(),
+        );
+        let package = scan_result.add_package(
+            PackageType::Os,
+            "openssl".to_string(),
+            "3.0.0".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            layer,
+            false,
+            None,
+            None,
+        );
+        let vulnerability = scan_result.add_vulnerability(
+            "CVE-2023-0001".to_string(),
+            Severity::High,
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            None,
+            false,
+            Some("3.0.1".to_string()),
+            Some(Cvss::new(7.5, String::new(), "3.1".to_string())),
+            None,
+        );
+        package.add_vulnerability_found(vulnerability);
+        let policy = scan_result.add_policy(
+            "p1".to_string(),
+            "No high vulns".to_string(),
+            created_at,
+            created_at,
+        );
+        scan_result
+            .add_policy_bundle("b1".to_string(), "Bundle".to_string(), policy)
+            .add_rule(
+                "r1".to_string(),
+                "High vulns".to_string(),
+                EvaluationResult::Failed,
+            )
+            .add_pkg_vuln_failure("upgrade openssl".to_string());
+
+        let exported = serde_json::to_value(ScanResultExport::from(&scan_result)).unwrap();
+
+        assert_eq!(exported["evaluationResult"], "failed");
+        assert_eq!(
+            exported["layers"],
+            json!([{"index": 0, "digest": "sha256:layer", "size": 512, "command": "ADD rootfs /"}])
+        );
+        assert_eq!(exported["packages"][0]["layerIndex"], 0);
+        assert_eq!(exported["packages"][0]["type"], "os");
+        assert_eq!(
+            exported["packages"][0]["vulnerabilities"],
+            json!(["CVE-2023-0001"])
+        );
+        assert_eq!(exported["vulnerabilities"][0]["severity"], "High");
+        assert_eq!(exported["vulnerabilities"][0]["fixVersion"], "3.0.1");
+        assert_eq!(exported["vulnerabilities"][0]["knownExploited"], false);
+        assert_eq!(
+            exported["vulnerabilities"][0]["cvss"],
+            json!({"score": 7.5, "vector": null, "version": "3.1"})
+        );
+        assert_eq!(
+            exported["policies"][0]["bundles"][0]["rules"][0]["failures"],
+            json!([{"type": "pkgVuln", "remediation": "upgrade openssl"}])
+        );
+        assert_eq!(exported["metadata"]["architecture"], "amd64");
+    }
+}
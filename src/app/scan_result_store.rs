@@ -0,0 +1,149 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tower_lsp::lsp_types::{Diagnostic, Range};
+
+use super::{
+    DocumentationAnchor, POLICY_DIAGNOSTIC_SOURCE, PolicyVerdict, StoredDocumentScanResult,
+    VULN_DIAGNOSTIC_SOURCE,
+};
+
+/// Image scan results of a document, as published to the client: the diagnostics
+/// and the hover reports rendered from the `ScanResult`, and the `ScanResult` itself.
+/// Persisted so they can be restored in a later session without re-scanning.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScanSnapshot {
+    /// Hash of the document text the scan was run against. Diagnostics anchor to
+    /// specific lines, so they are only restored if the document didn't change.
+    pub content_hash: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub documentations: Vec<SnapshotDocumentation>,
+    /// Last scan result of the document, for exports and reports. Absent in snapshots
+    /// persisted before scan results were.
+    #[serde(default)]
+    pub scan_result: Option<StoredDocumentScanResult>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotDocumentation {
+    pub range: Range,
+    pub content: String,
+    /// Absent in snapshots persisted before package hovers existed.
+    #[serde(default)]
+    pub anchors: Vec<DocumentationAnchor>,
+    #[serde(default)]
+    pub verdict: Option<PolicyVerdict>,
+    #[serde(default)]
+    pub scanned_at: Option<DateTime<Utc>>,
+}
+
+/// Sources the diagnostics of image scans had before they were namespaced, and the
+/// ones replacing them.
+const LEGACY_DIAGNOSTIC_SOURCES: [(&str, &str); 2] = [
+    ("sysdig-vuln", VULN_DIAGNOSTIC_SOURCE),
+    ("sysdig-policy", POLICY_DIAGNOSTIC_SOURCE),
+];
+
+impl ScanSnapshot {
+    pub fn content_hash_of(text: &str) -> String {
+        Sha256::digest(text.as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    pub fn matches_content(&self, text: &str) -> bool {
+        self.content_hash == Self::content_hash_of(text)
+    }
+
+    /// Renames the diagnostic sources of snapshots persisted with the legacy ones, so
+    /// their diagnostics are still restored.
+    pub fn with_current_sources(mut self) -> Self {
+        for diagnostic in &mut self.diagnostics {
+            let current = LEGACY_DIAGNOSTIC_SOURCES
+                .iter()
+                .find(|(legacy, _)| diagnostic.source.as_deref() == Some(legacy));
+            if let Some((_, current)) = current {
+                diagnostic.source = Some(current.to_string());
+            }
+        }
+        self
+    }
+}
+
+#[async_trait::async_trait]
+pub trait ScanResultStore {
+    async fn save(&self, uri: &str, snapshot: &ScanSnapshot) -> Result<(), ScanResultStoreError>;
+    async fn load(&self, uri: &str) -> Result<Option<ScanSnapshot>, ScanResultStoreError>;
+    /// Moves the snapshot stored for a renamed document, if any, to its new URI, or the
+    /// snapshots of the documents of a renamed folder to theirs (see `renamed_uri`).
+    async fn rename(&self, from: &str, to: &str) -> Result<(), ScanResultStoreError>;
+}
+
+#[derive(Error, Debug)]
+pub enum ScanResultStoreError {
+    #[error("unable to access the scan result store: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("unable to (de)serialize the stored scan result: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Diagnostic;
+
+    use super::ScanSnapshot;
+    use crate::app::{IAC_DIAGNOSTIC_SOURCE, POLICY_DIAGNOSTIC_SOURCE, VULN_DIAGNOSTIC_SOURCE};
+
+    #[test]
+    fn it_matches_only_the_scanned_content() {
+        let snapshot = ScanSnapshot {
+            content_hash: ScanSnapshot::content_hash_of("FROM alpine"),
+            diagnostics: vec![],
+            documentations: vec![],
+            scan_result: None,
+        };
+
+        assert!(snapshot.matches_content("FROM alpine"));
+        assert!(!snapshot.matches_content("FROM alpine:3.19"));
+        assert_eq!(snapshot.content_hash.len(), 64);
+    }
+
+    #[test]
+    fn it_renames_the_legacy_diagnostic_sources() {
+        let diagnostic = |source: &str| Diagnostic {
+            source: Some(source.to_string()),
+            ..Default::default()
+        };
+        let snapshot = ScanSnapshot {
+            content_hash: ScanSnapshot::content_hash_of("FROM alpine"),
+            diagnostics: vec![
+                diagnostic("sysdig-vuln"),
+                diagnostic("sysdig-policy"),
+                diagnostic(VULN_DIAGNOSTIC_SOURCE),
+                diagnostic(IAC_DIAGNOSTIC_SOURCE),
+            ],
+            documentations: vec![],
+            scan_result: None,
+        };
+
+        let sources: Vec<_> = snapshot
+            .with_current_sources()
+            .diagnostics
+            .into_iter()
+            .filter_map(|diagnostic| diagnostic.source)
+            .collect();
+
+        assert_eq!(
+            sources,
+            [
+                VULN_DIAGNOSTIC_SOURCE,
+                POLICY_DIAGNOSTIC_SOURCE,
+                VULN_DIAGNOSTIC_SOURCE,
+                IAC_DIAGNOSTIC_SOURCE
+            ]
+        );
+    }
+}
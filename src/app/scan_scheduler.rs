@@ -0,0 +1,506 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use tokio::sync::{Mutex, OnceCell, Semaphore, broadcast, watch};
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::domain::scanresult::scan_result::ScanResult;
+
+use super::protocol::{
+    CacheCounts, ScanCounts, ScanPhase, ScanProgressParams, ScanQueueParams, SeverityCounts,
+};
+use super::{ImageScanError, ImageScanner};
+
+/// Phase changes kept for a slow subscriber before the oldest ones are dropped.
+const PROGRESS_CAPACITY: usize = 64;
+
+/// Bounds the image scans running at the same time across every document and tenant,
+/// and tracks the images being scanned or waiting for a slot, and their progress.
+#[derive(Clone)]
+pub struct ScanScheduler {
+    permits: Arc<Semaphore>,
+    queue: Arc<watch::Sender<ScanQueueParams>>,
+    progress: broadcast::Sender<ScanProgressParams>,
+    statistics: Arc<ScanStatistics>,
+}
+
+impl ScanScheduler {
+    /// At least one scan runs at a time, even if `max_concurrent_scans` is 0.
+    pub fn new(max_concurrent_scans: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_concurrent_scans.max(1))),
+            queue: Arc::new(watch::Sender::new(ScanQueueParams::default())),
+            progress: broadcast::channel(PROGRESS_CAPACITY).0,
+            statistics: Default::default(),
+        }
+    }
+
+    /// Counters of the scans run through the scheduler, shared with the caches and the
+    /// scanners underneath so they record their part.
+    pub fn statistics(&self) -> Arc<ScanStatistics> {
+        self.statistics.clone()
+    }
+
+    /// Scans finished so far, and the ones running or waiting for a slot.
+    pub fn scan_counts(&self) -> ScanCounts {
+        let queue = self.queue.borrow();
+        ScanCounts {
+            succeeded: self.statistics.succeeded.load(Ordering::Relaxed),
+            failed: self.statistics.failed.load(Ordering::Relaxed),
+            running: queue.running.len() as u64,
+            queued: queue.queued.len() as u64,
+        }
+    }
+
+    /// Receives the state of the queue every time a scan is queued, starts or finishes.
+    pub fn subscribe(&self) -> watch::Receiver<ScanQueueParams> {
+        self.queue.subscribe()
+    }
+
+    /// Receives the phases running scans move on to, as reported with `report_progress`.
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<ScanProgressParams> {
+        self.progress.subscribe()
+    }
+
+    /// Tells the subscribers the scan of `image` moved on to `phase`. Dropped if nobody
+    /// listens, e.g. before the server is configured.
+    pub fn report_progress(&self, image: &str, phase: ScanPhase, message: &str) {
+        let _ = self.progress.send(ScanProgressParams {
+            pull_string: image.to_string(),
+            phase,
+            message: message.to_string(),
+        });
+    }
+
+    /// Runs `scan` once a slot is available, keeping `image` in the queue state meanwhile.
+    async fn run<F: Future>(&self, image: &str, scan: F) -> F::Output {
+        let mut entry = QueueEntry::queued(self.queue.clone(), image);
+        // The semaphore is never closed, so acquiring it doesn't fail.
+        let _permit = self.permits.acquire().await.ok();
+        entry.start();
+        scan.await
+    }
+}
+
+/// Counters of the scans of a `ScanScheduler`, answered by `sysdig/status`.
+#[derive(Default)]
+pub struct ScanStatistics {
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    scanner_version: std::sync::Mutex<Option<String>>,
+}
+
+impl ScanStatistics {
+    pub fn record_scan(&self, succeeded: bool) {
+        let counter = match succeeded {
+            true => &self.succeeded,
+            false => &self.failed,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records whether a scan was answered from the cache.
+    pub fn record_cache_lookup(&self, hit: bool) {
+        let counter = match hit {
+            true => &self.cache_hits,
+            false => &self.cache_misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_scanner_version(&self, version: String) {
+        if let Ok(mut scanner_version) = self.scanner_version.lock() {
+            *scanner_version = Some(version);
+        }
+    }
+
+    /// Version of the CLI scanner that ran the last scan, if any did.
+    pub fn scanner_version(&self) -> Option<String> {
+        self.scanner_version.lock().ok()?.clone()
+    }
+
+    pub fn cache_counts(&self, ttl_seconds: u64) -> CacheCounts {
+        CacheCounts {
+            hits: self.cache_hits.load(Ordering::Relaxed),
+            misses: self.cache_misses.load(Ordering::Relaxed),
+            ttl_seconds,
+        }
+    }
+
+    /// Adds the scans counted by `previous`, so the counts cover the whole session when
+    /// the components are created again for new settings. The cache starts over, as
+    /// does its count.
+    pub fn carry_over(&self, previous: &ScanStatistics) {
+        for (counter, previous) in [
+            (&self.succeeded, &previous.succeeded),
+            (&self.failed, &previous.failed),
+        ] {
+            counter.fetch_add(previous.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Image of the queue, removed from it when its scan finishes or is cancelled.
+struct QueueEntry {
+    queue: Arc<watch::Sender<ScanQueueParams>>,
+    image: String,
+    running: bool,
+}
+
+impl QueueEntry {
+    fn queued(queue: Arc<watch::Sender<ScanQueueParams>>, image: &str) -> Self {
+        queue.send_modify(|state| state.queued.push(image.to_string()));
+        Self {
+            queue,
+            image: image.to_string(),
+            running: false,
+        }
+    }
+
+    fn start(&mut self) {
+        self.queue.send_modify(|state| {
+            remove_first(&mut state.queued, &self.image);
+            state.running.push(self.image.clone());
+        });
+        self.running = true;
+    }
+}
+
+impl Drop for QueueEntry {
+    fn drop(&mut self) {
+        self.queue.send_modify(|state| {
+            let images = match self.running {
+                true => &mut state.running,
+                false => &mut state.queued,
+            };
+            remove_first(images, &self.image);
+        });
+    }
+}
+
+fn remove_first(images: &mut Vec<String>, image: &str) {
+    if let Some(index) = images.iter().position(|i| i == image) {
+        images.remove(index);
+    }
+}
+
+/// Outcome of a scan, shared by every request for the same pull string made while it
+/// was running.
+type SharedScan = Arc<OnceCell<Result<ScanResult, ScanFailure>>>;
+
+/// Runs the scans of a tenant through the `ScanScheduler`, so requesting the scan of
+/// an image already being scanned (e.g. clicking a code lens twice) awaits the running
+/// scan instead of starting a new one.
+///
+/// Requests are deduplicated by pull string and platform; the `CachingImageScanner` underneath
+/// also deduplicates pull strings resolving to the same digest.
+pub struct ScheduledImageScanner {
+    scanner: Box<dyn ImageScanner + Send + Sync>,
+    scheduler: ScanScheduler,
+    in_flight: Mutex<HashMap<(String, Option<String>), SharedScan>>,
+}
+
+impl ScheduledImageScanner {
+    pub fn new(scanner: Box<dyn ImageScanner + Send + Sync>, scheduler: ScanScheduler) -> Self {
+        Self {
+            scanner,
+            scheduler,
+            in_flight: Default::default(),
+        }
+    }
+
+    /// Runs the scan through the scheduler, logging how it went.
+    async fn run(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+        rescan: bool,
+    ) -> Result<ScanResult, ScanFailure> {
+        self.scheduler
+            .run(
+                image_pull_string,
+                async {
+                    let started = Instant::now();
+                    let result = match rescan {
+                        true => {
+                            self.scanner
+                                .rescan_image_for_platform(image_pull_string, platform)
+                                .await
+                        }
+                        false => {
+                            self.scanner
+                                .scan_image_for_platform(image_pull_string, platform)
+                                .await
+                        }
+                    };
+                    let duration_ms = started.elapsed().as_millis() as u64;
+                    self.scheduler.statistics.record_scan(result.is_ok());
+                    match &result {
+                        Ok(scan_result) => {
+                            let counts = SeverityCounts::from(scan_result);
+                            info!(
+                                duration_ms,
+                                critical = counts.critical,
+                                high = counts.high,
+                                medium = counts.medium,
+                                low = counts.low,
+                                negligible = counts.negligible,
+                                "image scanned"
+                            );
+                        }
+                        Err(e) => warn!(duration_ms, "image scan failed: {e}"),
+                    }
+                    result.map_err(|e| ScanFailure::from(&e))
+                }
+                .instrument(info_span!("scan", image = %image_pull_string)),
+            )
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl ImageScanner for ScheduledImageScanner {
+    async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+        self.scan_image_for_platform(image_pull_string, None).await
+    }
+
+    async fn scan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        let key = (image_pull_string.to_string(), platform.map(str::to_string));
+        let scan = self
+            .in_flight
+            .lock()
+            .await
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let result = scan
+            .get_or_init(|| self.run(image_pull_string, platform, false))
+            .await
+            .clone();
+
+        // Later requests scan again: caching results is up to the scanner underneath.
+        let mut in_flight = self.in_flight.lock().await;
+        if in_flight
+            .get(&key)
+            .is_some_and(|running| Arc::ptr_eq(running, &scan))
+        {
+            in_flight.remove(&key);
+        }
+
+        result.map_err(ImageScanError::from)
+    }
+
+    /// Rescans aren't deduplicated with the running scans, whose results may be cached.
+    async fn rescan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        self.run(image_pull_string, platform, true)
+            .await
+            .map_err(ImageScanError::from)
+    }
+}
+
+/// `ImageScanError` of a shared scan, which can be handed to every request awaiting it.
+#[derive(Debug, Clone)]
+enum ScanFailure {
+    AuthFailed(String),
+    RateLimited(String),
+    ImageNotFound(String),
+    NetworkError(String),
+    MalformedOutput(String),
+    InternalScannerError(String),
+}
+
+impl From<&ImageScanError> for ScanFailure {
+    fn from(error: &ImageScanError) -> Self {
+        match error {
+            ImageScanError::AuthFailed(e) => ScanFailure::AuthFailed(e.clone()),
+            ImageScanError::RateLimited(e) => ScanFailure::RateLimited(e.clone()),
+            ImageScanError::ImageNotFound(e) => ScanFailure::ImageNotFound(e.clone()),
+            ImageScanError::NetworkError(e) => ScanFailure::NetworkError(e.clone()),
+            ImageScanError::MalformedOutput(e) => ScanFailure::MalformedOutput(e.clone()),
+            ImageScanError::InternalScannerError(e) => {
+                ScanFailure::InternalScannerError(e.to_string())
+            }
+        }
+    }
+}
+
+impl From<ScanFailure> for ImageScanError {
+    fn from(failure: ScanFailure) -> Self {
+        match failure {
+            ScanFailure::AuthFailed(e) => ImageScanError::AuthFailed(e),
+            ScanFailure::RateLimited(e) => ImageScanError::RateLimited(e),
+            ScanFailure::ImageNotFound(e) => ImageScanError::ImageNotFound(e),
+            ScanFailure::NetworkError(e) => ImageScanError::NetworkError(e),
+            ScanFailure::MalformedOutput(e) => ImageScanError::MalformedOutput(e),
+            ScanFailure::InternalScannerError(e) => ImageScanError::InternalScannerError(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        scan_type::ScanType,
+    };
+
+    /// Scanner taking a while per scan, counting the scans and the highest number of
+    /// them running at once.
+    #[derive(Default, Clone)]
+    struct SlowScanner {
+        scans: Arc<AtomicUsize>,
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl ImageScanner for SlowScanner {
+        async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+            self.scans.fetch_add(1, Ordering::SeqCst);
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            if image_pull_string.starts_with("missing") {
+                return Err(ImageScanError::ImageNotFound(image_pull_string.to_string()));
+            }
+            Ok(ScanResult::new(
+                ScanType::Docker,
+                image_pull_string.to_string(),
+                "sha256:image-id".to_string(),
+                None,
+                OperatingSystem::new(Family::Linux, "alpine:3.19".to_string()),
+                1024,
+                Architecture::Amd64,
+                HashMap::new(),
+                chrono::Utc::now(),
+                EvaluationResult::Passed,
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn it_deduplicates_scans_of_the_same_image_in_flight() {
+        let scanner = SlowScanner::default();
+        let scans = scanner.scans.clone();
+        let scheduled = ScheduledImageScanner::new(Box::new(scanner), ScanScheduler::new(4));
+
+        let (first, second, failed, failed_again) = tokio::join!(
+            scheduled.scan_image("alpine:3.19"),
+            scheduled.scan_image("alpine:3.19"),
+            scheduled.scan_image("missing:1"),
+            scheduled.scan_image("missing:1"),
+        );
+        first.unwrap();
+        second.unwrap();
+        assert!(matches!(failed, Err(ImageScanError::ImageNotFound(_))));
+        assert!(matches!(
+            failed_again,
+            Err(ImageScanError::ImageNotFound(_))
+        ));
+        assert_eq!(scans.load(Ordering::SeqCst), 2);
+
+        scheduled.scan_image("alpine:3.19").await.unwrap();
+        assert_eq!(scans.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn it_counts_the_scans_across_configuration_changes() {
+        let scheduler = ScanScheduler::new(1);
+        let scheduled = ScheduledImageScanner::new(Box::new(SlowScanner::default()), scheduler);
+        scheduled.scan_image("alpine:3.19").await.unwrap();
+        scheduled.scan_image("missing:1").await.unwrap_err();
+
+        let reconfigured = ScanScheduler::new(1);
+        reconfigured
+            .statistics()
+            .carry_over(&scheduled.scheduler.statistics());
+        let scheduled = ScheduledImageScanner::new(Box::new(SlowScanner::default()), reconfigured);
+        scheduled.scan_image("alpine:3.19").await.unwrap();
+
+        assert_eq!(
+            scheduled.scheduler.scan_counts(),
+            ScanCounts {
+                succeeded: 2,
+                failed: 1,
+                running: 0,
+                queued: 0,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn it_hands_the_reported_progress_to_every_subscriber() {
+        let scheduler = ScanScheduler::new(1);
+        // Reported before subscribing, so nobody gets it.
+        scheduler.report_progress("alpine:3.19", ScanPhase::UpdatingDatabase, "");
+        let mut first = scheduler.subscribe_progress();
+        let mut second = scheduler.clone().subscribe_progress();
+
+        scheduler.report_progress("alpine:3.19", ScanPhase::Pulling, "Retrieving image...");
+
+        let expected = ScanProgressParams {
+            pull_string: "alpine:3.19".to_string(),
+            phase: ScanPhase::Pulling,
+            message: "Retrieving image...".to_string(),
+        };
+        assert_eq!(first.recv().await.unwrap(), expected);
+        assert_eq!(second.recv().await.unwrap(), expected);
+        assert!(first.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn it_bounds_the_scans_running_at_once_across_scanners() {
+        let scheduler = ScanScheduler::new(2);
+        let scanner = SlowScanner::default();
+        let max_running = scanner.max_running.clone();
+        let first = ScheduledImageScanner::new(Box::new(scanner.clone()), scheduler.clone());
+        let second = ScheduledImageScanner::new(Box::new(scanner), scheduler.clone());
+
+        let (_, _, _, state) = tokio::join!(
+            first.scan_image("alpine:3.19"),
+            first.scan_image("nginx:1.27"),
+            second.scan_image("redis:7"),
+            async {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                scheduler.subscribe().borrow().clone()
+            }
+        );
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            state,
+            ScanQueueParams {
+                running: vec!["alpine:3.19".to_string(), "nginx:1.27".to_string()],
+                queued: vec!["redis:7".to_string()],
+            }
+        );
+        assert_eq!(*scheduler.subscribe().borrow(), ScanQueueParams::default());
+    }
+}
@@ -0,0 +1,71 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tower_lsp::lsp_types::TraceValue;
+
+use super::component_factory::{TraceConfig, TraceLevel};
+
+/// Verbosity of the server logs forwarded to the client, shared by the server and the
+/// tracing layer forwarding them. It's the `trace` the client initialized the server
+/// with, or later sent with `$/setTrace`, unless `sysdig.trace.server` overrides it.
+#[derive(Clone, Default)]
+pub struct ServerTrace {
+    state: Arc<Mutex<TraceState>>,
+}
+
+#[derive(Default)]
+struct TraceState {
+    client: TraceLevel,
+    configured: Option<TraceLevel>,
+    show_errors: bool,
+}
+
+impl ServerTrace {
+    pub fn set_client_trace(&self, trace: TraceValue) {
+        self.state().client = trace.into();
+    }
+
+    pub fn configure(&self, config: &TraceConfig) {
+        let mut state = self.state();
+        state.configured = config.server;
+        state.show_errors = config.show_errors;
+    }
+
+    pub fn level(&self) -> TraceLevel {
+        let state = self.state();
+        state.configured.unwrap_or(state.client)
+    }
+
+    /// Whether errors are also shown to the user with `window/showMessage`.
+    pub fn show_errors(&self) -> bool {
+        self.state().show_errors
+    }
+
+    fn state(&self) -> MutexGuard<'_, TraceState> {
+        // The state is always left consistent, so a poisoned lock can still be used.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_setting_overrides_the_trace_of_the_client() {
+        let trace = ServerTrace::default();
+        assert_eq!(trace.level(), TraceLevel::Off);
+
+        trace.set_client_trace(TraceValue::Verbose);
+        assert_eq!(trace.level(), TraceLevel::Verbose);
+
+        trace.configure(&TraceConfig {
+            server: Some(TraceLevel::Messages),
+            show_errors: true,
+        });
+        trace.set_client_trace(TraceValue::Off);
+        assert_eq!(trace.level(), TraceLevel::Messages);
+        assert!(trace.show_errors());
+    }
+}
@@ -0,0 +1,527 @@
+use std::{collections::HashMap, time::Duration};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::Range;
+
+use crate::domain::scanresult::{
+    accepted_risk_reason::AcceptedRiskReason,
+    architecture::Architecture,
+    cvss::Cvss,
+    evaluation_result::EvaluationResult,
+    exploit::Exploit,
+    operating_system::{Family, OperatingSystem},
+    package::SuggestedFixSource,
+    package_type::PackageType,
+    policy_bundle_rule_failure::PolicyBundleRuleFailure,
+    scan_result::ScanResult,
+    scan_type::ScanType,
+    severity::Severity,
+};
+
+use super::{DocumentScanResult, scan_result_export::sorted};
+
+/// `DocumentScanResult` as persisted in a `ScanSnapshot`, so exports and reports of
+/// restored documents work without re-scanning.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredDocumentScanResult {
+    pub scan_result: StoredScanResult,
+    pub image_range: Range,
+    pub layer_ranges: HashMap<usize, Range>,
+}
+
+impl From<&DocumentScanResult> for StoredDocumentScanResult {
+    fn from(document_scan_result: &DocumentScanResult) -> Self {
+        Self {
+            scan_result: StoredScanResult::from(&document_scan_result.scan_result),
+            image_range: document_scan_result.image_range,
+            layer_ranges: document_scan_result.layer_ranges.clone(),
+        }
+    }
+}
+
+impl From<StoredDocumentScanResult> for DocumentScanResult {
+    fn from(stored: StoredDocumentScanResult) -> Self {
+        Self {
+            scan_result: ScanResult::from(stored.scan_result),
+            image_range: stored.image_range,
+            layer_ranges: stored.layer_ranges,
+        }
+    }
+}
+
+/// Serializable form of a `ScanResult`. Unlike `ScanResultExport` it keeps everything
+/// needed to rebuild the result; relations are kept by reference (layer index, CVE id,
+/// accepted risk id, policy id).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredScanResult {
+    scan_type: ScanType,
+    metadata: StoredMetadata,
+    evaluation_result: EvaluationResult,
+    layers: Vec<StoredLayer>,
+    packages: Vec<StoredPackage>,
+    vulnerabilities: Vec<StoredVulnerability>,
+    policies: Vec<StoredPolicy>,
+    policy_bundles: Vec<StoredPolicyBundle>,
+    accepted_risks: Vec<StoredAcceptedRisk>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredMetadata {
+    pull_string: String,
+    requested_pull_string: Option<String>,
+    image_id: String,
+    digest: Option<String>,
+    os_family: Family,
+    os_name: String,
+    size_in_bytes: u64,
+    architecture: Architecture,
+    labels: HashMap<String, String>,
+    created_at: DateTime<Utc>,
+    result_url: Option<String>,
+    parse_warnings: Vec<String>,
+    scanned_at: Option<DateTime<Utc>>,
+    scan_duration: Option<Duration>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredLayer {
+    index: usize,
+    digest: Option<String>,
+    size: Option<u64>,
+    command: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredPackage {
+    package_type: PackageType,
+    name: String,
+    version: String,
+    path: String,
+    layer_index: usize,
+    is_running: bool,
+    license: Option<String>,
+    /// Fix version suggested by the scanner; computed ones are computed again.
+    suggested_fix: Option<String>,
+    vulnerabilities: Vec<String>,
+    accepted_risks: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredVulnerability {
+    cve: String,
+    severity: Severity,
+    disclosure_date: NaiveDate,
+    solution_date: Option<NaiveDate>,
+    exploitable: bool,
+    fix_version: Option<String>,
+    cvss: Option<Cvss>,
+    exploit: Option<Exploit>,
+    accepted_risks: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredPolicy {
+    id: String,
+    name: String,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredPolicyBundle {
+    id: String,
+    name: String,
+    policies: Vec<String>,
+    rules: Vec<StoredPolicyRule>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredPolicyRule {
+    id: String,
+    description: String,
+    evaluation_result: EvaluationResult,
+    failures: Vec<StoredPolicyRuleFailure>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum StoredPolicyRuleFailure {
+    ImageConfig { description: String },
+    PkgVuln { remediation: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct StoredAcceptedRisk {
+    id: String,
+    reason: AcceptedRiskReason,
+    description: String,
+    expiration_date: Option<NaiveDate>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl From<&ScanResult> for StoredScanResult {
+    fn from(scan_result: &ScanResult) -> Self {
+        let metadata = scan_result.metadata();
+
+        Self {
+            scan_type: *scan_result.scan_type(),
+            metadata: StoredMetadata {
+                pull_string: metadata.pull_string().to_string(),
+                requested_pull_string: metadata.requested_pull_string().map(str::to_string),
+                image_id: metadata.image_id().to_string(),
+                digest: metadata.digest().map(str::to_string),
+                os_family: metadata.base_os().family(),
+                os_name: metadata.base_os().name().to_string(),
+                size_in_bytes: *metadata.size_in_bytes(),
+                architecture: *metadata.architecture(),
+                labels: metadata.labels().clone(),
+                created_at: metadata.created_at(),
+                result_url: metadata.result_url().map(str::to_string),
+                parse_warnings: metadata.parse_warnings().to_vec(),
+                scanned_at: metadata.scanned_at(),
+                scan_duration: metadata.scan_duration(),
+            },
+            evaluation_result: scan_result.evaluation_result(),
+            layers: scan_result
+                .layers()
+                .iter()
+                .map(|layer| StoredLayer {
+                    index: layer.index(),
+                    digest: layer.digest().map(str::to_string),
+                    size: layer.size().copied(),
+                    command: layer.command().to_string(),
+                })
+                .collect(),
+            packages: scan_result
+                .packages()
+                .iter()
+                .map(|package| StoredPackage {
+                    package_type: *package.package_type(),
+                    name: package.name().to_string(),
+                    version: package.version().to_string(),
+                    path: package.path().to_string(),
+                    layer_index: package.found_in_layer().index(),
+                    is_running: package.is_running(),
+                    license: package.license().map(str::to_string),
+                    suggested_fix: package
+                        .suggested_fix()
+                        .filter(|fix| fix.source == SuggestedFixSource::Scanner)
+                        .map(|fix| fix.version),
+                    vulnerabilities: sorted(package.vulnerabilities().iter().map(|v| v.cve())),
+                    accepted_risks: sorted(package.accepted_risks().iter().map(|r| r.id())),
+                })
+                .collect(),
+            vulnerabilities: scan_result
+                .vulnerabilities()
+                .iter()
+                .map(|vuln| StoredVulnerability {
+                    cve: vuln.cve().to_string(),
+                    severity: vuln.severity(),
+                    disclosure_date: vuln.disclosure_date(),
+                    solution_date: vuln.solution_date(),
+                    exploitable: vuln.exploitable(),
+                    fix_version: vuln.fix_version().cloned(),
+                    cvss: vuln.cvss().cloned(),
+                    exploit: vuln.exploit().cloned(),
+                    accepted_risks: sorted(vuln.accepted_risks().iter().map(|r| r.id())),
+                })
+                .collect(),
+            policies: scan_result
+                .policies()
+                .iter()
+                .map(|policy| StoredPolicy {
+                    id: policy.id().to_string(),
+                    name: policy.name().to_string(),
+                    created_at: policy.created_at(),
+                    updated_at: policy.updated_at(),
+                })
+                .collect(),
+            policy_bundles: scan_result
+                .policy_bundles()
+                .iter()
+                .map(|bundle| StoredPolicyBundle {
+                    id: bundle.id().to_string(),
+                    name: bundle.name().to_string(),
+                    policies: sorted(bundle.found_in_policies().iter().map(|p| p.id())),
+                    rules: bundle
+                        .rules()
+                        .iter()
+                        .map(|rule| StoredPolicyRule {
+                            id: rule.id().to_string(),
+                            description: rule.description().to_string(),
+                            evaluation_result: *rule.evaluation_result(),
+                            failures: rule
+                                .failures()
+                                .iter()
+                                .map(|failure| match failure {
+                                    PolicyBundleRuleFailure::ImageConfig(f) => {
+                                        StoredPolicyRuleFailure::ImageConfig {
+                                            description: f.description().to_string(),
+                                        }
+                                    }
+                                    PolicyBundleRuleFailure::PkgVuln(f) => {
+                                        StoredPolicyRuleFailure::PkgVuln {
+                                            remediation: f.remediation().to_string(),
+                                        }
+                                    }
+                                })
+                                .collect(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            accepted_risks: scan_result
+                .accepted_risks()
+                .iter()
+                .map(|risk| StoredAcceptedRisk {
+                    id: risk.id().to_string(),
+                    reason: *risk.reason(),
+                    description: risk.description().to_string(),
+                    expiration_date: risk.expiration_date(),
+                    is_active: risk.is_active(),
+                    created_at: risk.created_at(),
+                    updated_at: risk.updated_at(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<StoredScanResult> for ScanResult {
+    fn from(stored: StoredScanResult) -> Self {
+        let metadata = stored.metadata;
+        let mut scan_result = ScanResult::new(
+            stored.scan_type,
+            metadata.pull_string,
+            metadata.image_id,
+            metadata.digest,
+            OperatingSystem::new(metadata.os_family, metadata.os_name),
+            metadata.size_in_bytes,
+            metadata.architecture,
+            metadata.labels,
+            metadata.created_at,
+            stored.evaluation_result,
+        );
+        if let Some(requested_pull_string) = metadata.requested_pull_string {
+            scan_result.set_requested_pull_string(requested_pull_string);
+        }
+        if let Some(result_url) = metadata.result_url {
+            scan_result.set_result_url(result_url);
+        }
+        for warning in metadata.parse_warnings {
+            scan_result.add_parse_warning(warning);
+        }
+        if let Some(scanned_at) = metadata.scanned_at {
+            scan_result.set_scan_time(scanned_at, metadata.scan_duration);
+        }
+
+        for risk in stored.accepted_risks {
+            scan_result.add_accepted_risk(
+                risk.id,
+                risk.reason,
+                risk.description,
+                risk.expiration_date,
+                risk.is_active,
+                risk.created_at,
+                risk.updated_at,
+            );
+        }
+
+        for stored_vuln in stored.vulnerabilities {
+            let vuln = scan_result.add_vulnerability(
+                stored_vuln.cve,
+                stored_vuln.severity,
+                stored_vuln.disclosure_date,
+                stored_vuln.solution_date,
+                stored_vuln.exploitable,
+                stored_vuln.fix_version,
+                stored_vuln.cvss,
+                stored_vuln.exploit,
+            );
+            for risk in &stored_vuln.accepted_risks {
+                if let Some(risk) = scan_result.find_accepted_risk_by_id(risk) {
+                    vuln.add_accepted_risk(risk);
+                }
+            }
+        }
+
+        let layers: HashMap<_, _> = stored
+            .layers
+            .into_iter()
+            .map(|layer| {
+                let index = layer.index;
+                let layer = scan_result.add_layer(
+                    layer.digest.unwrap_or_default(),
+                    layer.index,
+                    layer.size,
+                    layer.command,
+                );
+                (index, layer)
+            })
+            .collect();
+
+        for stored_package in stored.packages {
+            let Some(layer) = layers.get(&stored_package.layer_index) else {
+                continue;
+            };
+            let package = scan_result.add_package(
+                stored_package.package_type,
+                stored_package.name,
+                stored_package.version,
+                stored_package.path,
+                layer.clone(),
+                stored_package.is_running,
+                stored_package.license,
+                stored_package.suggested_fix,
+            );
+            for cve in &stored_package.vulnerabilities {
+                if let Some(vuln) = scan_result.find_vulnerability_by_cve(cve) {
+                    package.add_vulnerability_found(vuln);
+                }
+            }
+            for risk in &stored_package.accepted_risks {
+                if let Some(risk) = scan_result.find_accepted_risk_by_id(risk) {
+                    package.add_accepted_risk(risk);
+                }
+            }
+        }
+
+        for policy in stored.policies {
+            scan_result.add_policy(policy.id, policy.name, policy.created_at, policy.updated_at);
+        }
+
+        for stored_bundle in stored.policy_bundles {
+            for policy in &stored_bundle.policies {
+                if let Some(policy) = scan_result.find_policy_by_id(policy) {
+                    scan_result.add_policy_bundle(
+                        stored_bundle.id.clone(),
+                        stored_bundle.name.clone(),
+                        policy,
+                    );
+                }
+            }
+            // Bundles only exist within policies.
+            let Some(bundle) = scan_result.find_policy_bundle_by_id(&stored_bundle.id) else {
+                continue;
+            };
+            for stored_rule in stored_bundle.rules {
+                let rule = bundle.add_rule(
+                    stored_rule.id,
+                    stored_rule.description,
+                    stored_rule.evaluation_result,
+                );
+                for failure in stored_rule.failures {
+                    match failure {
+                        StoredPolicyRuleFailure::ImageConfig { description } => {
+                            rule.add_image_config_failure(description);
+                        }
+                        StoredPolicyRuleFailure::PkgVuln { remediation } => {
+                            rule.add_pkg_vuln_failure(remediation);
+                        }
+                    }
+                }
+            }
+        }
+
+        scan_result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::app::ScanResultExport;
+    use crate::domain::scanresult::exploit::CisaKev;
+
+    #[test]
+    fn it_rebuilds_the_scan_result_it_stored() {
+        let created_at = DateTime::from_timestamp(0, 0).unwrap();
+        let date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "mirror.example.com/alpine:3.18".to_string(),
+            "sha256:image".to_string(),
+            Some("sha256:digest".to_string()),
+            OperatingSystem::new(Family::Linux, "alpine 3.18".to_string()),
+            1024,
+            Architecture::Arm64,
+            HashMap::from([("maintainer".to_string(), "acme".to_string())]),
+            created_at,
+            EvaluationResult::Failed,
+        );
+        scan_result.set_requested_pull_string("alpine:3.18".to_string());
+        scan_result.set_scan_time(created_at, Some(Duration::from_millis(1500)));
+        let layer = scan_result.add_layer(
+            "sha256:layer".to_string(),
+            0,
+            Some(512),
+            "ADD rootfs /".to_string(),
+        );
+        let package = scan_result.add_package(
+            PackageType::Os,
+            "openssl".to_string(),
+            "3.0.0".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            layer,
+            true,
+            Some("Apache-2.0".to_string()),
+            None,
+        );
+        let vulnerability = scan_result.add_vulnerability(
+            "CVE-2023-0001".to_string(),
+            Severity::High,
+            date,
+            None,
+            true,
+            Some("3.0.1".to_string()),
+            Some(Cvss::new(7.5, String::new(), "3.1".to_string())),
+            Some(Exploit::new(
+                None,
+                vec![],
+                Some(CisaKev::new(Some(date), None)),
+            )),
+        );
+        package.add_vulnerability_found(vulnerability.clone());
+        scan_result
+            .add_accepted_risk(
+                "risk1".to_string(),
+                AcceptedRiskReason::RiskMitigated,
+                "behind a proxy".to_string(),
+                None,
+                true,
+                created_at,
+                created_at,
+            )
+            .add_for_vulnerability(vulnerability);
+        let policy = scan_result.add_policy(
+            "p1".to_string(),
+            "No high vulns".to_string(),
+            created_at,
+            created_at,
+        );
+        scan_result
+            .add_policy_bundle("b1".to_string(), "Bundle".to_string(), policy)
+            .add_rule(
+                "r1".to_string(),
+                "High vulns".to_string(),
+                EvaluationResult::Failed,
+            )
+            .add_pkg_vuln_failure("upgrade openssl".to_string());
+
+        let stored = serde_json::to_string(&StoredScanResult::from(&scan_result)).unwrap();
+        let restored = ScanResult::from(serde_json::from_str::<StoredScanResult>(&stored).unwrap());
+
+        assert_eq!(
+            ScanResultExport::from(&restored),
+            ScanResultExport::from(&scan_result)
+        );
+        assert!(restored.vulnerabilities()[0].known_exploited());
+    }
+}
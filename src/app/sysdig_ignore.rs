@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use chrono::NaiveDate;
+use itertools::Itertools;
+
+use crate::domain::scanresult::{scan_result::ScanResult, vulnerability::Vulnerability};
+
+pub const SYSDIG_IGNORE_FILE: &str = ".sysdigignore";
+
+/// Starts the directive comments of Dockerfiles and Compose files, e.g.
+/// `# sysdig-lsp: ignore CVE-2023-1234 reason="accepted"`.
+pub(crate) const DIRECTIVE_PREFIX: &str = "sysdig-lsp:";
+
+/// Left in generated entries for the user to fill in; entries keeping it never expire.
+const EXPIRY_PLACEHOLDER: &str = "YYYY-MM-DD";
+
+const SCAFFOLD_HEADER: &str = "\
+# Vulnerabilities left out of the Sysdig LSP diagnostics, one CVE per line.
+# Set an expiry date to have a finding reported again once it's due for a fix,
+# entries keeping the YYYY-MM-DD placeholder never expire.
+";
+
+/// CVEs left out of the image scan diagnostics, e.g. the existing debt of a project
+/// frozen when adopting the scanner, so only new findings are reported.
+///
+/// One entry per line: `<CVE> [expires=<YYYY-MM-DD>]`, `#` starts a comment. Expired
+/// entries are reported again.
+///
+/// Documents can ignore CVEs too, with `# sysdig-lsp: ignore <CVE>... [reason="..."]`
+/// comments. Their entries never expire, the reason is only there for reviewers.
+///
+/// In focus mode (`sysdig.report.focus_in_use`), the vulnerabilities of packages not
+/// loaded at runtime are left out as well. With `sysdig.report.escalate_known_exploited`,
+/// the reported vulnerabilities in the CISA KEV catalog are escalated to errors.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SysdigIgnore {
+    entries: HashMap<String, Option<NaiveDate>>,
+    in_use_only: bool,
+    escalate_known_exploited: bool,
+}
+
+impl SysdigIgnore {
+    pub fn parse(content: &str) -> Self {
+        let entries = content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let cve = fields.next()?.to_string();
+                let expiry = fields
+                    .find_map(|field| field.strip_prefix("expires="))
+                    .filter(|date| *date != EXPIRY_PLACEHOLDER);
+                let expires = match expiry.map(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d")) {
+                    Some(Ok(date)) => Some(date),
+                    Some(Err(e)) => {
+                        // Ignoring it forever would be the unsafe way to fail.
+                        tracing::warn!("skipping {SYSDIG_IGNORE_FILE} entry '{line}': {e}");
+                        return None;
+                    }
+                    None => None,
+                };
+                Some((cve, expires))
+            })
+            .collect();
+
+        Self {
+            entries,
+            ..Self::default()
+        }
+    }
+
+    /// Adds the CVEs of the ignore directives among the comments of a document.
+    pub fn with_inline_directives<'a>(
+        mut self,
+        comments: impl IntoIterator<Item = &'a str>,
+    ) -> Self {
+        for cve in comments
+            .into_iter()
+            .filter_map(ignored_by_directive)
+            .flatten()
+        {
+            self.entries.insert(cve.to_string(), None);
+        }
+        self
+    }
+
+    pub fn focus_in_use(self, in_use_only: bool) -> Self {
+        Self {
+            in_use_only,
+            ..self
+        }
+    }
+
+    pub fn escalate_known_exploited(self, escalate_known_exploited: bool) -> Self {
+        Self {
+            escalate_known_exploited,
+            ..self
+        }
+    }
+
+    /// Whether the diagnostics reporting these vulnerabilities have to be errors, because
+    /// some are known to be exploited in the wild.
+    pub fn escalates(&self, vulnerabilities: &[Arc<Vulnerability>]) -> bool {
+        self.escalate_known_exploited && vulnerabilities.iter().any(|v| v.known_exploited())
+    }
+
+    /// Reads the ignore file at the workspace root, or next to the document if there is
+    /// no workspace. A missing or unreadable file ignores nothing.
+    pub async fn load(workspace_root: Option<&Path>, document: Option<&Path>) -> Self {
+        let Some(path) = Self::path_for(workspace_root, document) else {
+            return Self::default();
+        };
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                tracing::warn!("unable to read {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn path_for(workspace_root: Option<&Path>, document: Option<&Path>) -> Option<PathBuf> {
+        workspace_root
+            .or_else(|| document.and_then(Path::parent))
+            .map(|dir| dir.join(SYSDIG_IGNORE_FILE))
+    }
+
+    pub fn is_ignored(&self, cve: &str, today: NaiveDate) -> bool {
+        self.entries
+            .get(cve)
+            .is_some_and(|expires| expires.is_none_or(|expires| today <= expires))
+    }
+
+    /// The vulnerabilities of the scan that still have to be reported today. Focusing
+    /// on the ones in use requires runtime context, without it nothing is known to be dormant.
+    pub fn reported(
+        &self,
+        scan_result: &ScanResult,
+        vulnerabilities: Vec<Arc<Vulnerability>>,
+    ) -> Vec<Arc<Vulnerability>> {
+        let today = chrono::Utc::now().date_naive();
+        let in_use_only = self.in_use_only && scan_result.has_runtime_context();
+        vulnerabilities
+            .into_iter()
+            .filter(|vuln| !self.is_ignored(vuln.cve(), today))
+            .filter(|vuln| !in_use_only || vuln.in_use())
+            .collect()
+    }
+
+    /// Number of vulnerabilities of the scan left out of the diagnostics today.
+    pub fn suppressed(&self, scan_result: &ScanResult) -> usize {
+        let vulnerabilities = scan_result.vulnerabilities();
+        let total = vulnerabilities.len();
+        total - self.reported(scan_result, vulnerabilities).len()
+    }
+
+    /// Appends to the ignore file contents (`None` if it doesn't exist yet) an entry for
+    /// every vulnerability of the scan it doesn't cover yet, returning the new contents
+    /// and the number of entries added. Existing entries are kept as they are.
+    pub fn scaffold_file(existing: Option<&str>, scan_result: &ScanResult) -> (String, usize) {
+        let entries = existing
+            .map(Self::parse)
+            .unwrap_or_default()
+            .scaffold(scan_result);
+
+        let mut contents = existing.unwrap_or(SCAFFOLD_HEADER).to_string();
+        if !contents.is_empty() && !contents.ends_with('\n') {
+            contents.push('\n');
+        }
+        contents.extend(entries.iter().map(String::as_str));
+        (contents, entries.len())
+    }
+
+    /// Entries for the vulnerabilities of the scan not in this file (whether ignored or
+    /// expired) nor covered by an accepted risk, annotated with their packages and an
+    /// expiry placeholder.
+    fn scaffold(&self, scan_result: &ScanResult) -> Vec<String> {
+        scan_result
+            .vulnerabilities()
+            .into_iter()
+            .filter(|vuln| !self.entries.contains_key(vuln.cve()))
+            .filter(|vuln| vuln.accepted_risks().is_empty())
+            .sorted_by(|a, b| (a.severity(), a.cve()).cmp(&(b.severity(), b.cve())))
+            .map(|vuln| {
+                let packages = vuln
+                    .found_in_packages()
+                    .iter()
+                    .map(|p| format!("{} {}", p.name(), p.version()))
+                    .sorted()
+                    .dedup()
+                    .join(", ");
+                format!(
+                    "{} expires={EXPIRY_PLACEHOLDER} # {:?}: {packages}\n",
+                    vuln.cve(),
+                    vuln.severity()
+                )
+            })
+            .collect()
+    }
+}
+
+/// CVEs of a `sysdig-lsp: ignore <CVE>... [reason="..."]` comment, `None` for any
+/// other comment.
+fn ignored_by_directive(comment: &str) -> Option<impl Iterator<Item = &str>> {
+    let directive = comment
+        .trim_start_matches('#')
+        .trim()
+        .strip_prefix(DIRECTIVE_PREFIX)?
+        .trim_start();
+    let cves = directive
+        .strip_prefix("ignore")
+        .filter(|rest| rest.starts_with(char::is_whitespace))?;
+    let cves = cves.split_once("reason=").map_or(cves, |(cves, _)| cves);
+    Some(cves.split_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, Utc};
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        package_type::PackageType,
+        scan_type::ScanType,
+        severity::Severity,
+    };
+
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn it_ignores_entries_until_they_expire() {
+        let ignore = SysdigIgnore::parse(
+            "# frozen debt\n\
+             CVE-2023-0001 expires=YYYY-MM-DD # openssl 3.1.0\n\
+             CVE-2023-0002 expires=2024-06-30\n\
+             CVE-2023-0003\n\
+             CVE-2023-0004 expires=soon\n",
+        );
+
+        let today = date("2024-07-01");
+        assert!(ignore.is_ignored("CVE-2023-0001", today));
+        assert!(!ignore.is_ignored("CVE-2023-0002", today));
+        assert!(ignore.is_ignored("CVE-2023-0002", date("2024-06-30")));
+        assert!(ignore.is_ignored("CVE-2023-0003", today));
+        assert!(!ignore.is_ignored("CVE-2023-0004", today));
+        assert!(!ignore.is_ignored("CVE-2023-9999", today));
+    }
+
+    #[test]
+    fn it_ignores_the_cves_of_inline_directives() {
+        let ignore = SysdigIgnore::parse("CVE-2023-0001 expires=2024-06-30").with_inline_directives([
+            "sysdig-lsp: ignore CVE-2023-0001 CVE-2023-0002 reason=\"not reachable, CVE-2023-0003\"",
+            "# sysdig-lsp: ignore GHSA-xxxx-yyyy-zzzz",
+            "sysdig-lsp: ignoreCVE-2023-0004",
+            "ignore CVE-2023-0005",
+            "sysdig-lsp: ignore",
+        ]);
+
+        let today = date("2024-07-01");
+        assert!(ignore.is_ignored("CVE-2023-0001", today));
+        assert!(ignore.is_ignored("CVE-2023-0002", today));
+        assert!(!ignore.is_ignored("CVE-2023-0003", today));
+        assert!(ignore.is_ignored("GHSA-xxxx-yyyy-zzzz", today));
+        assert!(!ignore.is_ignored("CVE-2023-0004", today));
+        assert!(!ignore.is_ignored("CVE-2023-0005", today));
+        assert_eq!(ignore.entries.len(), 3);
+    }
+
+    #[test]
+    fn it_scaffolds_entries_for_the_findings_not_in_the_file() {
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:latest".to_string(),
+            "sha256:12345".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+            123456,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            EvaluationResult::Failed,
+        );
+        let layer = scan_result.add_layer("sha256:l1".to_string(), 0, None, String::new());
+        let openssl = scan_result.add_package(
+            PackageType::Os,
+            "openssl".to_string(),
+            "3.1.0".to_string(),
+            "/lib".to_string(),
+            layer,
+            false,
+            None,
+            None,
+        );
+        for (cve, severity) in [
+            ("CVE-2024-0002", Severity::Low),
+            ("CVE-2024-0001", Severity::Critical),
+            ("CVE-2024-0003", Severity::High),
+        ] {
+            let vuln = scan_result.add_vulnerability(
+                cve.to_string(),
+                severity,
+                date("2024-01-01"),
+                None,
+                false,
+                None,
+                None,
+                None,
+            );
+            openssl.add_vulnerability_found(vuln);
+        }
+
+        let (contents, added) =
+            SysdigIgnore::scaffold_file(Some("CVE-2024-0003 # reviewed"), &scan_result);
+
+        assert_eq!(added, 2);
+        assert_eq!(
+            contents,
+            "CVE-2024-0003 # reviewed\n\
+             CVE-2024-0001 expires=YYYY-MM-DD # Critical: openssl 3.1.0\n\
+             CVE-2024-0002 expires=YYYY-MM-DD # Low: openssl 3.1.0\n"
+        );
+
+        let (contents, added) = SysdigIgnore::scaffold_file(None, &scan_result);
+        assert_eq!(added, 3);
+        assert!(contents.starts_with("# Vulnerabilities left out"));
+        assert_eq!(SysdigIgnore::parse(&contents).entries.len(), 3);
+    }
+
+    #[test]
+    fn it_focuses_on_the_vulnerabilities_in_use_when_there_is_runtime_context() {
+        let scan_with_running = |running: bool| {
+            let mut scan_result = ScanResult::new(
+                ScanType::Docker,
+                "alpine:latest".to_string(),
+                "sha256:12345".to_string(),
+                None,
+                OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+                123456,
+                Architecture::Amd64,
+                HashMap::new(),
+                Utc::now(),
+                EvaluationResult::Failed,
+            );
+            let layer = scan_result.add_layer("sha256:l1".to_string(), 0, None, String::new());
+            for (name, cve, is_running) in [
+                ("openssl", "CVE-2024-0001", running),
+                ("curl", "CVE-2024-0002", false),
+            ] {
+                let package = scan_result.add_package(
+                    PackageType::Os,
+                    name.to_string(),
+                    "1.0".to_string(),
+                    "/lib".to_string(),
+                    layer.clone(),
+                    is_running,
+                    None,
+                    None,
+                );
+                package.add_vulnerability_found(scan_result.add_vulnerability(
+                    cve.to_string(),
+                    Severity::High,
+                    date("2024-01-01"),
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                ));
+            }
+            scan_result
+        };
+        let reported_cves = |ignore: &SysdigIgnore, scan_result: &ScanResult| {
+            ignore
+                .reported(scan_result, scan_result.vulnerabilities())
+                .iter()
+                .map(|v| v.cve().to_string())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+        let focused = SysdigIgnore::default().focus_in_use(true);
+
+        assert_eq!(
+            reported_cves(&focused, &scan_with_running(true)),
+            ["CVE-2024-0001"]
+        );
+        assert_eq!(
+            reported_cves(&focused, &scan_with_running(false)),
+            ["CVE-2024-0001", "CVE-2024-0002"]
+        );
+        assert_eq!(
+            reported_cves(&SysdigIgnore::default(), &scan_with_running(true)),
+            ["CVE-2024-0001", "CVE-2024-0002"]
+        );
+        assert_eq!(focused.suppressed(&scan_with_running(true)), 1);
+        assert_eq!(
+            SysdigIgnore::default().suppressed(&scan_with_running(true)),
+            0
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use tower_lsp::lsp_types::Url;
+
+use super::{IacScanner, ImageScanner};
+
+/// Scanners authenticated against a different Sysdig tenant, used for the documents
+/// whose path matches any of the scope globs (`sysdig.scopes`).
+pub struct TenantScope {
+    paths: GlobSet,
+    pub scanner: Box<dyn ImageScanner + Send + Sync>,
+    pub iac_scanner: Box<dyn IacScanner + Send + Sync>,
+}
+
+impl TenantScope {
+    /// Relative globs (e.g. `services/payments/**`) match at any depth of the document
+    /// path, so they don't depend on where the workspace is checked out.
+    pub fn new(
+        paths: &[String],
+        scanner: Box<dyn ImageScanner + Send + Sync>,
+        iac_scanner: Box<dyn IacScanner + Send + Sync>,
+    ) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for path in paths {
+            let pattern = if path.starts_with('/') || path.starts_with("**") {
+                path.clone()
+            } else {
+                format!("**/{path}")
+            };
+            builder.add(glob(&pattern)?);
+        }
+
+        Ok(Self {
+            paths: builder.build()?,
+            scanner,
+            iac_scanner,
+        })
+    }
+
+    pub fn contains(&self, uri: &Url) -> bool {
+        uri.to_file_path()
+            .is_ok_and(|path| self.paths.is_match(path))
+    }
+}
+
+/// `*` stops at path separators, only `**` crosses directories.
+fn glob(pattern: &str) -> Result<Glob, globset::Error> {
+    GlobBuilder::new(pattern).literal_separator(true).build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{IacScanError, IacScanScope, ImageScanError};
+    use crate::domain::iacscanresult::iac_scan_result::IacScanResult;
+    use crate::domain::scanresult::scan_result::ScanResult;
+
+    struct NoopScanner;
+
+    #[async_trait::async_trait]
+    impl ImageScanner for NoopScanner {
+        async fn scan_image(&self, _: &str) -> Result<ScanResult, ImageScanError> {
+            unimplemented!()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl IacScanner for NoopScanner {
+        async fn scan_iac(&self, _: &IacScanScope) -> Result<IacScanResult, IacScanError> {
+            unimplemented!()
+        }
+    }
+
+    fn scope(paths: &[&str]) -> TenantScope {
+        let paths: Vec<_> = paths.iter().map(|p| p.to_string()).collect();
+        TenantScope::new(&paths, Box::new(NoopScanner), Box::new(NoopScanner)).unwrap()
+    }
+
+    fn uri(path: &str) -> Url {
+        Url::from_file_path(path).unwrap()
+    }
+
+    #[test]
+    fn relative_globs_match_at_any_depth() {
+        let scope = scope(&["services/payments/**"]);
+
+        assert!(scope.contains(&uri("/repo/services/payments/Dockerfile")));
+        assert!(scope.contains(&uri("/repo/services/payments/api/Dockerfile")));
+        assert!(!scope.contains(&uri("/repo/services/billing/Dockerfile")));
+    }
+
+    #[test]
+    fn absolute_globs_and_single_stars_are_anchored() {
+        let scope = scope(&["/repo/teams/*/Dockerfile"]);
+
+        assert!(scope.contains(&uri("/repo/teams/a/Dockerfile")));
+        assert!(!scope.contains(&uri("/repo/teams/a/b/Dockerfile")));
+        assert!(!scope.contains(&uri("/other/repo/teams/a/Dockerfile")));
+    }
+
+    #[test]
+    fn invalid_globs_are_rejected() {
+        let result = TenantScope::new(
+            &["services/[payments".to_string()],
+            Box::new(NoopScanner),
+            Box::new(NoopScanner),
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// A source of the Sysdig API token other than the settings (an environment variable,
+/// the OS keychain...). Tokens are never logged, only where they were read from.
+pub trait TokenProvider {
+    /// Describes the source, e.g. `environment variable SECURE_API_TOKEN`.
+    fn source(&self) -> String;
+
+    /// The token, or `None` if the source doesn't have one.
+    fn token(&self) -> Result<Option<String>, TokenProviderError>;
+}
+
+#[derive(Error, Debug)]
+#[error("unable to read the API token from the {provider}: {reason}")]
+pub struct TokenProviderError {
+    pub provider: String,
+    pub reason: String,
+}
+
+/// Token of the first provider having one, falling back to the next provider when a
+/// source is empty or fails.
+pub fn first_token(providers: &[Box<dyn TokenProvider + Send + Sync>]) -> Option<String> {
+    providers
+        .iter()
+        .find_map(|provider| match provider.token() {
+            Ok(Some(token)) if !token.trim().is_empty() => {
+                debug!("using the API token of the {}", provider.source());
+                Some(token.trim().to_string())
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("{e}");
+                None
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedToken(Result<Option<&'static str>, &'static str>);
+
+    impl TokenProvider for FixedToken {
+        fn source(&self) -> String {
+            "fixed token".to_string()
+        }
+
+        fn token(&self) -> Result<Option<String>, TokenProviderError> {
+            self.0
+                .map(|token| token.map(str::to_string))
+                .map_err(|reason| TokenProviderError {
+                    provider: self.source(),
+                    reason: reason.to_string(),
+                })
+        }
+    }
+
+    #[test]
+    fn it_falls_back_to_the_next_provider() {
+        let providers: Vec<Box<dyn TokenProvider + Send + Sync>> = vec![
+            Box::new(FixedToken(Err("locked keychain"))),
+            Box::new(FixedToken(Ok(None))),
+            Box::new(FixedToken(Ok(Some("  ")))),
+            Box::new(FixedToken(Ok(Some("token\n")))),
+            Box::new(FixedToken(Ok(Some("ignored")))),
+        ];
+
+        assert_eq!(first_token(&providers).as_deref(), Some("token"));
+        assert_eq!(first_token(&providers[..3]), None);
+    }
+}
@@ -0,0 +1,173 @@
+/// Image reference with what Docker implies made explicit: the `docker.io` registry,
+/// the `library/` namespace of official images and the `latest` tag, e.g.
+/// `docker.io/library/alpine:latest` for `alpine`.
+pub fn normalize_image_reference(pull_string: &str) -> String {
+    let (name, reference) = match pull_string.split_once('@') {
+        Some((name, digest)) => (name, format!("@{digest}")),
+        None => match pull_string.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, format!(":{tag}")),
+            _ => (pull_string, ":latest".to_string()),
+        },
+    };
+    let name = match name.split_once('/') {
+        Some((registry, _)) if registry.contains(['.', ':']) || registry == "localhost" => {
+            name.to_string()
+        }
+        Some(_) => format!("docker.io/{name}"),
+        None => format!("docker.io/library/{name}"),
+    };
+    format!("{name}{reference}")
+}
+
+/// Registry an image is pulled from, e.g. `docker.io` for `alpine` or `ghcr.io` for
+/// `ghcr.io/acme/api:1.2`.
+pub fn image_registry(pull_string: &str) -> String {
+    let normalized = normalize_image_reference(pull_string);
+    match normalized.split_once('/') {
+        Some((registry, _)) => registry.to_string(),
+        None => normalized,
+    }
+}
+
+/// Whether `platform` is the platform of an image variant, `os/arch` or
+/// `os/arch/variant`, e.g. `linux/arm64` or `linux/arm/v7`.
+pub fn is_image_platform(platform: &str) -> bool {
+    let parts: Vec<_> = platform.split('/').collect();
+    (2..=3).contains(&parts.len())
+        && parts.iter().all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        })
+}
+
+/// Mirrors images are pulled from instead of their own registry, by prefix of the
+/// normalized reference, e.g. `docker.io` to `mirror.example.com/dockerhub` or
+/// `ghcr.io/acme` to `registry.example.com/acme`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegistryMirrors {
+    /// Prefix and mirror, the longest prefixes first so the most specific one wins.
+    mirrors: Vec<(String, String)>,
+}
+
+impl RegistryMirrors {
+    pub fn new(mirrors: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut mirrors: Vec<_> = mirrors
+            .into_iter()
+            .map(|(prefix, mirror)| {
+                (
+                    prefix.trim_end_matches('/').to_string(),
+                    mirror.trim_end_matches('/').to_string(),
+                )
+            })
+            .filter(|(prefix, mirror)| !prefix.is_empty() && !mirror.is_empty())
+            .collect();
+        mirrors.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        Self { mirrors }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mirrors.is_empty()
+    }
+
+    /// Reference to pull instead of `pull_string` when a mirror covers it: the normalized
+    /// reference with the prefix of the mirror replaced.
+    pub fn resolve(&self, pull_string: &str) -> Option<String> {
+        let normalized = normalize_image_reference(pull_string);
+        self.mirrors.iter().find_map(|(prefix, mirror)| {
+            let rest = normalized.strip_prefix(prefix.as_str())?;
+            // A registry prefix only covers its repositories, while a repository prefix
+            // also covers its tags and digests.
+            let at_boundary =
+                rest.starts_with('/') || (prefix.contains('/') && rest.starts_with([':', '@']));
+            at_boundary.then(|| format!("{mirror}{rest}"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("alpine", "docker.io/library/alpine:latest")]
+    #[case("alpine:3.19", "docker.io/library/alpine:3.19")]
+    #[case("bitnami/redis:7", "docker.io/bitnami/redis:7")]
+    #[case(
+        "quay.io/prometheus/prometheus:v2.40.1",
+        "quay.io/prometheus/prometheus:v2.40.1"
+    )]
+    #[case("localhost:5000/app", "localhost:5000/app:latest")]
+    #[case("localhost/app:1", "localhost/app:1")]
+    #[case("alpine@sha256:abc", "docker.io/library/alpine@sha256:abc")]
+    fn it_normalizes_references_as_docker_does(#[case] pull: &str, #[case] expected: &str) {
+        assert_eq!(normalize_image_reference(pull), expected);
+    }
+
+    #[rstest]
+    #[case("alpine", "docker.io")]
+    #[case("bitnami/redis:7", "docker.io")]
+    #[case("ghcr.io/acme/api:1.2", "ghcr.io")]
+    #[case("localhost:5000/app", "localhost:5000")]
+    fn it_tells_the_registry_of_an_image(#[case] pull: &str, #[case] expected: &str) {
+        assert_eq!(image_registry(pull), expected);
+    }
+
+    #[rstest]
+    #[case("linux/amd64", true)]
+    #[case("linux/arm/v7", true)]
+    #[case("linux", false)]
+    #[case("linux/", false)]
+    #[case("linux/arm64/v8/extra", false)]
+    #[case("$BUILDPLATFORM", false)]
+    fn it_tells_image_platforms(#[case] platform: &str, #[case] expected: bool) {
+        assert_eq!(is_image_platform(platform), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "alpine:3.19",
+        Some("mirror.example.com/dockerhub/library/alpine:3.19")
+    )]
+    #[case(
+        "docker.io/bitnami/redis",
+        Some("mirror.example.com/dockerhub/bitnami/redis:latest")
+    )]
+    #[case("ghcr.io/acme/api:1.2", Some("registry.example.com/acme-api:1.2"))]
+    #[case(
+        "ghcr.io/acme/web@sha256:abc",
+        Some("registry.example.com/acme/web@sha256:abc")
+    )]
+    #[case("ghcr.io/acme-labs/api:1", None)]
+    #[case("quay.io/prometheus/prometheus:v2.40.1", None)]
+    fn it_rewrites_references_to_the_most_specific_mirror(
+        #[case] pull: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let mirrors = RegistryMirrors::new([
+            (
+                "docker.io".to_string(),
+                "mirror.example.com/dockerhub/".to_string(),
+            ),
+            (
+                "ghcr.io/acme".to_string(),
+                "registry.example.com/acme".to_string(),
+            ),
+            (
+                "ghcr.io/acme/api".to_string(),
+                "registry.example.com/acme-api".to_string(),
+            ),
+        ]);
+
+        assert_eq!(mirrors.resolve(pull).as_deref(), expected);
+    }
+
+    #[test]
+    fn it_resolves_nothing_without_mirrors() {
+        assert!(RegistryMirrors::default().is_empty());
+        assert_eq!(RegistryMirrors::default().resolve("alpine"), None);
+    }
+}
@@ -1,3 +1,4 @@
 #![allow(dead_code)]
 pub mod iacscanresult;
+pub mod image_reference;
 pub mod scanresult;
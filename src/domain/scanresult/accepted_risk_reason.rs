@@ -1,4 +1,7 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AcceptedRiskReason {
     RiskOwned,
     RiskTransferred,
@@ -8,3 +11,55 @@ pub enum AcceptedRiskReason {
     Custom,
     Unknown,
 }
+
+impl AcceptedRiskReason {
+    /// Name of the reason in the Sysdig API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AcceptedRiskReason::RiskOwned => "RiskOwned",
+            AcceptedRiskReason::RiskTransferred => "RiskTransferred",
+            AcceptedRiskReason::RiskAvoided => "RiskAvoided",
+            AcceptedRiskReason::RiskMitigated => "RiskMitigated",
+            AcceptedRiskReason::RiskNotRelevant => "RiskNotRelevant",
+            AcceptedRiskReason::Custom => "Custom",
+            AcceptedRiskReason::Unknown => "Unknown",
+        }
+    }
+}
+
+impl FromStr for AcceptedRiskReason {
+    type Err = String;
+
+    /// Parses the reasons a risk can be accepted with, so `Unknown` is rejected.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            AcceptedRiskReason::RiskOwned,
+            AcceptedRiskReason::RiskTransferred,
+            AcceptedRiskReason::RiskAvoided,
+            AcceptedRiskReason::RiskMitigated,
+            AcceptedRiskReason::RiskNotRelevant,
+            AcceptedRiskReason::Custom,
+        ]
+        .into_iter()
+        .find(|reason| reason.as_str().eq_ignore_ascii_case(s))
+        .ok_or_else(|| format!("unknown risk acceptance reason '{s}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_reasons_of_the_api_ignoring_case() {
+        assert_eq!(
+            "RiskMitigated".parse::<AcceptedRiskReason>(),
+            Ok(AcceptedRiskReason::RiskMitigated)
+        );
+        assert_eq!(
+            "riskNotRelevant".parse::<AcceptedRiskReason>(),
+            Ok(AcceptedRiskReason::RiskNotRelevant)
+        );
+        assert!("Unknown".parse::<AcceptedRiskReason>().is_err());
+    }
+}
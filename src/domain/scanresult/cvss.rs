@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// CVSS assessment of a vulnerability, as reported by the scanner.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cvss {
+    score: f32,
+    vector: Option<String>,
+    version: String,
+}
+
+impl Cvss {
+    pub fn new(score: f32, vector: String, version: String) -> Self {
+        Self {
+            score,
+            vector: (!vector.is_empty()).then_some(vector),
+            version,
+        }
+    }
+
+    /// Base score, from 0.0 to 10.0.
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    /// Vector string the score was computed from, e.g. `AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`.
+    pub fn vector(&self) -> Option<&str> {
+        self.vector.as_deref()
+    }
+
+    /// CVSS version of the score and vector, e.g. `3.1`.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+}
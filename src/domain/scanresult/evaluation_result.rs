@@ -1,7 +1,11 @@
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum EvaluationResult {
     Passed,
     Failed,
+    /// The image wasn't evaluated against policies, e.g. in offline scans.
+    NotEvaluated,
 }
 
 impl EvaluationResult {
@@ -0,0 +1,64 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// What is known about the exploitation of a vulnerability, as reported by the scanner.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Exploit {
+    publication_date: Option<NaiveDate>,
+    links: Vec<String>,
+    cisa_kev: Option<CisaKev>,
+}
+
+impl Exploit {
+    pub fn new(
+        publication_date: Option<NaiveDate>,
+        links: Vec<String>,
+        cisa_kev: Option<CisaKev>,
+    ) -> Self {
+        Self {
+            publication_date,
+            links,
+            cisa_kev,
+        }
+    }
+
+    /// Date the first public exploit was published.
+    pub fn publication_date(&self) -> Option<NaiveDate> {
+        self.publication_date
+    }
+
+    /// Links to public exploits or proofs of concept.
+    pub fn links(&self) -> &[String] {
+        &self.links
+    }
+
+    pub fn cisa_kev(&self) -> Option<&CisaKev> {
+        self.cisa_kev.as_ref()
+    }
+}
+
+/// Entry of the vulnerability in the CISA Known Exploited Vulnerabilities catalog.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CisaKev {
+    publish_date: Option<NaiveDate>,
+    due_date: Option<NaiveDate>,
+}
+
+impl CisaKev {
+    pub fn new(publish_date: Option<NaiveDate>, due_date: Option<NaiveDate>) -> Self {
+        Self {
+            publish_date,
+            due_date,
+        }
+    }
+
+    /// Date the vulnerability was added to the catalog.
+    pub fn publish_date(&self) -> Option<NaiveDate> {
+        self.publish_date
+    }
+
+    /// Date US federal agencies have to remediate the vulnerability by.
+    pub fn due_date(&self) -> Option<NaiveDate> {
+        self.due_date
+    }
+}
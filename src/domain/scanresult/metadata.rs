@@ -2,10 +2,12 @@ use crate::domain::scanresult::architecture::Architecture;
 use crate::domain::scanresult::operating_system::OperatingSystem;
 use chrono::{DateTime, Utc};
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(PartialEq, Eq, Clone)]
 pub struct Metadata {
     pull_string: String,
+    requested_pull_string: Option<String>,
     image_id: String,
     digest: Option<String>,
     base_os: OperatingSystem,
@@ -13,6 +15,10 @@ pub struct Metadata {
     architecture: Architecture,
     labels: HashMap<String, String>,
     created_at: DateTime<Utc>,
+    result_url: Option<String>,
+    parse_warnings: Vec<String>,
+    scanned_at: Option<DateTime<Utc>>,
+    scan_duration: Option<Duration>,
 }
 
 impl Metadata {
@@ -29,6 +35,7 @@ impl Metadata {
     ) -> Self {
         Self {
             pull_string,
+            requested_pull_string: None,
             image_id,
             digest,
             base_os,
@@ -36,6 +43,10 @@ impl Metadata {
             architecture,
             labels,
             created_at,
+            result_url: None,
+            parse_warnings: Vec::new(),
+            scanned_at: None,
+            scan_duration: None,
         }
     }
 
@@ -43,6 +54,19 @@ impl Metadata {
         &self.pull_string
     }
 
+    /// Reference the image was requested as, when it was scanned under `pull_string`
+    /// through a registry mirror.
+    pub fn requested_pull_string(&self) -> Option<&str> {
+        self.requested_pull_string.as_deref()
+    }
+
+    pub(in crate::domain::scanresult) fn set_requested_pull_string(
+        &mut self,
+        requested_pull_string: String,
+    ) {
+        self.requested_pull_string = Some(requested_pull_string);
+    }
+
     pub fn image_id(&self) -> &str {
         &self.image_id
     }
@@ -70,4 +94,42 @@ impl Metadata {
     pub fn created_at(&self) -> DateTime<Utc> {
         self.created_at
     }
+
+    /// Page of the scan result in Sysdig Secure, when the scanner uploaded it.
+    pub fn result_url(&self) -> Option<&str> {
+        self.result_url.as_deref()
+    }
+
+    pub(in crate::domain::scanresult) fn set_result_url(&mut self, result_url: String) {
+        self.result_url = Some(result_url);
+    }
+
+    /// Parts of the scanner report that couldn't be read and were left out of the result,
+    /// e.g. a package missing its name or a severity this version doesn't know.
+    pub fn parse_warnings(&self) -> &[String] {
+        &self.parse_warnings
+    }
+
+    pub(in crate::domain::scanresult) fn add_parse_warning(&mut self, warning: String) {
+        self.parse_warnings.push(warning);
+    }
+
+    /// When the scanner scanned the image, which is older than the result for results
+    /// read from a cache.
+    pub fn scanned_at(&self) -> Option<DateTime<Utc>> {
+        self.scanned_at
+    }
+
+    pub fn scan_duration(&self) -> Option<Duration> {
+        self.scan_duration
+    }
+
+    pub(in crate::domain::scanresult) fn set_scan_time(
+        &mut self,
+        scanned_at: DateTime<Utc>,
+        scan_duration: Option<Duration>,
+    ) {
+        self.scanned_at = Some(scanned_at);
+        self.scan_duration = scan_duration;
+    }
 }
@@ -1,19 +1,25 @@
 pub mod accepted_risk;
 pub mod accepted_risk_reason;
 pub mod architecture;
+pub mod cvss;
 pub mod evaluation_result;
+pub mod exploit;
 pub mod layer;
 pub mod metadata;
 pub mod operating_system;
 pub mod package;
 pub mod package_type;
+pub mod package_version;
 pub mod policy;
 pub mod policy_bundle;
 pub mod policy_bundle_rule;
 pub mod policy_bundle_rule_failure;
 pub mod policy_bundle_rule_image_config_failure;
 pub mod policy_bundle_rule_pkg_vuln_failure;
+pub mod sarif;
+pub mod sbom;
 pub mod scan_result;
+pub mod scan_result_diff;
 pub mod scan_type;
 pub mod severity;
 pub mod vulnerability;
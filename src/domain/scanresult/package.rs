@@ -1,6 +1,7 @@
 use crate::domain::scanresult::accepted_risk::AcceptedRisk;
 use crate::domain::scanresult::layer::Layer;
 use crate::domain::scanresult::package_type::PackageType;
+use crate::domain::scanresult::package_version::compare_versions;
 use crate::domain::scanresult::severity::Severity;
 use crate::domain::scanresult::vulnerability::Vulnerability;
 use crate::domain::scanresult::weak_hash::WeakHash;
@@ -10,12 +11,31 @@ use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 use std::sync::{Arc, RwLock};
 
+/// Version a package can be upgraded to in order to fix its vulnerabilities.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuggestedFix {
+    pub version: String,
+    pub source: SuggestedFixSource,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuggestedFixSource {
+    /// Reported by the scanner.
+    Scanner,
+    /// Picked among the fix versions of the vulnerabilities of the package, because the
+    /// scanner suggested none.
+    Computed,
+}
+
 pub struct Package {
     package_type: PackageType,
     name: String,
     version: String,
     path: String,
     found_in_layer: Arc<Layer>,
+    is_running: bool,
+    license: Option<String>,
+    suggested_fix: Option<String>,
     vulnerabilities: RwLock<HashSet<WeakHash<Vulnerability>>>,
     accepted_risks: RwLock<HashSet<WeakHash<AcceptedRisk>>>,
 }
@@ -28,17 +48,24 @@ impl Debug for Package {
             .field("version", &self.version)
             .field("path", &self.path)
             .field("found_in_layer", &self.found_in_layer)
+            .field("is_running", &self.is_running)
+            .field("license", &self.license)
+            .field("suggested_fix", &self.suggested_fix)
             .finish()
     }
 }
 
 impl Package {
+    #[allow(clippy::too_many_arguments)]
     pub(in crate::domain::scanresult) fn new(
         package_type: PackageType,
         name: String,
         version: String,
         path: String,
         found_in_layer: Arc<Layer>,
+        is_running: bool,
+        license: Option<String>,
+        suggested_fix: Option<String>,
     ) -> Self {
         Self {
             package_type,
@@ -46,6 +73,9 @@ impl Package {
             version,
             path,
             found_in_layer,
+            is_running,
+            license: license.filter(|license| !license.is_empty()),
+            suggested_fix: suggested_fix.filter(|version| !version.is_empty()),
             vulnerabilities: RwLock::new(HashSet::new()),
             accepted_risks: RwLock::new(HashSet::new()),
         }
@@ -71,6 +101,18 @@ impl Package {
         &self.found_in_layer
     }
 
+    /// License of the package as reported by the scanner, usually an SPDX expression
+    /// such as `MIT` or `GPL-2.0-or-later`.
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    /// Whether the package is loaded by running containers of the image, as reported
+    /// by Sysdig runtime insights.
+    pub fn is_running(&self) -> bool {
+        self.is_running
+    }
+
     pub fn add_vulnerability_found(self: &Arc<Self>, vulnerability: Arc<Vulnerability>) {
         if self
             .vulnerabilities
@@ -111,7 +153,33 @@ impl Package {
             .collect()
     }
 
+    /// Version to upgrade to: the one the scanner suggests, or else the one fixing the
+    /// most severe vulnerabilities of the package.
+    pub fn suggested_fix(&self) -> Option<SuggestedFix> {
+        if let Some(version) = &self.suggested_fix {
+            return Some(SuggestedFix {
+                version: version.clone(),
+                source: SuggestedFixSource::Scanner,
+            });
+        }
+        self.computed_fix_version().map(|version| SuggestedFix {
+            version,
+            source: SuggestedFixSource::Computed,
+        })
+    }
+
+    /// Orders packages by name, then by version (see [`compare_versions`]).
+    pub fn cmp_by_name_and_version(&self, other: &Package) -> Ordering {
+        self.name
+            .cmp(&other.name)
+            .then_with(|| compare_versions(&self.version, &other.version))
+    }
+
     pub fn suggested_fix_version(&self) -> Option<String> {
+        self.suggested_fix().map(|fix| fix.version)
+    }
+
+    fn computed_fix_version(&self) -> Option<String> {
         let vulnerabilities = self.vulnerabilities();
         if vulnerabilities.is_empty() {
             return None;
@@ -171,16 +239,7 @@ impl Package {
             }
 
             // If scores are identical, lower version is better
-            if version_compare::compare_to(a, b, version_compare::Cmp::Eq).unwrap_or(false) {
-                return Ordering::Equal;
-            }
-            if version_compare::compare_to(a, b, version_compare::Cmp::Le).unwrap_or(false) {
-                return Ordering::Less;
-            }
-            if version_compare::compare_to(a, b, version_compare::Cmp::Ge).unwrap_or(false) {
-                return Ordering::Greater;
-            }
-            Ordering::Less
+            compare_versions(a, b)
         });
 
         sorted_candidates.first().cloned()
@@ -215,6 +274,9 @@ impl Clone for Package {
             version: self.version.clone(),
             path: self.path.clone(),
             found_in_layer: self.found_in_layer.clone(),
+            is_running: self.is_running,
+            license: self.license.clone(),
+            suggested_fix: self.suggested_fix.clone(),
             vulnerabilities: RwLock::new(
                 self.vulnerabilities
                     .read()
@@ -260,6 +322,9 @@ mod tests {
             version.to_string(),
             "a_path".to_string(),
             layer,
+            false,
+            None,
+            None,
         ))
     }
 
@@ -275,6 +340,8 @@ mod tests {
             None,
             false,
             fix_version.map(|v| v.to_string()),
+            None,
+            None,
         ))
     }
 
@@ -351,4 +418,44 @@ mod tests {
             expected_fix.map(|x| x.to_string())
         );
     }
+
+    #[rstest]
+    fn test_suggested_fix_prefers_the_one_from_the_scanner(layer: Arc<Layer>) {
+        let package = Arc::new(Package::new(
+            PackageType::Os,
+            "a_name".to_string(),
+            "1.0.0".to_string(),
+            "a_path".to_string(),
+            layer,
+            false,
+            None,
+            Some("1.0.3".to_string()),
+        ));
+        let vulnerability = a_vulnerability("CVE-1", Severity::High, Some("1.0.1"));
+        package.add_vulnerability_found(vulnerability.clone());
+
+        assert_eq!(
+            package.suggested_fix(),
+            Some(SuggestedFix {
+                version: "1.0.3".to_string(),
+                source: SuggestedFixSource::Scanner,
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_suggested_fix_is_computed_when_the_scanner_suggests_none(
+        #[with("1.0.0")] package: Arc<Package>,
+    ) {
+        let vulnerability = a_vulnerability("CVE-1", Severity::High, Some("1.0.1"));
+        package.add_vulnerability_found(vulnerability.clone());
+
+        assert_eq!(
+            package.suggested_fix(),
+            Some(SuggestedFix {
+                version: "1.0.1".to_string(),
+                source: SuggestedFixSource::Computed,
+            })
+        );
+    }
 }
@@ -0,0 +1,36 @@
+use std::cmp::Ordering;
+
+use version_compare::Cmp;
+
+/// Orders package versions as the ecosystems do, whether they are SemVer or not, e.g.
+/// `1.9` before `1.10` or `1.1.35-1.2+deb13u2` before `1.1.35-1.2+deb13u3`. Versions
+/// that can't be compared, or that compare equal while spelled differently (`1.0` and
+/// `1.0.0`), are ordered as strings, so the order is total.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let ordering = match version_compare::compare(a, b) {
+        Ok(Cmp::Lt) => Ordering::Less,
+        Ok(Cmp::Gt) => Ordering::Greater,
+        _ => Ordering::Equal,
+    };
+    ordering.then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("1.9", "1.10", Ordering::Less)]
+    #[case("2.0.0", "10.0.0", Ordering::Less)]
+    #[case("1.1.35-1.2+deb13u3", "1.1.35-1.2+deb13u2", Ordering::Greater)]
+    #[case("257.8-1~deb13u1", "257.8-1~deb13u2", Ordering::Less)]
+    #[case("31.1-jre", "32.0.0-android", Ordering::Less)]
+    #[case("1.0", "1.0.0", Ordering::Less)]
+    #[case("3.0.1", "3.0.1", Ordering::Equal)]
+    fn it_compares_versions(#[case] a: &str, #[case] b: &str, #[case] expected: Ordering) {
+        assert_eq!(compare_versions(a, b), expected);
+        assert_eq!(compare_versions(b, a), expected.reverse());
+    }
+}
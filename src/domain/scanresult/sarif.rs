@@ -0,0 +1,514 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::domain::scanresult::evaluation_result::EvaluationResult;
+use crate::domain::scanresult::policy_bundle_rule_failure::PolicyBundleRuleFailure;
+use crate::domain::scanresult::scan_result::ScanResult;
+use crate::domain::scanresult::severity::Severity;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+const TOOL_NAME: &str = "sysdig-lsp";
+const TOOL_INFORMATION_URI: &str = "https://github.com/sysdiglabs/sysdig-lsp";
+
+/// Region of the scanned file, with 1-based lines and columns as SARIF expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifRegion {
+    pub start_line: u32,
+    pub start_column: u32,
+    pub end_line: u32,
+    pub end_column: u32,
+}
+
+/// Where the findings of a scan are reported: the file declaring the image, the image
+/// reference in it, and the instructions the image layers were matched to (if any).
+#[derive(Debug, Clone)]
+pub struct SarifArtifactLocations {
+    pub artifact_uri: String,
+    pub image_region: SarifRegion,
+    /// By layer index. Findings in layers without a region are reported on the image.
+    pub layer_regions: HashMap<usize, SarifRegion>,
+}
+
+impl SarifArtifactLocations {
+    fn location_for_layer(&self, layer_index: Option<usize>) -> SarifLocation {
+        let region = layer_index
+            .and_then(|index| self.layer_regions.get(&index))
+            .unwrap_or(&self.image_region);
+
+        SarifLocation {
+            physical_location: SarifPhysicalLocation {
+                artifact_location: SarifArtifactLocation {
+                    uri: self.artifact_uri.clone(),
+                },
+                region: *region,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    pub schema: &'static str,
+    pub version: &'static str,
+    pub runs: Vec<SarifRun>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifRun {
+    pub tool: SarifTool,
+    pub results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifTool {
+    pub driver: SarifDriver,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifDriver {
+    pub name: &'static str,
+    pub information_uri: &'static str,
+    pub version: &'static str,
+    pub rules: Vec<SarifRule>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifRule {
+    pub id: String,
+    pub short_description: SarifMessage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help_uri: Option<String>,
+    pub properties: SarifRuleProperties,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifRuleProperties {
+    /// CVSS score GitHub code scanning uses to rank security findings, estimated from
+    /// the severity when the scanner reported none.
+    #[serde(rename = "security-severity", skip_serializing_if = "Option::is_none")]
+    pub security_severity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cvss_vector: Option<String>,
+    pub tags: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    pub rule_id: String,
+    pub level: SarifLevel,
+    pub message: SarifMessage,
+    pub locations: Vec<SarifLocation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SarifLevel {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SarifMessage {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifLocation {
+    pub physical_location: SarifPhysicalLocation,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifPhysicalLocation {
+    pub artifact_location: SarifArtifactLocation,
+    pub region: SarifRegion,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SarifArtifactLocation {
+    pub uri: String,
+}
+
+fn level_for(severity: Severity) -> SarifLevel {
+    match severity {
+        Severity::Critical | Severity::High => SarifLevel::Error,
+        Severity::Medium => SarifLevel::Warning,
+        Severity::Low | Severity::Negligible | Severity::Unknown => SarifLevel::Note,
+    }
+}
+
+fn security_severity_for(severity: Severity) -> Option<&'static str> {
+    match severity {
+        Severity::Critical => Some("9.5"),
+        Severity::High => Some("8.0"),
+        Severity::Medium => Some("5.5"),
+        Severity::Low => Some("2.0"),
+        Severity::Negligible => Some("0.0"),
+        Severity::Unknown => None,
+    }
+}
+
+impl SarifLog {
+    /// Merges the logs of several scans into a single run, as code scanning services
+    /// expect one run per tool. Rules shared by the scans are listed once.
+    pub fn merge(logs: impl IntoIterator<Item = SarifLog>) -> SarifLog {
+        let mut rules = BTreeMap::new();
+        let mut results = Vec::new();
+        for run in logs.into_iter().flat_map(|log| log.runs) {
+            for rule in run.tool.driver.rules {
+                rules.entry(rule.id.clone()).or_insert(rule);
+            }
+            results.extend(run.results);
+        }
+        sarif_log(rules.into_values().collect(), results)
+    }
+}
+
+impl ScanResult {
+    /// Converts the findings into a SARIF 2.1.0 log: every CVE becomes a rule with one
+    /// result per affected package, located at the layer that introduced the package;
+    /// every failed policy rule becomes a rule with a `note` result on the image.
+    pub fn to_sarif(&self, locations: &SarifArtifactLocations) -> SarifLog {
+        // Rules are kept sorted by id and findings iterated in a fixed order, so exporting
+        // the same scan twice yields the same log.
+        let mut rules = BTreeMap::new();
+        let mut results = Vec::new();
+
+        let mut vulnerabilities = self.vulnerabilities();
+        vulnerabilities.sort_by(|a, b| a.cve().cmp(b.cve()));
+        for vulnerability in vulnerabilities {
+            let cve = vulnerability.cve();
+            rules.entry(cve.to_string()).or_insert_with(|| SarifRule {
+                id: cve.to_string(),
+                short_description: SarifMessage {
+                    text: format!("{cve} ({})", vulnerability.severity()),
+                },
+                help_uri: cve
+                    .starts_with("CVE-")
+                    .then(|| format!("https://nvd.nist.gov/vuln/detail/{cve}")),
+                properties: SarifRuleProperties {
+                    security_severity: match vulnerability.cvss() {
+                        Some(cvss) => Some(format!("{:.1}", cvss.score())),
+                        None => security_severity_for(vulnerability.severity()).map(str::to_string),
+                    },
+                    cvss_vector: vulnerability
+                        .cvss()
+                        .and_then(|cvss| cvss.vector())
+                        .map(str::to_string),
+                    tags: vec!["security", "vulnerability"],
+                },
+            });
+
+            let mut packages = vulnerability.found_in_packages();
+            packages.sort_by(|a, b| a.cmp_by_name_and_version(b));
+            for package in packages {
+                let fix = vulnerability
+                    .fix_version()
+                    .map(|version| format!(", fixed in {version}"))
+                    .unwrap_or_default();
+                results.push(SarifResult {
+                    rule_id: cve.to_string(),
+                    level: level_for(vulnerability.severity()),
+                    message: SarifMessage {
+                        text: format!(
+                            "{cve} ({}) in {} {}{fix}",
+                            vulnerability.severity(),
+                            package.name(),
+                            package.version()
+                        ),
+                    },
+                    locations: vec![
+                        locations.location_for_layer(Some(package.found_in_layer().index())),
+                    ],
+                });
+            }
+        }
+
+        let mut policies = self.policies();
+        policies.sort_by(|a, b| a.id().cmp(b.id()));
+        for policy in policies {
+            let mut bundles = policy.bundles();
+            bundles.sort_by(|a, b| a.id().cmp(b.id()));
+            for bundle in bundles {
+                let mut bundle_rules = bundle.rules();
+                bundle_rules.sort_by(|a, b| a.id().cmp(b.id()));
+                for rule in bundle_rules {
+                    if *rule.evaluation_result() == EvaluationResult::Passed {
+                        continue;
+                    }
+
+                    let rule_id = format!("policy/{}/{}", bundle.id(), rule.id());
+                    rules.entry(rule_id.clone()).or_insert_with(|| SarifRule {
+                        id: rule_id.clone(),
+                        short_description: SarifMessage {
+                            text: rule.description().to_string(),
+                        },
+                        help_uri: None,
+                        properties: SarifRuleProperties {
+                            security_severity: None,
+                            cvss_vector: None,
+                            tags: vec!["policy"],
+                        },
+                    });
+
+                    let failures = rule
+                        .failures()
+                        .iter()
+                        .map(|failure| match failure {
+                            PolicyBundleRuleFailure::ImageConfig(f) => f.description().to_string(),
+                            PolicyBundleRuleFailure::PkgVuln(f) => f.remediation().to_string(),
+                        })
+                        .collect::<Vec<_>>();
+                    let details = if failures.is_empty() {
+                        String::new()
+                    } else {
+                        format!(": {}", failures.join("; "))
+                    };
+                    results.push(SarifResult {
+                        rule_id,
+                        level: SarifLevel::Note,
+                        message: SarifMessage {
+                            text: format!(
+                                "Policy \"{}\" failed rule \"{}\" of bundle \"{}\"{details}",
+                                policy.name(),
+                                rule.description(),
+                                bundle.name()
+                            ),
+                        },
+                        locations: vec![locations.location_for_layer(None)],
+                    });
+                }
+            }
+        }
+
+        sarif_log(rules.into_values().collect(), results)
+    }
+}
+
+fn sarif_log(rules: Vec<SarifRule>, results: Vec<SarifResult>) -> SarifLog {
+    SarifLog {
+        schema: SARIF_SCHEMA,
+        version: SARIF_VERSION,
+        runs: vec![SarifRun {
+            tool: SarifTool {
+                driver: SarifDriver {
+                    name: TOOL_NAME,
+                    information_uri: TOOL_INFORMATION_URI,
+                    version: env!("CARGO_PKG_VERSION"),
+                    rules,
+                },
+            },
+            results,
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        cvss::Cvss,
+        operating_system::{Family, OperatingSystem},
+        package_type::PackageType,
+        scan_type::ScanType,
+    };
+
+    fn region(line: u32) -> SarifRegion {
+        SarifRegion {
+            start_line: line,
+            start_column: 1,
+            end_line: line,
+            end_column: 12,
+        }
+    }
+
+    #[test]
+    fn it_maps_cves_to_rules_located_at_their_layer_and_policy_failures_to_notes() {
+        let now = chrono::Utc::now();
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:3.18".to_string(),
+            "sha256:image".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine 3.18".to_string()),
+            1024,
+            Architecture::Amd64,
+            HashMap::new(),
+            now,
+            EvaluationResult::Failed,
+        );
+        let base_layer =
+            scan_result.add_layer("sha256:l0".to_string(), 0, None, "ADD /".to_string());
+        let run_layer =
+            scan_result.add_layer("sha256:l1".to_string(), 1, None, "RUN apk add".to_string());
+        let openssl = scan_result.add_package(
+            PackageType::Os,
+            "openssl".to_string(),
+            "3.0.0".to_string(),
+            "/lib".to_string(),
+            run_layer,
+            false,
+            None,
+            None,
+        );
+        let musl = scan_result.add_package(
+            PackageType::Os,
+            "musl".to_string(),
+            "1.2.0".to_string(),
+            "/lib".to_string(),
+            base_layer,
+            false,
+            None,
+            None,
+        );
+        let critical = scan_result.add_vulnerability(
+            "CVE-2023-0001".to_string(),
+            Severity::Critical,
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            None,
+            false,
+            Some("3.0.1".to_string()),
+            Some(Cvss::new(
+                9.8,
+                "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H".to_string(),
+                "3.1".to_string(),
+            )),
+            None,
+        );
+        openssl.add_vulnerability_found(critical.clone());
+        musl.add_vulnerability_found(critical);
+        let policy = scan_result.add_policy("p1".to_string(), "Prod".to_string(), now, now);
+        scan_result
+            .add_policy_bundle("b1".to_string(), "Baseline".to_string(), policy)
+            .add_rule(
+                "r1".to_string(),
+                "No root user".to_string(),
+                EvaluationResult::Failed,
+            )
+            .add_image_config_failure("user is root".to_string());
+
+        let locations = SarifArtifactLocations {
+            artifact_uri: "Dockerfile".to_string(),
+            image_region: region(1),
+            layer_regions: HashMap::from([(1, region(3))]),
+        };
+        let sarif = serde_json::to_value(scan_result.to_sarif(&locations)).unwrap();
+
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(
+            run["tool"]["driver"]["rules"][0]["id"],
+            json!("CVE-2023-0001")
+        );
+        assert_eq!(
+            run["tool"]["driver"]["rules"][0]["properties"]["security-severity"],
+            "9.8"
+        );
+        assert_eq!(
+            run["tool"]["driver"]["rules"][0]["properties"]["cvssVector"],
+            "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"
+        );
+        assert_eq!(run["tool"]["driver"]["rules"][1]["id"], "policy/b1/r1");
+
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results.len(), 3);
+        // musl comes from the base layer, which has no region: reported on the image.
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            1
+        );
+        assert_eq!(
+            results[1]["message"]["text"],
+            "CVE-2023-0001 (Critical) in openssl 3.0.0, fixed in 3.0.1"
+        );
+        assert_eq!(
+            results[1]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            3
+        );
+        assert_eq!(results[2]["level"], "note");
+        assert_eq!(
+            results[2]["message"]["text"],
+            "Policy \"Prod\" failed rule \"No root user\" of bundle \"Baseline\": user is root"
+        );
+    }
+
+    #[test]
+    fn it_merges_the_logs_into_a_single_run_listing_shared_rules_once() {
+        let scan = |cve: &str| {
+            let mut scan_result = ScanResult::new(
+                ScanType::Docker,
+                "alpine:3.18".to_string(),
+                "sha256:image".to_string(),
+                None,
+                OperatingSystem::new(Family::Linux, "alpine 3.18".to_string()),
+                1024,
+                Architecture::Amd64,
+                HashMap::new(),
+                chrono::Utc::now(),
+                EvaluationResult::Passed,
+            );
+            let layer =
+                scan_result.add_layer("sha256:l0".to_string(), 0, None, "ADD /".to_string());
+            let openssl = scan_result.add_package(
+                PackageType::Os,
+                "openssl".to_string(),
+                "3.0.0".to_string(),
+                "/lib".to_string(),
+                layer,
+                false,
+                None,
+                None,
+            );
+            for cve in ["CVE-2023-0001", cve] {
+                openssl.add_vulnerability_found(scan_result.add_vulnerability(
+                    cve.to_string(),
+                    Severity::High,
+                    chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+                    None,
+                    false,
+                    None,
+                    None,
+                    None,
+                ));
+            }
+            let locations = SarifArtifactLocations {
+                artifact_uri: "Dockerfile".to_string(),
+                image_region: region(1),
+                layer_regions: HashMap::new(),
+            };
+            scan_result.to_sarif(&locations)
+        };
+
+        let merged = SarifLog::merge([scan("CVE-2023-0002"), scan("CVE-2023-0003")]);
+
+        assert_eq!(merged.runs.len(), 1);
+        let rules = merged.runs[0]
+            .tool
+            .driver
+            .rules
+            .iter()
+            .map(|rule| rule.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(rules, ["CVE-2023-0001", "CVE-2023-0002", "CVE-2023-0003"]);
+        assert_eq!(merged.runs[0].results.len(), 4);
+    }
+}
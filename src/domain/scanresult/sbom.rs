@@ -0,0 +1,520 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::domain::scanresult::package::Package;
+use crate::domain::scanresult::package_type::PackageType;
+use crate::domain::scanresult::scan_result::ScanResult;
+
+const TOOL_NAME: &str = "sysdig-lsp";
+const TOOL_INFORMATION_URI: &str = "https://github.com/sysdiglabs/sysdig-lsp";
+const CYCLONEDX_SCHEMA: &str = "http://cyclonedx.org/schema/bom-1.5.schema.json";
+const CYCLONEDX_SPEC_VERSION: &str = "1.5";
+const SPDX_VERSION: &str = "SPDX-2.3";
+const SPDX_DATA_LICENSE: &str = "CC0-1.0";
+const SPDX_DOCUMENT_ID: &str = "SPDXRef-DOCUMENT";
+const SPDX_IMAGE_ID: &str = "SPDXRef-Image";
+const NO_ASSERTION: &str = "NOASSERTION";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SbomFormat {
+    #[default]
+    CycloneDx,
+    Spdx,
+}
+
+impl SbomFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SbomFormat::CycloneDx => "cyclonedx",
+            SbomFormat::Spdx => "spdx",
+        }
+    }
+}
+
+impl Display for SbomFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [SbomFormat::CycloneDx, SbomFormat::Spdx]
+            .into_iter()
+            .find(|format| format.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown SBOM format '{s}', expected cyclonedx or spdx"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxBom {
+    #[serde(rename = "$schema")]
+    pub schema: &'static str,
+    pub bom_format: &'static str,
+    pub spec_version: &'static str,
+    pub version: u32,
+    pub metadata: CycloneDxMetadata,
+    pub components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxMetadata {
+    pub timestamp: String,
+    pub tools: CycloneDxTools,
+    pub component: CycloneDxComponent,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CycloneDxTools {
+    pub components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxComponent {
+    #[serde(rename = "type")]
+    pub component_type: &'static str,
+    #[serde(rename = "bom-ref", skip_serializing_if = "Option::is_none")]
+    pub bom_ref: Option<String>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub properties: Vec<CycloneDxProperty>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CycloneDxProperty {
+    pub name: &'static str,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxDocument {
+    pub spdx_version: &'static str,
+    pub data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: &'static str,
+    pub name: String,
+    pub document_namespace: String,
+    pub creation_info: SpdxCreationInfo,
+    pub packages: Vec<SpdxPackage>,
+    pub relationships: Vec<SpdxRelationship>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpdxCreationInfo {
+    pub created: String,
+    pub creators: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxPackage {
+    pub name: String,
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_info: Option<String>,
+    pub download_location: &'static str,
+    pub files_analyzed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_package_purpose: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_info: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub external_refs: Vec<SpdxExternalRef>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxExternalRef {
+    pub reference_category: &'static str,
+    pub reference_type: &'static str,
+    pub reference_locator: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxRelationship {
+    pub spdx_element_id: String,
+    pub relationship_type: &'static str,
+    pub related_spdx_element: String,
+}
+
+impl ScanResult {
+    /// Converts the package inventory into a CycloneDX 1.5 BOM describing the image,
+    /// with the layer each package was found in kept as component properties.
+    pub fn to_cyclonedx(&self, timestamp: DateTime<Utc>) -> CycloneDxBom {
+        let components = self
+            .sorted_packages()
+            .iter()
+            .enumerate()
+            .map(|(index, package)| CycloneDxComponent {
+                component_type: "library",
+                bom_ref: Some(format!("package-{index}")),
+                name: package.name().to_string(),
+                version: Some(package.version().to_string()),
+                purl: self.purl_for(package),
+                properties: [
+                    Some(("sysdig:package:type", package.package_type().to_string())),
+                    Some(("sysdig:package:path", package.path().to_string())),
+                    Some((
+                        "sysdig:layer:index",
+                        package.found_in_layer().index().to_string(),
+                    )),
+                    package
+                        .found_in_layer()
+                        .digest()
+                        .map(|digest| ("sysdig:layer:digest", digest.to_string())),
+                    Some(("sysdig:package:inUse", package.is_running().to_string())),
+                ]
+                .into_iter()
+                .flatten()
+                .map(|(name, value)| CycloneDxProperty { name, value })
+                .collect(),
+            })
+            .collect();
+
+        let metadata = self.metadata();
+        CycloneDxBom {
+            schema: CYCLONEDX_SCHEMA,
+            bom_format: "CycloneDX",
+            spec_version: CYCLONEDX_SPEC_VERSION,
+            version: 1,
+            metadata: CycloneDxMetadata {
+                timestamp: timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+                tools: CycloneDxTools {
+                    components: vec![CycloneDxComponent {
+                        component_type: "application",
+                        bom_ref: None,
+                        name: TOOL_NAME.to_string(),
+                        version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                        purl: None,
+                        properties: vec![],
+                    }],
+                },
+                component: CycloneDxComponent {
+                    component_type: "container",
+                    bom_ref: Some("image".to_string()),
+                    name: metadata.pull_string().to_string(),
+                    version: Some(metadata.digest().unwrap_or(metadata.image_id()).to_string()),
+                    purl: None,
+                    properties: vec![
+                        CycloneDxProperty {
+                            name: "sysdig:image:id",
+                            value: metadata.image_id().to_string(),
+                        },
+                        CycloneDxProperty {
+                            name: "sysdig:image:baseOs",
+                            value: metadata.base_os().name().to_string(),
+                        },
+                    ],
+                },
+            },
+            components,
+        }
+    }
+
+    /// Converts the package inventory into an SPDX 2.3 document whose only described
+    /// package is the image, which contains every package found in it.
+    pub fn to_spdx(&self, created: DateTime<Utc>) -> SpdxDocument {
+        let metadata = self.metadata();
+        let mut packages = vec![SpdxPackage {
+            name: metadata.pull_string().to_string(),
+            spdx_id: SPDX_IMAGE_ID.to_string(),
+            version_info: Some(metadata.digest().unwrap_or(metadata.image_id()).to_string()),
+            download_location: NO_ASSERTION,
+            files_analyzed: false,
+            primary_package_purpose: Some("CONTAINER"),
+            source_info: None,
+            external_refs: vec![],
+        }];
+        let mut relationships = vec![SpdxRelationship {
+            spdx_element_id: SPDX_DOCUMENT_ID.to_string(),
+            relationship_type: "DESCRIBES",
+            related_spdx_element: SPDX_IMAGE_ID.to_string(),
+        }];
+
+        for (index, package) in self.sorted_packages().iter().enumerate() {
+            let spdx_id = format!("SPDXRef-Package-{index}");
+            let layer = package.found_in_layer();
+            packages.push(SpdxPackage {
+                name: package.name().to_string(),
+                spdx_id: spdx_id.clone(),
+                version_info: Some(package.version().to_string()),
+                download_location: NO_ASSERTION,
+                files_analyzed: false,
+                primary_package_purpose: None,
+                source_info: Some(format!(
+                    "{} package found at {} in layer {} ({})",
+                    package.package_type(),
+                    package.path(),
+                    layer.index(),
+                    layer.digest().unwrap_or("no digest")
+                )),
+                external_refs: self
+                    .purl_for(package)
+                    .map(|purl| SpdxExternalRef {
+                        reference_category: "PACKAGE-MANAGER",
+                        reference_type: "purl",
+                        reference_locator: purl,
+                    })
+                    .into_iter()
+                    .collect(),
+            });
+            relationships.push(SpdxRelationship {
+                spdx_element_id: SPDX_IMAGE_ID.to_string(),
+                relationship_type: "CONTAINS",
+                related_spdx_element: spdx_id,
+            });
+        }
+
+        SpdxDocument {
+            spdx_version: SPDX_VERSION,
+            data_license: SPDX_DATA_LICENSE,
+            spdx_id: SPDX_DOCUMENT_ID,
+            name: metadata.pull_string().to_string(),
+            // Must be unique per document, so two SBOMs of the same image differ.
+            document_namespace: format!(
+                "{TOOL_INFORMATION_URI}/spdx/{}-{}",
+                percent_encode(metadata.image_id()),
+                created.timestamp()
+            ),
+            creation_info: SpdxCreationInfo {
+                created: created.to_rfc3339_opts(SecondsFormat::Secs, true),
+                creators: vec![format!("Tool: {TOOL_NAME}-{}", env!("CARGO_PKG_VERSION"))],
+            },
+            packages,
+            relationships,
+        }
+    }
+
+    /// The domain keeps packages in a hash set, so they are sorted for a stable output
+    /// and stable component references.
+    fn sorted_packages(&self) -> Vec<Arc<Package>> {
+        let mut packages = self.packages();
+        packages.sort_by(|a, b| {
+            (a.name(), a.version(), a.path()).cmp(&(b.name(), b.version(), b.path()))
+        });
+        packages
+    }
+
+    /// Package URL of the package, when its ecosystem has a purl type. OS packages
+    /// take theirs from the distribution of the image.
+    fn purl_for(&self, package: &Package) -> Option<String> {
+        let name = package.name();
+        let (purl_type, namespace, name) = match package.package_type() {
+            PackageType::Os => {
+                let distro = self
+                    .metadata()
+                    .base_os()
+                    .name()
+                    .split_whitespace()
+                    .next()?
+                    .to_lowercase();
+                let purl_type = match distro.as_str() {
+                    "alpine" | "wolfi" | "chainguard" => "apk",
+                    "debian" | "ubuntu" | "distroless" => "deb",
+                    "rhel" | "centos" | "fedora" | "amzn" | "amazon" | "rocky" | "almalinux"
+                    | "ol" | "oracle" | "sles" | "opensuse" | "photon" | "mariner" => "rpm",
+                    _ => return None,
+                };
+                (purl_type, Some(distro), name.to_string())
+            }
+            PackageType::Python => ("pypi", None, name.to_lowercase().replace('_', "-")),
+            PackageType::Java => {
+                // Maven coordinates come as `group:artifact`.
+                let (group, artifact) = name.split_once(':')?;
+                ("maven", Some(group.to_string()), artifact.to_string())
+            }
+            PackageType::Javascript => match name.strip_prefix('@').and_then(|n| n.split_once('/'))
+            {
+                Some((scope, name)) => ("npm", Some(format!("@{scope}")), name.to_string()),
+                None => ("npm", None, name.to_string()),
+            },
+            PackageType::Golang => match name.rsplit_once('/') {
+                Some((namespace, name)) => {
+                    ("golang", Some(namespace.to_string()), name.to_string())
+                }
+                None => ("golang", None, name.to_string()),
+            },
+            PackageType::Rust => ("cargo", None, name.to_string()),
+            PackageType::Ruby => ("gem", None, name.to_string()),
+            PackageType::Php => match name.split_once('/') {
+                Some((vendor, name)) => ("composer", Some(vendor.to_string()), name.to_string()),
+                None => ("composer", None, name.to_string()),
+            },
+            PackageType::CSharp => ("nuget", None, name.to_string()),
+            PackageType::Unknown => return None,
+        };
+
+        let namespace = namespace
+            .map(|namespace| {
+                let segments: Vec<_> = namespace.split('/').map(percent_encode).collect();
+                format!("{}/", segments.join("/"))
+            })
+            .unwrap_or_default();
+        Some(format!(
+            "pkg:{purl_type}/{namespace}{}@{}",
+            percent_encode(&name),
+            percent_encode(package.version())
+        ))
+    }
+}
+
+/// Percent-encodes everything but the unreserved characters, as purl components require.
+fn percent_encode(component: &str) -> String {
+    component
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::domain::scanresult::{
+        architecture::Architecture,
+        evaluation_result::EvaluationResult,
+        operating_system::{Family, OperatingSystem},
+        scan_type::ScanType,
+    };
+
+    fn scan_result() -> ScanResult {
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "node:20-alpine".to_string(),
+            "sha256:image".to_string(),
+            Some("sha256:digest".to_string()),
+            OperatingSystem::new(Family::Linux, "alpine 3.19".to_string()),
+            1024,
+            Architecture::Amd64,
+            HashMap::new(),
+            chrono::Utc::now(),
+            EvaluationResult::Passed,
+        );
+        let base = scan_result.add_layer("sha256:l0".to_string(), 0, None, "ADD /".to_string());
+        let npm = scan_result.add_layer("sha256:l1".to_string(), 1, None, "RUN npm i".to_string());
+        scan_result.add_package(
+            PackageType::Os,
+            "musl".to_string(),
+            "1.2.4-r2".to_string(),
+            "/lib/apk/db/installed".to_string(),
+            base,
+            true,
+            None,
+            None,
+        );
+        scan_result.add_package(
+            PackageType::Javascript,
+            "@babel/core".to_string(),
+            "7.23.0".to_string(),
+            "/app/node_modules/@babel/core/package.json".to_string(),
+            npm,
+            false,
+            None,
+            None,
+        );
+        scan_result
+    }
+
+    fn created() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn it_parses_the_formats_ignoring_case() {
+        assert_eq!("CycloneDX".parse(), Ok(SbomFormat::CycloneDx));
+        assert_eq!("spdx".parse(), Ok(SbomFormat::Spdx));
+        assert!("swid".parse::<SbomFormat>().is_err());
+    }
+
+    #[test]
+    fn it_lists_the_packages_as_cyclonedx_components_with_their_layer() {
+        let bom = serde_json::to_value(scan_result().to_cyclonedx(created())).unwrap();
+
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["specVersion"], "1.5");
+        assert_eq!(bom["metadata"]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(bom["metadata"]["component"]["type"], "container");
+        assert_eq!(bom["metadata"]["component"]["name"], "node:20-alpine");
+        assert_eq!(bom["metadata"]["component"]["version"], "sha256:digest");
+
+        let components = bom["components"].as_array().unwrap();
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0]["name"], "@babel/core");
+        assert_eq!(components[0]["purl"], "pkg:npm/%40babel/core@7.23.0");
+        assert_eq!(components[1]["purl"], "pkg:apk/alpine/musl@1.2.4-r2");
+        assert_eq!(
+            components[1]["properties"],
+            json!([
+                {"name": "sysdig:package:type", "value": "os"},
+                {"name": "sysdig:package:path", "value": "/lib/apk/db/installed"},
+                {"name": "sysdig:layer:index", "value": "0"},
+                {"name": "sysdig:layer:digest", "value": "sha256:l0"},
+                {"name": "sysdig:package:inUse", "value": "true"},
+            ])
+        );
+    }
+
+    #[test]
+    fn it_describes_the_image_containing_the_packages_as_spdx() {
+        let document = serde_json::to_value(scan_result().to_spdx(created())).unwrap();
+
+        assert_eq!(document["spdxVersion"], "SPDX-2.3");
+        assert_eq!(document["dataLicense"], "CC0-1.0");
+        assert_eq!(
+            document["documentNamespace"],
+            "https://github.com/sysdiglabs/sysdig-lsp/spdx/sha256%3Aimage-1700000000"
+        );
+        assert_eq!(document["creationInfo"]["created"], "2023-11-14T22:13:20Z");
+
+        let packages = document["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0]["SPDXID"], "SPDXRef-Image");
+        assert_eq!(packages[0]["primaryPackagePurpose"], "CONTAINER");
+        assert_eq!(packages[2]["name"], "musl");
+        assert_eq!(
+            packages[2]["externalRefs"],
+            json!([{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": "pkg:apk/alpine/musl@1.2.4-r2",
+            }])
+        );
+        assert_eq!(
+            packages[2]["sourceInfo"],
+            "os package found at /lib/apk/db/installed in layer 0 (sha256:l0)"
+        );
+
+        assert_eq!(
+            document["relationships"],
+            json!([
+                {"spdxElementId": "SPDXRef-DOCUMENT", "relationshipType": "DESCRIBES", "relatedSpdxElement": "SPDXRef-Image"},
+                {"spdxElementId": "SPDXRef-Image", "relationshipType": "CONTAINS", "relatedSpdxElement": "SPDXRef-Package-0"},
+                {"spdxElementId": "SPDXRef-Image", "relationshipType": "CONTAINS", "relatedSpdxElement": "SPDXRef-Package-1"},
+            ])
+        );
+    }
+}
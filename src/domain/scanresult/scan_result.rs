@@ -1,7 +1,9 @@
 use crate::domain::scanresult::accepted_risk::AcceptedRisk;
 use crate::domain::scanresult::accepted_risk_reason::AcceptedRiskReason;
 use crate::domain::scanresult::architecture::Architecture;
+use crate::domain::scanresult::cvss::Cvss;
 use crate::domain::scanresult::evaluation_result::EvaluationResult;
+use crate::domain::scanresult::exploit::Exploit;
 use crate::domain::scanresult::layer::Layer;
 use crate::domain::scanresult::metadata::Metadata;
 use crate::domain::scanresult::operating_system::OperatingSystem;
@@ -14,7 +16,8 @@ use crate::domain::scanresult::severity::Severity;
 use crate::domain::scanresult::vulnerability::Vulnerability;
 use chrono::{DateTime, NaiveDate, Utc};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
 use std::sync::Arc;
 
 #[derive(PartialEq, Eq, Clone)]
@@ -30,6 +33,18 @@ pub struct ScanResult {
     global_evaluation: EvaluationResult,
 }
 
+impl Debug for ScanResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScanResult")
+            .field("pull_string", &self.metadata.pull_string())
+            .field("layers", &self.layers.len())
+            .field("packages", &self.packages.len())
+            .field("vulnerabilities", &self.vulnerabilities.len())
+            .field("global_evaluation", &self.global_evaluation)
+            .finish()
+    }
+}
+
 impl ScanResult {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -74,6 +89,27 @@ impl ScanResult {
         &self.metadata
     }
 
+    pub fn set_result_url(&mut self, result_url: String) {
+        self.metadata.set_result_url(result_url);
+    }
+
+    pub fn set_requested_pull_string(&mut self, requested_pull_string: String) {
+        self.metadata
+            .set_requested_pull_string(requested_pull_string);
+    }
+
+    pub fn add_parse_warning(&mut self, warning: String) {
+        self.metadata.add_parse_warning(warning);
+    }
+
+    pub fn set_scan_time(
+        &mut self,
+        scanned_at: DateTime<Utc>,
+        scan_duration: Option<std::time::Duration>,
+    ) {
+        self.metadata.set_scan_time(scanned_at, scan_duration);
+    }
+
     pub fn add_layer(
         &mut self,
         digest: String,
@@ -105,6 +141,7 @@ impl ScanResult {
             .collect()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn add_package(
         &mut self,
         package_type: PackageType,
@@ -112,6 +149,9 @@ impl ScanResult {
         version: String,
         path: String,
         found_in_layer: Arc<Layer>,
+        is_running: bool,
+        license: Option<String>,
+        suggested_fix: Option<String>,
     ) -> Arc<Package> {
         let a_package = Arc::new(Package::new(
             package_type,
@@ -119,6 +159,9 @@ impl ScanResult {
             version,
             path.clone(),
             found_in_layer.clone(),
+            is_running,
+            license,
+            suggested_fix,
         ));
         found_in_layer.add_package(a_package.clone());
 
@@ -133,6 +176,30 @@ impl ScanResult {
         self.packages.keys().cloned().collect()
     }
 
+    /// Packages grouped by license, sorted by name and version. Packages without a known
+    /// license are under `None`.
+    pub fn packages_by_license(&self) -> BTreeMap<Option<String>, Vec<Arc<Package>>> {
+        let mut packages_by_license: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for package in self
+            .packages()
+            .into_iter()
+            .sorted_by(|a, b| a.cmp_by_name_and_version(b))
+        {
+            packages_by_license
+                .entry(package.license().map(str::to_string))
+                .or_default()
+                .push(package);
+        }
+        packages_by_license
+    }
+
+    /// Whether the backend knows which packages are loaded at runtime (runtime
+    /// insights), i.e. whether packages not in use are known to be dormant.
+    pub fn has_runtime_context(&self) -> bool {
+        self.packages.keys().any(|p| p.is_running())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn add_vulnerability(
         &mut self,
         cve: String,
@@ -141,6 +208,8 @@ impl ScanResult {
         solution_date: Option<NaiveDate>,
         exploitable: bool,
         fix_version: Option<String>,
+        cvss: Option<Cvss>,
+        exploit: Option<Exploit>,
     ) -> Arc<Vulnerability> {
         self.vulnerabilities
             .entry(cve.clone())
@@ -152,6 +221,8 @@ impl ScanResult {
                     solution_date,
                     exploitable,
                     fix_version,
+                    cvss,
+                    exploit,
                 ))
             })
             .clone()
@@ -247,6 +318,12 @@ impl ScanResult {
     pub fn evaluation_result(&self) -> EvaluationResult {
         self.global_evaluation
     }
+
+    /// Marks the result as not evaluated against policies, for scans that couldn't
+    /// reach the backend holding them.
+    pub fn mark_policies_not_evaluated(&mut self) {
+        self.global_evaluation = EvaluationResult::NotEvaluated;
+    }
 }
 
 #[cfg(test)]
@@ -289,6 +366,52 @@ mod tests {
         assert!(scan_result.accepted_risks().is_empty());
     }
 
+    #[test]
+    fn groups_packages_by_license() {
+        let mut scan_result = create_scan_result();
+        let layer = scan_result.add_layer("sha256:abc".to_string(), 0, None, "CMD".to_string());
+        for (name, license) in [
+            ("zlib", Some("Zlib")),
+            ("musl", Some("MIT")),
+            ("busybox", Some("GPL-2.0-only")),
+            ("apk-tools", Some("GPL-2.0-only")),
+            ("ca-certificates", None),
+        ] {
+            scan_result.add_package(
+                PackageType::Os,
+                name.to_string(),
+                "1.0".to_string(),
+                "/lib/apk/db/installed".to_string(),
+                layer.clone(),
+                false,
+                license.map(str::to_string),
+                None,
+            );
+        }
+
+        let names: Vec<_> = scan_result
+            .packages_by_license()
+            .into_iter()
+            .map(|(license, packages)| {
+                let names: Vec<_> = packages.iter().map(|p| p.name().to_string()).collect();
+                (license, names)
+            })
+            .collect();
+
+        assert_eq!(
+            names,
+            [
+                (None, vec!["ca-certificates".to_string()]),
+                (
+                    Some("GPL-2.0-only".to_string()),
+                    vec!["apk-tools".to_string(), "busybox".to_string()]
+                ),
+                (Some("MIT".to_string()), vec!["musl".to_string()]),
+                (Some("Zlib".to_string()), vec!["zlib".to_string()]),
+            ]
+        );
+    }
+
     #[test]
     fn add_and_find_layer() {
         let mut scan_result = create_scan_result();
@@ -316,6 +439,9 @@ mod tests {
             "1.2.3".to_string(),
             "/lib/ld-musl-x86_64.so.1".to_string(),
             layer.clone(),
+            false,
+            None,
+            None,
         );
 
         assert_eq!(scan_result.packages().len(), 1);
@@ -337,6 +463,8 @@ mod tests {
             None,
             false,
             Some("1.2.4".to_string()),
+            None,
+            None,
         );
 
         assert_eq!(scan_result.vulnerabilities().len(), 1);
@@ -360,6 +488,9 @@ mod tests {
             "1.2.3".to_string(),
             "/lib/ld-musl-x86_64.so.1".to_string(),
             layer.clone(),
+            false,
+            None,
+            None,
         );
         let vuln = scan_result.add_vulnerability(
             "CVE-2023-1234".to_string(),
@@ -368,6 +499,8 @@ mod tests {
             None,
             false,
             Some("1.2.4".to_string()),
+            None,
+            None,
         );
 
         package.add_vulnerability_found(vuln.clone());
@@ -463,6 +596,8 @@ mod tests {
             None,
             false,
             Some("1.2.4".to_string()),
+            None,
+            None,
         );
 
         vuln.add_accepted_risk(risk.clone());
@@ -491,6 +626,9 @@ mod tests {
             "1.2.3".to_string(),
             "/lib/ld-musl-x86_64.so.1".to_string(),
             layer.clone(),
+            false,
+            None,
+            None,
         );
 
         package.add_accepted_risk(risk.clone());
@@ -574,6 +712,9 @@ mod tests {
             "1.2.3".to_string(),
             "/path".to_string(),
             layer.clone(),
+            false,
+            None,
+            None,
         );
         assert_eq!(package.package_type(), &PackageType::Os);
         assert_eq!(package.name(), "musl");
@@ -590,6 +731,8 @@ mod tests {
             Some(now.naive_utc().date()),
             true,
             Some("1.2.4".to_string()),
+            None,
+            None,
         );
         assert_eq!(vuln.cve(), "CVE-1");
         assert_eq!(vuln.severity(), Severity::High);
@@ -650,6 +793,8 @@ mod tests {
             None,
             false,
             None,
+            None,
+            None,
         );
         let vuln2 = scan_result.add_vulnerability(
             "CVE-1".to_string(),
@@ -658,6 +803,8 @@ mod tests {
             None,
             false,
             None,
+            None,
+            None,
         );
         assert_eq!(Arc::as_ptr(&vuln), Arc::as_ptr(&vuln2));
         assert_eq!(scan_result.vulnerabilities().len(), 1);
@@ -675,6 +822,9 @@ mod tests {
             "1.0.0".to_string(),
             "/path".to_string(),
             layer.clone(),
+            false,
+            None,
+            None,
         );
         let pkg2 = scan_result.add_package(
             PackageType::Os,
@@ -682,6 +832,9 @@ mod tests {
             "1.0.0".to_string(),
             "/path".to_string(),
             layer.clone(),
+            false,
+            None,
+            None,
         );
         assert_eq!(Arc::as_ptr(&pkg), Arc::as_ptr(&pkg2));
         assert_eq!(scan_result.packages().len(), 1);
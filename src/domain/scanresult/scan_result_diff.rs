@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use itertools::Itertools;
+
+use crate::domain::scanresult::evaluation_result::EvaluationResult;
+use crate::domain::scanresult::package::Package;
+use crate::domain::scanresult::scan_result::ScanResult;
+use crate::domain::scanresult::vulnerability::Vulnerability;
+
+/// Delta between two scan results: the same image scanned again later, or a derived
+/// image against its base image. Vulnerabilities are matched by CVE and sorted by it,
+/// packages are matched by type, name, version and path and sorted by name.
+#[derive(Debug)]
+pub struct ScanResultDiff {
+    added_vulnerabilities: Vec<Arc<Vulnerability>>,
+    removed_vulnerabilities: Vec<Arc<Vulnerability>>,
+    unchanged_vulnerabilities: Vec<Arc<Vulnerability>>,
+    added_packages: Vec<Arc<Package>>,
+    removed_packages: Vec<Arc<Package>>,
+    evaluation_result: EvaluationResultChange,
+    policy_changes: Vec<PolicyStatusChange>,
+}
+
+/// Policy evaluation of the old scan result (`before`) and the new one (`after`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvaluationResultChange {
+    pub before: EvaluationResult,
+    pub after: EvaluationResult,
+}
+
+impl EvaluationResultChange {
+    pub fn is_changed(&self) -> bool {
+        self.before != self.after
+    }
+}
+
+/// A policy evaluated in both scan results whose status changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyStatusChange {
+    pub policy_name: String,
+    pub change: EvaluationResultChange,
+}
+
+impl ScanResultDiff {
+    /// Vulnerabilities of the new scan result that the old one didn't have.
+    pub fn added_vulnerabilities(&self) -> &[Arc<Vulnerability>] {
+        &self.added_vulnerabilities
+    }
+
+    /// Vulnerabilities of the old scan result that the new one doesn't have anymore.
+    pub fn removed_vulnerabilities(&self) -> &[Arc<Vulnerability>] {
+        &self.removed_vulnerabilities
+    }
+
+    /// Vulnerabilities present in both scan results, as found in the new one.
+    pub fn unchanged_vulnerabilities(&self) -> &[Arc<Vulnerability>] {
+        &self.unchanged_vulnerabilities
+    }
+
+    /// Packages of the new scan result that the old one didn't have, e.g. upgraded ones.
+    pub fn added_packages(&self) -> &[Arc<Package>] {
+        &self.added_packages
+    }
+
+    /// Packages of the old scan result that the new one doesn't have anymore.
+    pub fn removed_packages(&self) -> &[Arc<Package>] {
+        &self.removed_packages
+    }
+
+    /// Overall policy evaluation of both scan results.
+    pub fn evaluation_result(&self) -> EvaluationResultChange {
+        self.evaluation_result
+    }
+
+    /// Policies whose status differs between both scan results, sorted by name.
+    pub fn policy_changes(&self) -> &[PolicyStatusChange] {
+        &self.policy_changes
+    }
+
+    /// Whether both scan results have the same vulnerabilities, packages and policy status.
+    pub fn is_empty(&self) -> bool {
+        self.added_vulnerabilities.is_empty()
+            && self.removed_vulnerabilities.is_empty()
+            && self.added_packages.is_empty()
+            && self.removed_packages.is_empty()
+            && !self.evaluation_result.is_changed()
+            && self.policy_changes.is_empty()
+    }
+
+    pub fn is_added(&self, cve: &str) -> bool {
+        self.added_vulnerabilities
+            .binary_search_by(|v| v.cve().cmp(cve))
+            .is_ok()
+    }
+}
+
+impl ScanResult {
+    /// Compares this (newer, or derived) scan result against `other` (older, or base).
+    pub fn diff(&self, other: &ScanResult) -> ScanResultDiff {
+        let (unchanged_vulnerabilities, added_vulnerabilities) = self
+            .vulnerabilities()
+            .into_iter()
+            .sorted_by(|a, b| a.cve().cmp(b.cve()))
+            .partition(|v| other.find_vulnerability_by_cve(v.cve()).is_some());
+        let removed_vulnerabilities = other
+            .vulnerabilities()
+            .into_iter()
+            .filter(|v| self.find_vulnerability_by_cve(v.cve()).is_none())
+            .sorted_by(|a, b| a.cve().cmp(b.cve()))
+            .collect();
+
+        let packages_missing_from = |packages: Vec<Arc<Package>>, other: &ScanResult| {
+            let other_packages = other.packages();
+            packages
+                .into_iter()
+                .filter(|p| !other_packages.contains(p))
+                .sorted_by(|a, b| a.cmp_by_name_and_version(b))
+                .collect()
+        };
+
+        let policy_changes = self
+            .policies()
+            .into_iter()
+            .filter_map(|policy| {
+                let before = other.find_policy_by_id(policy.id())?.evaluation_result();
+                let after = policy.evaluation_result();
+                (before != after).then(|| PolicyStatusChange {
+                    policy_name: policy.name().to_string(),
+                    change: EvaluationResultChange { before, after },
+                })
+            })
+            .sorted_by(|a, b| a.policy_name.cmp(&b.policy_name))
+            .collect();
+
+        ScanResultDiff {
+            added_vulnerabilities,
+            removed_vulnerabilities,
+            unchanged_vulnerabilities,
+            added_packages: packages_missing_from(self.packages(), other),
+            removed_packages: packages_missing_from(other.packages(), self),
+            evaluation_result: EvaluationResultChange {
+                before: other.evaluation_result(),
+                after: self.evaluation_result(),
+            },
+            policy_changes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::{NaiveDate, Utc};
+
+    use super::*;
+    use crate::domain::scanresult::architecture::Architecture;
+    use crate::domain::scanresult::evaluation_result::EvaluationResult;
+    use crate::domain::scanresult::operating_system::{Family, OperatingSystem};
+    use crate::domain::scanresult::package_type::PackageType;
+    use crate::domain::scanresult::scan_type::ScanType;
+    use crate::domain::scanresult::severity::Severity;
+
+    fn scan_result_with(cves: &[&str]) -> ScanResult {
+        scan_result_evaluated(cves, EvaluationResult::Passed)
+    }
+
+    fn scan_result_evaluated(cves: &[&str], evaluation_result: EvaluationResult) -> ScanResult {
+        let mut scan_result = ScanResult::new(
+            ScanType::Docker,
+            "alpine:latest".to_string(),
+            "sha256:12345".to_string(),
+            None,
+            OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+            123456,
+            Architecture::Amd64,
+            HashMap::new(),
+            Utc::now(),
+            evaluation_result,
+        );
+        for cve in cves {
+            scan_result.add_vulnerability(
+                cve.to_string(),
+                Severity::High,
+                NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+                None,
+                false,
+                None,
+                None,
+                None,
+            );
+        }
+        scan_result
+    }
+
+    fn cves(vulnerabilities: &[Arc<Vulnerability>]) -> Vec<&str> {
+        vulnerabilities.iter().map(|v| v.cve()).collect()
+    }
+
+    #[test]
+    fn it_splits_vulnerabilities_into_added_removed_and_unchanged() {
+        let base = scan_result_with(&["CVE-1", "CVE-2", "CVE-3"]);
+        let derived = scan_result_with(&["CVE-4", "CVE-2", "CVE-5", "CVE-3"]);
+
+        let diff = derived.diff(&base);
+
+        assert_eq!(cves(diff.added_vulnerabilities()), ["CVE-4", "CVE-5"]);
+        assert_eq!(cves(diff.removed_vulnerabilities()), ["CVE-1"]);
+        assert_eq!(cves(diff.unchanged_vulnerabilities()), ["CVE-2", "CVE-3"]);
+        assert!(diff.is_added("CVE-4"));
+        assert!(!diff.is_added("CVE-2"));
+    }
+
+    #[test]
+    fn it_finds_no_changes_between_identical_results() {
+        let diff = scan_result_with(&["CVE-1"]).diff(&scan_result_with(&["CVE-1"]));
+
+        assert!(diff.added_vulnerabilities().is_empty());
+        assert!(diff.removed_vulnerabilities().is_empty());
+        assert_eq!(cves(diff.unchanged_vulnerabilities()), ["CVE-1"]);
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn it_finds_added_and_removed_packages() {
+        let add_package = |scan_result: &mut ScanResult, name: &str, version: &str| {
+            let layer = scan_result.add_layer("sha256:abc".to_string(), 0, None, "RUN".to_string());
+            scan_result.add_package(
+                PackageType::Os,
+                name.to_string(),
+                version.to_string(),
+                format!("/usr/lib/{name}"),
+                layer,
+                false,
+                None,
+                None,
+            );
+        };
+        let mut old = scan_result_with(&[]);
+        add_package(&mut old, "openssl", "3.1.4");
+        add_package(&mut old, "zlib", "1.3");
+        let mut new = scan_result_with(&[]);
+        add_package(&mut new, "openssl", "3.1.5");
+        add_package(&mut new, "zlib", "1.3");
+
+        let diff = new.diff(&old);
+
+        let packages = |packages: &[Arc<Package>]| -> Vec<String> {
+            packages
+                .iter()
+                .map(|p| format!("{}@{}", p.name(), p.version()))
+                .collect()
+        };
+        assert_eq!(packages(diff.added_packages()), ["openssl@3.1.5"]);
+        assert_eq!(packages(diff.removed_packages()), ["openssl@3.1.4"]);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn it_reports_the_policy_status_change() {
+        let old = scan_result_evaluated(&[], EvaluationResult::Passed);
+        let new = scan_result_evaluated(&[], EvaluationResult::Failed);
+
+        let diff = new.diff(&old);
+
+        assert_eq!(
+            diff.evaluation_result(),
+            EvaluationResultChange {
+                before: EvaluationResult::Passed,
+                after: EvaluationResult::Failed
+            }
+        );
+        assert!(diff.evaluation_result().is_changed());
+        assert!(diff.policy_changes().is_empty());
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ScanType {
     Docker,
 }
@@ -1,6 +1,10 @@
-use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Debug, Serialize, Deserialize)]
 pub enum Severity {
     Critical,
     High,
@@ -26,3 +30,37 @@ impl Display for Severity {
         )
     }
 }
+
+impl FromStr for Severity {
+    type Err = String;
+
+    /// Parses the severities vulnerabilities are reported with, ignoring case, e.g. to
+    /// take a severity threshold from the command line.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Severity::Critical,
+            Severity::High,
+            Severity::Medium,
+            Severity::Low,
+            Severity::Negligible,
+        ]
+        .into_iter()
+        .find(|severity| severity.to_string().eq_ignore_ascii_case(s))
+        .ok_or_else(|| {
+            format!("unknown severity '{s}', expected critical, high, medium, low or negligible")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_the_reported_severities_ignoring_case() {
+        assert_eq!("high".parse::<Severity>(), Ok(Severity::High));
+        assert_eq!("Critical".parse::<Severity>(), Ok(Severity::Critical));
+        assert!("unknown".parse::<Severity>().is_err());
+        assert!("severe".parse::<Severity>().is_err());
+    }
+}
@@ -1,4 +1,6 @@
 use crate::domain::scanresult::accepted_risk::AcceptedRisk;
+use crate::domain::scanresult::cvss::Cvss;
+use crate::domain::scanresult::exploit::Exploit;
 use crate::domain::scanresult::layer::Layer;
 use crate::domain::scanresult::package::Package;
 use crate::domain::scanresult::severity::Severity;
@@ -16,6 +18,8 @@ pub struct Vulnerability {
     solution_date: Option<NaiveDate>,
     exploitable: bool,
     fix_version: Option<String>,
+    cvss: Option<Cvss>,
+    exploit: Option<Exploit>,
     found_in_packages: RwLock<HashSet<WeakHash<Package>>>,
     accepted_risks: RwLock<HashSet<WeakHash<AcceptedRisk>>>,
 }
@@ -29,11 +33,14 @@ impl Debug for Vulnerability {
             .field("solution_date", &self.solution_date)
             .field("exploitable", &self.exploitable)
             .field("fix_version", &self.fix_version)
+            .field("cvss", &self.cvss)
+            .field("exploit", &self.exploit)
             .finish()
     }
 }
 
 impl Vulnerability {
+    #[allow(clippy::too_many_arguments)]
     pub(in crate::domain::scanresult) fn new(
         cve: String,
         severity: Severity,
@@ -41,6 +48,8 @@ impl Vulnerability {
         solution_date: Option<NaiveDate>,
         exploitable: bool,
         fix_version: Option<String>,
+        cvss: Option<Cvss>,
+        exploit: Option<Exploit>,
     ) -> Self {
         Self {
             cve,
@@ -49,6 +58,8 @@ impl Vulnerability {
             solution_date,
             exploitable,
             fix_version,
+            cvss,
+            exploit,
             found_in_packages: RwLock::new(HashSet::new()),
             accepted_risks: RwLock::new(HashSet::new()),
         }
@@ -82,6 +93,26 @@ impl Vulnerability {
         self.fix_version.as_ref()
     }
 
+    pub fn cvss(&self) -> Option<&Cvss> {
+        self.cvss.as_ref()
+    }
+
+    /// CVSS base score (0.0 to 10.0), when the scanner reports one.
+    pub fn cvss_score(&self) -> Option<f32> {
+        self.cvss.as_ref().map(Cvss::score)
+    }
+
+    pub fn exploit(&self) -> Option<&Exploit> {
+        self.exploit.as_ref()
+    }
+
+    /// Whether the vulnerability is in the CISA Known Exploited Vulnerabilities catalog.
+    pub fn known_exploited(&self) -> bool {
+        self.exploit
+            .as_ref()
+            .is_some_and(|exploit| exploit.cisa_kev().is_some())
+    }
+
     pub(in crate::domain::scanresult) fn add_found_in_package(
         self: &Arc<Self>,
         a_package: Arc<Package>,
@@ -105,6 +136,11 @@ impl Vulnerability {
             .collect()
     }
 
+    /// Whether any package with this vulnerability is loaded at runtime.
+    pub fn in_use(&self) -> bool {
+        self.found_in_packages().iter().any(|p| p.is_running())
+    }
+
     pub fn found_in_layers(&self) -> Vec<Arc<Layer>> {
         self.found_in_packages()
             .iter()
@@ -0,0 +1,271 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use globset::{GlobBuilder, GlobMatcher};
+use thiserror::Error;
+
+/// Ignore files looked up in the build context, in order of precedence.
+const IGNORE_FILES: [&str; 2] = [".dockerignore", ".containerignore"];
+
+#[derive(Error, Debug)]
+pub(in crate::infra) enum BuildContextError {
+    #[error("io error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("invalid pattern in {file}: {source}")]
+    InvalidIgnorePattern {
+        file: &'static str,
+        source: globset::Error,
+    },
+}
+
+struct IgnorePattern {
+    matcher: GlobMatcher,
+    /// `!pattern`: re-includes paths excluded by previous patterns.
+    negated: bool,
+}
+
+/// Patterns of a `.dockerignore` file, with Docker semantics: paths are relative to the
+/// context root, `*` doesn't cross directories while `**` does, a pattern matching a
+/// directory excludes its contents too, and the last matching pattern wins.
+#[derive(Default)]
+pub(in crate::infra) struct DockerIgnore {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl DockerIgnore {
+    pub fn parse(content: &str) -> Result<Self, globset::Error> {
+        let patterns = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let (negated, pattern) = match line.strip_prefix('!') {
+                    Some(pattern) => (true, pattern.trim()),
+                    None => (false, line),
+                };
+                let pattern = pattern.trim_start_matches("./").trim_start_matches('/');
+                let pattern = pattern.trim_end_matches('/');
+
+                Ok(IgnorePattern {
+                    matcher: GlobBuilder::new(pattern)
+                        .literal_separator(true)
+                        .backslash_escape(true)
+                        .build()?
+                        .compile_matcher(),
+                    negated,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { patterns })
+    }
+
+    /// Reads the ignore file of the context directory, if there is any.
+    fn from_context_dir(context_dir: &Path) -> Result<Self, BuildContextError> {
+        for file in IGNORE_FILES {
+            let path = context_dir.join(file);
+            if path.is_file() {
+                let content = fs::read_to_string(path)?;
+                return Self::parse(&content)
+                    .map_err(|source| BuildContextError::InvalidIgnorePattern { file, source });
+            }
+        }
+        Ok(Self::default())
+    }
+
+    pub fn is_excluded(&self, relative_path: &Path) -> bool {
+        let mut excluded = false;
+        for pattern in &self.patterns {
+            let matches = relative_path
+                .ancestors()
+                .filter(|path| !path.as_os_str().is_empty())
+                .any(|path| pattern.matcher.is_match(path));
+            if matches {
+                excluded = !pattern.negated;
+            }
+        }
+        excluded
+    }
+
+    fn has_negations(&self) -> bool {
+        self.patterns.iter().any(|pattern| pattern.negated)
+    }
+}
+
+/// Tars the directory of the containerfile to be sent as the build context, leaving out
+/// the paths excluded by its `.dockerignore` (or `.containerignore`). The containerfile
+/// itself is always included, as the daemon needs it to build.
+pub(in crate::infra) fn pack_build_context(
+    containerfile: &Path,
+) -> Result<Vec<u8>, BuildContextError> {
+    let Some(context_dir) = containerfile.parent() else {
+        return Err(BuildContextError::IO(std::io::Error::other(
+            "unable to find parent for provided containerfile",
+        )));
+    };
+    // A bare file name has an empty parent: the context is the current directory.
+    let context_dir = if context_dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        context_dir
+    };
+
+    let ignore = DockerIgnore::from_context_dir(context_dir)?;
+    let containerfile_name = containerfile.file_name().map(PathBuf::from);
+
+    let mut tar_builder = tar::Builder::new(Vec::new());
+    append_dir_contents(
+        &mut tar_builder,
+        context_dir,
+        Path::new(""),
+        &ignore,
+        containerfile_name.as_deref(),
+    )?;
+    tar_builder.finish()?;
+
+    Ok(tar_builder.into_inner()?)
+}
+
+fn append_dir_contents(
+    tar_builder: &mut tar::Builder<Vec<u8>>,
+    dir: &Path,
+    relative_dir: &Path,
+    ignore: &DockerIgnore,
+    containerfile: Option<&Path>,
+) -> Result<(), BuildContextError> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    // Sorted so the same context always produces the same tar.
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let relative_path = relative_dir.join(entry.file_name());
+        let excluded =
+            ignore.is_excluded(&relative_path) && Some(relative_path.as_path()) != containerfile;
+
+        if path.is_dir() {
+            // Negations may re-include something inside an excluded directory.
+            if excluded && !ignore.has_negations() {
+                continue;
+            }
+            if !excluded {
+                tar_builder.append_dir(&relative_path, &path)?;
+            }
+            append_dir_contents(tar_builder, &path, &relative_path, ignore, containerfile)?;
+        } else if !excluded {
+            tar_builder.append_path_with_name(&path, &relative_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    fn excluded(dockerignore: &str, path: &str) -> bool {
+        DockerIgnore::parse(dockerignore)
+            .unwrap()
+            .is_excluded(Path::new(path))
+    }
+
+    #[test]
+    fn patterns_are_anchored_to_the_context_root() {
+        assert!(excluded("target", "target/debug/app"));
+        assert!(!excluded("target", "crates/a/target/debug/app"));
+        assert!(excluded("/target/", "target"));
+    }
+
+    #[test]
+    fn double_stars_match_nested_paths_and_single_stars_do_not() {
+        assert!(excluded("**/*.log", "logs/2024/app.log"));
+        assert!(excluded("**/*.log", "app.log"));
+        assert!(excluded("*/temp*", "cache/temporary"));
+        assert!(!excluded("*/temp*", "a/b/temporary"));
+    }
+
+    #[test]
+    fn the_last_matching_pattern_wins() {
+        let dockerignore = "# comment\n*.md\n!README*.md\nREADME-secret.md\n";
+
+        assert!(excluded(dockerignore, "CHANGELOG.md"));
+        assert!(!excluded(dockerignore, "README.md"));
+        assert!(excluded(dockerignore, "README-secret.md"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(DockerIgnore::parse("[unclosed").is_err());
+    }
+
+    fn packed_paths(containerfile: &Path) -> Vec<String> {
+        let tar_contents = pack_build_context(containerfile).unwrap();
+        let mut archive = tar::Archive::new(tar_contents.as_slice());
+        archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path()
+                    .unwrap()
+                    .to_string_lossy()
+                    .trim_end_matches('/')
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_packs_the_context_without_ignored_paths() {
+        let context = tempfile::tempdir().unwrap();
+        let root = context.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("node_modules/lib")).unwrap();
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("Dockerfile"), "FROM alpine").unwrap();
+        fs::write(
+            root.join(".dockerignore"),
+            "Dockerfile\nnode_modules\ndocs\n!docs/keep.md\n**/*.tmp\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("src/nested/cache.tmp"), "").unwrap();
+        fs::write(root.join("node_modules/lib/index.js"), "").unwrap();
+        fs::write(root.join("docs/keep.md"), "").unwrap();
+        fs::write(root.join("docs/drop.md"), "").unwrap();
+
+        let paths = packed_paths(&root.join("Dockerfile"));
+
+        assert_eq!(
+            paths,
+            vec![
+                ".dockerignore",
+                "Dockerfile",
+                "docs/keep.md",
+                "src",
+                "src/main.rs",
+                "src/nested",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_containerignore() {
+        let context = tempfile::tempdir().unwrap();
+        let root = context.path();
+        fs::write(root.join("Containerfile"), "FROM alpine").unwrap();
+        fs::write(root.join(".containerignore"), "secret.txt\n").unwrap();
+        fs::write(root.join("secret.txt"), "").unwrap();
+
+        let paths = packed_paths(&root.join("Containerfile"));
+
+        assert_eq!(paths, vec![".containerignore", "Containerfile"]);
+    }
+}
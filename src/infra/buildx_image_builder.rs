@@ -0,0 +1,261 @@
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+};
+
+use crate::app::{
+    BuildLogSender, ImageBuildError, ImageBuildOptions, ImageBuildResult, ImageBuilder,
+};
+
+use super::docker_image_builder::{
+    DockerImageBuilder, DockerImageBuilderError, reserve_temporary_image_name,
+};
+
+/// Lines of the end of the build output kept to explain why a build failed.
+const FAILED_BUILD_LOG_TAIL_LINES: usize = 20;
+
+/// Builds images with BuildKit by running `docker buildx build`, since the build API
+/// of the daemon only runs the legacy builder. The images are loaded into the same
+/// daemon, so they're inspected and removed through the Docker API as usual.
+pub struct BuildxImageBuilder {
+    docker: DockerImageBuilder,
+    docker_host: String,
+    docker_tls_cert_path: Option<PathBuf>,
+}
+
+impl BuildxImageBuilder {
+    pub fn new(
+        docker: DockerImageBuilder,
+        docker_host: String,
+        docker_tls_cert_path: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            docker,
+            docker_host,
+            docker_tls_cert_path,
+        }
+    }
+
+    async fn build_image_with_buildx(
+        &self,
+        containerfile: &Path,
+        options: &ImageBuildOptions,
+        build_log: BuildLogSender,
+    ) -> Result<ImageBuildResult, DockerImageBuilderError> {
+        let dockerfile_name = containerfile
+            .file_name()
+            .and_then(|osstr| osstr.to_str())
+            .ok_or_else(|| {
+                DockerImageBuilderError::Generic(
+                    "invalid containerfile path: unable to extract filename".to_string(),
+                )
+            })?;
+        let (image_name, in_use) = reserve_temporary_image_name(dockerfile_name);
+
+        // The values of the build args are handed over in the environment, so they don't
+        // show up in the arguments of the process. The daemon set afterwards wins over any
+        // build arg of the same name.
+        let mut env_vars: Vec<(&str, &str)> = options
+            .build_args
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        env_vars.push(("DOCKER_HOST", self.docker_host.as_str()));
+        if let Some(cert_path) = self.docker_tls_cert_path.as_ref().and_then(|p| p.to_str()) {
+            env_vars.push(("DOCKER_TLS_VERIFY", "1"));
+            env_vars.push(("DOCKER_CERT_PATH", cert_path));
+        }
+
+        let mut child = Command::new("docker")
+            .args(buildx_args(containerfile, &image_name, options))
+            .envs(env_vars)
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            // Don't leave the build running if the LSP request is cancelled.
+            .kill_on_drop(true)
+            .spawn()?;
+        let Some(stderr) = child.stderr.take() else {
+            return Err(std::io::Error::other("the build output isn't piped").into());
+        };
+
+        let mut log_tail = VecDeque::with_capacity(FAILED_BUILD_LOG_TAIL_LINES);
+        let mut lines = BufReader::new(stderr).lines();
+        while let Some(line) = lines.next_line().await? {
+            if log_tail.len() == FAILED_BUILD_LOG_TAIL_LINES {
+                log_tail.pop_front();
+            }
+            log_tail.push_back(line.clone());
+            // The build goes on even if nobody listens to its log anymore.
+            let _ = build_log.send(line);
+        }
+
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(DockerImageBuilderError::Generic(format!(
+                "docker buildx build failed ({status}): {}",
+                Vec::from(log_tail).join("\n")
+            )));
+        }
+
+        Ok(self
+            .docker
+            .with_image_details(ImageBuildResult {
+                image_name,
+                in_use: Some(in_use),
+                ..Default::default()
+            })
+            .await)
+    }
+}
+
+/// Arguments of `docker buildx build` to build `containerfile` into `image_name`, loading
+/// it into the daemon. The context is the directory of the Containerfile, as with the
+/// legacy builder. The build args are passed by name only, so their values are taken
+/// from the environment of the process.
+fn buildx_args(
+    containerfile: &Path,
+    image_name: &str,
+    options: &ImageBuildOptions,
+) -> Vec<OsString> {
+    let context = containerfile
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let mut args: Vec<OsString> = vec![
+        "buildx".into(),
+        "build".into(),
+        "--load".into(),
+        "--progress=plain".into(),
+        "--tag".into(),
+        image_name.into(),
+        "--file".into(),
+        containerfile.into(),
+    ];
+    let mut build_arg_names = options.build_args.keys().collect::<Vec<_>>();
+    build_arg_names.sort();
+    for name in build_arg_names {
+        args.push("--build-arg".into());
+        args.push(name.into());
+    }
+    if let Some(target) = &options.target {
+        args.push("--target".into());
+        args.push(target.into());
+    }
+    args.push(context.into());
+    args
+}
+
+#[async_trait::async_trait]
+impl ImageBuilder for BuildxImageBuilder {
+    async fn build_image(
+        &self,
+        containerfile: &Path,
+        options: &ImageBuildOptions,
+        build_log: BuildLogSender,
+    ) -> Result<ImageBuildResult, ImageBuildError> {
+        Ok(self
+            .build_image_with_buildx(containerfile, options, build_log)
+            .await?)
+    }
+
+    async fn remove_image(&self, image_name: &str) -> Result<(), ImageBuildError> {
+        self.docker.remove_image(image_name).await
+    }
+
+    async fn remove_temporary_images(&self) -> Result<Vec<String>, ImageBuildError> {
+        self.docker.remove_temporary_images().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, path::Path};
+
+    use super::buildx_args;
+    use crate::{
+        app::{ImageBuildOptions, ImageBuilder},
+        infra::{BuildxImageBuilder, DockerImageBuilder, connect_to_docker},
+    };
+
+    #[test]
+    fn it_passes_the_build_args_by_name_only() {
+        let options = ImageBuildOptions::default()
+            .with_build_args([("NPM_TOKEN", "secret"), ("NODE_VERSION", "20")])
+            .with_target(Some("builder".to_string()));
+
+        let args = buildx_args(Path::new("app/Dockerfile"), "image", &options);
+
+        let expected: Vec<OsString> = [
+            "buildx",
+            "build",
+            "--load",
+            "--progress=plain",
+            "--tag",
+            "image",
+            "--file",
+            "app/Dockerfile",
+            "--build-arg",
+            "NODE_VERSION",
+            "--build-arg",
+            "NPM_TOKEN",
+            "--target",
+            "builder",
+            "app",
+        ]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        assert_eq!(args, expected);
+    }
+
+    #[test]
+    fn it_builds_in_the_current_directory_for_a_bare_containerfile() {
+        let args = buildx_args(
+            Path::new("Dockerfile"),
+            "image",
+            &ImageBuildOptions::default(),
+        );
+
+        assert_eq!(args.last(), Some(&OsString::from(".")));
+    }
+
+    #[tokio::test]
+    async fn it_builds_a_buildkit_only_dockerfile() {
+        let docker_connection = connect_to_docker().unwrap();
+        let image_builder = BuildxImageBuilder::new(
+            DockerImageBuilder::new(docker_connection.client),
+            docker_connection.socket_path,
+            docker_connection.tls_cert_path,
+        );
+
+        let (build_log, mut build_log_lines) = tokio::sync::mpsc::unbounded_channel();
+        let image_built = image_builder
+            .build_image(
+                Path::new("tests/fixtures/BuildKit.dockerfile"),
+                &ImageBuildOptions::default(),
+                build_log,
+            )
+            .await
+            .unwrap();
+
+        assert!(
+            image_built
+                .image_name
+                .starts_with("sysdig-lsp-image-build-buildkit-dockerfile-")
+        );
+        assert!(!image_built.image_id.is_empty());
+        assert!(build_log_lines.recv().await.is_some());
+        image_builder
+            .remove_image(&image_built.image_name)
+            .await
+            .unwrap();
+    }
+}
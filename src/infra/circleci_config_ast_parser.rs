@@ -0,0 +1,179 @@
+use thiserror::Error;
+use tower_lsp::lsp_types::{Position, Range};
+
+#[derive(Debug, PartialEq)]
+pub struct ImageInstruction {
+    pub image_name: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("Invalid yaml: {0}")]
+    InvalidYaml(marked_yaml::LoadError),
+}
+
+/// Extracts the images of the Docker executors of a `.circleci/config.yml`
+/// (`docker: - image: ...`), declared in jobs or in reusable `executors`.
+pub fn parse_circleci_config(content: &str) -> Result<Vec<ImageInstruction>, ParseError> {
+    let mut instructions = Vec::new();
+
+    let node = marked_yaml::parse_yaml(0, content).map_err(ParseError::InvalidYaml)?;
+    find_images_recursive(&node, &mut instructions, content);
+
+    Ok(instructions)
+}
+
+fn find_images_recursive(
+    node: &marked_yaml::Node,
+    instructions: &mut Vec<ImageInstruction>,
+    content: &str,
+) {
+    match node {
+        marked_yaml::Node::Mapping(map) => {
+            for (key, value) in map.iter() {
+                if key.as_str() == "docker" {
+                    find_docker_images(value, instructions, content);
+                } else {
+                    find_images_recursive(value, instructions, content);
+                }
+            }
+        }
+        marked_yaml::Node::Sequence(seq) => {
+            for item in seq.iter() {
+                find_images_recursive(item, instructions, content);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The first image is the primary container, the rest are service containers.
+fn find_docker_images(
+    node: &marked_yaml::Node,
+    instructions: &mut Vec<ImageInstruction>,
+    content: &str,
+) {
+    let marked_yaml::Node::Sequence(containers) = node else {
+        return;
+    };
+
+    for container in containers.iter() {
+        let marked_yaml::Node::Mapping(container_map) = container else {
+            continue;
+        };
+
+        if let Some(image_node) = container_map.get("image")
+            && let Some(instruction) = try_create_image_instruction(image_node, content)
+        {
+            instructions.push(instruction);
+        }
+    }
+}
+
+fn try_create_image_instruction(
+    node: &marked_yaml::Node,
+    content: &str,
+) -> Option<ImageInstruction> {
+    let marked_yaml::Node::Scalar(scalar) = node else {
+        return None;
+    };
+
+    let image_name = scalar.as_str().trim().to_string();
+    if !is_valid_image_name(&image_name) {
+        return None;
+    }
+
+    let start = node.span().start()?;
+
+    let range = calculate_range(start, &image_name, content);
+    Some(ImageInstruction { image_name, range })
+}
+
+/// Images built from parameters (e.g. `cimg/node:<< parameters.version >>`) are only known when the pipeline runs.
+fn is_valid_image_name(name: &str) -> bool {
+    !name.is_empty() && name != "null" && !name.contains("<<")
+}
+
+fn calculate_range(start: &marked_yaml::Marker, image_name: &str, content: &str) -> Range {
+    let start_line = start.line() as u32 - 1;
+    let start_char = start.column() as u32 - 1;
+
+    let start_line_content = content.lines().nth(start_line as usize).unwrap_or("");
+    let first_char = start_line_content.chars().nth(start_char as usize);
+
+    let mut raw_len = image_name.len();
+    if let Some(c) = first_char
+        && (c == '"' || c == '\'')
+    {
+        raw_len += 2;
+    }
+
+    let end_char = start_char + raw_len as u32;
+
+    Range {
+        start: Position {
+            line: start_line,
+            character: start_char,
+        },
+        end: Position {
+            line: start_line,
+            character: end_char,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CIRCLECI_CONFIG: &str = r#"version: 2.1
+
+executors:
+  node:
+    parameters:
+      version:
+        type: string
+    docker:
+      - image: cimg/node:<< parameters.version >>
+
+jobs:
+  test:
+    docker:
+      - image: cimg/python:3.12
+      - image: "cimg/postgres:16.2"
+        environment:
+          POSTGRES_USER: circleci
+    steps:
+      - checkout
+  build:
+    machine:
+      image: ubuntu-2204:current
+    steps:
+      - checkout
+"#;
+
+    #[test]
+    fn it_finds_the_docker_executor_images() {
+        let result = parse_circleci_config(CIRCLECI_CONFIG).unwrap();
+
+        assert_eq!(
+            result,
+            [
+                ImageInstruction {
+                    image_name: "cimg/python:3.12".to_string(),
+                    range: Range::new(Position::new(13, 15), Position::new(13, 31)),
+                },
+                ImageInstruction {
+                    image_name: "cimg/postgres:16.2".to_string(),
+                    range: Range::new(Position::new(14, 15), Position::new(14, 35)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_fails_on_invalid_yaml() {
+        assert!(parse_circleci_config("jobs: [unclosed").is_err());
+    }
+}
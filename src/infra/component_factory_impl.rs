@@ -1,52 +1,281 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use tokio::sync::Mutex;
+use tracing::warn;
 
 use crate::{
-    app::component_factory::{ComponentFactory, ComponentFactoryError, Components, Config},
+    app::{
+        CachingImageScanner, ImageBuilder, ImageScanner, MirroredImageScanner, ScanResultStore,
+        ScanScheduler, ScheduledImageScanner, TenantScope, TokenProvider,
+        component_factory::{
+            ComponentFactory, ComponentFactoryError, Components, Config, ScannerMode, Secret,
+        },
+        first_token, redaction,
+    },
+    domain::image_reference::RegistryMirrors,
     infra::{
-        DockerImageBuilder, SysdigAPIToken, SysdigImageScanner, connect_to_docker,
-        scanner_binary_manager::ScannerBinaryManager, sysdig_iac_scanner::SysdigIacScanner,
+        BuildxImageBuilder, DockerImageBuilder, DockerImageDigestResolver, EnvVarTokenProvider,
+        JsonScanResultStore, KeychainTokenProvider, OciRegistryClient, RemoteCachedImageScanner,
+        RemoteScanCache, SysdigAPIToken, SysdigApiClient, SysdigImageScanner, connect_to_docker,
+        connect_to_docker_context, connect_to_docker_host,
+        registry_credentials::{RegistryCredentialStore, RegistryCredentials},
+        retry_policy::RetryPolicy,
+        scanner_binary_manager::{ScannerBinaryManager, ScannerVersion},
+        sysdig_api_image_scanner::SysdigApiImageScanner,
+        sysdig_iac_scanner::SysdigIacScanner,
     },
 };
 
+/// Environment variable the API token is read from by default, as the CLI scanner does.
+const DEFAULT_API_TOKEN_ENV: &str = "SECURE_API_TOKEN";
+
 pub struct ConcreteComponentFactory;
 
+/// Token of the default tenant: the one in the settings, or else the first found in
+/// `sysdig.api_token_env`, the OS keychain (if enabled) and `SECURE_API_TOKEN`.
+fn api_token(config: &Config) -> Result<SysdigAPIToken, ComponentFactoryError> {
+    if let Some(token) = &config.sysdig.api_token {
+        return Ok(SysdigAPIToken(token.expose().to_string()));
+    }
+
+    let mut providers: Vec<Box<dyn TokenProvider + Send + Sync>> = vec![];
+    if let Some(name) = &config.sysdig.api_token_env {
+        providers.push(Box::new(EnvVarTokenProvider::new(name)));
+    }
+    if config.sysdig.api_token_keychain {
+        providers.push(Box::new(KeychainTokenProvider::new(&config.sysdig.api_url)));
+    }
+    providers.push(Box::new(EnvVarTokenProvider::new(DEFAULT_API_TOKEN_ENV)));
+
+    first_token(&providers).map(SysdigAPIToken).ok_or_else(|| {
+        let sources = std::iter::once("sysdig.api_token".to_string())
+            .chain(providers.iter().map(|provider| provider.source()))
+            .collect::<Vec<_>>();
+        ComponentFactoryError::MissingApiToken(sources.join(", "))
+    })
+}
+
 impl ComponentFactory for ConcreteComponentFactory {
     fn create_components(&self, config: Config) -> Result<Components, ComponentFactoryError> {
-        let token = config
+        let token = api_token(&config)?;
+        // Wherever they come from, tokens must never end up in the logs.
+        let secrets = config
             .sysdig
-            .api_token
-            .clone()
-            .map(Ok)
-            .unwrap_or_else(|| std::env::var("SECURE_API_TOKEN"))
-            .map(SysdigAPIToken)?;
+            .scopes
+            .iter()
+            .filter_map(|scope| scope.api_token.as_ref())
+            .chain(&config.sysdig.cache.remote_token)
+            .map(Secret::expose);
+        for secret in std::iter::once(token.0.as_str()).chain(secrets) {
+            redaction::register_secret(secret);
+        }
 
         // Get Docker connection with socket path
-        let docker_connection = connect_to_docker()
-            .map_err(|e| ComponentFactoryError::DockerClientError(e.to_string()))?;
+        let docker = &config.sysdig.docker;
+        let docker_connection = match (docker.host.as_deref(), docker.context.as_deref()) {
+            (Some(_), Some(_)) => {
+                return Err(ComponentFactoryError::InvalidDockerConfig(
+                    "host and context are mutually exclusive, set only one of them".to_string(),
+                ));
+            }
+            (Some(host), None) => connect_to_docker_host(host, docker.cert_path.as_deref())
+                .map_err(|e| ComponentFactoryError::InvalidDockerConfig(e.to_string()))?,
+            (None, Some(context)) => connect_to_docker_context(context)
+                .map_err(|e| ComponentFactoryError::InvalidDockerConfig(e.to_string()))?,
+            (None, None) => connect_to_docker()
+                .map_err(|e| ComponentFactoryError::DockerClientError(e.to_string()))?,
+        };
+
+        // The API only has the results of registry scans, there's nothing to read offline
+        let scanner_mode = config.sysdig.scanner.mode;
+        if scanner_mode == ScannerMode::Api && config.sysdig.scan.offline {
+            return Err(ComponentFactoryError::OfflineApiScanner);
+        }
 
+        let retry_policy = RetryPolicy::new(config.sysdig.scan.max_attempts);
         // Both scanners share the same binary manager so the CLI binary is installed only once
-        let scanner_binary_manager = Arc::new(Mutex::new(ScannerBinaryManager::default()));
+        let scanner_binary_manager = match config.sysdig.scanner.path.clone() {
+            Some(path) if !path.is_file() => {
+                return Err(ComponentFactoryError::InvalidScannerPath(path));
+            }
+            Some(path) => ScannerBinaryManager::preinstalled(path),
+            None => ScannerBinaryManager::with_retry_policy(retry_policy.clone()),
+        };
+        let scanner_version = match config.sysdig.scanner.version.as_deref() {
+            Some(version) => version.parse::<ScannerVersion>().map_err(|e| {
+                ComponentFactoryError::InvalidScannerVersion(version.to_string(), e.to_string())
+            })?,
+            None => ScannerVersion::Default,
+        };
+        let scanner_binary_manager = Arc::new(Mutex::new(
+            scanner_binary_manager.with_version(scanner_version),
+        ));
+
+        // Offline scans can't reach the remote cache, nor would their reports (without
+        // policy evaluation) be worth sharing.
+        let remote_cache = match config.sysdig.cache.remote_url.as_deref() {
+            Some(url) if !config.sysdig.scan.offline => Some(RemoteScanCache::new(
+                reqwest::Url::parse(url).map_err(|e| {
+                    ComponentFactoryError::InvalidRemoteCacheUrl(url.to_string(), e.to_string())
+                })?,
+                config
+                    .sysdig
+                    .cache
+                    .remote_token
+                    .as_ref()
+                    .map(|token| token.expose().to_string()),
+                Duration::from_secs(config.sysdig.cache.remote_ttl_seconds),
+            )),
+            _ => None,
+        };
+
+        // Scans are cached and deduplicated by image digest, resolved through the same daemon.
+        // Every tenant gets its own scanners, so cached results are never shared across tenants,
+        // but they all share the scheduler bounding the scans running at once.
+        let scan_scheduler = ScanScheduler::new(config.sysdig.scan.max_concurrent_scans);
+        let registry_mirrors = RegistryMirrors::new(config.sysdig.registry.mirrors.clone());
+        let registry_credentials =
+            RegistryCredentialStore::new(config.sysdig.registry.credentials.iter().map(
+                |(registry, credentials)| {
+                    let credentials = RegistryCredentials {
+                        username: credentials.username.clone(),
+                        password: credentials.password.expose().to_string(),
+                    };
+                    (registry.clone(), credentials)
+                },
+            ));
+        let registry_credentials = Arc::new(match config.sysdig.registry.docker_config {
+            true => registry_credentials.with_docker_config(),
+            false => registry_credentials,
+        });
+        let scanners_for = |api_url: &str,
+                            token: &SysdigAPIToken,
+                            remote_cache: Option<&RemoteScanCache>| {
+            // Create scanner WITH the docker_host so CLI subprocess uses the same socket
+            let scanner = SysdigImageScanner::with_docker_host(
+                api_url.to_string(),
+                token.clone(),
+                docker_connection.socket_path.clone(),
+                docker_connection.tls_cert_path.clone(),
+                scanner_binary_manager.clone(),
+            )
+            .with_retry_policy(retry_policy.clone())
+            .with_progress(scan_scheduler.clone())
+            .with_registry_credentials(registry_credentials.clone())
+            .with_platform(config.sysdig.scan.platform.clone());
+            let scanner = if config.sysdig.scan.offline {
+                scanner.offline(config.sysdig.scan.db_path.clone())
+            } else {
+                scanner
+            };
+            // Directories can only be scanned by the CLI, whatever the mode
+            let filesystem_scanner = scanner.clone();
+            let scanner: Box<dyn ImageScanner + Send + Sync> = match (scanner_mode, remote_cache) {
+                // Results read from the API are already shared, there's no point in caching
+                // them remotely too.
+                (ScannerMode::Api, _) => Box::new(
+                    SysdigApiImageScanner::new(api_url.to_string(), token.clone())
+                        .with_retry_policy(retry_policy.clone()),
+                ),
+                (ScannerMode::Cli, Some(remote_cache)) => Box::new(RemoteCachedImageScanner::new(
+                    scanner,
+                    Box::new(DockerImageDigestResolver::new(
+                        docker_connection.client.clone(),
+                    )),
+                    remote_cache.clone(),
+                )),
+                (ScannerMode::Cli, None) => Box::new(scanner),
+            };
+            let scanner = CachingImageScanner::new(
+                scanner,
+                Box::new(DockerImageDigestResolver::new(
+                    docker_connection.client.clone(),
+                )),
+                Duration::from_secs(config.sysdig.cache.scan_results_ttl_seconds),
+            )
+            .with_statistics(scan_scheduler.statistics());
+            let scanner = MirroredImageScanner::new(Box::new(scanner), registry_mirrors.clone());
+            let scanner = ScheduledImageScanner::new(Box::new(scanner), scan_scheduler.clone());
+            let iac_scanner = SysdigIacScanner::new(
+                api_url.to_string(),
+                token.clone(),
+                scanner_binary_manager.clone(),
+            );
+            (scanner, iac_scanner, filesystem_scanner)
+        };
 
-        // Create scanner WITH the docker_host so CLI subprocess uses the same socket
-        let scanner = SysdigImageScanner::with_docker_host(
-            config.sysdig.api_url.clone(),
-            token.clone(),
-            docker_connection.socket_path.clone(),
-            scanner_binary_manager.clone(),
-        );
+        // The remote cache is shared by the team of the default tenant only.
+        let (scanner, iac_scanner, filesystem_scanner) =
+            scanners_for(&config.sysdig.api_url, &token, remote_cache.as_ref());
+
+        let scopes = config
+            .sysdig
+            .scopes
+            .iter()
+            .map(|scope| {
+                let api_url = scope.api_url.as_deref().unwrap_or(&config.sysdig.api_url);
+                let token = scope
+                    .api_token
+                    .as_ref()
+                    .map_or_else(|| token.clone(), |t| SysdigAPIToken(t.expose().to_string()));
+                let (scanner, iac_scanner, _) = scanners_for(api_url, &token, None);
+                TenantScope::new(&scope.paths, Box::new(scanner), Box::new(iac_scanner))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         // Create builder with the Docker client
-        let builder = DockerImageBuilder::new(docker_connection.client);
+        let docker_builder = DockerImageBuilder::new(docker_connection.client.clone());
+        let builder: Box<dyn ImageBuilder + Send + Sync> = if config.sysdig.build.buildkit {
+            Box::new(BuildxImageBuilder::new(
+                docker_builder,
+                docker_connection.socket_path.clone(),
+                docker_connection.tls_cert_path.clone(),
+            ))
+        } else {
+            Box::new(docker_builder)
+        };
+
+        let scan_result_store: Option<Box<dyn ScanResultStore + Send + Sync>> =
+            if config.sysdig.cache.persist {
+                let directory = config
+                    .sysdig
+                    .cache
+                    .directory
+                    .clone()
+                    .or_else(JsonScanResultStore::default_directory);
+                if directory.is_none() {
+                    warn!(
+                        "scan result persistence disabled: unable to determine the user cache dir"
+                    );
+                }
+                directory.map(|directory| Box::new(JsonScanResultStore::new(directory)) as _)
+            } else {
+                None
+            };
+
+        // Offline scans don't need valid credentials, so they aren't checked.
+        let api_client = || SysdigApiClient::new(config.sysdig.api_url.clone(), token.clone());
+        let credentials_checker =
+            (!config.sysdig.scan.offline).then(|| Box::new(api_client()) as _);
+        let risk_acceptance_client =
+            (!config.sysdig.scan.offline).then(|| Box::new(api_client()) as _);
 
-        let iac_scanner =
-            SysdigIacScanner::new(config.sysdig.api_url.clone(), token, scanner_binary_manager);
+        let registry_client = (!config.sysdig.scan.offline).then(|| {
+            Box::new(OciRegistryClient::default().with_credentials(registry_credentials.clone()))
+                as _
+        });
 
         Ok(Components {
             scanner: Box::new(scanner),
-            builder: Box::new(builder),
+            scan_scheduler,
+            builder,
             iac_scanner: Box::new(iac_scanner),
+            filesystem_scanner: Box::new(filesystem_scanner),
+            scan_result_store,
+            scopes,
+            credentials_checker,
+            risk_acceptance_client,
+            registry_client,
         })
     }
 }
@@ -1,12 +1,51 @@
+use std::path::Path;
+
 use thiserror::Error;
 use tower_lsp::lsp_types::{Position, Range};
 
+/// Extensions Compose override files are looked up with, in order of precedence.
+const OVERRIDE_EXTENSIONS: [&str; 2] = ["yaml", "yml"];
+
 #[derive(Debug, PartialEq)]
 pub struct ImageInstruction {
     pub image_name: String,
     pub range: Range,
 }
 
+/// A service built from a Dockerfile, with the range of its `build` key.
+#[derive(Debug, PartialEq)]
+pub struct BuildInstruction {
+    /// Build context, relative to the Compose file unless absolute.
+    pub context: String,
+    /// Dockerfile, relative to the build context unless absolute.
+    pub dockerfile: String,
+    pub range: Range,
+    /// Ranges of the `context` and `dockerfile` values written in the document.
+    pub path_ranges: Vec<Range>,
+    /// `image` of the service, the name Compose tags the built image with.
+    pub image: Option<String>,
+}
+
+/// Override file Docker Compose merges on top of a Compose file, e.g.
+/// `docker-compose.override.yml` for `docker-compose.yml`.
+#[derive(Debug, PartialEq)]
+pub struct ComposeOverride {
+    pub file_name: String,
+    pub content: String,
+}
+
+/// Image actually deployed for a reference of a document, once the files customizing
+/// it are applied: the override file of a Compose file, or a kustomization.
+#[derive(Debug, PartialEq)]
+pub struct EffectiveImage {
+    pub image_name: String,
+    /// Range of the reference in the document, e.g. the `image:` value in a Compose
+    /// file or, when only its override sets it, the service name.
+    pub range: Range,
+    /// File the image comes from, `None` if it's the one in the document.
+    pub overridden_by: Option<String>,
+}
+
 #[derive(Debug, Error)]
 pub enum ParseError {
     #[error("Invalid yaml: {0}")]
@@ -22,6 +61,154 @@ pub fn parse_compose_file(content: &str) -> Result<Vec<ImageInstruction>, ParseE
     Ok(instructions)
 }
 
+/// Services with a `build:` section, either the short form (`build: ./dir`) or the
+/// long one (`build: { context, dockerfile }`). Inline Dockerfiles and remote contexts
+/// are skipped, as there is no file to build.
+pub fn parse_compose_builds(content: &str) -> Result<Vec<BuildInstruction>, ParseError> {
+    let node = marked_yaml::parse_yaml(0, content).map_err(ParseError::InvalidYaml)?;
+    let Some(services) = node
+        .as_mapping()
+        .and_then(|root| root.get_mapping("services"))
+    else {
+        return Ok(vec![]);
+    };
+
+    Ok(services
+        .values()
+        .filter_map(marked_yaml::Node::as_mapping)
+        .filter_map(|service| try_create_build_instruction(service, content))
+        .collect())
+}
+
+fn try_create_build_instruction(
+    service: &marked_yaml::types::MarkedMappingNode,
+    content: &str,
+) -> Option<BuildInstruction> {
+    let (key, build) = service.iter().find(|(key, _)| key.as_str() == "build")?;
+
+    let path_range = |path: &marked_yaml::types::MarkedScalarNode| {
+        let start = path.span().start()?;
+        Some(calculate_range(start, path.as_str(), content))
+    };
+    let (context, dockerfile, path_ranges) = match build {
+        marked_yaml::Node::Scalar(context) => {
+            (context.as_str().trim(), None, vec![path_range(context)])
+        }
+        marked_yaml::Node::Mapping(build) => {
+            if build.get_node("dockerfile_inline").is_some() {
+                return None;
+            }
+            let context = build.get_scalar("context");
+            let dockerfile = build.get_scalar("dockerfile");
+            let path_ranges = [context, dockerfile].into_iter().flatten().map(path_range);
+            (
+                context.map_or(".", |c| c.as_str().trim()),
+                dockerfile.map(|d| d.as_str().trim()),
+                path_ranges.collect(),
+            )
+        }
+        marked_yaml::Node::Sequence(_) => return None,
+    };
+    if context.is_empty() || context == "null" || is_remote_context(context) {
+        return None;
+    }
+
+    let range = calculate_range(key.span().start()?, key.as_str(), content);
+    Some(BuildInstruction {
+        context: context.to_string(),
+        dockerfile: dockerfile
+            .filter(|d| !d.is_empty())
+            .unwrap_or("Dockerfile")
+            .to_string(),
+        range,
+        path_ranges: path_ranges.into_iter().flatten().collect(),
+        image: service
+            .get_scalar("image")
+            .map(|image| image.as_str().trim().to_string())
+            .filter(|image| is_valid_image_name(image)),
+    })
+}
+
+/// Git repositories and tarballs served over HTTP can be build contexts too.
+fn is_remote_context(context: &str) -> bool {
+    context.contains("://") || context.starts_with("git@")
+}
+
+/// Override file next to the Compose file, read from disk. Override files have no
+/// override themselves.
+pub fn find_compose_override(compose_file: &Path) -> Option<ComposeOverride> {
+    let stem = compose_file.file_stem()?.to_str()?;
+    if stem.ends_with(".override") {
+        return None;
+    }
+
+    OVERRIDE_EXTENSIONS.iter().find_map(|extension| {
+        let file_name = format!("{stem}.override.{extension}");
+        let content = std::fs::read_to_string(compose_file.with_file_name(&file_name)).ok()?;
+        Some(ComposeOverride { file_name, content })
+    })
+}
+
+/// Images of the services of the Compose file after merging its override, where the
+/// `image` of a service in the override takes precedence, as in `docker compose`.
+/// Services only defined in the override have no place in the Compose file and are
+/// left out.
+pub fn merge_compose_override(
+    content: &str,
+    compose_override: &ComposeOverride,
+) -> Result<Vec<EffectiveImage>, ParseError> {
+    let base = marked_yaml::parse_yaml(0, content).map_err(ParseError::InvalidYaml)?;
+    let overrides =
+        marked_yaml::parse_yaml(0, &compose_override.content).map_err(ParseError::InvalidYaml)?;
+    let services = |node: &marked_yaml::Node| {
+        node.as_mapping()
+            .and_then(|root| root.get_mapping("services"))
+            .cloned()
+    };
+    let Some(base_services) = services(&base) else {
+        return Ok(vec![]);
+    };
+    let override_services = services(&overrides);
+
+    let mut images = Vec::new();
+    for (name, service) in base_services.iter() {
+        let base_image = service
+            .as_mapping()
+            .and_then(|service| service.get_node("image"))
+            .and_then(|image| try_create_image_instruction(image, content));
+        let override_image = override_services
+            .as_ref()
+            .and_then(|services| services.get_mapping(name.as_str()))
+            .and_then(|service| service.get_scalar("image"))
+            .map(|image| image.as_str().trim())
+            .filter(|image| is_valid_image_name(image));
+
+        let range = base_image
+            .as_ref()
+            .map(|base_image| base_image.range)
+            .or_else(|| {
+                let start = name.span().start()?;
+                Some(calculate_range(start, name.as_str(), content))
+            });
+        let image = match (override_image, base_image, range) {
+            (Some(image_name), _, Some(range)) => EffectiveImage {
+                image_name: image_name.to_string(),
+                range,
+                overridden_by: Some(compose_override.file_name.clone()),
+            },
+            (None, Some(base_image), _) => EffectiveImage {
+                image_name: base_image.image_name,
+                range: base_image.range,
+                overridden_by: None,
+            },
+            _ => continue,
+        };
+        images.push(image);
+    }
+
+    Ok(images)
+}
+
 fn find_images_recursive(
     node: &marked_yaml::Node,
     instructions: &mut Vec<ImageInstruction>,
@@ -385,4 +572,153 @@ services:
             }
         );
     }
+
+    #[test]
+    fn test_parse_build_sections() {
+        let content = r#"
+services:
+  web:
+    build: ./web
+  api:
+    image: registry.example.com/api:dev
+    build:
+      context: services/api
+      dockerfile: docker/Dockerfile.prod
+  worker:
+    build:
+      dockerfile: worker.Dockerfile
+  inline:
+    build:
+      dockerfile_inline: FROM alpine
+  remote:
+    build: https://github.com/example/repo.git
+  db:
+    image: postgres:13
+"#;
+        let result = parse_compose_builds(content).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                BuildInstruction {
+                    context: "./web".to_string(),
+                    dockerfile: "Dockerfile".to_string(),
+                    range: Range::new(Position::new(3, 4), Position::new(3, 9)),
+                    path_ranges: vec![Range::new(Position::new(3, 11), Position::new(3, 16))],
+                    image: None,
+                },
+                BuildInstruction {
+                    context: "services/api".to_string(),
+                    dockerfile: "docker/Dockerfile.prod".to_string(),
+                    range: Range::new(Position::new(6, 4), Position::new(6, 9)),
+                    path_ranges: vec![
+                        Range::new(Position::new(7, 15), Position::new(7, 27)),
+                        Range::new(Position::new(8, 18), Position::new(8, 40)),
+                    ],
+                    image: Some("registry.example.com/api:dev".to_string()),
+                },
+                BuildInstruction {
+                    context: ".".to_string(),
+                    dockerfile: "worker.Dockerfile".to_string(),
+                    range: Range::new(Position::new(10, 4), Position::new(10, 9)),
+                    path_ranges: vec![Range::new(Position::new(11, 18), Position::new(11, 35))],
+                    image: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_build_sections_without_services() {
+        assert!(parse_compose_builds("version: '3.8'\n").unwrap().is_empty());
+        assert!(parse_compose_builds("").unwrap().is_empty());
+    }
+
+    fn compose_override(content: &str) -> ComposeOverride {
+        ComposeOverride {
+            file_name: "docker-compose.override.yml".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_merge_override_takes_the_image_from_the_override() {
+        let content = r#"
+services:
+  web:
+    image: nginx:1.25
+  api:
+    build: .
+  db:
+    image: postgres:13
+"#;
+        let compose_override = compose_override(
+            r#"
+services:
+  web:
+    image: nginx:1.27-alpine
+  api:
+    image: registry.example.com/api:dev
+  cache:
+    image: redis:7
+"#,
+        );
+
+        let result = merge_compose_override(content, &compose_override).unwrap();
+
+        let overridden_by = Some("docker-compose.override.yml".to_string());
+        assert_eq!(
+            result,
+            vec![
+                EffectiveImage {
+                    image_name: "nginx:1.27-alpine".to_string(),
+                    range: Range::new(Position::new(3, 11), Position::new(3, 21)),
+                    overridden_by: overridden_by.clone(),
+                },
+                EffectiveImage {
+                    image_name: "registry.example.com/api:dev".to_string(),
+                    range: Range::new(Position::new(4, 2), Position::new(4, 5)),
+                    overridden_by,
+                },
+                EffectiveImage {
+                    image_name: "postgres:13".to_string(),
+                    range: Range::new(Position::new(7, 11), Position::new(7, 22)),
+                    overridden_by: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_override_fails_on_invalid_override() {
+        let result = merge_compose_override(
+            "services:\n  web:\n    image: nginx\n",
+            &compose_override("services: [\n"),
+        );
+
+        assert!(matches!(result, Err(ParseError::InvalidYaml(_))));
+    }
+
+    #[test]
+    fn test_find_override_next_to_the_compose_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let compose_file = dir.path().join("docker-compose.yml");
+        assert_eq!(find_compose_override(&compose_file), None);
+
+        std::fs::write(
+            dir.path().join("docker-compose.override.yml"),
+            "services: {}\n",
+        )
+        .unwrap();
+        assert_eq!(
+            find_compose_override(&compose_file),
+            Some(ComposeOverride {
+                file_name: "docker-compose.override.yml".to_string(),
+                content: "services: {}\n".to_string(),
+            })
+        );
+        assert_eq!(
+            find_compose_override(&dir.path().join("docker-compose.override.yml")),
+            None
+        );
+    }
 }
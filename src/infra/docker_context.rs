@@ -0,0 +1,256 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Name of the implicit context that points to the local daemon.
+pub const DEFAULT_DOCKER_CONTEXT: &str = "default";
+
+/// Endpoint of the Docker daemon described by a Docker CLI context
+/// (`docker context create ...`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DockerContextEndpoint {
+    /// Daemon address in DOCKER_HOST format (e.g. "tcp://10.0.0.2:2376", "ssh://user@host").
+    pub host: String,
+    /// Directory containing `ca.pem`, `cert.pem` and `key.pem`, if the context has TLS material.
+    pub tls_cert_path: Option<PathBuf>,
+}
+
+#[derive(Error, Debug)]
+pub enum DockerContextError {
+    #[error("unable to determine the docker config directory, set DOCKER_CONFIG or HOME")]
+    ConfigDirNotFound,
+
+    #[error("docker context '{0}' not found, check `docker context ls`")]
+    NotFound(String),
+
+    #[error("docker context '{0}' does not define a docker endpoint")]
+    MissingDockerEndpoint(String),
+
+    #[error("unable to read docker context store: {0}")]
+    IO(#[from] std::io::Error),
+}
+
+#[derive(Deserialize)]
+struct ContextMeta {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Endpoints", default)]
+    endpoints: ContextEndpoints,
+}
+
+#[derive(Deserialize, Default)]
+struct ContextEndpoints {
+    docker: Option<ContextDockerEndpoint>,
+}
+
+#[derive(Deserialize)]
+struct ContextDockerEndpoint {
+    #[serde(rename = "Host")]
+    host: String,
+    #[serde(rename = "SkipTLSVerify", default)]
+    skip_tls_verify: bool,
+}
+
+#[derive(Deserialize)]
+struct DockerCliConfig {
+    #[serde(rename = "currentContext")]
+    current_context: Option<String>,
+}
+
+/// Directory where the Docker CLI keeps its configuration: `$DOCKER_CONFIG` or `~/.docker`.
+pub fn docker_config_dir() -> Option<PathBuf> {
+    std::env::var_os("DOCKER_CONFIG")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".docker")))
+}
+
+/// Context selected with `docker context use`, read from the CLI `config.json`.
+pub fn current_docker_context() -> Option<String> {
+    current_docker_context_in(&docker_config_dir()?)
+}
+
+fn current_docker_context_in(config_dir: &Path) -> Option<String> {
+    let contents = fs::read_to_string(config_dir.join("config.json")).ok()?;
+    serde_json::from_str::<DockerCliConfig>(&contents)
+        .ok()?
+        .current_context
+        .filter(|context| !context.is_empty())
+}
+
+/// Resolves a Docker CLI context by name from the context store.
+pub fn resolve_docker_context(name: &str) -> Result<DockerContextEndpoint, DockerContextError> {
+    let config_dir = docker_config_dir().ok_or(DockerContextError::ConfigDirNotFound)?;
+    resolve_docker_context_in(&config_dir, name)
+}
+
+fn resolve_docker_context_in(
+    config_dir: &Path,
+    name: &str,
+) -> Result<DockerContextEndpoint, DockerContextError> {
+    let meta_dir = config_dir.join("contexts").join("meta");
+    if !meta_dir.is_dir() {
+        return Err(DockerContextError::NotFound(name.to_string()));
+    }
+
+    // The CLI stores each context in a directory named after the sha256 of its name;
+    // matching on the `Name` field avoids depending on a hashing implementation.
+    for entry in fs::read_dir(&meta_dir)? {
+        let context_dir = entry?.path();
+        let Ok(contents) = fs::read_to_string(context_dir.join("meta.json")) else {
+            continue;
+        };
+        let meta: ContextMeta = match serde_json::from_str(&contents) {
+            Ok(meta) => meta,
+            Err(e) => {
+                debug!(
+                    "ignoring invalid docker context at {:?}: {}",
+                    context_dir, e
+                );
+                continue;
+            }
+        };
+        if meta.name != name {
+            continue;
+        }
+
+        let endpoint = meta
+            .endpoints
+            .docker
+            .ok_or_else(|| DockerContextError::MissingDockerEndpoint(name.to_string()))?;
+        if endpoint.skip_tls_verify {
+            warn!(
+                "docker context '{name}' skips TLS verification, which is not supported; certificates will be verified"
+            );
+        }
+
+        let tls_cert_path = context_dir
+            .file_name()
+            .map(|id| {
+                config_dir
+                    .join("contexts")
+                    .join("tls")
+                    .join(id)
+                    .join("docker")
+            })
+            .filter(|path| path.join("ca.pem").is_file());
+
+        return Ok(DockerContextEndpoint {
+            host: endpoint.host,
+            tls_cert_path,
+        });
+    }
+
+    Err(DockerContextError::NotFound(name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_context(config_dir: &Path, id: &str, meta: &str) {
+        let dir = config_dir.join("contexts").join("meta").join(id);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("meta.json"), meta).unwrap();
+    }
+
+    #[test]
+    fn it_resolves_a_tcp_context_with_tls_material() {
+        let config_dir = tempfile::tempdir().unwrap();
+        write_context(
+            config_dir.path(),
+            "abc123",
+            r#"{"Name":"remote","Metadata":{},"Endpoints":{"docker":{"Host":"tcp://10.0.0.2:2376","SkipTLSVerify":false}}}"#,
+        );
+        let tls_dir = config_dir.path().join("contexts/tls/abc123/docker");
+        fs::create_dir_all(&tls_dir).unwrap();
+        fs::write(tls_dir.join("ca.pem"), "").unwrap();
+
+        let endpoint = resolve_docker_context_in(config_dir.path(), "remote").unwrap();
+
+        assert_eq!(
+            endpoint,
+            DockerContextEndpoint {
+                host: "tcp://10.0.0.2:2376".to_string(),
+                tls_cert_path: Some(tls_dir),
+            }
+        );
+    }
+
+    #[test]
+    fn it_resolves_an_ssh_context_without_tls_material() {
+        let config_dir = tempfile::tempdir().unwrap();
+        write_context(
+            config_dir.path(),
+            "other",
+            r#"{"Name":"other","Endpoints":{"docker":{"Host":"unix:///other.sock"}}}"#,
+        );
+        write_context(
+            config_dir.path(),
+            "def456",
+            r#"{"Name":"vm","Endpoints":{"docker":{"Host":"ssh://dev@vm"}}}"#,
+        );
+
+        let endpoint = resolve_docker_context_in(config_dir.path(), "vm").unwrap();
+
+        assert_eq!(endpoint.host, "ssh://dev@vm");
+        assert_eq!(endpoint.tls_cert_path, None);
+    }
+
+    #[test]
+    fn it_fails_for_unknown_contexts() {
+        let config_dir = tempfile::tempdir().unwrap();
+        write_context(
+            config_dir.path(),
+            "abc123",
+            r#"{"Name":"remote","Endpoints":{"docker":{"Host":"tcp://10.0.0.2:2376"}}}"#,
+        );
+
+        let result = resolve_docker_context_in(config_dir.path(), "missing");
+
+        assert!(matches!(result, Err(DockerContextError::NotFound(name)) if name == "missing"));
+    }
+
+    #[test]
+    fn it_fails_for_contexts_without_docker_endpoint() {
+        let config_dir = tempfile::tempdir().unwrap();
+        write_context(
+            config_dir.path(),
+            "abc123",
+            r#"{"Name":"k8s-only","Endpoints":{}}"#,
+        );
+
+        let result = resolve_docker_context_in(config_dir.path(), "k8s-only");
+
+        assert!(matches!(
+            result,
+            Err(DockerContextError::MissingDockerEndpoint(_))
+        ));
+    }
+
+    #[test]
+    fn it_reads_the_current_context_from_the_cli_config() {
+        let config_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            config_dir.path().join("config.json"),
+            r#"{"auths":{},"currentContext":"remote"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            current_docker_context_in(config_dir.path()),
+            Some("remote".to_string())
+        );
+    }
+
+    #[test]
+    fn it_has_no_current_context_without_cli_config() {
+        let config_dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(current_docker_context_in(config_dir.path()), None);
+    }
+}
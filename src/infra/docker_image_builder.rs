@@ -1,11 +1,28 @@
-use std::path::Path;
-
-use bollard::{Docker, body_full, config::BuildInfo, query_parameters::BuildImageOptionsBuilder};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{LazyLock, Mutex, MutexGuard, PoisonError},
+};
+
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+
+use bollard::{
+    Docker, body_full,
+    config::BuildInfo,
+    query_parameters::{
+        BuildImageOptionsBuilder, ListImagesOptionsBuilder, RemoveImageOptionsBuilder,
+    },
+};
 use bytes::Bytes;
 use futures::StreamExt;
 use thiserror::Error;
 
-use crate::app::{ImageBuildError, ImageBuildResult, ImageBuilder};
+use crate::app::{
+    BuildLogSender, ImageBuildError, ImageBuildOptions, ImageBuildResult, ImageBuilder, ImageInUse,
+};
+
+use super::build_context::{BuildContextError, pack_build_context};
 
 #[derive(Error, Debug)]
 pub(in crate::infra) enum DockerImageBuilderError {
@@ -18,6 +35,9 @@ pub(in crate::infra) enum DockerImageBuilderError {
     #[error("internal docker client error: {0:?}")]
     Docker(#[from] bollard::errors::Error),
 
+    #[error("unable to pack the build context: {0}")]
+    BuildContext(#[from] BuildContextError),
+
     #[error("internal generic error: {0}")]
     Generic(String),
 }
@@ -28,6 +48,23 @@ impl From<DockerImageBuilderError> for ImageBuildError {
     }
 }
 
+/// Start of the names of the images built to be scanned, which are removed afterwards.
+const TEMPORARY_IMAGE_PREFIX: &str = "sysdig-lsp-image-build-";
+
+/// Temporary images this process is building or scanning, kept by the leftover pruning
+/// whichever builder it goes through, as the builders are recreated on every
+/// configuration change. Each is released when its `ImageInUse` is dropped.
+static IMAGES_IN_USE: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+fn images_in_use() -> MutexGuard<'static, HashSet<String>> {
+    IMAGES_IN_USE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Repository of a tag (`<name>:<tag>`), as the temporary images are named.
+fn repository(image_name: &str) -> &str {
+    image_name.split(':').next().unwrap_or(image_name)
+}
+
 #[derive(Clone)]
 pub struct DockerImageBuilder {
     docker_client: Docker,
@@ -41,6 +78,8 @@ impl DockerImageBuilder {
     async fn build_image_from_dockerfile(
         &self,
         containerfile: &Path,
+        options: &ImageBuildOptions,
+        build_log: BuildLogSender,
     ) -> Result<ImageBuildResult, DockerImageBuilderError> {
         let tar_contents = self
             .pack_containerfile_dir_into_a_tar(containerfile)
@@ -55,12 +94,16 @@ impl DockerImageBuilder {
                 )
             })?;
 
-        let image_name = format!("sysdig-lsp-image-build-{}", rand::random::<u8>());
+        let (image_name, in_use) = reserve_temporary_image_name(dockerfile_name);
+        let mut build_options = BuildImageOptionsBuilder::new()
+            .dockerfile(dockerfile_name)
+            .t(&image_name)
+            .buildargs(&options.build_args);
+        if let Some(target) = &options.target {
+            build_options = build_options.target(target);
+        }
         let mut results = self.docker_client.build_image(
-            BuildImageOptionsBuilder::new()
-                .dockerfile(dockerfile_name)
-                .t(&image_name)
-                .build(),
+            build_options.build(),
             None,
             Some(body_full(Bytes::from_owner(tar_contents))),
         );
@@ -69,72 +112,214 @@ impl DockerImageBuilder {
             "image was built, but no id was detected, this should have never happened".to_string(),
         ));
         while let Some(result) = results.next().await {
-            match result {
-                Ok(BuildInfo {
-                    aux: Some(aux_info @ bollard::config::ImageId { id: Some(_), .. }),
-                    ..
-                }) => {
-                    if let Some(image_id) = aux_info.id {
-                        build_info = Ok(ImageBuildResult {
-                            image_name: image_name.clone(),
-                            image_id,
-                        });
-                    }
+            let BuildInfo { stream, aux, .. } = result?;
+            for line in stream.iter().flat_map(|chunk| chunk.lines()) {
+                if !line.trim().is_empty() {
+                    // Nobody listening to the log is no reason to stop the build.
+                    let _ = build_log.send(line.to_string());
+                }
+            }
+            if let Some(bollard::config::ImageId {
+                id: Some(image_id), ..
+            }) = aux
+            {
+                build_info = Ok(ImageBuildResult {
+                    image_name: image_name.clone(),
+                    image_id,
+                    ..Default::default()
+                });
+            }
+        }
+
+        let build_info = ImageBuildResult {
+            in_use: Some(in_use),
+            ..build_info?
+        };
+        Ok(self.with_image_details(build_info).await)
+    }
+
+    /// Completes the result of a build with the id, size and creation time of the image.
+    /// Only the name is needed to scan it, so the build doesn't fail if the image can't
+    /// be inspected.
+    pub(super) async fn with_image_details(
+        &self,
+        mut result: ImageBuildResult,
+    ) -> ImageBuildResult {
+        match self.docker_client.inspect_image(&result.image_name).await {
+            Ok(image) => {
+                if let Some(image_id) = image.id {
+                    result.image_id = image_id;
                 }
-                Err(error) => return Err(DockerImageBuilderError::Docker(error)),
-                _ => {}
+                result.size = image.size.and_then(|size| u64::try_from(size).ok());
+                result.created = image
+                    .created
+                    .and_then(|created| DateTime::parse_from_rfc3339(&created).ok())
+                    .map(|created| created.with_timezone(&Utc));
             }
+            Err(e) => tracing::warn!(
+                "unable to inspect the built image {}: {e}",
+                result.image_name
+            ),
         }
+        result
+    }
+
+    pub(super) async fn remove_image_by_name(
+        &self,
+        image_name: &str,
+    ) -> Result<(), DockerImageBuilderError> {
+        // Forced, as the containers of a leftover image would otherwise keep it around.
+        self.docker_client
+            .remove_image(
+                image_name,
+                Some(RemoveImageOptionsBuilder::new().force(true).build()),
+                None,
+            )
+            .await?;
+        Ok(())
+    }
 
-        build_info
+    /// Removes the temporary images but the ones this process is still building or
+    /// scanning, e.g. the leftovers of a server stopped mid-scan.
+    pub(super) async fn remove_leftover_images(
+        &self,
+    ) -> Result<Vec<String>, DockerImageBuilderError> {
+        let filters = HashMap::from([("reference", vec![format!("{TEMPORARY_IMAGE_PREFIX}*")])]);
+        let images = self
+            .docker_client
+            .list_images(Some(
+                ListImagesOptionsBuilder::new().filters(&filters).build(),
+            ))
+            .await?;
+
+        let mut removed = vec![];
+        for image_name in images
+            .into_iter()
+            .flat_map(|image| image.repo_tags)
+            .filter(|tag| tag.starts_with(TEMPORARY_IMAGE_PREFIX))
+            .filter(|tag| !images_in_use().contains(repository(tag)))
+        {
+            self.remove_image_by_name(&image_name).await?;
+            removed.push(image_name);
+        }
+        Ok(removed)
     }
 
     async fn pack_containerfile_dir_into_a_tar(
         &self,
         containerfile: &Path,
     ) -> Result<Vec<u8>, DockerImageBuilderError> {
-        let Some(parent) = containerfile.parent() else {
-            return Err(DockerImageBuilderError::Generic(
-                "unable to find parent for provided containerfile".to_string(),
-            ));
-        };
-        let parent = parent.to_owned();
+        let containerfile = containerfile.to_owned();
 
-        tokio::task::spawn_blocking(move || -> Result<Vec<u8>, DockerImageBuilderError> {
-            let mut tar_builder = tar::Builder::new(Vec::new());
+        tokio::task::spawn_blocking(move || pack_build_context(&containerfile))
+            .await?
+            .map_err(DockerImageBuilderError::from)
+    }
+}
 
-            tar_builder.append_dir_all(".", parent)?;
-            tar_builder.finish()?;
+/// Unique name for an image built from `containerfile`, so concurrent builds don't
+/// overwrite each other's tag, telling the Containerfile it comes from.
+pub(super) fn temporary_image_name(containerfile: &str) -> String {
+    // Repository names only allow lowercase alphanumerics and separators.
+    let source = containerfile
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .join("-");
+    format!(
+        "{TEMPORARY_IMAGE_PREFIX}{source}-{:032x}",
+        rand::random::<u128>()
+    )
+}
 
-            Ok(tar_builder.into_inner()?)
-        })
-        .await?
-    }
+/// `temporary_image_name`, recorded as in use until the returned `ImageInUse` is dropped.
+pub(super) fn reserve_temporary_image_name(containerfile: &str) -> (String, ImageInUse) {
+    let image_name = temporary_image_name(containerfile);
+    images_in_use().insert(image_name.clone());
+    let in_use = ImageInUse::new({
+        let image_name = image_name.clone();
+        move || {
+            images_in_use().remove(&image_name);
+        }
+    });
+    (image_name, in_use)
 }
 
 #[async_trait::async_trait]
 impl ImageBuilder for DockerImageBuilder {
-    async fn build_image(&self, containerfile: &Path) -> Result<ImageBuildResult, ImageBuildError> {
-        Ok(self.build_image_from_dockerfile(containerfile).await?)
+    async fn build_image(
+        &self,
+        containerfile: &Path,
+        options: &ImageBuildOptions,
+        build_log: BuildLogSender,
+    ) -> Result<ImageBuildResult, ImageBuildError> {
+        Ok(self
+            .build_image_from_dockerfile(containerfile, options, build_log)
+            .await?)
+    }
+
+    async fn remove_image(&self, image_name: &str) -> Result<(), ImageBuildError> {
+        Ok(self.remove_image_by_name(image_name).await?)
+    }
+
+    async fn remove_temporary_images(&self) -> Result<Vec<String>, ImageBuildError> {
+        Ok(self.remove_leftover_images().await?)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{assert_matches, path::PathBuf, str::FromStr};
+    use std::{
+        assert_matches,
+        path::{Path, PathBuf},
+        str::FromStr,
+    };
+
+    use bollard::{API_DEFAULT_VERSION, Docker, query_parameters::TagImageOptionsBuilder};
 
+    use super::{images_in_use, reserve_temporary_image_name, temporary_image_name};
     use crate::{
-        app::{ImageBuildError, ImageBuilder},
+        app::{ImageBuildError, ImageBuildOptions, ImageBuilder},
         infra::{DockerImageBuilder, connect_to_docker},
     };
 
+    #[test]
+    fn it_names_the_images_after_their_containerfile_uniquely() {
+        let name = temporary_image_name("Dockerfile.Dev");
+
+        assert!(
+            name.starts_with("sysdig-lsp-image-build-dockerfile-dev-"),
+            "{name}"
+        );
+        assert_eq!(
+            name.len(),
+            "sysdig-lsp-image-build-dockerfile-dev-".len() + 32
+        );
+        assert_ne!(name, temporary_image_name("Dockerfile.Dev"));
+    }
+
+    #[test]
+    fn it_keeps_the_images_in_use_until_released() {
+        let (name, in_use) = reserve_temporary_image_name("Dockerfile");
+        assert!(images_in_use().contains(&name));
+
+        drop(in_use);
+
+        assert!(!images_in_use().contains(&name));
+    }
+
     #[tokio::test]
     async fn it_builds_a_dockerfile() {
         let docker_connection = connect_to_docker().unwrap();
         let image_builder = DockerImageBuilder::new(docker_connection.client);
 
+        let (build_log, mut build_log_lines) = tokio::sync::mpsc::unbounded_channel();
         let image_built = image_builder
-            .build_image(&PathBuf::from_str("tests/fixtures/Dockerfile").unwrap())
+            .build_image(
+                &PathBuf::from_str("tests/fixtures/Dockerfile").unwrap(),
+                &ImageBuildOptions::default(),
+                build_log,
+            )
             .await
             .unwrap();
 
@@ -144,6 +329,9 @@ mod tests {
                 .starts_with("sysdig-lsp-image-build-")
         );
         assert!(!image_built.image_id.is_empty());
+        assert!(image_built.size.is_some_and(|size| size > 0));
+        assert!(image_built.created.is_some());
+        assert!(build_log_lines.recv().await.is_some());
     }
 
     #[tokio::test]
@@ -152,7 +340,11 @@ mod tests {
         let image_builder = DockerImageBuilder::new(docker_connection.client);
 
         let image_built = image_builder
-            .build_image(&PathBuf::from_str("tests/fixtures/Containerfile").unwrap())
+            .build_image(
+                &PathBuf::from_str("tests/fixtures/Containerfile").unwrap(),
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
             .await
             .unwrap();
 
@@ -164,13 +356,162 @@ mod tests {
         assert!(!image_built.image_id.is_empty());
     }
 
+    #[tokio::test]
+    async fn it_passes_the_build_args() {
+        let docker_connection = connect_to_docker().unwrap();
+        let image_builder = DockerImageBuilder::new(docker_connection.client);
+        let containerfile = PathBuf::from_str("tests/fixtures/BuildArg.dockerfile").unwrap();
+
+        let without_args = image_builder
+            .build_image(
+                &containerfile,
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
+            .await;
+        let with_args = image_builder
+            .build_image(
+                &containerfile,
+                &ImageBuildOptions::default().with_build_args([("APP_VERSION", "1.2.3")]),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
+            .await;
+
+        assert!(without_args.is_err());
+        assert!(with_args.is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_builds_the_target_stage() {
+        let docker_connection = connect_to_docker().unwrap();
+        let image_builder = DockerImageBuilder::new(docker_connection.client);
+        let containerfile = PathBuf::from_str("tests/fixtures/MultiStage.dockerfile").unwrap();
+
+        let final_stage = image_builder
+            .build_image(
+                &containerfile,
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
+            .await;
+        let build_stage = image_builder
+            .build_image(
+                &containerfile,
+                &ImageBuildOptions::default().with_target(Some("build".to_string())),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
+            .await;
+
+        assert!(final_stage.is_err());
+        assert!(build_stage.is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_removes_the_built_images() {
+        let docker_connection = connect_to_docker().unwrap();
+        let docker_client = docker_connection.client;
+        let image_builder = DockerImageBuilder::new(docker_client.clone());
+
+        let scanned = image_builder
+            .build_image(
+                Path::new("tests/fixtures/Containerfile"),
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
+            .await
+            .unwrap();
+        // As left behind by an earlier server, which this one didn't build.
+        let leftover = temporary_image_name("Containerfile");
+        docker_client
+            .tag_image(
+                &scanned.image_name,
+                Some(TagImageOptionsBuilder::new().repo(&leftover).build()),
+            )
+            .await
+            .unwrap();
+        image_builder
+            .remove_image(&scanned.image_name)
+            .await
+            .unwrap();
+        let removed = image_builder.remove_temporary_images().await.unwrap();
+
+        assert!(!removed.contains(&format!("{}:latest", scanned.image_name)));
+        assert!(removed.contains(&format!("{leftover}:latest")));
+        assert!(
+            image_builder
+                .remove_temporary_images()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_images_still_in_use_when_removing_the_leftovers() {
+        let docker_connection = connect_to_docker().unwrap();
+        let docker_client = docker_connection.client;
+        let image_builder = DockerImageBuilder::new(docker_client.clone());
+        let options = ImageBuildOptions::default();
+        let (build_log, mut build_log_lines) = tokio::sync::mpsc::unbounded_channel();
+
+        let (built, removed_while_building) = tokio::join!(
+            image_builder.build_image(Path::new("tests/fixtures/Dockerfile"), &options, build_log),
+            async {
+                build_log_lines.recv().await;
+                image_builder.remove_temporary_images().await.unwrap()
+            }
+        );
+        let built = built.unwrap();
+        // Built, but still to be scanned.
+        let removed_while_scanning = image_builder.remove_temporary_images().await.unwrap();
+
+        let tag = format!("{}:latest", built.image_name);
+        assert!(!removed_while_building.contains(&tag));
+        assert!(!removed_while_scanning.contains(&tag));
+        assert!(docker_client.inspect_image(&built.image_name).await.is_ok());
+        image_builder.remove_image(&built.image_name).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn it_removes_the_leftovers_of_failed_removals() {
+        let docker_connection = connect_to_docker().unwrap();
+        let image_builder = DockerImageBuilder::new(docker_connection.client);
+        let unreachable_daemon = DockerImageBuilder::new(
+            Docker::connect_with_http("http://127.0.0.1:1", 1, API_DEFAULT_VERSION).unwrap(),
+        );
+
+        let scanned = image_builder
+            .build_image(
+                Path::new("tests/fixtures/Containerfile"),
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
+            .await
+            .unwrap();
+        assert!(
+            unreachable_daemon
+                .remove_image(&scanned.image_name)
+                .await
+                .is_err()
+        );
+        let leftover = format!("{}:latest", scanned.image_name);
+        drop(scanned);
+        let removed = image_builder.remove_temporary_images().await.unwrap();
+
+        assert!(removed.contains(&leftover));
+    }
+
     #[tokio::test]
     async fn it_fails_to_build_non_existent_dockerfile() {
         let docker_connection = connect_to_docker().unwrap();
         let image_builder = DockerImageBuilder::new(docker_connection.client);
 
         let image_built = image_builder
-            .build_image(&PathBuf::from_str("tests/fixtures/Nonexistent.dockerfile").unwrap())
+            .build_image(
+                &PathBuf::from_str("tests/fixtures/Nonexistent.dockerfile").unwrap(),
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
             .await;
 
         assert!(image_built.is_err());
@@ -186,7 +527,11 @@ mod tests {
         let image_builder = DockerImageBuilder::new(docker_connection.client);
 
         let image_built = image_builder
-            .build_image(&PathBuf::from_str("tests/fixtures/Invalid.dockerfile").unwrap())
+            .build_image(
+                &PathBuf::from_str("tests/fixtures/Invalid.dockerfile").unwrap(),
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
             .await;
 
         assert_matches!(image_built, Err(ImageBuildError::ImageBuilderError(_)));
@@ -195,4 +540,20 @@ mod tests {
             "image builder error: internal docker client error: DockerStreamError { error: \"The command '/bin/sh -c apt update # should fail, apt is not present in alpine' returned a non-zero code: 127\" }"
         );
     }
+
+    #[tokio::test]
+    async fn it_fails_to_build_a_buildkit_only_dockerfile() {
+        let docker_connection = connect_to_docker().unwrap();
+        let image_builder = DockerImageBuilder::new(docker_connection.client);
+
+        let image_built = image_builder
+            .build_image(
+                &PathBuf::from_str("tests/fixtures/BuildKit.dockerfile").unwrap(),
+                &ImageBuildOptions::default(),
+                tokio::sync::mpsc::unbounded_channel().0,
+            )
+            .await;
+
+        assert_matches!(image_built, Err(ImageBuildError::ImageBuilderError(_)));
+    }
 }
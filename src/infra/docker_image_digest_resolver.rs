@@ -0,0 +1,54 @@
+use bollard::Docker;
+use tracing::debug;
+
+use crate::app::ImageDigestResolver;
+
+/// Resolves image digests through the Docker daemon: first asking the registry for
+/// the current manifest (a HEAD-like distribution inspect), then falling back to the
+/// local image, by repo digest or, for images that were never pushed (e.g. built by
+/// "Build and scan"), by image ID.
+pub struct DockerImageDigestResolver {
+    docker_client: Docker,
+}
+
+impl DockerImageDigestResolver {
+    pub fn new(docker_client: Docker) -> Self {
+        Self { docker_client }
+    }
+}
+
+#[async_trait::async_trait]
+impl ImageDigestResolver for DockerImageDigestResolver {
+    async fn resolve_digest(&self, image_pull_string: &str) -> Option<String> {
+        match self
+            .docker_client
+            .inspect_registry_image(image_pull_string, None)
+            .await
+        {
+            Ok(distribution) => {
+                if let Some(digest) = distribution.descriptor.digest {
+                    return Some(digest);
+                }
+            }
+            Err(e) => debug!("unable to inspect {image_pull_string} in its registry: {e}"),
+        }
+
+        let image = self
+            .docker_client
+            .inspect_image(image_pull_string)
+            .await
+            .inspect_err(|e| debug!("unable to inspect local image {image_pull_string}: {e}"))
+            .ok()?;
+
+        image
+            .repo_digests
+            .unwrap_or_default()
+            .iter()
+            .find_map(|repo_digest| {
+                repo_digest
+                    .split_once('@')
+                    .map(|(_, digest)| digest.to_string())
+            })
+            .or(image.id)
+    }
+}
@@ -1,8 +1,13 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use bollard::Docker;
 use tracing::{debug, info, warn};
 
+use super::docker_context::{
+    DEFAULT_DOCKER_CONTEXT, DockerContextError, current_docker_context, docker_config_dir,
+    resolve_docker_context,
+};
+
 /// Result of a successful Docker connection, including the socket path used.
 pub struct DockerConnection {
     /// The connected Docker client
@@ -10,6 +15,8 @@ pub struct DockerConnection {
     /// The socket path that was used to connect.
     /// Format: "unix:///path/to/socket" for Unix sockets, or the DOCKER_HOST value if set.
     pub socket_path: String,
+    /// Directory with `ca.pem`, `cert.pem` and `key.pem` when the daemon is reached over TLS.
+    pub tls_cert_path: Option<PathBuf>,
 }
 
 /// List of Docker socket paths to try, in order of preference.
@@ -47,24 +54,27 @@ fn get_candidate_socket_paths() -> Vec<PathBuf> {
 /// Attempts to connect to Docker using multiple socket paths.
 ///
 /// This function tries the following in order:
-/// 1. `DOCKER_HOST` environment variable (if set)
-/// 2. Standard Docker socket at `/var/run/docker.sock`
-/// 3. Colima sockets at `$HOME/.colima/docker.sock`, `$HOME/.colima/default/docker.sock`
-/// 4. Colima containerd socket at `$HOME/.colima/default/containerd.sock`
-/// 5. Lima socket at `$HOME/.lima/default/sock/docker.sock`
+/// 1. `DOCKER_HOST` environment variable (if set), using TLS if `DOCKER_TLS_VERIFY` is set
+/// 2. Docker CLI context from `DOCKER_CONTEXT` or the one selected with `docker context use`
+/// 3. Standard Docker socket at `/var/run/docker.sock`
+/// 4. Colima sockets at `$HOME/.colima/docker.sock`, `$HOME/.colima/default/docker.sock`
+/// 5. Colima containerd socket at `$HOME/.colima/default/containerd.sock`
+/// 6. Lima socket at `$HOME/.lima/default/sock/docker.sock`
 ///
 /// Returns a `DockerConnection` containing both the client and the socket path used,
 /// or an error if no socket could be connected.
 pub fn connect_to_docker() -> Result<DockerConnection, DockerConnectionError> {
-    // First, check if DOCKER_HOST is set - if so, use Bollard's default behavior
+    // First, check if DOCKER_HOST is set - it takes precedence over contexts, as in the Docker CLI
     if let Ok(docker_host) = std::env::var("DOCKER_HOST") {
         debug!("DOCKER_HOST environment variable is set: {}", docker_host);
-        match Docker::connect_with_local_defaults() {
+        let tls_cert_path = tls_cert_path_from_env();
+        match connect_to_endpoint(&docker_host, tls_cert_path.as_deref()) {
             Ok(client) => {
                 info!("Connected to Docker via DOCKER_HOST: {}", docker_host);
                 return Ok(DockerConnection {
                     client,
                     socket_path: docker_host,
+                    tls_cert_path,
                 });
             }
             Err(e) => {
@@ -74,6 +84,20 @@ pub fn connect_to_docker() -> Result<DockerConnection, DockerConnectionError> {
         }
     }
 
+    let selected_context = std::env::var("DOCKER_CONTEXT")
+        .ok()
+        .or_else(current_docker_context)
+        .filter(|context| context != DEFAULT_DOCKER_CONTEXT);
+    if let Some(context) = selected_context {
+        match connect_to_docker_context(&context) {
+            Ok(connection) => return Ok(connection),
+            Err(e) => {
+                warn!("Failed to connect via docker context '{}': {}", context, e);
+                // Continue to try other sockets
+            }
+        }
+    }
+
     // Try each candidate socket path
     let candidate_paths = get_candidate_socket_paths();
     let mut last_error = None;
@@ -100,6 +124,7 @@ pub fn connect_to_docker() -> Result<DockerConnection, DockerConnectionError> {
                 return Ok(DockerConnection {
                     client,
                     socket_path: format!("unix://{}", socket_path_str),
+                    tls_cert_path: None,
                 });
             }
             Err(e) => {
@@ -121,6 +146,163 @@ pub fn connect_to_docker() -> Result<DockerConnection, DockerConnectionError> {
     })
 }
 
+/// Connects to the daemon of a Docker CLI context (`docker context ls`).
+///
+/// Unlike the automatic discovery in [`connect_to_docker`], an explicitly requested
+/// context doesn't fall back to local sockets: building against a different daemon
+/// than the one the user chose would be surprising.
+pub fn connect_to_docker_context(
+    name: &str,
+) -> Result<DockerConnection, DockerContextConnectionError> {
+    if name == DEFAULT_DOCKER_CONTEXT {
+        return Ok(connect_to_docker()?);
+    }
+
+    let endpoint = resolve_docker_context(name)?;
+    let client =
+        connect_to_endpoint(&endpoint.host, endpoint.tls_cert_path.as_deref()).map_err(|e| {
+            DockerContextConnectionError::Endpoint {
+                context: name.to_string(),
+                host: endpoint.host.clone(),
+                source: e,
+            }
+        })?;
+
+    info!(
+        "Connected to Docker via context '{}': {}",
+        name, endpoint.host
+    );
+    Ok(DockerConnection {
+        client,
+        socket_path: endpoint.host,
+        tls_cert_path: endpoint.tls_cert_path,
+    })
+}
+
+/// Connects to the daemon configured with `sysdig.docker.host`, e.g. a remote daemon
+/// over TLS or SSH, or a rootless one. As with contexts, there's no fallback to
+/// local sockets.
+pub fn connect_to_docker_host(
+    host: &str,
+    tls_cert_path: Option<&Path>,
+) -> Result<DockerConnection, DockerHostConnectionError> {
+    const SUPPORTED_SCHEMES: [&str; 5] = ["unix://", "tcp://", "https://", "ssh://", "npipe://"];
+    if !SUPPORTED_SCHEMES
+        .iter()
+        .any(|scheme| host.starts_with(scheme))
+    {
+        return Err(DockerHostConnectionError::UnsupportedScheme(
+            host.to_string(),
+        ));
+    }
+
+    let is_tcp = host.starts_with("tcp://") || host.starts_with("https://");
+    match tls_cert_path {
+        Some(cert_path) if is_tcp => {
+            if let Some(missing) = ["ca.pem", "cert.pem", "key.pem"]
+                .iter()
+                .map(|file| cert_path.join(file))
+                .find(|file| !file.is_file())
+            {
+                return Err(DockerHostConnectionError::MissingCertificate(missing));
+            }
+        }
+        Some(_) => warn!(
+            "ignoring the TLS certificates configured for docker host {}, they only apply to tcp:// hosts",
+            host
+        ),
+        None => {}
+    }
+
+    let tls_cert_path = tls_cert_path.filter(|_| is_tcp).map(Path::to_path_buf);
+    let client = connect_to_endpoint(host, tls_cert_path.as_deref()).map_err(|source| {
+        DockerHostConnectionError::Endpoint {
+            host: host.to_string(),
+            source,
+        }
+    })?;
+
+    info!("Connected to Docker via sysdig.docker.host: {}", host);
+    Ok(DockerConnection {
+        client,
+        socket_path: host.to_string(),
+        tls_cert_path,
+    })
+}
+
+/// Connects to a daemon address in DOCKER_HOST format (unix://, tcp://, https://, ssh://),
+/// using client certificates from `tls_cert_path` for TCP endpoints if provided.
+fn connect_to_endpoint(
+    host: &str,
+    tls_cert_path: Option<&Path>,
+) -> Result<Docker, bollard::errors::Error> {
+    match tls_cert_path {
+        Some(cert_path) if host.starts_with("tcp://") || host.starts_with("https://") => {
+            Docker::connect_with_ssl(
+                host,
+                &cert_path.join("key.pem"),
+                &cert_path.join("cert.pem"),
+                &cert_path.join("ca.pem"),
+                120,
+                bollard::API_DEFAULT_VERSION,
+            )
+        }
+        _ => Docker::connect_with_host(host),
+    }
+}
+
+/// Certificate directory to use for DOCKER_HOST, following the Docker CLI conventions:
+/// TLS is enabled by `DOCKER_TLS_VERIFY`, and certificates are read from `DOCKER_CERT_PATH`
+/// (defaulting to the docker config directory).
+fn tls_cert_path_from_env() -> Option<PathBuf> {
+    let tls_verify = std::env::var("DOCKER_TLS_VERIFY").ok()?;
+    if tls_verify.is_empty() {
+        return None;
+    }
+
+    std::env::var_os("DOCKER_CERT_PATH")
+        .map(PathBuf::from)
+        .or_else(docker_config_dir)
+}
+
+/// Error returned when the daemon of an explicitly selected Docker context is unreachable.
+#[derive(thiserror::Error, Debug)]
+pub enum DockerContextConnectionError {
+    #[error(transparent)]
+    Context(#[from] DockerContextError),
+
+    #[error("unable to connect to docker context '{context}' at {host}: {source}")]
+    Endpoint {
+        context: String,
+        host: String,
+        source: bollard::errors::Error,
+    },
+
+    #[error(transparent)]
+    Discovery(#[from] DockerConnectionError),
+}
+
+/// Error returned when the daemon configured with `sysdig.docker.host` can't be used.
+#[derive(thiserror::Error, Debug)]
+pub enum DockerHostConnectionError {
+    #[error(
+        "unsupported docker host '{0}', expected a unix://, tcp://, https://, ssh:// or npipe:// address"
+    )]
+    UnsupportedScheme(String),
+
+    #[error(
+        "TLS certificate {} not found, the certificates directory must contain ca.pem, cert.pem and key.pem",
+        .0.display()
+    )]
+    MissingCertificate(PathBuf),
+
+    #[error("unable to connect to docker host {host}: {source}")]
+    Endpoint {
+        host: String,
+        source: bollard::errors::Error,
+    },
+}
+
 /// Error returned when no Docker socket could be connected.
 #[derive(Debug)]
 pub struct DockerConnectionError {
@@ -228,6 +410,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_connect_to_docker_host_rejects_unsupported_schemes() {
+        let result = connect_to_docker_host("10.0.0.2:2376", None);
+
+        assert!(matches!(
+            result,
+            Err(DockerHostConnectionError::UnsupportedScheme(host)) if host == "10.0.0.2:2376"
+        ));
+    }
+
+    #[test]
+    fn test_connect_to_docker_host_requires_every_tls_certificate() {
+        let cert_dir = tempfile::tempdir().unwrap();
+        std::fs::write(cert_dir.path().join("ca.pem"), "").unwrap();
+
+        let result = connect_to_docker_host("tcp://10.0.0.2:2376", Some(cert_dir.path()));
+
+        assert!(matches!(
+            result,
+            Err(DockerHostConnectionError::MissingCertificate(path)) if path == cert_dir.path().join("cert.pem")
+        ));
+    }
+
+    #[test]
+    fn test_connect_to_docker_host_uses_the_configured_address() {
+        let connection = connect_to_docker_host("tcp://10.0.0.2:2375", None).unwrap();
+
+        assert_eq!(connection.socket_path, "tcp://10.0.0.2:2375");
+        assert_eq!(connection.tls_cert_path, None);
+    }
+
     #[test]
     fn test_socket_path_format_for_unix_socket() {
         // Validates the format logic for Unix sockets
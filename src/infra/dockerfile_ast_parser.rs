@@ -9,6 +9,72 @@ pub struct Instruction {
     pub range: Range,
 }
 
+/// A build stage: a `FROM` instruction and every instruction up to the next `FROM`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Stage {
+    /// Position of the stage in the Dockerfile, usable in `COPY --from=<index>`.
+    pub index: usize,
+    /// Name given with `FROM <image> AS <name>`.
+    pub name: Option<String>,
+    /// Image (or previous stage) the stage is based on.
+    pub base: Option<String>,
+    /// Instructions of the stage, starting with its `FROM`. Comment-only lines are skipped.
+    pub instructions: Vec<Instruction>,
+}
+
+impl Stage {
+    /// Range spanning from the `FROM` instruction to the end of the last instruction of the stage.
+    pub fn range(&self) -> Range {
+        match (self.instructions.first(), self.instructions.last()) {
+            (Some(first), Some(last)) => Range::new(first.range.start, last.range.end),
+            _ => Range::default(),
+        }
+    }
+
+    /// Whether `reference` (a stage name or index, as used in `--from=`) designates this stage.
+    pub fn is_referenced_by(&self, reference: &str) -> bool {
+        self.name
+            .as_deref()
+            .is_some_and(|name| name.eq_ignore_ascii_case(reference))
+            || reference.parse::<usize>() == Ok(self.index)
+    }
+}
+
+/// Groups the Dockerfile instructions by build stage. Instructions before the
+/// first `FROM` (global `ARG`s) don't belong to any stage and are skipped.
+pub fn parse_dockerfile_stages(contents: &str) -> Vec<Stage> {
+    let mut stages: Vec<Stage> = Vec::new();
+
+    for instruction in parse_dockerfile(contents) {
+        if instruction.keyword == "FROM" {
+            let mut arguments = instruction
+                .arguments
+                .iter()
+                .filter(|arg| !arg.starts_with("--"));
+            let base = arguments.next().cloned();
+            let name = match (arguments.next(), arguments.next()) {
+                (Some(as_keyword), Some(name)) if as_keyword.eq_ignore_ascii_case("AS") => {
+                    Some(name.clone())
+                }
+                _ => None,
+            };
+
+            stages.push(Stage {
+                index: stages.len(),
+                name,
+                base,
+                instructions: vec![instruction],
+            });
+        } else if !instruction.keyword.is_empty()
+            && let Some(stage) = stages.last_mut()
+        {
+            stage.instructions.push(instruction);
+        }
+    }
+
+    stages
+}
+
 pub fn parse_dockerfile(contents: &str) -> Vec<Instruction> {
     let lines: Vec<&str> = contents.lines().collect();
     let mut instructions = Vec::new();
@@ -105,7 +171,7 @@ mod tests {
 
     use crate::infra::dockerfile_ast_parser::Instruction;
 
-    use super::parse_dockerfile;
+    use super::{parse_dockerfile, parse_dockerfile_stages};
 
     #[test]
     fn it_parses_a_basic_dockerfile() {
@@ -262,4 +328,46 @@ CMD ["echo", "Hello, world!"]   # Print greeting
         ];
         assert_eq!(instructions, expected);
     }
+
+    #[test]
+    fn it_groups_instructions_by_stage() {
+        let dockerfile = r#"ARG VERSION=1.19
+FROM --platform=linux/amd64 golang:${VERSION} AS build
+# compile the app
+RUN go build -o app main.go
+
+FROM alpine:3.17
+COPY --from=build /app /app
+"#;
+
+        let stages = parse_dockerfile_stages(dockerfile);
+
+        assert_eq!(stages.len(), 2);
+        assert_eq!(stages[0].index, 0);
+        assert_eq!(stages[0].name.as_deref(), Some("build"));
+        assert_eq!(stages[0].base.as_deref(), Some("golang:${VERSION}"));
+        assert_eq!(
+            stages[0]
+                .instructions
+                .iter()
+                .map(|i| i.keyword.as_str())
+                .collect::<Vec<_>>(),
+            vec!["FROM", "RUN"]
+        );
+        assert_eq!(
+            stages[0].range(),
+            Range::new(Position::new(1, 0), Position::new(3, 27))
+        );
+        assert_eq!(stages[1].index, 1);
+        assert_eq!(stages[1].name, None);
+        assert_eq!(stages[1].base.as_deref(), Some("alpine:3.17"));
+        assert!(stages[0].is_referenced_by("build"));
+        assert!(stages[0].is_referenced_by("0"));
+        assert!(!stages[1].is_referenced_by("build"));
+    }
+
+    #[test]
+    fn it_has_no_stages_without_from() {
+        assert!(parse_dockerfile_stages("ARG X=1\nRUN echo\n").is_empty());
+    }
 }
@@ -0,0 +1,215 @@
+use thiserror::Error;
+use tower_lsp::lsp_types::{Position, Range};
+
+#[derive(Debug, PartialEq)]
+pub struct ImageInstruction {
+    pub image_name: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("Invalid yaml: {0}")]
+    InvalidYaml(marked_yaml::LoadError),
+}
+
+/// Prefix of the `uses:` steps running a container image instead of an action.
+const DOCKER_ACTION_PREFIX: &str = "docker://";
+
+/// Extracts the images of a GitHub Actions workflow: job containers (`container:`),
+/// service containers (`services.*.image`) and Docker steps (`uses: docker://...`).
+pub fn parse_github_workflow(content: &str) -> Result<Vec<ImageInstruction>, ParseError> {
+    let mut instructions = Vec::new();
+
+    let node = marked_yaml::parse_yaml(0, content).map_err(ParseError::InvalidYaml)?;
+    let Some(jobs) = node.as_mapping().and_then(|root| root.get_mapping("jobs")) else {
+        return Ok(instructions);
+    };
+
+    for job in jobs.values() {
+        let marked_yaml::Node::Mapping(job) = job else {
+            continue;
+        };
+
+        if let Some(container) = job.get("container") {
+            instructions.extend(container_image(container, content));
+        }
+
+        if let Some(services) = job.get_mapping("services") {
+            for service in services.values() {
+                instructions.extend(container_image(service, content));
+            }
+        }
+
+        if let Some(steps) = job.get_sequence("steps") {
+            for step in steps.iter() {
+                let Some(uses) = step.as_mapping().and_then(|step| step.get("uses")) else {
+                    continue;
+                };
+                instructions.extend(try_create_image_instruction(
+                    uses,
+                    DOCKER_ACTION_PREFIX,
+                    content,
+                ));
+            }
+        }
+    }
+
+    Ok(instructions)
+}
+
+/// Containers are either an image or a mapping with an `image` key.
+fn container_image(node: &marked_yaml::Node, content: &str) -> Option<ImageInstruction> {
+    match node {
+        marked_yaml::Node::Mapping(container) => {
+            try_create_image_instruction(container.get("image")?, "", content)
+        }
+        _ => try_create_image_instruction(node, "", content),
+    }
+}
+
+/// The range only covers the image, leaving out the `prefix` it must start with.
+fn try_create_image_instruction(
+    node: &marked_yaml::Node,
+    prefix: &str,
+    content: &str,
+) -> Option<ImageInstruction> {
+    let marked_yaml::Node::Scalar(scalar) = node else {
+        return None;
+    };
+
+    let image_name = scalar.as_str().trim().strip_prefix(prefix)?.to_string();
+    if !is_valid_image_name(&image_name) {
+        return None;
+    }
+
+    let start = node.span().start()?;
+
+    let range = calculate_range(start, prefix, &image_name, content);
+    Some(ImageInstruction { image_name, range })
+}
+
+/// Images built from expressions (e.g. `${{ matrix.image }}`) are only known at runtime.
+fn is_valid_image_name(name: &str) -> bool {
+    !name.is_empty() && name != "null" && !name.contains("${{")
+}
+
+fn calculate_range(
+    start: &marked_yaml::Marker,
+    prefix: &str,
+    image_name: &str,
+    content: &str,
+) -> Range {
+    let start_line = start.line() as u32 - 1;
+    let mut start_char = start.column() as u32 - 1;
+
+    let start_line_content = content.lines().nth(start_line as usize).unwrap_or("");
+    let first_char = start_line_content.chars().nth(start_char as usize);
+
+    if let Some(c) = first_char
+        && (c == '"' || c == '\'')
+    {
+        start_char += 1;
+    }
+    start_char += prefix.len() as u32;
+
+    let end_char = start_char + image_name.len() as u32;
+
+    Range {
+        start: Position {
+            line: start_line,
+            character: start_char,
+        },
+        end: Position {
+            line: start_line,
+            character: end_char,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKFLOW: &str = r#"name: CI
+on: [push]
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    container: node:20
+    services:
+      postgres:
+        image: postgres:16
+        env:
+          POSTGRES_PASSWORD: postgres
+      redis:
+        image: ${{ matrix.redis }}
+    steps:
+      - uses: actions/checkout@v4
+      - uses: "docker://alpine:3.19"
+        with:
+          args: echo hello
+      - run: npm test
+  lint:
+    runs-on: ubuntu-latest
+    container:
+      image: 'golangci/golangci-lint:v1.59'
+      options: --user root
+    steps:
+      - uses: docker://hadolint/hadolint:latest
+"#;
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn it_finds_the_images_of_every_job() {
+        let images: Vec<_> = parse_github_workflow(WORKFLOW)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.image_name)
+            .collect();
+
+        assert_eq!(
+            images,
+            [
+                "node:20",
+                "postgres:16",
+                "alpine:3.19",
+                "golangci/golangci-lint:v1.59",
+                "hadolint/hadolint:latest"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_ranges_over_the_image_only() {
+        let result = parse_github_workflow(WORKFLOW).unwrap();
+
+        assert_eq!(result[0].range, range(5, 15, 22));
+        assert_eq!(result[1].range, range(8, 15, 26));
+        assert_eq!(result[2].range, range(15, 24, 35));
+        assert_eq!(result[3].range, range(22, 14, 42));
+        assert_eq!(result[4].range, range(25, 23, 47));
+    }
+
+    #[test]
+    fn it_finds_nothing_in_workflows_without_containers() {
+        let content = r#"
+on: push
+jobs:
+  build:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+"#;
+
+        assert!(parse_github_workflow(content).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_fails_on_invalid_yaml() {
+        assert!(parse_github_workflow("jobs: [unclosed").is_err());
+    }
+}
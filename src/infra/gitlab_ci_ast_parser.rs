@@ -0,0 +1,186 @@
+use thiserror::Error;
+use tower_lsp::lsp_types::{Position, Range};
+
+#[derive(Debug, PartialEq)]
+pub struct ImageInstruction {
+    pub image_name: String,
+    pub range: Range,
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("Invalid yaml: {0}")]
+    InvalidYaml(marked_yaml::LoadError),
+}
+
+/// Extracts the `image:` and `services:` entries of a `.gitlab-ci.yml`, either global,
+/// under `default:` or in any job. Both accept an image or a mapping with a `name`.
+pub fn parse_gitlab_ci(content: &str) -> Result<Vec<ImageInstruction>, ParseError> {
+    let mut instructions = Vec::new();
+
+    let node = marked_yaml::parse_yaml(0, content).map_err(ParseError::InvalidYaml)?;
+    find_images_recursive(&node, &mut instructions, content);
+
+    Ok(instructions)
+}
+
+fn find_images_recursive(
+    node: &marked_yaml::Node,
+    instructions: &mut Vec<ImageInstruction>,
+    content: &str,
+) {
+    let marked_yaml::Node::Mapping(map) = node else {
+        return;
+    };
+
+    for (key, value) in map.iter() {
+        match key.as_str() {
+            "image" => instructions.extend(image_entry(value, content)),
+            "services" => {
+                if let marked_yaml::Node::Sequence(services) = value {
+                    instructions.extend(services.iter().filter_map(|s| image_entry(s, content)));
+                }
+            }
+            // Templates and jobs, possibly nesting `image:` and `services:`.
+            _ => find_images_recursive(value, instructions, content),
+        }
+    }
+}
+
+fn image_entry(node: &marked_yaml::Node, content: &str) -> Option<ImageInstruction> {
+    match node {
+        marked_yaml::Node::Mapping(entry) => {
+            try_create_image_instruction(entry.get("name")?, content)
+        }
+        _ => try_create_image_instruction(node, content),
+    }
+}
+
+fn try_create_image_instruction(
+    node: &marked_yaml::Node,
+    content: &str,
+) -> Option<ImageInstruction> {
+    let marked_yaml::Node::Scalar(scalar) = node else {
+        return None;
+    };
+
+    let image_name = scalar.as_str().trim().to_string();
+    if !is_valid_image_name(&image_name) {
+        return None;
+    }
+
+    let start = node.span().start()?;
+
+    let range = calculate_range(start, &image_name, content);
+    Some(ImageInstruction { image_name, range })
+}
+
+/// Images referencing CI/CD variables (e.g. `$CI_REGISTRY_IMAGE`) are only known when the pipeline runs.
+fn is_valid_image_name(name: &str) -> bool {
+    !name.is_empty() && name != "null" && !name.contains('$')
+}
+
+fn calculate_range(start: &marked_yaml::Marker, image_name: &str, content: &str) -> Range {
+    let start_line = start.line() as u32 - 1;
+    let start_char = start.column() as u32 - 1;
+
+    let start_line_content = content.lines().nth(start_line as usize).unwrap_or("");
+    let first_char = start_line_content.chars().nth(start_char as usize);
+
+    let mut raw_len = image_name.len();
+    if let Some(c) = first_char
+        && (c == '"' || c == '\'')
+    {
+        raw_len += 2;
+    }
+
+    let end_char = start_char + raw_len as u32;
+
+    Range {
+        start: Position {
+            line: start_line,
+            character: start_char,
+        },
+        end: Position {
+            line: start_line,
+            character: end_char,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GITLAB_CI: &str = r#"image: ruby:3.3
+
+default:
+  services:
+    - postgres:16
+
+variables:
+  POSTGRES_DB: test
+
+test:
+  stage: test
+  services:
+    - name: redis:7
+      alias: cache
+  script:
+    - bundle exec rspec
+
+build:
+  image:
+    name: "gcr.io/kaniko-project/executor:debug"
+    entrypoint: [""]
+  script:
+    - /kaniko/executor
+
+deploy:
+  image: $CI_REGISTRY_IMAGE:latest
+  script:
+    - ./deploy.sh
+"#;
+
+    #[test]
+    fn it_finds_the_global_default_and_job_images() {
+        let images: Vec<_> = parse_gitlab_ci(GITLAB_CI)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.image_name)
+            .collect();
+
+        assert_eq!(
+            images,
+            [
+                "ruby:3.3",
+                "postgres:16",
+                "redis:7",
+                "gcr.io/kaniko-project/executor:debug"
+            ]
+        );
+    }
+
+    #[test]
+    fn it_ranges_over_the_image() {
+        let result = parse_gitlab_ci(GITLAB_CI).unwrap();
+
+        assert_eq!(
+            result[0].range,
+            Range::new(Position::new(0, 7), Position::new(0, 15))
+        );
+        assert_eq!(
+            result[2].range,
+            Range::new(Position::new(12, 12), Position::new(12, 19))
+        );
+        assert_eq!(
+            result[3].range,
+            Range::new(Position::new(19, 10), Position::new(19, 48))
+        );
+    }
+
+    #[test]
+    fn it_fails_on_invalid_yaml() {
+        assert!(parse_gitlab_ci("image: [unclosed").is_err());
+    }
+}
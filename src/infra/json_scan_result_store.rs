@@ -0,0 +1,245 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::app::{ScanResultStore, ScanResultStoreError, ScanSnapshot, renamed_uri};
+
+/// Stores one JSON file per document under a directory (by default in the user
+/// cache dir), named after the hash of the document URI.
+pub struct JsonScanResultStore {
+    directory: PathBuf,
+}
+
+/// Contents of a snapshot file. The URI is kept so the snapshots of the documents of
+/// a renamed folder can be found; files saved before it was lack it.
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile<S> {
+    #[serde(default)]
+    uri: Option<String>,
+    #[serde(flatten)]
+    snapshot: S,
+}
+
+impl JsonScanResultStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// `<cache dir>/sysdig-lsp/scan-results`, if the platform has a cache dir.
+    pub fn default_directory() -> Option<PathBuf> {
+        dirs::cache_dir().map(|cache| cache.join("sysdig-lsp").join("scan-results"))
+    }
+
+    fn path_for(&self, uri: &str) -> PathBuf {
+        self.directory
+            .join(format!("{}.json", ScanSnapshot::content_hash_of(uri)))
+    }
+}
+
+#[async_trait::async_trait]
+impl ScanResultStore for JsonScanResultStore {
+    async fn save(&self, uri: &str, snapshot: &ScanSnapshot) -> Result<(), ScanResultStoreError> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+
+        // Write to a temporary file first so a crash never leaves a truncated snapshot behind.
+        let path = self.path_for(uri);
+        let tmp_path = path.with_extension("json.tmp");
+        let file = SnapshotFile {
+            uri: Some(uri.to_string()),
+            snapshot,
+        };
+        tokio::fs::write(&tmp_path, serde_json::to_vec(&file)?).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        debug!("persisted scan results for {uri} at {}", path.display());
+        Ok(())
+    }
+
+    async fn load(&self, uri: &str) -> Result<Option<ScanSnapshot>, ScanResultStoreError> {
+        let contents = match tokio::fs::read(self.path_for(uri)).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let file: SnapshotFile<ScanSnapshot> = serde_json::from_slice(&contents)?;
+        Ok(Some(file.snapshot))
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), ScanResultStoreError> {
+        // The renamed document itself may have been saved without its URI.
+        let mut renamed = vec![(from.to_string(), to.to_string())];
+        renamed.extend(
+            self.stored_uris()
+                .await?
+                .into_iter()
+                .filter(|uri| uri != from)
+                .filter_map(|uri| Some((uri.clone(), renamed_uri(&uri, from, to)?))),
+        );
+
+        for (old_uri, new_uri) in renamed {
+            // Saved again rather than moved, so the file holds its new URI.
+            let Some(snapshot) = self.load(&old_uri).await? else {
+                continue;
+            };
+            self.save(&new_uri, &snapshot).await?;
+            tokio::fs::remove_file(self.path_for(&old_uri)).await?;
+            debug!("moved the scan results of {old_uri} to {new_uri}");
+        }
+        Ok(())
+    }
+}
+
+impl JsonScanResultStore {
+    /// URIs of the stored snapshots that tell theirs. Unreadable files are skipped.
+    async fn stored_uris(&self) -> Result<Vec<String>, ScanResultStoreError> {
+        let mut entries = match tokio::fs::read_dir(&self.directory).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut uris = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let Ok(contents) = tokio::fs::read(&path).await else {
+                continue;
+            };
+            if let Ok(SnapshotFile { uri: Some(uri), .. }) =
+                serde_json::from_slice::<SnapshotFile<serde::de::IgnoredAny>>(&contents)
+            {
+                uris.push(uri);
+            }
+        }
+        Ok(uris)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Diagnostic, Position, Range};
+
+    use super::*;
+    use crate::app::SnapshotDocumentation;
+
+    fn snapshot() -> ScanSnapshot {
+        let range = Range::new(Position::new(0, 0), Position::new(0, 11));
+        ScanSnapshot {
+            content_hash: ScanSnapshot::content_hash_of("FROM alpine"),
+            diagnostics: vec![Diagnostic {
+                range,
+                message: "Vulnerabilities found".to_string(),
+                source: Some("sysdig-lsp/vuln".to_string()),
+                ..Default::default()
+            }],
+            documentations: vec![SnapshotDocumentation {
+                range,
+                content: "## Report".to_string(),
+                anchors: vec![],
+                verdict: None,
+                scanned_at: "2025-09-30T16:05:33Z".parse().ok(),
+            }],
+            scan_result: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_round_trips_snapshots_per_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = JsonScanResultStore::new(dir.path().join("nested"));
+
+        store.save("file:///Dockerfile", &snapshot()).await.unwrap();
+
+        assert_eq!(
+            store.load("file:///Dockerfile").await.unwrap(),
+            Some(snapshot())
+        );
+        assert_eq!(store.load("file:///other/Dockerfile").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn it_moves_snapshots_to_the_renamed_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = JsonScanResultStore::new(dir.path());
+        store.save("file:///Dockerfile", &snapshot()).await.unwrap();
+
+        store
+            .rename("file:///Dockerfile", "file:///Containerfile")
+            .await
+            .unwrap();
+        store
+            .rename("file:///never/scanned", "file:///other")
+            .await
+            .unwrap();
+
+        assert_eq!(store.load("file:///Dockerfile").await.unwrap(), None);
+        assert_eq!(
+            store.load("file:///Containerfile").await.unwrap(),
+            Some(snapshot())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_moves_the_snapshots_of_a_renamed_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = JsonScanResultStore::new(dir.path());
+        for uri in [
+            "file:///app/Dockerfile",
+            "file:///app/api/Dockerfile",
+            "file:///application/Dockerfile",
+        ] {
+            store.save(uri, &snapshot()).await.unwrap();
+        }
+
+        store
+            .rename("file:///app", "file:///service")
+            .await
+            .unwrap();
+
+        for (uri, stored) in [
+            ("file:///app/Dockerfile", false),
+            ("file:///app/api/Dockerfile", false),
+            ("file:///service/Dockerfile", true),
+            ("file:///service/api/Dockerfile", true),
+            ("file:///application/Dockerfile", true),
+        ] {
+            assert_eq!(store.load(uri).await.unwrap().is_some(), stored, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn it_loads_snapshots_saved_without_their_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = JsonScanResultStore::new(dir.path());
+        std::fs::write(
+            store.path_for("file:///Dockerfile"),
+            serde_json::to_vec(&snapshot()).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            store.load("file:///Dockerfile").await.unwrap(),
+            Some(snapshot())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_corrupted_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = JsonScanResultStore::new(dir.path());
+        std::fs::write(store.path_for("file:///Dockerfile"), "{not json").unwrap();
+
+        let result = store.load("file:///Dockerfile").await;
+
+        assert!(matches!(
+            result,
+            Err(ScanResultStoreError::Serialization(_))
+        ));
+    }
+}
@@ -13,15 +13,54 @@ pub enum ParseError {
     InvalidYaml(marked_yaml::LoadError),
 }
 
+/// Pod spec fields listing containers, whose `image` is scanned.
+const CONTAINER_LISTS: [&str; 3] = ["containers", "initContainers", "ephemeralContainers"];
+
+/// Images of every document of the manifest, including the items of `List` kinds and
+/// the pod templates of workloads like CronJobs, at any depth.
 pub fn parse_k8s_manifest(content: &str) -> Result<Vec<ImageInstruction>, ParseError> {
     let mut instructions = Vec::new();
 
-    let node = marked_yaml::parse_yaml(0, content).map_err(ParseError::InvalidYaml)?;
-    find_images_recursive(&node, &mut instructions, content);
+    for document in yaml_documents(content) {
+        let node = marked_yaml::parse_yaml(0, document).map_err(ParseError::InvalidYaml)?;
+        find_images_recursive(&node, &mut instructions, content);
+    }
 
     Ok(instructions)
 }
 
+/// Documents of a multi-document YAML stream, separated by `---` lines or ended by
+/// `...` ones. `marked_yaml` only loads single documents, so each one is padded with
+/// the blank lines preceding it to keep the parsed positions pointing into the file.
+fn yaml_documents(content: &str) -> Vec<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut documents = Vec::new();
+    let mut start = 0;
+
+    for (index, line) in lines.iter().enumerate() {
+        if is_document_marker(line) {
+            documents.push(padded_document(&lines, start, index));
+            start = index + 1;
+        }
+    }
+    documents.push(padded_document(&lines, start, lines.len()));
+
+    documents
+}
+
+fn is_document_marker(line: &str) -> bool {
+    ["---", "..."].iter().any(|marker| {
+        line.strip_prefix(marker)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    })
+}
+
+fn padded_document(lines: &[&str], start: usize, end: usize) -> String {
+    let mut document = "\n".repeat(start);
+    document.push_str(&lines[start..end].join("\n"));
+    document
+}
+
 fn find_images_recursive(
     node: &marked_yaml::Node,
     instructions: &mut Vec<ImageInstruction>,
@@ -29,10 +68,9 @@ fn find_images_recursive(
 ) {
     match node {
         marked_yaml::Node::Mapping(map) => {
-            // Check if this is a containers or initContainers array
             for (key, value) in map.iter() {
                 let key_str = key.as_str();
-                if key_str == "containers" || key_str == "initContainers" {
+                if CONTAINER_LISTS.contains(&key_str) {
                     find_container_images(value, instructions, content);
                 } else if key_str == "image" {
                     if let Some(instruction) = try_create_image_instruction(value, content) {
@@ -125,6 +163,180 @@ fn calculate_range(start: &marked_yaml::Marker, image_name: &str, content: &str)
     }
 }
 
+/// A container of a pod spec, with the settings checked for misconfigurations.
+#[derive(Debug, PartialEq)]
+pub struct K8sContainer {
+    pub name: Option<String>,
+    /// Range of the first key of the container, e.g. `name` in `- name: nginx`.
+    pub range: Range,
+    /// `ephemeralContainers` entries, which can't set resources.
+    pub ephemeral: bool,
+    /// `runAsNonRoot` of the container `securityContext`, or of the pod's if unset.
+    pub run_as_non_root: Option<bool>,
+    /// `runAsUser` of the container `securityContext`, or of the pod's if unset.
+    pub run_as_user: Option<i64>,
+    /// Range of the `runAsNonRoot` value of the container `securityContext`, if set.
+    pub run_as_non_root_value: Option<Range>,
+    /// The `securityContext` of the container, if set.
+    pub security_context: Option<SecurityContextKey>,
+    /// Whether the first key of the container has its value on the same line, so other
+    /// keys can be inserted right below it.
+    pub single_line_first_key: bool,
+    /// Whether `resources.limits` sets any limit.
+    pub has_resource_limits: bool,
+}
+
+/// Where a `securityContext` key is, to insert settings in it.
+#[derive(Debug, PartialEq)]
+pub struct SecurityContextKey {
+    pub line: u32,
+    /// Column of the keys of the mapping, `None` if it's empty or a flow mapping (`{}`).
+    pub keys_column: Option<u32>,
+}
+
+/// Containers of every pod spec of the manifest, like `parse_k8s_manifest` finds
+/// their images.
+pub fn parse_k8s_containers(content: &str) -> Result<Vec<K8sContainer>, ParseError> {
+    let mut containers = Vec::new();
+
+    for document in yaml_documents(content) {
+        let node = marked_yaml::parse_yaml(0, document).map_err(ParseError::InvalidYaml)?;
+        find_containers_recursive(&node, &mut containers, content);
+    }
+
+    Ok(containers)
+}
+
+fn find_containers_recursive(
+    node: &marked_yaml::Node,
+    containers: &mut Vec<K8sContainer>,
+    content: &str,
+) {
+    match node {
+        marked_yaml::Node::Mapping(map) => {
+            let pod_security_context = map.get("securityContext");
+            for (key, value) in map.iter() {
+                let key_str = key.as_str();
+                if CONTAINER_LISTS.contains(&key_str) {
+                    let marked_yaml::Node::Sequence(items) = value else {
+                        continue;
+                    };
+                    containers.extend(items.iter().filter_map(|item| {
+                        container_from(
+                            item,
+                            key_str == "ephemeralContainers",
+                            pod_security_context,
+                            content,
+                        )
+                    }));
+                } else {
+                    find_containers_recursive(value, containers, content);
+                }
+            }
+        }
+        marked_yaml::Node::Sequence(seq) => {
+            for item in seq.iter() {
+                find_containers_recursive(item, containers, content);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn container_from(
+    node: &marked_yaml::Node,
+    ephemeral: bool,
+    pod_security_context: Option<&marked_yaml::Node>,
+    content: &str,
+) -> Option<K8sContainer> {
+    let marked_yaml::Node::Mapping(container) = node else {
+        return None;
+    };
+    let (first_key, first_value) = container.iter().next()?;
+    let start = first_key.span().start()?;
+    let range = Range::new(
+        position_of(start),
+        Position::new(
+            start.line() as u32 - 1,
+            (start.column() + first_key.as_str().len()) as u32 - 1,
+        ),
+    );
+    let single_line_first_key = matches!(first_value, marked_yaml::Node::Scalar(_))
+        && first_value
+            .span()
+            .start()
+            .is_some_and(|value| value.line() == start.line())
+        && !content
+            .lines()
+            .nth(start.line() - 1)
+            .is_some_and(|line| line.contains(['{', '[']));
+
+    let security_context = container.get("securityContext");
+    let setting = |name: &str| {
+        security_context
+            .and_then(|context| scalar_setting(context, name))
+            .or_else(|| pod_security_context.and_then(|context| scalar_setting(context, name)))
+    };
+    let run_as_non_root = setting("runAsNonRoot").and_then(|value| value.parse().ok());
+    let run_as_user = setting("runAsUser").and_then(|value| value.parse().ok());
+    let run_as_non_root_value = security_context
+        .and_then(|context| context.as_mapping())
+        .and_then(|context| context.get_scalar("runAsNonRoot"))
+        .and_then(|value| {
+            let start = value.span().start()?;
+            Some(Range::new(
+                position_of(start),
+                Position::new(
+                    start.line() as u32 - 1,
+                    (start.column() + value.as_str().len()) as u32 - 1,
+                ),
+            ))
+        });
+    let security_context = container
+        .iter()
+        .find(|(key, _)| key.as_str() == "securityContext")
+        .and_then(|(key, value)| {
+            let line = key.span().start()?.line() as u32 - 1;
+            let keys_column = value
+                .as_mapping()
+                .and_then(|mapping| mapping.iter().next())
+                .and_then(|(key, _)| key.span().start())
+                .filter(|key| key.line() as u32 - 1 > line)
+                .map(|key| key.column() as u32 - 1);
+            Some(SecurityContextKey { line, keys_column })
+        });
+
+    let has_resource_limits = container
+        .get_mapping("resources")
+        .and_then(|resources| resources.get_mapping("limits"))
+        .is_some_and(|limits| !limits.is_empty());
+
+    Some(K8sContainer {
+        name: container
+            .get_scalar("name")
+            .map(|name| name.as_str().to_string()),
+        range,
+        ephemeral,
+        run_as_non_root,
+        run_as_user,
+        run_as_non_root_value,
+        security_context,
+        single_line_first_key,
+        has_resource_limits,
+    })
+}
+
+fn scalar_setting<'a>(security_context: &'a marked_yaml::Node, name: &str) -> Option<&'a str> {
+    security_context
+        .as_mapping()?
+        .get_scalar(name)
+        .map(|value| value.as_str())
+}
+
+fn position_of(marker: &marked_yaml::Marker) -> Position {
+    Position::new(marker.line() as u32 - 1, marker.column() as u32 - 1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -418,4 +630,89 @@ spec:
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].image_name, "perl:5.34");
     }
+
+    #[test]
+    fn test_parse_multi_document_manifest() {
+        let content = r#"apiVersion: v1
+kind: Service
+metadata:
+  name: web
+---
+apiVersion: apps/v1
+kind: Deployment
+spec:
+  template:
+    spec:
+      containers:
+      - name: web
+        image: nginx:1.27
+--- # the job
+apiVersion: batch/v1
+kind: Job
+spec:
+  template:
+    spec:
+      containers:
+      - name: migrate
+        image: "flyway/flyway:10"
+...
+"#;
+        let result = parse_k8s_manifest(content).unwrap();
+        assert_eq!(
+            result,
+            vec![
+                ImageInstruction {
+                    image_name: "nginx:1.27".to_string(),
+                    range: Range::new(Position::new(12, 15), Position::new(12, 25)),
+                },
+                ImageInstruction {
+                    image_name: "flyway/flyway:10".to_string(),
+                    range: Range::new(Position::new(21, 15), Position::new(21, 33)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_list_with_cronjob_and_ephemeral_containers() {
+        let content = r#"
+apiVersion: v1
+kind: List
+items:
+- apiVersion: batch/v1
+  kind: CronJob
+  spec:
+    jobTemplate:
+      spec:
+        template:
+          spec:
+            containers:
+            - name: backup
+              image: postgres:16
+- apiVersion: v1
+  kind: Pod
+  spec:
+    containers:
+    - name: app
+      image: app:1.0
+    ephemeralContainers:
+    - name: debugger
+      image: busybox:1.36
+"#;
+        let images: Vec<_> = parse_k8s_manifest(content)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.image_name)
+            .collect();
+        assert_eq!(images, ["postgres:16", "app:1.0", "busybox:1.36"]);
+    }
+
+    #[test]
+    fn test_parse_multi_document_manifest_with_invalid_document() {
+        let content = "kind: Pod\n---\nspec: [\n";
+        assert!(matches!(
+            parse_k8s_manifest(content),
+            Err(ParseError::InvalidYaml(_))
+        ));
+    }
 }
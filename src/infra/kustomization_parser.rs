@@ -0,0 +1,291 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+use tower_lsp::lsp_types::{Position, Range};
+
+use super::parse_k8s_manifest;
+
+/// Names Kustomize looks up its configuration with, in order of precedence.
+pub const KUSTOMIZATION_FILE_NAMES: [&str; 3] =
+    ["kustomization.yaml", "kustomization.yml", "Kustomization"];
+
+/// An entry of the `images:` transformer of a `kustomization.yaml`, with the range of
+/// its `name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageOverride {
+    pub name: String,
+    pub new_name: Option<String>,
+    pub new_tag: Option<String>,
+    pub digest: Option<String>,
+    pub range: Range,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Kustomization {
+    pub images: Vec<ImageOverride>,
+    /// Manifests and directories the kustomization is built from.
+    pub resources: Vec<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("Invalid yaml: {0}")]
+    InvalidYaml(marked_yaml::LoadError),
+}
+
+pub fn parse_kustomization(content: &str) -> Result<Kustomization, ParseError> {
+    let node = marked_yaml::parse_yaml(0, content).map_err(ParseError::InvalidYaml)?;
+    let Some(root) = node.as_mapping() else {
+        return Ok(Kustomization::default());
+    };
+
+    let images = root
+        .get_sequence("images")
+        .map(|images| {
+            images
+                .iter()
+                .filter_map(marked_yaml::Node::as_mapping)
+                .filter_map(|image| try_create_image_override(image, content))
+                .collect()
+        })
+        .unwrap_or_default();
+    let resources = root
+        .get_sequence("resources")
+        .map(|resources| {
+            resources
+                .iter()
+                .filter_map(marked_yaml::Node::as_scalar)
+                .map(|resource| resource.as_str().trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(Kustomization { images, resources })
+}
+
+fn try_create_image_override(
+    image: &marked_yaml::types::MarkedMappingNode,
+    content: &str,
+) -> Option<ImageOverride> {
+    let name_node = image.get_scalar("name")?;
+    let name = name_node.as_str().trim().to_string();
+    if name.is_empty() {
+        return None;
+    }
+    let field = |key| {
+        image
+            .get_scalar(key)
+            .map(|value| value.as_str().trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let range = calculate_range(name_node.span().start()?, &name, content);
+    Some(ImageOverride {
+        name,
+        new_name: field("newName"),
+        new_tag: field("newTag"),
+        digest: field("digest"),
+        range,
+    })
+}
+
+impl ImageOverride {
+    /// The image Kustomize deploys instead of `image`, if the override applies to it:
+    /// the name is replaced by `newName`, and the tag by `digest` or `newTag`, in
+    /// that order of precedence.
+    pub fn apply(&self, image: &str) -> Option<String> {
+        let (name, reference) = split_image_reference(image);
+        if name != self.name {
+            return None;
+        }
+
+        let name = self.new_name.as_deref().unwrap_or(name);
+        Some(match (&self.digest, &self.new_tag, reference) {
+            (Some(digest), _, _) => format!("{name}@{digest}"),
+            (None, Some(tag), _) => format!("{name}:{tag}"),
+            (None, None, Some(reference)) => format!("{name}{reference}"),
+            (None, None, None) => name.to_string(),
+        })
+    }
+
+    /// The image the override sets, for when no manifest using it is known.
+    pub fn image(&self) -> String {
+        self.apply(&self.name).unwrap_or_else(|| self.name.clone())
+    }
+}
+
+impl Kustomization {
+    /// The image Kustomize deploys instead of `image`, if any override applies to it.
+    pub fn transform(&self, image: &str) -> Option<String> {
+        self.images
+            .iter()
+            .find_map(|image_override| image_override.apply(image))
+    }
+
+    /// Whether the manifest at `manifest` is one of the resources of the kustomization
+    /// at `kustomization_dir`.
+    fn includes(&self, kustomization_dir: &Path, manifest: &Path) -> bool {
+        self.resources
+            .iter()
+            .any(|resource| kustomization_dir.join(resource) == manifest)
+    }
+}
+
+/// Splits the name of the image from its `:tag` or `@digest` reference, kept with
+/// its separator.
+fn split_image_reference(image: &str) -> (&str, Option<&str>) {
+    if let Some(at) = image.find('@') {
+        return (&image[..at], Some(&image[at..]));
+    }
+    let last_component_start = image.rfind('/').map_or(0, |slash| slash + 1);
+    match image[last_component_start..].rfind(':') {
+        Some(colon) => {
+            let colon = last_component_start + colon;
+            (&image[..colon], Some(&image[colon..]))
+        }
+        None => (image, None),
+    }
+}
+
+/// Kustomization next to the manifest listing it as a resource, read from disk, with
+/// the name of its file.
+pub fn find_kustomization(manifest: &Path) -> Option<(String, Kustomization)> {
+    let dir = manifest.parent()?;
+    let (file_name, kustomization) = read_kustomization(dir)?;
+    let manifest = dir.join(manifest.file_name()?);
+    kustomization
+        .includes(dir, &manifest)
+        .then_some((file_name, kustomization))
+}
+
+fn read_kustomization(dir: &Path) -> Option<(String, Kustomization)> {
+    KUSTOMIZATION_FILE_NAMES.iter().find_map(|file_name| {
+        let content = std::fs::read_to_string(dir.join(file_name)).ok()?;
+        match parse_kustomization(&content) {
+            Ok(kustomization) => Some((file_name.to_string(), kustomization)),
+            Err(e) => {
+                tracing::warn!("ignoring {}: {e}", dir.join(file_name).display());
+                None
+            }
+        }
+    })
+}
+
+/// Images referenced by the manifests the kustomization at `kustomization_dir` lists
+/// as resources, before applying its overrides. Directories (bases, components) and
+/// remote resources aren't followed.
+pub fn kustomization_resource_images(
+    kustomization_dir: &Path,
+    kustomization: &Kustomization,
+) -> Vec<String> {
+    kustomization
+        .resources
+        .iter()
+        .map(|resource| kustomization_dir.join(resource))
+        .filter(|path: &PathBuf| path.is_file())
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|content| parse_k8s_manifest(&content).ok())
+        .flatten()
+        .map(|instruction| instruction.image_name)
+        .collect()
+}
+
+fn calculate_range(start: &marked_yaml::Marker, value: &str, content: &str) -> Range {
+    let start_line = start.line() as u32 - 1;
+    let start_char = start.column() as u32 - 1;
+
+    let start_line_content = content.lines().nth(start_line as usize).unwrap_or("");
+    let first_char = start_line_content.chars().nth(start_char as usize);
+
+    let mut raw_len = value.len();
+    if let Some(c) = first_char
+        && (c == '"' || c == '\'')
+    {
+        raw_len += 2;
+    }
+
+    Range::new(
+        Position::new(start_line, start_char),
+        Position::new(start_line, start_char + raw_len as u32),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    const KUSTOMIZATION: &str = r#"apiVersion: kustomize.config.k8s.io/v1beta1
+kind: Kustomization
+resources:
+  - deployment.yaml
+  - ../base
+images:
+  - name: nginx
+    newTag: "1.27"
+  - name: "app"
+    newName: registry.example.com/app
+    digest: sha256:abc
+  - name: redis
+    newName: valkey/valkey
+"#;
+
+    fn image_override(index: usize) -> ImageOverride {
+        parse_kustomization(KUSTOMIZATION).unwrap().images[index].clone()
+    }
+
+    #[test]
+    fn it_parses_the_images_and_resources() {
+        let kustomization = parse_kustomization(KUSTOMIZATION).unwrap();
+
+        assert_eq!(kustomization.resources, ["deployment.yaml", "../base"]);
+        assert_eq!(
+            kustomization.images[0],
+            ImageOverride {
+                name: "nginx".to_string(),
+                new_name: None,
+                new_tag: Some("1.27".to_string()),
+                digest: None,
+                range: Range::new(Position::new(6, 10), Position::new(6, 15)),
+            }
+        );
+        assert_eq!(
+            kustomization.images[1].range,
+            Range::new(Position::new(8, 10), Position::new(8, 15))
+        );
+    }
+
+    #[rstest]
+    #[case(0, "nginx", Some("nginx:1.27"))]
+    #[case(0, "nginx:1.25", Some("nginx:1.27"))]
+    #[case(0, "nginx-unprivileged:1.25", None)]
+    #[case(1, "app:dev", Some("registry.example.com/app@sha256:abc"))]
+    #[case(2, "redis:7", Some("valkey/valkey:7"))]
+    #[case(2, "redis@sha256:def", Some("valkey/valkey@sha256:def"))]
+    fn it_applies_the_overrides_like_kustomize(
+        #[case] index: usize,
+        #[case] image: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(image_override(index).apply(image).as_deref(), expected);
+    }
+
+    #[test]
+    fn it_finds_the_kustomization_listing_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("deployment.yaml");
+        std::fs::write(&manifest, "kind: Deployment\n").unwrap();
+        assert_eq!(find_kustomization(&manifest), None);
+
+        std::fs::write(dir.path().join("kustomization.yaml"), KUSTOMIZATION).unwrap();
+        let (file_name, kustomization) = find_kustomization(&manifest).unwrap();
+        assert_eq!(file_name, "kustomization.yaml");
+        assert_eq!(
+            kustomization.transform("nginx:1.25").as_deref(),
+            Some("nginx:1.27")
+        );
+
+        assert_eq!(find_kustomization(&dir.path().join("service.yaml")), None);
+    }
+}
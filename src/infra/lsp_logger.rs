@@ -1,42 +1,85 @@
+use std::io::{self, Write};
+
 use tower_lsp::{Client, lsp_types::MessageType};
-use tracing::{Level, Subscriber};
-use tracing_subscriber::Layer;
+use tracing::{Level, Subscriber, span};
+use tracing_subscriber::{Layer, fmt::MakeWriter, registry::LookupSpan};
+
+use crate::app::{ServerTrace, component_factory::TraceLevel, redaction::redact};
 
+/// Forwards the tracing events to the client with `window/logMessage`, as verbose as
+/// the `ServerTrace` says, redacting the secrets. Errors are also shown to the user
+/// if `sysdig.trace.show_errors` is enabled.
 pub struct LSPLogger {
     client: Client,
+    trace: ServerTrace,
 }
 
 impl LSPLogger {
-    pub fn new(client: Client) -> Self {
-        Self { client }
+    pub fn new(client: Client, trace: ServerTrace) -> Self {
+        Self { client, trace }
     }
 }
 
+/// Fields of a span, formatted when it's created.
+struct SpanFields(String);
+
 impl<S> Layer<S> for LSPLogger
 where
-    S: Subscriber,
+    S: Subscriber + for<'a> LookupSpan<'a>,
 {
-    fn on_event(
+    fn on_new_span(
         &self,
-        event: &tracing::Event<'_>,
-        _ctx: tracing_subscriber::layer::Context<'_, S>,
+        attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
     ) {
+        let mut visitor = StringVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanFields(visitor.message));
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
         let level = *event.metadata().level();
+        let trace = self.trace.level();
         let message_type = match level {
             Level::ERROR => MessageType::ERROR,
             Level::WARN => MessageType::WARNING,
             Level::INFO => MessageType::INFO,
-            Level::DEBUG => MessageType::LOG,
-            Level::TRACE => return,
+            Level::DEBUG if trace >= TraceLevel::Messages => MessageType::LOG,
+            Level::TRACE if trace >= TraceLevel::Verbose => MessageType::LOG,
+            Level::DEBUG | Level::TRACE => return,
         };
 
         let mut visitor = StringVisitor::default();
         event.record(&mut visitor);
-        let message = visitor.message;
+        let mut message = visitor.message;
+        if trace >= TraceLevel::Verbose
+            && let Some(scope) = ctx.event_scope(event)
+        {
+            let context: Vec<_> = scope
+                .from_root()
+                .map(|span| match span.extensions().get::<SpanFields>() {
+                    Some(SpanFields(fields)) if !fields.is_empty() => {
+                        format!("{}{{{fields}}}", span.name())
+                    }
+                    _ => span.name().to_string(),
+                })
+                .collect();
+            message = format!("{}: {message}", context.join(":"));
+        }
+        let message = redact(&message).into_owned();
+        let show = level == Level::ERROR && self.trace.show_errors();
 
         let join_handle = tokio::spawn({
             let client = self.client.clone();
             async move {
+                if show {
+                    client
+                        .show_message(message_type, format!("Sysdig LSP: {message}"))
+                        .await;
+                }
                 client.log_message(message_type, message).await;
             }
         });
@@ -45,16 +88,74 @@ where
     }
 }
 
+/// Formats the `message` of an event followed by its other fields, as `name=value`.
 #[derive(Default)]
 struct StringVisitor {
     message: String,
 }
 
-impl tracing::field::Visit for StringVisitor {
-    fn record_debug(&mut self, _field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+impl StringVisitor {
+    fn push(&mut self, field: &tracing::field::Field, value: &str) {
         if !self.message.is_empty() {
             self.message.push(' ');
         }
-        self.message.push_str(&format!("{value:?}"));
+        if field.name() != "message" {
+            self.message.push_str(field.name());
+            self.message.push('=');
+        }
+        self.message.push_str(value);
+    }
+}
+
+impl tracing::field::Visit for StringVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.push(field, value);
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.push(field, &format!("{value:?}"));
+    }
+}
+
+/// Writes the logs to stderr, redacting the secrets.
+pub struct RedactingStderr;
+
+impl<'a> MakeWriter<'a> for RedactingStderr {
+    type Writer = RedactingWriter<io::Stderr>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter(io::stderr())
+    }
+}
+
+/// Redacts the secrets of every write, which holds a whole log line.
+pub struct RedactingWriter<W>(W);
+
+impl<W: Write> Write for RedactingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        self.0.write_all(redact(&text).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::app::redaction::register_secret;
+
+    use super::*;
+
+    #[test]
+    fn it_redacts_the_secrets_written() {
+        register_secret("lsp-logger-test-token");
+        let mut writer = RedactingWriter(vec![]);
+
+        write!(writer, "token=lsp-logger-test-token").unwrap();
+
+        assert_eq!(String::from_utf8(writer.0).unwrap(), "token=[redacted]");
     }
 }
@@ -0,0 +1,362 @@
+use std::{fmt::Display, str::FromStr};
+
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream},
+    net::TcpStream,
+    sync::mpsc,
+};
+use tokio_tungstenite::tungstenite::{
+    self, Message, Utf8Bytes,
+    protocol::{CloseFrame, WebSocketConfig, frame::coding::CloseCode},
+};
+
+/// Where `sysdig-lsp --listen` accepts editors: a TCP socket speaking the LSP base
+/// protocol, or a WebSocket carrying a JSON-RPC message per text message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    /// `host:port`, or `tcp://host:port`.
+    Tcp(String),
+    /// `ws://host:port`.
+    WebSocket(String),
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, listen_address): (&str, fn(String) -> ListenAddress) =
+            match s.split_once("://") {
+                None => (s, ListenAddress::Tcp),
+                Some(("tcp", address)) => (address, ListenAddress::Tcp),
+                Some(("ws", address)) => (address, ListenAddress::WebSocket),
+                Some((scheme, _)) => {
+                    return Err(format!(
+                        "unsupported scheme '{scheme}' in '{s}', expected tcp or ws"
+                    ));
+                }
+            };
+        let address = address.trim_end_matches('/');
+        match address.rsplit_once(':') {
+            Some((_, port)) if port.parse::<u16>().is_ok() => {
+                Ok(listen_address(address.to_string()))
+            }
+            _ => Err(format!("'{s}' has no port, expected e.g. 127.0.0.1:9257")),
+        }
+    }
+}
+
+impl Display for ListenAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddress::Tcp(address) => write!(f, "tcp://{address}"),
+            ListenAddress::WebSocket(address) => write!(f, "ws://{address}"),
+        }
+    }
+}
+
+/// Biggest WebSocket message accepted, as the LSP messages are buffered whole.
+const MAX_WEBSOCKET_MESSAGE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Opens the WebSocket requested on `stream` and returns the input and output of the LSP
+/// server: every message received is handed over with a `Content-Length` header, and
+/// every message the server writes is sent as a text message. The session ends when
+/// either side closes, or when the client breaks the protocol.
+pub async fn accept_websocket(stream: TcpStream) -> std::io::Result<(DuplexStream, DuplexStream)> {
+    let config = WebSocketConfig::default().max_message_size(Some(MAX_WEBSOCKET_MESSAGE_BYTES));
+    let websocket = tokio_tungstenite::accept_async_with_config(stream, Some(config))
+        .await
+        .map_err(std::io::Error::other)?;
+    let (mut websocket_sink, mut websocket_messages) = websocket.split();
+
+    let (server_input, mut client_messages) = tokio::io::duplex(64 * 1024);
+    let (mut server_messages, server_output) = tokio::io::duplex(64 * 1024);
+    let (messages, mut messages_to_send) = mpsc::unbounded_channel::<Message>();
+
+    // The socket is written by a single task, as both the closing frames and the server
+    // messages go through it. Pongs are answered while reading.
+    tokio::spawn(async move {
+        while let Some(message) = messages_to_send.recv().await {
+            let closing = message.is_close();
+            if websocket_sink.send(message).await.is_err() || closing {
+                break;
+            }
+        }
+        let _ = websocket_sink.close().await;
+    });
+
+    tokio::spawn({
+        let messages = messages.clone();
+        async move {
+            while let Some(message) = websocket_messages.next().await {
+                let payload = match message {
+                    Ok(Message::Text(text)) => text.into(),
+                    Ok(Message::Binary(payload)) => payload,
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        if let Some(code) = close_code(&e) {
+                            let _ = messages.send(Message::Close(Some(CloseFrame {
+                                code,
+                                reason: e.to_string().into(),
+                            })));
+                        }
+                        break;
+                    }
+                };
+                let header = format!("Content-Length: {}\r\n\r\n", payload.len());
+                if client_messages.write_all(header.as_bytes()).await.is_err()
+                    || client_messages.write_all(&payload).await.is_err()
+                {
+                    break;
+                }
+            }
+            // Dropping `client_messages` ends the input of the server, and so the session.
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut server_messages = BufReader::new(&mut server_messages);
+        while let Ok(Some(message)) = read_lsp_message(&mut server_messages).await {
+            let Ok(text) = Utf8Bytes::try_from(message) else {
+                tracing::warn!("dropping an LSP message that isn't valid UTF-8");
+                continue;
+            };
+            if messages.send(Message::Text(text)).is_err() {
+                return;
+            }
+        }
+        let _ = messages.send(Message::Close(None));
+    });
+
+    Ok((server_input, server_output))
+}
+
+/// Code the connection is closed with when the client breaks the protocol, e.g. with
+/// unmasked frames or fragmented control frames, or sends too big a message.
+fn close_code(error: &tungstenite::Error) -> Option<CloseCode> {
+    match error {
+        tungstenite::Error::Protocol(_) => Some(CloseCode::Protocol),
+        tungstenite::Error::Capacity(_) => Some(CloseCode::Size),
+        tungstenite::Error::Utf8(_) => Some(CloseCode::Invalid),
+        _ => None,
+    }
+}
+
+/// Reads the content of the next message written by the LSP server, framed by its
+/// `Content-Length` header, or `None` once the server is done.
+async fn read_lsp_message<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "LSP message without Content-Length",
+        ));
+    };
+    let mut content = vec![0u8; content_length];
+    reader.read_exact(&mut content).await?;
+    Ok(Some(content))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    #[test]
+    fn it_parses_the_listen_addresses() {
+        assert_eq!(
+            "127.0.0.1:9257".parse(),
+            Ok(ListenAddress::Tcp("127.0.0.1:9257".to_string()))
+        );
+        assert_eq!(
+            "tcp://0.0.0.0:9257".parse(),
+            Ok(ListenAddress::Tcp("0.0.0.0:9257".to_string()))
+        );
+        assert_eq!(
+            "ws://localhost:9257/".parse(),
+            Ok(ListenAddress::WebSocket("localhost:9257".to_string()))
+        );
+        assert!("localhost".parse::<ListenAddress>().is_err());
+        assert!("http://localhost:9257".parse::<ListenAddress>().is_err());
+    }
+
+    /// Client connected to `accept_websocket`, past the handshake, with the input and
+    /// output of the server.
+    async fn open_websocket() -> (BufReader<TcpStream>, BufReader<DuplexStream>, DuplexStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut client = TcpStream::connect(address).await.unwrap();
+        client
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let (server_input, server_output) = accept_websocket(stream).await.unwrap();
+
+        let mut client = BufReader::new(client);
+        let mut response = String::new();
+        while !response.ends_with("\r\n\r\n") {
+            client.read_line(&mut response).await.unwrap();
+        }
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+        // The accept key of the handshake example of RFC 6455.
+        assert!(
+            response
+                .to_ascii_lowercase()
+                .contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo=\r\n")
+        );
+
+        (client, BufReader::new(server_input), server_output)
+    }
+
+    /// Frame of the client: `first_byte` holds FIN and the opcode, and the payload is
+    /// masked as clients must.
+    fn masked_frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [1, 2, 3, 4];
+        let mut frame = vec![first_byte];
+        match payload.len() {
+            length @ 0..=125 => frame.push(0x80 | length as u8),
+            length => {
+                frame.push(0x80 | 126);
+                frame.extend((length as u16).to_be_bytes());
+            }
+        }
+        frame.extend(mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+        frame
+    }
+
+    /// Opcode and payload of the next (unmasked) frame of the server.
+    async fn read_server_frame(client: &mut BufReader<TcpStream>) -> (u8, Vec<u8>) {
+        let mut header = [0u8; 2];
+        client.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0] & 0x80, 0x80, "the server doesn't fragment frames");
+        assert!(header[1] < 126, "longer frames than the tests send");
+        let mut payload = vec![0u8; usize::from(header[1])];
+        client.read_exact(&mut payload).await.unwrap();
+        (header[0] & 0x0F, payload)
+    }
+
+    #[tokio::test]
+    async fn it_exchanges_lsp_messages_over_a_websocket() {
+        let (mut client, mut server_input, mut server_output) = open_websocket().await;
+
+        // A masked text message of the client reaches the server with its header.
+        let request = br#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#;
+        client
+            .get_mut()
+            .write_all(&masked_frame(0x81, request))
+            .await
+            .unwrap();
+        assert_eq!(
+            read_lsp_message(&mut server_input).await.unwrap().unwrap(),
+            request
+        );
+
+        // A message of the server reaches the client as a text message.
+        let response = br#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+        server_output
+            .write_all(format!("Content-Length: {}\r\n\r\n", response.len()).as_bytes())
+            .await
+            .unwrap();
+        server_output.write_all(response).await.unwrap();
+        assert_eq!(
+            read_server_frame(&mut client).await,
+            (0x1, response.to_vec())
+        );
+
+        // Closing the WebSocket ends the input of the server.
+        client
+            .get_mut()
+            .write_all(&masked_frame(0x88, &[]))
+            .await
+            .unwrap();
+        assert_eq!(read_lsp_message(&mut server_input).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn it_answers_pings_between_the_fragments_of_a_message() {
+        let (mut client, mut server_input, _server_output) = open_websocket().await;
+        let request = br#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#;
+        let (first, last) = request.split_at(10);
+
+        let mut frames = masked_frame(0x01, first);
+        frames.extend(masked_frame(0x89, b"ping"));
+        frames.extend(masked_frame(0x80, last));
+        client.get_mut().write_all(&frames).await.unwrap();
+
+        assert_eq!(
+            read_lsp_message(&mut server_input).await.unwrap().unwrap(),
+            request
+        );
+        assert_eq!(
+            read_server_frame(&mut client).await,
+            (0xA, b"ping".to_vec())
+        );
+    }
+
+    #[rstest]
+    #[case::unmasked_frame(vec![0x81, 2, b'{', b'}'], CloseCode::Protocol)]
+    #[case::fragmented_control_frame(masked_frame(0x09, b"ping"), CloseCode::Protocol)]
+    #[case::long_control_frame(masked_frame(0x89, &[0; 126]), CloseCode::Protocol)]
+    // A continuation frame claiming the longest 64-bit length, with its mask.
+    #[case::overflowing_fragments(
+        [
+            masked_frame(0x01, b"{}"),
+            vec![0x80, 0x80 | 127],
+            u64::MAX.to_be_bytes().to_vec(),
+            vec![0; 4],
+        ]
+        .concat(),
+        CloseCode::Size
+    )]
+    #[tokio::test]
+    async fn it_closes_the_connection_on_frames_breaking_the_protocol(
+        #[case] frames: Vec<u8>,
+        #[case] expected_code: CloseCode,
+    ) {
+        let (mut client, mut server_input, _server_output) = open_websocket().await;
+
+        client.get_mut().write_all(&frames).await.unwrap();
+
+        assert_eq!(read_lsp_message(&mut server_input).await.unwrap(), None);
+        let (opcode, payload) = read_server_frame(&mut client).await;
+        assert_eq!(opcode, 0x8);
+        assert_eq!(
+            u16::from_be_bytes([payload[0], payload[1]]),
+            u16::from(expected_code)
+        );
+    }
+}
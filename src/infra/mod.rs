@@ -1,20 +1,60 @@
+mod build_context;
+mod buildx_image_builder;
+mod circleci_config_ast_parser;
 mod component_factory_impl;
 mod compose_ast_parser;
+mod docker_context;
 mod docker_image_builder;
+mod docker_image_digest_resolver;
 mod docker_socket_discovery;
 mod dockerfile_ast_parser;
+mod github_workflow_ast_parser;
+mod gitlab_ci_ast_parser;
+mod json_scan_result_store;
 mod k8s_manifest_ast_parser;
+mod kustomization_parser;
+mod lsp_transport;
+mod oci_registry_client;
+mod registry_credentials;
+mod remote_scan_cache;
+mod retry_policy;
+mod scan_fixture;
 mod scanner_binary_manager;
+mod sysdig_api_client;
+mod sysdig_api_image_scanner;
 mod sysdig_iac_scanner;
 mod sysdig_iac_scanner_json_result_v1;
 mod sysdig_image_scanner;
+mod sysdig_image_scanner_json_scan_result;
 mod sysdig_image_scanner_json_scan_result_v1;
+mod token_providers;
 
+pub use sysdig_api_client::SysdigApiClient;
 pub use sysdig_image_scanner::{SysdigAPIToken, SysdigImageScanner};
 pub mod lsp_logger;
+pub use buildx_image_builder::BuildxImageBuilder;
+pub use circleci_config_ast_parser::parse_circleci_config;
 pub use component_factory_impl::ConcreteComponentFactory;
-pub use compose_ast_parser::parse_compose_file;
+pub use compose_ast_parser::{
+    BuildInstruction, EffectiveImage, find_compose_override, merge_compose_override,
+    parse_compose_builds, parse_compose_file,
+};
 pub use docker_image_builder::DockerImageBuilder;
-pub use docker_socket_discovery::connect_to_docker;
-pub use dockerfile_ast_parser::parse_dockerfile;
-pub use k8s_manifest_ast_parser::parse_k8s_manifest;
+pub use docker_image_digest_resolver::DockerImageDigestResolver;
+pub use docker_socket_discovery::{
+    connect_to_docker, connect_to_docker_context, connect_to_docker_host,
+};
+pub use dockerfile_ast_parser::{Instruction, Stage, parse_dockerfile, parse_dockerfile_stages};
+pub use github_workflow_ast_parser::parse_github_workflow;
+pub use gitlab_ci_ast_parser::parse_gitlab_ci;
+pub use json_scan_result_store::JsonScanResultStore;
+pub use k8s_manifest_ast_parser::{K8sContainer, parse_k8s_containers, parse_k8s_manifest};
+pub use kustomization_parser::{
+    KUSTOMIZATION_FILE_NAMES, find_kustomization, kustomization_resource_images,
+    parse_kustomization,
+};
+pub use lsp_transport::{ListenAddress, accept_websocket};
+pub use oci_registry_client::OciRegistryClient;
+pub use remote_scan_cache::{RemoteCachedImageScanner, RemoteScanCache};
+pub use scan_fixture::{DEFAULT_FIXTURES_DIRECTORY, generate_scan_fixture};
+pub use token_providers::{EnvVarTokenProvider, KeychainTokenProvider};
@@ -0,0 +1,392 @@
+use std::{sync::Arc, time::Duration};
+
+use reqwest::{
+    Client, Method, RequestBuilder, Response, StatusCode,
+    header::{ACCEPT, AUTHORIZATION, WWW_AUTHENTICATE},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use tracing::debug;
+
+use crate::app::{ImageTag, RegistryClient, RegistryError, split_image_tag};
+
+use super::registry_credentials::{RegistryCredentialStore, RegistryCredentials};
+
+const DOCKER_HUB_API_URL: &str = "https://hub.docker.com";
+const DOCKER_HUB_HOSTS: [&str; 3] = ["docker.io", "index.docker.io", "registry-1.docker.io"];
+/// Host of the distribution API of Docker Hub, used to resolve digests.
+const DOCKER_HUB_REGISTRY_HOST: &str = "registry-1.docker.io";
+/// Manifest types accepted when resolving digests, multi-platform indexes first so
+/// the digest is valid for every platform.
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.oci.image.index.v1+json, \
+application/vnd.docker.distribution.manifest.list.v2+json, \
+application/vnd.oci.image.manifest.v1+json, \
+application/vnd.docker.distribution.manifest.v2+json";
+const DOCKER_CONTENT_DIGEST: &str = "Docker-Content-Digest";
+/// Tags listed per repository, enough for completion without paging.
+const MAX_TAGS: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lists tags through the Docker Hub API, which also returns their digests, or the
+/// OCI distribution API (`/v2/<name>/tags/list`) of any other registry (and of Docker
+/// Hub, when it has credentials of it). When the registry asks for authentication, it
+/// requests a token with the credentials of the registry, anonymously without them.
+pub struct OciRegistryClient {
+    client: Client,
+    docker_hub_api_url: String,
+    credentials: Arc<RegistryCredentialStore>,
+}
+
+impl Default for OciRegistryClient {
+    fn default() -> Self {
+        Self {
+            client: Client::new(),
+            docker_hub_api_url: DOCKER_HUB_API_URL.to_string(),
+            credentials: Default::default(),
+        }
+    }
+}
+
+/// How a request to a registry is authenticated.
+enum Authorization {
+    Bearer(String),
+    Basic(RegistryCredentials),
+}
+
+/// `GET /v2/repositories/<namespace>/<name>/tags` of the Docker Hub API.
+#[derive(Deserialize)]
+struct DockerHubTags {
+    results: Vec<DockerHubTag>,
+}
+
+#[derive(Deserialize)]
+struct DockerHubTag {
+    name: String,
+    digest: Option<String>,
+}
+
+/// `GET /v2/<name>/tags/list` of the OCI distribution API.
+#[derive(Deserialize)]
+struct OciTags {
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(alias = "access_token")]
+    token: String,
+}
+
+/// Where the tags of an image repository are listed.
+#[derive(Debug, PartialEq, Eq)]
+enum Repository {
+    DockerHub { path: String },
+    Registry { host: String, path: String },
+}
+
+impl Repository {
+    fn parse(repository: &str) -> Self {
+        let (first, rest) = repository.split_once('/').unwrap_or(("", repository));
+        let is_host = first.contains('.') || first.contains(':') || first == "localhost";
+        match (is_host, first) {
+            (true, host) if DOCKER_HUB_HOSTS.contains(&host) => Self::docker_hub(rest),
+            (true, host) => Repository::Registry {
+                host: host.to_string(),
+                path: rest.to_string(),
+            },
+            (false, _) => Self::docker_hub(repository),
+        }
+    }
+
+    /// Official images live in the `library` namespace.
+    fn docker_hub(path: &str) -> Self {
+        let path = if path.contains('/') {
+            path.to_string()
+        } else {
+            format!("library/{path}")
+        };
+        Repository::DockerHub { path }
+    }
+}
+
+impl OciRegistryClient {
+    /// Authenticates to the registries with their credentials in `credentials`.
+    pub fn with_credentials(self, credentials: Arc<RegistryCredentialStore>) -> Self {
+        Self {
+            credentials,
+            ..self
+        }
+    }
+
+    fn request(
+        &self,
+        method: Method,
+        url: &str,
+        authorization: Option<&Authorization>,
+    ) -> RequestBuilder {
+        let request = self.client.request(method, url).timeout(REQUEST_TIMEOUT);
+        match authorization {
+            Some(Authorization::Bearer(token)) => {
+                request.header(AUTHORIZATION, format!("Bearer {token}"))
+            }
+            Some(Authorization::Basic(credentials)) => {
+                request.basic_auth(&credentials.username, Some(&credentials.password))
+            }
+            None => request,
+        }
+    }
+
+    /// Sends the request built by `request`, and again authenticated if the registry
+    /// of `host` answers `401`: with the token of a bearer challenge, or with the
+    /// credentials of the registry for a basic one.
+    async fn send_authenticated(
+        &self,
+        url: &str,
+        host: &str,
+        request: impl Fn(Option<&Authorization>) -> RequestBuilder,
+    ) -> Result<Response, RegistryError> {
+        let response = send(url, request(None)).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return successful(url, response);
+        }
+
+        let credentials = self.credentials.credentials_for(host).await;
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .unwrap_or_default();
+        let authorization = match (bearer_token_url(challenge), credentials) {
+            (Some(token_url), credentials) => {
+                Authorization::Bearer(self.token(&token_url, credentials.as_ref()).await?)
+            }
+            (None, Some(credentials)) if challenge.starts_with("Basic") => {
+                Authorization::Basic(credentials)
+            }
+            (None, _) => {
+                return Err(RegistryError::UnexpectedStatus {
+                    url: url.to_string(),
+                    status: StatusCode::UNAUTHORIZED.as_u16(),
+                });
+            }
+        };
+        successful(url, send(url, request(Some(&authorization))).await?)
+    }
+
+    async fn docker_hub_tags(&self, path: &str) -> Result<Vec<ImageTag>, RegistryError> {
+        let url = format!(
+            "{}/v2/repositories/{path}/tags?page_size={MAX_TAGS}&ordering=last_updated",
+            self.docker_hub_api_url.trim_end_matches('/')
+        );
+        let response = send(&url, self.request(Method::GET, &url, None)).await?;
+        let tags: DockerHubTags = json(&url, successful(&url, response)?).await?;
+
+        Ok(tags
+            .results
+            .into_iter()
+            .map(|tag| ImageTag {
+                name: tag.name,
+                digest: tag.digest,
+            })
+            .collect())
+    }
+
+    async fn registry_tags(&self, host: &str, path: &str) -> Result<Vec<ImageTag>, RegistryError> {
+        let url = format!("{}/v2/{path}/tags/list?n={MAX_TAGS}", registry_url(host));
+        let response = self
+            .send_authenticated(&url, host, |authorization| {
+                self.request(Method::GET, &url, authorization)
+            })
+            .await?;
+        let tags: OciTags = json(&url, response).await?;
+
+        Ok(tags
+            .tags
+            .into_iter()
+            .take(MAX_TAGS)
+            .map(|name| ImageTag { name, digest: None })
+            .collect())
+    }
+
+    /// Requests the token a bearer challenge of the registry asks for, at `token_url`,
+    /// with the credentials of the registry, or anonymously, which is enough for public
+    /// repositories.
+    async fn token(
+        &self,
+        token_url: &str,
+        credentials: Option<&RegistryCredentials>,
+    ) -> Result<String, RegistryError> {
+        debug!(
+            "requesting a registry token from {token_url} {}",
+            match credentials {
+                Some(credentials) => format!("as {}", credentials.username),
+                None => "anonymously".to_string(),
+            }
+        );
+
+        let authorization = credentials.cloned().map(Authorization::Basic);
+        let request = self.request(Method::GET, token_url, authorization.as_ref());
+        let response = send(token_url, request).await?;
+        let token: TokenResponse = json(token_url, successful(token_url, response)?).await?;
+        Ok(token.token)
+    }
+}
+
+async fn send(url: &str, request: RequestBuilder) -> Result<Response, RegistryError> {
+    request.send().await.map_err(|e| unreachable(url, e))
+}
+
+fn successful(url: &str, response: Response) -> Result<Response, RegistryError> {
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        status => Err(RegistryError::UnexpectedStatus {
+            url: url.to_string(),
+            status: status.as_u16(),
+        }),
+    }
+}
+
+async fn json<T: DeserializeOwned>(url: &str, response: Response) -> Result<T, RegistryError> {
+    let body = response.bytes().await.map_err(|e| unreachable(url, e))?;
+    serde_json::from_slice(&body).map_err(|e| RegistryError::InvalidResponse {
+        url: url.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Base URL of the distribution API of a registry host, over plain HTTP for local registries.
+fn registry_url(host: &str) -> String {
+    match host.starts_with("localhost") {
+        true => format!("http://{host}"),
+        false => format!("https://{host}"),
+    }
+}
+
+fn unreachable(url: &str, reason: impl ToString) -> RegistryError {
+    RegistryError::Unreachable {
+        url: url.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// URL of the token endpoint of a `Bearer realm="...",service="...",scope="..."`
+/// challenge, with the service and scope as query parameters.
+fn bearer_token_url(challenge: &str) -> Option<String> {
+    let mut parameters = challenge.strip_prefix("Bearer ")?.trim();
+    let mut realm = None;
+    let mut query = vec![];
+    // Quoted values may contain commas, e.g. `scope="repository:org/app:pull,push"`.
+    while let Some((key, rest)) = parameters.split_once('=') {
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"')?,
+            None => rest.split_once(',').unwrap_or((rest, "")),
+        };
+        match key.trim() {
+            "realm" => realm = Some(value),
+            key @ ("service" | "scope") => query.push(format!("{key}={value}")),
+            _ => {}
+        }
+        parameters = rest.trim_start_matches([',', ' ']);
+    }
+
+    let realm = realm?;
+    Some(match query.is_empty() {
+        true => realm.to_string(),
+        false => format!("{realm}?{}", query.join("&")),
+    })
+}
+
+#[async_trait::async_trait]
+impl RegistryClient for OciRegistryClient {
+    async fn list_tags(&self, repository: &str) -> Result<Vec<ImageTag>, RegistryError> {
+        match Repository::parse(repository) {
+            // The Docker Hub API only lists public repositories.
+            Repository::DockerHub { path }
+                if self
+                    .credentials
+                    .credentials_for(DOCKER_HUB_REGISTRY_HOST)
+                    .await
+                    .is_some() =>
+            {
+                self.registry_tags(DOCKER_HUB_REGISTRY_HOST, &path).await
+            }
+            Repository::DockerHub { path } => self.docker_hub_tags(&path).await,
+            Repository::Registry { host, path } => self.registry_tags(&host, &path).await,
+        }
+    }
+
+    async fn resolve_digest(&self, image: &str) -> Result<String, RegistryError> {
+        let (repository, tag) = split_image_tag(image);
+        let (host, path) = match Repository::parse(repository) {
+            Repository::DockerHub { path } => (DOCKER_HUB_REGISTRY_HOST.to_string(), path),
+            Repository::Registry { host, path } => (host, path),
+        };
+        let url = format!(
+            "{}/v2/{path}/manifests/{}",
+            registry_url(&host),
+            tag.unwrap_or("latest")
+        );
+
+        let response = self
+            .send_authenticated(&url, &host, |authorization| {
+                self.request(Method::HEAD, &url, authorization)
+                    .header(ACCEPT, MANIFEST_MEDIA_TYPES)
+            })
+            .await?;
+        response
+            .headers()
+            .get(DOCKER_CONTENT_DIGEST)
+            .and_then(|digest| digest.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| RegistryError::InvalidResponse {
+                url,
+                reason: format!("missing {DOCKER_CONTENT_DIGEST} header"),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case("nginx", Repository::DockerHub { path: "library/nginx".to_string() })]
+    #[case("bitnami/redis", Repository::DockerHub { path: "bitnami/redis".to_string() })]
+    #[case("docker.io/nginx", Repository::DockerHub { path: "library/nginx".to_string() })]
+    #[case("ghcr.io/org/app", Repository::Registry { host: "ghcr.io".to_string(), path: "org/app".to_string() })]
+    #[case("localhost:5000/app", Repository::Registry { host: "localhost:5000".to_string(), path: "app".to_string() })]
+    fn it_finds_the_registry_of_a_repository(
+        #[case] repository: &str,
+        #[case] expected: Repository,
+    ) {
+        assert_eq!(Repository::parse(repository), expected);
+    }
+
+    #[test]
+    fn it_builds_the_token_url_of_a_bearer_challenge() {
+        let challenge = r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:org/app:pull""#;
+
+        assert_eq!(
+            bearer_token_url(challenge).as_deref(),
+            Some("https://ghcr.io/token?service=ghcr.io&scope=repository:org/app:pull")
+        );
+    }
+
+    #[test]
+    fn it_keeps_commas_of_quoted_challenge_parameters() {
+        let challenge =
+            r#"Bearer realm="https://auth.example.com/token", scope="repository:app:pull,push""#;
+
+        assert_eq!(
+            bearer_token_url(challenge).as_deref(),
+            Some("https://auth.example.com/token?scope=repository:app:pull,push")
+        );
+    }
+
+    #[test]
+    fn it_ignores_non_bearer_challenges() {
+        assert_eq!(bearer_token_url(r#"Basic realm="registry""#), None);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{collections::HashMap, fmt, fs, path::Path, process::Stdio, time::Duration};
+
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::Deserialize;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{debug, warn};
+
+use crate::app::redaction::REDACTED;
+
+use super::docker_context::docker_config_dir;
+
+/// Registry the Docker CLI stores the credentials of Docker Hub under.
+const DOCKER_HUB_SERVER_URL: &str = "https://index.docker.io/v1/";
+const DOCKER_HUB_HOSTS: [&str; 3] = ["docker.io", "index.docker.io", "registry-1.docker.io"];
+/// Username credential helpers answer with for identity tokens, which only the Docker
+/// daemon knows how to exchange.
+const IDENTITY_TOKEN_USERNAME: &str = "<token>";
+const CREDENTIAL_HELPER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Username and password of a container registry.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RegistryCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for RegistryCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegistryCredentials")
+            .field("username", &self.username)
+            .field("password", &REDACTED)
+            .finish()
+    }
+}
+
+/// The parts of the Docker CLI `config.json` telling the credentials of each registry.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DockerCredentialsConfig {
+    #[serde(default)]
+    auths: HashMap<String, DockerAuth>,
+    creds_store: Option<String>,
+    #[serde(default)]
+    cred_helpers: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct DockerAuth {
+    /// Base64 of `username:password`.
+    auth: Option<String>,
+}
+
+/// Output of `docker-credential-<helper> get`.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct HelperCredentials {
+    username: String,
+    secret: String,
+}
+
+/// Credentials of private registries, for the scanner and the `OciRegistryClient`. The
+/// ones of `sysdig.registry.credentials` win over the ones of the Docker CLI
+/// configuration: its `auths`, then its credential helpers (`credHelpers`, `credsStore`).
+#[derive(Debug, Default)]
+pub struct RegistryCredentialStore {
+    configured: HashMap<String, RegistryCredentials>,
+    docker_config: Option<DockerCredentialsConfig>,
+}
+
+impl RegistryCredentialStore {
+    /// Credentials by registry host, e.g. `ghcr.io` or `docker.io`.
+    pub fn new(configured: impl IntoIterator<Item = (String, RegistryCredentials)>) -> Self {
+        Self {
+            configured: configured
+                .into_iter()
+                .map(|(registry, credentials)| (registry_key(&registry), credentials))
+                .collect(),
+            docker_config: None,
+        }
+    }
+
+    /// Also looks up the credentials the Docker CLI uses, from the `config.json` of its
+    /// configuration directory (`$DOCKER_CONFIG` or `~/.docker`).
+    pub fn with_docker_config(self) -> Self {
+        match docker_config_dir() {
+            Some(config_dir) => self.with_docker_config_in(&config_dir),
+            None => self,
+        }
+    }
+
+    fn with_docker_config_in(self, config_dir: &Path) -> Self {
+        let path = config_dir.join("config.json");
+        let Ok(contents) = fs::read_to_string(&path) else {
+            debug!("no Docker CLI configuration at {}", path.display());
+            return self;
+        };
+        match serde_json::from_str(&contents) {
+            Ok(docker_config) => Self {
+                docker_config: Some(docker_config),
+                ..self
+            },
+            Err(e) => {
+                warn!(
+                    "ignoring the registry credentials of {}: {e}",
+                    path.display()
+                );
+                self
+            }
+        }
+    }
+
+    /// Credentials of `registry` (a host such as `ghcr.io`), if any.
+    pub async fn credentials_for(&self, registry: &str) -> Option<RegistryCredentials> {
+        let key = registry_key(registry);
+        if let Some(credentials) = self.configured.get(&key) {
+            return Some(credentials.clone());
+        }
+
+        let docker_config = self.docker_config.as_ref()?;
+        let helper = docker_config
+            .cred_helpers
+            .iter()
+            .find(|(server, _)| registry_key(server) == key)
+            .map(|(_, helper)| helper);
+        if let Some(helper) = helper {
+            return credential_helper_get(helper, &key).await;
+        }
+        let stored = docker_config
+            .auths
+            .iter()
+            .find(|(server, _)| registry_key(server) == key)
+            .and_then(|(_, auth)| auth.auth.as_deref())
+            .and_then(decode_auth);
+        match (stored, &docker_config.creds_store) {
+            (Some(credentials), _) => Some(credentials),
+            (None, Some(store)) => credential_helper_get(store, &key).await,
+            (None, None) => None,
+        }
+    }
+}
+
+/// Registry host credentials are stored under, without scheme nor path, and with the
+/// aliases of Docker Hub as `docker.io`.
+fn registry_key(server: &str) -> String {
+    let host = server
+        .split_once("://")
+        .map_or(server, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    match DOCKER_HUB_HOSTS.contains(&host.as_str()) {
+        true => "docker.io".to_string(),
+        false => host,
+    }
+}
+
+fn decode_auth(auth: &str) -> Option<RegistryCredentials> {
+    let decoded = BASE64.decode(auth.trim()).ok()?;
+    let (username, password) = String::from_utf8(decoded)
+        .ok()?
+        .split_once(':')
+        .map(|(username, password)| (username.to_string(), password.to_string()))?;
+    Some(RegistryCredentials { username, password })
+}
+
+/// Asks `docker-credential-<helper>` for the credentials of `registry`, as the Docker CLI does.
+async fn credential_helper_get(helper: &str, registry: &str) -> Option<RegistryCredentials> {
+    let program = format!("docker-credential-{helper}");
+    let server_url = match registry {
+        "docker.io" => DOCKER_HUB_SERVER_URL,
+        registry => registry,
+    };
+    let output = tokio::time::timeout(CREDENTIAL_HELPER_TIMEOUT, async {
+        let mut child = Command::new(&program)
+            .arg("get")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(server_url.as_bytes()).await?;
+        }
+        child.wait_with_output().await
+    })
+    .await;
+
+    match output {
+        Ok(Ok(output)) if output.status.success() => helper_credentials(&output.stdout),
+        Ok(Ok(output)) => {
+            // Helpers fail for registries they have no credentials of.
+            debug!(
+                "{program} has no credentials of {registry}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Ok(Err(e)) => {
+            warn!("unable to run {program} for the credentials of {registry}: {e}");
+            None
+        }
+        Err(_) => {
+            warn!("{program} didn't answer for the credentials of {registry} in time");
+            None
+        }
+    }
+}
+
+fn helper_credentials(output: &[u8]) -> Option<RegistryCredentials> {
+    let credentials: HelperCredentials = serde_json::from_slice(output).ok()?;
+    (credentials.username != IDENTITY_TOKEN_USERNAME).then_some(RegistryCredentials {
+        username: credentials.username,
+        password: credentials.secret,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn credentials(username: &str, password: &str) -> RegistryCredentials {
+        RegistryCredentials {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[rstest]
+    #[case("ghcr.io", "ghcr.io")]
+    #[case("https://index.docker.io/v1/", "docker.io")]
+    #[case("registry-1.docker.io", "docker.io")]
+    #[case("https://Registry.Example.com:5000/v2/", "registry.example.com:5000")]
+    fn it_keys_credentials_by_registry_host(#[case] server: &str, #[case] expected: &str) {
+        assert_eq!(registry_key(server), expected);
+    }
+
+    #[tokio::test]
+    async fn it_prefers_the_configured_credentials_over_the_docker_ones() {
+        let config_dir = tempfile::tempdir().unwrap();
+        let auth = |value: &str| BASE64.encode(value);
+        fs::write(
+            config_dir.path().join("config.json"),
+            serde_json::json!({
+                "auths": {
+                    "https://index.docker.io/v1/": { "auth": auth("hub-user:hub-password") },
+                    "ghcr.io": { "auth": auth("docker-user:docker-password") },
+                    "quay.io": {}
+                }
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let store = RegistryCredentialStore::new([(
+            "https://ghcr.io".to_string(),
+            credentials("configured-user", "configured-password"),
+        )])
+        .with_docker_config_in(config_dir.path());
+
+        assert_eq!(
+            store.credentials_for("ghcr.io").await,
+            Some(credentials("configured-user", "configured-password"))
+        );
+        assert_eq!(
+            store.credentials_for("registry-1.docker.io").await,
+            Some(credentials("hub-user", "hub-password"))
+        );
+        assert_eq!(store.credentials_for("quay.io").await, None);
+        assert_eq!(store.credentials_for("gcr.io").await, None);
+    }
+
+    #[test]
+    fn it_reads_the_credentials_of_helpers_but_not_identity_tokens() {
+        assert_eq!(
+            helper_credentials(br#"{"ServerURL":"ghcr.io","Username":"user","Secret":"password"}"#),
+            Some(credentials("user", "password"))
+        );
+        assert_eq!(
+            helper_credentials(br#"{"ServerURL":"ghcr.io","Username":"<token>","Secret":"t"}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn it_redacts_passwords_when_logged() {
+        let logged = format!("{:?}", credentials("user", "hunter2"));
+
+        assert!(!logged.contains("hunter2"), "{logged}");
+    }
+}
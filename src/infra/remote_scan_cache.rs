@@ -0,0 +1,285 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{
+    Client, StatusCode, Url,
+    header::{AUTHORIZATION, ETAG, IF_MATCH, IF_NONE_MATCH},
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::{
+    app::{ImageDigestResolver, ImageScanError, ImageScanner, digest_in_pull_string},
+    domain::scanresult::scan_result::ScanResult,
+};
+
+use super::{SysdigImageScanner, sysdig_image_scanner_json_scan_result::JsonScanResult};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Error, Debug)]
+pub(in crate::infra) enum RemoteScanCacheError {
+    #[error("http error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("unexpected response status {0}")]
+    UnexpectedStatus(StatusCode),
+
+    #[error("invalid cached report: {0}")]
+    InvalidReport(#[from] serde_json::Error),
+}
+
+/// Scan report as stored in the remote cache.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedReport {
+    scanned_at: DateTime<Utc>,
+    /// The JSON report emitted by the CLI scanner.
+    report: serde_json::Value,
+}
+
+/// A report found in the remote cache, with the version to replace if it's stale.
+struct CacheHit {
+    cached: CachedReport,
+    etag: Option<String>,
+}
+
+/// Team-shared HTTP cache of scan reports: `GET`/`PUT <remote_url>/<digest>`, so an
+/// image scanned by one developer is reused by the rest of the team.
+#[derive(Clone)]
+pub struct RemoteScanCache {
+    client: Client,
+    base_url: Url,
+    token: Option<String>,
+    ttl: Duration,
+}
+
+impl RemoteScanCache {
+    pub fn new(base_url: Url, token: Option<String>, ttl: Duration) -> Self {
+        Self {
+            client: Client::new(),
+            base_url,
+            token,
+            ttl,
+        }
+    }
+
+    fn url_for(&self, digest: &str) -> String {
+        format!("{}/{digest}", self.base_url.as_str().trim_end_matches('/'))
+    }
+
+    fn with_auth(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => request.header(AUTHORIZATION, format!("Bearer {token}")),
+            None => request,
+        }
+    }
+
+    async fn get(&self, digest: &str) -> Result<Option<CacheHit>, RemoteScanCacheError> {
+        let response = self
+            .with_auth(self.client.get(self.url_for(digest)))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => {
+                return Err(RemoteScanCacheError::UnexpectedStatus(status));
+            }
+            _ => {}
+        }
+
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string);
+        let cached = serde_json::from_slice(&response.bytes().await?)?;
+        Ok(Some(CacheHit { cached, etag }))
+    }
+
+    /// Publishes the report, only if the cache still holds what we read: nothing
+    /// (`previous` is `None`) or the stale report with the `previous` ETag. Losing the
+    /// race to another developer is fine, their report is as good as ours.
+    async fn put(
+        &self,
+        digest: &str,
+        report: &CachedReport,
+        previous: Option<&CacheHit>,
+    ) -> Result<(), RemoteScanCacheError> {
+        let request = self
+            .with_auth(self.client.put(self.url_for(digest)))
+            .timeout(REQUEST_TIMEOUT)
+            .header("content-type", "application/json")
+            .body(serde_json::to_vec(report)?);
+        let request = match previous {
+            None => request.header(IF_NONE_MATCH, "*"),
+            Some(CacheHit {
+                etag: Some(etag), ..
+            }) => request.header(IF_MATCH, etag),
+            Some(_) => request,
+        };
+
+        let status = request.send().await?.status();
+        match status {
+            StatusCode::PRECONDITION_FAILED => {
+                debug!("{digest} was published to the remote cache by someone else first");
+                Ok(())
+            }
+            status if status.is_success() => Ok(()),
+            status => Err(RemoteScanCacheError::UnexpectedStatus(status)),
+        }
+    }
+
+    fn is_fresh(&self, report: &CachedReport, now: DateTime<Utc>) -> bool {
+        // Reports from clocks slightly ahead of ours are as young as it gets.
+        let age = (now - report.scanned_at).to_std().unwrap_or_default();
+        age < self.ttl
+    }
+}
+
+/// Scanner reusing the reports of the remote cache, and publishing the ones it
+/// produces. The cache is best effort: if it's unreachable or holds something
+/// invalid, images are scanned locally.
+pub struct RemoteCachedImageScanner {
+    scanner: SysdigImageScanner,
+    digest_resolver: Box<dyn ImageDigestResolver + Send + Sync>,
+    cache: RemoteScanCache,
+}
+
+impl RemoteCachedImageScanner {
+    pub fn new(
+        scanner: SysdigImageScanner,
+        digest_resolver: Box<dyn ImageDigestResolver + Send + Sync>,
+        cache: RemoteScanCache,
+    ) -> Self {
+        Self {
+            scanner,
+            digest_resolver,
+            cache,
+        }
+    }
+
+    async fn digest_of(&self, image_pull_string: &str) -> Option<String> {
+        match digest_in_pull_string(image_pull_string) {
+            Some(digest) => Some(digest.to_string()),
+            None => self.digest_resolver.resolve_digest(image_pull_string).await,
+        }
+    }
+
+    /// Scans the image, reusing the report in the cache if fresh and `reuse_cached`.
+    async fn scan(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+        reuse_cached: bool,
+    ) -> Result<ScanResult, ImageScanError> {
+        // The digest of a multi-platform image is the one of its index, shared by the
+        // reports of every platform, so only the default platform is cached.
+        let digest = match platform {
+            Some(_) => None,
+            None => self.digest_of(image_pull_string).await,
+        };
+        let Some(digest) = digest else {
+            return self
+                .scanner
+                .scan_image_for_platform(image_pull_string, platform)
+                .await;
+        };
+
+        let previous = match self.cache.get(&digest).await {
+            Ok(Some(hit)) if reuse_cached && self.cache.is_fresh(&hit.cached, Utc::now()) => {
+                match JsonScanResult::from_value(hit.cached.report.clone()) {
+                    Ok(report) => {
+                        info!("reusing the scan of {image_pull_string} from the remote cache");
+                        return Ok(ScanResult::from(report));
+                    }
+                    Err(e) => {
+                        warn!("ignoring invalid report of {digest} in the remote cache: {e}");
+                        Some(hit)
+                    }
+                }
+            }
+            Ok(previous) => previous,
+            Err(e) => {
+                // Publishing would most likely fail the same way, so don't wait for it.
+                warn!("remote scan cache unavailable, scanning {image_pull_string} locally: {e}");
+                return self.scanner.scan_image(image_pull_string).await;
+            }
+        };
+
+        let (scan_result, report) = self.scanner.scan_with_report(image_pull_string).await?;
+
+        let cache = self.cache.clone();
+        tokio::spawn(async move {
+            let report = CachedReport {
+                scanned_at: Utc::now(),
+                report,
+            };
+            if let Err(e) = cache.put(&digest, &report, previous.as_ref()).await {
+                warn!("unable to publish the scan of {digest} to the remote cache: {e}");
+            }
+        });
+
+        Ok(scan_result)
+    }
+}
+
+#[async_trait::async_trait]
+impl ImageScanner for RemoteCachedImageScanner {
+    async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+        self.scan_image_for_platform(image_pull_string, None).await
+    }
+
+    async fn scan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        self.scan(image_pull_string, platform, true).await
+    }
+
+    async fn rescan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        self.scan(image_pull_string, platform, false).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(base_url: &str) -> RemoteScanCache {
+        RemoteScanCache::new(base_url.parse().unwrap(), None, Duration::from_secs(3600))
+    }
+
+    #[test]
+    fn it_addresses_reports_by_digest() {
+        assert_eq!(
+            cache("https://cache.example.com/scans/").url_for("sha256:abc"),
+            "https://cache.example.com/scans/sha256:abc"
+        );
+        assert_eq!(
+            cache("https://cache.example.com").url_for("sha256:abc"),
+            "https://cache.example.com/sha256:abc"
+        );
+    }
+
+    #[test]
+    fn it_only_reuses_reports_younger_than_the_ttl() {
+        let cache = cache("https://cache.example.com");
+        let now = Utc::now();
+        let report_scanned = |ago: chrono::Duration| CachedReport {
+            scanned_at: now - ago,
+            report: serde_json::Value::Null,
+        };
+
+        assert!(cache.is_fresh(&report_scanned(chrono::Duration::minutes(59)), now));
+        assert!(!cache.is_fresh(&report_scanned(chrono::Duration::minutes(61)), now));
+        assert!(cache.is_fresh(&report_scanned(chrono::Duration::minutes(-1)), now));
+    }
+}
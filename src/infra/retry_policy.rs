@@ -0,0 +1,141 @@
+use std::{fmt::Display, time::Duration};
+
+use tracing::warn;
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Retries operations failing transiently (network blips, rate limits) with jittered
+/// exponential backoff: the delay doubles on each retry, capped at `max_delay`, and a
+/// random part of it is skipped so concurrent retries don't hit the backend together.
+#[derive(Clone, Debug)]
+pub(super) struct RetryPolicy {
+    max_attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ATTEMPTS)
+    }
+}
+
+impl RetryPolicy {
+    /// `max_attempts` counts the first one, so `1` (or `0`) never retries.
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            initial_delay: INITIAL_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+
+    /// Runs `operation` until it succeeds, fails with an error `is_transient` rejects
+    /// or runs out of attempts, returning its last result. Every retry is logged as a
+    /// warning, which clients show in their output.
+    pub async fn retry<T, E, Fut>(
+        &self,
+        operation_name: &str,
+        is_transient: impl Fn(&E) -> bool,
+        mut operation: impl FnMut() -> Fut,
+    ) -> Result<T, E>
+    where
+        E: Display,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 1;
+        loop {
+            // The error is dropped before sleeping, as it may not be `Send`.
+            let delay = match operation().await {
+                Err(e) if attempt < self.max_attempts && is_transient(&e) => {
+                    let delay = self.delay_before_retry(attempt);
+                    warn!(
+                        "{operation_name} failed: {e}, retrying in {}s (attempt {}/{})",
+                        delay.as_secs_f32().ceil(),
+                        attempt + 1,
+                        self.max_attempts
+                    );
+                    delay
+                }
+                result => return result,
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Between half and the whole exponential delay of the `retry`-th retry.
+    fn delay_before_retry(&self, retry: u32) -> Duration {
+        let exponential = self
+            .initial_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(rand::random_range(0.0..=1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    async fn failing_until(attempts: &AtomicU32, succeeds_at: u32) -> Result<u32, String> {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        match attempt >= succeeds_at {
+            true => Ok(attempt),
+            false => Err(format!("attempt {attempt} failed")),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_retries_transient_failures_until_they_succeed() {
+        let attempts = AtomicU32::new(0);
+
+        let result = RetryPolicy::new(3)
+            .retry("download", |_| true, || failing_until(&attempts, 3))
+            .await;
+
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_gives_up_after_the_max_attempts() {
+        let attempts = AtomicU32::new(0);
+
+        let result = RetryPolicy::new(2)
+            .retry("download", |_| true, || failing_until(&attempts, 5))
+            .await;
+
+        assert_eq!(result, Err("attempt 2 failed".to_string()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn it_doesnt_retry_permanent_failures() {
+        let attempts = AtomicU32::new(0);
+
+        let result = RetryPolicy::new(3)
+            .retry("download", |_| false, || failing_until(&attempts, 2))
+            .await;
+
+        assert_eq!(result, Err("attempt 1 failed".to_string()));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn it_doubles_the_delay_up_to_the_max_delay() {
+        let policy = RetryPolicy::default();
+
+        for (retry, exponential) in [(1, 1), (2, 2), (3, 4), (6, 30), (40, 30)] {
+            let delay = policy.delay_before_retry(retry);
+            let exponential = Duration::from_secs(exponential);
+            assert!(
+                delay >= exponential / 2 && delay <= exponential,
+                "{delay:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::app::ImageScanError;
+
+use super::{
+    SysdigAPIToken, SysdigImageScanner, sysdig_image_scanner_json_scan_result::JsonScanResult,
+};
+
+/// Where the scan result fixtures used by the parser tests live.
+pub const DEFAULT_FIXTURES_DIRECTORY: &str = "tests/fixtures/scan-results";
+
+#[derive(Error, Debug)]
+pub enum ScanFixtureError {
+    #[error("unable to scan the image: {0}")]
+    Scan(#[from] ImageScanError),
+
+    #[error("unable to write the fixture: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("unable to serialize the fixture: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Scans `image` and writes its anonymized report to `output_directory`, returning the
+/// path of the fixture.
+pub async fn generate_scan_fixture(
+    image: &str,
+    api_url: String,
+    api_token: SysdigAPIToken,
+    output_directory: &Path,
+) -> Result<PathBuf, ScanFixtureError> {
+    let scanner = SysdigImageScanner::new(api_url, api_token);
+    let mut report = scanner.scan_raw_report(image).await?;
+    anonymize_scan_report(&mut report);
+    // The fixture must still be a valid report after scrubbing it.
+    JsonScanResult::from_value(report.clone())
+        .map_err(|e| ImageScanError::MalformedOutput(e.to_string()))?;
+
+    tokio::fs::create_dir_all(output_directory).await?;
+    let path = output_directory.join(fixture_file_name(image));
+    let mut contents = serde_json::to_vec_pretty(&report)?;
+    contents.push(b'\n');
+    tokio::fs::write(&path, contents).await?;
+
+    Ok(path)
+}
+
+/// Removes the details of the environment the scan ran in from a CLI report: image
+/// labels and author are dropped, risk acceptance authors cleared, and image and layer
+/// digests replaced by a hash of themselves (so references within the report still match).
+pub fn anonymize_scan_report(report: &mut Value) {
+    let Some(result) = report.get_mut("result") else {
+        return;
+    };
+
+    if let Some(metadata) = result.get_mut("metadata").and_then(Value::as_object_mut) {
+        metadata.insert("labels".to_string(), Value::Object(Default::default()));
+        if metadata.contains_key("author") {
+            metadata.insert("author".to_string(), Value::String(String::new()));
+        }
+        for field in ["digest", "imageId"] {
+            if let Some(digest) = metadata.get_mut(field) {
+                hash_digest(digest);
+            }
+        }
+    }
+
+    if let Some(layers) = result.get_mut("layers").and_then(Value::as_object_mut) {
+        for layer in layers.values_mut() {
+            if let Some(digest) = layer.get_mut("digest") {
+                hash_digest(digest);
+            }
+        }
+    }
+
+    if let Some(risk_accepts) = result.get_mut("riskAccepts").and_then(Value::as_object_mut) {
+        for risk_accept in risk_accepts.values_mut().filter_map(Value::as_object_mut) {
+            for field in ["createdBy", "updatedBy"] {
+                if risk_accept.contains_key(field) {
+                    risk_accept.insert(field.to_string(), Value::String(String::new()));
+                }
+            }
+        }
+    }
+}
+
+/// `sha256:<hex>` of the digest; empty digests (e.g. of metadata-only layers) are kept.
+fn hash_digest(digest: &mut Value) {
+    let Some(original) = digest.as_str().filter(|d| !d.is_empty()) else {
+        return;
+    };
+    let hash: String = Sha256::digest(original.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    *digest = Value::String(format!("sha256:{hash}"));
+}
+
+/// `postgres:13` -> `postgres_13.json`, `quay.io/org/app:v1` -> `quay_io_org_app_v1.json`.
+pub fn fixture_file_name(image: &str) -> String {
+    let name: String = image
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{name}.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::domain::scanresult::scan_result::ScanResult;
+
+    #[test]
+    fn it_names_fixtures_after_the_image() {
+        assert_eq!(fixture_file_name("postgres:13"), "postgres_13.json");
+        assert_eq!(
+            fixture_file_name("quay.io/org/app:v1"),
+            "quay_io_org_app_v1.json"
+        );
+    }
+
+    #[test]
+    fn it_scrubs_environment_details() {
+        let mut report = json!({
+            "result": {
+                "metadata": {
+                    "author": "jane@example.com",
+                    "labels": { "com.example.build-host": "ci-runner-42" },
+                    "digest": "sha256:aaaa",
+                    "imageId": "sha256:bbbb",
+                },
+                "layers": {
+                    "l1": { "digest": "sha256:aaaa" },
+                    "l2": { "digest": "" },
+                },
+                "riskAccepts": {
+                    "r1": { "createdBy": "jane@example.com", "reason": "RiskMitigated" },
+                },
+            }
+        });
+
+        anonymize_scan_report(&mut report);
+
+        let result = &report["result"];
+        assert_eq!(result["metadata"]["author"], "");
+        assert_eq!(result["metadata"]["labels"], json!({}));
+        let digest = result["metadata"]["digest"].as_str().unwrap();
+        assert!(digest.starts_with("sha256:") && digest != "sha256:aaaa");
+        assert_eq!(result["layers"]["l1"]["digest"], digest);
+        assert_eq!(result["layers"]["l2"]["digest"], "");
+        assert_eq!(result["riskAccepts"]["r1"]["createdBy"], "");
+        assert_eq!(result["riskAccepts"]["r1"]["reason"], "RiskMitigated");
+    }
+
+    #[test]
+    fn anonymized_fixtures_are_still_valid_reports() {
+        let mut report: Value = serde_json::from_slice(include_bytes!(
+            "../../tests/fixtures/scan-results/postgres_13.json"
+        ))
+        .unwrap();
+
+        anonymize_scan_report(&mut report);
+
+        let scan_result = ScanResult::from(JsonScanResult::from_value(report).unwrap());
+        assert_eq!(scan_result.metadata().pull_string(), "postgres:13");
+        assert!(scan_result.metadata().labels().is_empty());
+        assert!(!scan_result.layers().is_empty());
+    }
+}
@@ -2,15 +2,25 @@
 
 use regex::Regex;
 use semver::Version;
+use sha2::{Digest, Sha256};
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 use tokio::process::Command;
+use tracing::warn;
+
+use super::retry_policy::RetryPolicy;
 
 #[derive(Error, Debug)]
 pub(in crate::infra) enum ScannerBinaryManagerError {
-    #[error("operating system is not supported, current supported systems are linux and darwin")]
+    #[error(
+        "operating system is not supported, current supported systems are linux, darwin and windows"
+    )]
     UnsupportedOS,
 
     #[error("architecture is not supported, current supported architectures are arm64 and amd64")]
@@ -33,24 +43,158 @@ pub(in crate::infra) enum ScannerBinaryManagerError {
 
     #[error("error performing http request: {0}")]
     HTTPError(#[from] reqwest::Error),
+
+    #[error(
+        "the scanner at {path} is version {version}, but at least {expected_version} is required"
+    )]
+    OutdatedScanner {
+        path: PathBuf,
+        version: Version,
+        expected_version: Version,
+    },
+
+    #[error("invalid checksum file of the scanner: {0:?}")]
+    InvalidChecksumFile(String),
+
+    #[error(
+        "the SHA-256 checksum of the downloaded scanner ({actual}) doesn't match the published one ({expected})"
+    )]
+    ChecksumMismatch { expected: String, actual: String },
 }
 
 /// Exit codes of the Sysdig CLI scanner, shared by every scan mode.
 pub(super) const SCANNER_EXIT_CODE_INVALID_PARAMS: i32 = 2;
 pub(super) const SCANNER_EXIT_CODE_INTERNAL_ERROR: i32 = 3;
 
+impl ScannerBinaryManagerError {
+    /// Whether downloading the scanner again may succeed: timeouts, connection errors,
+    /// rate limits and server errors.
+    fn is_transient(&self) -> bool {
+        match self {
+            ScannerBinaryManagerError::HTTPError(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.is_body()
+                    || e.status().is_some_and(|status| {
+                        status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Lists the newest release of the scanner, e.g. `1.23.0`.
+const LATEST_VERSION_URL: &str =
+    "https://download.sysdig.com/scanning/sysdig-cli-scanner/latest_version.txt";
+/// How long the newest release is reused before looking it up again.
+const LATEST_VERSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Version of the scanner to install (`sysdig.scanner.version`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) enum ScannerVersion {
+    /// The version this release of the LSP was tested with.
+    #[default]
+    Default,
+    Pinned(Version),
+    /// The newest release, looked up in the Sysdig download site.
+    Latest,
+}
+
+impl FromStr for ScannerVersion {
+    type Err = semver::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "latest" => Ok(ScannerVersion::Latest),
+            version => Ok(ScannerVersion::Pinned(Version::parse(
+                version.trim_start_matches('v'),
+            )?)),
+        }
+    }
+}
+
 #[derive(Clone, Default)]
-pub(super) struct ScannerBinaryManager {}
+pub(super) struct ScannerBinaryManager {
+    retry_policy: RetryPolicy,
+    /// Binary installed by the user (`sysdig.scanner.path`), never downloaded.
+    preinstalled_path: Option<PathBuf>,
+    requested_version: ScannerVersion,
+    latest_version: Option<(Version, Instant)>,
+    /// Version of the binary last checked to be installed.
+    installed_version: Option<Version>,
+}
 
 impl ScannerBinaryManager {
+    pub fn with_retry_policy(retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..Default::default()
+        }
+    }
+
+    /// Uses the binary at `path` instead of downloading one, once its version is checked.
+    pub fn preinstalled(path: PathBuf) -> Self {
+        Self {
+            preinstalled_path: Some(path),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_version(self, requested_version: ScannerVersion) -> Self {
+        Self {
+            requested_version,
+            ..self
+        }
+    }
+
     const fn version(&self) -> Version {
         Version::new(1, 23, 0)
     }
 
+    /// Version to install: the requested one, falling back to the default one if the
+    /// newest release can't be looked up.
+    async fn expected_version(&mut self) -> Version {
+        match &self.requested_version {
+            ScannerVersion::Default => self.version(),
+            ScannerVersion::Pinned(version) => version.clone(),
+            ScannerVersion::Latest => match self.latest_version().await {
+                Ok(version) => version,
+                Err(e) => {
+                    warn!(
+                        "unable to look up the latest scanner release, using {}: {e}",
+                        self.version()
+                    );
+                    self.version()
+                }
+            },
+        }
+    }
+
+    async fn latest_version(&mut self) -> Result<Version, ScannerBinaryManagerError> {
+        if let Some((version, looked_up_at)) = &self.latest_version
+            && looked_up_at.elapsed() < LATEST_VERSION_TTL
+        {
+            return Ok(version.clone());
+        }
+
+        let body = self.download_with_retries(LATEST_VERSION_URL).await?;
+        let version = parse_latest_version(&body)?;
+        self.latest_version = Some((version.clone(), Instant::now()));
+        Ok(version)
+    }
+
     pub async fn install_expected_version_if_not_present(
         &mut self,
     ) -> Result<PathBuf, ScannerBinaryManagerError> {
-        let expected_version = self.version();
+        let expected_version = self.expected_version().await;
+        if let Some(path) = self.preinstalled_path.clone() {
+            let version = self
+                .check_preinstalled_version(&path, expected_version)
+                .await?;
+            self.installed_version = Some(version);
+            return Ok(path);
+        }
         let binary_path = self.binary_path_for_version(&expected_version);
 
         if self
@@ -60,10 +204,33 @@ impl ScannerBinaryManager {
             self.install_expected_version(&binary_path, &expected_version)
                 .await?;
         }
+        self.installed_version = Some(expected_version);
 
         Ok(binary_path)
     }
 
+    /// Version of the binary returned by the last `install_expected_version_if_not_present`.
+    pub fn installed_version(&self) -> Option<&Version> {
+        self.installed_version.as_ref()
+    }
+
+    /// Version of the preinstalled binary, if it's at least the expected one.
+    async fn check_preinstalled_version(
+        &self,
+        path: &Path,
+        expected_version: Version,
+    ) -> Result<Version, ScannerBinaryManagerError> {
+        let version = self.get_current_installed_version_from(path).await?;
+        if version < expected_version {
+            return Err(ScannerBinaryManagerError::OutdatedScanner {
+                path: path.to_path_buf(),
+                version,
+                expected_version,
+            });
+        }
+        Ok(version)
+    }
+
     async fn needs_to_install_it(
         &self,
         binary_path: &Path,
@@ -83,8 +250,12 @@ impl ScannerBinaryManager {
         binary_path: &Path,
         expected_version: &Version,
     ) -> Result<(), ScannerBinaryManagerError> {
-        let response = reqwest::get(self.download_url(expected_version)?).await?;
-        let body = response.bytes().await?;
+        let download_url = self.download_url(expected_version)?;
+        let checksum_file = self
+            .download_with_retries(&format!("{download_url}.sha256"))
+            .await?;
+        let body = self.download_with_retries(&download_url).await?;
+        verify_checksum(&body, &checksum_file)?;
 
         let parent_path = binary_path.parent().ok_or_else(|| {
             ScannerBinaryManagerError::IOError(std::io::Error::new(
@@ -101,21 +272,21 @@ impl ScannerBinaryManager {
         Ok(())
     }
 
+    async fn download_with_retries(
+        &self,
+        url: &str,
+    ) -> Result<bytes::Bytes, ScannerBinaryManagerError> {
+        self.retry_policy
+            .retry(
+                &format!("download of {url}"),
+                ScannerBinaryManagerError::is_transient,
+                || download(url),
+            )
+            .await
+    }
+
     fn download_url(&self, version: &Version) -> Result<String, ScannerBinaryManagerError> {
-        let os = match std::env::consts::OS {
-            "linux" => "linux",
-            "macos" => "darwin",
-            _ => return Err(ScannerBinaryManagerError::UnsupportedOS),
-        };
-        let arch = match std::env::consts::ARCH {
-            "x86_64" => "amd64",
-            "aarch64" => "arm64",
-            _ => return Err(ScannerBinaryManagerError::UnsupportedArch),
-        };
-
-        Ok(format!(
-            "https://download.sysdig.com/scanning/bin/sysdig-cli-scanner/{version}/{os}/{arch}/sysdig-cli-scanner"
-        ))
+        download_url_for(version, std::env::consts::OS, std::env::consts::ARCH)
     }
 
     async fn get_current_installed_version_from(
@@ -170,25 +341,92 @@ impl ScannerBinaryManager {
 
         #[cfg(windows)]
         {
-            if let Some(ext) = binary_path.extension() {
-                matches!(ext.to_str(), Some("exe") | Some("bat") | Some("cmd"))
-            } else {
-                false
-            }
+            binary_path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ["exe", "bat", "cmd"]
+                        .iter()
+                        .any(|executable| ext.eq_ignore_ascii_case(executable))
+                })
         }
     }
 
     fn binary_path_for_version(&self, version: &Version) -> PathBuf {
         let mut cache_dir = dirs::cache_dir().unwrap_or_else(|| PathBuf::from("."));
         cache_dir.push("sysdig-cli-scanner");
-        cache_dir.push(format!("sysdig-cli-scanner.{version}"));
+        // Windows only runs binaries with an executable extension.
+        cache_dir.push(format!(
+            "sysdig-cli-scanner.{version}{}",
+            std::env::consts::EXE_SUFFIX
+        ));
         cache_dir
     }
 }
 
+/// Download URL of the scanner for an operating system and architecture, as named by
+/// `std::env::consts`. The Windows binary is published with the `.exe` extension.
+fn download_url_for(
+    version: &Version,
+    os: &str,
+    arch: &str,
+) -> Result<String, ScannerBinaryManagerError> {
+    let (os, binary_name) = match os {
+        "linux" => ("linux", "sysdig-cli-scanner"),
+        "macos" => ("darwin", "sysdig-cli-scanner"),
+        "windows" => ("windows", "sysdig-cli-scanner.exe"),
+        _ => return Err(ScannerBinaryManagerError::UnsupportedOS),
+    };
+    let arch = match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        _ => return Err(ScannerBinaryManagerError::UnsupportedArch),
+    };
+
+    Ok(format!(
+        "https://download.sysdig.com/scanning/bin/sysdig-cli-scanner/{version}/{os}/{arch}/{binary_name}"
+    ))
+}
+
+fn parse_latest_version(body: &[u8]) -> Result<Version, ScannerBinaryManagerError> {
+    let body = String::from_utf8_lossy(body);
+    Ok(Version::parse(body.trim().trim_start_matches('v'))?)
+}
+
+/// Checks `binary` against the `<sha256>  sysdig-cli-scanner` file published next to
+/// each release, so a corrupted or tampered download is never made executable.
+fn verify_checksum(binary: &[u8], checksum_file: &[u8]) -> Result<(), ScannerBinaryManagerError> {
+    let checksum_file = String::from_utf8_lossy(checksum_file);
+    let expected = checksum_file
+        .split_whitespace()
+        .next()
+        .filter(|checksum| checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit()))
+        .ok_or_else(|| ScannerBinaryManagerError::InvalidChecksumFile(checksum_file.to_string()))?
+        .to_ascii_lowercase();
+
+    let actual: String = Sha256::digest(binary)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    if actual != expected {
+        return Err(ScannerBinaryManagerError::ChecksumMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+async fn download(url: &str) -> Result<bytes::Bytes, ScannerBinaryManagerError> {
+    let response = reqwest::get(url).await?.error_for_status()?;
+    Ok(response.bytes().await?)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::ScannerBinaryManager;
+    use std::time::Instant;
+
+    use super::{
+        ScannerBinaryManager, ScannerBinaryManagerError, ScannerVersion, download_url_for,
+        parse_latest_version, verify_checksum,
+    };
     use core::panic;
     use semver::Version;
     use serial_test::file_serial;
@@ -220,6 +458,105 @@ mod tests {
         );
     }
 
+    #[rstest::rstest]
+    #[case("latest", ScannerVersion::Latest)]
+    #[case("1.24.1", ScannerVersion::Pinned(Version::new(1, 24, 1)))]
+    #[case("v1.24.1", ScannerVersion::Pinned(Version::new(1, 24, 1)))]
+    fn it_parses_the_requested_version(#[case] setting: &str, #[case] expected: ScannerVersion) {
+        assert_eq!(setting.parse::<ScannerVersion>().unwrap(), expected);
+        assert!("newest".parse::<ScannerVersion>().is_err());
+    }
+
+    #[tokio::test]
+    async fn it_installs_the_requested_version() {
+        let mut mgr = ScannerBinaryManager::default();
+        assert_eq!(mgr.expected_version().await, Version::new(1, 23, 0));
+
+        let mut mgr = mgr.with_version(ScannerVersion::Pinned(Version::new(1, 19, 2)));
+        assert_eq!(mgr.expected_version().await, Version::new(1, 19, 2));
+    }
+
+    #[tokio::test]
+    async fn it_reuses_the_latest_version_looked_up() {
+        let mut mgr = ScannerBinaryManager::default().with_version(ScannerVersion::Latest);
+        mgr.latest_version = Some((Version::new(1, 25, 0), Instant::now()));
+
+        assert_eq!(mgr.expected_version().await, Version::new(1, 25, 0));
+        assert_eq!(
+            parse_latest_version(b"1.25.3\n").unwrap(),
+            Version::new(1, 25, 3)
+        );
+    }
+
+    #[test]
+    fn it_verifies_the_checksum_of_the_download() {
+        let published =
+            b"6105d8625ada3c9bf514062b5696e9d7aa11e4b5e2c3d8629713f0bf2e45f6fd  sysdig-cli-scanner\n";
+
+        assert!(verify_checksum(b"scanner", published).is_ok());
+        assert!(matches!(
+            verify_checksum(b"tampered", published),
+            Err(ScannerBinaryManagerError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            verify_checksum(b"scanner", b"<html>Not Found</html>"),
+            Err(ScannerBinaryManagerError::InvalidChecksumFile(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[rstest::rstest]
+    #[case("1.23.0", true)]
+    #[case("1.30.2", true)]
+    #[case("1.20.0", false)]
+    #[tokio::test]
+    async fn it_uses_a_preinstalled_scanner_of_a_supported_version(
+        #[case] version: &str,
+        #[case] is_supported: bool,
+    ) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sysdig-cli-scanner");
+        std::fs::write(
+            &path,
+            format!("#!/bin/sh\necho 'Sysdig CLI Scanner {version}'\n"),
+        )
+        .unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let result = ScannerBinaryManager::preinstalled(path.clone())
+            .install_expected_version_if_not_present()
+            .await;
+
+        match is_supported {
+            true => assert_eq!(result.unwrap(), path),
+            false => assert!(matches!(
+                result,
+                Err(ScannerBinaryManagerError::OutdatedScanner { .. })
+            )),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("linux", "aarch64", "1.22.1/linux/arm64/sysdig-cli-scanner")]
+    #[case("macos", "x86_64", "1.22.1/darwin/amd64/sysdig-cli-scanner")]
+    #[case("windows", "x86_64", "1.22.1/windows/amd64/sysdig-cli-scanner.exe")]
+    fn it_downloads_the_binary_of_each_platform(
+        #[case] os: &str,
+        #[case] arch: &str,
+        #[case] path: &str,
+    ) {
+        assert_eq!(
+            download_url_for(&Version::new(1, 22, 1), os, arch).unwrap(),
+            format!("https://download.sysdig.com/scanning/bin/sysdig-cli-scanner/{path}")
+        );
+        assert!(matches!(
+            download_url_for(&Version::new(1, 22, 1), "freebsd", arch),
+            Err(ScannerBinaryManagerError::UnsupportedOS)
+        ));
+    }
+
     #[tokio::test]
     #[file_serial]
     async fn it_downloads_if_it_doesnt_exist() {
@@ -0,0 +1,233 @@
+use std::time::Duration;
+
+use reqwest::{
+    Client, StatusCode,
+    header::{AUTHORIZATION, CONTENT_TYPE},
+};
+use serde::Serialize;
+
+use crate::app::{
+    CredentialsChecker, CredentialsError, RiskAcceptance, RiskAcceptanceClient,
+    RiskAcceptanceError, RiskAcceptanceScope,
+};
+
+use super::SysdigAPIToken;
+
+/// Cheap authenticated endpoint, returning the user the token belongs to.
+const CURRENT_USER_PATH: &str = "/api/user/me";
+const ACCEPTED_RISKS_PATH: &str = "/secure/vulnerability/v1beta1/accepted-risks";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Client of the Sysdig HTTP API, for the checks that don't need the CLI scanner.
+pub struct SysdigApiClient {
+    client: Client,
+    api_url: String,
+    api_token: SysdigAPIToken,
+}
+
+impl SysdigApiClient {
+    pub fn new(api_url: String, api_token: SysdigAPIToken) -> Self {
+        Self {
+            client: Client::new(),
+            api_url,
+            api_token,
+        }
+    }
+
+    fn current_user_url(&self) -> String {
+        self.url_for(CURRENT_USER_PATH)
+    }
+
+    fn url_for(&self, path: &str) -> String {
+        format!("{}{path}", self.api_url.trim_end_matches('/'))
+    }
+
+    fn unreachable(&self, reason: impl ToString) -> CredentialsError {
+        CredentialsError::Unreachable {
+            url: self.api_url.clone(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialsChecker for SysdigApiClient {
+    async fn check_credentials(&self) -> Result<(), CredentialsError> {
+        let response = self
+            .client
+            .get(self.current_user_url())
+            .header(AUTHORIZATION, format!("Bearer {}", self.api_token.0))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| self.unreachable(e))?;
+
+        match response.status() {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                Err(CredentialsError::Rejected(self.api_url.clone()))
+            }
+            status if status.is_success() => Ok(()),
+            status => Err(self.unreachable(format!("unexpected response status {status}"))),
+        }
+    }
+}
+
+/// Body of `POST /secure/vulnerability/v1beta1/accepted-risks`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AcceptedRiskRequest<'a> {
+    entity_type: &'static str,
+    entity_value: &'a str,
+    context: Vec<AcceptedRiskContext<'a>>,
+    reason: &'static str,
+    description: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expiration_date: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AcceptedRiskContext<'a> {
+    #[serde(rename = "type")]
+    context_type: &'static str,
+    value: &'a str,
+}
+
+impl<'a> From<&'a RiskAcceptance> for AcceptedRiskRequest<'a> {
+    fn from(acceptance: &'a RiskAcceptance) -> Self {
+        Self {
+            entity_type: "vulnerability",
+            entity_value: &acceptance.cve,
+            context: match &acceptance.scope {
+                RiskAcceptanceScope::Image(image) => vec![AcceptedRiskContext {
+                    context_type: "imageName",
+                    value: image,
+                }],
+                RiskAcceptanceScope::Package(package) => vec![
+                    AcceptedRiskContext {
+                        context_type: "packageName",
+                        value: &package.name,
+                    },
+                    AcceptedRiskContext {
+                        context_type: "packageVersion",
+                        value: &package.version,
+                    },
+                ],
+            },
+            reason: acceptance.reason.as_str(),
+            description: &acceptance.description,
+            expiration_date: acceptance
+                .expiration_date
+                .map(|date| date.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl RiskAcceptanceClient for SysdigApiClient {
+    async fn accept_risk(&self, acceptance: &RiskAcceptance) -> Result<(), RiskAcceptanceError> {
+        let url = self.url_for(ACCEPTED_RISKS_PATH);
+        let unreachable = |reason: String| RiskAcceptanceError::Unreachable {
+            url: self.api_url.clone(),
+            reason,
+        };
+
+        let body = serde_json::to_vec(&AcceptedRiskRequest::from(acceptance))
+            .map_err(|e| unreachable(e.to_string()))?;
+        let response = self
+            .client
+            .post(url)
+            .header(AUTHORIZATION, format!("Bearer {}", self.api_token.0))
+            .header(CONTENT_TYPE, "application/json")
+            .timeout(REQUEST_TIMEOUT)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| unreachable(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(RiskAcceptanceError::Rejected {
+            cve: acceptance.cve.clone(),
+            status: status.as_u16(),
+            message: response.text().await.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    use crate::app::PackageVersion;
+    use crate::domain::scanresult::accepted_risk_reason::AcceptedRiskReason;
+
+    use super::*;
+
+    #[test]
+    fn it_checks_the_token_against_the_current_user_endpoint() {
+        let client = |api_url: &str| {
+            SysdigApiClient::new(api_url.to_string(), SysdigAPIToken("token".to_string()))
+        };
+
+        assert_eq!(
+            client("https://secure.sysdig.com/").current_user_url(),
+            "https://secure.sysdig.com/api/user/me"
+        );
+        assert_eq!(
+            client("https://eu1.app.sysdig.com").current_user_url(),
+            "https://eu1.app.sysdig.com/api/user/me"
+        );
+    }
+
+    #[test]
+    fn it_scopes_risk_acceptances_to_the_vulnerability_in_the_image() {
+        let acceptance = RiskAcceptance {
+            cve: "CVE-2021-1234".to_string(),
+            scope: RiskAcceptanceScope::Image("alpine:3.18".to_string()),
+            reason: AcceptedRiskReason::RiskMitigated,
+            description: "not reachable".to_string(),
+            expiration_date: NaiveDate::from_ymd_opt(2027, 1, 31),
+        };
+
+        let body = serde_json::to_value(AcceptedRiskRequest::from(&acceptance)).unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "entityType": "vulnerability",
+                "entityValue": "CVE-2021-1234",
+                "context": [{ "type": "imageName", "value": "alpine:3.18" }],
+                "reason": "RiskMitigated",
+                "description": "not reachable",
+                "expirationDate": "2027-01-31"
+            })
+        );
+    }
+
+    #[test]
+    fn it_scopes_risk_acceptances_to_the_vulnerability_in_a_package() {
+        let acceptance = RiskAcceptance {
+            cve: "CVE-2021-1234".to_string(),
+            scope: RiskAcceptanceScope::Package(PackageVersion {
+                name: "openssl".to_string(),
+                version: "3.1.4-r0".to_string(),
+            }),
+            reason: AcceptedRiskReason::RiskOwned,
+            description: String::new(),
+            expiration_date: None,
+        };
+
+        let body = serde_json::to_value(AcceptedRiskRequest::from(&acceptance)).unwrap();
+
+        assert_eq!(
+            body["context"],
+            json!([
+                { "type": "packageName", "value": "openssl" },
+                { "type": "packageVersion", "value": "3.1.4-r0" }
+            ])
+        );
+    }
+}
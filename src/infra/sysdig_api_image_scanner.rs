@@ -0,0 +1,284 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use reqwest::{Client, StatusCode, Url, header::AUTHORIZATION};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{
+    app::{ImageScanError, ImageScanner},
+    domain::{image_reference::normalize_image_reference, scanresult::scan_result::ScanResult},
+};
+
+use super::{
+    SysdigAPIToken, retry_policy::RetryPolicy,
+    sysdig_image_scanner_json_scan_result::JsonScanResult,
+};
+
+const REGISTRY_RESULTS_PATH: &str = "/secure/vulnerability/v1/registry-results";
+const RESULTS_PATH: &str = "/secure/vulnerability/v1/results";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Registry results looked at for a pull string. The free text filter also matches
+/// other tags and repositories containing it, so there can be a few.
+const REGISTRY_RESULTS_LIMIT: usize = 100;
+
+/// Scans images by reading their latest registry scan from the Sysdig Secure API, so
+/// the CLI scanner doesn't have to be downloaded. Only images of registries scanned by
+/// Sysdig Secure have results.
+#[derive(Clone)]
+pub struct SysdigApiImageScanner {
+    client: Client,
+    api_url: String,
+    api_token: SysdigAPIToken,
+    retry_policy: RetryPolicy,
+}
+
+/// Entry of `GET /secure/vulnerability/v1/registry-results`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistryResult {
+    result_id: String,
+    pull_string: String,
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RegistryResults {
+    #[serde(default)]
+    data: Vec<RegistryResult>,
+}
+
+impl SysdigApiImageScanner {
+    pub fn new(api_url: String, api_token: SysdigAPIToken) -> Self {
+        Self {
+            client: Client::new(),
+            api_url,
+            api_token,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Retries requests failing transiently, e.g. rate limited or unable to reach the API.
+    pub(super) fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    fn url_for(&self, path: &str) -> String {
+        format!("{}{path}", self.api_url.trim_end_matches('/'))
+    }
+
+    fn registry_results_url(&self, image_pull_string: &str) -> Result<Url, ImageScanError> {
+        Url::parse_with_params(
+            &self.url_for(REGISTRY_RESULTS_PATH),
+            [
+                ("filter", format!("freeText in (\"{image_pull_string}\")")),
+                ("limit", REGISTRY_RESULTS_LIMIT.to_string()),
+            ],
+        )
+        .map_err(|e| ImageScanError::InternalScannerError(Box::new(e)))
+    }
+
+    async fn get(&self, request: reqwest::RequestBuilder) -> Result<Vec<u8>, ImageScanError> {
+        let response = request
+            .header(AUTHORIZATION, format!("Bearer {}", self.api_token.0))
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| ImageScanError::NetworkError(e.to_string()))?;
+        if let Some(error) = error_for_status(response.status(), &self.api_url) {
+            return Err(error);
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| ImageScanError::NetworkError(e.to_string()))?;
+        Ok(body.to_vec())
+    }
+
+    async fn latest_registry_result(
+        &self,
+        image_pull_string: &str,
+    ) -> Result<RegistryResult, ImageScanError> {
+        let request = self
+            .client
+            .get(self.registry_results_url(image_pull_string)?);
+        let results: RegistryResults = serde_json::from_slice(&self.get(request).await?)
+            .map_err(|e| ImageScanError::MalformedOutput(e.to_string()))?;
+
+        latest_result_of(results.data, image_pull_string).ok_or_else(|| {
+            ImageScanError::ImageNotFound(format!(
+                "Sysdig Secure has no registry scan of {image_pull_string}"
+            ))
+        })
+    }
+
+    async fn fetch_scan_result(
+        &self,
+        image_pull_string: &str,
+    ) -> Result<ScanResult, ImageScanError> {
+        let registry_result = self.latest_registry_result(image_pull_string).await?;
+        let url = format!(
+            "{}/{}",
+            self.url_for(RESULTS_PATH),
+            registry_result.result_id
+        );
+        let result = self.get(self.client.get(url)).await?;
+        let result: serde_json::Value = serde_json::from_slice(&result)
+            .map_err(|e| ImageScanError::MalformedOutput(e.to_string()))?;
+
+        scan_result_from(result, registry_result.created_at)
+    }
+}
+
+/// The newest result of `image_pull_string`, among the ones the free text filter found.
+fn latest_result_of(
+    results: Vec<RegistryResult>,
+    image_pull_string: &str,
+) -> Option<RegistryResult> {
+    let pull_string = normalize_image_reference(image_pull_string);
+    results
+        .into_iter()
+        .filter(|result| normalize_image_reference(&result.pull_string) == pull_string)
+        .max_by_key(|result| result.created_at)
+}
+
+/// Reads the result as the CLI scanner reports it, of which it is the `result`.
+fn scan_result_from(
+    result: serde_json::Value,
+    scanned_at: Option<DateTime<Utc>>,
+) -> Result<ScanResult, ImageScanError> {
+    let report = json!({ "info": { "scanTime": scanned_at }, "result": result });
+    JsonScanResult::from_value(report)
+        .map(ScanResult::from)
+        .map_err(|e| ImageScanError::MalformedOutput(e.to_string()))
+}
+
+fn error_for_status(status: StatusCode, api_url: &str) -> Option<ImageScanError> {
+    let reason = format!("{api_url} answered {status}");
+    match status {
+        status if status.is_success() => None,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            Some(ImageScanError::AuthFailed(reason))
+        }
+        StatusCode::NOT_FOUND => Some(ImageScanError::ImageNotFound(reason)),
+        StatusCode::TOO_MANY_REQUESTS => Some(ImageScanError::RateLimited(reason)),
+        status if status.is_server_error() => Some(ImageScanError::NetworkError(reason)),
+        _ => Some(ImageScanError::InternalScannerError(reason.into())),
+    }
+}
+
+#[async_trait::async_trait]
+impl ImageScanner for SysdigApiImageScanner {
+    async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
+        self.retry_policy
+            .retry(
+                &format!("scan of {image_pull_string}"),
+                ImageScanError::is_transient,
+                || self.fetch_scan_result(image_pull_string),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn it_looks_for_the_registry_results_of_the_image() {
+        let scanner = SysdigApiImageScanner::new(
+            "https://secure.sysdig.com/".to_string(),
+            SysdigAPIToken("token".to_string()),
+        );
+
+        let url = scanner.registry_results_url("alpine:3.19").unwrap();
+
+        assert_eq!(url.path(), "/secure/vulnerability/v1/registry-results");
+        assert_eq!(
+            url.query_pairs().collect::<Vec<_>>(),
+            vec![
+                ("filter".into(), "freeText in (\"alpine:3.19\")".into()),
+                ("limit".into(), "100".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_picks_the_newest_result_of_the_same_image() {
+        let result = |id: &str, pull_string: &str, created_at: &str| RegistryResult {
+            result_id: id.to_string(),
+            pull_string: pull_string.to_string(),
+            created_at: created_at.parse().ok(),
+        };
+        let results = vec![
+            result(
+                "old",
+                "docker.io/library/alpine:3.19",
+                "2025-01-01T00:00:00Z",
+            ),
+            result(
+                "new",
+                "docker.io/library/alpine:3.19",
+                "2025-02-01T00:00:00Z",
+            ),
+            result(
+                "other-tag",
+                "docker.io/library/alpine:3.19.1",
+                "2025-03-01T00:00:00Z",
+            ),
+            result(
+                "other-repo",
+                "quay.io/acme/alpine:3.19",
+                "2025-03-01T00:00:00Z",
+            ),
+        ];
+
+        let latest = latest_result_of(results, "alpine:3.19").unwrap();
+
+        assert_eq!(latest.result_id, "new");
+        assert!(latest_result_of(vec![], "alpine:3.19").is_none());
+    }
+
+    #[test]
+    fn it_reads_results_as_the_cli_reports_them() {
+        let report: serde_json::Value = serde_json::from_slice(include_bytes!(
+            "../../tests/fixtures/scan-results/postgres_13.json"
+        ))
+        .unwrap();
+        let scanned_at = "2025-09-30T16:05:33Z".parse().ok();
+
+        let scan_result = scan_result_from(report["result"].clone(), scanned_at).unwrap();
+
+        assert_eq!(scan_result.metadata().pull_string(), "postgres:13");
+        assert!(!scan_result.vulnerabilities().is_empty());
+        assert_eq!(scan_result.metadata().scanned_at(), scanned_at);
+    }
+
+    #[rstest]
+    #[case(StatusCode::OK, "")]
+    #[case(StatusCode::UNAUTHORIZED, "AuthFailed")]
+    #[case(StatusCode::FORBIDDEN, "AuthFailed")]
+    #[case(StatusCode::NOT_FOUND, "ImageNotFound")]
+    #[case(StatusCode::TOO_MANY_REQUESTS, "RateLimited")]
+    #[case(StatusCode::BAD_GATEWAY, "NetworkError")]
+    #[case(StatusCode::BAD_REQUEST, "InternalScannerError")]
+    fn it_classifies_the_failed_responses(#[case] status: StatusCode, #[case] expected: &str) {
+        let variant = match error_for_status(status, "https://secure.sysdig.com") {
+            None => "",
+            Some(ImageScanError::AuthFailed(_)) => "AuthFailed",
+            Some(ImageScanError::ImageNotFound(_)) => "ImageNotFound",
+            Some(ImageScanError::RateLimited(_)) => "RateLimited",
+            Some(ImageScanError::NetworkError(_)) => "NetworkError",
+            Some(ImageScanError::InternalScannerError(_)) => "InternalScannerError",
+            Some(other) => panic!("unexpected error {other}"),
+        };
+
+        assert_eq!(variant, expected);
+    }
+}
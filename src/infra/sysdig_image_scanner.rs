@@ -1,22 +1,33 @@
 #![allow(dead_code)]
 
-use std::{fmt::Display, sync::Arc};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+};
 
 use serde::Deserialize;
 use thiserror::Error;
-use tokio::{process::Command, sync::Mutex};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader},
+    process::Command,
+    sync::Mutex,
+};
 
 use crate::{
-    app::{ImageScanError, ImageScanner},
-    domain::scanresult::scan_result::ScanResult,
+    app::{FilesystemScanner, ImageScanError, ImageScanner, ScanScheduler, protocol::ScanPhase},
+    domain::{image_reference::image_registry, scanresult::scan_result::ScanResult},
 };
 
 use super::{
+    registry_credentials::{RegistryCredentialStore, RegistryCredentials},
+    retry_policy::RetryPolicy,
     scanner_binary_manager::{
         SCANNER_EXIT_CODE_INTERNAL_ERROR, SCANNER_EXIT_CODE_INVALID_PARAMS, ScannerBinaryManager,
         ScannerBinaryManagerError,
     },
-    sysdig_image_scanner_json_scan_result_v1::JsonScanResultV1,
+    sysdig_image_scanner_json_scan_result::{JsonScanResult, JsonScanResultError},
 };
 
 #[derive(Clone)]
@@ -25,6 +36,22 @@ pub struct SysdigImageScanner {
     api_token: SysdigAPIToken,
     scanner_binary_manager: Arc<Mutex<ScannerBinaryManager>>,
     docker_host: Option<String>,
+    docker_tls_cert_path: Option<PathBuf>,
+    offline: Option<OfflineScan>,
+    retry_policy: RetryPolicy,
+    /// Told the phases of the scans, read from the console log of the scanner.
+    progress: Option<ScanScheduler>,
+    /// Credentials of the registries images are pulled from by the scanner.
+    registry_credentials: Arc<RegistryCredentialStore>,
+    /// Platform of the multi-platform images scanned when the scan doesn't ask for one,
+    /// e.g. `linux/arm64`. The scanner picks the one of the host if unset.
+    platform: Option<String>,
+}
+
+/// Scans without reaching the backend, see [`SysdigImageScanner::offline`].
+#[derive(Clone, Debug, Default)]
+struct OfflineScan {
+    db_path: Option<PathBuf>,
 }
 
 #[derive(Clone, Deserialize)]
@@ -51,7 +78,7 @@ pub(in crate::infra) enum SysdigImageScannerError {
     CommandExecution(#[from] std::io::Error),
 
     #[error("error deserializing the report: {0}")]
-    ReportDeserialization(#[from] serde_json::Error),
+    ReportDeserialization(#[from] JsonScanResultError),
 
     #[error("invalid parameters provided to the image scanner, check the URL and API Token: {0:?}")]
     InvalidParametersProvided(String),
@@ -60,9 +87,77 @@ pub(in crate::infra) enum SysdigImageScannerError {
     InternalScannerExecutionError(String),
 }
 
+impl SysdigImageScannerError {
+    /// Whether running the scanner again may succeed, e.g. after a network error.
+    fn is_transient(&self) -> bool {
+        match self {
+            SysdigImageScannerError::InvalidParametersProvided(stderr)
+            | SysdigImageScannerError::InternalScannerExecutionError(stderr) => {
+                classify_scanner_failure(stderr).is_some_and(|e| e.is_transient())
+            }
+            _ => false,
+        }
+    }
+}
+
 impl From<SysdigImageScannerError> for ImageScanError {
     fn from(value: SysdigImageScannerError) -> Self {
-        ImageScanError::InternalScannerError(Box::new(value))
+        match value {
+            SysdigImageScannerError::InvalidParametersProvided(ref stderr)
+            | SysdigImageScannerError::InternalScannerExecutionError(ref stderr) => {
+                classify_scanner_failure(stderr)
+                    .unwrap_or_else(|| ImageScanError::InternalScannerError(Box::new(value)))
+            }
+            SysdigImageScannerError::ReportDeserialization(e) => {
+                ImageScanError::MalformedOutput(e.to_string())
+            }
+            SysdigImageScannerError::ScannerBinaryManager(
+                ScannerBinaryManagerError::HTTPError(e),
+            ) => ImageScanError::NetworkError(format!("unable to download the scanner: {e}")),
+            _ => ImageScanError::InternalScannerError(Box::new(value)),
+        }
+    }
+}
+
+/// Substrings of the scanner (and registry) errors, lowercased, that tell why a
+/// scan failed. Checked in order, as registries answer `pull access denied` or
+/// `unauthorized` for images that don't exist.
+const IMAGE_NOT_FOUND_ERRORS: [&str; 5] = [
+    "manifest unknown",
+    "pull access denied",
+    "repository does not exist",
+    "no such image",
+    "not found",
+];
+const RATE_LIMITED_ERRORS: [&str; 4] =
+    ["429", "too many requests", "toomanyrequests", "rate limit"];
+const AUTH_FAILED_ERRORS: [&str; 5] = ["401", "403", "unauthorized", "forbidden", "invalid token"];
+const NETWORK_ERRORS: [&str; 7] = [
+    "connection refused",
+    "connection reset",
+    "no such host",
+    "network is unreachable",
+    "timeout",
+    "timed out",
+    "tls handshake",
+];
+
+/// Typed error of a failed scanner execution from its stderr, if it's recognized.
+fn classify_scanner_failure(stderr: &str) -> Option<ImageScanError> {
+    let lowercase = stderr.to_lowercase();
+    let matches = |errors: &[&str]| errors.iter().any(|error| lowercase.contains(error));
+    let reason = stderr.trim().to_string();
+
+    if matches(&IMAGE_NOT_FOUND_ERRORS) {
+        Some(ImageScanError::ImageNotFound(reason))
+    } else if matches(&RATE_LIMITED_ERRORS) {
+        Some(ImageScanError::RateLimited(reason))
+    } else if matches(&AUTH_FAILED_ERRORS) {
+        Some(ImageScanError::AuthFailed(reason))
+    } else if matches(&NETWORK_ERRORS) {
+        Some(ImageScanError::NetworkError(reason))
+    } else {
+        None
     }
 }
 
@@ -73,16 +168,24 @@ impl SysdigImageScanner {
             api_token,
             scanner_binary_manager: Default::default(),
             docker_host: None,
+            docker_tls_cert_path: None,
+            offline: None,
+            retry_policy: RetryPolicy::default(),
+            progress: None,
+            registry_credentials: Default::default(),
+            platform: None,
         }
     }
 
     /// Creates a new scanner with a specific Docker host.
-    /// The docker_host should be in DOCKER_HOST format (e.g., "unix:///var/run/docker.sock").
+    /// The docker_host should be in DOCKER_HOST format (e.g., "unix:///var/run/docker.sock"),
+    /// and docker_tls_cert_path is forwarded as DOCKER_CERT_PATH for TLS-protected daemons.
     /// The scanner binary manager is shared so every scanner reuses the same CLI binary installation.
     pub(super) fn with_docker_host(
         url: String,
         api_token: SysdigAPIToken,
         docker_host: String,
+        docker_tls_cert_path: Option<PathBuf>,
         scanner_binary_manager: Arc<Mutex<ScannerBinaryManager>>,
     ) -> Self {
         Self {
@@ -90,31 +193,181 @@ impl SysdigImageScanner {
             api_token,
             scanner_binary_manager,
             docker_host: Some(docker_host),
+            docker_tls_cert_path,
+            offline: None,
+            retry_policy: RetryPolicy::default(),
+            progress: None,
+            registry_credentials: Default::default(),
+            platform: None,
+        }
+    }
+
+    /// Scans with a local vulnerability database (`db_path`, or the one cached by the
+    /// CLI) instead of the backend, for air-gapped environments. Policies can't be
+    /// evaluated, so the results are marked as not evaluated.
+    pub(super) fn offline(self, db_path: Option<PathBuf>) -> Self {
+        Self {
+            offline: Some(OfflineScan { db_path }),
+            ..self
+        }
+    }
+
+    /// Retries scans failing transiently, e.g. rate limited or unable to reach the backend.
+    pub(super) fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// Reports the phases of the scans (pulling, analyzing...) to `scheduler` while they run.
+    pub(super) fn with_progress(self, scheduler: ScanScheduler) -> Self {
+        Self {
+            progress: Some(scheduler),
+            ..self
+        }
+    }
+
+    /// Pulls private images with the credentials of their registry.
+    pub(super) fn with_registry_credentials(
+        self,
+        registry_credentials: Arc<RegistryCredentialStore>,
+    ) -> Self {
+        Self {
+            registry_credentials,
+            ..self
         }
     }
 
+    /// Scans the images of `platform` (e.g. `linux/arm64`) unless the scan asks for another.
+    pub(super) fn with_platform(self, platform: Option<String>) -> Self {
+        Self { platform, ..self }
+    }
+
     async fn scan(
         &self,
         image_pull_string: &str,
-    ) -> Result<JsonScanResultV1, SysdigImageScannerError> {
-        let path_to_cli = self
-            .scanner_binary_manager
-            .lock()
-            .await
-            .install_expected_version_if_not_present()
+        platform: Option<&str>,
+    ) -> Result<JsonScanResult, SysdigImageScannerError> {
+        let output = self.run_scanner(image_pull_string, platform).await?;
+        deserialize_with_debug(&output)
+    }
+
+    /// Scans the image and returns the report as emitted by the CLI, checking it can be
+    /// deserialized. Used to generate test fixtures.
+    pub(in crate::infra) async fn scan_raw_report(
+        &self,
+        image_pull_string: &str,
+    ) -> Result<serde_json::Value, ImageScanError> {
+        let output = self
+            .run_scanner(image_pull_string, self.platform.as_deref())
+            .await?;
+        deserialize_with_debug(&output)?;
+        Ok(serde_json::from_slice(&output)
+            .map_err(|e| SysdigImageScannerError::ReportDeserialization(e.into()))?)
+    }
+
+    /// Scans the image, returning the result together with the report it was read
+    /// from, e.g. to share it with other developers.
+    pub(in crate::infra) async fn scan_with_report(
+        &self,
+        image_pull_string: &str,
+    ) -> Result<(ScanResult, serde_json::Value), ImageScanError> {
+        let output = self
+            .run_scanner(image_pull_string, self.platform.as_deref())
             .await?;
+        let scan = deserialize_with_debug(&output)?;
+        let report = serde_json::from_slice(&output)
+            .map_err(|e| SysdigImageScannerError::ReportDeserialization(e.into()))?;
+        Ok((self.scan_result_from(scan), report))
+    }
+
+    fn scan_result_from(&self, scan: JsonScanResult) -> ScanResult {
+        let mut scan_result = ScanResult::from(scan);
+        if self.offline.is_some() {
+            scan_result.mark_policies_not_evaluated();
+        }
+        scan_result
+    }
 
-        let args = [
-            image_pull_string,
-            "--no-cache", // needed for concurrent scanning execution
-            "--output=json",
-            "--output-schema=v1",
-            "--separate-by-layer",
-            "--console-log",
-            "--skipupload",
-            "--apiurl",
-            self.url.as_str(),
+    fn scanner_args(&self, image_pull_string: &str, platform: Option<&str>) -> Vec<String> {
+        let mut args = vec![
+            image_pull_string.to_string(),
+            "--no-cache".to_string(), // needed for concurrent scanning execution
+            "--output=json".to_string(),
+            "--output-schema=v1".to_string(),
+            "--separate-by-layer".to_string(),
+            "--console-log".to_string(),
+            "--skipupload".to_string(),
+            "--apiurl".to_string(),
+            self.url.clone(),
         ];
+        if let Some(offline) = &self.offline {
+            args.push("--offline-analyser".to_string());
+            if let Some(db_path) = &offline.db_path {
+                args.push(format!("--dbpath={}", db_path.display()));
+            }
+        }
+        if let Some(platform) = platform {
+            args.push(format!("--platform={platform}"));
+        }
+        args
+    }
+
+    /// Runs the CLI scanner over the image, returning its JSON report.
+    async fn run_scanner(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<Vec<u8>, SysdigImageScannerError> {
+        let path_to_cli = {
+            let mut scanner_binary_manager = self.scanner_binary_manager.lock().await;
+            let path_to_cli = scanner_binary_manager
+                .install_expected_version_if_not_present()
+                .await?;
+            if let (Some(progress), Some(version)) =
+                (&self.progress, scanner_binary_manager.installed_version())
+            {
+                progress
+                    .statistics()
+                    .record_scanner_version(version.to_string());
+            }
+            path_to_cli
+        };
+        // Directories aren't pulled from any registry.
+        let registry_credentials = match image_pull_string.starts_with(DIRECTORY_SOURCE_PREFIX) {
+            true => None,
+            false => {
+                self.registry_credentials
+                    .credentials_for(&image_registry(image_pull_string))
+                    .await
+            }
+        };
+
+        self.retry_policy
+            .retry(
+                &format!("scan of {image_pull_string}"),
+                SysdigImageScannerError::is_transient,
+                || {
+                    self.execute_scanner(
+                        &path_to_cli,
+                        image_pull_string,
+                        platform,
+                        registry_credentials.as_ref(),
+                    )
+                },
+            )
+            .await
+    }
+
+    async fn execute_scanner(
+        &self,
+        path_to_cli: &Path,
+        image_pull_string: &str,
+        platform: Option<&str>,
+        registry_credentials: Option<&RegistryCredentials>,
+    ) -> Result<Vec<u8>, SysdigImageScannerError> {
+        let args = self.scanner_args(image_pull_string, platform);
 
         // Build environment variables dynamically
         let mut env_vars: Vec<(&str, &str)> = vec![("SECURE_API_TOKEN", self.api_token.0.as_str())];
@@ -123,52 +376,147 @@ impl SysdigImageScanner {
         if let Some(ref docker_host) = self.docker_host {
             env_vars.push(("DOCKER_HOST", docker_host.as_str()));
         }
+        if let Some(cert_path) = self.docker_tls_cert_path.as_ref().and_then(|p| p.to_str()) {
+            env_vars.push(("DOCKER_TLS_VERIFY", "1"));
+            env_vars.push(("DOCKER_CERT_PATH", cert_path));
+        }
+        if let Some(credentials) = registry_credentials {
+            env_vars.push(("REGISTRY_USER", credentials.username.as_str()));
+            env_vars.push(("REGISTRY_PASSWORD", credentials.password.as_str()));
+        }
 
-        let output = Command::new(path_to_cli)
+        let mut child = Command::new(path_to_cli)
             .args(args)
             .envs(env_vars)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             // Don't leave the scanner running if the LSP request is cancelled.
             .kill_on_drop(true)
-            .output()
-            .await?;
+            .spawn()?;
+        let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+            return Err(std::io::Error::other("the scanner output isn't piped").into());
+        };
+
+        // The console log is read as it's written, so the phases are reported while the
+        // scan runs instead of once it exits.
+        let mut phase = None;
+        let (stdout, stderr) = read_scanner_output(stdout, stderr, |line| {
+            let Some(progress) = &self.progress else {
+                return;
+            };
+            if let Some(next) = scan_phase_of(line).filter(|next| phase != Some(*next)) {
+                phase = Some(next);
+                progress.report_progress(image_pull_string, next, line.trim());
+            }
+        })
+        .await?;
+        let status = child.wait().await?;
 
-        match output.status.code() {
+        match status.code() {
             Some(SCANNER_EXIT_CODE_INVALID_PARAMS) => {
-                return Err(SysdigImageScannerError::InvalidParametersProvided(
-                    String::from_utf8_lossy(&output.stderr).to_string(),
-                ));
+                return Err(SysdigImageScannerError::InvalidParametersProvided(stderr));
             }
             Some(SCANNER_EXIT_CODE_INTERNAL_ERROR) => {
                 return Err(SysdigImageScannerError::InternalScannerExecutionError(
-                    String::from_utf8_lossy(&output.stderr).to_string(),
+                    stderr,
                 ));
             }
             None => {
                 return Err(SysdigImageScannerError::InternalScannerExecutionError(
-                    format!(
-                        "scanner terminated by a signal: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    ),
+                    format!("scanner terminated by a signal: {stderr}"),
                 ));
             }
             _ => {}
         };
 
-        deserialize_with_debug(&output.stdout)
+        Ok(stdout)
     }
 }
 
 #[async_trait::async_trait]
 impl ImageScanner for SysdigImageScanner {
     async fn scan_image(&self, image_pull_string: &str) -> Result<ScanResult, ImageScanError> {
-        let scan = self.scan(image_pull_string).await?;
-        Ok(ScanResult::from(scan))
+        self.scan_image_for_platform(image_pull_string, None).await
+    }
+
+    async fn scan_image_for_platform(
+        &self,
+        image_pull_string: &str,
+        platform: Option<&str>,
+    ) -> Result<ScanResult, ImageScanError> {
+        let platform = platform.or(self.platform.as_deref());
+        let scan = self.scan(image_pull_string, platform).await?;
+        Ok(self.scan_result_from(scan))
+    }
+}
+
+#[async_trait::async_trait]
+impl FilesystemScanner for SysdigImageScanner {
+    async fn scan_directory(&self, directory: &Path) -> Result<ScanResult, ImageScanError> {
+        let scan = self.scan(&directory_source(directory), None).await?;
+        Ok(self.scan_result_from(scan))
     }
 }
 
-fn deserialize_with_debug(json_bytes: &[u8]) -> Result<JsonScanResultV1, SysdigImageScannerError> {
-    let output_json = String::from_utf8_lossy(json_bytes);
-    serde_json::from_str(&output_json).map_err(|e| {
+/// Reads the whole report of the scanner from `stdout` while handing each line of its
+/// console log (`stderr`) to `on_log_line`, returning both.
+async fn read_scanner_output(
+    mut stdout: impl AsyncRead + Unpin,
+    stderr: impl AsyncRead + Unpin,
+    mut on_log_line: impl FnMut(&str),
+) -> std::io::Result<(Vec<u8>, String)> {
+    let read_report = async {
+        let mut report = Vec::new();
+        stdout.read_to_end(&mut report).await?;
+        Ok::<_, std::io::Error>(report)
+    };
+    let read_log = async {
+        let mut lines = BufReader::new(stderr).lines();
+        let mut log = String::new();
+        while let Some(line) = lines.next_line().await? {
+            on_log_line(&line);
+            log.push_str(&line);
+            log.push('\n');
+        }
+        Ok(log)
+    };
+
+    tokio::try_join!(read_report, read_log)
+}
+
+/// Lowercased substrings of the console log lines of the scanner telling the phase of
+/// the scan, checked in order.
+const SCAN_PHASE_LOGS: [(&str, ScanPhase); 8] = [
+    ("maindb", ScanPhase::UpdatingDatabase),
+    ("database", ScanPhase::UpdatingDatabase),
+    ("retrieving image", ScanPhase::Pulling),
+    ("pulling", ScanPhase::Pulling),
+    ("scan started", ScanPhase::Analyzing),
+    ("analyz", ScanPhase::Analyzing),
+    ("polic", ScanPhase::EvaluatingPolicies),
+    ("upload", ScanPhase::Uploading),
+];
+
+/// Phase of the scan a console log line of the scanner starts, if it tells one.
+fn scan_phase_of(line: &str) -> Option<ScanPhase> {
+    let lowercase = line.to_lowercase();
+    SCAN_PHASE_LOGS
+        .iter()
+        .find(|(log, _)| lowercase.contains(log))
+        .map(|(_, phase)| *phase)
+}
+
+/// Prefix of the sources of directory scans, see [`directory_source`].
+const DIRECTORY_SOURCE_PREFIX: &str = "dir:";
+
+/// Source the CLI scanner reads the packages of a directory from, instead of an image.
+fn directory_source(directory: &Path) -> String {
+    format!("{DIRECTORY_SOURCE_PREFIX}{}", directory.display())
+}
+
+fn deserialize_with_debug(json_bytes: &[u8]) -> Result<JsonScanResult, SysdigImageScannerError> {
+    JsonScanResult::from_slice(json_bytes).map_err(|e| {
+        let output_json = String::from_utf8_lossy(json_bytes);
         tracing::error!(
             "Failed to deserialize scanner output. Raw JSON: {}",
             output_json
@@ -196,6 +544,150 @@ mod tests {
         ));
     }
 
+    #[rstest]
+    #[case(
+        "error: GET https://registry/v2/app/manifests/v9: MANIFEST_UNKNOWN: manifest unknown",
+        "ImageNotFound"
+    )]
+    #[case(
+        "pull access denied for acme/app, repository does not exist",
+        "ImageNotFound"
+    )]
+    #[case(
+        "toomanyrequests: You have reached your pull rate limit",
+        "RateLimited"
+    )]
+    #[case("unexpected status code 401 Unauthorized", "AuthFailed")]
+    #[case("dial tcp: lookup secure.sysdig.com: no such host", "NetworkError")]
+    #[case("panic: runtime error: index out of range", "")]
+    fn it_classifies_scanner_failures_from_their_stderr(
+        #[case] stderr: &str,
+        #[case] expected: &str,
+    ) {
+        let variant = match classify_scanner_failure(stderr) {
+            Some(ImageScanError::ImageNotFound(reason)) => {
+                assert_eq!(reason, stderr);
+                "ImageNotFound"
+            }
+            Some(ImageScanError::RateLimited(_)) => "RateLimited",
+            Some(ImageScanError::AuthFailed(_)) => "AuthFailed",
+            Some(ImageScanError::NetworkError(_)) => "NetworkError",
+            Some(other) => panic!("unexpected error {other}"),
+            None => "",
+        };
+
+        assert_eq!(variant, expected);
+    }
+
+    #[rstest]
+    #[case(
+        "2025-09-30T18:05:23+02:00 INFO Retrieving MainDB...",
+        Some(ScanPhase::UpdatingDatabase)
+    )]
+    #[case(
+        "2025-09-30T18:05:25+02:00 INFO Retrieving image...",
+        Some(ScanPhase::Pulling)
+    )]
+    #[case("Pulling fs layer 3f4ca61aafcd", Some(ScanPhase::Pulling))]
+    #[case(
+        "2025-09-30T18:05:31+02:00 INFO Scan started...",
+        Some(ScanPhase::Analyzing)
+    )]
+    #[case("Evaluating policies", Some(ScanPhase::EvaluatingPolicies))]
+    #[case(
+        "2025-09-30T18:05:33+02:00 INFO Uploading result to backend...",
+        Some(ScanPhase::Uploading)
+    )]
+    #[case("2025-09-30T18:05:33+02:00 INFO Done", None)]
+    fn it_reads_the_scan_phase_from_the_console_log(
+        #[case] line: &str,
+        #[case] expected: Option<ScanPhase>,
+    ) {
+        assert_eq!(scan_phase_of(line), expected);
+    }
+
+    #[tokio::test]
+    async fn it_reads_the_console_log_line_by_line_along_with_the_report() {
+        let mut lines = Vec::new();
+
+        let (report, log) = read_scanner_output(
+            &b"{\"info\": {}}"[..],
+            &b"Retrieving image...\nScan started...\nDone"[..],
+            |line| lines.push(line.to_string()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report, b"{\"info\": {}}");
+        assert_eq!(lines, ["Retrieving image...", "Scan started...", "Done"]);
+        assert_eq!(log, "Retrieving image...\nScan started...\nDone\n");
+    }
+
+    #[test]
+    fn it_scans_directories_as_a_dir_source() {
+        let scanner = SysdigImageScanner::new(
+            "https://secure.sysdig.com".to_string(),
+            SysdigAPIToken("token".to_string()),
+        );
+
+        let args = scanner.scanner_args(&directory_source(Path::new("/workspace/app")), None);
+
+        assert_eq!(args[0], "dir:/workspace/app");
+    }
+
+    #[test]
+    fn it_keeps_unrecognized_failures_as_internal_errors() {
+        let error = ImageScanError::from(SysdigImageScannerError::InvalidParametersProvided(
+            "unknown flag: --foo".to_string(),
+        ));
+        assert!(matches!(error, ImageScanError::InternalScannerError(_)));
+
+        let error = ImageScanError::from(SysdigImageScannerError::InvalidParametersProvided(
+            "invalid token".to_string(),
+        ));
+        assert!(matches!(error, ImageScanError::AuthFailed(_)));
+
+        let error = ImageScanError::from(deserialize_with_debug(b"{}").unwrap_err());
+        assert!(matches!(error, ImageScanError::MalformedOutput(_)));
+    }
+
+    #[test]
+    fn it_scans_with_the_local_database_when_offline() {
+        let token = SysdigAPIToken("token".to_string());
+        let online = SysdigImageScanner::new("https://secure".to_string(), token.clone());
+        let offline = SysdigImageScanner::new("https://secure".to_string(), token)
+            .offline(Some(PathBuf::from("/opt/sysdig/db")));
+
+        assert!(
+            !online
+                .scanner_args("alpine", None)
+                .contains(&"--offline-analyser".to_string())
+        );
+        let args = offline.scanner_args("alpine", None);
+        assert!(args.contains(&"--offline-analyser".to_string()));
+        assert!(args.contains(&"--dbpath=/opt/sysdig/db".to_string()));
+    }
+
+    #[test]
+    fn it_scans_the_requested_platform() {
+        let scanner = SysdigImageScanner::new(
+            "https://secure".to_string(),
+            SysdigAPIToken("token".to_string()),
+        );
+
+        assert!(
+            !scanner
+                .scanner_args("alpine", None)
+                .iter()
+                .any(|arg| arg.starts_with("--platform"))
+        );
+        assert!(
+            scanner
+                .scanner_args("alpine", Some("linux/arm64"))
+                .contains(&"--platform=linux/arm64".to_string())
+        );
+    }
+
     #[fixture]
     fn scanner() -> SysdigImageScanner {
         let sysdig_secure_url: String =
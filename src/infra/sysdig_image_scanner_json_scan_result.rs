@@ -0,0 +1,146 @@
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::domain::scanresult::scan_result::ScanResult;
+
+use super::sysdig_image_scanner_json_scan_result_v1::JsonScanResultV1;
+
+/// Key reports carry their schema version in. v1 reports predate it and have none.
+const SCHEMA_VERSION_KEY: &str = "schemaVersion";
+
+/// Versions of the report schema of the CLI scanner (`--output-schema`) that can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SchemaVersion {
+    V1,
+}
+
+impl SchemaVersion {
+    fn of(report: &Value) -> Result<Self, JsonScanResultError> {
+        let version = match report.get(SCHEMA_VERSION_KEY) {
+            None | Some(Value::Null) => return Ok(SchemaVersion::V1),
+            Some(Value::String(version)) => version.trim_start_matches('v').to_string(),
+            Some(version) => version.to_string(),
+        };
+
+        match version.as_str() {
+            "1" => Ok(SchemaVersion::V1),
+            _ => Err(JsonScanResultError::UnsupportedSchemaVersion(version)),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub(super) enum JsonScanResultError {
+    #[error("unsupported report schema version {0}, only v1 is supported")]
+    UnsupportedSchemaVersion(String),
+
+    #[error("{0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+/// Report of the CLI scanner, in any of the supported schema versions. The single entry
+/// point to read reports, whether they come from the scanner or from a cache.
+#[derive(Debug, Clone)]
+pub(super) enum JsonScanResult {
+    V1(JsonScanResultV1),
+}
+
+impl JsonScanResult {
+    pub fn from_slice(report: &[u8]) -> Result<Self, JsonScanResultError> {
+        Self::from_value(serde_json::from_slice(report)?)
+    }
+
+    pub fn from_value(report: Value) -> Result<Self, JsonScanResultError> {
+        match SchemaVersion::of(&report)? {
+            SchemaVersion::V1 => Ok(JsonScanResult::V1(serde_json::from_value(report)?)),
+        }
+    }
+}
+
+impl From<JsonScanResult> for ScanResult {
+    fn from(report: JsonScanResult) -> Self {
+        match report {
+            JsonScanResult::V1(report) => ScanResult::from(report),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    #[rstest]
+    #[case::without_version(json!({}))]
+    #[case::null_version(json!({"schemaVersion": null}))]
+    #[case::prefixed_version(json!({"schemaVersion": "v1"}))]
+    #[case::plain_version(json!({"schemaVersion": "1"}))]
+    #[case::numeric_version(json!({"schemaVersion": 1}))]
+    fn it_detects_v1_reports(#[case] report: Value) {
+        assert_eq!(SchemaVersion::of(&report).unwrap(), SchemaVersion::V1);
+    }
+
+    #[rstest]
+    #[case(json!({"schemaVersion": "v2"}), "2")]
+    #[case(json!({"schemaVersion": 2}), "2")]
+    fn it_rejects_unknown_schema_versions(#[case] report: Value, #[case] expected: &str) {
+        let error = JsonScanResult::from_value(report).unwrap_err();
+
+        assert!(
+            matches!(&error, JsonScanResultError::UnsupportedSchemaVersion(version) if version == expected)
+        );
+    }
+
+    #[test]
+    fn it_reports_malformed_v1_reports() {
+        let error = JsonScanResult::from_slice(b"{\"foo\": \"bar\"}").unwrap_err();
+
+        assert!(matches!(error, JsonScanResultError::Malformed(_)));
+    }
+
+    /// Properties every report of the fixtures must keep once read, so a new fixture
+    /// exercising another shape of the report is covered by just adding it.
+    #[rstest]
+    fn fixtures_keep_the_relations_of_the_report(
+        #[files("tests/fixtures/scan-results/*.json")] fixture: PathBuf,
+    ) {
+        let contents = std::fs::read(&fixture).unwrap();
+        let raw: Value = serde_json::from_slice(&contents).unwrap();
+        let report = JsonScanResult::from_slice(&contents).unwrap();
+        assert!(matches!(report, JsonScanResult::V1(_)));
+
+        let scan_result = ScanResult::from(report);
+
+        let raw_packages = raw["result"]["packages"].as_object().map_or(0, |p| p.len());
+        assert_eq!(scan_result.packages().len(), raw_packages);
+        let layers = scan_result.layers();
+        for package in scan_result.packages() {
+            assert!(
+                layers
+                    .iter()
+                    .any(|layer| layer.index() == package.found_in_layer().index()),
+                "{} is in an unknown layer",
+                package.name()
+            );
+            for vulnerability in package.vulnerabilities() {
+                assert!(
+                    vulnerability
+                        .found_in_packages()
+                        .iter()
+                        .any(|p| p.name() == package.name() && p.version() == package.version()),
+                    "{} doesn't point back to {}",
+                    vulnerability.cve(),
+                    package.name()
+                );
+            }
+        }
+        let raw_vulnerabilities = raw["result"]["vulnerabilities"]
+            .as_object()
+            .map_or(0, |v| v.len());
+        assert!(scan_result.vulnerabilities().len() <= raw_vulnerabilities);
+    }
+}
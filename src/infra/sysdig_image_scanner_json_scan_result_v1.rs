@@ -1,12 +1,18 @@
 #![allow(dead_code)]
 
 use chrono::{DateTime, NaiveDate, Utc};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::ops::Deref;
+use std::time::Duration;
+use tracing::warn;
 
 use crate::domain::scanresult::{
     accepted_risk_reason::AcceptedRiskReason,
     architecture::Architecture,
+    cvss::Cvss,
+    exploit::{CisaKev, Exploit},
     operating_system::{Family, OperatingSystem},
     package_type::PackageType,
     scan_result::ScanResult,
@@ -17,12 +23,29 @@ use crate::domain::scanresult::{
 impl From<JsonScanResultV1> for ScanResult {
     fn from(report: JsonScanResultV1) -> Self {
         let mut scan_result = ScanResult::from(&report.result);
+        let mut warnings = report.result.invalid_entries();
 
         add_layers(&report.result, &mut scan_result);
-        add_risk_accepts(&report.result, &mut scan_result);
-        add_vulnerabilities(&report.result, &mut scan_result);
-        add_packages(&report.result, &mut scan_result);
+        add_risk_accepts(&report.result, &mut scan_result, &mut warnings);
+        add_vulnerabilities(&report.result, &mut scan_result, &mut warnings);
+        add_packages(&report.result, &mut scan_result, &mut warnings);
         add_policies(&report.result, &mut scan_result);
+        if let Some(result_url) = &report.info.result_url {
+            scan_result.set_result_url(result_url.clone());
+        }
+        if let Some(scan_time) = report.info.scan_time {
+            let scan_duration = report.info.scan_duration.as_deref().and_then(go_duration);
+            scan_result.set_scan_time(scan_time, scan_duration);
+        }
+
+        warnings.sort();
+        for warning in warnings {
+            warn!(
+                "ignoring part of the scan report of {}: {warning}",
+                report.result.metadata.pull_string
+            );
+            scan_result.add_parse_warning(warning);
+        }
 
         scan_result
     }
@@ -39,11 +62,14 @@ fn add_layers(report: &JsonResult, scan_result: &mut ScanResult) {
     });
 }
 
-fn add_risk_accepts(result: &JsonResult, scan_result: &mut ScanResult) {
-    for json_risk in result.risk_accepts.values() {
+fn add_risk_accepts(result: &JsonResult, scan_result: &mut ScanResult, warnings: &mut Vec<String>) {
+    for (key, json_risk) in result.risk_accepts.iter() {
         scan_result.add_accepted_risk(
             json_risk.id.clone(),
-            json_risk.reason.clone().into(),
+            json_risk
+                .reason
+                .value_or_default(&format!("riskAccepts.{key}.reason"), warnings)
+                .into(),
             json_risk.description.clone(),
             json_risk.expiration_date,
             json_risk.status.eq_ignore_ascii_case("active"),
@@ -53,15 +79,25 @@ fn add_risk_accepts(result: &JsonResult, scan_result: &mut ScanResult) {
     }
 }
 
-fn add_vulnerabilities(result: &JsonResult, scan_result: &mut ScanResult) {
-    for v in result.vulnerabilities.values() {
+fn add_vulnerabilities(
+    result: &JsonResult,
+    scan_result: &mut ScanResult,
+    warnings: &mut Vec<String>,
+) {
+    for (key, v) in result.vulnerabilities.iter() {
         let vuln = scan_result.add_vulnerability(
             v.name.clone(),
-            v.severity.clone().into(),
+            v.severity
+                .value_or_default(&format!("vulnerabilities.{key}.severity"), warnings)
+                .into(),
             v.disclosure_date,
             v.solution_date,
             v.exploitable,
             v.fix_version.clone(),
+            v.cvss_score
+                .as_ref()
+                .map(|cvss| Cvss::new(cvss.score, cvss.vector.clone(), cvss.version.clone())),
+            exploit_of(v),
         );
 
         v.risk_accept_refs
@@ -74,8 +110,41 @@ fn add_vulnerabilities(result: &JsonResult, scan_result: &mut ScanResult) {
     }
 }
 
-fn add_packages(result: &JsonResult, scan_result: &mut ScanResult) {
-    for json_pkg in result.packages.values() {
+/// Exploit details of the vulnerability, if the scanner reported any.
+fn exploit_of(v: &JsonVulnerability) -> Option<Exploit> {
+    if v.exploit.is_none() && v.cisa_kev.is_none() {
+        return None;
+    }
+    let (publication_date, links) = v
+        .exploit
+        .as_ref()
+        .map(|exploit| {
+            (
+                parse_date(exploit.publication_date.as_deref()),
+                exploit.links.clone(),
+            )
+        })
+        .unwrap_or_default();
+    let cisa_kev = v.cisa_kev.as_ref().map(|kev| {
+        CisaKev::new(
+            parse_date(kev.publish_date.as_deref()),
+            parse_date(kev.due_date.as_deref()),
+        )
+    });
+    Some(Exploit::new(publication_date, links, cisa_kev))
+}
+
+/// Dates come either as plain dates or as timestamps, depending on the field.
+fn parse_date(date: Option<&str>) -> Option<NaiveDate> {
+    let date = date?;
+    DateTime::parse_from_rfc3339(date)
+        .map(|timestamp| timestamp.date_naive())
+        .or_else(|_| date.parse())
+        .ok()
+}
+
+fn add_packages(result: &JsonResult, scan_result: &mut ScanResult, warnings: &mut Vec<String>) {
+    for (key, json_pkg) in result.packages.iter() {
         let Some(json_layer) = result.layers.get(&json_pkg.layer_ref) else {
             continue;
         };
@@ -87,11 +156,17 @@ fn add_packages(result: &JsonResult, scan_result: &mut ScanResult) {
         };
 
         let pkg = scan_result.add_package(
-            json_pkg.package_type.clone().into(),
+            json_pkg
+                .package_type
+                .value_or_default(&format!("packages.{key}.type"), warnings)
+                .into(),
             json_pkg.name.clone(),
             json_pkg.version.clone(),
             json_pkg.path.clone(),
             layer_where_this_package_is_found,
+            json_pkg.is_running,
+            json_pkg.license.clone(),
+            json_pkg.suggested_fix.clone(),
         );
 
         json_pkg
@@ -218,25 +293,143 @@ fn arch_from_str(string: &str) -> Architecture {
     }
 }
 
+/// Reads a duration as Go prints them, e.g. `10.413926974s` or `1m30.5s`.
+fn go_duration(string: &str) -> Option<Duration> {
+    let mut rest = string;
+    let mut total = 0f64;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(number_len);
+        let unit_len = tail
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_len);
+        let seconds_per_unit = match unit {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 1e-3,
+            "us" | "µs" | "μs" => 1e-6,
+            "ns" => 1e-9,
+            _ => return None,
+        };
+        total += number.parse::<f64>().ok()? * seconds_per_unit;
+        rest = tail;
+    }
+    Duration::try_from_secs_f64(total).ok()
+}
+
+/// Entries of a collection of the report, by key. The ones that can't be read are left
+/// out, keeping why, instead of failing the whole report.
+#[derive(Debug, Clone)]
+pub(super) struct JsonEntries<T> {
+    entries: HashMap<String, T>,
+    invalid: Vec<(String, String)>,
+}
+
+impl<T> Default for JsonEntries<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            invalid: Vec::new(),
+        }
+    }
+}
+
+impl<T> Deref for JsonEntries<T> {
+    type Target = HashMap<String, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+impl<T> JsonEntries<T> {
+    fn invalid_entries<'a>(&'a self, collection: &'a str) -> impl Iterator<Item = String> + 'a {
+        self.invalid
+            .iter()
+            .map(move |(key, error)| format!("{collection}.{key}: {error}"))
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for JsonEntries<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let values = Option::<HashMap<String, Value>>::deserialize(deserializer)?;
+        let mut entries = JsonEntries::default();
+        for (key, value) in values.unwrap_or_default() {
+            match serde_json::from_value(value) {
+                Ok(entry) => {
+                    entries.entries.insert(key, entry);
+                }
+                Err(e) => entries.invalid.push((key, e.to_string())),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Value of an enum of the report, keeping the raw value when it isn't one of the known
+/// variants, e.g. a severity added by a newer scanner.
+#[derive(Debug, Clone)]
+pub(super) enum JsonEnum<T> {
+    Known(T),
+    Unknown(String),
+}
+
+impl<T: Default> Default for JsonEnum<T> {
+    fn default() -> Self {
+        JsonEnum::Known(T::default())
+    }
+}
+
+impl<T: Clone + Default> JsonEnum<T> {
+    /// The value, or the default variant noting the unknown value in `warnings`.
+    fn value_or_default(&self, field: &str, warnings: &mut Vec<String>) -> T {
+        match self {
+            JsonEnum::Known(value) => value.clone(),
+            JsonEnum::Unknown(raw) => {
+                warnings.push(format!("{field}: unknown value {raw}"));
+                T::default()
+            }
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for JsonEnum<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        Ok(match serde_json::from_value(value.clone()) {
+            Ok(known) => JsonEnum::Known(known),
+            Err(_) => JsonEnum::Unknown(value.to_string()),
+        })
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub(super) struct JsonScanResultV1 {
+    #[serde(default)]
     pub info: JsonInfo,
+    #[serde(default)]
     pub scanner: JsonScanner,
     pub result: JsonResult,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Default, Clone)]
 pub(super) struct JsonScanner {
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub version: String,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Default, Clone)]
 pub(super) struct JsonInfo {
-    #[serde(rename = "scanTime")]
-    pub scan_time: DateTime<Utc>,
-    #[serde(rename = "scanDuration")]
-    pub scan_duration: String,
+    #[serde(rename = "scanTime", default)]
+    pub scan_time: Option<DateTime<Utc>>,
+    #[serde(rename = "scanDuration", default)]
+    pub scan_duration: Option<String>,
     #[serde(rename = "resultUrl", default)]
     pub result_url: Option<String>,
     #[serde(rename = "resultId", default)]
@@ -275,7 +468,7 @@ pub enum ImageMetadataArchitecture {
     Wasm,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, Deserialize, Default, PartialEq, Eq, Hash, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum JsonSeverity {
     Critical,
@@ -283,6 +476,8 @@ pub enum JsonSeverity {
     Low,
     Medium,
     Negligible,
+    #[default]
+    Unknown,
 }
 
 impl From<JsonSeverity> for Severity {
@@ -293,6 +488,7 @@ impl From<JsonSeverity> for Severity {
             JsonSeverity::Low => Self::Low,
             JsonSeverity::Medium => Self::Medium,
             JsonSeverity::Negligible => Self::Negligible,
+            JsonSeverity::Unknown => Self::Unknown,
         }
     }
 }
@@ -312,11 +508,27 @@ pub(super) struct JsonBundle {
 #[derive(Debug, Deserialize, Clone)]
 pub(super) struct JsonCvssScore {
     pub score: f32,
-    #[serde(default)] // FIXME(fede): test this
+    #[serde(default)]
     pub vector: String,
     pub version: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub(super) struct JsonExploit {
+    #[serde(rename = "publicationDate", default)]
+    pub publication_date: Option<String>,
+    #[serde(rename = "links", default)]
+    pub links: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub(super) struct JsonCisaKev {
+    #[serde(rename = "publishDate", default)]
+    pub publish_date: Option<String>,
+    #[serde(rename = "dueDate", default)]
+    pub due_date: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub(super) struct JsonLayer {
     #[serde(rename = "command", default)]
@@ -337,15 +549,16 @@ pub(super) struct JsonPackage {
     pub is_running: bool,
     #[serde(rename = "layerRef")]
     pub layer_ref: String,
+    #[serde(rename = "license", default)]
+    pub license: Option<String>,
     #[serde(rename = "name")]
     pub name: String,
     #[serde(rename = "path", default)]
     pub path: String,
-    // FIXME(fede): Maybe we could use this to implement a suggestion to fix in the LSP?
-    // #[serde(rename = "suggestedFix", default)]
-    // pub suggested_fix: Option<String>,
+    #[serde(rename = "suggestedFix", default)]
+    pub suggested_fix: Option<String>,
     #[serde(rename = "type", default)]
-    pub package_type: JsonPackageType,
+    pub package_type: JsonEnum<JsonPackageType>,
     #[serde(rename = "version")]
     pub version: String,
     #[serde(rename = "vulnerabilitiesRefs", default)]
@@ -405,17 +618,17 @@ pub(super) struct JsonPolicies {
 pub(super) struct JsonPolicy {
     #[serde(rename = "bundles", default)]
     pub bundles: Option<Vec<JsonBundle>>,
-    #[serde(rename = "createdAt")]
+    #[serde(rename = "createdAt", default)]
     pub created_at: DateTime<Utc>,
     #[serde(rename = "description", default)]
     pub description: String,
-    #[serde(rename = "evaluation")]
+    #[serde(rename = "evaluation", default)]
     pub evaluation: String,
     #[serde(rename = "identifier")]
     pub identifier: String,
     #[serde(rename = "name")]
     pub name: String,
-    #[serde(rename = "updatedAt")]
+    #[serde(rename = "updatedAt", default)]
     pub updated_at: DateTime<Utc>,
 }
 
@@ -431,17 +644,17 @@ pub(super) struct JsonRiskAccept {
     pub created_at: DateTime<Utc>,
     #[serde(rename = "description", default)]
     pub description: String,
-    #[serde(rename = "entityType")]
+    #[serde(rename = "entityType", default)]
     pub entity_type: String,
-    #[serde(rename = "entityValue")]
+    #[serde(rename = "entityValue", default)]
     pub entity_value: String,
     #[serde(rename = "expirationDate", default)]
     pub expiration_date: Option<NaiveDate>,
     #[serde(rename = "id")]
     pub id: String,
     #[serde(rename = "reason", default)]
-    pub reason: JsonRiskAcceptReason,
-    #[serde(rename = "status")]
+    pub reason: JsonEnum<JsonRiskAcceptReason>,
+    #[serde(rename = "status", default)]
     pub status: String,
     #[serde(rename = "updatedAt")]
     pub updated_at: DateTime<Utc>,
@@ -474,17 +687,17 @@ impl From<JsonRiskAcceptReason> for AcceptedRiskReason {
 }
 #[derive(Debug, Deserialize, Clone)]
 pub(super) struct JsonRule {
-    #[serde(rename = "description")]
+    #[serde(rename = "description", default)]
     pub description: String,
     #[serde(rename = "evaluationResult", default)]
     pub evaluation_result: String,
-    #[serde(rename = "failureType")]
+    #[serde(rename = "failureType", default)]
     pub failure_type: String,
     #[serde(rename = "failures", default)]
     pub failures: Option<Vec<JsonFailure>>,
     #[serde(rename = "ruleId", default)]
     pub rule_id: String,
-    #[serde(rename = "ruleType")]
+    #[serde(rename = "ruleType", default)]
     pub rule_type: String,
 }
 
@@ -500,35 +713,47 @@ pub(super) struct JsonFailure {
 
 #[derive(Debug, Deserialize, Clone)]
 pub(super) struct JsonResult {
-    #[serde(rename = "assetType")]
+    #[serde(rename = "assetType", default)]
     pub asset_type: String,
     #[serde(rename = "layers", default)]
-    pub layers: HashMap<String, JsonLayer>,
+    pub layers: JsonEntries<JsonLayer>,
     #[serde(rename = "metadata")]
     pub metadata: JsonMetadata,
     #[serde(rename = "packages", default)]
-    pub packages: HashMap<String, JsonPackage>,
+    pub packages: JsonEntries<JsonPackage>,
     #[serde(rename = "policies", default)]
     pub policies: JsonPolicies,
     #[serde(rename = "producer", default)]
     pub producer: JsonProducer,
     #[serde(rename = "riskAccepts", default)]
-    pub risk_accepts: HashMap<String, JsonRiskAccept>,
-    #[serde(rename = "stage")]
+    pub risk_accepts: JsonEntries<JsonRiskAccept>,
+    #[serde(rename = "stage", default)]
     pub stage: String,
     #[serde(rename = "vulnerabilities", default)]
-    pub vulnerabilities: HashMap<String, JsonVulnerability>,
+    pub vulnerabilities: JsonEntries<JsonVulnerability>,
+}
+
+impl JsonResult {
+    /// Entries of the collections of the report left out because they couldn't be read.
+    fn invalid_entries(&self) -> Vec<String> {
+        self.layers
+            .invalid_entries("layers")
+            .chain(self.packages.invalid_entries("packages"))
+            .chain(self.risk_accepts.invalid_entries("riskAccepts"))
+            .chain(self.vulnerabilities.invalid_entries("vulnerabilities"))
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub(super) struct JsonMetadata {
-    #[serde(rename = "architecture")]
+    #[serde(rename = "architecture", default)]
     pub architecture: String,
-    #[serde(rename = "author")]
+    #[serde(rename = "author", default)]
     pub author: String,
-    #[serde(rename = "baseOs")]
+    #[serde(rename = "baseOs", default)]
     pub base_os: String,
-    #[serde(rename = "createdAt")]
+    #[serde(rename = "createdAt", default)]
     pub created_at: DateTime<Utc>,
     #[serde(rename = "digest", default)]
     pub digest: Option<String>,
@@ -536,22 +761,26 @@ pub(super) struct JsonMetadata {
     pub image_id: String,
     #[serde(rename = "labels", default)]
     pub labels: HashMap<String, String>,
-    #[serde(rename = "os")]
+    #[serde(rename = "os", default)]
     pub os: String,
     #[serde(rename = "pullString")]
     pub pull_string: String,
-    #[serde(rename = "size")]
+    #[serde(rename = "size", default)]
     pub size: u64,
 }
 
 #[derive(Debug, Deserialize, Clone)]
 pub(super) struct JsonVulnerability {
-    #[serde(rename = "cvssScore")]
-    pub cvss_score: JsonCvssScore,
+    #[serde(rename = "cvssScore", default)]
+    pub cvss_score: Option<JsonCvssScore>,
     #[serde(rename = "disclosureDate", default)]
     pub disclosure_date: NaiveDate,
-    #[serde(rename = "exploitable")]
+    #[serde(rename = "exploitable", default)]
     pub exploitable: bool,
+    #[serde(rename = "exploit", default)]
+    pub exploit: Option<JsonExploit>,
+    #[serde(rename = "cisaKev", default)]
+    pub cisa_kev: Option<JsonCisaKev>,
     #[serde(rename = "fixVersion", default)]
     pub fix_version: Option<String>,
     #[serde(rename = "mainProvider", default)]
@@ -562,8 +791,8 @@ pub(super) struct JsonVulnerability {
     pub package_ref: String,
     #[serde(rename = "riskAcceptRefs", default)]
     pub risk_accept_refs: Option<Vec<String>>,
-    #[serde(rename = "severity")]
-    pub severity: JsonSeverity,
+    #[serde(rename = "severity", default)]
+    pub severity: JsonEnum<JsonSeverity>,
     #[serde(rename = "solutionDate", default)]
     pub solution_date: Option<NaiveDate>,
 }
@@ -571,9 +800,16 @@ pub(super) struct JsonVulnerability {
 #[cfg(test)]
 mod tests {
     use crate::{
-        domain::scanresult::{scan_result::ScanResult, severity::Severity},
-        infra::sysdig_image_scanner_json_scan_result_v1::JsonScanResultV1,
+        domain::scanresult::{
+            package_type::PackageType, scan_result::ScanResult, severity::Severity,
+        },
+        infra::sysdig_image_scanner_json_scan_result_v1::{
+            JsonScanResultV1, JsonVulnerability, exploit_of, go_duration,
+        },
     };
+    use chrono::NaiveDate;
+    use rstest::rstest;
+    use std::time::Duration;
 
     #[test]
     fn it_loads_postgres13() {
@@ -626,6 +862,87 @@ mod tests {
         // assert_eq!(scan_result.vulnerabilities().len(), 97);
     }
 
+    #[test]
+    fn it_carries_the_cvss_of_the_vulnerabilities() {
+        let postgres_13_json = include_bytes!("../../tests/fixtures/scan-results/postgres_13.json");
+        let json_scan_result: JsonScanResultV1 = serde_json::from_slice(postgres_13_json).unwrap();
+        let scan_result: ScanResult = json_scan_result.into();
+
+        let vulnerability = scan_result
+            .vulnerabilities()
+            .into_iter()
+            .find(|v| v.cve() == "CVE-2025-6141")
+            .unwrap();
+        let cvss = vulnerability.cvss().unwrap();
+
+        assert_eq!(cvss.score(), 7.5);
+        assert_eq!(
+            cvss.vector(),
+            Some("CVSS:3.0/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:H")
+        );
+        assert_eq!(cvss.version(), "3.0");
+    }
+
+    #[test]
+    fn it_carries_the_license_of_the_packages() {
+        let postgres_13_json = include_bytes!("../../tests/fixtures/scan-results/postgres_13.json");
+        let json_scan_result: JsonScanResultV1 = serde_json::from_slice(postgres_13_json).unwrap();
+        let scan_result: ScanResult = json_scan_result.into();
+
+        let package = scan_result
+            .packages()
+            .into_iter()
+            .find(|p| p.name() == "sqv")
+            .unwrap();
+
+        assert_eq!(package.license(), Some("LGPL-2.0-or-later"));
+    }
+
+    #[test]
+    fn it_carries_the_exploits_and_cisa_kev_entries() {
+        let json = serde_json::json!({
+            "cvssScore": {"score": 9.8, "version": "3.1"},
+            "disclosureDate": "2021-12-10",
+            "exploitable": true,
+            "exploit": {
+                "publicationDate": "2021-12-11T00:00:00Z",
+                "links": ["https://www.exploit-db.com/exploits/50592"]
+            },
+            "cisaKev": {"publishDate": "2021-12-10", "dueDate": "2021-12-24"},
+            "name": "CVE-2021-44228",
+            "severity": "critical"
+        });
+        let vulnerability: JsonVulnerability = serde_json::from_value(json).unwrap();
+
+        let exploit = exploit_of(&vulnerability).unwrap();
+
+        assert_eq!(
+            exploit.publication_date(),
+            NaiveDate::from_ymd_opt(2021, 12, 11)
+        );
+        assert_eq!(
+            exploit.links(),
+            ["https://www.exploit-db.com/exploits/50592"]
+        );
+        let kev = exploit.cisa_kev().unwrap();
+        assert_eq!(kev.publish_date(), NaiveDate::from_ymd_opt(2021, 12, 10));
+        assert_eq!(kev.due_date(), NaiveDate::from_ymd_opt(2021, 12, 24));
+    }
+
+    #[test]
+    fn it_leaves_vulnerabilities_without_exploits_unexploited() {
+        let postgres_13_json = include_bytes!("../../tests/fixtures/scan-results/postgres_13.json");
+        let json_scan_result: JsonScanResultV1 = serde_json::from_slice(postgres_13_json).unwrap();
+        let scan_result: ScanResult = json_scan_result.into();
+
+        assert!(
+            scan_result
+                .vulnerabilities()
+                .iter()
+                .all(|v| v.exploit().is_none() && !v.known_exploited())
+        );
+    }
+
     #[test]
     fn test_handles_layers_without_digest() {
         let postgres_13_json = include_bytes!("../../tests/fixtures/scan-results/postgres_13.json");
@@ -672,4 +989,110 @@ mod tests {
         assert!(found_layer.is_some(), "Should find layer by valid digest");
         assert_eq!(found_layer.unwrap().digest(), Some(digest));
     }
+
+    #[test]
+    fn it_keeps_what_can_be_read_of_reports_with_unknown_or_missing_fields() {
+        let json = serde_json::json!({
+            "result": {
+                "metadata": {"pullString": "alpine:3.19", "imageId": "sha256:12345"},
+                "layers": {"l1": {"digest": "sha256:layer", "index": 0}},
+                "packages": {
+                    "p1": {"layerRef": "l1", "name": "musl", "version": "1.2.4", "type": "zig",
+                           "vulnerabilitiesRefs": ["v1"]},
+                    "p2": {"layerRef": "l1", "version": "1.0"}
+                },
+                "vulnerabilities": {
+                    "v1": {"name": "CVE-2024-0001", "severity": "extreme"}
+                },
+                "riskAccepts": null
+            }
+        });
+
+        let scan_result: ScanResult = serde_json::from_value::<JsonScanResultV1>(json)
+            .unwrap()
+            .into();
+
+        let package = &scan_result.packages()[0];
+        assert_eq!(scan_result.packages().len(), 1);
+        assert_eq!(package.package_type(), &PackageType::Unknown);
+        let vulnerability = &package.vulnerabilities()[0];
+        assert_eq!(vulnerability.severity(), Severity::Unknown);
+        assert!(vulnerability.cvss().is_none());
+        assert_eq!(
+            scan_result.metadata().parse_warnings(),
+            [
+                "packages.p1.type: unknown value \"zig\"",
+                "packages.p2: missing field `name`",
+                "vulnerabilities.v1.severity: unknown value \"extreme\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_keeps_the_packages_whose_version_is_not_semver() {
+        let json = serde_json::json!({
+            "result": {
+                "metadata": {"pullString": "debian:12", "imageId": "sha256:12345"},
+                "layers": {"l1": {"digest": "sha256:layer", "index": 0}},
+                "packages": {
+                    "p1": {"layerRef": "l1", "name": "libc6", "version": "2.36-9+deb12u4", "type": "os"},
+                    "p2": {"layerRef": "l1", "name": "tzdata", "version": "2024a-0+deb12u1", "type": "os"},
+                    "p3": {"layerRef": "l1", "name": "perl-base", "version": "5.36.0-7+deb12u1", "type": "os"},
+                    "p4": {"layerRef": "l1", "name": "guava", "version": "31.1-jre", "type": "java"}
+                }
+            }
+        });
+
+        let scan_result: ScanResult = serde_json::from_value::<JsonScanResultV1>(json)
+            .unwrap()
+            .into();
+
+        let mut versions: Vec<_> = scan_result
+            .packages()
+            .iter()
+            .map(|p| p.version().to_string())
+            .collect();
+        versions.sort();
+        assert_eq!(
+            versions,
+            [
+                "2.36-9+deb12u4",
+                "2024a-0+deb12u1",
+                "31.1-jre",
+                "5.36.0-7+deb12u1"
+            ]
+        );
+        assert!(scan_result.metadata().parse_warnings().is_empty());
+    }
+
+    #[test]
+    fn it_reads_the_scan_time() {
+        let postgres_13_json = include_bytes!("../../tests/fixtures/scan-results/postgres_13.json");
+        let scan_result: ScanResult = serde_json::from_slice::<JsonScanResultV1>(postgres_13_json)
+            .unwrap()
+            .into();
+
+        assert_eq!(
+            scan_result.metadata().scanned_at().unwrap().to_rfc3339(),
+            "2025-09-30T16:05:33.318466678+00:00"
+        );
+        assert_eq!(
+            scan_result.metadata().scan_duration(),
+            Some(Duration::from_nanos(10_413_926_974))
+        );
+    }
+
+    #[rstest]
+    #[case("10.413926974s", Some(Duration::from_nanos(10_413_926_974)))]
+    #[case("1m30.5s", Some(Duration::from_millis(90_500)))]
+    #[case("1h2m", Some(Duration::from_secs(3720)))]
+    #[case("250ms", Some(Duration::from_millis(250)))]
+    #[case("12µs", Some(Duration::from_micros(12)))]
+    #[case("0s", Some(Duration::ZERO))]
+    #[case("10", None)]
+    #[case("-1s", None)]
+    #[case("1d", None)]
+    fn it_reads_go_durations(#[case] string: &str, #[case] expected: Option<Duration>) {
+        assert_eq!(go_duration(string), expected);
+    }
 }
@@ -0,0 +1,108 @@
+use std::process::Command;
+
+use crate::app::{TokenProvider, TokenProviderError};
+
+/// Keychain service the API token is stored under, with the API URL as the account.
+const KEYCHAIN_SERVICE: &str = "sysdig-lsp";
+
+/// Reads the token from an environment variable.
+pub struct EnvVarTokenProvider {
+    name: String,
+}
+
+impl EnvVarTokenProvider {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+}
+
+impl TokenProvider for EnvVarTokenProvider {
+    fn source(&self) -> String {
+        format!("environment variable {}", self.name)
+    }
+
+    fn token(&self) -> Result<Option<String>, TokenProviderError> {
+        match std::env::var(&self.name) {
+            Ok(token) => Ok(Some(token)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(TokenProviderError {
+                provider: self.source(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+}
+
+/// Reads the token from the OS keychain through its CLI: `security` (macOS Keychain)
+/// or `secret-tool` (Secret Service of GNOME Keyring, KWallet...) on Linux.
+pub struct KeychainTokenProvider {
+    account: String,
+}
+
+impl KeychainTokenProvider {
+    pub fn new(account: impl Into<String>) -> Self {
+        Self {
+            account: account.into(),
+        }
+    }
+
+    fn lookup_command(&self) -> Option<Command> {
+        let mut command = match std::env::consts::OS {
+            "macos" => {
+                let mut command = Command::new("security");
+                command.args(["find-generic-password", "-w", "-s", KEYCHAIN_SERVICE]);
+                command.args(["-a", &self.account]);
+                command
+            }
+            "linux" => {
+                let mut command = Command::new("secret-tool");
+                command.args(["lookup", "service", KEYCHAIN_SERVICE]);
+                command.args(["account", &self.account]);
+                command
+            }
+            _ => return None,
+        };
+        command.stdin(std::process::Stdio::null());
+        Some(command)
+    }
+}
+
+impl TokenProvider for KeychainTokenProvider {
+    fn source(&self) -> String {
+        format!(
+            "OS keychain (service {KEYCHAIN_SERVICE}, account {})",
+            self.account
+        )
+    }
+
+    fn token(&self) -> Result<Option<String>, TokenProviderError> {
+        let error = |reason: String| TokenProviderError {
+            provider: self.source(),
+            reason,
+        };
+        let mut command = self
+            .lookup_command()
+            .ok_or_else(|| error("the keychain isn't supported on this OS".to_string()))?;
+        let output = command.output().map_err(|e| error(e.to_string()))?;
+
+        // Both tools exit with an error when there's no such entry.
+        let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok((output.status.success() && !token.is_empty()).then_some(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_the_token_of_an_env_var() {
+        let provider = EnvVarTokenProvider::new("SYSDIG_LSP_TEST_UNSET_TOKEN");
+
+        assert_eq!(provider.token().unwrap(), None);
+        assert_eq!(
+            provider.source(),
+            "environment variable SYSDIG_LSP_TEST_UNSET_TOKEN"
+        );
+    }
+}
@@ -1,10 +1,29 @@
+use std::{
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
 use crate::{
-    app::LSPServer,
-    infra::{ConcreteComponentFactory, lsp_logger::LSPLogger},
+    app::{
+        CheckFormat, CheckStatus, LSPServer, ServerTrace, check_files,
+        component_factory::{ComponentFactory, Config},
+        expand_check_paths,
+        protocol::{ServerStatusRequest, ServerVersionRequest},
+    },
+    domain::scanresult::severity::Severity,
+    infra::{
+        ConcreteComponentFactory, DEFAULT_FIXTURES_DIRECTORY, ListenAddress, SysdigAPIToken,
+        accept_websocket, generate_scan_fixture,
+        lsp_logger::{LSPLogger, RedactingStderr},
+    },
+};
+use clap::{Parser, Subcommand};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
 };
-use clap::Parser;
-use tower_lsp::{LspService, Server};
-use tracing_subscriber::layer::SubscriberExt;
+use tower_lsp::{LspService, Server, lsp_types::request::Request};
+use tracing_subscriber::{Registry, layer::SubscriberExt, reload};
 
 mod app;
 mod domain;
@@ -12,24 +31,267 @@ mod infra;
 
 #[derive(Parser, Debug)]
 #[command(version, author, about, long_about)]
-struct Args {}
+struct Args {
+    /// Prints the JSON Schema of the settings (`initializationOptions`) and exits.
+    #[arg(long)]
+    print_config_schema: bool,
+
+    /// Scans the images of Dockerfiles, Compose files, K8s manifests or CI configs, prints
+    /// a report and exits instead of starting the language server. Takes paths and globs
+    /// (e.g. `'services/**/Dockerfile'`), matched from the current directory.
+    ///
+    /// Exits with 1 if a policy fails or a vulnerability reaches `--fail-on`, and with 2
+    /// if some file or image can't be scanned.
+    #[arg(long, value_name = "FILE", num_args = 1..)]
+    check: Vec<String>,
+
+    /// Format of the `--check` report: text, json, markdown or sarif.
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "text",
+        requires = "check"
+    )]
+    format: CheckFormat,
+
+    /// Also fails the check on reported vulnerabilities at least this severe: critical,
+    /// high, medium, low or negligible.
+    #[arg(long, value_name = "SEVERITY", requires = "check")]
+    fail_on: Option<Severity>,
+
+    /// JSON file with the settings of the check, as sent by editors in
+    /// `initializationOptions`. Defaults to the API URL in SECURE_API_URL.
+    #[arg(long, value_name = "FILE", requires = "check")]
+    settings: Option<PathBuf>,
+
+    /// Talks to the editor over stdin and stdout. This is the default, the flag is
+    /// accepted as editors usually pass it.
+    #[arg(long, conflicts_with = "listen")]
+    stdio: bool,
+
+    /// Accepts editors on a socket instead of stdio, one session at a time, e.g. to run
+    /// in a container or a remote environment: `host:port` (or `tcp://host:port`) for the
+    /// LSP base protocol over TCP, `ws://host:port` for a JSON-RPC message per WebSocket
+    /// message.
+    #[arg(long, value_name = "ADDR", conflicts_with = "check")]
+    listen: Option<ListenAddress>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Scans an image and writes its anonymized report as a test fixture.
+    ///
+    /// Reads the API token from SECURE_API_TOKEN.
+    GenFixture {
+        /// Pull string of the image to scan, e.g. `postgres:13`.
+        image: String,
+
+        /// Directory the fixture is written to.
+        #[arg(long, default_value = DEFAULT_FIXTURES_DIRECTORY)]
+        output_dir: PathBuf,
+
+        /// Sysdig API URL, defaults to SECURE_API_URL.
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+}
 
 #[tokio::main]
-async fn main() {
-    let _ = Args::parse();
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    if args.print_config_schema {
+        return print_config_schema();
+    }
+    if !args.check.is_empty() {
+        return check(
+            &args.check,
+            args.format,
+            args.fail_on,
+            args.settings.as_deref(),
+        )
+        .await;
+    }
 
-    let (service, messages) = LspService::new(|client| {
-        let subscriber = tracing_subscriber::registry()
-            .with(LSPLogger::new(client.clone()))
-            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+    match args.command {
+        None => serve(args.listen).await,
+        Some(Command::GenFixture {
+            image,
+            output_dir,
+            api_url,
+        }) => gen_fixture(&image, api_url, &output_dir).await,
+    }
+}
 
-        tracing::subscriber::set_global_default(subscriber)
-            .expect("setting default subscriber failed");
+fn print_config_schema() -> ExitCode {
+    match serde_json::to_string_pretty(&Config::json_schema()) {
+        Ok(schema) => {
+            println!("{schema}");
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("unable to serialize the config schema: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn gen_fixture(
+    image: &str,
+    api_url: Option<String>,
+    output_dir: &std::path::Path,
+) -> ExitCode {
+    let Some(api_url) = api_url.or_else(|| std::env::var("SECURE_API_URL").ok()) else {
+        eprintln!("missing Sysdig API URL: pass --api-url or set SECURE_API_URL");
+        return ExitCode::FAILURE;
+    };
+    let Ok(api_token) = std::env::var("SECURE_API_TOKEN") else {
+        eprintln!("missing Sysdig API token: set SECURE_API_TOKEN");
+        return ExitCode::FAILURE;
+    };
+
+    match generate_scan_fixture(image, api_url, SysdigAPIToken(api_token), output_dir).await {
+        Ok(path) => {
+            println!("{}", path.display());
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("unable to generate fixture for {image}: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Exit status of checks that couldn't run or scan every image, told apart from the
+/// `ExitCode::FAILURE` of failed checks.
+const CHECK_ERROR_EXIT_CODE: u8 = 2;
+
+async fn check(
+    patterns: &[String],
+    format: CheckFormat,
+    fail_on: Option<Severity>,
+    settings: Option<&Path>,
+) -> ExitCode {
+    tracing_subscriber::fmt()
+        .with_writer(RedactingStderr)
+        .with_max_level(tracing::Level::WARN)
+        .init();
+
+    let workspace_root = std::env::current_dir().unwrap_or_default();
+    let paths = match expand_check_paths(&workspace_root, patterns) {
+        Ok(paths) => paths,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::from(CHECK_ERROR_EXIT_CODE);
+        }
+    };
+    let config = match check_config(settings) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::from(CHECK_ERROR_EXIT_CODE);
+        }
+    };
+    let components = match ConcreteComponentFactory.create_components(config.clone()) {
+        Ok(components) => components,
+        Err(error) => {
+            eprintln!("unable to start the scanner: {error}");
+            return ExitCode::from(CHECK_ERROR_EXIT_CODE);
+        }
+    };
+
+    let report = check_files(&components, &config, &workspace_root, &paths, fail_on).await;
+    println!("{}", report.render(format, &workspace_root));
+    match report.status() {
+        CheckStatus::Passed => ExitCode::SUCCESS,
+        CheckStatus::Failed => ExitCode::FAILURE,
+        CheckStatus::Errored => ExitCode::from(CHECK_ERROR_EXIT_CODE),
+    }
+}
+
+/// Settings of `--check`: the ones in `settings`, or else the API URL in SECURE_API_URL.
+fn check_config(settings: Option<&Path>) -> Result<Config, String> {
+    let settings = match settings {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+            .map_err(|e| format!("unable to read the settings in {}: {e}", path.display()))?,
+        None => serde_json::json!({
+            "sysdig": { "api_url": std::env::var("SECURE_API_URL").unwrap_or_default() }
+        }),
+    };
+    Config::from_settings(&settings).map_err(|e| e.to_string())
+}
+
+/// Forwards the logs to the editor of the current session, if any.
+type SessionLogger = reload::Handle<Option<LSPLogger>, Registry>;
+
+async fn serve(listen: Option<ListenAddress>) -> ExitCode {
+    let (lsp_logger, session_logger) = reload::Layer::new(None);
+    let subscriber = tracing_subscriber::registry()
+        .with(lsp_logger)
+        .with(tracing_subscriber::fmt::layer().with_writer(RedactingStderr));
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
+
+    let Some(listen) = listen else {
+        serve_session(tokio::io::stdin(), tokio::io::stdout(), &session_logger).await;
+        return ExitCode::SUCCESS;
+    };
+
+    let (ListenAddress::Tcp(address) | ListenAddress::WebSocket(address)) = &listen;
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("unable to listen on {listen}: {error}");
+            return ExitCode::FAILURE;
+        }
+    };
+    tracing::info!("listening on {listen}");
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(error) => {
+                tracing::warn!("unable to accept a connection on {listen}: {error}");
+                continue;
+            }
+        };
+        tracing::info!("editor connected from {peer}");
+        match listen {
+            ListenAddress::Tcp(_) => {
+                let (read, write) = stream.into_split();
+                serve_session(read, write, &session_logger).await;
+            }
+            ListenAddress::WebSocket(_) => match accept_websocket(stream).await {
+                Ok((read, write)) => serve_session(read, write, &session_logger).await,
+                Err(error) => {
+                    tracing::warn!("unable to open a WebSocket with {peer}: {error}");
+                    continue;
+                }
+            },
+        }
+        tracing::info!("editor disconnected from {peer}");
+    }
+}
 
-        LSPServer::new(client, ConcreteComponentFactory)
-    });
+/// Serves an editor until it exits or disconnects, with a fresh server state.
+async fn serve_session(
+    input: impl AsyncRead + Unpin,
+    output: impl AsyncWrite,
+    session_logger: &SessionLogger,
+) {
+    let (service, messages) = LspService::build(|client| {
+        let trace = ServerTrace::default();
+        let _ = session_logger.reload(Some(LSPLogger::new(client.clone(), trace.clone())));
+        LSPServer::new(client, ConcreteComponentFactory).with_trace(trace)
+    })
+    .custom_method("$/setTrace", LSPServer::set_trace)
+    .custom_method(ServerStatusRequest::METHOD, LSPServer::status)
+    .custom_method(ServerVersionRequest::METHOD, LSPServer::version)
+    .finish();
 
-    Server::new(stdin, stdout, messages).serve(service).await;
+    Server::new(input, output, messages).serve(service).await;
+    let _ = session_logger.reload(None);
 }
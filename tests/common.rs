@@ -1,16 +1,26 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 use mockall::mock;
 use sysdig_lsp::{
     app::{
-        IacScanError, IacScanScope, IacScanner, ImageBuildError, ImageBuildResult, ImageBuilder,
-        ImageScanError, ImageScanner, LSPServer,
+        BuildLogSender, CachingImageScanner, CredentialsChecker, CredentialsError,
+        FilesystemScanner, IacScanError, IacScanScope, IacScanner, ImageBuildError,
+        ImageBuildOptions, ImageBuildResult, ImageBuilder, ImageDigestResolver, ImageScanError,
+        ImageScanner, ImageTag, LSPServer, RegistryClient, RegistryError, RiskAcceptance,
+        RiskAcceptanceClient, RiskAcceptanceError, ScanResultStore, ScanResultStoreError,
+        ScanScheduler, ScanSnapshot, TenantScope,
         component_factory::{ComponentFactory, ComponentFactoryError, Components, Config},
+        renamed_uri,
     },
     domain::{iacscanresult::iac_scan_result::IacScanResult, scanresult::scan_result::ScanResult},
 };
-use tower_lsp::lsp_types::{Diagnostic, MessageType};
+use tower_lsp::lsp_types::{
+    ApplyWorkspaceEditResponse, Diagnostic, MessageType, Registration, WorkspaceEdit,
+    notification::Notification,
+};
 
 // --- Contenido de recorder.rs ---
 pub type PublishedDiagnostics = Vec<(String, Vec<Diagnostic>)>;
@@ -18,14 +28,30 @@ pub type PublishedDiagnostics = Vec<(String, Vec<Diagnostic>)>;
 #[derive(Clone)]
 pub struct TestClientRecorder {
     pub messages: Arc<Mutex<Vec<(MessageType, String)>>>,
+    pub log_messages: Arc<Mutex<Vec<(MessageType, String)>>>,
     pub diagnostics: Arc<Mutex<PublishedDiagnostics>>,
+    pub registrations: Arc<Mutex<Vec<Registration>>>,
+    /// Custom notifications, as method and params.
+    pub notifications: Arc<Mutex<Vec<(String, serde_json::Value)>>>,
+    /// Edits requested with `workspace/applyEdit`, all reported as applied.
+    pub applied_edits: Arc<Mutex<Vec<WorkspaceEdit>>>,
+    /// Number of `workspace/codeLens/refresh` requests.
+    pub code_lens_refreshes: Arc<Mutex<usize>>,
+    /// Number of `workspace/diagnostic/refresh` requests.
+    pub diagnostic_refreshes: Arc<Mutex<usize>>,
 }
 
 impl TestClientRecorder {
     pub fn new() -> Self {
         Self {
             messages: Arc::new(Mutex::new(Vec::new())),
+            log_messages: Arc::new(Mutex::new(Vec::new())),
             diagnostics: Arc::new(Mutex::new(Vec::new())),
+            registrations: Arc::new(Mutex::new(Vec::new())),
+            notifications: Arc::new(Mutex::new(Vec::new())),
+            applied_edits: Arc::new(Mutex::new(Vec::new())),
+            code_lens_refreshes: Arc::new(Mutex::new(0)),
+            diagnostic_refreshes: Arc::new(Mutex::new(0)),
         }
     }
 }
@@ -49,6 +75,17 @@ impl sysdig_lsp::app::LSPClient for TestClientRecorder {
             .push((message_type, message.to_string()));
     }
 
+    async fn log_message<M: std::fmt::Display + Send>(
+        &self,
+        message_type: MessageType,
+        message: M,
+    ) {
+        self.log_messages
+            .lock()
+            .await
+            .push((message_type, message.to_string()));
+    }
+
     async fn publish_diagnostics(
         &self,
         url: &str,
@@ -60,6 +97,46 @@ impl sysdig_lsp::app::LSPClient for TestClientRecorder {
             .await
             .push((url.to_string(), diagnostics));
     }
+
+    async fn register_capability(
+        &self,
+        registrations: Vec<Registration>,
+    ) -> tower_lsp::jsonrpc::Result<()> {
+        self.registrations.lock().await.extend(registrations);
+        Ok(())
+    }
+
+    async fn apply_edit(
+        &self,
+        edit: WorkspaceEdit,
+    ) -> tower_lsp::jsonrpc::Result<ApplyWorkspaceEditResponse> {
+        self.applied_edits.lock().await.push(edit);
+        Ok(ApplyWorkspaceEditResponse {
+            applied: true,
+            failure_reason: None,
+            failed_change: None,
+        })
+    }
+
+    async fn code_lens_refresh(&self) -> tower_lsp::jsonrpc::Result<()> {
+        *self.code_lens_refreshes.lock().await += 1;
+        Ok(())
+    }
+
+    async fn workspace_diagnostic_refresh(&self) -> tower_lsp::jsonrpc::Result<()> {
+        *self.diagnostic_refreshes.lock().await += 1;
+        Ok(())
+    }
+
+    async fn send_notification<N: Notification>(&self, params: N::Params)
+    where
+        N::Params: Send,
+    {
+        self.notifications
+            .lock()
+            .await
+            .push((N::METHOD.to_string(), serde_json::to_value(params).unwrap()));
+    }
 }
 
 // --- Contenido de mocks.rs ---
@@ -67,7 +144,9 @@ mock! {
     pub ImageBuilder {}
     #[async_trait::async_trait]
     impl ImageBuilder for ImageBuilder {
-        async fn build_image(&self, containerfile: &std::path::Path) -> Result<ImageBuildResult, ImageBuildError>;
+        async fn build_image(&self, containerfile: &std::path::Path, options: &ImageBuildOptions, build_log: BuildLogSender) -> Result<ImageBuildResult, ImageBuildError>;
+        async fn remove_image(&self, image_name: &str) -> Result<(), ImageBuildError>;
+        async fn remove_temporary_images(&self) -> Result<Vec<String>, ImageBuildError>;
     }
 }
 
@@ -87,6 +166,39 @@ mock! {
     }
 }
 
+mock! {
+    pub FilesystemScanner {}
+    #[async_trait::async_trait]
+    impl FilesystemScanner for FilesystemScanner {
+        async fn scan_directory(&self, directory: &std::path::Path) -> Result<ScanResult, ImageScanError>;
+    }
+}
+
+mock! {
+    pub CredentialsChecker {}
+    #[async_trait::async_trait]
+    impl CredentialsChecker for CredentialsChecker {
+        async fn check_credentials(&self) -> Result<(), CredentialsError>;
+    }
+}
+
+mock! {
+    pub RiskAcceptanceClient {}
+    #[async_trait::async_trait]
+    impl RiskAcceptanceClient for RiskAcceptanceClient {
+        async fn accept_risk(&self, acceptance: &RiskAcceptance) -> Result<(), RiskAcceptanceError>;
+    }
+}
+
+mock! {
+    pub RegistryClient {}
+    #[async_trait::async_trait]
+    impl RegistryClient for RegistryClient {
+        async fn list_tags(&self, repository: &str) -> Result<Vec<ImageTag>, RegistryError>;
+        async fn resolve_digest(&self, image: &str) -> Result<String, RegistryError>;
+    }
+}
+
 // --- Implementaciones de traits para Arc<Mutex<Mock>> ---
 #[derive(Clone)]
 pub struct MockImageBuilderWrapper(pub Arc<Mutex<MockImageBuilder>>);
@@ -94,14 +206,36 @@ pub struct MockImageBuilderWrapper(pub Arc<Mutex<MockImageBuilder>>);
 pub struct MockImageScannerWrapper(pub Arc<Mutex<MockImageScanner>>);
 #[derive(Clone)]
 pub struct MockIacScannerWrapper(pub Arc<Mutex<MockIacScanner>>);
+#[derive(Clone)]
+pub struct MockFilesystemScannerWrapper(pub Arc<Mutex<MockFilesystemScanner>>);
+#[derive(Clone)]
+pub struct MockCredentialsCheckerWrapper(pub Arc<Mutex<MockCredentialsChecker>>);
+#[derive(Clone)]
+pub struct MockRiskAcceptanceClientWrapper(pub Arc<Mutex<MockRiskAcceptanceClient>>);
+#[derive(Clone)]
+pub struct MockRegistryClientWrapper(pub Arc<Mutex<MockRegistryClient>>);
 
 #[async_trait::async_trait]
 impl ImageBuilder for MockImageBuilderWrapper {
     async fn build_image(
         &self,
         containerfile: &std::path::Path,
+        options: &ImageBuildOptions,
+        build_log: BuildLogSender,
     ) -> Result<ImageBuildResult, ImageBuildError> {
-        self.0.lock().await.build_image(containerfile).await
+        self.0
+            .lock()
+            .await
+            .build_image(containerfile, options, build_log)
+            .await
+    }
+
+    async fn remove_image(&self, image_name: &str) -> Result<(), ImageBuildError> {
+        self.0.lock().await.remove_image(image_name).await
+    }
+
+    async fn remove_temporary_images(&self) -> Result<Vec<String>, ImageBuildError> {
+        self.0.lock().await.remove_temporary_images().await
     }
 }
 
@@ -112,6 +246,16 @@ impl ImageScanner for MockImageScannerWrapper {
     }
 }
 
+/// Resolves no digest, so scans are cached by pull string.
+pub struct NoDigestResolver;
+
+#[async_trait::async_trait]
+impl ImageDigestResolver for NoDigestResolver {
+    async fn resolve_digest(&self, _image_pull_string: &str) -> Option<String> {
+        None
+    }
+}
+
 #[async_trait::async_trait]
 impl IacScanner for MockIacScannerWrapper {
     async fn scan_iac(&self, scope: &IacScanScope) -> Result<IacScanResult, IacScanError> {
@@ -119,20 +263,139 @@ impl IacScanner for MockIacScannerWrapper {
     }
 }
 
+#[async_trait::async_trait]
+impl FilesystemScanner for MockFilesystemScannerWrapper {
+    async fn scan_directory(
+        &self,
+        directory: &std::path::Path,
+    ) -> Result<ScanResult, ImageScanError> {
+        self.0.lock().await.scan_directory(directory).await
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialsChecker for MockCredentialsCheckerWrapper {
+    async fn check_credentials(&self) -> Result<(), CredentialsError> {
+        self.0.lock().await.check_credentials().await
+    }
+}
+
+#[async_trait::async_trait]
+impl RiskAcceptanceClient for MockRiskAcceptanceClientWrapper {
+    async fn accept_risk(&self, acceptance: &RiskAcceptance) -> Result<(), RiskAcceptanceError> {
+        self.0.lock().await.accept_risk(acceptance).await
+    }
+}
+
+#[async_trait::async_trait]
+impl RegistryClient for MockRegistryClientWrapper {
+    async fn list_tags(&self, repository: &str) -> Result<Vec<ImageTag>, RegistryError> {
+        self.0.lock().await.list_tags(repository).await
+    }
+
+    async fn resolve_digest(&self, image: &str) -> Result<String, RegistryError> {
+        self.0.lock().await.resolve_digest(image).await
+    }
+}
+
+/// Scan result store shared across `TestSetup`s to simulate editor restarts.
+#[derive(Clone, Default)]
+pub struct InMemoryScanResultStore(pub Arc<Mutex<HashMap<String, ScanSnapshot>>>);
+
+#[async_trait::async_trait]
+impl ScanResultStore for InMemoryScanResultStore {
+    async fn save(&self, uri: &str, snapshot: &ScanSnapshot) -> Result<(), ScanResultStoreError> {
+        self.0
+            .lock()
+            .await
+            .insert(uri.to_string(), snapshot.clone());
+        Ok(())
+    }
+
+    async fn load(&self, uri: &str) -> Result<Option<ScanSnapshot>, ScanResultStoreError> {
+        Ok(self.0.lock().await.get(uri).cloned())
+    }
+
+    async fn rename(&self, from: &str, to: &str) -> Result<(), ScanResultStoreError> {
+        let mut snapshots = self.0.lock().await;
+        let renamed: Vec<_> = snapshots
+            .keys()
+            .filter_map(|uri| Some((uri.clone(), renamed_uri(uri, from, to)?)))
+            .collect();
+        for (old_uri, new_uri) in renamed {
+            if let Some(snapshot) = snapshots.remove(&old_uri) {
+                snapshots.insert(new_uri, snapshot);
+            }
+        }
+        Ok(())
+    }
+}
+
 // --- Estructuras de Setup ---
 #[derive(Clone)]
 pub struct MockComponentFactory {
     pub image_builder: Arc<Mutex<MockImageBuilder>>,
     pub image_scanner: Arc<Mutex<MockImageScanner>>,
     pub iac_scanner: Arc<Mutex<MockIacScanner>>,
+    pub filesystem_scanner: Arc<Mutex<MockFilesystemScanner>>,
+    /// Shared by every scope configured in `sysdig.scopes`.
+    pub scoped_image_scanner: Arc<Mutex<MockImageScanner>>,
+    pub scan_result_store: Option<InMemoryScanResultStore>,
+    /// `None` skips the credentials check, as offline scans do.
+    pub credentials_checker: Option<Arc<Mutex<MockCredentialsChecker>>>,
+    pub risk_acceptance_client: Arc<Mutex<MockRiskAcceptanceClient>>,
+    pub registry_client: Arc<Mutex<MockRegistryClient>>,
+    /// Caches the results of `image_scanner` as the real scanner chain does.
+    pub cache_scans: bool,
 }
 
 impl ComponentFactory for MockComponentFactory {
-    fn create_components(&self, _config: Config) -> Result<Components, ComponentFactoryError> {
+    fn create_components(&self, config: Config) -> Result<Components, ComponentFactoryError> {
+        let scopes = config
+            .sysdig
+            .scopes
+            .iter()
+            .map(|scope| {
+                TenantScope::new(
+                    &scope.paths,
+                    Box::new(MockImageScannerWrapper(self.scoped_image_scanner.clone())),
+                    Box::new(MockIacScannerWrapper(self.iac_scanner.clone())),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut scanner: Box<dyn ImageScanner + Send + Sync> =
+            Box::new(MockImageScannerWrapper(self.image_scanner.clone()));
+        if self.cache_scans {
+            scanner = Box::new(CachingImageScanner::new(
+                scanner,
+                Box::new(NoDigestResolver),
+                Duration::from_secs(300),
+            ));
+        }
+
         Ok(Components {
             builder: Box::new(MockImageBuilderWrapper(self.image_builder.clone())),
-            scanner: Box::new(MockImageScannerWrapper(self.image_scanner.clone())),
+            scanner,
+            scan_scheduler: ScanScheduler::new(config.sysdig.scan.max_concurrent_scans),
             iac_scanner: Box::new(MockIacScannerWrapper(self.iac_scanner.clone())),
+            filesystem_scanner: Box::new(MockFilesystemScannerWrapper(
+                self.filesystem_scanner.clone(),
+            )),
+            scan_result_store: self
+                .scan_result_store
+                .clone()
+                .map(|store| Box::new(store) as Box<dyn ScanResultStore + Send + Sync>),
+            scopes,
+            credentials_checker: self.credentials_checker.clone().map(|checker| {
+                Box::new(MockCredentialsCheckerWrapper(checker))
+                    as Box<dyn CredentialsChecker + Send + Sync>
+            }),
+            risk_acceptance_client: Some(Box::new(MockRiskAcceptanceClientWrapper(
+                self.risk_acceptance_client.clone(),
+            ))),
+            registry_client: Some(Box::new(MockRegistryClientWrapper(
+                self.registry_client.clone(),
+            ))),
         })
     }
 }
@@ -145,11 +408,42 @@ pub struct TestSetup {
 
 impl TestSetup {
     pub fn new() -> Self {
+        Self::with_scan_result_store(None)
+    }
+
+    pub fn with_scan_result_store(scan_result_store: Option<InMemoryScanResultStore>) -> Self {
+        Self::with_components(scan_result_store, None, false)
+    }
+
+    pub fn with_credentials_checker(credentials_checker: MockCredentialsChecker) -> Self {
+        Self::with_components(None, Some(credentials_checker), false)
+    }
+
+    /// Scan results are cached, as they are outside of tests.
+    pub fn with_cached_scans() -> Self {
+        Self::with_components(None, None, true)
+    }
+
+    fn with_components(
+        scan_result_store: Option<InMemoryScanResultStore>,
+        credentials_checker: Option<MockCredentialsChecker>,
+        cache_scans: bool,
+    ) -> Self {
         let client_recorder = TestClientRecorder::new();
+        let mut image_builder = MockImageBuilder::new();
+        // Built images are removed after every scan, which most tests don't care about.
+        image_builder.expect_remove_image().returning(|_| Ok(()));
         let component_factory = MockComponentFactory {
-            image_builder: Arc::new(Mutex::new(MockImageBuilder::new())),
+            image_builder: Arc::new(Mutex::new(image_builder)),
             image_scanner: Arc::new(Mutex::new(MockImageScanner::new())),
             iac_scanner: Arc::new(Mutex::new(MockIacScanner::new())),
+            filesystem_scanner: Arc::new(Mutex::new(MockFilesystemScanner::new())),
+            scoped_image_scanner: Arc::new(Mutex::new(MockImageScanner::new())),
+            scan_result_store,
+            credentials_checker: credentials_checker.map(|checker| Arc::new(Mutex::new(checker))),
+            risk_acceptance_client: Arc::new(Mutex::new(MockRiskAcceptanceClient::new())),
+            registry_client: Arc::new(Mutex::new(MockRegistryClient::new())),
+            cache_scans,
         };
         let server = LSPServer::new(client_recorder.clone(), component_factory.clone());
         Self {
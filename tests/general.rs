@@ -4,28 +4,43 @@ use common::TestSetup;
 use rstest::{fixture, rstest};
 use serde_json::json;
 use std::collections::HashMap;
+use sysdig_lsp::app::component_factory::{ComponentFactory, Config};
+use sysdig_lsp::app::{
+    CheckFormat, CheckStatus, ImageScanError, ImageTag, PackageVersion, RiskAcceptanceError,
+    RiskAcceptanceScope, check_files,
+};
+use sysdig_lsp::domain::scanresult::accepted_risk_reason::AcceptedRiskReason;
 use sysdig_lsp::domain::scanresult::architecture::Architecture;
+use sysdig_lsp::domain::scanresult::cvss::Cvss;
 use sysdig_lsp::domain::scanresult::evaluation_result::EvaluationResult;
+use sysdig_lsp::domain::scanresult::exploit::{CisaKev, Exploit};
 use sysdig_lsp::domain::scanresult::operating_system::{Family, OperatingSystem};
 use sysdig_lsp::domain::scanresult::scan_result::ScanResult;
 use sysdig_lsp::domain::scanresult::scan_type::ScanType;
 use tower_lsp::LanguageServer;
 use tower_lsp::lsp_types::{
-    CodeActionContext, CodeActionParams, DiagnosticSeverity, DidChangeConfigurationParams,
-    DidChangeTextDocumentParams, DidOpenTextDocumentParams, ExecuteCommandParams, HoverParams,
-    InitializeParams, PartialResultParams, Position, Range, TextDocumentIdentifier,
-    TextDocumentItem, TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
-    WorkDoneProgressParams,
+    ClientCapabilities, CodeActionContext, CodeActionParams, CompletionParams, CompletionResponse,
+    CompletionTextEdit, DiagnosticSeverity, DiagnosticTag, DidChangeConfigurationParams,
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
+    DocumentDiagnosticParams, DocumentSymbolParams, DocumentSymbolResponse, ExecuteCommandParams,
+    FileRename, GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    InitializeParams, InitializedParams, Location, PartialResultParams, Position, PreviousResultId,
+    Range, ReferenceContext, ReferenceParams, RenameFilesParams, SemanticTokensParams,
+    SemanticTokensResult, TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams,
+    TextEdit, Url, VersionedTextDocumentIdentifier, WorkDoneProgressParams,
+    WorkspaceDiagnosticParams,
 };
 
 #[fixture]
 async fn initialized_server() -> TestSetup {
     let setup = TestSetup::new();
     let params = InitializeParams {
+        // Most tests open an untagged `alpine`: keep their diagnostics to the scan ones.
         initialization_options: Some(serde_json::json!({
             "sysdig": {
                 "apiUrl": "http://localhost:8080",
-                "api_token": "dummy-token"
+                "api_token": "dummy-token",
+                "lint": { "rules": { "latest-tag": false } }
             }
         })),
         ..Default::default()
@@ -56,6 +71,22 @@ async fn test_initialize_advertises_all_supported_commands() {
         "sysdig-lsp.execute-scan",
         "sysdig-lsp.execute-build-and-scan",
         "sysdig-lsp.execute-iac-scan",
+        "sysdig-lsp.scan-image-under-cursor",
+        "sysdig-lsp.export-scan-result",
+        "sysdig-lsp.export-sarif",
+        "sysdig-lsp.export-licenses",
+        "sysdig-lsp.generate-sbom",
+        "sysdig-lsp.generate-sysdigignore",
+        "sysdig-lsp.accept-risks",
+        "sysdig-lsp.scan-all-images",
+        "sysdig-lsp.pin-image-digest",
+        "sysdig-lsp.recommend-base-image",
+        "sysdig-lsp.request-accepted-risk",
+        "sysdig-lsp.show-scan-report",
+        "sysdig-lsp.open-scan-in-browser",
+        "sysdig-lsp.scan-directory",
+        "sysdig-lsp.cleanup-temp-images",
+        "sysdig-lsp.list-images",
     ] {
         assert!(
             advertised.iter().any(|c| c == command),
@@ -64,6 +95,45 @@ async fn test_initialize_advertises_all_supported_commands() {
     }
 }
 
+#[rstest]
+#[tokio::test]
+async fn test_initialized_registers_the_supported_documents_dynamically() {
+    let setup = TestSetup::new();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+        })),
+        capabilities: serde_json::from_value(json!({
+            "textDocument": {
+                "codeLens": { "dynamicRegistration": true },
+                "hover": { "dynamicRegistration": true }
+            }
+        }))
+        .unwrap(),
+        ..Default::default()
+    };
+    let result = setup.server.initialize(params).await.unwrap();
+    setup.server.initialized(InitializedParams {}).await;
+
+    // Registered features aren't advertised statically, or clients would offer them twice.
+    assert!(result.capabilities.code_lens_provider.is_none());
+    assert!(result.capabilities.hover_provider.is_none());
+    assert!(result.capabilities.code_action_provider.is_some());
+    assert!(result.capabilities.semantic_tokens_provider.is_some());
+
+    let registrations = setup.client_recorder.registrations.lock().await;
+    let methods: Vec<_> = registrations.iter().map(|r| r.method.as_str()).collect();
+    assert_eq!(methods, ["textDocument/codeLens", "textDocument/hover"]);
+    let patterns: Vec<_> = registrations[0].register_options.as_ref().unwrap()["documentSelector"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|filter| filter["pattern"].as_str())
+        .collect();
+    assert!(patterns.contains(&"**/{Dockerfile,Containerfile}"));
+    assert!(patterns.contains(&"**/*.{yml,yaml}"));
+}
+
 #[rstest]
 #[awt]
 #[tokio::test]
@@ -82,6 +152,340 @@ async fn test_did_change_configuration(#[future] initialized_server: TestSetup)
         .await;
 }
 
+#[rstest]
+#[tokio::test(start_paused = true)]
+async fn test_credentials_are_checked_when_configured() {
+    let mut checker = common::MockCredentialsChecker::new();
+    let mut sequence = mockall::Sequence::new();
+    checker
+        .expect_check_credentials()
+        .times(1)
+        .in_sequence(&mut sequence)
+        .returning(|| {
+            Err(sysdig_lsp::app::CredentialsError::Rejected(
+                "http://localhost:8080".to_string(),
+            ))
+        });
+    checker
+        .expect_check_credentials()
+        .times(1)
+        .in_sequence(&mut sequence)
+        .returning(|| Ok(()));
+    let setup = TestSetup::with_credentials_checker(checker);
+    let settings = json!({
+        "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+    });
+
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(settings.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    setup.server.initialized(InitializedParams {}).await;
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    {
+        let messages = setup.client_recorder.messages.lock().await;
+        assert!(
+            messages.iter().any(|(t, m)| *t == MessageType::ERROR
+                && m.contains("the Sysdig API token was rejected by http://localhost:8080")),
+            "{messages:?}"
+        );
+        let notifications = setup.client_recorder.notifications.lock().await;
+        let (method, params) = notifications.last().unwrap();
+        assert_eq!(method, "sysdig/serverStatus");
+        assert_eq!(params["health"], "error");
+    }
+
+    // Fixing the token in the settings checks it again.
+    setup
+        .server
+        .did_change_configuration(DidChangeConfigurationParams { settings })
+        .await;
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let notifications = setup.client_recorder.notifications.lock().await;
+    assert_eq!(notifications.len(), 2);
+    assert_eq!(notifications[1].1, json!({ "health": "ok" }));
+}
+
+#[rstest]
+#[case::without_options(None)]
+#[case::null_options(Some(json!(null)))]
+#[case::empty_options(Some(json!({})))]
+#[tokio::test(start_paused = true)]
+async fn test_settings_can_arrive_after_initializing(
+    #[case] initialization_options: Option<serde_json::Value>,
+) {
+    let mut checker = common::MockCredentialsChecker::new();
+    checker.expect_check_credentials().returning(|| Ok(()));
+    let setup = TestSetup::with_credentials_checker(checker);
+
+    let result = setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    setup.server.initialized(InitializedParams {}).await;
+
+    assert!(result.capabilities.execute_command_provider.is_some());
+    {
+        let notifications = setup.client_recorder.notifications.lock().await;
+        let (method, params) = notifications.last().unwrap();
+        assert_eq!(method, "sysdig/serverStatus");
+        assert_eq!(params["health"], "notConfigured");
+    }
+    let error = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":"file:///Dockerfile"}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        error.message.contains("Sysdig LSP not configured"),
+        "{}",
+        error.message
+    );
+
+    setup
+        .server
+        .did_change_configuration(DidChangeConfigurationParams {
+            settings: json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            }),
+        })
+        .await;
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let notifications = setup.client_recorder.notifications.lock().await;
+    assert_eq!(notifications.last().unwrap().1, json!({ "health": "ok" }));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_documents_opened_before_the_settings_are_replayed_with_them() {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams::default())
+        .await
+        .unwrap();
+    let dockerfile: Url = "file:///Dockerfile".parse().unwrap();
+    let closed: Url = "file:///closed/Dockerfile".parse().unwrap();
+    for uri in [&dockerfile, &closed] {
+        setup
+            .server
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "dockerfile".to_string(),
+                    1,
+                    "FROM debian\n".to_string(),
+                ),
+            })
+            .await;
+    }
+    setup
+        .server
+        .did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(dockerfile.clone(), 2),
+            content_changes: vec![tower_lsp::lsp_types::TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "FROM debian\nADD app.py /app/\n".to_string(),
+            }],
+        })
+        .await;
+    setup
+        .server
+        .did_close(tower_lsp::lsp_types::DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier::new(closed.clone()),
+        })
+        .await;
+    let lint_codes = |diagnostics: &[(String, Vec<tower_lsp::lsp_types::Diagnostic>)]| {
+        last_published_diagnostics_for(diagnostics, dockerfile.as_str())
+            .unwrap()
+            .iter()
+            .map(|diagnostic| diagnostic.code.clone())
+            .collect::<Vec<_>>()
+    };
+    let code = |rule: &str| {
+        Some(tower_lsp::lsp_types::NumberOrString::String(
+            rule.to_string(),
+        ))
+    };
+    {
+        // Linted right away with the default rules.
+        let diagnostics = setup.client_recorder.diagnostics.lock().await;
+        assert_eq!(
+            lint_codes(&diagnostics),
+            [code("latest-tag"), code("add-instead-of-copy")]
+        );
+    }
+    let published = setup.client_recorder.diagnostics.lock().await.len();
+
+    setup
+        .server
+        .did_change_configuration(DidChangeConfigurationParams {
+            settings: json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "lint": { "rules": { "latest-tag": false } }
+                }
+            }),
+        })
+        .await;
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    assert_eq!(lint_codes(&diagnostics), [code("add-instead-of-copy")]);
+    assert!(
+        diagnostics[published..]
+            .iter()
+            .all(|(uri, _)| uri != closed.as_str()),
+        "{diagnostics:?}"
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_invalid_configuration_points_at_the_offending_setting(
+    #[future] initialized_server: TestSetup,
+) {
+    initialized_server
+        .server
+        .did_change_configuration(DidChangeConfigurationParams {
+            settings: serde_json::json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "scopes": [{ "paths": "services/**" }]
+                }
+            }),
+        })
+        .await;
+
+    let messages = initialized_server.client_recorder.messages.lock().await;
+    let (message_type, message) = messages.last().unwrap();
+    assert_eq!(*message_type, MessageType::ERROR);
+    assert!(
+        message.contains("invalid setting `sysdig.scopes[0].paths`"),
+        "{message}"
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_invalid_setting_values_are_reported_in_the_server_status(
+    #[future] initialized_server: TestSetup,
+) {
+    initialized_server
+        .server
+        .did_change_configuration(DidChangeConfigurationParams {
+            settings: serde_json::json!({
+                "sysdig": { "apiUrl": "secure.sysdig.com", "api_token": "dummy-token" }
+            }),
+        })
+        .await;
+
+    let messages = initialized_server.client_recorder.messages.lock().await;
+    let (message_type, message) = messages.last().unwrap();
+    assert_eq!(*message_type, MessageType::ERROR);
+    assert!(
+        message.contains("invalid setting `sysdig.api_url`: invalid URL `secure.sysdig.com`"),
+        "{message}"
+    );
+    let notifications = initialized_server
+        .client_recorder
+        .notifications
+        .lock()
+        .await;
+    let (method, params) = notifications.last().unwrap();
+    assert_eq!(method, "sysdig/serverStatus");
+    assert_eq!(params["health"], "error");
+    assert_eq!(params["message"], json!(message));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_status_and_version_describe_the_server_in_use() {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams::default())
+        .await
+        .unwrap();
+
+    let status = serde_json::to_value(setup.server.status().await.unwrap()).unwrap();
+    assert_eq!(status["configured"], false);
+    assert_eq!(status["apiUrl"], json!(null));
+    assert_eq!(status["scans"]["succeeded"], 0);
+
+    setup
+        .server
+        .did_change_configuration(DidChangeConfigurationParams {
+            settings: json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "cache": { "scan_results_ttl_seconds": 60 }
+                }
+            }),
+        })
+        .await;
+
+    let status = serde_json::to_value(setup.server.status().await.unwrap()).unwrap();
+    assert_eq!(
+        status,
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "configured": true,
+            "apiUrl": "http://localhost:8080",
+            "apiToken": "[redacted]",
+            "scannerMode": "cli",
+            "scannerVersion": null,
+            "offline": false,
+            "scans": { "succeeded": 0, "failed": 0, "running": 0, "queued": 0 },
+            "cache": { "hits": 0, "misses": 0, "ttlSeconds": 60 }
+        })
+    );
+    let version = setup.server.version().await.unwrap();
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(version.scanner_version, None);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_initialize_rejects_invalid_settings_with_their_path() {
+    let setup = TestSetup::new();
+    let error = setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(serde_json::json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "cache": { "persist": "yes" } }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
+    assert_eq!(error.data, Some(json!({ "path": "sysdig.cache.persist" })));
+}
+
 #[rstest]
 #[awt]
 #[tokio::test]
@@ -166,6 +570,9 @@ fn scan_result() -> ScanResult {
         "1.0.0".to_string(),
         "/usr/lib/package1".to_string(),
         layer.clone(),
+        false,
+        None,
+        None,
     );
 
     result.add_package(
@@ -174,6 +581,9 @@ fn scan_result() -> ScanResult {
         "2.0.0".to_string(),
         "/usr/lib/package2".to_string(),
         layer,
+        false,
+        None,
+        None,
     );
 
     let vulnerability = result.add_vulnerability(
@@ -183,6 +593,8 @@ fn scan_result() -> ScanResult {
         None,
         false,
         Some("1.0.1".to_string()),
+        None,
+        None,
     );
 
     package1.add_vulnerability_found(vulnerability);
@@ -378,12 +790,11 @@ async fn test_execute_command(
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_hover(
+async fn test_scans_send_a_scan_result_notification(
     #[future] server_with_open_file: TestSetup,
     open_file_url: Url,
     scan_result: ScanResult,
 ) {
-    // Given
     server_with_open_file
         .component_factory
         .image_scanner
@@ -394,500 +805,683 @@ async fn test_hover(
         .times(1)
         .returning(move |_| Ok(scan_result.clone()));
 
-    let params = ExecuteCommandParams {
-        command: "sysdig-lsp.execute-scan".to_string(),
-        arguments: vec![
-            json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url.clone()}),
-            json!("alpine"),
-        ],
-        work_done_progress_params: WorkDoneProgressParams::default(),
-    };
-    let result = server_with_open_file.server.execute_command(params).await;
-    assert!(result.is_ok());
+    server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
 
-    // When
-    let params = HoverParams {
-        text_document_position_params: TextDocumentPositionParams {
-            text_document: TextDocumentIdentifier::new(open_file_url),
-            position: Position::new(0, 5), // Position inside "alpine"
-        },
+    let notifications = server_with_open_file
+        .client_recorder
+        .notifications
+        .lock()
+        .await;
+    let (_, params) = notifications
+        .iter()
+        .find(|(method, _)| method == "sysdig/scanResult")
+        .expect("a sysdig/scanResult notification must be sent after the scan");
+    assert_eq!(
+        params,
+        &json!({
+            "version": 1,
+            "uri": open_file_url.as_str(),
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 11}},
+            "pullString": "alpine:latest",
+            "requestedPullString": null,
+            "imageId": "sha256:12345",
+            "digest": "sha256:67890",
+            "vulnerabilities": {"critical": 0, "high": 1, "medium": 0, "low": 0, "negligible": 0},
+            "policyEvaluation": {"result": "passed", "failedPolicies": []},
+            "resultUrl": null,
+            "parseWarnings": [],
+            "scanTime": null,
+            "scanDurationMs": null
+        })
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_list_images_returns_the_images_with_their_last_scan(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    let list_images = || ExecuteCommandParams {
+        command: "sysdig-lsp.list-images".to_string(),
+        arguments: vec![],
         work_done_progress_params: WorkDoneProgressParams::default(),
     };
-    let result = server_with_open_file.server.hover(params).await;
-    assert!(result.is_ok());
-    let hover = result.unwrap().unwrap();
-
-    // Then
-    let expected_markdown = r#"## Sysdig Scan Result
-### Summary
-* **PullString**: alpine:latest
-* **ImageID**: `sha256:12345`
-* **Digest**: `sha256:67890`
-* **BaseOS**: alpine:3.18
-
-| TOTAL VULNS FOUND | CRITICAL |     HIGH      | MEDIUM | LOW | NEGLIGIBLE |
-|-------------------|----------|---------------|--------|-----|------------|
-|         1         |    0     | 1 (1 Fixable) |   0    |  0  |     0      |
+    let images = server_with_open_file
+        .server
+        .execute_command(list_images())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        images,
+        json!([{
+            "uri": open_file_url.as_str(),
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 11}},
+            "image": "alpine",
+            "lastScan": null
+        }])
+    );
 
-### Fixable Packages
-| PACKAGE  | TYPE | VERSION | SUGGESTED FIX | CRITICAL | HIGH | MEDIUM | LOW | NEGLIGIBLE | EXPLOIT |
-|----------|------|---------|---------------|----------|------|--------|-----|------------|---------|
-| package1 |  os  | 1.0.0   | 1.0.1         |    -     |  1   |   -    |  -  |     -      |    -    |
-
-
-### Vulnerability Detail
-
-| VULN CVE      | SEVERITY | PACKAGES | FIXABLE | EXPLOITABLE | ACCEPTED RISK |
-|---------------|----------|----------|---------|-------------|---------------|
-| CVE-2021-1234 | High     | 1        | ✅      | ❌          | ❌            |"#;
-
-    let expected_json = serde_json::json!({
-        "contents": {
-            "kind": "markdown",
-            "value": expected_markdown.to_string()
-        }
-    });
-    assert_eq!(serde_json::to_value(hover).unwrap(), expected_json);
-}
+    server_with_open_file
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
 
-#[rstest]
-#[awt]
-#[tokio::test]
-async fn test_shutdown(#[future] initialized_server: TestSetup) {
-    let result = initialized_server.server.shutdown().await;
-    assert!(result.is_ok());
+    let images = server_with_open_file
+        .server
+        .execute_command(list_images())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(images[0]["image"], "alpine");
+    assert_eq!(images[0]["lastScan"]["pullString"], "alpine:latest");
+    assert_eq!(
+        images[0]["lastScan"]["vulnerabilities"],
+        json!({"critical": 0, "high": 1, "medium": 0, "low": 0, "negligible": 0})
+    );
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_k8s_manifest_code_lens(#[future] initialized_server: TestSetup) {
-    let k8s_url: Url = "file:///deployment.yaml".parse().unwrap();
-    let k8s_content = include_str!("fixtures/k8s-deployment.yaml");
-
-    initialized_server
+async fn test_scanned_images_can_be_pinned_to_their_digest(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    server_with_open_file
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .with(mockall::predicate::eq("alpine"))
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+    server_with_open_file
         .server
-        .did_open(DidOpenTextDocumentParams {
-            text_document: TextDocumentItem::new(
-                k8s_url.clone(),
-                "yaml".to_string(),
-                1,
-                k8s_content.to_string(),
-            ),
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
         })
-        .await;
-
-    let params = tower_lsp::lsp_types::CodeLensParams {
-        text_document: TextDocumentIdentifier::new(k8s_url.clone()),
-        work_done_progress_params: WorkDoneProgressParams::default(),
-        partial_result_params: PartialResultParams::default(),
+        .await
+        .unwrap();
+    let diagnostic = {
+        let diagnostics = server_with_open_file
+            .client_recorder
+            .diagnostics
+            .lock()
+            .await;
+        last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap()[0].clone()
     };
 
-    let result = initialized_server
+    let actions = server_with_open_file
         .server
-        .code_lens(params)
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(open_file_url.clone()),
+            range: Range::new(Position::new(0, 7), Position::new(0, 7)),
+            context: CodeActionContext {
+                diagnostics: vec![diagnostic],
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
         .await
         .unwrap()
         .unwrap();
 
-    let result_json = serde_json::to_value(result).unwrap();
-
-    let expected_json = serde_json::json!([
-        {
-            "command": {
-                "arguments": ["file:///deployment.yaml"],
-                "command": "sysdig-lsp.execute-iac-scan",
-                "title": "Scan IaC file"
-            },
-            "range": {
-                "end": { "character": 0, "line": 0 },
-                "start": { "character": 0, "line": 0 }
-            }
-        },
-        {
-            "command": {
-                "arguments": [
-                    {
-                        "range": {
-                            "end": { "character": 25, "line": 10 },
-                            "start": { "character": 15, "line": 10 }
-                        },
-                        "uri": "file:///deployment.yaml"
-                    },
-                    "nginx:1.19"
-                ],
-                "command": "sysdig-lsp.execute-scan",
-                "title": "Scan base image"
-            },
-            "range": {
-                "end": { "character": 25, "line": 10 },
-                "start": { "character": 15, "line": 10 }
-            }
-        }
-    ]);
-
-    assert_eq!(result_json, expected_json);
-}
-
-use std::path::PathBuf;
-use sysdig_lsp::app::{IacScanError, IacScanScope};
-use sysdig_lsp::domain::iacscanresult::{
-    iac_finding::IacFinding, iac_resource::IacResource, iac_scan_result::IacScanResult,
-    iac_severity::IacSeverity,
-};
-use tower_lsp::lsp_types::MessageType;
-
-fn file_scope(uri: &str, path: &str) -> IacScanScope {
-    IacScanScope::File {
-        uri: uri.parse().unwrap(),
-        path: PathBuf::from(path),
-    }
-}
-
-fn iac_finding_for(source: &str, name: &str) -> IacFinding {
-    IacFinding {
-        name: name.to_string(),
-        severity: IacSeverity::High,
-        resources: vec![IacResource {
-            source: PathBuf::from(source),
-            location: "spec.template.spec.containers[0]".to_string(),
-            resource_type: "Deployment".to_string(),
-            name: "nginx-deployment".to_string(),
-        }],
-    }
-}
-
-fn execute_iac_scan_params(arguments: Vec<serde_json::Value>) -> ExecuteCommandParams {
-    ExecuteCommandParams {
-        command: "sysdig-lsp.execute-iac-scan".to_string(),
-        arguments,
-        work_done_progress_params: WorkDoneProgressParams::default(),
-    }
-}
-
-fn last_published_diagnostics_for<'a>(
-    published: &'a [(String, Vec<tower_lsp::lsp_types::Diagnostic>)],
-    url: &str,
-) -> Option<&'a Vec<tower_lsp::lsp_types::Diagnostic>> {
-    published
+    let actions = serde_json::to_value(actions).unwrap();
+    let pin = actions
+        .as_array()
+        .unwrap()
         .iter()
-        .rev()
-        .find(|(u, _)| u == url)
-        .map(|(_, d)| d)
-}
-
-#[fixture]
-#[awt]
-async fn server_with_open_k8s_manifest(#[future] initialized_server: TestSetup) -> TestSetup {
-    initialized_server
-        .server
-        .did_open(DidOpenTextDocumentParams {
-            text_document: TextDocumentItem::new(
-                "file:///deployment.yaml".parse().unwrap(),
-                "yaml".to_string(),
-                1,
-                include_str!("fixtures/k8s-deployment.yaml").to_string(),
-            ),
-        })
-        .await;
-    initialized_server
+        .find(|action| action["title"] == "Pin to digest")
+        .expect("pin to digest must be offered for the scanned image");
+    assert_eq!(
+        pin["edit"]["changes"][open_file_url.as_str()],
+        json!([{
+            "range": {"start": {"line": 0, "character": 5}, "end": {"line": 0, "character": 11}},
+            "newText": "alpine@sha256:67890"
+        }])
+    );
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_execute_iac_scan_for_single_file(#[future] server_with_open_k8s_manifest: TestSetup) {
-    let scan_result = IacScanResult {
-        findings: vec![iac_finding_for(
-            "/deployment.yaml",
-            "Container runs without memory limits",
-        )],
-    };
-    server_with_open_k8s_manifest
+async fn test_pin_image_digest_resolves_the_digest_from_the_registry(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+) {
+    server_with_open_file
         .component_factory
-        .iac_scanner
+        .registry_client
         .lock()
         .await
-        .expect_scan_iac()
-        .withf(|scope| *scope == file_scope("file:///deployment.yaml", "/deployment.yaml"))
+        .expect_resolve_digest()
+        .with(mockall::predicate::eq("alpine"))
         .times(1)
-        .returning(move |_| Ok(scan_result.clone()));
-
-    server_with_open_k8s_manifest
-        .client_recorder
-        .diagnostics
-        .lock()
-        .await
-        .clear();
+        .returning(|_| Ok("sha256:c5b1261d".to_string()));
 
-    let result = server_with_open_k8s_manifest
+    let result = server_with_open_file
         .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "file:///deployment.yaml"
-        )]))
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.pin-image-digest".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 5,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
         .await;
-    assert!(result.is_ok());
+    assert!(result.is_ok(), "{result:?}");
 
-    let diagnostics = server_with_open_k8s_manifest
+    let applied_edits = server_with_open_file
         .client_recorder
-        .diagnostics
+        .applied_edits
         .lock()
         .await;
-    let diags_for_file = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
-        .expect("no diagnostics published for the scanned file");
-
-    assert_eq!(diags_for_file.len(), 1);
-    let diagnostic = &diags_for_file[0];
-    assert_eq!(
-        diagnostic.message,
-        "Container runs without memory limits: spec.template.spec.containers[0] (Deployment: nginx-deployment)"
-    );
-    assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(applied_edits.len(), 1);
     assert_eq!(
-        diagnostic.range,
-        Range::new(Position::new(0, 0), Position::new(0, 0))
+        applied_edits[0].changes.as_ref().unwrap()[&open_file_url],
+        [TextEdit::new(
+            Range::new(Position::new(0, 5), Position::new(0, 11)),
+            "alpine@sha256:c5b1261d".to_string()
+        )]
     );
-    assert_eq!(diagnostic.source.as_deref(), Some("sysdig-iac"));
 }
 
 #[rstest]
+#[awt]
 #[tokio::test]
-async fn test_execute_iac_scan_for_workspace_publishes_multiple_files() {
-    let setup = TestSetup::new();
-    let params = InitializeParams {
-        initialization_options: Some(serde_json::json!({
-            "sysdig": {
-                "apiUrl": "http://localhost:8080",
-                "api_token": "dummy-token"
-            }
-        })),
-        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
-            uri: "file:///workspace".parse().unwrap(),
-            name: "workspace".to_string(),
-        }]),
-        ..Default::default()
-    };
-    assert!(setup.server.initialize(params).await.is_ok());
-
-    let scan_result = IacScanResult {
-        findings: vec![
-            iac_finding_for("/workspace/a.yaml", "Finding in a"),
-            iac_finding_for("/workspace/subdir/b.yaml", "Finding in b"),
-        ],
-    };
-    setup
+async fn test_recommended_base_image_is_compared_in_hover_and_offered_as_quick_fix(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    server_with_open_file
         .component_factory
-        .iac_scanner
+        .registry_client
         .lock()
         .await
-        .expect_scan_iac()
-        .withf(|scope| *scope == IacScanScope::Directory(PathBuf::from("/workspace")))
-        .times(1)
-        .returning(move |_| Ok(scan_result.clone()));
+        .expect_list_tags()
+        .with(mockall::predicate::eq("alpine"))
+        .returning(|_| {
+            Ok(["latest", "slim"]
+                .map(|name| ImageTag {
+                    name: name.to_string(),
+                    digest: None,
+                })
+                .to_vec())
+        });
+    server_with_open_file
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |image| match image {
+            "alpine" => Ok(scan_result.clone()),
+            _ => Ok(ScanResult::new(
+                ScanType::Docker,
+                image.to_string(),
+                "sha256:slim".to_string(),
+                None,
+                OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+                1000,
+                Architecture::Amd64,
+                HashMap::new(),
+                chrono::Utc::now(),
+                EvaluationResult::Passed,
+            )),
+        });
+    let location = json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url});
+    server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![location.clone(), json!("alpine")],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
 
-    let result = setup
+    let best = server_with_open_file
         .server
-        .execute_command(execute_iac_scan_params(vec![]))
-        .await;
-    assert!(result.is_ok());
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.recommend-base-image".to_string(),
+            arguments: vec![location, json!("alpine")],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(best["image"], "alpine:slim");
 
-    let diagnostics = setup.client_recorder.diagnostics.lock().await;
-    let diags_a = last_published_diagnostics_for(&diagnostics, "file:///workspace/a.yaml")
-        .expect("no diagnostics for a.yaml");
-    let diags_b = last_published_diagnostics_for(&diagnostics, "file:///workspace/subdir/b.yaml")
-        .expect("no diagnostics for subdir/b.yaml");
+    let hover = server_with_open_file
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(open_file_url.clone()),
+                position: Position::new(0, 7),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let tower_lsp::lsp_types::HoverContents::Markup(hover) = hover.contents else {
+        panic!("expected markdown hover");
+    };
+    assert!(hover.value.contains("### Base Image Alternatives"));
+    assert!(hover.value.contains("`alpine:slim` (recommended)"));
 
-    assert_eq!(diags_a.len(), 1);
-    assert!(diags_a[0].message.starts_with("Finding in a"));
-    assert_eq!(diags_b.len(), 1);
-    assert!(diags_b[0].message.starts_with("Finding in b"));
+    let diagnostic = {
+        let diagnostics = server_with_open_file
+            .client_recorder
+            .diagnostics
+            .lock()
+            .await;
+        last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap()[0].clone()
+    };
+    let actions = server_with_open_file
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(open_file_url.clone()),
+            range: Range::new(Position::new(0, 7), Position::new(0, 7)),
+            context: CodeActionContext {
+                diagnostics: vec![diagnostic],
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let actions = serde_json::to_value(actions).unwrap();
+    let switch = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["title"] == "Switch base image to alpine:slim")
+        .expect("the recommended image must be offered as a quick fix");
+    assert_eq!(
+        switch["edit"]["changes"][open_file_url.as_str()],
+        json!([{
+            "range": {"start": {"line": 0, "character": 5}, "end": {"line": 0, "character": 11}},
+            "newText": "alpine:slim"
+        }])
+    );
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_iac_rescan_clears_stale_diagnostics(
-    #[future] server_with_open_k8s_manifest: TestSetup,
+async fn test_hover(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+    scan_result: ScanResult,
 ) {
-    let scan_result = IacScanResult {
-        findings: vec![iac_finding_for("/deployment.yaml", "Stale finding")],
+    // Given
+    server_with_open_file
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .with(mockall::predicate::eq("alpine"))
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    let params = ExecuteCommandParams {
+        command: "sysdig-lsp.execute-scan".to_string(),
+        arguments: vec![
+            json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url.clone()}),
+            json!("alpine"),
+        ],
+        work_done_progress_params: WorkDoneProgressParams::default(),
     };
-    {
-        let mut scanner = server_with_open_k8s_manifest
-            .component_factory
-            .iac_scanner
-            .lock()
-            .await;
-        scanner
-            .expect_scan_iac()
-            .times(1)
-            .returning(move |_| Ok(scan_result.clone()));
-        scanner
-            .expect_scan_iac()
-            .times(1)
-            .returning(|_| Ok(IacScanResult::default()));
-    }
+    let result = server_with_open_file.server.execute_command(params).await;
+    assert!(result.is_ok());
 
-    let first = server_with_open_k8s_manifest
-        .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "file:///deployment.yaml"
-        )]))
-        .await;
-    assert!(first.is_ok());
-    let second = server_with_open_k8s_manifest
-        .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "file:///deployment.yaml"
-        )]))
-        .await;
-    assert!(second.is_ok());
+    // When
+    let params = HoverParams {
+        text_document_position_params: TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier::new(open_file_url),
+            position: Position::new(0, 5), // Position inside "alpine"
+        },
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    };
+    let result = server_with_open_file.server.hover(params).await;
+    assert!(result.is_ok());
+    let hover = result.unwrap().unwrap();
 
-    let diagnostics = server_with_open_k8s_manifest
-        .client_recorder
-        .diagnostics
-        .lock()
-        .await;
-    let last = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
-        .expect("no diagnostics published for the scanned file");
-    assert!(
-        last.is_empty(),
-        "stale IaC diagnostics were not cleared: {last:?}"
-    );
+    // Then
+    let expected_markdown = r#"## Sysdig Scan Result
+### Summary
+* **PullString**: alpine:latest
+* **ImageID**: `sha256:12345`
+* **Digest**: `sha256:67890`
+* **BaseOS**: alpine:3.18
+* **Architecture**: amd64
+
+| TOTAL VULNS FOUND | CRITICAL |     HIGH      | MEDIUM | LOW | NEGLIGIBLE |
+|-------------------|----------|---------------|--------|-----|------------|
+|         1         |    0     | 1 (1 Fixable) |   0    |  0  |     0      |
+
+### Fixable Packages
+| PACKAGE  | TYPE | VERSION | SUGGESTED FIX | CRITICAL | HIGH | MEDIUM | LOW | NEGLIGIBLE | EXPLOIT |
+|----------|------|---------|---------------|----------|------|--------|-----|------------|---------|
+| package1 |  os  | 1.0.0   | 1.0.1 \*      |    -     |  1   |   -    |  -  |     -      |    -    |
+
+\* Computed from the fix versions of the vulnerabilities, the scanner suggested none.
+
+
+### Vulnerability Detail
+
+| VULN CVE      | SEVERITY | CVSS | PACKAGES | FIXABLE | EXPLOITABLE | KEV | ACCEPTED RISK |
+|---------------|----------|------|----------|---------|-------------|-----|---------------|
+| CVE-2021-1234 | High     | -    | 1        | ✅      | ❌          | ❌  | ❌            |"#;
+
+    let expected_json = serde_json::json!({
+        "contents": {
+            "kind": "markdown",
+            "value": expected_markdown.to_string()
+        }
+    });
+    assert_eq!(serde_json::to_value(hover).unwrap(), expected_json);
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_iac_scan_preserves_image_scan_diagnostics(
-    #[future] server_with_open_k8s_manifest: TestSetup,
+async fn test_hover_over_a_cve_shows_its_detail(
+    #[future] initialized_server: TestSetup,
+    open_file_url: Url,
     scan_result: ScanResult,
 ) {
-    // First, an image scan on the same document produces a vulnerability diagnostic
-    server_with_open_k8s_manifest
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                open_file_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\n# Tracking CVE-2021-1234 upstream\n".to_string(),
+            ),
+        })
+        .await;
+    initialized_server
         .component_factory
         .image_scanner
         .lock()
         .await
         .expect_scan_image()
-        .times(1)
         .returning(move |_| Ok(scan_result.clone()));
-
-    let image_scan = server_with_open_k8s_manifest
+    initialized_server
         .server
         .execute_command(ExecuteCommandParams {
             command: "sysdig-lsp.execute-scan".to_string(),
             arguments: vec![
-                json!({"range":{"end":{"character":25,"line":10},"start":{"character":15,"line":10}},"uri":"file:///deployment.yaml"}),
-                json!("nginx:1.19"),
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
             ],
             work_done_progress_params: WorkDoneProgressParams::default(),
         })
-        .await;
-    assert!(image_scan.is_ok());
-
-    // Then an IaC scan on the same file
-    let iac_result = IacScanResult {
-        findings: vec![iac_finding_for("/deployment.yaml", "IaC finding")],
-    };
-    server_with_open_k8s_manifest
-        .component_factory
-        .iac_scanner
-        .lock()
         .await
-        .expect_scan_iac()
-        .times(1)
-        .returning(move |_| Ok(iac_result.clone()));
-
-    let iac_scan = server_with_open_k8s_manifest
-        .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "file:///deployment.yaml"
-        )]))
-        .await;
-    assert!(iac_scan.is_ok());
+        .unwrap();
 
-    let diagnostics = server_with_open_k8s_manifest
-        .client_recorder
-        .diagnostics
-        .lock()
-        .await;
-    let last = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
-        .expect("no diagnostics published");
+    let hover_at = |position: Position| HoverParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(open_file_url.clone()),
+            position,
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    };
+    let hover_markdown = |hover: Option<Hover>| match hover.map(|hover| hover.contents) {
+        Some(HoverContents::Markup(markup)) => markup.value,
+        other => panic!("unexpected hover: {other:?}"),
+    };
 
-    let sources: Vec<_> = last.iter().filter_map(|d| d.source.as_deref()).collect();
+    let detail = hover_markdown(
+        initialized_server
+            .server
+            .hover(hover_at(Position::new(1, 15)))
+            .await
+            .unwrap(),
+    );
     assert!(
-        sources.contains(&"sysdig-vuln") && sources.contains(&"sysdig-iac"),
-        "both scan types must coexist on the same document, got: {sources:?}"
+        detail.starts_with("## Sysdig Vulnerability Detail for `CVE-2021-1234`"),
+        "{detail}"
+    );
+    assert!(detail.contains("- **Fixed in:** 1.0.1"), "{detail}");
+    assert!(detail.contains("| package1 |"), "{detail}");
+
+    let report = hover_markdown(
+        initialized_server
+            .server
+            .hover(hover_at(Position::new(0, 5)))
+            .await
+            .unwrap(),
     );
+    assert!(report.starts_with("## Sysdig Scan Result"), "{report}");
+    let elsewhere = initialized_server
+        .server
+        .hover(hover_at(Position::new(1, 4)))
+        .await
+        .unwrap();
+    assert!(elsewhere.is_none());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_clients_pulling_diagnostics_get_them_on_request(
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    let setup = TestSetup::new();
+    let capabilities: ClientCapabilities = serde_json::from_value(json!({
+        "textDocument": { "diagnostic": { "dynamicRegistration": false } },
+    }))
+    .unwrap();
+    let result = setup
+        .server
+        .initialize(InitializeParams {
+            capabilities,
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "lint": { "rules": { "latest-tag": false } }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(result.capabilities.diagnostic_provider.is_some());
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                open_file_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    assert!(
+        setup.client_recorder.diagnostics.lock().await.is_empty(),
+        "diagnostics must not be published to clients pulling them"
+    );
+    assert!(*setup.client_recorder.diagnostic_refreshes.lock().await > 0);
+
+    let pull = |previous_result_id: Option<String>| {
+        setup.server.diagnostic(DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier::new(open_file_url.clone()),
+            identifier: None,
+            previous_result_id,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+    };
+    let report = serde_json::to_value(pull(None).await.unwrap()).unwrap();
+    assert_eq!(report["kind"], "full");
+    assert_eq!(report["items"][0]["source"], "sysdig-lsp/vuln");
+    let result_id = report["resultId"].as_str().unwrap().to_string();
+    let report = serde_json::to_value(pull(Some(result_id.clone())).await.unwrap()).unwrap();
+    assert_eq!(
+        report,
+        json!({ "kind": "unchanged", "resultId": result_id })
+    );
+
+    let workspace_report = setup
+        .server
+        .workspace_diagnostic(WorkspaceDiagnosticParams {
+            identifier: None,
+            previous_result_ids: vec![PreviousResultId {
+                uri: open_file_url.clone(),
+                value: result_id,
+            }],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap();
+    let workspace_report = serde_json::to_value(workspace_report).unwrap();
+    assert_eq!(workspace_report["items"][0]["uri"], open_file_url.as_str());
+    assert_eq!(workspace_report["items"][0]["kind"], "unchanged");
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_execute_iac_scan_shows_error_when_scanner_fails(
-    #[future] server_with_open_k8s_manifest: TestSetup,
+async fn test_scan_without_policy_evaluation_reports_vulnerabilities_only(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+    mut scan_result: ScanResult,
 ) {
-    server_with_open_k8s_manifest
+    scan_result.mark_policies_not_evaluated();
+    server_with_open_file
         .component_factory
-        .iac_scanner
+        .image_scanner
         .lock()
         .await
-        .expect_scan_iac()
+        .expect_scan_image()
         .times(1)
-        .returning(|_| Err(IacScanError::InternalScannerError("boom".into())));
+        .returning(move |_| Ok(scan_result.clone()));
 
-    let result = server_with_open_k8s_manifest
+    let result = server_with_open_file
         .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "file:///deployment.yaml"
-        )]))
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
         .await;
-    assert!(result.is_err());
+    assert!(result.is_ok());
 
-    let messages = server_with_open_k8s_manifest
+    let diagnostics = server_with_open_file
         .client_recorder
-        .messages
+        .diagnostics
         .lock()
         .await;
+    let (_, last_diagnostics) = diagnostics.last().unwrap();
+    assert_eq!(last_diagnostics.len(), 1, "{last_diagnostics:?}");
+    assert_eq!(
+        last_diagnostics[0].source.as_deref(),
+        Some("sysdig-lsp/vuln")
+    );
     assert!(
-        messages
-            .iter()
-            .any(|(t, m)| *t == MessageType::ERROR && m.contains("boom")),
-        "expected an ERROR message to be shown to the client, got: {messages:?}"
+        last_diagnostics[0]
+            .message
+            .ends_with("(offline scan, policies not evaluated)"),
+        "{}",
+        last_diagnostics[0].message
     );
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_execute_iac_scan_without_workspace_root_fails(
-    #[future] initialized_server: TestSetup,
-) {
-    let result = initialized_server
-        .server
-        .execute_command(execute_iac_scan_params(vec![]))
-        .await;
-
-    let err = result.expect_err("should fail without a workspace root");
-    assert!(err.message.contains("no workspace root"));
+async fn test_shutdown(#[future] initialized_server: TestSetup) {
+    let result = initialized_server.server.shutdown().await;
+    assert!(result.is_ok());
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_iac_lens_is_offered_even_if_image_parsing_fails(
-    #[future] initialized_server: TestSetup,
-) {
-    let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
+async fn test_github_workflow_code_lens(#[future] initialized_server: TestSetup) {
+    let workflow_url: Url = "file:///repo/.github/workflows/ci.yml".parse().unwrap();
+
     initialized_server
         .server
         .did_open(DidOpenTextDocumentParams {
             text_document: TextDocumentItem::new(
-                compose_url.clone(),
+                workflow_url.clone(),
                 "yaml".to_string(),
                 1,
-                "services: [broken".to_string(),
+                include_str!("fixtures/github-workflow.yml").to_string(),
             ),
         })
         .await;
@@ -895,7 +1489,7 @@ async fn test_iac_lens_is_offered_even_if_image_parsing_fails(
     let result = initialized_server
         .server
         .code_lens(tower_lsp::lsp_types::CodeLensParams {
-            text_document: TextDocumentIdentifier::new(compose_url),
+            text_document: TextDocumentIdentifier::new(workflow_url.clone()),
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         })
@@ -903,111 +1497,244 @@ async fn test_iac_lens_is_offered_even_if_image_parsing_fails(
         .unwrap()
         .unwrap();
 
-    // The IaC scan doesn't need parseable image instructions: the CLI scanner
-    // parses the file itself, so the lens survives image parse failures.
-    assert_eq!(result.len(), 1);
-    let lens = serde_json::to_value(&result[0]).unwrap();
-    assert_eq!(lens["command"]["command"], "sysdig-lsp.execute-iac-scan");
+    let scanned: Vec<_> = result
+        .iter()
+        .map(|lens| {
+            let command = lens.command.as_ref().unwrap();
+            assert_eq!(command.command, "sysdig-lsp.execute-scan");
+            (
+                lens.range.start.line,
+                command.arguments.as_ref().unwrap()[1].clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        scanned,
+        [
+            (5, json!("node:20")),
+            (8, json!("postgres:16")),
+            (11, json!("alpine:3.19"))
+        ]
+    );
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_iac_scan_aggregates_multiple_findings_on_the_same_file(
-    #[future] server_with_open_k8s_manifest: TestSetup,
-) {
-    let scan_result = IacScanResult {
-        findings: vec![
-            iac_finding_for("/deployment.yaml", "First finding"),
-            iac_finding_for("/deployment.yaml", "Second finding"),
-        ],
-    };
-    server_with_open_k8s_manifest
-        .component_factory
-        .iac_scanner
-        .lock()
-        .await
-        .expect_scan_iac()
-        .times(1)
-        .returning(move |_| Ok(scan_result.clone()));
+async fn test_k8s_manifest_code_lens(#[future] initialized_server: TestSetup) {
+    let k8s_url: Url = "file:///deployment.yaml".parse().unwrap();
+    let k8s_content = include_str!("fixtures/k8s-deployment.yaml");
 
-    let result = server_with_open_k8s_manifest
+    initialized_server
         .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "file:///deployment.yaml"
-        )]))
-        .await;
-    assert!(result.is_ok());
-
-    let diagnostics = server_with_open_k8s_manifest
-        .client_recorder
-        .diagnostics
-        .lock()
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                k8s_url.clone(),
+                "yaml".to_string(),
+                1,
+                k8s_content.to_string(),
+            ),
+        })
         .await;
-    let last = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
-        .expect("no diagnostics published");
 
-    assert_eq!(last.len(), 2);
-    let messages: Vec<_> = last.iter().map(|d| d.message.as_str()).collect();
-    assert!(messages[0].starts_with("First finding"));
-    assert!(messages[1].starts_with("Second finding"));
-}
-
-#[rstest]
-#[awt]
-#[tokio::test]
-async fn test_execute_iac_scan_maps_invalid_configuration_to_invalid_params(
-    #[future] server_with_open_k8s_manifest: TestSetup,
-) {
-    server_with_open_k8s_manifest
-        .component_factory
-        .iac_scanner
-        .lock()
-        .await
-        .expect_scan_iac()
-        .times(1)
-        .returning(|_| Err(IacScanError::InvalidConfiguration("bad token".to_string())));
+    let params = tower_lsp::lsp_types::CodeLensParams {
+        text_document: TextDocumentIdentifier::new(k8s_url.clone()),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    };
 
-    let result = server_with_open_k8s_manifest
+    let result = initialized_server
         .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "file:///deployment.yaml"
-        )]))
-        .await;
+        .code_lens(params)
+        .await
+        .unwrap()
+        .unwrap();
 
-    let err = result.expect_err("should fail with invalid configuration");
-    assert_eq!(err.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
-}
+    let result_json = serde_json::to_value(result).unwrap();
 
-#[rstest]
-#[awt]
-#[tokio::test]
-async fn test_execute_iac_scan_rejects_non_file_uris(#[future] initialized_server: TestSetup) {
-    let result = initialized_server
-        .server
-        .execute_command(execute_iac_scan_params(vec![json!(
-            "https://example.com/deployment.yaml"
-        )]))
-        .await;
+    let expected_json = serde_json::json!([
+        {
+            "command": {
+                "arguments": ["file:///deployment.yaml"],
+                "command": "sysdig-lsp.execute-iac-scan",
+                "title": "Scan IaC file"
+            },
+            "range": {
+                "end": { "character": 0, "line": 0 },
+                "start": { "character": 0, "line": 0 }
+            }
+        },
+        {
+            "command": {
+                "arguments": [
+                    {
+                        "range": {
+                            "end": { "character": 25, "line": 10 },
+                            "start": { "character": 15, "line": 10 }
+                        },
+                        "uri": "file:///deployment.yaml"
+                    },
+                    "nginx:1.19"
+                ],
+                "command": "sysdig-lsp.execute-scan",
+                "title": "Scan base image"
+            },
+            "range": {
+                "end": { "character": 25, "line": 10 },
+                "start": { "character": 15, "line": 10 }
+            }
+        }
+    ]);
 
-    let err = result.expect_err("should reject non-file URIs");
-    assert!(err.message.contains("only file:// URIs are supported"));
+    assert_eq!(result_json, expected_json);
 }
 
-#[rstest]
-#[tokio::test]
-async fn test_workspace_root_falls_back_to_root_uri() {
+use std::path::PathBuf;
+use sysdig_lsp::app::{IacScanError, IacScanScope};
+use sysdig_lsp::domain::iacscanresult::{
+    iac_finding::IacFinding, iac_resource::IacResource, iac_scan_result::IacScanResult,
+    iac_severity::IacSeverity,
+};
+use tower_lsp::lsp_types::MessageType;
+
+fn file_scope(uri: &str, path: &str) -> IacScanScope {
+    IacScanScope::File {
+        uri: uri.parse().unwrap(),
+        path: PathBuf::from(path),
+    }
+}
+
+fn iac_finding_for(source: &str, name: &str) -> IacFinding {
+    IacFinding {
+        name: name.to_string(),
+        severity: IacSeverity::High,
+        resources: vec![IacResource {
+            source: PathBuf::from(source),
+            location: "spec.template.spec.containers[0]".to_string(),
+            resource_type: "Deployment".to_string(),
+            name: "nginx-deployment".to_string(),
+        }],
+    }
+}
+
+fn execute_iac_scan_params(arguments: Vec<serde_json::Value>) -> ExecuteCommandParams {
+    ExecuteCommandParams {
+        command: "sysdig-lsp.execute-iac-scan".to_string(),
+        arguments,
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    }
+}
+
+fn last_published_diagnostics_for<'a>(
+    published: &'a [(String, Vec<tower_lsp::lsp_types::Diagnostic>)],
+    url: &str,
+) -> Option<&'a Vec<tower_lsp::lsp_types::Diagnostic>> {
+    published
+        .iter()
+        .rev()
+        .find(|(u, _)| u == url)
+        .map(|(_, d)| d)
+}
+
+#[fixture]
+#[awt]
+async fn server_with_open_k8s_manifest(#[future] initialized_server: TestSetup) -> TestSetup {
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                "file:///deployment.yaml".parse().unwrap(),
+                "yaml".to_string(),
+                1,
+                include_str!("fixtures/k8s-deployment.yaml").to_string(),
+            ),
+        })
+        .await;
+    initialized_server
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_execute_iac_scan_for_single_file(#[future] server_with_open_k8s_manifest: TestSetup) {
+    let scan_result = IacScanResult {
+        findings: vec![iac_finding_for(
+            "/deployment.yaml",
+            "Container runs without memory limits",
+        )],
+    };
+    server_with_open_k8s_manifest
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .withf(|scope| *scope == file_scope("file:///deployment.yaml", "/deployment.yaml"))
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    server_with_open_k8s_manifest
+        .client_recorder
+        .diagnostics
+        .lock()
+        .await
+        .clear();
+
+    let result = server_with_open_k8s_manifest
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "file:///deployment.yaml"
+        )]))
+        .await;
+    assert!(result.is_ok());
+
+    let diagnostics = server_with_open_k8s_manifest
+        .client_recorder
+        .diagnostics
+        .lock()
+        .await;
+    let diags_for_file = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
+        .expect("no diagnostics published for the scanned file");
+
+    assert_eq!(diags_for_file.len(), 1);
+    let diagnostic = &diags_for_file[0];
+    assert_eq!(
+        diagnostic.message,
+        "Container runs without memory limits: spec.template.spec.containers[0] (Deployment: nginx-deployment)"
+    );
+    assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(
+        diagnostic.range,
+        Range::new(Position::new(0, 0), Position::new(0, 0))
+    );
+    assert_eq!(diagnostic.source.as_deref(), Some("sysdig-lsp/iac"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_execute_iac_scan_for_workspace_publishes_multiple_files() {
     let setup = TestSetup::new();
-    #[allow(deprecated)]
     let params = InitializeParams {
         initialization_options: Some(serde_json::json!({
-            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            "sysdig": {
+                "apiUrl": "http://localhost:8080",
+                "api_token": "dummy-token"
+            }
         })),
-        root_uri: Some("file:///workspace".parse().unwrap()),
+        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+            uri: "file:///workspace".parse().unwrap(),
+            name: "workspace".to_string(),
+        }]),
         ..Default::default()
     };
     assert!(setup.server.initialize(params).await.is_ok());
 
+    let scan_result = IacScanResult {
+        findings: vec![
+            iac_finding_for("/workspace/a.yaml", "Finding in a"),
+            iac_finding_for("/workspace/subdir/b.yaml", "Finding in b"),
+        ],
+    };
     setup
         .component_factory
         .iac_scanner
@@ -1016,44 +1743,87 @@ async fn test_workspace_root_falls_back_to_root_uri() {
         .expect_scan_iac()
         .withf(|scope| *scope == IacScanScope::Directory(PathBuf::from("/workspace")))
         .times(1)
-        .returning(|_| Ok(IacScanResult::default()));
+        .returning(move |_| Ok(scan_result.clone()));
 
     let result = setup
         .server
         .execute_command(execute_iac_scan_params(vec![]))
         .await;
     assert!(result.is_ok());
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    let diags_a = last_published_diagnostics_for(&diagnostics, "file:///workspace/a.yaml")
+        .expect("no diagnostics for a.yaml");
+    let diags_b = last_published_diagnostics_for(&diagnostics, "file:///workspace/subdir/b.yaml")
+        .expect("no diagnostics for subdir/b.yaml");
+
+    assert_eq!(diags_a.len(), 1);
+    assert!(diags_a[0].message.starts_with("Finding in a"));
+    assert_eq!(diags_b.len(), 1);
+    assert!(diags_b[0].message.starts_with("Finding in b"));
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_image_rescan_preserves_iac_diagnostics(
+async fn test_iac_rescan_clears_stale_diagnostics(
     #[future] server_with_open_k8s_manifest: TestSetup,
-    scan_result: ScanResult,
 ) {
-    // IaC scan first
-    let iac_result = IacScanResult {
-        findings: vec![iac_finding_for("/deployment.yaml", "IaC finding")],
+    let scan_result = IacScanResult {
+        findings: vec![iac_finding_for("/deployment.yaml", "Stale finding")],
     };
-    server_with_open_k8s_manifest
-        .component_factory
-        .iac_scanner
-        .lock()
-        .await
-        .expect_scan_iac()
-        .times(1)
-        .returning(move |_| Ok(iac_result.clone()));
+    {
+        let mut scanner = server_with_open_k8s_manifest
+            .component_factory
+            .iac_scanner
+            .lock()
+            .await;
+        scanner
+            .expect_scan_iac()
+            .times(1)
+            .returning(move |_| Ok(scan_result.clone()));
+        scanner
+            .expect_scan_iac()
+            .times(1)
+            .returning(|_| Ok(IacScanResult::default()));
+    }
 
-    let iac_scan = server_with_open_k8s_manifest
+    let first = server_with_open_k8s_manifest
         .server
         .execute_command(execute_iac_scan_params(vec![json!(
             "file:///deployment.yaml"
         )]))
         .await;
-    assert!(iac_scan.is_ok());
+    assert!(first.is_ok());
+    let second = server_with_open_k8s_manifest
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "file:///deployment.yaml"
+        )]))
+        .await;
+    assert!(second.is_ok());
 
-    // Image scan afterwards must not wipe the IaC findings
+    let diagnostics = server_with_open_k8s_manifest
+        .client_recorder
+        .diagnostics
+        .lock()
+        .await;
+    let last = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
+        .expect("no diagnostics published for the scanned file");
+    assert!(
+        last.is_empty(),
+        "stale IaC diagnostics were not cleared: {last:?}"
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_iac_scan_preserves_image_scan_diagnostics(
+    #[future] server_with_open_k8s_manifest: TestSetup,
+    scan_result: ScanResult,
+) {
+    // First, an image scan on the same document produces a vulnerability diagnostic
     server_with_open_k8s_manifest
         .component_factory
         .image_scanner
@@ -1076,6 +1846,27 @@ async fn test_image_rescan_preserves_iac_diagnostics(
         .await;
     assert!(image_scan.is_ok());
 
+    // Then an IaC scan on the same file
+    let iac_result = IacScanResult {
+        findings: vec![iac_finding_for("/deployment.yaml", "IaC finding")],
+    };
+    server_with_open_k8s_manifest
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .times(1)
+        .returning(move |_| Ok(iac_result.clone()));
+
+    let iac_scan = server_with_open_k8s_manifest
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "file:///deployment.yaml"
+        )]))
+        .await;
+    assert!(iac_scan.is_ok());
+
     let diagnostics = server_with_open_k8s_manifest
         .client_recorder
         .diagnostics
@@ -1086,78 +1877,110 @@ async fn test_image_rescan_preserves_iac_diagnostics(
 
     let sources: Vec<_> = last.iter().filter_map(|d| d.source.as_deref()).collect();
     assert!(
-        sources.contains(&"sysdig-vuln") && sources.contains(&"sysdig-iac"),
-        "image rescan must preserve IaC diagnostics, got: {sources:?}"
+        sources.contains(&"sysdig-lsp/vuln") && sources.contains(&"sysdig-lsp/iac"),
+        "both scan types must coexist on the same document, got: {sources:?}"
     );
 }
 
 #[rstest]
+#[awt]
 #[tokio::test]
-async fn test_workspace_rescan_clears_files_dropped_from_the_report() {
-    let setup = TestSetup::new();
-    let params = InitializeParams {
-        initialization_options: Some(serde_json::json!({
-            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
-        })),
-        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
-            uri: "file:///workspace".parse().unwrap(),
-            name: "workspace".to_string(),
-        }]),
-        ..Default::default()
-    };
-    assert!(setup.server.initialize(params).await.is_ok());
-
-    {
-        let mut scanner = setup.component_factory.iac_scanner.lock().await;
-        let first_result = IacScanResult {
-            findings: vec![
-                iac_finding_for("/workspace/a.yaml", "Finding in a"),
-                iac_finding_for("/workspace/b.yaml", "Finding in b"),
-            ],
-        };
-        scanner
-            .expect_scan_iac()
-            .times(1)
-            .returning(move |_| Ok(first_result.clone()));
-        let second_result = IacScanResult {
-            findings: vec![iac_finding_for("/workspace/b.yaml", "Finding in b")],
-        };
-        scanner
-            .expect_scan_iac()
-            .times(1)
-            .returning(move |_| Ok(second_result.clone()));
-    }
+async fn test_execute_iac_scan_shows_error_when_scanner_fails(
+    #[future] server_with_open_k8s_manifest: TestSetup,
+) {
+    server_with_open_k8s_manifest
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .times(1)
+        .returning(|_| Err(IacScanError::InternalScannerError("boom".into())));
 
-    for _ in 0..2 {
-        let result = setup
-            .server
-            .execute_command(execute_iac_scan_params(vec![]))
-            .await;
-        assert!(result.is_ok());
-    }
+    let result = server_with_open_k8s_manifest
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "file:///deployment.yaml"
+        )]))
+        .await;
+    assert!(result.is_err());
 
-    let diagnostics = setup.client_recorder.diagnostics.lock().await;
-    let last_a = last_published_diagnostics_for(&diagnostics, "file:///workspace/a.yaml")
-        .expect("a.yaml should have received a clearing publish");
+    let messages = server_with_open_k8s_manifest
+        .client_recorder
+        .messages
+        .lock()
+        .await;
     assert!(
-        last_a.is_empty(),
-        "findings for a file dropped from the report must be cleared: {last_a:?}"
+        messages
+            .iter()
+            .any(|(t, m)| *t == MessageType::ERROR && m.contains("boom")),
+        "expected an ERROR message to be shown to the client, got: {messages:?}"
     );
-    let last_b = last_published_diagnostics_for(&diagnostics, "file:///workspace/b.yaml")
-        .expect("no diagnostics for b.yaml");
-    assert_eq!(last_b.len(), 1);
 }
 
 #[rstest]
 #[awt]
 #[tokio::test]
-async fn test_document_edit_preserves_iac_diagnostics_and_clears_vuln_ones(
+async fn test_execute_iac_scan_without_workspace_root_fails(
+    #[future] initialized_server: TestSetup,
+) {
+    let result = initialized_server
+        .server
+        .execute_command(execute_iac_scan_params(vec![]))
+        .await;
+
+    let err = result.expect_err("should fail without a workspace root");
+    assert!(err.message.contains("no workspace root"));
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_iac_lens_is_offered_even_if_image_parsing_fails(
+    #[future] initialized_server: TestSetup,
+) {
+    let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                compose_url.clone(),
+                "yaml".to_string(),
+                1,
+                "services: [broken".to_string(),
+            ),
+        })
+        .await;
+
+    let result = initialized_server
+        .server
+        .code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new(compose_url),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The IaC scan doesn't need parseable image instructions: the CLI scanner
+    // parses the file itself, so the lens survives image parse failures.
+    assert_eq!(result.len(), 1);
+    let lens = serde_json::to_value(&result[0]).unwrap();
+    assert_eq!(lens["command"]["command"], "sysdig-lsp.execute-iac-scan");
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_iac_scan_aggregates_multiple_findings_on_the_same_file(
     #[future] server_with_open_k8s_manifest: TestSetup,
-    scan_result: ScanResult,
 ) {
-    // Produce one IaC and one vulnerability diagnostic on the same document
-    let iac_result = IacScanResult {
-        findings: vec![iac_finding_for("/deployment.yaml", "IaC finding")],
+    let scan_result = IacScanResult {
+        findings: vec![
+            iac_finding_for("/deployment.yaml", "First finding"),
+            iac_finding_for("/deployment.yaml", "Second finding"),
+        ],
     };
     server_with_open_k8s_manifest
         .component_factory
@@ -1166,55 +1989,15 @@ async fn test_document_edit_preserves_iac_diagnostics_and_clears_vuln_ones(
         .await
         .expect_scan_iac()
         .times(1)
-        .returning(move |_| Ok(iac_result.clone()));
-    server_with_open_k8s_manifest
-        .component_factory
-        .image_scanner
-        .lock()
-        .await
-        .expect_scan_image()
-        .times(1)
         .returning(move |_| Ok(scan_result.clone()));
 
-    assert!(
-        server_with_open_k8s_manifest
-            .server
-            .execute_command(execute_iac_scan_params(vec![json!(
-                "file:///deployment.yaml"
-            )]))
-            .await
-            .is_ok()
-    );
-    assert!(
-        server_with_open_k8s_manifest
-            .server
-            .execute_command(ExecuteCommandParams {
-                command: "sysdig-lsp.execute-scan".to_string(),
-                arguments: vec![
-                    json!({"range":{"end":{"character":25,"line":10},"start":{"character":15,"line":10}},"uri":"file:///deployment.yaml"}),
-                    json!("nginx:1.19"),
-                ],
-                work_done_progress_params: WorkDoneProgressParams::default(),
-            })
-            .await
-            .is_ok()
-    );
-
-    // Editing the document goes through the full lifecycle
-    server_with_open_k8s_manifest
+    let result = server_with_open_k8s_manifest
         .server
-        .did_change(DidChangeTextDocumentParams {
-            text_document: VersionedTextDocumentIdentifier::new(
-                "file:///deployment.yaml".parse().unwrap(),
-                2,
-            ),
-            content_changes: vec![tower_lsp::lsp_types::TextDocumentContentChangeEvent {
-                range: None,
-                range_length: None,
-                text: "apiVersion: v1\nkind: Pod\n".to_string(),
-            }],
-        })
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "file:///deployment.yaml"
+        )]))
         .await;
+    assert!(result.is_ok());
 
     let diagnostics = server_with_open_k8s_manifest
         .client_recorder
@@ -1224,376 +2007,5431 @@ async fn test_document_edit_preserves_iac_diagnostics_and_clears_vuln_ones(
     let last = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
         .expect("no diagnostics published");
 
-    let sources: Vec<_> = last.iter().filter_map(|d| d.source.as_deref()).collect();
-    assert!(
-        sources.contains(&"sysdig-iac"),
-        "IaC diagnostics anchor at the top of the file and must survive edits: {sources:?}"
-    );
-    assert!(
-        !sources.contains(&"sysdig-vuln"),
-        "vulnerability diagnostics anchor to lines and must be cleared on edits: {sources:?}"
-    );
+    assert_eq!(last.len(), 2);
+    let messages: Vec<_> = last.iter().map(|d| d.message.as_str()).collect();
+    assert!(messages[0].starts_with("First finding"));
+    assert!(messages[1].starts_with("Second finding"));
 }
 
 #[rstest]
+#[awt]
 #[tokio::test]
-async fn test_workspace_rescan_preserves_findings_outside_the_scanned_root() {
+async fn test_execute_iac_scan_maps_invalid_configuration_to_invalid_params(
+    #[future] server_with_open_k8s_manifest: TestSetup,
+) {
+    server_with_open_k8s_manifest
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .times(1)
+        .returning(|_| Err(IacScanError::InvalidConfiguration("bad token".to_string())));
+
+    let result = server_with_open_k8s_manifest
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "file:///deployment.yaml"
+        )]))
+        .await;
+
+    let err = result.expect_err("should fail with invalid configuration");
+    assert_eq!(err.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_execute_iac_scan_rejects_non_file_uris(#[future] initialized_server: TestSetup) {
+    let result = initialized_server
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "https://example.com/deployment.yaml"
+        )]))
+        .await;
+
+    let err = result.expect_err("should reject non-file URIs");
+    assert!(err.message.contains("only file:// URIs are supported"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_workspace_root_falls_back_to_root_uri() {
     let setup = TestSetup::new();
+    #[allow(deprecated)]
     let params = InitializeParams {
         initialization_options: Some(serde_json::json!({
             "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
         })),
-        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
-            uri: "file:///workspace".parse().unwrap(),
-            name: "workspace".to_string(),
-        }]),
+        root_uri: Some("file:///workspace".parse().unwrap()),
         ..Default::default()
     };
     assert!(setup.server.initialize(params).await.is_ok());
 
-    {
-        let mut scanner = setup.component_factory.iac_scanner.lock().await;
-        // File-scoped scan of a file outside the workspace root. The path shares
-        // the root as a string prefix ("/workspace-other" vs "/workspace") to pin
-        // the trailing-slash boundary of the prefix-scoped clearing.
-        let outside_result = IacScanResult {
-            findings: vec![iac_finding_for(
-                "/workspace-other/x.yaml",
-                "Outside finding",
-            )],
-        };
-        scanner
-            .expect_scan_iac()
-            .withf(|scope| matches!(scope, IacScanScope::File { .. }))
-            .times(1)
-            .returning(move |_| Ok(outside_result.clone()));
-        // Workspace scan afterwards returns nothing
-        scanner
-            .expect_scan_iac()
-            .withf(|scope| matches!(scope, IacScanScope::Directory(_)))
-            .times(1)
-            .returning(|_| Ok(IacScanResult::default()));
-    }
+    setup
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .withf(|scope| *scope == IacScanScope::Directory(PathBuf::from("/workspace")))
+        .times(1)
+        .returning(|_| Ok(IacScanResult::default()));
+
+    let result = setup
+        .server
+        .execute_command(execute_iac_scan_params(vec![]))
+        .await;
+    assert!(result.is_ok());
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_image_rescan_preserves_iac_diagnostics(
+    #[future] server_with_open_k8s_manifest: TestSetup,
+    scan_result: ScanResult,
+) {
+    // IaC scan first
+    let iac_result = IacScanResult {
+        findings: vec![iac_finding_for("/deployment.yaml", "IaC finding")],
+    };
+    server_with_open_k8s_manifest
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .times(1)
+        .returning(move |_| Ok(iac_result.clone()));
+
+    let iac_scan = server_with_open_k8s_manifest
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!(
+            "file:///deployment.yaml"
+        )]))
+        .await;
+    assert!(iac_scan.is_ok());
+
+    // Image scan afterwards must not wipe the IaC findings
+    server_with_open_k8s_manifest
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    let image_scan = server_with_open_k8s_manifest
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":25,"line":10},"start":{"character":15,"line":10}},"uri":"file:///deployment.yaml"}),
+                json!("nginx:1.19"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+    assert!(image_scan.is_ok());
+
+    let diagnostics = server_with_open_k8s_manifest
+        .client_recorder
+        .diagnostics
+        .lock()
+        .await;
+    let last = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
+        .expect("no diagnostics published");
 
+    let sources: Vec<_> = last.iter().filter_map(|d| d.source.as_deref()).collect();
     assert!(
-        setup
-            .server
-            .execute_command(execute_iac_scan_params(vec![json!(
-                "file:///workspace-other/x.yaml"
-            )]))
-            .await
-            .is_ok()
+        sources.contains(&"sysdig-lsp/vuln") && sources.contains(&"sysdig-lsp/iac"),
+        "image rescan must preserve IaC diagnostics, got: {sources:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_definition_goes_from_a_deployed_image_to_the_dockerfile_building_it() {
+    let workspace = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(workspace.path().join("api")).unwrap();
+    std::fs::write(workspace.path().join("api/Dockerfile"), "FROM alpine\n").unwrap();
+    std::fs::write(
+        workspace.path().join("docker-compose.yml"),
+        "services:\n  api:\n    image: acme/api:dev\n    build: ./api\n",
+    )
+    .unwrap();
+    let dockerfile = Location::new(
+        Url::from_file_path(workspace.path().join("api/Dockerfile")).unwrap(),
+        Range::default(),
+    );
+
+    let setup = TestSetup::new();
+    let initialize_result = setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(serde_json::json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+                uri: Url::from_file_path(workspace.path()).unwrap(),
+                name: "workspace".to_string(),
+            }]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(initialize_result.capabilities.definition_provider.is_some());
+
+    let definition_at = |uri: &Url, position: Position| GotoDefinitionParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            position,
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+    };
+    let manifest = Url::from_file_path(workspace.path().join("deploy/deployment.yaml")).unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                manifest.clone(),
+                "yaml".to_string(),
+                1,
+                "apiVersion: v1\nkind: Pod\nspec:\n  containers:\n    - name: api\n      image: acme/api:1.2\n".to_string(),
+            ),
+        })
+        .await;
+
+    let definition = setup
+        .server
+        .goto_definition(definition_at(&manifest, Position::new(5, 16)))
+        .await
+        .unwrap();
+    assert_eq!(
+        definition,
+        Some(GotoDefinitionResponse::Array(vec![dockerfile.clone()]))
+    );
+    let elsewhere = setup
+        .server
+        .goto_definition(definition_at(&manifest, Position::new(4, 10)))
+        .await
+        .unwrap();
+    assert_eq!(elsewhere, None);
+
+    let compose = Url::from_file_path(workspace.path().join("docker-compose.yml")).unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                compose.clone(),
+                "yaml".to_string(),
+                1,
+                "services:\n  api:\n    image: acme/api:dev\n    build: ./api\n".to_string(),
+            ),
+        })
+        .await;
+    let definition = setup
+        .server
+        .goto_definition(definition_at(&compose, Position::new(3, 12)))
+        .await
+        .unwrap();
+    assert_eq!(definition, Some(GotoDefinitionResponse::Scalar(dockerfile)));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_references_to_an_image_span_the_workspace() {
+    let workspace = tempfile::tempdir().unwrap();
+    std::fs::write(
+        workspace.path().join("Dockerfile"),
+        "FROM nginx:1.23
+",
+    )
+    .unwrap();
+    std::fs::write(
+        workspace.path().join("docker-compose.yml"),
+        "services:\n  web:\n    image: docker.io/library/nginx:1.23\n  db:\n    image: postgres\n",
+    )
+    .unwrap();
+    let dockerfile = Url::from_file_path(workspace.path().join("Dockerfile")).unwrap();
+    let compose = Url::from_file_path(workspace.path().join("docker-compose.yml")).unwrap();
+
+    let setup = TestSetup::new();
+    let initialize_result = setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(serde_json::json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+                uri: Url::from_file_path(workspace.path()).unwrap(),
+                name: "workspace".to_string(),
+            }]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(initialize_result.capabilities.references_provider.is_some());
+
+    // The manifest only exists in the editor.
+    let manifest = Url::from_file_path(workspace.path().join("deploy/pod.yaml")).unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                manifest.clone(),
+                "yaml".to_string(),
+                1,
+                "apiVersion: v1\nkind: Pod\nspec:\n  containers:\n    - name: web\n      image: nginx:1.23\n".to_string(),
+            ),
+        })
+        .await;
+
+    let references_at = |position: Position, include_declaration: bool| ReferenceParams {
+        text_document_position: TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(manifest.clone()),
+            position,
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+        partial_result_params: PartialResultParams::default(),
+        context: ReferenceContext {
+            include_declaration,
+        },
+    };
+    let references = setup
+        .server
+        .references(references_at(Position::new(5, 16), false))
+        .await
+        .unwrap();
+    assert_eq!(
+        references,
+        Some(vec![
+            Location::new(
+                dockerfile,
+                Range::new(Position::new(0, 0), Position::new(0, 15))
+            ),
+            Location::new(
+                compose,
+                Range::new(Position::new(2, 11), Position::new(2, 39))
+            ),
+        ])
     );
+
+    let with_declaration = setup
+        .server
+        .references(references_at(Position::new(5, 16), true))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(with_declaration.len(), 3);
     assert!(
-        setup
+        with_declaration
+            .iter()
+            .any(|location| location.uri == manifest)
+    );
+
+    let elsewhere = setup
+        .server
+        .references(references_at(Position::new(4, 10), false))
+        .await
+        .unwrap();
+    assert_eq!(elsewhere, None);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_workspace_rescan_clears_files_dropped_from_the_report() {
+    let setup = TestSetup::new();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+        })),
+        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+            uri: "file:///workspace".parse().unwrap(),
+            name: "workspace".to_string(),
+        }]),
+        ..Default::default()
+    };
+    assert!(setup.server.initialize(params).await.is_ok());
+
+    {
+        let mut scanner = setup.component_factory.iac_scanner.lock().await;
+        let first_result = IacScanResult {
+            findings: vec![
+                iac_finding_for("/workspace/a.yaml", "Finding in a"),
+                iac_finding_for("/workspace/b.yaml", "Finding in b"),
+            ],
+        };
+        scanner
+            .expect_scan_iac()
+            .times(1)
+            .returning(move |_| Ok(first_result.clone()));
+        let second_result = IacScanResult {
+            findings: vec![iac_finding_for("/workspace/b.yaml", "Finding in b")],
+        };
+        scanner
+            .expect_scan_iac()
+            .times(1)
+            .returning(move |_| Ok(second_result.clone()));
+    }
+
+    for _ in 0..2 {
+        let result = setup
             .server
             .execute_command(execute_iac_scan_params(vec![]))
-            .await
-            .is_ok()
-    );
+            .await;
+        assert!(result.is_ok());
+    }
 
     let diagnostics = setup.client_recorder.diagnostics.lock().await;
-    let last = last_published_diagnostics_for(&diagnostics, "file:///workspace-other/x.yaml")
-        .expect("no diagnostics for the outside file");
-    assert_eq!(
-        last.len(),
-        1,
-        "a workspace scan must not clear findings outside its root: {last:?}"
+    let last_a = last_published_diagnostics_for(&diagnostics, "file:///workspace/a.yaml")
+        .expect("a.yaml should have received a clearing publish");
+    assert!(
+        last_a.is_empty(),
+        "findings for a file dropped from the report must be cleared: {last_a:?}"
+    );
+    let last_b = last_published_diagnostics_for(&diagnostics, "file:///workspace/b.yaml")
+        .expect("no diagnostics for b.yaml");
+    assert_eq!(last_b.len(), 1);
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_document_edit_preserves_iac_diagnostics_and_clears_vuln_ones(
+    #[future] server_with_open_k8s_manifest: TestSetup,
+    scan_result: ScanResult,
+) {
+    // Produce one IaC and one vulnerability diagnostic on the same document
+    let iac_result = IacScanResult {
+        findings: vec![iac_finding_for("/deployment.yaml", "IaC finding")],
+    };
+    server_with_open_k8s_manifest
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .times(1)
+        .returning(move |_| Ok(iac_result.clone()));
+    server_with_open_k8s_manifest
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    assert!(
+        server_with_open_k8s_manifest
+            .server
+            .execute_command(execute_iac_scan_params(vec![json!(
+                "file:///deployment.yaml"
+            )]))
+            .await
+            .is_ok()
+    );
+    assert!(
+        server_with_open_k8s_manifest
+            .server
+            .execute_command(ExecuteCommandParams {
+                command: "sysdig-lsp.execute-scan".to_string(),
+                arguments: vec![
+                    json!({"range":{"end":{"character":25,"line":10},"start":{"character":15,"line":10}},"uri":"file:///deployment.yaml"}),
+                    json!("nginx:1.19"),
+                ],
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await
+            .is_ok()
+    );
+
+    // Editing the document goes through the full lifecycle
+    server_with_open_k8s_manifest
+        .server
+        .did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(
+                "file:///deployment.yaml".parse().unwrap(),
+                2,
+            ),
+            content_changes: vec![tower_lsp::lsp_types::TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "apiVersion: v1\nkind: Pod\n".to_string(),
+            }],
+        })
+        .await;
+
+    let diagnostics = server_with_open_k8s_manifest
+        .client_recorder
+        .diagnostics
+        .lock()
+        .await;
+    let last = last_published_diagnostics_for(&diagnostics, "file:///deployment.yaml")
+        .expect("no diagnostics published");
+
+    let sources: Vec<_> = last.iter().filter_map(|d| d.source.as_deref()).collect();
+    assert!(
+        sources.contains(&"sysdig-lsp/iac"),
+        "IaC diagnostics anchor at the top of the file and must survive edits: {sources:?}"
+    );
+    assert!(
+        !sources.contains(&"sysdig-lsp/vuln"),
+        "vulnerability diagnostics anchor to lines and must be cleared on edits: {sources:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_workspace_rescan_preserves_findings_outside_the_scanned_root() {
+    let setup = TestSetup::new();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+        })),
+        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+            uri: "file:///workspace".parse().unwrap(),
+            name: "workspace".to_string(),
+        }]),
+        ..Default::default()
+    };
+    assert!(setup.server.initialize(params).await.is_ok());
+
+    {
+        let mut scanner = setup.component_factory.iac_scanner.lock().await;
+        // File-scoped scan of a file outside the workspace root. The path shares
+        // the root as a string prefix ("/workspace-other" vs "/workspace") to pin
+        // the trailing-slash boundary of the prefix-scoped clearing.
+        let outside_result = IacScanResult {
+            findings: vec![iac_finding_for(
+                "/workspace-other/x.yaml",
+                "Outside finding",
+            )],
+        };
+        scanner
+            .expect_scan_iac()
+            .withf(|scope| matches!(scope, IacScanScope::File { .. }))
+            .times(1)
+            .returning(move |_| Ok(outside_result.clone()));
+        // Workspace scan afterwards returns nothing
+        scanner
+            .expect_scan_iac()
+            .withf(|scope| matches!(scope, IacScanScope::Directory(_)))
+            .times(1)
+            .returning(|_| Ok(IacScanResult::default()));
+    }
+
+    assert!(
+        setup
+            .server
+            .execute_command(execute_iac_scan_params(vec![json!(
+                "file:///workspace-other/x.yaml"
+            )]))
+            .await
+            .is_ok()
+    );
+    assert!(
+        setup
+            .server
+            .execute_command(execute_iac_scan_params(vec![]))
+            .await
+            .is_ok()
+    );
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, "file:///workspace-other/x.yaml")
+        .expect("no diagnostics for the outside file");
+    assert_eq!(
+        last.len(),
+        1,
+        "a workspace scan must not clear findings outside its root: {last:?}"
+    );
+}
+
+/// IacScanner double that blocks inside scan_iac until released, to observe the
+/// server while a command is in flight.
+#[derive(Clone)]
+struct BlockingIacScanner {
+    started: std::sync::Arc<tokio::sync::Notify>,
+    release: std::sync::Arc<tokio::sync::Notify>,
+}
+
+#[async_trait::async_trait]
+impl sysdig_lsp::app::IacScanner for BlockingIacScanner {
+    async fn scan_iac(
+        &self,
+        _scope: &IacScanScope,
+    ) -> Result<IacScanResult, sysdig_lsp::app::IacScanError> {
+        self.started.notify_one();
+        self.release.notified().await;
+        Ok(IacScanResult::default())
+    }
+}
+
+#[derive(Clone)]
+struct BlockingComponentFactory {
+    iac_scanner: BlockingIacScanner,
+}
+
+impl sysdig_lsp::app::component_factory::ComponentFactory for BlockingComponentFactory {
+    fn create_components(
+        &self,
+        _config: sysdig_lsp::app::component_factory::Config,
+    ) -> Result<
+        sysdig_lsp::app::component_factory::Components,
+        sysdig_lsp::app::component_factory::ComponentFactoryError,
+    > {
+        Ok(sysdig_lsp::app::component_factory::Components {
+            scanner: Box::new(common::MockImageScannerWrapper(std::sync::Arc::new(
+                tokio::sync::Mutex::new(common::MockImageScanner::new()),
+            ))),
+            scan_scheduler: sysdig_lsp::app::ScanScheduler::new(1),
+            builder: Box::new(common::MockImageBuilderWrapper(std::sync::Arc::new(
+                tokio::sync::Mutex::new(common::MockImageBuilder::new()),
+            ))),
+            iac_scanner: Box::new(self.iac_scanner.clone()),
+            filesystem_scanner: Box::new(common::MockFilesystemScannerWrapper(
+                std::sync::Arc::new(tokio::sync::Mutex::new(common::MockFilesystemScanner::new())),
+            )),
+            scan_result_store: None,
+            scopes: vec![],
+            credentials_checker: None,
+            risk_acceptance_client: None,
+            registry_client: None,
+        })
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_commands_run_without_holding_the_server_lock() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let started = Arc::new(tokio::sync::Notify::new());
+    let release = Arc::new(tokio::sync::Notify::new());
+    let recorder = common::TestClientRecorder::new();
+    let server = Arc::new(sysdig_lsp::app::LSPServer::new(
+        recorder.clone(),
+        BlockingComponentFactory {
+            iac_scanner: BlockingIacScanner {
+                started: started.clone(),
+                release: release.clone(),
+            },
+        },
+    ));
+
+    let init = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+        })),
+        ..Default::default()
+    };
+    assert!(server.initialize(init).await.is_ok());
+    server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                "file:///deployment.yaml".parse().unwrap(),
+                "yaml".to_string(),
+                1,
+                include_str!("fixtures/k8s-deployment.yaml").to_string(),
+            ),
+        })
+        .await;
+
+    let command_server = server.clone();
+    let command = tokio::spawn(async move {
+        command_server
+            .execute_command(execute_iac_scan_params(vec![json!(
+                "file:///deployment.yaml"
+            )]))
+            .await
+    });
+    started.notified().await;
+
+    // While the scan is in flight, a write-lock operation and a read operation
+    // must both complete: the command must not hold the (FIFO-fair) server lock.
+    tokio::time::timeout(
+        Duration::from_secs(1),
+        server.did_change_configuration(DidChangeConfigurationParams {
+            settings: serde_json::json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            }),
+        }),
+    )
+    .await
+    .expect("did_change_configuration deadlocked behind a running command");
+
+    tokio::time::timeout(
+        Duration::from_secs(1),
+        server.code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new("file:///deployment.yaml".parse().unwrap()),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        }),
+    )
+    .await
+    .expect("code_lens deadlocked behind a running command")
+    .expect("code_lens failed");
+
+    release.notify_one();
+    let result = command.await.expect("command task panicked");
+    assert!(result.is_ok());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_shutdown_aborts_the_commands_outliving_the_grace_period() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let started = Arc::new(tokio::sync::Notify::new());
+    let server = Arc::new(sysdig_lsp::app::LSPServer::new(
+        common::TestClientRecorder::new(),
+        BlockingComponentFactory {
+            iac_scanner: BlockingIacScanner {
+                started: started.clone(),
+                release: Arc::new(tokio::sync::Notify::new()),
+            },
+        },
+    ));
+
+    let init = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "sysdig": {
+                "apiUrl": "http://localhost:8080",
+                "api_token": "dummy-token",
+                "scan": { "shutdown_grace_seconds": 0 }
+            }
+        })),
+        ..Default::default()
+    };
+    assert!(server.initialize(init).await.is_ok());
+
+    let command_server = server.clone();
+    let command = tokio::spawn(async move {
+        command_server
+            .execute_command(execute_iac_scan_params(vec![json!(
+                "file:///deployment.yaml"
+            )]))
+            .await
+    });
+    started.notified().await;
+
+    tokio::time::timeout(Duration::from_secs(1), server.shutdown())
+        .await
+        .expect("shutdown waited for a command that is never released")
+        .unwrap();
+
+    let error = command.await.unwrap().unwrap_err();
+    assert_eq!(error.code, tower_lsp::jsonrpc::ErrorCode::RequestCancelled);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_workspace_scan_never_publishes_findings_escaping_the_root() {
+    let setup = TestSetup::new();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+        })),
+        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+            uri: "file:///workspace".parse().unwrap(),
+            name: "workspace".to_string(),
+        }]),
+        ..Default::default()
+    };
+    assert!(setup.server.initialize(params).await.is_ok());
+
+    // A finding whose source escapes the scanned root (e.g. via `..`) must be
+    // dropped: inserting it outside the cleared scope would accumulate duplicates
+    // on every rescan.
+    let scan_result = IacScanResult {
+        findings: vec![
+            iac_finding_for("/outside/x.yaml", "Escaping finding"),
+            iac_finding_for("/workspace/ok.yaml", "In-root finding"),
+        ],
+    };
+    setup
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .times(2)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    for _ in 0..2 {
+        let result = setup
+            .server
+            .execute_command(execute_iac_scan_params(vec![]))
+            .await;
+        assert!(result.is_ok());
+    }
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    assert!(
+        diagnostics
+            .iter()
+            .all(|(url, _)| url != "file:///outside/x.yaml"),
+        "findings escaping the scanned root must never be published"
+    );
+    let in_root = last_published_diagnostics_for(&diagnostics, "file:///workspace/ok.yaml")
+        .expect("no diagnostics for the in-root file");
+    assert_eq!(in_root.len(), 1, "rescans must not accumulate duplicates");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_execute_command_on_uninitialized_server_surfaces_the_error() {
+    let setup = TestSetup::new();
+
+    let result = setup
+        .server
+        .execute_command(execute_iac_scan_params(vec![json!("file:///a.yaml")]))
+        .await;
+
+    let err = result.expect_err("commands must fail before initialization");
+    assert!(err.message.contains("Sysdig LSP not configured"));
+
+    let messages = setup.client_recorder.messages.lock().await;
+    assert!(
+        messages
+            .iter()
+            .any(|(t, m)| *t == MessageType::ERROR && m.contains("Sysdig LSP not configured")),
+        "the error must be surfaced to the user via showMessage: {messages:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_workspace_scan_drops_findings_with_relative_sources() {
+    let setup = TestSetup::new();
+    let params = InitializeParams {
+        initialization_options: Some(serde_json::json!({
+            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+        })),
+        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+            uri: "file:///workspace".parse().unwrap(),
+            name: "workspace".to_string(),
+        }]),
+        ..Default::default()
+    };
+    assert!(setup.server.initialize(params).await.is_ok());
+
+    // A relative source cannot be turned into a file URI: the finding is dropped
+    // (with a warning) instead of being published under a broken URI.
+    let scan_result = IacScanResult {
+        findings: vec![iac_finding_for("relative.yaml", "Relative finding")],
+    };
+    setup
+        .component_factory
+        .iac_scanner
+        .lock()
+        .await
+        .expect_scan_iac()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    let result = setup
+        .server
+        .execute_command(execute_iac_scan_params(vec![]))
+        .await;
+    assert!(result.is_ok());
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    assert!(
+        diagnostics
+            .iter()
+            .all(|(_, diags)| diags.iter().all(|d| !d.message.contains("Relative"))),
+        "findings with relative sources must not be published: {diagnostics:?}"
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_compose_code_lens(#[future] initialized_server: TestSetup) {
+    let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                compose_url.clone(),
+                "yaml".to_string(),
+                1,
+                include_str!("fixtures/docker-compose.yml").to_string(),
+            ),
+        })
+        .await;
+
+    let result = initialized_server
+        .server
+        .code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new(compose_url),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    let lenses = serde_json::to_value(result).unwrap();
+    let lenses = lenses.as_array().unwrap();
+
+    // First lens: whole-file IaC scan
+    assert_eq!(
+        lenses[0]["command"]["command"],
+        "sysdig-lsp.execute-iac-scan"
+    );
+    assert_eq!(lenses[0]["command"]["title"], "Scan IaC file");
+    assert_eq!(
+        lenses[0]["command"]["arguments"],
+        json!(["file:///docker-compose.yml"])
+    );
+
+    // Then a lens scanning every image of the file at once
+    assert_eq!(
+        lenses[1]["command"]["command"],
+        "sysdig-lsp.scan-all-images"
+    );
+    assert_eq!(lenses[1]["command"]["title"], "Scan all images in file");
+    assert_eq!(
+        lenses[1]["command"]["arguments"],
+        json!(["file:///docker-compose.yml"])
+    );
+
+    // Then one image scan lens per compose image
+    let images: Vec<_> = lenses[2..]
+        .iter()
+        .map(|l| {
+            assert_eq!(l["command"]["command"], "sysdig-lsp.execute-scan");
+            l["command"]["arguments"][1].as_str().unwrap().to_owned()
+        })
+        .collect();
+    assert_eq!(images, vec!["nginx:latest", "postgres:13"]);
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_scan_all_images_publishes_a_diagnostic_per_image(
+    #[future] initialized_server: TestSetup,
+    scan_result: ScanResult,
+) {
+    let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                compose_url.clone(),
+                "yaml".to_string(),
+                1,
+                include_str!("fixtures/docker-compose.yml").to_string(),
+            ),
+        })
+        .await;
+    for image in ["nginx:latest", "postgres:13"] {
+        let scan_result = scan_result.clone();
+        initialized_server
+            .component_factory
+            .image_scanner
+            .lock()
+            .await
+            .expect_scan_image()
+            .with(mockall::predicate::eq(image))
+            .times(1)
+            .returning(move |_| Ok(scan_result.clone()));
+    }
+
+    let result = initialized_server
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.scan-all-images".to_string(),
+            arguments: vec![json!(compose_url.clone())],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+    assert!(result.is_ok());
+
+    let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, compose_url.as_str())
+        .expect("no diagnostics published for the compose file");
+    let mut lines: Vec<_> = last.iter().map(|d| d.range.start.line).collect();
+    lines.sort();
+    assert_eq!(lines, vec![2, 4]);
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_semantic_tokens_highlight_the_images_with_high_vulnerabilities(
+    #[future] initialized_server: TestSetup,
+    scan_result: ScanResult,
+) {
+    let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                compose_url.clone(),
+                "yaml".to_string(),
+                1,
+                include_str!("fixtures/docker-compose.yml").to_string(),
+            ),
+        })
+        .await;
+    let clean_scan_result = ScanResult::new(
+        ScanType::Docker,
+        "postgres:13".to_string(),
+        "sha256:postgres".to_string(),
+        None,
+        OperatingSystem::new(Family::Linux, "debian:12".to_string()),
+        123456,
+        Architecture::Amd64,
+        HashMap::new(),
+        chrono::Utc::now(),
+        EvaluationResult::Passed,
+    );
+    for (image, result) in [
+        ("nginx:latest", scan_result),
+        ("postgres:13", clean_scan_result),
+    ] {
+        initialized_server
+            .component_factory
+            .image_scanner
+            .lock()
+            .await
+            .expect_scan_image()
+            .with(mockall::predicate::eq(image))
+            .returning(move |_| Ok(result.clone()));
+    }
+    let semantic_tokens = async || {
+        let response = initialized_server
+            .server
+            .semantic_tokens_full(SemanticTokensParams {
+                text_document: TextDocumentIdentifier::new(compose_url.clone()),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap();
+        let Some(SemanticTokensResult::Tokens(tokens)) = response else {
+            panic!("expected semantic tokens, got {response:?}");
+        };
+        tokens.data
+    };
+    assert!(semantic_tokens().await.is_empty());
+
+    initialized_server
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.scan-all-images".to_string(),
+            arguments: vec![json!(compose_url.clone())],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    // Only nginx has vulnerabilities, a High one.
+    let tokens = semantic_tokens().await;
+    assert_eq!(tokens.len(), 1);
+    assert_eq!((tokens[0].delta_line, tokens[0].length), (2, 12));
+    assert_eq!(tokens[0].token_modifiers_bitset, 0b10);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_only_reports_vulnerabilities_missing_from_the_base_image(
+    scan_result: ScanResult,
+) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "diagnostics": { "onlyNewVulns": true }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM golang:1.22 AS build\nRUN go build\n\nFROM build AS app\nCOPY . .\n"
+                    .to_string(),
+            ),
+        })
+        .await;
+
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .times(1)
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    // The base image already has every vulnerability of the built one.
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .with(mockall::predicate::in_iter([
+            "sysdig-lsp-built",
+            "golang:1.22",
+        ]))
+        .times(2)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    let result = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":21,"line":3},"start":{"character": 0,"line":3}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+    assert!(result.is_ok());
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str())
+        .expect("no diagnostics published for the Dockerfile");
+    let messages: Vec<_> = last.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        ["No vulnerabilities found on top of golang:1.22."]
+    );
+    drop(diagnostics);
+
+    let hover = setup
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(dockerfile_url),
+                position: Position::new(3, 5),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let tower_lsp::lsp_types::HoverContents::Markup(content) = hover.contents else {
+        panic!("expected markdown hover contents");
+    };
+    assert!(content.value.contains(
+        "Compared with the base image `golang:1.22`:\n\n### Changes\n* **Vulnerabilities**: 0 new, 0 fixed, 1 unchanged"
+    ));
+}
+
+#[rstest]
+#[case::enabled(true)]
+#[case::disabled(false)]
+#[tokio::test]
+async fn test_build_and_scan_report_includes_layer_mapping_when_enabled(
+    #[case] layer_mapping: bool,
+    scan_result: ScanResult,
+) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "report": { "layerMapping": layer_mapping }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nCOPY . .\n".to_string(),
+            ),
+        })
+        .await;
+
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .times(1)
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .with(mockall::predicate::eq("sysdig-lsp-built"))
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    let result = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+    assert!(result.is_ok());
+
+    let hover = setup
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(dockerfile_url),
+                position: Position::new(0, 5),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let tower_lsp::lsp_types::HoverContents::Markup(content) = hover.contents else {
+        panic!("expected markdown hover contents");
+    };
+
+    assert_eq!(content.value.contains("### Layer Mapping"), layer_mapping);
+    if layer_mapping {
+        assert!(
+            content
+                .value
+                .contains("|   0   | sha256:layer1 | COPY . . |        2         |"),
+            "layer should be mapped to the COPY instruction on line 2: {}",
+            content.value
+        );
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_of_a_stage_maps_the_layers_to_that_stage(scan_result: ScanResult) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "report": { "layerMapping": true }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine AS build\nCOPY . .\n\nFROM build\nRUN make\n".to_string(),
+            ),
+        })
+        .await;
+
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .withf(|_, options, _| options.target.as_deref() == Some("build"))
+        .times(1)
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":20,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+                json!("build"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let hover = setup
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(dockerfile_url),
+                position: Position::new(0, 5),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let tower_lsp::lsp_types::HoverContents::Markup(content) = hover.contents else {
+        panic!("expected markdown hover contents");
+    };
+    assert!(
+        content
+            .value
+            .contains("|   0   | sha256:layer1 | COPY . . |        2         |"),
+        "layer should be mapped to the COPY instruction of the build stage: {}",
+        content.value
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_flags_large_layers_and_reports_the_largest(
+    mut scan_result: ScanResult,
+) {
+    scan_result.add_layer(
+        "sha256:layer2".to_string(),
+        1,
+        Some(150_000_000),
+        "RUN make".to_string(),
+    );
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine:3.19\nCOPY . .\nRUN make\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":16,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    {
+        let published = setup.client_recorder.diagnostics.lock().await;
+        let large_layers: Vec<_> =
+            last_published_diagnostics_for(&published, dockerfile_url.as_str())
+                .unwrap()
+                .iter()
+                .filter(|d| {
+                    d.code
+                        == Some(tower_lsp::lsp_types::NumberOrString::String(
+                            "large-layer".to_string(),
+                        ))
+                })
+                .cloned()
+                .collect();
+        assert_eq!(large_layers.len(), 1, "{large_layers:?}");
+        assert_eq!(
+            large_layers[0].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(large_layers[0].range.start.line, 2);
+        assert_eq!(
+            large_layers[0].message,
+            "Layer size: 150.0 MB, above the 100 MB threshold"
+        );
+    }
+
+    let hover = |position| {
+        setup.server.hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+    };
+    let markdown = |hover: Option<tower_lsp::lsp_types::Hover>| match hover.unwrap().contents {
+        tower_lsp::lsp_types::HoverContents::Markup(content) => content.value,
+        contents => panic!("expected markdown hover contents, got {contents:?}"),
+    };
+
+    let report = markdown(hover(Position::new(0, 5)).await.unwrap());
+    assert!(report.contains("### Largest Layers"), "{report}");
+    assert!(
+        report.contains("|   1   | 150.0 MB |   100.0%   |        3         | RUN make |"),
+        "{report}"
+    );
+    let layer_report = markdown(hover(Position::new(2, 2)).await.unwrap());
+    assert!(
+        layer_report.contains("Layer size: **150.0 MB**"),
+        "{layer_report}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_sorts_the_hints_by_severity_then_cvss(mut scan_result: ScanResult) {
+    let package = scan_result
+        .packages()
+        .into_iter()
+        .find(|p| p.name() == "package1")
+        .unwrap();
+    for (cve, severity, cvss_score) in [
+        ("CVE-2023-0001", Severity::Critical, Some(9.1)),
+        ("CVE-2023-0002", Severity::Critical, Some(9.8)),
+        ("CVE-2023-0003", Severity::Critical, None),
+    ] {
+        let cvss = cvss_score.map(|score| Cvss::new(score, String::new(), "3.1".to_string()));
+        let vulnerability = scan_result.add_vulnerability(
+            cve.to_string(),
+            severity,
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            None,
+            false,
+            None,
+            cvss,
+            None,
+        );
+        package.add_vulnerability_found(vulnerability);
+    }
+
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "diagnostics": { "maxVulnsPerLayer": 2 }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nCOPY . .\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let published = setup.client_recorder.diagnostics.lock().await;
+    let messages: Vec<_> = last_published_diagnostics_for(&published, dockerfile_url.as_str())
+        .unwrap()
+        .iter()
+        .filter(|d| d.range.start.line == 1 && d.severity == Some(DiagnosticSeverity::HINT))
+        .map(|d| d.message.clone())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "Vulnerability: CVE-2023-0002 (Critical, CVSS 9.8) https://nvd.nist.gov/vuln/detail/CVE-2023-0002",
+            "Vulnerability: CVE-2023-0001 (Critical, CVSS 9.1) https://nvd.nist.gov/vuln/detail/CVE-2023-0001",
+            "2 more vulnerabilities in this layer, use \"Show all layer findings\" to list them",
+        ]
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_escalates_known_exploited_vulnerabilities(
+    mut scan_result: ScanResult,
+) {
+    let package = scan_result
+        .packages()
+        .into_iter()
+        .find(|p| p.name() == "package1")
+        .unwrap();
+    let vulnerability = scan_result.add_vulnerability(
+        "CVE-2023-0001".to_string(),
+        Severity::Low,
+        chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+        None,
+        true,
+        None,
+        None,
+        Some(Exploit::new(None, vec![], Some(CisaKev::new(None, None)))),
+    );
+    package.add_vulnerability_found(vulnerability);
+
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "report": { "escalateKnownExploited": true }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nCOPY . .\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let published = setup.client_recorder.diagnostics.lock().await;
+    let diagnostics: Vec<_> = last_published_diagnostics_for(&published, dockerfile_url.as_str())
+        .unwrap()
+        .iter()
+        .filter(|d| d.range.start.line == 1)
+        .map(|d| (d.severity, d.message.clone()))
+        .collect();
+    assert_eq!(
+        diagnostics,
+        [
+            (
+                Some(DiagnosticSeverity::ERROR),
+                "Vulnerabilities found in layer: 0 Critical, 1 High, 0 Medium, 1 Low, 0 Negligible"
+                    .to_string()
+            ),
+            (
+                Some(DiagnosticSeverity::HINT),
+                "Vulnerability: CVE-2021-1234 (High) https://nvd.nist.gov/vuln/detail/CVE-2021-1234"
+                    .to_string()
+            ),
+            (
+                Some(DiagnosticSeverity::ERROR),
+                "Vulnerability: CVE-2023-0001 (Low, known exploited) https://nvd.nist.gov/vuln/detail/CVE-2023-0001"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_caps_the_hints_per_layer_and_lists_the_rest_on_demand(
+    mut scan_result: ScanResult,
+) {
+    let package = scan_result
+        .packages()
+        .into_iter()
+        .find(|p| p.name() == "package1")
+        .unwrap();
+    for (cve, severity) in [
+        ("CVE-2023-0001", Severity::Critical),
+        ("CVE-2023-0002", Severity::Low),
+    ] {
+        let vulnerability = scan_result.add_vulnerability(
+            cve.to_string(),
+            severity,
+            chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            None,
+            false,
+            None,
+            None,
+            None,
+        );
+        package.add_vulnerability_found(vulnerability);
+    }
+
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "diagnostics": { "maxHintsPerLayer": 1 }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nCOPY . .\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let layer_hints: Vec<_> = {
+        let published = setup.client_recorder.diagnostics.lock().await;
+        last_published_diagnostics_for(&published, dockerfile_url.as_str())
+            .unwrap()
+            .iter()
+            .filter(|d| d.range.start.line == 1 && d.severity == Some(DiagnosticSeverity::HINT))
+            .cloned()
+            .collect()
+    };
+    assert_eq!(layer_hints.len(), 2, "{layer_hints:?}");
+    assert!(layer_hints[0].message.contains("CVE-2023-0001"));
+    assert!(
+        layer_hints[1].message.starts_with("2 more vulnerabilities"),
+        "{layer_hints:?}"
+    );
+
+    let actions = setup
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+            range: layer_hints[1].range,
+            context: CodeActionContext {
+                diagnostics: vec![layer_hints[1].clone()],
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let show_all = actions
+        .into_iter()
+        .find_map(|action| match action {
+            tower_lsp::lsp_types::CodeActionOrCommand::Command(command)
+                if command.command == "sysdig-lsp.show-all-layer-findings" =>
+            {
+                Some(command)
+            }
+            _ => None,
+        })
+        .expect("the omitted hints must offer to list every finding");
+
+    let report = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: show_all.command,
+            arguments: show_all.arguments.unwrap(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let report = report.as_str().unwrap();
+    assert!(report.starts_with("# Layer 0: `COPY . .`"), "{report}");
+    for cve in ["CVE-2021-1234", "CVE-2023-0001", "CVE-2023-0002"] {
+        assert!(report.contains(cve), "{report}");
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_streams_the_build_log_to_the_client(scan_result: ScanResult) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token"
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .times(1)
+        .returning(|_, _, build_log| {
+            build_log
+                .send("Step 1/1 : FROM alpine".to_string())
+                .unwrap();
+            build_log
+                .send("Successfully built 1234".to_string())
+                .unwrap();
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                size: Some(12_300_000),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let log_messages = setup.client_recorder.log_messages.lock().await;
+    assert_eq!(
+        *log_messages,
+        vec![
+            (
+                MessageType::LOG,
+                "[build] Step 1/1 : FROM alpine".to_string()
+            ),
+            (
+                MessageType::LOG,
+                "[build] Successfully built 1234".to_string()
+            ),
+        ]
+    );
+    let messages = setup.client_recorder.messages.lock().await;
+    assert!(
+        messages.contains(&(
+            MessageType::INFO,
+            "Temporal image built 'sysdig-lsp-built' (12.3 MB), starting scan...".to_string()
+        )),
+        "{messages:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_build_and_scan_passes_the_build_args_and_redacts_the_secret_ones(
+    scan_result: ScanResult,
+) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "build": { "args": { "NODE_VERSION": "18", "NPM_TOKEN": "npm-secret" } }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "# sysdig-lsp: build-arg NODE_VERSION=20\nFROM node:${NODE_VERSION}\n".to_string(),
+            ),
+        })
+        .await;
+
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .withf(|_, options, _| {
+            options.build_args
+                == HashMap::from([
+                    ("NODE_VERSION".to_string(), "20".to_string()),
+                    ("NPM_TOKEN".to_string(), "npm-secret".to_string()),
+                ])
+        })
+        .times(1)
+        .returning(|_, _, build_log| {
+            build_log
+                .send("RUN npm config set //registry.npmjs.org/:_authToken=npm-secret".to_string())
+                .unwrap();
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":25,"line":1},"start":{"character": 0,"line":1}},"uri":dockerfile_url}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let log_messages = setup.client_recorder.log_messages.lock().await;
+    assert_eq!(
+        *log_messages,
+        vec![(
+            MessageType::LOG,
+            "[build] RUN npm config set //registry.npmjs.org/:_authToken=[redacted]".to_string()
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_build_and_scan_removes_the_built_image_even_if_the_scan_fails() {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token"
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+
+    {
+        let mut image_builder = setup.component_factory.image_builder.lock().await;
+        image_builder.checkpoint();
+        image_builder
+            .expect_build_image()
+            .times(1)
+            .returning(|_, _, _| {
+                Ok(sysdig_lsp::app::ImageBuildResult {
+                    image_id: "sha256:built".to_string(),
+                    image_name: "sysdig-lsp-built".to_string(),
+                    ..Default::default()
+                })
+            });
+        image_builder
+            .expect_remove_image()
+            .with(mockall::predicate::eq("sysdig-lsp-built"))
+            .times(1)
+            .returning(|_| Ok(()));
+    }
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(|_| {
+            Err(sysdig_lsp::app::ImageScanError::NetworkError(
+                "connection reset".to_string(),
+            ))
+        });
+
+    let result = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+
+    assert!(result.is_err());
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .checkpoint();
+}
+
+#[tokio::test]
+async fn test_cleanup_temp_images_returns_the_removed_images() {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token"
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_remove_temporary_images()
+        .times(1)
+        .returning(|| Ok(vec!["sysdig-lsp-image-build-42:latest".to_string()]));
+
+    let result = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.cleanup-temp-images".to_string(),
+            arguments: vec![],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(result, Some(json!(["sysdig-lsp-image-build-42:latest"])));
+    let messages = setup.client_recorder.messages.lock().await;
+    assert!(
+        messages
+            .iter()
+            .any(|(_, message)| message == "Removed 1 temporary images."),
+        "{messages:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_hover_over_a_fixable_package_shows_its_card(scan_result: ScanResult) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token"
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nRUN apk add package1 package2\n".to_string(),
+            ),
+        })
+        .await;
+
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .times(1)
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let hover_at = |position: Position| {
+        let server = &setup.server;
+        let uri = dockerfile_url.clone();
+        async move {
+            let hover = server
+                .hover(HoverParams {
+                    text_document_position_params: TextDocumentPositionParams {
+                        text_document: TextDocumentIdentifier::new(uri),
+                        position,
+                    },
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            let tower_lsp::lsp_types::HoverContents::Markup(content) = hover.contents else {
+                panic!("expected markdown hover contents");
+            };
+            content.value
+        }
+    };
+
+    let package_card = hover_at(Position::new(1, 14)).await;
+    assert!(
+        package_card.starts_with("## Sysdig Scan Result for Package `package1`"),
+        "{package_card}"
+    );
+    assert!(package_card.contains("CVE-2021-1234"));
+    assert!(package_card.contains("1.0.1"));
+
+    // package2 has no fixable vulnerabilities, so it falls back to the layer report.
+    let layer_report = hover_at(Position::new(1, 23)).await;
+    assert!(
+        layer_report.starts_with("## Sysdig Scan Result for Layer"),
+        "{layer_report}"
+    );
+    assert_eq!(hover_at(Position::new(1, 2)).await, layer_report);
+    // The whole line of the instruction shows the layer, including its edges.
+    assert_eq!(hover_at(Position::new(1, 0)).await, layer_report);
+    assert_eq!(hover_at(Position::new(1, 29)).await, layer_report);
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_completion_offers_the_registry_tags_of_the_image(
+    #[future] initialized_server: TestSetup,
+) {
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine:3.1\nRUN apk add curl\n".to_string(),
+            ),
+        })
+        .await;
+    initialized_server
+        .component_factory
+        .registry_client
+        .lock()
+        .await
+        .expect_list_tags()
+        .with(mockall::predicate::eq("alpine"))
+        .times(1)
+        .returning(|_| {
+            Ok(vec![
+                sysdig_lsp::app::ImageTag {
+                    name: "3.19".to_string(),
+                    digest: Some("sha256:c5b1261d".to_string()),
+                },
+                sysdig_lsp::app::ImageTag {
+                    name: "3.18".to_string(),
+                    digest: None,
+                },
+            ])
+        });
+
+    let completion = initialized_server
+        .server
+        .completion(CompletionParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(dockerfile_url),
+                position: Position::new(0, 15),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: None,
+        })
+        .await
+        .unwrap();
+
+    let Some(CompletionResponse::Array(items)) = completion else {
+        panic!("expected completion items, got {completion:?}");
+    };
+    let labels: Vec<_> = items.iter().map(|item| item.label.as_str()).collect();
+    assert_eq!(labels, ["3.19", "3.18"]);
+    assert_eq!(items[0].detail.as_deref(), Some("sha256:c5b1261d"));
+    assert_eq!(
+        items[0].text_edit,
+        Some(CompletionTextEdit::Edit(TextEdit::new(
+            Range::new(Position::new(0, 12), Position::new(0, 15)),
+            "3.19".to_string()
+        )))
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_completion_offers_dockerfile_keywords_and_flags(
+    #[future] initialized_server: TestSetup,
+) {
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine:3.19\nCO\nCOPY --ch\n".to_string(),
+            ),
+        })
+        .await;
+
+    let labels_at = async |position: Position| {
+        let completion = initialized_server
+            .server
+            .completion(CompletionParams {
+                text_document_position: TextDocumentPositionParams {
+                    text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+                    position,
+                },
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+                context: None,
+            })
+            .await
+            .unwrap();
+        let Some(CompletionResponse::Array(items)) = completion else {
+            panic!("expected completion items, got {completion:?}");
+        };
+        items.into_iter().map(|item| item.label).collect::<Vec<_>>()
+    };
+
+    let keywords = labels_at(Position::new(1, 2)).await;
+    assert!(keywords.contains(&"COPY".to_string()));
+    assert!(keywords.contains(&"HEALTHCHECK".to_string()));
+    let flags = labels_at(Position::new(2, 9)).await;
+    assert!(flags.contains(&"--chown=".to_string()));
+    assert!(!flags.contains(&"--mount=".to_string()));
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_scan_image_under_cursor_scans_the_nearest_image(
+    #[future] initialized_server: TestSetup,
+    scan_result: ScanResult,
+) {
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM golang:1.19 AS build\nRUN go build\n\nFROM alpine:3.17\nCOPY --from=build /app /app\n"
+                    .to_string(),
+            ),
+        })
+        .await;
+    initialized_server
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .with(mockall::predicate::eq("alpine:3.17"))
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    let result = initialized_server
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.scan-image-under-cursor".to_string(),
+            arguments: vec![
+                json!(dockerfile_url.clone()),
+                json!({"line": 4, "character": 3}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+    assert!(result.is_ok());
+
+    let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str())
+        .expect("no diagnostics published for the Dockerfile");
+    assert_eq!(last.len(), 1);
+    assert_eq!(last[0].range.start.line, 3);
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_scan_image_under_cursor_fails_without_image_references(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+) {
+    server_with_open_file
+        .server
+        .did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(open_file_url.clone(), 2),
+            content_changes: vec![tower_lsp::lsp_types::TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "FROM scratch\n".to_string(),
+            }],
+        })
+        .await;
+
+    let result = server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.scan-image-under-cursor".to_string(),
+            arguments: vec![json!(open_file_url), json!({"line": 0, "character": 0})],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+
+    let err = result.expect_err("a document without images can't be scanned");
+    assert_eq!(err.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_dead_stages_are_flagged_and_can_be_removed(#[future] initialized_server: TestSetup) {
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM node:18 AS unused\nRUN npm test\n\nFROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+
+    {
+        let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+        let last = last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str())
+            .expect("no diagnostics published for the Dockerfile");
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].source.as_deref(), Some("sysdig-lsp/lint"));
+        assert_eq!(
+            last[0].range,
+            Range::new(Position::new(0, 0), Position::new(0, 22))
+        );
+    }
+
+    let actions = initialized_server
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+            range: Range::new(Position::new(0, 3), Position::new(0, 3)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    let actions = serde_json::to_value(actions).unwrap();
+    let remove_stage = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["title"] == "Remove unused stage 'unused'")
+        .expect("remove stage quick fix must be offered");
+    assert_eq!(remove_stage["kind"], "quickfix");
+    assert_eq!(
+        remove_stage["edit"]["changes"][dockerfile_url.as_str()],
+        json!([{
+            "range": {"start": {"line": 0, "character": 0}, "end": {"line": 2, "character": 0}},
+            "newText": ""
+        }])
+    );
+
+    initialized_server
+        .server
+        .did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(dockerfile_url.clone(), 2),
+            content_changes: vec![tower_lsp::lsp_types::TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "\nFROM alpine\n".to_string(),
+            }],
+        })
+        .await;
+
+    let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str()).unwrap();
+    assert!(last.is_empty(), "fixed findings must be cleared: {last:?}");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_dockerfile_best_practices_are_flagged_with_their_enabled_rules() {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "lint": { "rules": { "apt-cache-not-cleaned": false } }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM debian\nRUN apt-get update && apt-get install -y curl\nADD app.py /app/\n"
+                    .to_string(),
+            ),
+        })
+        .await;
+
+    {
+        let diagnostics = setup.client_recorder.diagnostics.lock().await;
+        let last = last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str())
+            .expect("no diagnostics published for the Dockerfile");
+        let codes: Vec<_> = last
+            .iter()
+            .map(|diagnostic| diagnostic.code.clone())
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                "latest-tag",
+                "apt-no-install-recommends",
+                "add-instead-of-copy"
+            ]
+            .map(|rule| Some(tower_lsp::lsp_types::NumberOrString::String(
+                rule.to_string()
+            )))
+        );
+    }
+
+    let actions = setup
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+            range: Range::new(Position::new(2, 0), Position::new(2, 0)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    let actions = serde_json::to_value(actions).unwrap();
+    let replace_add = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["title"] == "Replace ADD with COPY")
+        .expect("replace ADD quick fix must be offered");
+    assert_eq!(
+        replace_add["edit"]["changes"][dockerfile_url.as_str()],
+        json!([{
+            "range": {"start": {"line": 2, "character": 0}, "end": {"line": 2, "character": 3}},
+            "newText": "COPY"
+        }])
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_k8s_misconfigurations_are_flagged_with_a_run_as_non_root_fix(
+    #[future] initialized_server: TestSetup,
+) {
+    let manifest_url: Url = "file:///pod.yaml".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                manifest_url.clone(),
+                "yaml".to_string(),
+                1,
+                "apiVersion: v1\nkind: Pod\nspec:\n  containers:\n  - name: nginx\n    image: nginx:1.27\n"
+                    .to_string(),
+            ),
+        })
+        .await;
+
+    {
+        let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+        let last = last_published_diagnostics_for(&diagnostics, manifest_url.as_str())
+            .expect("no diagnostics published for the manifest");
+        let codes: Vec<_> = last
+            .iter()
+            .map(|diagnostic| (diagnostic.source.as_deref(), diagnostic.code.clone()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (
+                    Some("sysdig-lsp/lint"),
+                    Some(tower_lsp::lsp_types::NumberOrString::String(
+                        "run-as-root".to_string()
+                    ))
+                ),
+                (
+                    Some("sysdig-lsp/lint"),
+                    Some(tower_lsp::lsp_types::NumberOrString::String(
+                        "missing-resource-limits".to_string()
+                    ))
+                ),
+            ]
+        );
+    }
+
+    let actions = initialized_server
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(manifest_url.clone()),
+            range: Range::new(Position::new(4, 6), Position::new(4, 6)),
+            context: CodeActionContext::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    let actions = serde_json::to_value(actions).unwrap();
+    let run_as_non_root = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["title"] == "Set runAsNonRoot: true")
+        .expect("run as non-root quick fix must be offered");
+    assert_eq!(
+        run_as_non_root["edit"]["changes"][manifest_url.as_str()],
+        json!([{
+            "range": {"start": {"line": 5, "character": 0}, "end": {"line": 5, "character": 0}},
+            "newText": "    securityContext:\n      runAsNonRoot: true\n"
+        }])
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_document_symbols_outline_dockerfile_stages(#[future] initialized_server: TestSetup) {
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM golang:1.22 AS build\nRUN go build -o /app\n\nFROM alpine\nCOPY --from=build /app /app\nEXPOSE 8080\nENTRYPOINT [\"/app\"]\n".to_string(),
+            ),
+        })
+        .await;
+
+    let response = initialized_server
+        .server
+        .document_symbol(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let Some(DocumentSymbolResponse::Nested(symbols)) = response else {
+        panic!("expected nested document symbols, got {response:?}");
+    };
+    let outline: Vec<(String, Vec<String>)> = symbols
+        .into_iter()
+        .map(|stage| {
+            let children = stage
+                .children
+                .unwrap_or_default()
+                .into_iter()
+                .map(|child| child.name)
+                .collect();
+            (stage.name, children)
+        })
+        .collect();
+    assert_eq!(
+        outline,
+        vec![
+            (
+                "build".to_string(),
+                vec!["RUN go build -o /app".to_string()]
+            ),
+            (
+                "stage #1".to_string(),
+                vec![
+                    "COPY --from=build /app /app".to_string(),
+                    "EXPOSE 8080".to_string(),
+                    "ENTRYPOINT [\"/app\"]".to_string(),
+                ]
+            ),
+        ]
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_document_symbols_are_not_provided_for_compose_files(
+    #[future] initialized_server: TestSetup,
+) {
+    let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                compose_url.clone(),
+                "yaml".to_string(),
+                1,
+                "services:\n  web:\n    image: nginx:latest\n".to_string(),
+            ),
+        })
+        .await;
+
+    let response = initialized_server
+        .server
+        .document_symbol(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(compose_url),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap();
+
+    assert!(response.is_none());
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_policy_failures_are_published_with_their_own_source(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+) {
+    let mut failed_result = ScanResult::new(
+        ScanType::Docker,
+        "alpine".to_string(),
+        "sha256:12345".to_string(),
+        None,
+        OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+        123456,
+        Architecture::Amd64,
+        HashMap::new(),
+        chrono::Utc::now(),
+        EvaluationResult::Failed,
+    );
+    let policy = failed_result.add_policy(
+        "p1".to_string(),
+        "No critical vulnerabilities".to_string(),
+        chrono::Utc::now(),
+        chrono::Utc::now(),
+    );
+    failed_result
+        .add_policy_bundle("b1".to_string(), "b1".to_string(), policy)
+        .add_rule(
+            "r1".to_string(),
+            "desc".to_string(),
+            EvaluationResult::Failed,
+        );
+
+    server_with_open_file
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(failed_result.clone()));
+
+    server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    {
+        let diagnostics = server_with_open_file
+            .client_recorder
+            .diagnostics
+            .lock()
+            .await;
+        let last = last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap();
+        let policy_diagnostic = last
+            .iter()
+            .find(|d| d.source.as_deref() == Some("sysdig-lsp/policy"))
+            .expect("policy failures must be published under the sysdig-lsp/policy source");
+        assert_eq!(
+            policy_diagnostic.message,
+            "Policy evaluation failed for alpine: No critical vulnerabilities"
+        );
+        assert_eq!(policy_diagnostic.severity, Some(DiagnosticSeverity::ERROR));
+        assert!(
+            last.iter()
+                .any(|d| d.source.as_deref() == Some("sysdig-lsp/vuln"))
+        );
+    }
+
+    server_with_open_file
+        .server
+        .did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(open_file_url.clone(), 2),
+            content_changes: vec![tower_lsp::lsp_types::TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "FROM alpine:3.19".to_string(),
+            }],
+        })
+        .await;
+
+    let diagnostics = server_with_open_file
+        .client_recorder
+        .diagnostics
+        .lock()
+        .await;
+    let last = last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap();
+    assert!(
+        last.is_empty(),
+        "stale policy findings must be cleared: {last:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_scanned_images_get_a_lens_with_their_policy_verdict() {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            capabilities: serde_json::from_value(json!({
+                "workspace": { "codeLens": { "refreshSupport": true } }
+            }))
+            .unwrap(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine".to_string(),
+            ),
+        })
+        .await;
+    let mut failed_result = ScanResult::new(
+        ScanType::Docker,
+        "alpine".to_string(),
+        "sha256:12345".to_string(),
+        None,
+        OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+        123456,
+        Architecture::Amd64,
+        HashMap::new(),
+        chrono::Utc::now(),
+        EvaluationResult::Failed,
+    );
+    let policy = failed_result.add_policy(
+        "p1".to_string(),
+        "No critical vulnerabilities".to_string(),
+        chrono::Utc::now(),
+        chrono::Utc::now(),
+    );
+    failed_result
+        .add_policy_bundle("b1".to_string(), "b1".to_string(), policy)
+        .add_rule(
+            "r1".to_string(),
+            "desc".to_string(),
+            EvaluationResult::Failed,
+        );
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(failed_result.clone()));
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(*setup.client_recorder.code_lens_refreshes.lock().await, 1);
+    let lenses = setup
+        .server
+        .code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let verdict = lenses
+        .into_iter()
+        .filter_map(|lens| lens.command)
+        .find(|command| command.command == "sysdig-lsp.show-scan-report")
+        .expect("the scanned image must have a verdict lens");
+    assert_eq!(verdict.title, "Policy: FAILED (1 policy) — View details");
+
+    let report = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: verdict.command,
+            arguments: verdict.arguments.unwrap(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    let hover = setup
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(dockerfile_url.clone()),
+                Position::new(0, 2),
+            ),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let tower_lsp::lsp_types::HoverContents::Markup(hover) = hover.contents else {
+        panic!("expected a markdown hover");
+    };
+    let report = report.unwrap();
+    let report = report.as_str().unwrap();
+    assert!(report.starts_with(&hover.value), "{report}");
+    assert!(report.contains("### Accepted Risks"), "{report}");
+
+    let report_of_the_document = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.show-scan-report".to_string(),
+            arguments: vec![json!(dockerfile_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(report_of_the_document, Some(json!(report)));
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_export_scan_result_returns_the_last_scan_as_json(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    server_with_open_file
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+
+    server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let exported = server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.export-scan-result".to_string(),
+            arguments: vec![json!(open_file_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .expect("the scan result must be returned when no path is given");
+    assert_eq!(exported["metadata"]["pullString"], "alpine:latest");
+    assert_eq!(exported["vulnerabilities"][0]["severity"], "High");
+    assert!(
+        exported["packages"]
+            .as_array()
+            .is_some_and(|p| !p.is_empty())
+    );
+
+    let output = tempfile::tempdir().unwrap();
+    let path = output.path().join("scan.json");
+    let written = server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.export-scan-result".to_string(),
+            arguments: vec![json!(open_file_url), json!(path)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(written, Some(json!(path.display().to_string())));
+    let contents: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(contents, exported);
+}
+
+#[rstest]
+#[tokio::test(start_paused = true)]
+async fn test_saving_a_scanned_document_rescans_it_once_saves_settle(scan_result: ScanResult) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "autoScan": { "onSave": true }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let uri: Url = "file:///Dockerfile".parse().unwrap();
+    let unscanned: Url = "file:///other/Dockerfile".parse().unwrap();
+    for uri in [&uri, &unscanned] {
+        setup
+            .server
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "dockerfile".to_string(),
+                    1,
+                    "FROM alpine".to_string(),
+                ),
+            })
+            .await;
+    }
+    let mut sequence = mockall::Sequence::new();
+    let mut scanner = setup.component_factory.image_scanner.lock().await;
+    for image in ["alpine", "alpine:3.19"] {
+        let scan_result = scan_result.clone();
+        scanner
+            .expect_scan_image()
+            .withf(move |pull_string| pull_string == image)
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(move |_| Ok(scan_result.clone()));
+    }
+    drop(scanner);
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 5,"line":0}},"uri":uri}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    setup
+        .server
+        .did_change(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+            content_changes: vec![tower_lsp::lsp_types::TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "FROM alpine:3.19".to_string(),
+            }],
+        })
+        .await;
+    // A burst of saves scans once, with the image currently in the document.
+    for uri in [&uri, &uri, &uri, &unscanned] {
+        setup
+            .server
+            .did_save(DidSaveTextDocumentParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                text: None,
+            })
+            .await;
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .checkpoint();
+}
+
+#[rstest]
+#[case::cleared(true)]
+#[case::kept(false)]
+#[tokio::test]
+async fn test_closing_a_document_releases_its_scan_results(
+    #[case] clear_on_close: bool,
+    scan_result: ScanResult,
+) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "diagnostics": { "clearOnClose": clear_on_close }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let uri: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 5,"line":0}},"uri":uri}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    setup
+        .server
+        .did_close(tower_lsp::lsp_types::DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+        })
+        .await;
+
+    {
+        let published = setup.client_recorder.diagnostics.lock().await;
+        let last = last_published_diagnostics_for(&published, uri.as_str()).unwrap();
+        assert_eq!(last.is_empty(), clear_on_close, "{last:?}");
+    }
+    let hover = setup
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(uri.clone()),
+                position: Position::new(0, 6),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    assert!(hover.is_none());
+    let export = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.export-scan-result".to_string(),
+            arguments: vec![json!(uri)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+    assert!(export.is_err(), "the scan result must be released");
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_accept_risks_of_the_last_scan_in_one_batch(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+    mut scan_result: ScanResult,
+) {
+    scan_result.add_vulnerability(
+        "CVE-2022-5678".to_string(),
+        Severity::Low,
+        chrono::NaiveDate::from_ymd_opt(2022, 1, 1).unwrap(),
+        None,
+        false,
+        None,
+        None,
+        None,
+    );
+    server_with_open_file
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    server_with_open_file
+        .component_factory
+        .risk_acceptance_client
+        .lock()
+        .await
+        .expect_accept_risk()
+        .times(2)
+        .returning(|acceptance| {
+            assert_eq!(
+                acceptance.scope,
+                RiskAcceptanceScope::Image("alpine:latest".to_string())
+            );
+            assert_eq!(acceptance.description, "legacy image, triaged");
+            match acceptance.cve.as_str() {
+                "CVE-2021-1234" => Ok(()),
+                _ => Err(RiskAcceptanceError::Rejected {
+                    cve: acceptance.cve.clone(),
+                    status: 409,
+                    message: "already accepted".to_string(),
+                }),
+            }
+        });
+
+    server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let accept_risks = |cves: Vec<&str>| ExecuteCommandParams {
+        command: "sysdig-lsp.accept-risks".to_string(),
+        arguments: vec![
+            json!(open_file_url),
+            json!({
+                "cves": cves,
+                "reason": "RiskOwned",
+                "description": "legacy image, triaged",
+                "expirationDate": "2027-01-31"
+            }),
+        ],
+        work_done_progress_params: WorkDoneProgressParams::default(),
+    };
+
+    let err = server_with_open_file
+        .server
+        .execute_command(accept_risks(vec!["CVE-2021-1234", "CVE-1999-0001"]))
+        .await
+        .expect_err("CVEs missing from the scan must be rejected before accepting any");
+    assert!(err.message.contains("CVE-1999-0001"), "{err:?}");
+
+    let result = server_with_open_file
+        .server
+        .execute_command(accept_risks(vec!["CVE-2021-1234", "CVE-2022-5678"]))
+        .await
+        .unwrap()
+        .expect("the outcome of every acceptance must be returned");
+    assert_eq!(result["accepted"], json!(["CVE-2021-1234"]));
+    assert_eq!(result["failed"][0]["cve"], "CVE-2022-5678");
+
+    let messages = server_with_open_file.client_recorder.messages.lock().await;
+    assert!(
+        messages.last().is_some_and(|(message_type, message)| {
+            *message_type == MessageType::WARNING && message.contains("1 of 2")
+        }),
+        "{messages:?}"
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_vulnerability_hints_offer_to_request_an_accepted_risk(
+    #[future] initialized_server: TestSetup,
+    scan_result: ScanResult,
+) {
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nCOPY . .\n".to_string(),
+            ),
+        })
+        .await;
+    initialized_server
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    initialized_server
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    initialized_server
+        .component_factory
+        .risk_acceptance_client
+        .lock()
+        .await
+        .expect_accept_risk()
+        .times(1)
+        .returning(|acceptance| {
+            assert_eq!(acceptance.cve, "CVE-2021-1234");
+            assert_eq!(
+                acceptance.scope,
+                RiskAcceptanceScope::Package(PackageVersion {
+                    name: "package1".to_string(),
+                    version: "1.0.0".to_string(),
+                })
+            );
+            Ok(())
+        });
+
+    initialized_server
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    let hint = {
+        let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+        last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str())
+            .unwrap()
+            .iter()
+            .find(|d| d.message.starts_with("Vulnerability: CVE-2021-1234"))
+            .cloned()
+            .expect("the vulnerability must be hinted at its layer")
+    };
+
+    let actions = initialized_server
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+            range: hint.range,
+            context: CodeActionContext {
+                diagnostics: vec![hint],
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let actions = serde_json::to_value(actions).unwrap();
+    let request = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| {
+            action["title"] == "Request accepted risk in Sysdig for CVE-2021-1234 in package1 1.0.0"
+        })
+        .expect("accepting the risk in the package must be offered");
+
+    initialized_server
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: request["command"]["command"].as_str().unwrap().to_string(),
+            arguments: request["command"]["arguments"].as_array().unwrap().clone(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+    let hint = last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str())
+        .unwrap()
+        .iter()
+        .find(|d| d.message.starts_with("Vulnerability: CVE-2021-1234"))
+        .unwrap();
+    assert!(
+        hint.message.ends_with("(risk accepted)"),
+        "{}",
+        hint.message
+    );
+    assert_eq!(hint.tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+    let messages = initialized_server.client_recorder.messages.lock().await;
+    assert!(
+        messages.last().is_some_and(|(message_type, message)| {
+            *message_type == MessageType::INFO
+                && message.contains("Accepted the risk of CVE-2021-1234 in package1 1.0.0")
+        }),
+        "{messages:?}"
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_build_and_scan_diagnostics_tag_accepted_risks_and_carry_their_findings(
+    #[future] initialized_server: TestSetup,
+    mut scan_result: ScanResult,
+) {
+    let now = chrono::Utc::now();
+    let risk = scan_result.add_accepted_risk(
+        "risk-1".to_string(),
+        AcceptedRiskReason::RiskMitigated,
+        "Not reachable".to_string(),
+        None,
+        true,
+        now,
+        now,
+    );
+    scan_result.vulnerabilities()[0].add_accepted_risk(risk);
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nCOPY . .\n".to_string(),
+            ),
+        })
+        .await;
+    initialized_server
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    initialized_server
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+
+    initialized_server
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let diagnostics = initialized_server
+        .client_recorder
+        .diagnostics
+        .lock()
+        .await
+        .clone();
+    let diagnostics =
+        last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str()).unwrap();
+    assert!(
+        diagnostics
+            .iter()
+            .all(|d| d.source.as_deref() == Some("sysdig-lsp/vuln")),
+        "{diagnostics:?}"
+    );
+    let layer = diagnostics
+        .iter()
+        .find(|d| d.message.starts_with("Vulnerabilities found in layer"))
+        .expect("the layer must be summarized");
+    assert_eq!(layer.data, Some(json!({ "cves": ["CVE-2021-1234"] })));
+    let hint = diagnostics
+        .iter()
+        .find(|d| d.message.starts_with("Vulnerability: CVE-2021-1234"))
+        .expect("accepted risks must still be hinted");
+    assert!(
+        hint.message.ends_with("(risk accepted)"),
+        "{}",
+        hint.message
+    );
+    assert_eq!(hint.tags, Some(vec![DiagnosticTag::UNNECESSARY]));
+    assert_eq!(
+        hint.data,
+        Some(json!({
+            "cve": "CVE-2021-1234",
+            "packages": [{ "name": "package1", "version": "1.0.0" }],
+            "riskAccepted": true,
+        }))
+    );
+
+    let actions = initialized_server
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+            range: hint.range,
+            context: CodeActionContext {
+                diagnostics: vec![hint.clone()],
+                ..Default::default()
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap_or_default();
+    let actions = serde_json::to_string(&actions).unwrap();
+    assert!(
+        !actions.contains("Request accepted risk"),
+        "accepted risks can't be accepted again: {actions}"
+    );
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_export_scan_result_fails_without_a_previous_scan(
+    #[future] server_with_open_file: TestSetup,
+    open_file_url: Url,
+) {
+    let result = server_with_open_file
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.export-scan-result".to_string(),
+            arguments: vec![json!(open_file_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+
+    let err = result.expect_err("there is nothing to export");
+    assert_eq!(err.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_export_sarif_locates_findings_at_the_instruction_of_their_layer(
+    scan_result: ScanResult,
+) {
+    let setup = TestSetup::new();
+    #[allow(deprecated)]
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            root_uri: Some("file:///workspace".parse().unwrap()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url: Url = "file:///workspace/app/Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\nCOPY . .\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-build-and-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let sarif = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.export-sarif".to_string(),
+            arguments: vec![json!(dockerfile_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .expect("the SARIF log must be returned when no path is given");
+
+    assert_eq!(sarif["version"], "2.1.0");
+    let result = &sarif["runs"][0]["results"][0];
+    assert_eq!(result["level"], "error");
+    let location = &result["locations"][0]["physicalLocation"];
+    assert_eq!(location["artifactLocation"]["uri"], "app/Dockerfile");
+    assert_eq!(location["region"]["startLine"], 2);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_export_licenses_groups_the_packages_by_license(mut scan_result: ScanResult) {
+    let layer = scan_result.layers()[0].clone();
+    scan_result.add_package(
+        PackageType::Os,
+        "musl".to_string(),
+        "1.2.4".to_string(),
+        "/lib/apk/db/installed".to_string(),
+        layer,
+        false,
+        Some("MIT".to_string()),
+        None,
+    );
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let inventory = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.export-licenses".to_string(),
+            arguments: vec![json!(dockerfile_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .expect("the license inventory must be returned when no path is given");
+
+    assert_eq!(inventory["pullString"], "alpine:latest");
+    let licenses = inventory["licenses"].as_array().unwrap();
+    assert_eq!(licenses.last().unwrap()["license"], "MIT");
+    assert_eq!(
+        licenses.last().unwrap()["packages"],
+        json!([{"name": "musl", "version": "1.2.4", "type": "os", "path": "/lib/apk/db/installed"}])
+    );
+    assert_eq!(licenses[0]["license"], serde_json::Value::Null);
+}
+
+#[rstest]
+#[case::uploaded(Some("https://secure.sysdig.com/#/vulnerabilities/results/1"))]
+#[case::not_uploaded(None)]
+#[tokio::test]
+async fn test_open_scan_in_browser_returns_the_url_of_the_uploaded_scan(
+    mut scan_result: ScanResult,
+    #[case] result_url: Option<&str>,
+) {
+    if let Some(result_url) = result_url {
+        scan_result.set_result_url(result_url.to_string());
+    }
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let opened = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.open-scan-in-browser".to_string(),
+            arguments: vec![json!(dockerfile_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await;
+
+    match result_url {
+        Some(result_url) => assert_eq!(opened.unwrap(), Some(json!(result_url))),
+        None => assert!(
+            opened
+                .unwrap_err()
+                .message
+                .contains("wasn't uploaded to Sysdig Secure")
+        ),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_generate_sbom_returns_or_writes_the_package_inventory_next_to_the_document(
+    scan_result: ScanResult,
+) {
+    let workspace = tempfile::tempdir().unwrap();
+    let dockerfile_url = Url::from_file_path(workspace.path().join("Dockerfile")).unwrap();
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let bom = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.generate-sbom".to_string(),
+            arguments: vec![json!(dockerfile_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .expect("the SBOM must be returned when no path is given");
+    assert_eq!(bom["bomFormat"], "CycloneDX");
+    assert_eq!(bom["metadata"]["component"]["name"], "alpine:latest");
+    assert!(bom["components"].as_array().is_some_and(|c| !c.is_empty()));
+
+    let written = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.generate-sbom".to_string(),
+            arguments: vec![
+                json!(dockerfile_url),
+                json!("spdx"),
+                json!("sbom.spdx.json"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    let path = workspace.path().join("sbom.spdx.json");
+    assert_eq!(written, Some(json!(path.display().to_string())));
+    let document: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(document["spdxVersion"], "SPDX-2.3");
+    assert_eq!(
+        document["packages"].as_array().map(Vec::len),
+        bom["components"].as_array().map(|c| c.len() + 1)
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_generated_sysdigignore_leaves_current_findings_out_of_the_diagnostics(
+    scan_result: ScanResult,
+) {
+    let workspace = tempfile::tempdir().unwrap();
+    let workspace_url = Url::from_directory_path(workspace.path()).unwrap();
+    let setup = TestSetup::new();
+    #[allow(deprecated)]
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            root_uri: Some(workspace_url.clone()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let dockerfile_url = workspace_url.join("Dockerfile").unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(2)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    let scan = || {
+        setup.server.execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 5,"line":0}},"uri":dockerfile_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+    };
+    let last_vuln_message = || async {
+        let diagnostics = setup.client_recorder.diagnostics.lock().await;
+        let (_, last) = diagnostics.last().unwrap();
+        last.iter()
+            .find(|d| d.source.as_deref() == Some("sysdig-lsp/vuln"))
+            .unwrap()
+            .message
+            .clone()
+    };
+
+    scan().await.unwrap();
+    assert!(
+        last_vuln_message().await.contains("1 High"),
+        "{}",
+        last_vuln_message().await
+    );
+
+    let path = setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.generate-sysdigignore".to_string(),
+            arguments: vec![json!(dockerfile_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        path,
+        json!(workspace.path().join(".sysdigignore").display().to_string())
+    );
+    let sysdigignore = std::fs::read_to_string(workspace.path().join(".sysdigignore")).unwrap();
+    assert!(
+        sysdigignore.contains("CVE-2021-1234 expires=YYYY-MM-DD # High: package1 1.0.0\n"),
+        "{sysdigignore}"
+    );
+
+    scan().await.unwrap();
+    assert_eq!(last_vuln_message().await, "No vulnerabilities found.");
+}
+
+#[rstest]
+#[awt]
+#[tokio::test]
+async fn test_ignore_directives_leave_findings_out_of_the_diagnostics(
+    #[future] initialized_server: TestSetup,
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    initialized_server
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                open_file_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "# sysdig-lsp: ignore CVE-2021-1234 reason=\"not reachable\"\nFROM alpine\n"
+                    .to_string(),
+            ),
+        })
+        .await;
+    initialized_server
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    initialized_server
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":1},"start":{"character": 0,"line":1}},"uri":open_file_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let diagnostics = initialized_server.client_recorder.diagnostics.lock().await;
+    let (_, last) = diagnostics.last().unwrap();
+    let summary = last
+        .iter()
+        .find(|d| d.source.as_deref() == Some("sysdig-lsp/vuln"))
+        .unwrap();
+    assert_eq!(summary.message, "No vulnerabilities found.");
+    drop(diagnostics);
+
+    let hover = initialized_server
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(open_file_url),
+                position: Position::new(1, 5),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let tower_lsp::lsp_types::HoverContents::Markup(content) = hover.contents else {
+        panic!("expected markdown hover contents");
+    };
+    assert!(
+        content
+            .value
+            .contains("* **Suppressed vulnerabilities**: 1 (ignore directives or `.sysdigignore`)"),
+        "{}",
+        content.value
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_documents_in_a_scope_are_scanned_with_the_scope_tenant(scan_result: ScanResult) {
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "apiToken": "dummy-token",
+                    "scopes": [{ "paths": ["services/payments/**"], "apiToken": "payments-token" }]
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let scoped_result = scan_result.clone();
+    setup
+        .component_factory
+        .scoped_image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scoped_result.clone()));
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    for uri in [
+        "file:///repo/services/payments/api/Dockerfile",
+        "file:///repo/services/billing/Dockerfile",
+    ] {
+        let uri = Url::parse(uri).unwrap();
+        setup
+            .server
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "dockerfile".to_string(),
+                    1,
+                    "FROM alpine".to_string(),
+                ),
+            })
+            .await;
+        setup
+            .server
+            .execute_command(ExecuteCommandParams {
+                command: "sysdig-lsp.execute-scan".to_string(),
+                arguments: vec![
+                    json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":uri}),
+                    json!("alpine"),
+                ],
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            })
+            .await
+            .unwrap();
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_invalid_scope_globs_are_rejected_on_initialize() {
+    let setup = TestSetup::new();
+    let result = setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "apiToken": "dummy-token",
+                    "scopes": [{ "paths": ["services/[payments"] }]
+                }
+            })),
+            ..Default::default()
+        })
+        .await;
+
+    let err = result.expect_err("invalid globs must be reported");
+    assert_eq!(err.code, tower_lsp::jsonrpc::ErrorCode::InvalidParams);
+    assert!(err.message.contains("sysdig.scopes"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_persisted_scan_results_are_restored_for_unchanged_documents(
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    let store = common::InMemoryScanResultStore::default();
+    let initialize = || InitializeParams {
+        initialization_options: Some(json!({
+            "sysdig": {
+                "apiUrl": "http://localhost:8080",
+                "api_token": "dummy-token",
+                "lint": { "rules": { "latest-tag": false } }
+            }
+        })),
+        ..Default::default()
+    };
+    let open = |text: &str| DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(
+            open_file_url.clone(),
+            "dockerfile".to_string(),
+            1,
+            text.to_string(),
+        ),
+    };
+
+    let first_session = TestSetup::with_scan_result_store(Some(store.clone()));
+    first_session.server.initialize(initialize()).await.unwrap();
+    first_session.server.did_open(open("FROM alpine")).await;
+    first_session
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    first_session
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let second_session = TestSetup::with_scan_result_store(Some(store.clone()));
+    second_session
+        .server
+        .initialize(initialize())
+        .await
+        .unwrap();
+    second_session.server.did_open(open("FROM alpine")).await;
+
+    {
+        let diagnostics = second_session.client_recorder.diagnostics.lock().await;
+        let last = last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(
+            last[0].message,
+            "Vulnerabilities found for alpine: 0 Critical, 1 High, 0 Medium, 0 Low, 0 Negligible"
+        );
+    }
+    let hover = second_session
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(open_file_url.clone()),
+                Position::new(0, 5),
+            ),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    assert!(
+        hover.is_some(),
+        "the scan report must be restored for hover"
+    );
+
+    let third_session = TestSetup::with_scan_result_store(Some(store));
+    third_session.server.initialize(initialize()).await.unwrap();
+    third_session
+        .server
+        .did_open(open("FROM alpine:3.19"))
+        .await;
+
+    let diagnostics = third_session.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap();
+    assert!(
+        last.is_empty(),
+        "results of a different content must not be restored: {last:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_restored_scan_results_can_be_exported(open_file_url: Url, scan_result: ScanResult) {
+    let store = common::InMemoryScanResultStore::default();
+    let initialize = || InitializeParams {
+        initialization_options: Some(json!({
+            "sysdig": {
+                "apiUrl": "http://localhost:8080",
+                "api_token": "dummy-token"
+            }
+        })),
+        ..Default::default()
+    };
+    let open = DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(
+            open_file_url.clone(),
+            "dockerfile".to_string(),
+            1,
+            "FROM alpine".to_string(),
+        ),
+    };
+
+    let first_session = TestSetup::with_scan_result_store(Some(store.clone()));
+    first_session.server.initialize(initialize()).await.unwrap();
+    first_session.server.did_open(open.clone()).await;
+    first_session
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    first_session
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    // Snapshots are persisted as JSON between sessions.
+    {
+        let mut snapshots = store.0.lock().await;
+        let snapshot = snapshots.get_mut(open_file_url.as_str()).unwrap();
+        *snapshot = serde_json::from_value(serde_json::to_value(&*snapshot).unwrap()).unwrap();
+    }
+
+    let second_session = TestSetup::with_scan_result_store(Some(store));
+    second_session
+        .server
+        .initialize(initialize())
+        .await
+        .unwrap();
+    second_session.server.did_open(open).await;
+
+    let exported = second_session
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.export-scan-result".to_string(),
+            arguments: vec![json!(open_file_url)],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .expect("the restored scan result must be exported");
+    assert_eq!(exported["metadata"]["pullString"], "alpine:latest");
+    assert_eq!(exported["vulnerabilities"][0]["severity"], "High");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_renamed_documents_keep_their_scan_results_under_the_new_uri(
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    let store = common::InMemoryScanResultStore::default();
+    let setup = TestSetup::with_scan_result_store(Some(store.clone()));
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "lint": { "rules": { "latest-tag": false } }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                open_file_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    let renamed_url = open_file_url.join("Containerfile").unwrap();
+    setup
+        .server
+        .did_rename_files(RenameFilesParams {
+            files: vec![FileRename {
+                old_uri: open_file_url.to_string(),
+                new_uri: renamed_url.to_string(),
+            }],
+        })
+        .await;
+
+    {
+        let diagnostics = setup.client_recorder.diagnostics.lock().await;
+        let old = last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap();
+        assert!(old.is_empty(), "the old URI must be cleared: {old:?}");
+        let new = last_published_diagnostics_for(&diagnostics, renamed_url.as_str()).unwrap();
+        assert_eq!(new.len(), 1);
+        assert_eq!(
+            new[0].message,
+            "Vulnerabilities found for alpine: 0 Critical, 1 High, 0 Medium, 0 Low, 0 Negligible"
+        );
+    }
+    let hover = setup
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(renamed_url.clone()),
+                Position::new(0, 5),
+            ),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    assert!(
+        hover.is_some(),
+        "the scan report must move with the document"
+    );
+    let snapshots = store.0.lock().await;
+    assert!(!snapshots.contains_key(open_file_url.as_str()));
+    assert!(snapshots.contains_key(renamed_url.as_str()));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_renamed_folders_move_the_scan_results_of_their_documents(scan_result: ScanResult) {
+    let store = common::InMemoryScanResultStore::default();
+    let setup = TestSetup::with_scan_result_store(Some(store.clone()));
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "lint": { "rules": { "latest-tag": false } }
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile = Url::parse("file:///app/api/Dockerfile").unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                dockerfile.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine".to_string(),
+            ),
+        })
+        .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+
+    setup
+        .server
+        .did_rename_files(RenameFilesParams {
+            files: vec![FileRename {
+                old_uri: "file:///app".to_string(),
+                new_uri: "file:///service".to_string(),
+            }],
+        })
+        .await;
+
+    let renamed = Url::parse("file:///service/api/Dockerfile").unwrap();
+    {
+        let diagnostics = setup.client_recorder.diagnostics.lock().await;
+        let old = last_published_diagnostics_for(&diagnostics, dockerfile.as_str()).unwrap();
+        assert!(old.is_empty(), "the old URI must be cleared: {old:?}");
+        let new = last_published_diagnostics_for(&diagnostics, renamed.as_str()).unwrap();
+        assert_eq!(new.len(), 1);
+    }
+    let hover = setup
+        .server
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(renamed.clone()),
+                Position::new(0, 5),
+            ),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    assert!(hover.is_some(), "the scan report must move with the folder");
+    let references = setup
+        .server
+        .references(ReferenceParams {
+            text_document_position: TextDocumentPositionParams::new(
+                TextDocumentIdentifier::new(renamed.clone()),
+                Position::new(0, 6),
+            ),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: ReferenceContext {
+                include_declaration: true,
+            },
+        })
+        .await
+        .unwrap()
+        .unwrap_or_default();
+    assert_eq!(
+        references
+            .iter()
+            .map(|r| r.uri.as_str())
+            .collect::<Vec<_>>(),
+        [renamed.as_str()]
+    );
+    let snapshots = store.0.lock().await;
+    assert!(!snapshots.contains_key(dockerfile.as_str()));
+    assert!(snapshots.contains_key(renamed.as_str()));
+}
+
+#[rstest]
+#[case::uncached(false)]
+#[case::cached(true)]
+#[tokio::test(start_paused = true)]
+async fn test_policy_refresher_updates_diagnostics_when_the_evaluation_flips(
+    #[case] cached_scans: bool,
+    open_file_url: Url,
+    scan_result: ScanResult,
+) {
+    // Cached results must not hide the new verdict.
+    let setup = match cached_scans {
+        true => TestSetup::with_cached_scans(),
+        false => TestSetup::new(),
+    };
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "policyRefreshIntervalSeconds": 60
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                open_file_url.clone(),
+                "dockerfile".to_string(),
+                1,
+                "FROM alpine".to_string(),
+            ),
+        })
+        .await;
+
+    let failing_result = ScanResult::new(
+        ScanType::Docker,
+        "alpine".to_string(),
+        "sha256:12345".to_string(),
+        None,
+        OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+        123456,
+        Architecture::Amd64,
+        HashMap::new(),
+        chrono::Utc::now(),
+        EvaluationResult::Failed,
+    );
+    let scans = std::sync::atomic::AtomicUsize::new(0);
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| {
+            // Policies change server-side after the first scan
+            if scans.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+                Ok(scan_result.clone())
+            } else {
+                Ok(failing_result.clone())
+            }
+        });
+
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":open_file_url}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
+    {
+        let diagnostics = setup.client_recorder.diagnostics.lock().await;
+        let last = last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap();
+        assert!(
+            last.iter()
+                .all(|d| d.source.as_deref() != Some("sysdig-lsp/policy"))
+        );
+    }
+
+    tokio::time::sleep(std::time::Duration::from_secs(61)).await;
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, open_file_url.as_str()).unwrap();
+    assert!(
+        last.iter()
+            .any(|d| d.source.as_deref() == Some("sysdig-lsp/policy")),
+        "the flipped evaluation must be published: {last:?}"
+    );
+    let messages = setup.client_recorder.messages.lock().await;
+    assert!(
+        messages
+            .iter()
+            .any(|(_, m)| m == "Policy evaluation of alpine changed: it now fails"),
+        "{messages:?}"
     );
 }
 
-/// IacScanner double that blocks inside scan_iac until released, to observe the
-/// server while a command is in flight.
-#[derive(Clone)]
-struct BlockingIacScanner {
-    started: std::sync::Arc<tokio::sync::Notify>,
-    release: std::sync::Arc<tokio::sync::Notify>,
-}
+#[rstest]
+#[tokio::test]
+async fn test_compose_build_sections_build_and_scan_the_referenced_dockerfile(
+    scan_result: ScanResult,
+) {
+    let workspace = tempfile::tempdir().unwrap();
+    let dockerfile_path = workspace.path().join("api").join("Dockerfile.prod");
+    std::fs::create_dir_all(dockerfile_path.parent().unwrap()).unwrap();
+    std::fs::write(&dockerfile_path, "# API\nFROM alpine\n").unwrap();
+    let dockerfile_url = Url::from_file_path(&dockerfile_path).unwrap();
+    let compose_url = Url::from_file_path(workspace.path().join("compose.yaml")).unwrap();
 
-#[async_trait::async_trait]
-impl sysdig_lsp::app::IacScanner for BlockingIacScanner {
-    async fn scan_iac(
-        &self,
-        _scope: &IacScanScope,
-    ) -> Result<IacScanResult, sysdig_lsp::app::IacScanError> {
-        self.started.notify_one();
-        self.release.notified().await;
-        Ok(IacScanResult::default())
-    }
-}
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                compose_url.clone(),
+                "yaml".to_string(),
+                1,
+                "services:\n  api:\n    build:\n      context: ./api\n      dockerfile: Dockerfile.prod\n"
+                    .to_string(),
+            ),
+        })
+        .await;
 
-#[derive(Clone)]
-struct BlockingComponentFactory {
-    iac_scanner: BlockingIacScanner,
-}
+    let lenses = setup
+        .server
+        .code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new(compose_url),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let build_lens = lenses
+        .into_iter()
+        .find(|lens| {
+            lens.command.as_ref().map(|c| c.command.as_str())
+                == Some("sysdig-lsp.execute-build-and-scan")
+        })
+        .expect("no build and scan lens for the build section");
+    assert_eq!(
+        build_lens.range,
+        Range::new(Position::new(2, 4), Position::new(2, 9))
+    );
+    let command = build_lens.command.unwrap();
+    assert_eq!(
+        command.arguments.as_ref().unwrap()[0]["uri"],
+        json!(dockerfile_url)
+    );
 
-impl sysdig_lsp::app::component_factory::ComponentFactory for BlockingComponentFactory {
-    fn create_components(
-        &self,
-        _config: sysdig_lsp::app::component_factory::Config,
-    ) -> Result<
-        sysdig_lsp::app::component_factory::Components,
-        sysdig_lsp::app::component_factory::ComponentFactoryError,
-    > {
-        Ok(sysdig_lsp::app::component_factory::Components {
-            scanner: Box::new(common::MockImageScannerWrapper(std::sync::Arc::new(
-                tokio::sync::Mutex::new(common::MockImageScanner::new()),
-            ))),
-            builder: Box::new(common::MockImageBuilderWrapper(std::sync::Arc::new(
-                tokio::sync::Mutex::new(common::MockImageBuilder::new()),
-            ))),
-            iac_scanner: Box::new(self.iac_scanner.clone()),
+    let expected_path = dockerfile_path.clone();
+    setup
+        .component_factory
+        .image_builder
+        .lock()
+        .await
+        .expect_build_image()
+        .withf(move |path, _, _| path == expected_path)
+        .times(1)
+        .returning(|_, _, _| {
+            Ok(sysdig_lsp::app::ImageBuildResult {
+                image_id: "sha256:built".to_string(),
+                image_name: "sysdig-lsp-built".to_string(),
+                ..Default::default()
+            })
+        });
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+
+    // The Dockerfile isn't open in the editor: it's read from disk.
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: command.command,
+            arguments: command.arguments.unwrap(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
         })
-    }
+        .await
+        .unwrap();
+
+    let diagnostics = setup.client_recorder.diagnostics.lock().await;
+    let last = last_published_diagnostics_for(&diagnostics, dockerfile_url.as_str())
+        .expect("no diagnostics published for the Dockerfile");
+    assert_eq!(last[0].range.start.line, 1);
 }
 
 #[rstest]
 #[tokio::test]
-async fn test_commands_run_without_holding_the_server_lock() {
-    use std::sync::Arc;
-    use std::time::Duration;
-
-    let started = Arc::new(tokio::sync::Notify::new());
-    let release = Arc::new(tokio::sync::Notify::new());
-    let recorder = common::TestClientRecorder::new();
-    let server = Arc::new(sysdig_lsp::app::LSPServer::new(
-        recorder.clone(),
-        BlockingComponentFactory {
-            iac_scanner: BlockingIacScanner {
-                started: started.clone(),
-                release: release.clone(),
-            },
-        },
-    ));
+async fn test_compose_lenses_show_the_images_of_the_override_file(
+    #[future] initialized_server: TestSetup,
+) {
+    let workspace = tempfile::tempdir().unwrap();
+    std::fs::write(
+        workspace.path().join("docker-compose.override.yml"),
+        "services:\n  web:\n    image: nginx:1.27-alpine\n",
+    )
+    .unwrap();
+    let compose_url = Url::from_file_path(workspace.path().join("docker-compose.yml")).unwrap();
 
-    let init = InitializeParams {
-        initialization_options: Some(serde_json::json!({
-            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
-        })),
-        ..Default::default()
-    };
-    assert!(server.initialize(init).await.is_ok());
-    server
+    let setup = initialized_server.await;
+    setup
+        .server
         .did_open(DidOpenTextDocumentParams {
             text_document: TextDocumentItem::new(
-                "file:///deployment.yaml".parse().unwrap(),
+                compose_url.clone(),
                 "yaml".to_string(),
                 1,
-                include_str!("fixtures/k8s-deployment.yaml").to_string(),
+                "services:\n  web:\n    image: nginx:1.25\n  db:\n    image: postgres:13\n"
+                    .to_string(),
             ),
         })
         .await;
 
-    let command_server = server.clone();
-    let command = tokio::spawn(async move {
-        command_server
-            .execute_command(execute_iac_scan_params(vec![json!(
-                "file:///deployment.yaml"
-            )]))
-            .await
-    });
-    started.notified().await;
-
-    // While the scan is in flight, a write-lock operation and a read operation
-    // must both complete: the command must not hold the (FIFO-fair) server lock.
-    tokio::time::timeout(
-        Duration::from_secs(1),
-        server.did_change_configuration(DidChangeConfigurationParams {
-            settings: serde_json::json!({
-                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
-            }),
-        }),
-    )
-    .await
-    .expect("did_change_configuration deadlocked behind a running command");
-
-    tokio::time::timeout(
-        Duration::from_secs(1),
-        server.code_lens(tower_lsp::lsp_types::CodeLensParams {
-            text_document: TextDocumentIdentifier::new("file:///deployment.yaml".parse().unwrap()),
+    let lenses = setup
+        .server
+        .code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new(compose_url),
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
-        }),
-    )
-    .await
-    .expect("code_lens deadlocked behind a running command")
-    .expect("code_lens failed");
+        })
+        .await
+        .unwrap()
+        .unwrap();
 
-    release.notify_one();
-    let result = command.await.expect("command task panicked");
-    assert!(result.is_ok());
+    let scans: Vec<_> = lenses
+        .into_iter()
+        .filter_map(|lens| lens.command)
+        .filter(|command| command.command == "sysdig-lsp.execute-scan")
+        .map(|command| (command.title, command.arguments.unwrap()[1].clone()))
+        .collect();
+    assert_eq!(
+        scans,
+        [
+            (
+                "Scan base image (from docker-compose.override.yml)".to_string(),
+                json!("nginx:1.27-alpine")
+            ),
+            ("Scan base image".to_string(), json!("postgres:13")),
+        ]
+    );
 }
 
 #[rstest]
+#[case::unrelated_file("file:///notes.txt", "plaintext", 0)]
+#[case::containerfile("file:///app.containerfile", "plaintext", 2)]
+#[case::dockerfile_language_id("untitled:Untitled-1", "dockerfile", 2)]
 #[tokio::test]
-async fn test_workspace_scan_never_publishes_findings_escaping_the_root() {
-    let setup = TestSetup::new();
-    let params = InitializeParams {
-        initialization_options: Some(serde_json::json!({
-            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
-        })),
-        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
-            uri: "file:///workspace".parse().unwrap(),
-            name: "workspace".to_string(),
-        }]),
-        ..Default::default()
-    };
-    assert!(setup.server.initialize(params).await.is_ok());
-
-    // A finding whose source escapes the scanned root (e.g. via `..`) must be
-    // dropped: inserting it outside the cleared scope would accumulate duplicates
-    // on every rescan.
-    let scan_result = IacScanResult {
-        findings: vec![
-            iac_finding_for("/outside/x.yaml", "Escaping finding"),
-            iac_finding_for("/workspace/ok.yaml", "In-root finding"),
-        ],
-    };
+async fn test_dockerfile_lenses_are_only_generated_for_dockerfiles(
+    #[future] initialized_server: TestSetup,
+    #[case] uri: &str,
+    #[case] language_id: &str,
+    #[case] expected_lenses: usize,
+) {
+    let setup = initialized_server.await;
+    let uri: Url = uri.parse().unwrap();
     setup
-        .component_factory
-        .iac_scanner
-        .lock()
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                language_id.to_string(),
+                1,
+                "FROM alpine\n".to_string(),
+            ),
+        })
+        .await;
+
+    let lenses = setup
+        .server
+        .code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new(uri),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
         .await
-        .expect_scan_iac()
-        .times(2)
-        .returning(move |_| Ok(scan_result.clone()));
+        .unwrap()
+        .unwrap_or_default();
 
-    for _ in 0..2 {
-        let result = setup
-            .server
-            .execute_command(execute_iac_scan_params(vec![]))
-            .await;
-        assert!(result.is_ok());
-    }
+    assert_eq!(lenses.len(), expected_lenses);
+}
 
-    let diagnostics = setup.client_recorder.diagnostics.lock().await;
-    assert!(
-        diagnostics
-            .iter()
-            .all(|(url, _)| url != "file:///outside/x.yaml"),
-        "findings escaping the scanned root must never be published"
+#[rstest]
+#[tokio::test]
+async fn test_multi_document_k8s_manifest_gets_a_lens_per_image(
+    #[future] initialized_server: TestSetup,
+) {
+    let setup = initialized_server.await;
+    let uri: Url = "file:///manifests.yaml".parse().unwrap();
+    setup
+        .server
+        .did_open(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "yaml".to_string(),
+                1,
+                "apiVersion: v1\nkind: Pod\nspec:\n  containers:\n  - image: nginx:1.27\n---\napiVersion: v1\nkind: Pod\nspec:\n  ephemeralContainers:\n  - image: busybox:1.36\n"
+                    .to_string(),
+            ),
+        })
+        .await;
+
+    let lenses = setup
+        .server
+        .code_lens(tower_lsp::lsp_types::CodeLensParams {
+            text_document: TextDocumentIdentifier::new(uri),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+
+    let commands: Vec<_> = lenses
+        .into_iter()
+        .filter_map(|lens| lens.command)
+        .map(|command| {
+            let image = command
+                .arguments
+                .and_then(|arguments| arguments.get(1).cloned())
+                .unwrap_or_default();
+            (command.command, image)
+        })
+        .collect();
+    assert_eq!(
+        commands,
+        [
+            ("sysdig-lsp.execute-iac-scan".to_string(), json!(null)),
+            ("sysdig-lsp.scan-all-images".to_string(), json!(null)),
+            ("sysdig-lsp.execute-scan".to_string(), json!("nginx:1.27")),
+            ("sysdig-lsp.execute-scan".to_string(), json!("busybox:1.36")),
+        ]
     );
-    let in_root = last_published_diagnostics_for(&diagnostics, "file:///workspace/ok.yaml")
-        .expect("no diagnostics for the in-root file");
-    assert_eq!(in_root.len(), 1, "rescans must not accumulate duplicates");
 }
 
 #[rstest]
 #[tokio::test]
-async fn test_execute_command_on_uninitialized_server_surfaces_the_error() {
-    let setup = TestSetup::new();
-
-    let result = setup
-        .server
-        .execute_command(execute_iac_scan_params(vec![json!("file:///a.yaml")]))
-        .await;
-
-    let err = result.expect_err("commands must fail before initialization");
-    assert!(err.message.contains("LSP not initialized"));
+async fn test_kustomize_image_overrides_apply_to_manifests_and_kustomization_lenses(
+    #[future] initialized_server: TestSetup,
+) {
+    let setup = initialized_server.await;
+    let dir = tempfile::tempdir().unwrap();
+    let manifest_path = dir.path().join("deployment.yaml");
+    let manifest = "apiVersion: apps/v1\nkind: Deployment\nspec:\n  template:\n    spec:\n      containers:\n      - image: nginx:1.25\n";
+    let kustomization_path = dir.path().join("kustomization.yaml");
+    let kustomization =
+        "resources:\n  - deployment.yaml\nimages:\n  - name: nginx\n    newTag: \"1.27\"\n";
+    std::fs::write(&manifest_path, manifest).unwrap();
+    std::fs::write(&kustomization_path, kustomization).unwrap();
+
+    let mut lenses_by_file = vec![];
+    for (path, content) in [
+        (manifest_path, manifest),
+        (kustomization_path, kustomization),
+    ] {
+        let uri = Url::from_file_path(path).unwrap();
+        setup
+            .server
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "yaml".to_string(),
+                    1,
+                    content.to_string(),
+                ),
+            })
+            .await;
+        let lenses = setup
+            .server
+            .code_lens(tower_lsp::lsp_types::CodeLensParams {
+                text_document: TextDocumentIdentifier::new(uri),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        lenses_by_file.push(
+            lenses
+                .into_iter()
+                .filter_map(|lens| lens.command)
+                .filter(|command| command.command == "sysdig-lsp.execute-scan")
+                .map(|command| {
+                    let image = command
+                        .arguments
+                        .and_then(|arguments| arguments.get(1).cloned())
+                        .unwrap_or_default();
+                    (command.title, image)
+                })
+                .collect::<Vec<_>>(),
+        );
+    }
 
-    let messages = setup.client_recorder.messages.lock().await;
-    assert!(
-        messages
-            .iter()
-            .any(|(t, m)| *t == MessageType::ERROR && m.contains("LSP not initialized")),
-        "the error must be surfaced to the user via showMessage: {messages:?}"
+    assert_eq!(
+        lenses_by_file,
+        [
+            vec![(
+                "Scan base image (from kustomization.yaml)".to_string(),
+                json!("nginx:1.27")
+            )],
+            vec![("Scan base image".to_string(), json!("nginx:1.27"))],
+        ]
     );
 }
 
 #[rstest]
 #[tokio::test]
-async fn test_workspace_scan_drops_findings_with_relative_sources() {
+async fn test_scan_directory_publishes_the_vulnerable_packages_on_their_files() {
+    let workspace = tempfile::tempdir().unwrap();
+    std::fs::write(
+        workspace.path().join("requirements.txt"),
+        "flask==3.0.0\nrequests==2.19.0\n",
+    )
+    .unwrap();
     let setup = TestSetup::new();
-    let params = InitializeParams {
-        initialization_options: Some(serde_json::json!({
-            "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
-        })),
-        workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
-            uri: "file:///workspace".parse().unwrap(),
-            name: "workspace".to_string(),
-        }]),
-        ..Default::default()
-    };
-    assert!(setup.server.initialize(params).await.is_ok());
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+            })),
+            workspace_folders: Some(vec![tower_lsp::lsp_types::WorkspaceFolder {
+                uri: Url::from_directory_path(workspace.path()).unwrap(),
+                name: "workspace".to_string(),
+            }]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
 
-    // A relative source cannot be turned into a file URI: the finding is dropped
-    // (with a warning) instead of being published under a broken URI.
-    let scan_result = IacScanResult {
-        findings: vec![iac_finding_for("relative.yaml", "Relative finding")],
-    };
+    let mut result = ScanResult::new(
+        ScanType::Docker,
+        workspace.path().display().to_string(),
+        "sha256:12345".to_string(),
+        None,
+        OperatingSystem::new(Family::Linux, "".to_string()),
+        0,
+        Architecture::Amd64,
+        HashMap::new(),
+        chrono::Utc::now(),
+        EvaluationResult::Passed,
+    );
+    let layer = result.add_layer("".to_string(), 0, None, "".to_string());
+    result.add_package(
+        PackageType::Python,
+        "flask".to_string(),
+        "3.0.0".to_string(),
+        "/requirements.txt".to_string(),
+        layer.clone(),
+        false,
+        None,
+        None,
+    );
+    let requests = result.add_package(
+        PackageType::Python,
+        "requests".to_string(),
+        "2.19.0".to_string(),
+        "/requirements.txt".to_string(),
+        layer,
+        false,
+        None,
+        None,
+    );
+    for (cve, severity) in [
+        ("CVE-2018-18074", Severity::High),
+        ("CVE-2023-32681", Severity::Medium),
+    ] {
+        requests.add_vulnerability_found(result.add_vulnerability(
+            cve.to_string(),
+            severity,
+            chrono::NaiveDate::from_ymd_opt(2018, 10, 9).unwrap(),
+            None,
+            false,
+            Some("2.31.0".to_string()),
+            None,
+            None,
+        ));
+    }
+    let scanned_directory = workspace.path().to_path_buf();
     setup
         .component_factory
-        .iac_scanner
+        .filesystem_scanner
         .lock()
         .await
-        .expect_scan_iac()
+        .expect_scan_directory()
+        .withf(move |directory| directory == scanned_directory)
         .times(1)
-        .returning(move |_| Ok(scan_result.clone()));
+        .returning(move |_| Ok(result.clone()));
 
-    let result = setup
+    setup
         .server
-        .execute_command(execute_iac_scan_params(vec![]))
-        .await;
-    assert!(result.is_ok());
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.scan-directory".to_string(),
+            arguments: vec![],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
 
+    let requirements_url = Url::from_file_path(workspace.path().join("requirements.txt")).unwrap();
     let diagnostics = setup.client_recorder.diagnostics.lock().await;
-    assert!(
-        diagnostics
-            .iter()
-            .all(|(_, diags)| diags.iter().all(|d| !d.message.contains("Relative"))),
-        "findings with relative sources must not be published: {diagnostics:?}"
+    let diagnostics = last_published_diagnostics_for(&diagnostics, requirements_url.as_str())
+        .expect("no diagnostics for requirements.txt");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "requests 2.19.0: 2 vulnerabilities (1 High, 1 Medium), fixed in 2.31.0"
+    );
+    assert_eq!(
+        diagnostics[0].range,
+        Range::new(Position::new(1, 0), Position::new(1, 8))
     );
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(diagnostics[0].source.as_deref(), Some("sysdig-lsp/fs-vuln"));
 }
 
 #[rstest]
-#[awt]
+#[case::stale(json!({}), true)]
+#[case::disabled(json!({"staleAfterHours": 0}), false)]
 #[tokio::test]
-async fn test_compose_code_lens(#[future] initialized_server: TestSetup) {
-    let compose_url: Url = "file:///docker-compose.yml".parse().unwrap();
-    initialized_server
+async fn test_old_scan_results_tell_their_age_and_offer_a_rescan(
+    mut scan_result: ScanResult,
+    #[case] report: serde_json::Value,
+    #[case] rescan_offered: bool,
+) {
+    scan_result.set_scan_time(chrono::Utc::now() - chrono::TimeDelta::days(3), None);
+    let setup = TestSetup::new();
+    setup
+        .server
+        .initialize(InitializeParams {
+            initialization_options: Some(json!({
+                "sysdig": {
+                    "apiUrl": "http://localhost:8080",
+                    "api_token": "dummy-token",
+                    "report": report
+                }
+            })),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let dockerfile_url: Url = "file:///Dockerfile".parse().unwrap();
+    setup
         .server
         .did_open(DidOpenTextDocumentParams {
             text_document: TextDocumentItem::new(
-                compose_url.clone(),
-                "yaml".to_string(),
+                dockerfile_url.clone(),
+                "dockerfile".to_string(),
                 1,
-                include_str!("fixtures/docker-compose.yml").to_string(),
+                "FROM alpine\n".to_string(),
             ),
         })
         .await;
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(move |_| Ok(scan_result.clone()));
+    setup
+        .server
+        .execute_command(ExecuteCommandParams {
+            command: "sysdig-lsp.execute-scan".to_string(),
+            arguments: vec![
+                json!({"range":{"end":{"character":11,"line":0},"start":{"character": 0,"line":0}},"uri":dockerfile_url.clone()}),
+                json!("alpine"),
+            ],
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap();
 
-    let result = initialized_server
+    let hover = setup
         .server
-        .code_lens(tower_lsp::lsp_types::CodeLensParams {
-            text_document: TextDocumentIdentifier::new(compose_url),
+        .hover(HoverParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+                position: Position::new(0, 7),
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    let tower_lsp::lsp_types::HoverContents::Markup(hover) = hover.contents else {
+        panic!("expected markdown hover");
+    };
+    assert!(hover.value.ends_with("_Scanned 3 days ago._"));
+
+    let actions = setup
+        .server
+        .code_action(CodeActionParams {
+            text_document: TextDocumentIdentifier::new(dockerfile_url.clone()),
+            range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+            context: CodeActionContext::default(),
             work_done_progress_params: WorkDoneProgressParams::default(),
             partial_result_params: PartialResultParams::default(),
         })
         .await
         .unwrap()
         .unwrap();
+    let actions = serde_json::to_value(actions).unwrap();
+    let rescan = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|action| action["title"] == "Scan base image again (scanned 3 days ago)");
+    assert_eq!(rescan.is_some(), rescan_offered);
+    if let Some(rescan) = rescan {
+        assert_eq!(rescan["command"], "sysdig-lsp.execute-scan");
+        assert_eq!(rescan["arguments"][1], "alpine");
+    }
+}
 
-    let lenses = serde_json::to_value(result).unwrap();
-    let lenses = lenses.as_array().unwrap();
+#[rstest]
+#[case::passed(EvaluationResult::Passed, None, "", CheckStatus::Passed)]
+#[case::policy_failed(EvaluationResult::Failed, None, "", CheckStatus::Failed)]
+#[case::severity_reached(
+    EvaluationResult::Passed,
+    Some(Severity::High),
+    "",
+    CheckStatus::Failed
+)]
+#[case::severity_not_reached(
+    EvaluationResult::Passed,
+    Some(Severity::Critical),
+    "",
+    CheckStatus::Passed
+)]
+#[case::severity_ignored(
+    EvaluationResult::Passed,
+    Some(Severity::High),
+    "# sysdig-lsp: ignore CVE-2021-1234\n",
+    CheckStatus::Passed
+)]
+#[tokio::test]
+async fn test_check_gates_on_the_policies_and_the_reported_severities(
+    #[case] evaluation_result: EvaluationResult,
+    #[case] fail_on: Option<Severity>,
+    #[case] directives: &str,
+    #[case] expected: CheckStatus,
+) {
+    let setup = TestSetup::new();
+    let mut scan_result = ScanResult::new(
+        ScanType::Docker,
+        "alpine:3.18".to_string(),
+        "sha256:12345".to_string(),
+        None,
+        OperatingSystem::new(Family::Linux, "alpine:3.18".to_string()),
+        123456,
+        Architecture::Amd64,
+        HashMap::new(),
+        chrono::Utc::now(),
+        evaluation_result,
+    );
+    let layer = scan_result.add_layer("sha256:layer1".to_string(), 0, None, "FROM".to_string());
+    let package = scan_result.add_package(
+        PackageType::Os,
+        "openssl".to_string(),
+        "3.0.0".to_string(),
+        "/lib/apk/db/installed".to_string(),
+        layer,
+        false,
+        None,
+        None,
+    );
+    package.add_vulnerability_found(scan_result.add_vulnerability(
+        "CVE-2021-1234".to_string(),
+        Severity::High,
+        chrono::NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+        None,
+        false,
+        None,
+        None,
+        None,
+    ));
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .withf(|image| image == "alpine:3.18")
+        .times(1)
+        .returning(move |_| Ok(scan_result.clone()));
+    let config = Config::from_settings(&json!({
+        "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+    }))
+    .unwrap();
+    let components = setup
+        .component_factory
+        .create_components(config.clone())
+        .unwrap();
+    let workspace = tempfile::tempdir().unwrap();
+    let dockerfile = workspace.path().join("Dockerfile");
+    std::fs::write(&dockerfile, format!("{directives}FROM alpine:3.18\n")).unwrap();
+
+    let report = check_files(
+        &components,
+        &config,
+        workspace.path(),
+        std::slice::from_ref(&dockerfile),
+        fail_on,
+    )
+    .await;
 
-    // First lens: whole-file IaC scan
-    assert_eq!(
-        lenses[0]["command"]["command"],
-        "sysdig-lsp.execute-iac-scan"
+    assert_eq!(report.status(), expected);
+    let line = if directives.is_empty() { 1 } else { 2 };
+    let text = report.render(CheckFormat::Text, workspace.path());
+    assert!(
+        text.starts_with(&format!("{}:{line} alpine:3.18: ", dockerfile.display())),
+        "{text}"
     );
-    assert_eq!(lenses[0]["command"]["title"], "Scan IaC file");
-    assert_eq!(
-        lenses[0]["command"]["arguments"],
-        json!(["file:///docker-compose.yml"])
+}
+
+#[tokio::test]
+async fn test_check_errors_when_an_image_cant_be_scanned() {
+    let setup = TestSetup::new();
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .returning(|_| Err(ImageScanError::ImageNotFound("alpine:3.18".to_string())));
+    let config = Config::from_settings(&json!({
+        "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+    }))
+    .unwrap();
+    let components = setup
+        .component_factory
+        .create_components(config.clone())
+        .unwrap();
+    let workspace = tempfile::tempdir().unwrap();
+    let dockerfile = workspace.path().join("Dockerfile");
+    std::fs::write(&dockerfile, "FROM alpine:3.18\n").unwrap();
+
+    let report = check_files(&components, &config, workspace.path(), &[dockerfile], None).await;
+
+    assert_eq!(report.status(), CheckStatus::Errored);
+    let text = report.render(CheckFormat::Text, workspace.path());
+    assert!(text.contains(": scan failed: "), "{text}");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_check_reports_every_file_in_the_requested_format(scan_result: ScanResult) {
+    let setup = TestSetup::new();
+    setup
+        .component_factory
+        .image_scanner
+        .lock()
+        .await
+        .expect_scan_image()
+        .times(2)
+        .returning(move |_| Ok(scan_result.clone()));
+    let config = Config::from_settings(&json!({
+        "sysdig": { "apiUrl": "http://localhost:8080", "api_token": "dummy-token" }
+    }))
+    .unwrap();
+    let components = setup
+        .component_factory
+        .create_components(config.clone())
+        .unwrap();
+    let workspace = tempfile::tempdir().unwrap();
+    std::fs::write(workspace.path().join("Dockerfile"), "FROM alpine:3.18\n").unwrap();
+    std::fs::write(
+        workspace.path().join("docker-compose.yml"),
+        "services:\n  web:\n    image: nginx:1.25\n",
+    )
+    .unwrap();
+    std::fs::write(workspace.path().join("README.md"), "# Readme\n").unwrap();
+    let paths =
+        ["Dockerfile", "docker-compose.yml", "README.md"].map(|file| workspace.path().join(file));
+
+    let report = check_files(
+        &components,
+        &config,
+        workspace.path(),
+        &paths,
+        Some(Severity::High),
+    )
+    .await;
+
+    assert_eq!(report.status(), CheckStatus::Errored);
+    let json: serde_json::Value =
+        serde_json::from_str(&report.render(CheckFormat::Json, workspace.path())).unwrap();
+    assert_eq!(json["status"], "errored");
+    assert_eq!(json["failOn"], "high");
+    assert_eq!(json["files"][0]["images"][0]["image"], "alpine:3.18");
+    assert_eq!(json["files"][0]["images"][0]["status"], "failed");
+    assert_eq!(json["files"][0]["images"][0]["vulnerabilities"]["high"], 1);
+    assert_eq!(json["files"][1]["images"][0]["line"], 3);
+    assert!(
+        json["files"][2]["error"]
+            .as_str()
+            .unwrap()
+            .ends_with("is neither a Dockerfile, a Compose file, a K8s manifest nor a CI config")
     );
 
-    // Then one image scan lens per compose image
-    let images: Vec<_> = lenses[1..]
+    let sarif: serde_json::Value =
+        serde_json::from_str(&report.render(CheckFormat::Sarif, workspace.path())).unwrap();
+    let runs = sarif["runs"].as_array().unwrap();
+    assert_eq!(runs.len(), 1);
+    let uris = runs[0]["results"]
+        .as_array()
+        .unwrap()
         .iter()
-        .map(|l| {
-            assert_eq!(l["command"]["command"], "sysdig-lsp.execute-scan");
-            l["command"]["arguments"][1].as_str().unwrap().to_owned()
-        })
-        .collect();
-    assert_eq!(images, vec!["nginx:latest", "postgres:13"]);
+        .map(|result| result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"].clone())
+        .collect::<Vec<_>>();
+    assert_eq!(uris, [json!("Dockerfile"), json!("docker-compose.yml")]);
+
+    let markdown = report.render(CheckFormat::Markdown, workspace.path());
+    assert!(
+        markdown.starts_with("# Sysdig LSP check\n\n| IMAGE "),
+        "{markdown}"
+    );
+    assert!(
+        markdown.contains(&format!(
+            "<details><summary>{}:1 alpine:3.18</summary>",
+            paths[0].display()
+        )),
+        "{markdown}"
+    );
 }